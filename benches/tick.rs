@@ -0,0 +1,112 @@
+//! Per tick latency: computing features from a book, evaluating strategies on them, and both
+//! together through the allocator as the live loop does, on synthetic books. `tick` reports
+//! books per second over runs of a thousand or so.
+//!
+//! ```sh
+//! cargo bench --bench tick
+//! ```
+
+use barter_data::subscription::book::OrderBook;
+use chrono::Utc;
+use criterion::criterion_group;
+use criterion::criterion_main;
+use criterion::BatchSize;
+use criterion::BenchmarkId;
+use criterion::Criterion;
+use criterion::Throughput;
+use fast_imbalance_trading::allocation::Allocator;
+use fast_imbalance_trading::config::Config;
+use fast_imbalance_trading::features::Features;
+use fast_imbalance_trading::strategy::ensemble::Combination;
+use fast_imbalance_trading::strategy::ensemble::Ensemble;
+use fast_imbalance_trading::strategy::imbalance::ImbalanceParams;
+use fast_imbalance_trading::strategy::imbalance::ImbalanceStrategy;
+use fast_imbalance_trading::strategy::plugin::PluginRegistry;
+use fast_imbalance_trading::strategy::Strategy;
+use fast_imbalance_trading::synthetic::SyntheticConfig;
+use fast_imbalance_trading::synthetic::SyntheticMarket;
+use fast_imbalance_trading::TradingState;
+use std::hint::black_box;
+
+/// Books cycled through by each benchmark, so signals vary from one iteration to the next.
+const BOOKS: usize = 1024;
+
+fn books(depth: usize) -> Vec<OrderBook> {
+    let config = SyntheticConfig {
+        depth,
+        ..SyntheticConfig::default()
+    };
+    SyntheticMarket::new(42, config)
+        .take(BOOKS)
+        .map(|event| event.kind)
+        .collect()
+}
+
+fn features(c: &mut Criterion) {
+    let mut group = c.benchmark_group("features");
+    for depth in [10, 100, 500] {
+        let books = books(depth);
+        group.bench_with_input(BenchmarkId::from_parameter(depth), &books, |b, books| {
+            let mut books = books.iter().cycle();
+            b.iter(|| Features::from_order_book(black_box(books.next().unwrap())))
+        });
+    }
+    group.finish();
+}
+
+fn signal(c: &mut Criterion) {
+    let features: Vec<Features> = books(20)
+        .iter()
+        .filter_map(Features::from_order_book)
+        .collect();
+    let state = TradingState::new(1000.0, "BTC/USDT");
+    let imbalance = || Box::new(ImbalanceStrategy::new(ImbalanceParams::default()));
+
+    let mut group = c.benchmark_group("signal");
+    let mut strategy = imbalance();
+    group.bench_function("imbalance", |b| {
+        let mut features = features.iter().cycle();
+        b.iter(|| strategy.evaluate(black_box(features.next().unwrap()), &state))
+    });
+    let members: Vec<(Box<dyn Strategy>, f64)> = (0..3).map(|_| (imbalance() as _, 1.0)).collect();
+    let mut ensemble = Ensemble::new(members, Combination::Majority);
+    group.bench_function("ensemble", |b| {
+        let mut features = features.iter().cycle();
+        b.iter(|| ensemble.evaluate(black_box(features.next().unwrap()), &state))
+    });
+    group.finish();
+}
+
+fn tick(c: &mut Criterion) {
+    let books = books(20);
+    let allocator = || {
+        Allocator::from_config(
+            &Config::default(),
+            &PluginRegistry::default(),
+            1000.0,
+            "BTC/USDT",
+            Utc::now(),
+        )
+        .expect("default strategies always build")
+    };
+    // Each run trades every book from a fresh account, so positions don't pile up across runs
+    let mut group = c.benchmark_group("tick");
+    group.throughput(Throughput::Elements(BOOKS as u64));
+    group.bench_function("books", |b| {
+        b.iter_batched_ref(
+            allocator,
+            |allocator| {
+                for book in &books {
+                    if let Some(features) = Features::from_order_book(black_box(book)) {
+                        black_box(allocator.on_features(&features, Utc::now()));
+                    }
+                }
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, features, signal, tick);
+criterion_main!(benches);
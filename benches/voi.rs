@@ -0,0 +1,39 @@
+//! Summing book levels with each implementation in `simd`, at a few depths.
+//!
+//! ```sh
+//! cargo bench --bench voi
+//! ```
+
+use criterion::criterion_group;
+use criterion::criterion_main;
+use criterion::BenchmarkId;
+use criterion::Criterion;
+use fast_imbalance_trading::simd;
+use fast_imbalance_trading::synthetic::SyntheticConfig;
+use fast_imbalance_trading::synthetic::SyntheticMarket;
+use std::hint::black_box;
+
+fn total_amount(c: &mut Criterion) {
+    let mut group = c.benchmark_group("total_amount");
+    for depth in [10, 100, 500, 1000] {
+        let config = SyntheticConfig {
+            depth,
+            ..SyntheticConfig::default()
+        };
+        let book = SyntheticMarket::new(1, config).next_book().kind;
+        let levels = &book.bids.levels;
+        group.bench_with_input(BenchmarkId::new("scalar", depth), levels, |b, levels| {
+            b.iter(|| simd::total_amount_scalar(black_box(levels)))
+        });
+        group.bench_with_input(BenchmarkId::new("unrolled", depth), levels, |b, levels| {
+            b.iter(|| simd::total_amount_unrolled(black_box(levels)))
+        });
+        group.bench_with_input(BenchmarkId::new("detected", depth), levels, |b, levels| {
+            b.iter(|| simd::total_amount(black_box(levels)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, total_amount);
+criterion_main!(benches);
@@ -0,0 +1,14 @@
+fn main() {
+    // Compile the engine's gRPC service from its proto, without needing protoc installed
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto/engine.proto");
+        let descriptors = protox::compile(["engine.proto"], ["proto"])
+            .expect("failed to parse proto/engine.proto");
+        tonic_build::configure()
+            .build_client(true)
+            .build_server(true)
+            .compile_fds(descriptors)
+            .expect("failed to generate gRPC service");
+    }
+}
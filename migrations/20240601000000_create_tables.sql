@@ -0,0 +1,47 @@
+-- Every row is owned by the bot instance that wrote it, so several can share one database.
+
+CREATE TABLE orders (
+    id BIGSERIAL PRIMARY KEY,
+    instance TEXT NOT NULL,
+    time TIMESTAMPTZ NOT NULL,
+    symbol TEXT NOT NULL,
+    strategy TEXT NOT NULL,
+    side TEXT NOT NULL,
+    price DOUBLE PRECISION NOT NULL,
+    size DOUBLE PRECISION NOT NULL
+);
+
+CREATE TABLE trades (
+    id BIGSERIAL PRIMARY KEY,
+    instance TEXT NOT NULL,
+    time TIMESTAMPTZ NOT NULL,
+    symbol TEXT NOT NULL,
+    strategy TEXT NOT NULL,
+    side TEXT NOT NULL,
+    price DOUBLE PRECISION NOT NULL,
+    size DOUBLE PRECISION NOT NULL,
+    fee DOUBLE PRECISION NOT NULL
+);
+
+-- Positions currently open, one row per entry
+CREATE TABLE positions (
+    id BIGSERIAL PRIMARY KEY,
+    instance TEXT NOT NULL,
+    symbol TEXT NOT NULL,
+    strategy TEXT NOT NULL,
+    entry_price DOUBLE PRECISION NOT NULL,
+    size DOUBLE PRECISION NOT NULL,
+    opened_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE equity (
+    instance TEXT NOT NULL,
+    time TIMESTAMPTZ NOT NULL,
+    symbol TEXT NOT NULL,
+    portfolio_value DOUBLE PRECISION NOT NULL,
+    PRIMARY KEY (instance, symbol, time)
+);
+
+CREATE INDEX orders_instance_time ON orders (instance, time);
+CREATE INDEX trades_instance_time ON trades (instance, time);
+CREATE INDEX positions_instance_strategy ON positions (instance, symbol, strategy);
@@ -0,0 +1,7 @@
+-- Where and how each trade was executed, and the mid price it was decided on, for slippage
+-- analysis. Trades recorded before this have no decision price.
+
+ALTER TABLE trades
+    ADD COLUMN venue TEXT NOT NULL DEFAULT 'paper',
+    ADD COLUMN order_type TEXT NOT NULL DEFAULT 'limit',
+    ADD COLUMN decision_price DOUBLE PRECISION;
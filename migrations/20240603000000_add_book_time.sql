@@ -0,0 +1,10 @@
+-- When the book each order and trade was decided on was stamped by the exchange and received by
+-- the bot. Rows recorded before this have neither.
+
+ALTER TABLE orders
+    ADD COLUMN exchange_time TIMESTAMPTZ,
+    ADD COLUMN received_time TIMESTAMPTZ;
+
+ALTER TABLE trades
+    ADD COLUMN exchange_time TIMESTAMPTZ,
+    ADD COLUMN received_time TIMESTAMPTZ;
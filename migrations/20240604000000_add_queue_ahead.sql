@@ -0,0 +1,5 @@
+-- The size estimated still ahead of each trade's order in its level's queue when it filled, for
+-- orders that rested in the book. Trades taken at once have none.
+
+ALTER TABLE trades
+    ADD COLUMN queue_ahead DOUBLE PRECISION;
@@ -0,0 +1,11 @@
+-- The allocator sleeve behind each trade and open position, telling apart members running the
+-- same strategy. Rows recorded before this are all taken to be the first sleeve's.
+
+ALTER TABLE trades
+    ADD COLUMN sleeve INTEGER NOT NULL DEFAULT 0;
+
+ALTER TABLE positions
+    ADD COLUMN sleeve INTEGER NOT NULL DEFAULT 0;
+
+DROP INDEX positions_instance_strategy;
+CREATE INDEX positions_instance_sleeve ON positions (instance, symbol, strategy, sleeve);
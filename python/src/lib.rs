@@ -0,0 +1,243 @@
+//! Python bindings for the production feature and backtest code, so research in notebooks runs
+//! the exact signal implementations the bot trades on.
+//!
+//! Books are passed as `(timestamp_millis, bids, asks)` with each side a list of
+//! `(price, amount)` pairs.
+//!
+//! ```python
+//! import fast_imbalance_trading as fit
+//!
+//! fit.features([(100.0, 3.0)], [(101.0, 1.0)])["oir"]  # 0.5
+//! report = fit.backtest(books, config=open("config.toml").read())
+//!
+//! env = fit.TradingEnv(books)
+//! observation, info = env.reset()
+//! observation, reward, terminated, truncated, info = env.step(1)  # buy
+//! ```
+
+// pyo3 0.22's `#[pyfunction]` expansion converts `PyErr` into itself
+#![allow(clippy::useless_conversion)]
+
+use barter_data::subscription::book::Level;
+use barter_data::subscription::book::OrderBook;
+use barter_data::subscription::book::OrderBookSide;
+use barter_integration::model::Side;
+use chrono::DateTime;
+use fit::backtest;
+use fit::config::Config;
+use fit::features::Features;
+use fit::features::FEATURE_COUNT;
+use fit::gym::TradingEnv;
+use fit::gym::ACTIONS;
+use fit::strategy::plugin::PluginRegistry;
+use fit::TradingState;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+type Levels = Vec<(f64, f64)>;
+type Book = (i64, Levels, Levels);
+/// `(observation, reward, terminated, truncated, info)`
+type StepResult<'py> = (Vec<f64>, f64, bool, bool, Bound<'py, PyDict>);
+
+fn book_side(side: Side, levels: Levels) -> OrderBookSide {
+    let levels: Vec<Level> = levels
+        .into_iter()
+        .map(|(price, amount)| Level { price, amount })
+        .collect();
+    OrderBookSide::new(side, levels)
+}
+
+fn order_book((timestamp_millis, bids, asks): Book) -> PyResult<OrderBook> {
+    let last_update_time = DateTime::from_timestamp_millis(timestamp_millis)
+        .ok_or_else(|| PyValueError::new_err(format!("invalid timestamp {timestamp_millis}")))?;
+
+    Ok(OrderBook {
+        last_update_time,
+        bids: book_side(Side::Buy, bids),
+        asks: book_side(Side::Sell, asks),
+    })
+}
+
+fn features_dict<'py>(py: Python<'py>, features: &Features) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new_bound(py);
+    dict.set_item("bid", features.bid)?;
+    dict.set_item("ask", features.ask)?;
+    dict.set_item("mid_price", features.mid_price)?;
+    dict.set_item("microprice", features.microprice)?;
+    dict.set_item("spread", features.spread)?;
+    dict.set_item("voi", features.voi)?;
+    dict.set_item("oir", features.oir)?;
+    dict.set_item("mpb", features.mpb)?;
+    dict.set_item("flow", features.flow)?;
+    dict.set_item("liquidations", features.liquidations)?;
+    dict.set_item("order_imbalance", features.order_imbalance)?;
+    dict.set_item("avg_order_size", features.avg_order_size)?;
+    dict.set_item("persistent_oir", features.persistent_oir)?;
+    dict.set_item("oir_top1", features.oir_top1)?;
+    dict.set_item("oir_top5", features.oir_top5)?;
+    dict.set_item("oir_top20", features.oir_top20)?;
+    dict.set_item("oir_10bps", features.oir_10bps)?;
+    dict.set_item("oir_50bps", features.oir_50bps)?;
+    dict.set_item("volatility", features.volatility)?;
+    Ok(dict)
+}
+
+/// Volume order imbalance of the book, returned as `(voi, bid_volume, ask_volume)`.
+#[pyfunction]
+fn calculate_voi(bids: Levels, asks: Levels) -> PyResult<(f64, f64, f64)> {
+    let book = order_book((0, bids, asks))?;
+    Ok(TradingState::calculate_voi(&book))
+}
+
+#[pyfunction]
+fn calculate_oir(bid_volume: f64, ask_volume: f64) -> f64 {
+    TradingState::calculate_oir(bid_volume, ask_volume)
+}
+
+#[pyfunction]
+fn calculate_mpb(last_price: f64, mid_price: f64) -> f64 {
+    TradingState::calculate_mpb(last_price, mid_price)
+}
+
+#[pyfunction]
+fn calculate_spread(bid: f64, ask: f64) -> f64 {
+    TradingState::calculate_spread(bid, ask)
+}
+
+/// Features of a single book as a dict, or `None` if either side is empty.
+#[pyfunction]
+fn features(py: Python<'_>, bids: Levels, asks: Levels) -> PyResult<Option<Bound<'_, PyDict>>> {
+    let book = order_book((0, bids, asks))?;
+    Features::from_order_book(&book)
+        .map(|features| features_dict(py, &features))
+        .transpose()
+}
+
+/// Features of every book in order, with `None` for books with an empty side.
+#[pyfunction]
+fn feature_pipeline(py: Python<'_>, books: Vec<Book>) -> PyResult<Vec<Option<Bound<'_, PyDict>>>> {
+    books
+        .into_iter()
+        .map(|book| {
+            Features::from_order_book(&order_book(book)?)
+                .map(|features| features_dict(py, &features))
+                .transpose()
+        })
+        .collect()
+}
+
+/// Replay `books` through the strategies described by the TOML `config`.
+#[pyfunction]
+#[pyo3(name = "backtest", signature = (books, config = "", cash = 1000.0))]
+fn run_backtest<'py>(
+    py: Python<'py>,
+    books: Vec<Book>,
+    config: &str,
+    cash: f64,
+) -> PyResult<Bound<'py, PyDict>> {
+    let config = Config::parse(config).map_err(|error| PyValueError::new_err(error.to_string()))?;
+    let plugins = PluginRegistry::load_dir(&config.plugins.dir)
+        .map_err(|error| PyValueError::new_err(error.to_string()))?;
+    let books = books
+        .into_iter()
+        .map(order_book)
+        .collect::<PyResult<Vec<_>>>()?;
+
+    let report = backtest::run(&config, &plugins, cash, &books)
+        .map_err(|error| PyValueError::new_err(error.to_string()))?;
+
+    let dict = PyDict::new_bound(py);
+    dict.set_item("ticks", report.ticks)?;
+    dict.set_item("initial_value", report.initial_value)?;
+    dict.set_item("final_value", report.final_value)?;
+    dict.set_item("return_pct", report.return_pct)?;
+    dict.set_item("max_drawdown_pct", report.max_drawdown_pct)?;
+    dict.set_item("open_positions", report.open_positions)?;
+    dict.set_item(
+        "equity_curve",
+        report
+            .equity_curve
+            .iter()
+            .map(|(time, value)| (time.timestamp_millis(), *value))
+            .collect::<Vec<_>>(),
+    )?;
+    Ok(dict)
+}
+
+/// Gym-style environment replaying `books`, following the Gymnasium `reset`/`step` signatures.
+/// Actions are `0` to hold, `1` to buy and `2` to sell.
+#[pyclass(name = "TradingEnv")]
+struct PyTradingEnv {
+    env: TradingEnv,
+}
+
+#[pymethods]
+impl PyTradingEnv {
+    #[new]
+    #[pyo3(signature = (books, cash = 1000.0))]
+    fn new(books: Vec<Book>, cash: f64) -> PyResult<Self> {
+        let books = books
+            .into_iter()
+            .map(order_book)
+            .collect::<PyResult<Vec<_>>>()?;
+        TradingEnv::new(&books, cash)
+            .map(|env| Self { env })
+            .ok_or_else(|| PyValueError::new_err("no book has both a bid and an ask"))
+    }
+
+    #[getter]
+    fn action_count(&self) -> usize {
+        ACTIONS.len()
+    }
+
+    #[getter]
+    fn observation_size(&self) -> usize {
+        FEATURE_COUNT
+    }
+
+    fn __len__(&self) -> usize {
+        self.env.len()
+    }
+
+    /// Returns `(observation, info)`.
+    fn reset<'py>(&mut self, py: Python<'py>) -> PyResult<(Vec<f64>, Bound<'py, PyDict>)> {
+        let observation = self.env.reset().to_vec();
+        let info = PyDict::new_bound(py);
+        info.set_item("portfolio_value", self.env.state().cash)?;
+        info.set_item("open_positions", 0)?;
+        Ok((observation, info))
+    }
+
+    /// Returns `(observation, reward, terminated, truncated, info)`.
+    fn step<'py>(&mut self, py: Python<'py>, action: usize) -> PyResult<StepResult<'py>> {
+        let action = *ACTIONS
+            .get(action)
+            .ok_or_else(|| PyValueError::new_err(format!("invalid action {action}")))?;
+        let step = self.env.step(action);
+
+        let info = PyDict::new_bound(py);
+        info.set_item("portfolio_value", step.portfolio_value)?;
+        info.set_item("open_positions", step.open_positions)?;
+        Ok((
+            step.observation.to_vec(),
+            step.reward,
+            step.done,
+            false,
+            info,
+        ))
+    }
+}
+
+#[pymodule]
+fn fast_imbalance_trading(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(calculate_voi, module)?)?;
+    module.add_function(wrap_pyfunction!(calculate_oir, module)?)?;
+    module.add_function(wrap_pyfunction!(calculate_mpb, module)?)?;
+    module.add_function(wrap_pyfunction!(calculate_spread, module)?)?;
+    module.add_function(wrap_pyfunction!(features, module)?)?;
+    module.add_function(wrap_pyfunction!(feature_pipeline, module)?)?;
+    module.add_function(wrap_pyfunction!(run_backtest, module)?)?;
+    module.add_class::<PyTradingEnv>()?;
+    Ok(())
+}
@@ -0,0 +1,158 @@
+//! Core pinning and real-time priority for the threads on the latency sensitive path, set under
+//! [`[threads]`](crate::config::ThreadsConfig), to keep the scheduler from moving them off a warm
+//! cache or preempting them for less urgent work.
+//!
+//! Both need the `affinity` feature and Linux. A real-time priority also needs `CAP_SYS_NICE`, or
+//! root.
+
+use std::io;
+
+use crate::config::ThreadConfig;
+
+/// Priorities `SCHED_FIFO` accepts.
+const PRIORITIES: std::ops::RangeInclusive<i32> = 1..=99;
+
+#[derive(Debug, thiserror::Error)]
+pub enum AffinityError {
+    #[error("failed to pin the {thread} thread to core {core}: {error}")]
+    Pin {
+        thread: &'static str,
+        core: usize,
+        error: io::Error,
+    },
+
+    #[error("failed to give the {thread} thread real-time priority {priority}: {error}")]
+    Priority {
+        thread: &'static str,
+        priority: i32,
+        error: io::Error,
+    },
+
+    #[error("invalid {thread} thread priority {priority}, expected 1 to 99")]
+    InvalidPriority { thread: &'static str, priority: i32 },
+}
+
+/// Check the priority in `config`, if any, is one `SCHED_FIFO` accepts.
+pub fn check(thread: &'static str, config: &ThreadConfig) -> Result<(), AffinityError> {
+    match config.priority {
+        Some(priority) if !PRIORITIES.contains(&priority) => {
+            Err(AffinityError::InvalidPriority { thread, priority })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Pin the calling thread, named `thread` in errors, and set its priority as `config` says.
+pub fn apply(thread: &'static str, config: &ThreadConfig) -> Result<(), AffinityError> {
+    check(thread, config)?;
+    if let Some(core) = config.core {
+        pin_current_thread(core).map_err(|error| AffinityError::Pin {
+            thread,
+            core,
+            error,
+        })?;
+    }
+    if let Some(priority) = config.priority {
+        set_realtime_priority(priority).map_err(|error| AffinityError::Priority {
+            thread,
+            priority,
+            error,
+        })?;
+    }
+    Ok(())
+}
+
+/// Pin the calling thread to `core`.
+#[cfg(all(feature = "affinity", target_os = "linux"))]
+pub fn pin_current_thread(core: usize) -> io::Result<()> {
+    if core >= libc::CPU_SETSIZE as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("core {core} is beyond the largest supported"),
+        ));
+    }
+    // SAFETY: the set is zeroed before use and only passed for the calling thread
+    let result = unsafe {
+        let mut set = std::mem::zeroed::<libc::cpu_set_t>();
+        libc::CPU_ZERO(&mut set);
+        libc::CPU_SET(core, &mut set);
+        libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set)
+    };
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// Schedule the calling thread `SCHED_FIFO` at `priority`, ahead of every normal thread.
+#[cfg(all(feature = "affinity", target_os = "linux"))]
+pub fn set_realtime_priority(priority: i32) -> io::Result<()> {
+    let param = libc::sched_param {
+        sched_priority: priority,
+    };
+    // SAFETY: only changes the calling thread's own scheduling, with a valid param
+    let result =
+        unsafe { libc::pthread_setschedparam(libc::pthread_self(), libc::SCHED_FIFO, &param) };
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::from_raw_os_error(result))
+    }
+}
+
+/// Pin the calling thread to `core`.
+#[cfg(not(all(feature = "affinity", target_os = "linux")))]
+pub fn pin_current_thread(_core: usize) -> io::Result<()> {
+    Err(unsupported())
+}
+
+/// Schedule the calling thread `SCHED_FIFO` at `priority`, ahead of every normal thread.
+#[cfg(not(all(feature = "affinity", target_os = "linux")))]
+pub fn set_realtime_priority(_priority: i32) -> io::Result<()> {
+    Err(unsupported())
+}
+
+#[cfg(not(all(feature = "affinity", target_os = "linux")))]
+fn unsupported() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "thread pinning and priority require the affinity feature on Linux",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_priorities() {
+        let priority = |priority| ThreadConfig {
+            core: None,
+            priority: Some(priority),
+        };
+        assert!(check("strategy", &ThreadConfig::default()).is_ok());
+        assert!(check("strategy", &priority(99)).is_ok());
+        assert_eq!(
+            check("strategy", &priority(0)).unwrap_err().to_string(),
+            "invalid strategy thread priority 0, expected 1 to 99"
+        );
+        assert!(apply("feed", &priority(100)).is_err());
+    }
+
+    #[cfg(all(feature = "affinity", target_os = "linux"))]
+    #[test]
+    fn test_pin_to_an_allowed_core() {
+        let pinned = std::thread::spawn(|| {
+            apply(
+                "feed",
+                &ThreadConfig {
+                    core: Some(0),
+                    priority: None,
+                },
+            )
+        });
+        pinned.join().unwrap().unwrap();
+        assert!(pin_current_thread(libc::CPU_SETSIZE as usize).is_err());
+    }
+}
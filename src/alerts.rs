@@ -0,0 +1,331 @@
+//! Alerts declared as rules in `[[alerts]]`
+//! ([`AlertRule`](crate::config::AlertRule)), each watching one of the bot's own [`Metric`]s and
+//! raising an alert once it has stayed above or below a threshold for as long as the rule says,
+//! such as unrealized PnL staying under -50 for five minutes or the book going without an update
+//! for 30 seconds.
+//!
+//! A rule's name is the [condition](Alert::condition) its alerts are about, so they reach every
+//! configured sink like any other alert, and another is raised, resolved, once the metric is back
+//! within its threshold.
+
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::info;
+use tracing::warn;
+
+use crate::config::AlertRule;
+use crate::event::Alert;
+use crate::event::Severity;
+
+/// How often rules are checked when nothing else wakes the trading loop, so a feed that has gone
+/// quiet is still noticed.
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, thiserror::Error)]
+pub enum AlertError {
+    #[error("alert rule {0} needs exactly one of above or below")]
+    Threshold(String),
+
+    #[error("alert rule {0} is declared more than once")]
+    Duplicate(String),
+}
+
+/// What a rule watches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    /// Seconds since the last order book update, or since starting before the first.
+    BookAgeSecs,
+    /// The latest book's spread, as a percentage of the bid.
+    SpreadPct,
+    /// What the open positions would make or lose at the latest mark, or the bid without one.
+    UnrealizedPnl,
+    /// Realized PnL net of fees since the session rolled over.
+    SessionPnl,
+    /// Portfolio value at the latest bid.
+    Equity,
+    /// The traded perpetual's latest polled open interest.
+    OpenInterest,
+    /// How much open interest changed over its window, as a percentage.
+    OpenInterestChangePct,
+    /// The latest polled mark's premium over the index, in bps.
+    MarkPremiumBps,
+}
+
+impl Metric {
+    fn name(self) -> &'static str {
+        match self {
+            Self::BookAgeSecs => "book age",
+            Self::SpreadPct => "spread",
+            Self::UnrealizedPnl => "unrealized PnL",
+            Self::SessionPnl => "session PnL",
+            Self::Equity => "equity",
+            Self::OpenInterest => "open interest",
+            Self::OpenInterestChangePct => "open interest change",
+            Self::MarkPremiumBps => "mark premium",
+        }
+    }
+}
+
+/// The latest value of every [`Metric`], those not known yet left unset.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Metrics {
+    pub last_book: Instant,
+    pub spread_pct: Option<f64>,
+    pub unrealized_pnl: Option<f64>,
+    pub session_pnl: Option<f64>,
+    pub equity: Option<f64>,
+    pub open_interest: Option<f64>,
+    pub open_interest_change_pct: Option<f64>,
+    pub mark_premium_bps: Option<f64>,
+}
+
+impl Metrics {
+    /// Nothing known yet at `now`, when the book starts ageing.
+    pub fn new(now: Instant) -> Self {
+        Self {
+            last_book: now,
+            spread_pct: None,
+            unrealized_pnl: None,
+            session_pnl: None,
+            equity: None,
+            open_interest: None,
+            open_interest_change_pct: None,
+            mark_premium_bps: None,
+        }
+    }
+
+    fn value(&self, metric: Metric, now: Instant) -> Option<f64> {
+        match metric {
+            Metric::BookAgeSecs => Some(now.duration_since(self.last_book).as_secs_f64()),
+            Metric::SpreadPct => self.spread_pct,
+            Metric::UnrealizedPnl => self.unrealized_pnl,
+            Metric::SessionPnl => self.session_pnl,
+            Metric::Equity => self.equity,
+            Metric::OpenInterest => self.open_interest,
+            Metric::OpenInterestChangePct => self.open_interest_change_pct,
+            Metric::MarkPremiumBps => self.mark_premium_bps,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Rule {
+    config: AlertRule,
+    /// When the metric last went past the threshold, while it stays there.
+    breached_since: Option<Instant>,
+    firing: bool,
+}
+
+impl Rule {
+    /// Whether `value` is past the threshold, and which way.
+    fn breach(&self, value: f64) -> Option<(&'static str, f64)> {
+        match (self.config.above, self.config.below) {
+            (Some(above), _) if value > above => Some(("above", above)),
+            (_, Some(below)) if value < below => Some(("below", below)),
+            _ => None,
+        }
+    }
+}
+
+/// Checks every rule against the latest [`Metrics`].
+#[derive(Debug)]
+pub struct AlertEngine {
+    rules: Vec<Rule>,
+    next_check: Option<Instant>,
+}
+
+impl AlertEngine {
+    /// Check `rules` from `now`, each with exactly one threshold and a name of its own.
+    pub fn new(rules: &[AlertRule], now: Instant) -> Result<Self, AlertError> {
+        let mut names = HashSet::new();
+        for rule in rules {
+            if rule.above.is_some() == rule.below.is_some() {
+                return Err(AlertError::Threshold(rule.name.clone()));
+            }
+            if !names.insert(&rule.name) {
+                return Err(AlertError::Duplicate(rule.name.clone()));
+            }
+        }
+        Ok(Self {
+            rules: rules
+                .iter()
+                .map(|config| Rule {
+                    config: config.clone(),
+                    breached_since: None,
+                    firing: false,
+                })
+                .collect(),
+            next_check: (!rules.is_empty()).then_some(now),
+        })
+    }
+
+    /// Check every rule against `metrics` at `now`, returning the alerts raised by rules that
+    /// have been breached for long enough and by those no longer breached.
+    pub fn evaluate(&mut self, metrics: &Metrics, now: Instant) -> Vec<Alert> {
+        let mut alerts = Vec::new();
+        for rule in &mut self.rules {
+            let value = metrics.value(rule.config.metric, now);
+            let name = &rule.config.name;
+            let metric = rule.config.metric.name();
+            match value.and_then(|value| Some((value, rule.breach(value)?))) {
+                Some((value, (side, threshold))) => {
+                    let since = *rule.breached_since.get_or_insert(now);
+                    let held = now.duration_since(since);
+                    if !rule.firing && held >= Duration::from_secs(rule.config.for_secs) {
+                        rule.firing = true;
+                        alerts.push(raise(
+                            name,
+                            rule.config.severity,
+                            format!(
+                                "Alert {name}: {metric} at {value:.2}, {side} {threshold} for {}s",
+                                held.as_secs()
+                            ),
+                        ));
+                    }
+                }
+                None => {
+                    rule.breached_since = None;
+                    if rule.firing {
+                        rule.firing = false;
+                        let message = match value {
+                            Some(value) => format!("Alert {name} cleared: {metric} at {value:.2}"),
+                            None => format!("Alert {name} cleared: {metric} unknown"),
+                        };
+                        let mut cleared = raise(name, Severity::Info, message);
+                        cleared.resolved = true;
+                        alerts.push(cleared);
+                    }
+                }
+            }
+        }
+        if self.next_check.is_some() {
+            self.next_check = Some(now + CHECK_INTERVAL);
+        }
+        alerts
+    }
+
+    /// Wait until the rules are next due a check. Never resolves without any rules.
+    pub async fn check_due(&self) {
+        match self.next_check {
+            Some(next_check) => tokio::time::sleep_until(next_check).await,
+            None => std::future::pending().await,
+        }
+    }
+}
+
+/// Log an alert as it is raised.
+fn raise(name: &str, severity: Severity, message: String) -> Alert {
+    match severity {
+        Severity::Info => info!("{}", message),
+        Severity::Warning | Severity::Critical => warn!("{}", message),
+    }
+    Alert {
+        severity,
+        message,
+        condition: Some(name.to_owned()),
+        resolved: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(name: &str, metric: Metric, above: Option<f64>, below: Option<f64>) -> AlertRule {
+        AlertRule {
+            name: name.to_owned(),
+            metric,
+            above,
+            below,
+            for_secs: 300,
+            severity: Severity::Critical,
+        }
+    }
+
+    #[test]
+    fn test_fires_after_holding_and_clears() {
+        let start = Instant::now();
+        let seconds = |secs| start + Duration::from_secs(secs);
+        let rules = [rule("pnl", Metric::UnrealizedPnl, None, Some(-50.0))];
+        let mut engine = AlertEngine::new(&rules, start).unwrap();
+        let mut metrics = Metrics::new(start);
+        assert!(engine.evaluate(&metrics, start).is_empty());
+
+        metrics.unrealized_pnl = Some(-60.0);
+        assert!(engine.evaluate(&metrics, seconds(10)).is_empty());
+        // A recovery in between starts the wait over
+        metrics.unrealized_pnl = Some(-40.0);
+        assert!(engine.evaluate(&metrics, seconds(100)).is_empty());
+        metrics.unrealized_pnl = Some(-62.5);
+        assert!(engine.evaluate(&metrics, seconds(200)).is_empty());
+        assert!(engine.evaluate(&metrics, seconds(499)).is_empty());
+
+        let alerts = engine.evaluate(&metrics, seconds(500));
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].severity, Severity::Critical);
+        assert_eq!(
+            alerts[0].message,
+            "Alert pnl: unrealized PnL at -62.50, below -50 for 300s"
+        );
+        assert_eq!(alerts[0].condition.as_deref(), Some("pnl"));
+        assert!(engine.evaluate(&metrics, seconds(600)).is_empty());
+
+        metrics.unrealized_pnl = Some(-10.0);
+        let alerts = engine.evaluate(&metrics, seconds(601));
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].severity, Severity::Info);
+        assert!(alerts[0].resolved);
+    }
+
+    #[test]
+    fn test_book_age_and_spread() {
+        let start = Instant::now();
+        let seconds = |secs| start + Duration::from_secs(secs);
+        let rules = [
+            AlertRule {
+                for_secs: 0,
+                ..rule("stale", Metric::BookAgeSecs, Some(30.0), None)
+            },
+            AlertRule {
+                for_secs: 0,
+                ..rule("wide", Metric::SpreadPct, Some(1.0), None)
+            },
+        ];
+        let mut engine = AlertEngine::new(&rules, start).unwrap();
+        let mut metrics = Metrics::new(start);
+        metrics.spread_pct = Some(1.5);
+        let alerts = engine.evaluate(&metrics, seconds(31));
+        assert_eq!(alerts.len(), 2);
+        assert_eq!(alerts[0].condition.as_deref(), Some("stale"));
+        assert_eq!(alerts[1].condition.as_deref(), Some("wide"));
+
+        metrics.last_book = seconds(31);
+        metrics.spread_pct = Some(0.5);
+        assert!(engine
+            .evaluate(&metrics, seconds(32))
+            .iter()
+            .all(|alert| alert.resolved));
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        let start = Instant::now();
+        assert!(matches!(
+            AlertEngine::new(&[rule("both", Metric::Equity, Some(1.0), Some(0.0))], start),
+            Err(AlertError::Threshold(_))
+        ));
+        assert!(matches!(
+            AlertEngine::new(&[rule("neither", Metric::Equity, None, None)], start),
+            Err(AlertError::Threshold(_))
+        ));
+        let twice = rule("twice", Metric::Equity, None, Some(900.0));
+        assert!(matches!(
+            AlertEngine::new(&[twice.clone(), twice], start),
+            Err(AlertError::Duplicate(_))
+        ));
+        assert_eq!(AlertEngine::new(&[], start).unwrap().next_check, None);
+    }
+}
@@ -0,0 +1,158 @@
+//! Counting heap allocations on the hot path, from a book to the decision on it, which should
+//! make none while the strategies hold.
+//!
+//! Built with the `alloc-count` feature, the bot installs [`CountingAllocator`] as its global
+//! allocator and warns about every book that allocated without being traded on. Without it
+//! [`HotPath::measure`] just runs what it is given and nothing is counted.
+
+use std::alloc::GlobalAlloc;
+use std::alloc::Layout;
+use std::alloc::System;
+use std::cell::Cell;
+
+thread_local! {
+    // No destructor, so safe to touch from the allocator at any point in a thread's life
+    static ALLOCATIONS: Cell<u64> = const { Cell::new(0) };
+}
+
+/// The system allocator, counting every allocation and reallocation made by each thread.
+#[derive(Debug, Default)]
+pub struct CountingAllocator;
+
+impl CountingAllocator {
+    fn count() {
+        let _ = ALLOCATIONS.try_with(|allocations| allocations.set(allocations.get() + 1));
+    }
+}
+
+// SAFETY: every call is passed straight on to the system allocator
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        Self::count();
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        Self::count();
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        Self::count();
+        System.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+/// Allocations made by the calling thread so far, while [`CountingAllocator`] is installed.
+pub fn allocations() -> u64 {
+    ALLOCATIONS.try_with(Cell::get).unwrap_or(0)
+}
+
+/// Allocations made within the measured parts of a tick.
+#[derive(Debug, Default)]
+pub struct HotPath {
+    allocations: u64,
+}
+
+impl HotPath {
+    /// Run `f`, counting what it allocates toward this tick.
+    #[inline]
+    pub fn measure<T>(&mut self, f: impl FnOnce() -> T) -> T {
+        #[cfg(feature = "alloc-count")]
+        {
+            let before = allocations();
+            let result = f();
+            self.allocations += allocations() - before;
+            result
+        }
+        #[cfg(not(feature = "alloc-count"))]
+        f()
+    }
+
+    /// Allocations counted since the last call, starting the next tick.
+    pub fn take(&mut self) -> u64 {
+        std::mem::take(&mut self.allocations)
+    }
+}
+
+#[cfg(all(test, feature = "alloc-count"))]
+mod tests {
+    use barter_data::subscription::book::Level;
+    use barter_data::subscription::book::OrderBook;
+    use barter_data::subscription::book::OrderBookSide;
+    use barter_integration::model::Side;
+    use chrono::DateTime;
+    use chrono::Utc;
+
+    use crate::allocation::Allocator;
+    use crate::config::AllocationConfig;
+    use crate::features::Features;
+    use crate::strategy::ensemble::Combination;
+    use crate::strategy::ensemble::Ensemble;
+    use crate::strategy::imbalance::ImbalanceParams;
+    use crate::strategy::imbalance::ImbalanceStrategy;
+    use crate::strategy::Strategy;
+
+    use super::*;
+
+    #[global_allocator]
+    static GLOBAL: CountingAllocator = CountingAllocator;
+
+    #[test]
+    fn test_counts_allocations() {
+        let mut hot_path = HotPath::default();
+        let buffer: Vec<u8> = hot_path.measure(|| Vec::with_capacity(16));
+        hot_path.measure(|| drop(buffer));
+        hot_path.measure(Vec::<u8>::new);
+        assert_eq!(hot_path.take(), 1);
+        assert_eq!(hot_path.take(), 0);
+    }
+
+    #[test]
+    fn test_holding_does_not_allocate() {
+        let level = |price| Level::new(price, 1.0);
+        let book = OrderBook {
+            last_update_time: DateTime::from_timestamp_millis(0).unwrap(),
+            bids: OrderBookSide::new(Side::Buy, vec![level(100.0), level(99.0)]),
+            asks: OrderBookSide::new(Side::Sell, vec![level(101.0), level(102.0)]),
+        };
+        let members: Vec<(Box<dyn Strategy>, f64)> = vec![(
+            Box::new(Ensemble::new(
+                vec![
+                    (
+                        Box::new(ImbalanceStrategy::new(ImbalanceParams::default())),
+                        1.0,
+                    ),
+                    (
+                        Box::new(ImbalanceStrategy::new(ImbalanceParams::default())),
+                        1.0,
+                    ),
+                ],
+                Combination::Majority,
+            )),
+            1.0,
+        )];
+        let now = Utc::now();
+        let mut allocator = Allocator::new(
+            1000.0,
+            "BTC/USDT",
+            members,
+            AllocationConfig::default(),
+            now,
+        );
+
+        let mut hot_path = HotPath::default();
+        for _ in 0..3 {
+            let features = hot_path
+                .measure(|| Features::from_order_book(&book))
+                .unwrap();
+            let events = hot_path.measure(|| allocator.on_features(&features, now));
+            assert!(events.is_empty());
+        }
+        assert_eq!(hot_path.take(), 0);
+    }
+}
@@ -0,0 +1,1535 @@
+use barter_data::subscription::book::OrderBook;
+use barter_integration::model::Side;
+use chrono::DateTime;
+use chrono::Utc;
+use serde::Deserialize;
+use serde::Serialize;
+use tracing::field;
+use tracing::info;
+use tracing::warn;
+use tracing::Span;
+
+use crate::chaos::Fault;
+use crate::chaos::OrderChaos;
+use crate::config::AddPolicy;
+use crate::config::AllocationConfig;
+use crate::config::Config;
+use crate::config::EntriesConfig;
+use crate::config::StrategyConfig;
+use crate::event::Event;
+use crate::event::Execution;
+use crate::event::OrderType;
+use crate::features::Features;
+use crate::placement::Crossing;
+use crate::queue::QueuePosition;
+use crate::strategy;
+use crate::strategy::plugin::PluginRegistry;
+use crate::strategy::Signal;
+use crate::strategy::Strategy;
+use crate::strategy::StrategyError;
+use crate::telemetry;
+use crate::telemetry::Trades;
+use crate::Fill;
+use crate::Lot;
+use crate::RiskParams;
+use crate::TradeError;
+use crate::TradingState;
+use crate::TRADE_SIZE;
+use crate::TRANSACTION_COST;
+
+/// A strategy trading its own fraction of total equity.
+#[derive(Debug)]
+pub struct Sleeve {
+    pub strategy: Box<dyn Strategy>,
+    pub state: TradingState,
+    pub weight: f64,
+    pub trades: Trades,
+    // Position among the allocator's sleeves, telling apart members running the same strategy
+    index: usize,
+    // Equity at the last rebalance, used to measure performance between rebalances
+    capital: f64,
+    // Initial allocation plus net transfers in, used to report PnL since inception
+    contributed: f64,
+    // Outside trading hours buys are dropped, while exits still run
+    entries_blocked: bool,
+    // Buys are dropped while holding this many lots, as volatility limits them
+    max_lots: Option<usize>,
+    // When a buy may add to a position already held, how big a lot it buys and the most lots held
+    entries: EntriesConfig,
+    // Orders on these sides take the far touch instead of resting at the near one
+    crossing: Crossing,
+    // Take profit and stop loss are judged at the mark when one is known, else the bid
+    mark: Option<f64>,
+    // In chaos mode, the rest of a partly filled buy, filling on the next update
+    resting: Option<Resting>,
+}
+
+/// An order only partly filled, with the spans to record the rest of its fill under.
+#[derive(Debug)]
+struct Resting {
+    side: Side,
+    price: f64,
+    /// What is left of the lot to fill.
+    size: f64,
+    decision_price: f64,
+    decision: Span,
+    order: Span,
+    /// Where it stands in its level's queue, from the first book seen after it rested.
+    queue: Option<QueuePosition>,
+}
+
+impl Sleeve {
+    fn new(
+        index: usize,
+        strategy: Box<dyn Strategy>,
+        weight: f64,
+        capital: f64,
+        symbol: &'static str,
+    ) -> Self {
+        Self {
+            strategy,
+            state: TradingState::new(capital, symbol),
+            weight,
+            trades: Trades::default(),
+            index,
+            capital,
+            contributed: capital,
+            entries_blocked: false,
+            max_lots: None,
+            entries: EntriesConfig::default(),
+            crossing: Crossing::default(),
+            mark: None,
+            resting: None,
+        }
+    }
+
+    /// The strategy's signal on `features`, holding instead of buying while entries are blocked,
+    /// the sleeve already holds its most lots or the entry policy won't add to its position, for
+    /// an update arriving at `now`.
+    pub fn evaluate(&mut self, features: &Features, now: DateTime<Utc>) -> Signal {
+        self.strategy.on_time(now);
+        match self.strategy.evaluate(features, &self.state) {
+            Signal::Buy
+                if self.entries_blocked
+                    || self.full()
+                    || !may_add(&self.entries, &self.state, features.bid) =>
+            {
+                Signal::Hold
+            }
+            signal => signal,
+        }
+    }
+
+    /// Where the rest of a partly filled order stands in its level's queue, for deciding whether
+    /// it is worth waiting on or better chased.
+    pub fn queue(&self) -> Option<&QueuePosition> {
+        self.resting.as_ref()?.queue.as_ref()
+    }
+
+    /// Whether the sleeve holds the most lots either `[entries]` or volatility allows.
+    fn full(&self) -> bool {
+        self.entries
+            .max_lots
+            .into_iter()
+            .chain(self.max_lots)
+            .min()
+            .is_some_and(|max_lots| self.state.positions.len() >= max_lots)
+    }
+
+    /// The size of the next order on `side`: the newest lot when selling, and when buying
+    /// [`TRADE_SIZE`] unless averaging down tapers it.
+    pub fn lot_size(&self, side: Side) -> f64 {
+        match side {
+            Side::Buy => lot_size(&self.entries, self.state.positions.len()),
+            Side::Sell => self
+                .state
+                .positions
+                .last()
+                .map_or(TRADE_SIZE, |lot| lot.size),
+        }
+    }
+
+    /// Evaluate the strategy on `features` and trade this sleeve's state accordingly, returning
+    /// what happened. With `chaos`, the order may be rejected or, when buying, only partly filled.
+    pub fn on_features(
+        &mut self,
+        features: &Features,
+        now: DateTime<Utc>,
+        chaos: Option<&mut OrderChaos>,
+    ) -> Vec<Event> {
+        if let Some(resting) = self.resting.take() {
+            return self.fill_resting(resting, features, now);
+        }
+        let started = telemetry::now();
+        let signal = self.evaluate(features, now);
+        let evaluated = telemetry::now();
+        let placement = self.placement(signal, features);
+        // Sized before trading, which changes the lots the size follows
+        let size = placement.map_or(TRADE_SIZE, |(side, _, _)| self.lot_size(side));
+        let fault = match (placement, chaos) {
+            (Some((side, _, _)), Some(chaos)) => match chaos.fault() {
+                // Every sell closes a whole lot, so only buys fill in part
+                Fault::Partial(_) if side == Side::Sell => Fault::None,
+                fault => fault,
+            },
+            _ => Fault::None,
+        };
+        // A rejected order isn't filled, and a partly filled one only for its fraction of the
+        // lot, though exits still run
+        let filled = match fault {
+            Fault::None => Some(size),
+            Fault::Partial(fraction) => Some(size * fraction),
+            Fault::Rejected => None,
+        };
+        let placed_fill = match (placement, filled) {
+            (Some((side, price, _)), Some(filled)) => self
+                .state
+                .execute_trade(price, side, filled, TRANSACTION_COST)
+                .map(Some),
+            _ => Ok(None),
+        };
+        let exits = self.exits(features);
+
+        let mut events = self.signal_events(signal, features, size, now);
+        if let Some((side, price, order_type)) = placement {
+            let decision = self.trades.decide(
+                self.state.symbol,
+                self.strategy.name(),
+                signal,
+                features,
+                started,
+                evaluated,
+            );
+            let order = Trades::order(&decision, side, price);
+            match (fault, placed_fill) {
+                (Fault::Rejected, _) => {
+                    order.record("rejected", "chaos");
+                    warn!("Chaos: rejecting {:?} order at {}", side, price);
+                }
+                // The signal's own fill, at its limit price or across the spread
+                (_, Ok(Some(fill))) => {
+                    if let Fault::Partial(_) = fault {
+                        info!(
+                            "Chaos: {:?} order filled {} of {} {} at {}, resting the rest",
+                            side, fill.size, size, self.state.symbol, price
+                        );
+                        self.resting = Some(Resting {
+                            side,
+                            price,
+                            size: size - fill.size,
+                            decision_price: features.mid_price,
+                            decision: decision.clone(),
+                            order: order.clone(),
+                            queue: None,
+                        });
+                    }
+                    self.trades.filled(decision, &order, &fill);
+                    let execution = Execution::paper(order_type, features.mid_price);
+                    events.push(self.fill_event(fill, execution, now));
+                }
+                (_, Ok(None)) => {}
+                (_, Err(error)) => self.reject(&order, side, price, error),
+            }
+        }
+        for fill in exits {
+            let decision = self.trades.exit("risk");
+            let span = Trades::order(&decision, fill.side, fill.price);
+            self.trades.filled(decision, &span, &fill);
+            let execution = Execution::paper(OrderType::Market, features.mid_price);
+            events.push(self.fill_event(fill, execution, now));
+        }
+        events
+    }
+
+    /// Fill the rest of a partly filled buy at its price, as a lot of its own, unless entries
+    /// were blocked meanwhile, then let take profit and stop loss run on `features`.
+    fn fill_resting(
+        &mut self,
+        resting: Resting,
+        features: &Features,
+        now: DateTime<Utc>,
+    ) -> Vec<Event> {
+        let mut events = Vec::new();
+        let filled = (!self.entries_blocked).then(|| {
+            self.state
+                .execute_trade(resting.price, resting.side, resting.size, TRANSACTION_COST)
+        });
+        match filled {
+            None => {
+                resting.order.record("rejected", "entries blocked");
+                info!(
+                    "Dropping the rest of the {:?} order at {}, entries are blocked",
+                    resting.side, resting.price
+                );
+            }
+            Some(Ok(fill)) => {
+                self.trades.filled(resting.decision, &resting.order, &fill);
+                let queue_ahead = resting.queue.as_ref().map(QueuePosition::ahead);
+                if let Some(queue_ahead) = queue_ahead {
+                    info!(
+                        "{:?} order at {} filled with {} estimated ahead of it",
+                        resting.side, resting.price, queue_ahead
+                    );
+                }
+                let execution = Execution {
+                    queue_ahead,
+                    ..Execution::paper(OrderType::Limit, resting.decision_price)
+                };
+                events.push(self.fill_event(fill, execution, now));
+            }
+            Some(Err(error)) => self.reject(&resting.order, resting.side, resting.price, error),
+        }
+        for fill in self.exits(features) {
+            let decision = self.trades.exit("risk");
+            let span = Trades::order(&decision, fill.side, fill.price);
+            self.trades.filled(decision, &span, &fill);
+            let execution = Execution::paper(OrderType::Market, features.mid_price);
+            events.push(self.fill_event(fill, execution, now));
+        }
+        events
+    }
+
+    /// Sell the positions take profit or stop loss trigger for at the bid of `features`.
+    fn exits(&mut self, features: &Features) -> Vec<Fill> {
+        let mark = self.mark.unwrap_or(features.bid);
+        self.state.check_exits_marked(mark, features.bid)
+    }
+
+    /// Record why `order`, to `side` at `price`, couldn't be filled.
+    fn reject(&self, order: &Span, side: Side, price: f64, error: TradeError) {
+        order.record("rejected", field::display(&error));
+        info!(
+            "{} {:?} order at {} rejected: {}",
+            self.strategy.name(),
+            side,
+            price,
+            error
+        );
+    }
+
+    /// The order `signal` places at the top of book in `features`, if any.
+    pub fn order(signal: Signal, features: &Features) -> Option<(Side, f64)> {
+        match signal {
+            Signal::Buy => Some((Side::Buy, features.bid)),
+            Signal::Sell => Some((Side::Sell, features.ask)),
+            Signal::Hold => None,
+        }
+    }
+
+    /// The side, price and type of the order acting on `signal`: resting at the touch, or taking
+    /// the far touch on the sides set to cross the spread.
+    pub fn placement(&self, signal: Signal, features: &Features) -> Option<(Side, f64, OrderType)> {
+        let (side, price) = Self::order(signal, features)?;
+        if !self.crossing.crosses(side) {
+            return Some((side, price, OrderType::Limit));
+        }
+        let price = match side {
+            Side::Buy => features.ask,
+            Side::Sell => features.bid,
+        };
+        Some((side, price, OrderType::Market))
+    }
+
+    /// The signal and order events for acting on `signal` with an order of `size`, empty when
+    /// holding.
+    pub fn signal_events(
+        &self,
+        signal: Signal,
+        features: &Features,
+        size: f64,
+        now: DateTime<Utc>,
+    ) -> Vec<Event> {
+        let Some((side, price, _)) = self.placement(signal, features) else {
+            return Vec::new();
+        };
+
+        let symbol = self.state.symbol;
+        let strategy = self.strategy.name();
+        vec![
+            Event::Signal {
+                time: now,
+                symbol,
+                strategy: strategy.to_owned(),
+                signal,
+                features: *features,
+                book_time: None,
+            },
+            Event::Order {
+                time: now,
+                symbol,
+                strategy: strategy.to_owned(),
+                side,
+                price,
+                size,
+                book_time: None,
+            },
+        ]
+    }
+
+    pub fn fill_event(&self, fill: Fill, execution: Execution, now: DateTime<Utc>) -> Event {
+        Event::Fill {
+            time: now,
+            symbol: self.state.symbol,
+            strategy: self.strategy.name().to_owned(),
+            sleeve: self.index,
+            execution,
+            fill,
+            book_time: None,
+        }
+    }
+
+    /// Take `weight` of equity, moving `transfer` cash in (or out, when negative) to reach
+    /// `capital`.
+    fn rebalance(&mut self, weight: f64, capital: f64, transfer: f64) {
+        self.weight = weight;
+        self.state.cash += transfer;
+        self.contributed += transfer;
+        self.capital = capital;
+    }
+
+    /// Position among the allocator's sleeves, which stays put while strategies are reloaded.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Profit or loss since inception, net of capital moved in or out by rebalancing.
+    pub fn pnl(&self, bid: f64) -> f64 {
+        self.state.calculate_portfolio_value(bid) - self.contributed
+    }
+}
+
+/// Everything about a sleeve that changes while trading.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SleeveSnapshot {
+    pub strategy: String,
+    pub cash: f64,
+    pub positions: Vec<Lot>,
+    pub weight: f64,
+    pub capital: f64,
+    pub contributed: f64,
+}
+
+/// The mutable state of an [`Allocator`], saved so a restarted bot carries on where it stopped.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AllocatorSnapshot {
+    pub last_rebalance: DateTime<Utc>,
+    pub sleeves: Vec<SleeveSnapshot>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RestoreError {
+    #[error("saved state has {saved} sleeves but {configured} are configured")]
+    SleeveCount { saved: usize, configured: usize },
+
+    #[error("saved sleeve {index} ran {saved} but {configured} is configured")]
+    Strategy {
+        index: usize,
+        saved: String,
+        configured: String,
+    },
+}
+
+/// Strategies to run, one per sleeve, with their weights.
+pub type Members = Vec<(Box<dyn Strategy>, f64)>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReconfigureError {
+    #[error(transparent)]
+    Strategy(#[from] StrategyError),
+
+    #[error("{configured} strategies are configured but {running} sleeves are running")]
+    SleeveCount { running: usize, configured: usize },
+
+    #[error("switching between independent and combined allocation requires a restart")]
+    Independent,
+}
+
+/// Whether `entries` lets a lot bought at `bid` add to the position `state` holds.
+fn may_add(entries: &EntriesConfig, state: &TradingState, bid: f64) -> bool {
+    let Some(mean) = state.average_entry() else {
+        return true;
+    };
+    match entries.policy {
+        AddPolicy::Stack => true,
+        AddPolicy::Pyramid => bid >= mean * (1.0 + entries.min_profit),
+        AddPolicy::Dca => {
+            let lowest = state
+                .positions
+                .iter()
+                .map(|lot| lot.price)
+                .fold(f64::INFINITY, f64::min);
+            let held = state.positions.len() as i32;
+            let step = entries.dca_step * entries.step_growth.powi(held - 1);
+            bid <= lowest * (1.0 - step)
+        }
+    }
+}
+
+/// The size of the lot bought while holding `held` lots: each add averaging down is
+/// `size_decay` times the last.
+fn lot_size(entries: &EntriesConfig, held: usize) -> f64 {
+    match entries.policy {
+        AddPolicy::Dca => TRADE_SIZE * entries.size_decay.powi(held as i32),
+        AddPolicy::Stack | AddPolicy::Pyramid => TRADE_SIZE,
+    }
+}
+
+/// Splits total equity between concurrently running strategies and periodically shifts weight
+/// toward the better performers.
+#[derive(Debug)]
+pub struct Allocator {
+    sleeves: Vec<Sleeve>,
+    config: AllocationConfig,
+    last_rebalance: DateTime<Utc>,
+    chaos: Option<OrderChaos>,
+}
+
+impl Allocator {
+    /// Split `cash` between `members` in proportion to their weights.
+    pub fn new(
+        cash: f64,
+        symbol: &'static str,
+        members: Vec<(Box<dyn Strategy>, f64)>,
+        config: AllocationConfig,
+        now: DateTime<Utc>,
+    ) -> Self {
+        let total_weight: f64 = members.iter().map(|(_, weight)| weight.max(0.0)).sum();
+        let count = members.len() as f64;
+        let sleeves = members
+            .into_iter()
+            .enumerate()
+            .map(|(index, (strategy, weight))| {
+                let weight = if total_weight > 0.0 {
+                    weight.max(0.0) / total_weight
+                } else {
+                    1.0 / count
+                };
+                Sleeve::new(index, strategy, weight, cash * weight, symbol)
+            })
+            .collect();
+
+        Self {
+            sleeves,
+            config,
+            last_rebalance: now,
+            chaos: None,
+        }
+    }
+
+    /// Build the allocator described by `config`: one sleeve per strategy member when allocation
+    /// is independent, otherwise a single sleeve trading the combined ensemble signal.
+    pub fn from_config(
+        config: &Config,
+        plugins: &PluginRegistry,
+        cash: f64,
+        symbol: &'static str,
+        now: DateTime<Utc>,
+    ) -> Result<Self, StrategyError> {
+        let members =
+            Self::build_members(&config.strategy, config.allocation.independent, plugins)?;
+
+        let mut allocator = Self::new(cash, symbol, members, config.allocation.clone(), now);
+        allocator.set_entries(config.entries);
+        Ok(allocator)
+    }
+
+    /// The strategy for each sleeve and its weight: one per member when `independent`,
+    /// otherwise the single combined ensemble.
+    pub fn build_members(
+        config: &StrategyConfig,
+        independent: bool,
+        plugins: &PluginRegistry,
+    ) -> Result<Members, StrategyError> {
+        if independent {
+            config
+                .members
+                .iter()
+                .map(|member| Ok((member.strategy.build(plugins)?, member.weight)))
+                .collect()
+        } else {
+            let ensemble: Box<dyn Strategy> = Box::new(strategy::build(config, plugins)?);
+            Ok(vec![(ensemble, 1.0)])
+        }
+    }
+
+    /// Trade `members`, built by [`build_members`](Self::build_members), from the next update
+    /// on. Every sleeve keeps its cash, positions and weight, so there must be one per sleeve.
+    pub fn replace_strategies(&mut self, members: Members) -> Result<(), ReconfigureError> {
+        if members.len() != self.sleeves.len() {
+            return Err(ReconfigureError::SleeveCount {
+                running: self.sleeves.len(),
+                configured: members.len(),
+            });
+        }
+        for (sleeve, (strategy, _)) in self.sleeves.iter_mut().zip(members) {
+            info!(
+                "Replacing {} strategy with {}",
+                sleeve.strategy.name(),
+                strategy.name()
+            );
+            sleeve.strategy = strategy;
+        }
+        Ok(())
+    }
+
+    /// Rebalance according to `config` from now on.
+    pub fn set_allocation(&mut self, config: AllocationConfig) -> Result<(), ReconfigureError> {
+        if config.independent != self.config.independent {
+            return Err(ReconfigureError::Independent);
+        }
+        self.config = config;
+        Ok(())
+    }
+
+    pub fn sleeves(&self) -> &[Sleeve] {
+        &self.sleeves
+    }
+
+    pub fn sleeves_mut(&mut self) -> &mut [Sleeve] {
+        &mut self.sleeves
+    }
+
+    pub fn snapshot(&self) -> AllocatorSnapshot {
+        AllocatorSnapshot {
+            last_rebalance: self.last_rebalance,
+            sleeves: self
+                .sleeves
+                .iter()
+                .map(|sleeve| SleeveSnapshot {
+                    strategy: sleeve.strategy.name().to_owned(),
+                    cash: sleeve.state.cash,
+                    positions: sleeve.state.positions.clone(),
+                    weight: sleeve.weight,
+                    capital: sleeve.capital,
+                    contributed: sleeve.contributed,
+                })
+                .collect(),
+        }
+    }
+
+    /// Carry on from `snapshot`, which must have been taken with the same strategies configured.
+    pub fn restore(&mut self, snapshot: &AllocatorSnapshot) -> Result<(), RestoreError> {
+        if snapshot.sleeves.len() != self.sleeves.len() {
+            return Err(RestoreError::SleeveCount {
+                saved: snapshot.sleeves.len(),
+                configured: self.sleeves.len(),
+            });
+        }
+        for (index, (sleeve, saved)) in self.sleeves.iter().zip(&snapshot.sleeves).enumerate() {
+            if sleeve.strategy.name() != saved.strategy {
+                return Err(RestoreError::Strategy {
+                    index,
+                    saved: saved.strategy.clone(),
+                    configured: sleeve.strategy.name().to_owned(),
+                });
+            }
+        }
+
+        for (sleeve, saved) in self.sleeves.iter_mut().zip(&snapshot.sleeves) {
+            sleeve.state.cash = saved.cash;
+            sleeve.state.positions = saved.positions.clone();
+            sleeve.trades = Trades::default();
+            sleeve.weight = saved.weight;
+            sleeve.capital = saved.capital;
+            sleeve.contributed = saved.contributed;
+        }
+        self.last_rebalance = snapshot.last_rebalance;
+        Ok(())
+    }
+
+    /// Hand every sleeve's strategy `price`, the instrument's mid on another venue.
+    pub fn on_reference(&mut self, price: f64) {
+        for sleeve in &mut self.sleeves {
+            sleeve.strategy.on_reference(price);
+        }
+    }
+
+    /// Follow every resting order's queue on `book`, the one just traded on when it rested.
+    pub fn on_book(&mut self, book: &OrderBook) {
+        for resting in self
+            .sleeves
+            .iter_mut()
+            .filter_map(|sleeve| sleeve.resting.as_mut())
+        {
+            match &mut resting.queue {
+                Some(queue) => queue.on_book(book),
+                None => {
+                    resting.queue = Some(QueuePosition::join(resting.side, resting.price, book))
+                }
+            }
+        }
+    }
+
+    /// Count a public trade of `amount` at `price`, taken on `side`, against every resting order.
+    pub fn on_trade(&mut self, side: Side, price: f64, amount: f64) {
+        let queues = self
+            .sleeves
+            .iter_mut()
+            .filter_map(|sleeve| sleeve.resting.as_mut()?.queue.as_mut());
+        for queue in queues {
+            queue.on_trade(side, price, amount);
+        }
+    }
+
+    /// Cross the spread on `crossing`'s sides from the next update on.
+    pub fn set_crossing(&mut self, crossing: Crossing) {
+        for sleeve in &mut self.sleeves {
+            sleeve.crossing = crossing;
+        }
+    }
+
+    /// Judge take profit and stop loss at `mark` from the next update on, or at the bid without
+    /// one.
+    pub fn set_mark(&mut self, mark: Option<f64>) {
+        for sleeve in &mut self.sleeves {
+            sleeve.mark = mark;
+        }
+    }
+
+    /// Let every sleeve trade on `features`, then rebalance if due.
+    pub fn on_features(&mut self, features: &Features, now: DateTime<Utc>) -> Vec<Event> {
+        let chaos = &mut self.chaos;
+        let mut events: Vec<Event> = self
+            .sleeves
+            .iter_mut()
+            .flat_map(|sleeve| sleeve.on_features(features, now, chaos.as_mut()))
+            .collect();
+
+        // Shift capital toward the better performing strategies when due
+        events.extend(
+            self.maybe_rebalance(now, features.bid)
+                .into_iter()
+                .flatten(),
+        );
+        events
+    }
+
+    /// Inject `chaos` faults into every sleeve's orders from the next update on.
+    pub fn set_chaos(&mut self, chaos: OrderChaos) {
+        self.chaos = Some(chaos);
+    }
+
+    /// Use `risk` for every sleeve's take profit and stop loss from the next update on.
+    pub fn set_risk(&mut self, risk: RiskParams) {
+        for sleeve in &mut self.sleeves {
+            sleeve.state.risk = risk;
+        }
+    }
+
+    /// Stop every sleeve opening positions while `blocked`, leaving exits to run.
+    pub fn block_entries(&mut self, blocked: bool) {
+        for sleeve in &mut self.sleeves {
+            sleeve.entries_blocked = blocked;
+        }
+    }
+
+    /// Add to positions already held only as `entries` allows, in every sleeve, from the next buy.
+    pub fn set_entries(&mut self, entries: EntriesConfig) {
+        for sleeve in &mut self.sleeves {
+            sleeve.entries = entries;
+        }
+    }
+
+    /// Stop every sleeve buying while it holds `max_lots`, if set, or the fewer lots `[entries]`
+    /// allows, leaving exits to run.
+    pub fn limit_lots(&mut self, max_lots: Option<usize>) {
+        for sleeve in &mut self.sleeves {
+            sleeve.max_lots = max_lots;
+        }
+    }
+
+    /// Sell every open position of every sleeve at the bid in `features`, returning the fills,
+    /// and drop the rest of any partly filled buy.
+    pub fn flatten(&mut self, features: &Features, now: DateTime<Utc>) -> Vec<Event> {
+        let mut events = Vec::new();
+        for sleeve in &mut self.sleeves {
+            // The rest of a partly filled buy would open a position again
+            if let Some(resting) = sleeve.resting.take() {
+                resting.order.record("rejected", "flatten");
+            }
+            // Until there is no position left to sell
+            while let Ok(fill) =
+                sleeve
+                    .state
+                    .execute_trade(features.bid, Side::Sell, TRADE_SIZE, TRANSACTION_COST)
+            {
+                let decision = sleeve.trades.exit("flatten");
+                let span = Trades::order(&decision, fill.side, fill.price);
+                sleeve.trades.filled(decision, &span, &fill);
+                let execution = Execution::paper(OrderType::Market, features.mid_price);
+                events.push(sleeve.fill_event(fill, execution, now));
+            }
+        }
+        events
+    }
+
+    pub fn total_value(&self, bid: f64) -> f64 {
+        self.sleeves
+            .iter()
+            .map(|sleeve| sleeve.state.calculate_portfolio_value(bid))
+            .sum()
+    }
+
+    /// What every sleeve's open lots would make or lose at `price`, before fees.
+    pub fn unrealized_pnl(&self, price: f64) -> f64 {
+        self.sleeves
+            .iter()
+            .map(|sleeve| sleeve.state.unrealized_pnl(price))
+            .sum()
+    }
+
+    /// Rebalance if the configured interval has elapsed since the last rebalance, returning the
+    /// resulting rebalance events if it did.
+    pub fn maybe_rebalance(&mut self, now: DateTime<Utc>, bid: f64) -> Option<Vec<Event>> {
+        let interval = self.config.rebalance_interval_secs?;
+        if self.sleeves.len() < 2 || (now - self.last_rebalance).num_seconds() < interval as i64 {
+            return None;
+        }
+
+        let events = self.rebalance(bid, now);
+        self.last_rebalance = now;
+        Some(events)
+    }
+
+    /// Book a fill replayed from a log to sleeve number `sleeve` running `strategy`, returning
+    /// whether there is one.
+    pub fn replay_fill(&mut self, sleeve: Option<usize>, strategy: &str, fill: &Fill) -> bool {
+        let Some(sleeve) = self.sleeve_mut(sleeve, strategy) else {
+            return false;
+        };
+        sleeve.state.apply_fill(fill);
+        true
+    }
+
+    /// Apply a rebalance replayed from a log to sleeve number `sleeve` running `strategy`,
+    /// returning whether there is one.
+    pub fn replay_rebalance(
+        &mut self,
+        time: DateTime<Utc>,
+        sleeve: Option<usize>,
+        strategy: &str,
+        weight: f64,
+        capital: f64,
+        transfer: f64,
+    ) -> bool {
+        let Some(sleeve) = self.sleeve_mut(sleeve, strategy) else {
+            return false;
+        };
+        sleeve.rebalance(weight, capital, transfer);
+        self.last_rebalance = time;
+        true
+    }
+
+    /// Sleeve number `index` if it runs `strategy`. Logs written before events carried the
+    /// sleeve only name the strategy, so those go to the first sleeve running it.
+    fn sleeve_mut(&mut self, index: Option<usize>, strategy: &str) -> Option<&mut Sleeve> {
+        match index {
+            Some(index) => self
+                .sleeves
+                .get_mut(index)
+                .filter(|sleeve| sleeve.strategy.name() == strategy),
+            None => self
+                .sleeves
+                .iter_mut()
+                .find(|sleeve| sleeve.strategy.name() == strategy),
+        }
+    }
+
+    /// Move each sleeve's weight toward its share of performance-scaled weight, then transfer
+    /// cash so every sleeve's equity matches its new weight. Open positions are never touched, so
+    /// a sleeve gives up at most the cash it holds and the sleeves taking cash share whatever it
+    /// couldn't give, each ending up with the weight its equity actually is.
+    fn rebalance(&mut self, bid: f64, now: DateTime<Utc>) -> Vec<Event> {
+        let total_value = self.total_value(bid);
+        if total_value <= 0.0 {
+            return Vec::new();
+        }
+
+        let values: Vec<f64> = self
+            .sleeves
+            .iter()
+            .map(|sleeve| sleeve.state.calculate_portfolio_value(bid))
+            .collect();
+
+        // Weight scaled by growth since the last rebalance
+        let scores: Vec<f64> = self
+            .sleeves
+            .iter()
+            .zip(&values)
+            .map(|(sleeve, value)| {
+                let growth = if sleeve.capital > 0.0 {
+                    value / sleeve.capital
+                } else {
+                    1.0
+                };
+                sleeve.weight * growth.max(0.0)
+            })
+            .collect();
+        let total_score: f64 = scores.iter().sum();
+
+        let rate = self.config.rebalance_rate.clamp(0.0, 1.0);
+        let min_weight = self.config.min_weight.max(0.0);
+        for (sleeve, score) in self.sleeves.iter_mut().zip(&scores) {
+            let target = if total_score > 0.0 {
+                score / total_score
+            } else {
+                sleeve.weight
+            };
+            sleeve.weight = ((1.0 - rate) * sleeve.weight + rate * target).max(min_weight);
+        }
+
+        let total_weight: f64 = self.sleeves.iter().map(|sleeve| sleeve.weight).sum();
+        let mut transfers: Vec<f64> = self
+            .sleeves
+            .iter()
+            .zip(&values)
+            .map(|(sleeve, value)| {
+                let transfer = sleeve.weight / total_weight * total_value - value;
+                transfer.max(-sleeve.state.cash.max(0.0))
+            })
+            .collect();
+
+        // Only hand out as much as the other sleeves could give
+        let outflow: f64 = transfers.iter().filter(|t| **t < 0.0).map(|t| -t).sum();
+        let inflow: f64 = transfers.iter().filter(|t| **t > 0.0).sum();
+        if inflow > outflow {
+            let scale = outflow / inflow;
+            for transfer in transfers.iter_mut().filter(|t| **t > 0.0) {
+                *transfer *= scale;
+            }
+        }
+
+        let mut events = Vec::with_capacity(self.sleeves.len());
+        for ((sleeve, value), transfer) in self.sleeves.iter_mut().zip(values).zip(transfers) {
+            let capital = value + transfer;
+            let weight = capital / total_value;
+            sleeve.rebalance(weight, capital, transfer);
+
+            info!(
+                "Rebalanced {} sleeve to {:.1}% of equity (pnl: {:.2}, transfer: {:.2})",
+                sleeve.strategy.name(),
+                sleeve.weight * 100.0,
+                sleeve.pnl(bid),
+                transfer
+            );
+            events.push(Event::Rebalance {
+                time: now,
+                symbol: sleeve.state.symbol,
+                strategy: sleeve.strategy.name().to_owned(),
+                sleeve: sleeve.index,
+                weight,
+                capital,
+                transfer,
+            });
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use barter_data::subscription::book::OrderBookSide;
+    use chrono::Duration;
+
+    use super::*;
+    use crate::config::ChaosConfig;
+
+    #[derive(Debug)]
+    struct Idle;
+
+    impl Strategy for Idle {
+        fn name(&self) -> &str {
+            "idle"
+        }
+
+        fn evaluate(&mut self, _features: &Features, _state: &TradingState) -> Signal {
+            Signal::Hold
+        }
+    }
+
+    #[derive(Debug)]
+    struct Buyer;
+
+    impl Strategy for Buyer {
+        fn name(&self) -> &str {
+            "buyer"
+        }
+
+        fn evaluate(&mut self, _features: &Features, _state: &TradingState) -> Signal {
+            Signal::Buy
+        }
+    }
+
+    #[derive(Debug)]
+    struct Seller;
+
+    impl Strategy for Seller {
+        fn name(&self) -> &str {
+            "seller"
+        }
+
+        fn evaluate(&mut self, _features: &Features, _state: &TradingState) -> Signal {
+            Signal::Sell
+        }
+    }
+
+    fn config(rebalance_interval_secs: Option<u64>) -> AllocationConfig {
+        AllocationConfig {
+            independent: true,
+            rebalance_interval_secs,
+            rebalance_rate: 1.0,
+            min_weight: 0.0,
+        }
+    }
+
+    fn allocator(weights: &[f64], config: AllocationConfig, now: DateTime<Utc>) -> Allocator {
+        let members = weights
+            .iter()
+            .map(|&weight| (Box::new(Idle) as Box<dyn Strategy>, weight))
+            .collect();
+        Allocator::new(1000.0, "BTC/USDT", members, config, now)
+    }
+
+    #[test]
+    fn test_initial_allocation_follows_weights() {
+        let allocator = allocator(&[3.0, 1.0], config(None), Utc::now());
+        assert_eq!(allocator.sleeves()[0].state.cash, 750.0);
+        assert_eq!(allocator.sleeves()[1].state.cash, 250.0);
+        assert_eq!(allocator.total_value(100.0), 1000.0);
+    }
+
+    #[test]
+    fn test_rebalance_favours_better_performer() {
+        let start = Utc::now();
+        let mut allocator = allocator(&[1.0, 1.0], config(Some(60)), start);
+
+        // First sleeve gains 100, the second is flat
+        allocator.sleeves_mut()[0].state.cash += 100.0;
+
+        assert!(allocator
+            .maybe_rebalance(start + Duration::seconds(30), 100.0)
+            .is_none());
+        let events = allocator
+            .maybe_rebalance(start + Duration::seconds(60), 100.0)
+            .unwrap();
+        let kinds: Vec<_> = events.iter().map(Event::kind).collect();
+        assert_eq!(kinds, ["rebalance", "rebalance"]);
+
+        let sleeves = allocator.sleeves();
+        assert!(sleeves[0].weight > sleeves[1].weight);
+        assert!((sleeves[0].weight - 600.0 / 1100.0).abs() < 1e-9);
+        assert!((allocator.total_value(100.0) - 1100.0).abs() < 1e-9);
+
+        // PnL since inception is unaffected by the transfer
+        assert!((sleeves[0].pnl(100.0) - 100.0).abs() < 1e-9);
+        assert!(sleeves[1].pnl(100.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_rebalance_never_overdraws() {
+        let start = Utc::now();
+        let config = AllocationConfig {
+            rebalance_rate: 0.5,
+            ..config(Some(60))
+        };
+        let mut allocator = allocator(&[1.0, 1.0, 1.0], config, start);
+
+        // First sleeve puts nearly all its cash in positions that double, so its new weight is
+        // worth less than they are
+        let sleeve = &mut allocator.sleeves_mut()[0];
+        sleeve.state.cash -= 300.0;
+        sleeve.state.positions.extend([Lot::at(100_000.0); 3]);
+
+        allocator
+            .maybe_rebalance(start + Duration::seconds(60), 200_000.0)
+            .unwrap();
+
+        let sleeves = allocator.sleeves();
+        assert!(sleeves.iter().all(|sleeve| sleeve.state.cash >= 0.0));
+        assert_eq!(sleeves[0].state.cash, 0.0);
+        assert!((allocator.total_value(200_000.0) - 1300.0).abs() < 1e-9);
+        let weights: f64 = sleeves.iter().map(|sleeve| sleeve.weight).sum();
+        assert!((weights - 1.0).abs() < 1e-9);
+        assert!(sleeves[1].state.cash > 1000.0 / 3.0);
+    }
+
+    #[test]
+    fn test_no_rebalance_without_interval() {
+        let start = Utc::now();
+        let mut allocator = allocator(&[1.0, 1.0], config(None), start);
+        assert!(allocator
+            .maybe_rebalance(start + Duration::days(1), 100.0)
+            .is_none());
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let start = Utc::now();
+        let mut running = allocator(&[3.0, 1.0], config(Some(60)), start);
+        running.sleeves_mut()[0].state.cash -= 100.0;
+        running.sleeves_mut()[0]
+            .state
+            .positions
+            .push(Lot::at(100.0));
+        running.maybe_rebalance(start + Duration::seconds(60), 100.0);
+        let snapshot = running.snapshot();
+
+        let mut restarted = allocator(&[3.0, 1.0], config(Some(60)), Utc::now());
+        restarted.restore(&snapshot).unwrap();
+        assert_eq!(restarted.snapshot(), snapshot);
+        assert_eq!(restarted.sleeves()[0].state.positions, [Lot::at(100.0)]);
+
+        let mut different = allocator(&[1.0], config(None), Utc::now());
+        assert!(matches!(
+            different.restore(&snapshot),
+            Err(RestoreError::SleeveCount {
+                saved: 2,
+                configured: 1
+            })
+        ));
+    }
+
+    #[test]
+    fn test_on_features_reports_events() {
+        let now = Utc::now();
+        let members: Vec<(Box<dyn Strategy>, f64)> =
+            vec![(Box::new(Buyer), 1.0), (Box::new(Idle), 1.0)];
+        let mut allocator = Allocator::new(1000.0, "BTC/USDT", members, config(None), now);
+        let features = Features {
+            bid: 100.0,
+            ask: 100.01,
+            ..Features::default()
+        };
+
+        let events = allocator.on_features(&features, now);
+        let kinds: Vec<_> = events.iter().map(Event::kind).collect();
+        assert_eq!(kinds, ["signal", "order", "fill"]);
+        let Event::Fill { strategy, fill, .. } = &events[2] else {
+            panic!("expected a fill");
+        };
+        assert_eq!(strategy, "buyer");
+        assert_eq!(fill.side, Side::Buy);
+        assert_eq!(fill.price, 100.0);
+    }
+
+    #[test]
+    fn test_blocked_entries_hold() {
+        let now = Utc::now();
+        let members: Vec<(Box<dyn Strategy>, f64)> = vec![(Box::new(Buyer), 1.0)];
+        let mut allocator = Allocator::new(1000.0, "BTC/USDT", members, config(None), now);
+        let features = Features {
+            bid: 100.0,
+            ask: 100.01,
+            ..Features::default()
+        };
+
+        allocator.block_entries(true);
+        assert!(allocator.on_features(&features, now).is_empty());
+        assert!(allocator.sleeves()[0].state.positions.is_empty());
+
+        allocator.block_entries(false);
+        assert_eq!(allocator.on_features(&features, now).len(), 3);
+    }
+
+    #[test]
+    fn test_lot_cap_holds() {
+        let now = Utc::now();
+        let members: Vec<(Box<dyn Strategy>, f64)> = vec![(Box::new(Buyer), 1.0)];
+        let mut allocator = Allocator::new(1000.0, "BTC/USDT", members, config(None), now);
+        let features = Features {
+            bid: 100.0,
+            ask: 100.01,
+            ..Features::default()
+        };
+
+        allocator.limit_lots(Some(2));
+        for _ in 0..4 {
+            allocator.on_features(&features, now);
+        }
+        assert_eq!(allocator.sleeves()[0].state.positions.len(), 2);
+
+        allocator.limit_lots(None);
+        allocator.on_features(&features, now);
+        assert_eq!(allocator.sleeves()[0].state.positions.len(), 3);
+    }
+
+    /// How many lots are held after a buy signal on a book bid at each of `bids` in turn.
+    fn lots_after(entries: EntriesConfig, bids: &[f64]) -> Vec<usize> {
+        lots_held(entries, bids).iter().map(Vec::len).collect()
+    }
+
+    /// The lots held after a buy signal on a book bid at each of `bids` in turn.
+    fn lots_held(entries: EntriesConfig, bids: &[f64]) -> Vec<Vec<Lot>> {
+        let now = Utc::now();
+        let members: Vec<(Box<dyn Strategy>, f64)> = vec![(Box::new(Buyer), 1.0)];
+        let mut allocator = Allocator::new(1000.0, "BTC/USDT", members, config(None), now);
+        allocator.set_entries(entries);
+        bids.iter()
+            .map(|&bid| {
+                let features = Features {
+                    bid,
+                    ask: bid + 0.01,
+                    ..Features::default()
+                };
+                allocator.on_features(&features, now);
+                allocator.sleeves()[0].state.positions.clone()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_pyramid_adds_in_profit() {
+        let entries = EntriesConfig {
+            policy: AddPolicy::Pyramid,
+            min_profit: 0.001,
+            max_lots: Some(3),
+            ..EntriesConfig::default()
+        };
+        // 0.1% over the mean entry of 100, then of 100.055, then capped
+        assert_eq!(
+            lots_after(
+                entries,
+                &[100.0, 100.05, 99.0, 100.11, 100.15, 100.21, 100.5]
+            ),
+            [1, 1, 1, 2, 2, 3, 3]
+        );
+        assert_eq!(
+            lots_after(EntriesConfig::default(), &[100.0, 99.5, 100.0]),
+            [1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn test_dca_adds_further_against() {
+        let entries = EntriesConfig {
+            policy: AddPolicy::Dca,
+            dca_step: 0.005,
+            step_growth: 2.0,
+            ..EntriesConfig::default()
+        };
+        // 0.5% under the first entry, then 1% under the second
+        assert_eq!(
+            lots_after(entries, &[100.0, 99.8, 100.5, 99.49, 99.0, 98.49]),
+            [1, 1, 1, 2, 2, 3]
+        );
+
+        // Each add is half the size of the last
+        let held = lots_held(entries, &[100.0, 99.49, 98.49]);
+        let sizes: Vec<f64> = held[2].iter().map(|lot| lot.size).collect();
+        assert_eq!(sizes, [TRADE_SIZE, TRADE_SIZE / 2.0, TRADE_SIZE / 4.0]);
+    }
+
+    #[test]
+    fn test_orders_report_the_size_filled() {
+        let now = Utc::now();
+        let members: Vec<(Box<dyn Strategy>, f64)> = vec![(Box::new(Buyer), 1.0)];
+        let mut allocator = Allocator::new(1000.0, "BTC/USDT", members, config(None), now);
+        allocator.set_entries(EntriesConfig {
+            policy: AddPolicy::Dca,
+            dca_step: 0.005,
+            ..EntriesConfig::default()
+        });
+        let mut sizes = Vec::new();
+        for bid in [100.0, 99.49, 98.49] {
+            let features = Features {
+                bid,
+                ask: bid + 0.01,
+                ..Features::default()
+            };
+            let events = allocator.on_features(&features, now);
+            let [_, Event::Order { size, .. }, Event::Fill { fill, .. }] = events.as_slice() else {
+                panic!("expected an order and its fill");
+            };
+            assert_eq!(*size, fill.size);
+            sizes.push(*size);
+        }
+        assert_eq!(sizes, [TRADE_SIZE, TRADE_SIZE / 2.0, TRADE_SIZE / 4.0]);
+
+        // A sell is the size of the newest lot it closes
+        allocator.sleeves_mut()[0].strategy = Box::new(Seller);
+        let features = Features {
+            bid: 98.5,
+            ask: 98.51,
+            ..Features::default()
+        };
+        let events = allocator.on_features(&features, now);
+        let [_, Event::Order { size, .. }, Event::Fill { fill, .. }] = events.as_slice() else {
+            panic!("expected an order and its fill");
+        };
+        assert_eq!((*size, fill.size), (TRADE_SIZE / 4.0, TRADE_SIZE / 4.0));
+    }
+
+    #[test]
+    fn test_entries_and_volatility_share_a_cap() {
+        let now = Utc::now();
+        let members: Vec<(Box<dyn Strategy>, f64)> = vec![(Box::new(Buyer), 1.0)];
+        let mut allocator = Allocator::new(1000.0, "BTC/USDT", members, config(None), now);
+        allocator.set_entries(EntriesConfig {
+            max_lots: Some(3),
+            ..EntriesConfig::default()
+        });
+        let features = Features {
+            bid: 100.0,
+            ask: 100.01,
+            ..Features::default()
+        };
+
+        // The lower of the two caps holds, whichever it is
+        allocator.limit_lots(Some(2));
+        for _ in 0..3 {
+            allocator.on_features(&features, now);
+        }
+        assert_eq!(allocator.sleeves()[0].state.positions.len(), 2);
+        allocator.limit_lots(Some(5));
+        for _ in 0..3 {
+            allocator.on_features(&features, now);
+        }
+        assert_eq!(allocator.sleeves()[0].state.positions.len(), 3);
+    }
+
+    #[test]
+    fn test_crossing_takes_the_far_touch() {
+        let now = Utc::now();
+        let members: Vec<(Box<dyn Strategy>, f64)> = vec![(Box::new(Buyer), 1.0)];
+        let mut allocator = Allocator::new(1000.0, "BTC/USDT", members, config(None), now);
+        let features = Features {
+            bid: 100.0,
+            ask: 100.01,
+            mid_price: 100.005,
+            ..Features::default()
+        };
+
+        allocator.set_crossing(Crossing {
+            buys: true,
+            sells: false,
+        });
+        let events = allocator.on_features(&features, now);
+        let [_, Event::Order { price, .. }, Event::Fill {
+            execution, fill, ..
+        }] = events.as_slice()
+        else {
+            panic!("expected a signal, order and fill");
+        };
+        assert_eq!((*price, fill.price), (100.01, 100.01));
+        assert_eq!(execution.order_type, OrderType::Market);
+    }
+
+    #[test]
+    fn test_stop_loss_judged_at_mark() {
+        let now = Utc::now();
+        let mut allocator = allocator(&[1.0], config(None), now);
+        allocator.sleeves_mut()[0]
+            .state
+            .positions
+            .push(Lot::at(100.0));
+        let features = Features {
+            bid: 99.9,
+            ask: 100.0,
+            ..Features::default()
+        };
+        assert!(allocator.on_features(&features, now).is_empty());
+
+        // The bid hasn't moved, but the mark the position settles at has fallen through the stop
+        allocator.set_mark(Some(95.0));
+        let events = allocator.on_features(&features, now);
+        let [Event::Fill { fill, .. }] = events.as_slice() else {
+            panic!("expected the stop loss's fill");
+        };
+        assert_eq!((fill.side, fill.price), (Side::Sell, 99.9));
+    }
+
+    #[test]
+    fn test_chaos_rejects_and_rests_orders() {
+        let now = Utc::now();
+        let members: Vec<(Box<dyn Strategy>, f64)> = vec![(Box::new(Buyer), 1.0)];
+        let mut allocator = Allocator::new(1000.0, "BTC/USDT", members, config(None), now);
+        let features = Features {
+            bid: 100.0,
+            ask: 100.01,
+            ..Features::default()
+        };
+        let chaos = |reject_rate, partial_rate| {
+            let config = ChaosConfig {
+                reject_rate,
+                partial_rate,
+                ..ChaosConfig::default()
+            };
+            OrderChaos::new(&config, 0)
+        };
+
+        allocator.set_chaos(chaos(1.0, 0.0));
+        let kinds: Vec<_> = allocator
+            .on_features(&features, now)
+            .iter()
+            .map(Event::kind)
+            .collect();
+        assert_eq!(kinds, ["signal", "order"]);
+        assert!(allocator.sleeves()[0].state.positions.is_empty());
+        assert_eq!(allocator.sleeves()[0].state.cash, 1000.0);
+
+        // Part of the lot fills at once, the rest at the order's price on the next update, as lots
+        // of their own, nothing else placed
+        allocator.set_chaos(chaos(0.0, 1.0));
+        let events = allocator.on_features(&features, now);
+        let [_, Event::Order { size, .. }, Event::Fill { fill: part, .. }] = events.as_slice()
+        else {
+            panic!("expected the order and its partial fill");
+        };
+        assert_eq!(*size, TRADE_SIZE);
+        assert!(part.size > 0.0 && part.size < TRADE_SIZE);
+        assert_eq!(allocator.sleeves()[0].state.position_size(), part.size);
+        let moved = Features {
+            bid: 100.5,
+            ask: 100.51,
+            ..features
+        };
+        let events = allocator.on_features(&moved, now);
+        let [Event::Fill { fill: rest, .. }] = events.as_slice() else {
+            panic!("expected only the resting fill");
+        };
+        assert_eq!((rest.price, rest.size), (100.0, TRADE_SIZE - part.size));
+        assert_eq!(allocator.sleeves()[0].state.positions.len(), 2);
+        assert!((allocator.sleeves()[0].state.position_size() - TRADE_SIZE).abs() < 1e-12);
+
+        // Sells close whole lots however the chaos falls
+        allocator.sleeves_mut()[0].strategy = Box::new(Seller);
+        let events = allocator.on_features(&features, now);
+        let [_, _, Event::Fill { fill, .. }] = events.as_slice() else {
+            panic!("expected the sell's fill");
+        };
+        assert_eq!(fill.size, TRADE_SIZE - part.size);
+        assert!(allocator.sleeves()[0].queue().is_none());
+    }
+
+    #[test]
+    fn test_rest_of_a_partial_fill_is_dropped_without_entries() {
+        let now = Utc::now();
+        let features = Features {
+            bid: 100.0,
+            ask: 100.01,
+            ..Features::default()
+        };
+        let partly_filled = || {
+            let members: Vec<(Box<dyn Strategy>, f64)> = vec![(Box::new(Buyer), 1.0)];
+            let mut allocator = Allocator::new(1000.0, "BTC/USDT", members, config(None), now);
+            allocator.set_chaos(OrderChaos::new(
+                &ChaosConfig {
+                    reject_rate: 0.0,
+                    partial_rate: 1.0,
+                    ..ChaosConfig::default()
+                },
+                0,
+            ));
+            allocator.on_features(&features, now);
+            allocator
+        };
+
+        let mut blocked = partly_filled();
+        blocked.block_entries(true);
+        assert!(blocked.on_features(&features, now).is_empty());
+        assert_eq!(blocked.sleeves()[0].state.positions.len(), 1);
+
+        // A fresh order is placed rather than the rest of the flattened one filling
+        let mut flattened = partly_filled();
+        flattened.flatten(&features, now);
+        let kinds: Vec<_> = flattened
+            .on_features(&features, now)
+            .iter()
+            .map(Event::kind)
+            .collect();
+        assert_eq!(kinds, ["signal", "order", "fill"]);
+    }
+
+    #[test]
+    fn test_resting_order_follows_its_queue() {
+        let now = Utc::now();
+        let members: Vec<(Box<dyn Strategy>, f64)> = vec![(Box::new(Buyer), 1.0)];
+        let mut allocator = Allocator::new(1000.0, "BTC/USDT", members, config(None), now);
+        let features = Features {
+            bid: 100.0,
+            ask: 100.01,
+            ..Features::default()
+        };
+        let book = OrderBook {
+            last_update_time: now,
+            bids: OrderBookSide::new(Side::Buy, [(100.0, 3.0), (99.0, 1.0)]),
+            asks: OrderBookSide::new(Side::Sell, [(100.01, 1.0)]),
+        };
+        allocator.set_chaos(OrderChaos::new(
+            &ChaosConfig {
+                partial_rate: 1.0,
+                ..ChaosConfig::default()
+            },
+            0,
+        ));
+
+        allocator.on_features(&features, now);
+        assert_eq!(allocator.sleeves()[0].queue(), None);
+        allocator.on_book(&book);
+        allocator.on_trade(Side::Sell, 100.0, 1.0);
+        assert_eq!(
+            allocator.sleeves()[0].queue().map(QueuePosition::ahead),
+            Some(2.0)
+        );
+
+        let events = allocator.on_features(&features, now);
+        let [Event::Fill { execution, .. }] = events.as_slice() else {
+            panic!("expected only the resting fill");
+        };
+        assert_eq!(execution.queue_ahead, Some(2.0));
+        assert_eq!(allocator.sleeves()[0].queue(), None);
+    }
+
+    #[test]
+    fn test_refused_orders_place_without_filling() {
+        let now = Utc::now();
+        let members: Vec<(Box<dyn Strategy>, f64)> = vec![(Box::new(Buyer), 1.0)];
+        // Not enough for a single lot
+        let mut allocator = Allocator::new(0.05, "BTC/USDT", members, config(None), now);
+        let features = Features {
+            bid: 100.0,
+            ask: 100.01,
+            ..Features::default()
+        };
+        let kinds: Vec<_> = allocator
+            .on_features(&features, now)
+            .iter()
+            .map(Event::kind)
+            .collect();
+        assert_eq!(kinds, ["signal", "order"]);
+        assert!(allocator.sleeves()[0].state.positions.is_empty());
+        assert_eq!(allocator.sleeves()[0].state.cash, 0.05);
+    }
+
+    #[test]
+    fn test_flatten_sells_everything() {
+        let now = Utc::now();
+        let members: Vec<(Box<dyn Strategy>, f64)> =
+            vec![(Box::new(Buyer), 1.0), (Box::new(Idle), 1.0)];
+        let mut allocator = Allocator::new(1000.0, "BTC/USDT", members, config(None), now);
+        let features = Features {
+            bid: 100.0,
+            ask: 100.01,
+            mid_price: 100.005,
+            ..Features::default()
+        };
+        allocator.on_features(&features, now);
+        allocator.on_features(&features, now);
+        assert_eq!(allocator.sleeves()[0].state.positions.len(), 2);
+
+        let events = allocator.flatten(&features, now);
+        assert_eq!(events.len(), 2);
+        assert!(allocator
+            .sleeves()
+            .iter()
+            .all(|sleeve| sleeve.state.positions.is_empty()));
+        let Event::Fill {
+            execution, fill, ..
+        } = &events[0]
+        else {
+            panic!("expected a fill");
+        };
+        assert_eq!((fill.side, fill.price), (Side::Sell, 100.0));
+        assert_eq!(execution.order_type, OrderType::Market);
+    }
+}
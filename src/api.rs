@@ -0,0 +1,567 @@
+//! Authenticated HTTP API for managing the bot while it runs. Every request needs a token from
+//! the [auth](crate::auth) config, and those changing anything one with the `control` scope, but
+//! for the [probes](crate::probe), which orchestrators call without one.
+//!
+//! - `GET /status`: whether trading is paused, the latest equity and the risk params
+//! - `GET /positions`: open positions, marked at the latest bid
+//! - `POST /pause`, `POST /resume`: stop and restart trading
+//! - `POST /flatten`: sell every open position on the next update and pause
+//! - `GET /params`, `PUT /params`: read or update the live [`RiskParams`], for example
+//!   `{"take_profit": 0.015}`
+//! - `GET /metrics`: [latency](crate::latency) histograms in the Prometheus text format
+//! - `GET /healthz`: `200` while the trading loop is running, `503` once it has stalled
+//! - `GET /readyz`: `200` while ready to trade, `503` otherwise, with the result of every check,
+//!   as in `{"ready": false, "live": true, "feed_connected": true, "book_fresh": false, ...}`
+//! - `GET /stream`: a WebSocket pushing signals, orders, fills and equity marks as they happen,
+//!   optionally only some kinds, as in `/stream?kinds=fill,equity`
+//!
+//! Each streamed message is the event's JSON with a `seq` number counting up by one. A client
+//! too slow to keep up is sent `{"type": "lagged", "skipped": 12}` instead of the events it
+//! missed, and should fetch `/status` and `/positions` again to catch up.
+//!
+//! ```toml
+//! [api]
+//! address = "127.0.0.1:8081"
+//! ```
+
+use axum::extract::ws::Message;
+use axum::extract::ws::WebSocket;
+use axum::extract::Query;
+use axum::extract::State;
+use axum::extract::WebSocketUpgrade;
+use axum::http::header::CONTENT_TYPE;
+use axum::http::HeaderName;
+use axum::http::StatusCode;
+use axum::middleware;
+use axum::response::Response;
+use axum::routing::get;
+use axum::routing::post;
+use axum::Json;
+use axum::Router;
+use serde::Deserialize;
+use serde::Serialize;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::Mutex;
+use tokio::net::TcpListener;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::Instant;
+use tracing::info;
+use tracing::warn;
+
+use crate::auth;
+use crate::auth::AuthError;
+use crate::auth::Authenticator;
+use crate::config::TlsConfig;
+use crate::control::Control;
+use crate::event::Event;
+use crate::latency::Latency;
+use crate::live::LiveState;
+use crate::live::Position;
+use crate::probe::Probes;
+use crate::probe::Readiness;
+use crate::RiskParams;
+
+/// Kinds of event pushed to stream clients.
+pub const STREAMED_KINDS: [&str; 4] = ["signal", "order", "fill", "equity"];
+
+/// Messages buffered per stream client before a slow reader starts skipping them.
+const CLIENT_BUFFER: usize = 1024;
+
+/// A streamed event, serialized once for every client.
+#[derive(Debug)]
+struct Delta {
+    kind: &'static str,
+    json: Arc<str>,
+}
+
+#[derive(Debug, Serialize)]
+struct Sequenced<'a> {
+    seq: u64,
+    #[serde(flatten)]
+    event: &'a Event,
+}
+
+#[derive(Debug)]
+struct Feed {
+    live: LiveState,
+    /// Sequence number of the latest streamed event.
+    seq: u64,
+}
+
+#[derive(Debug)]
+struct Shared {
+    feed: Mutex<Feed>,
+    deltas: broadcast::Sender<Arc<Delta>>,
+    control: Control,
+    latency: Latency,
+    probes: Probes,
+}
+
+#[derive(Debug, Serialize)]
+struct Status {
+    paused: bool,
+    symbol: Option<&'static str>,
+    equity: Option<f64>,
+    open_positions: usize,
+    risk: RiskParams,
+}
+
+/// Changes to the live risk params; fields left out keep their value.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ParamsUpdate {
+    take_profit: Option<f64>,
+    stop_loss: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamQuery {
+    /// Comma separated kinds to stream, all of [`STREAMED_KINDS`] if not given.
+    kinds: Option<String>,
+}
+
+/// Serves the control API and keeps its view of the bot up to date with the events it is given.
+#[derive(Debug)]
+pub struct ApiServer {
+    local_addr: SocketAddr,
+    shared: Arc<Shared>,
+}
+
+impl ApiServer {
+    /// Listen on `address` and start serving in the background, over TLS if `tls` is set,
+    /// letting through the requests `auth` allows, managing the bot through `control`, reporting
+    /// the histograms of `latency` and answering liveness and readiness from `probes`.
+    pub async fn bind(
+        address: &str,
+        auth: Authenticator,
+        tls: Option<&TlsConfig>,
+        control: Control,
+        latency: Latency,
+        probes: Probes,
+    ) -> Result<Self, AuthError> {
+        let listener = TcpListener::bind(address).await?;
+        let local_addr = listener.local_addr()?;
+        let (deltas, _) = broadcast::channel(CLIENT_BUFFER);
+        let shared = Arc::new(Shared {
+            feed: Mutex::new(Feed {
+                live: LiveState::default(),
+                seq: 0,
+            }),
+            deltas,
+            control,
+            latency,
+            probes,
+        });
+
+        auth::serve(listener, router(shared.clone(), auth), tls, "Control API")?;
+        let scheme = if tls.is_some() { "https" } else { "http" };
+        info!("Serving control API on {}://{}", scheme, local_addr);
+
+        Ok(Self { local_addr, shared })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Count `events` toward the state the API reports and push them to every stream client.
+    pub fn publish(&self, events: &[Event]) {
+        let mut feed = self.shared.feed.lock().expect("live state lock poisoned");
+        for event in events {
+            feed.live.record(event);
+            if !STREAMED_KINDS.contains(&event.kind()) {
+                continue;
+            }
+            // Count events even with nobody listening, so numbers stay stable between clients
+            feed.seq += 1;
+            if self.shared.deltas.receiver_count() > 0 {
+                let event = Sequenced {
+                    seq: feed.seq,
+                    event,
+                };
+                let json = serde_json::to_string(&event).expect("events serialize to JSON");
+                let delta = Delta {
+                    kind: event.event.kind(),
+                    json: json.into(),
+                };
+                // Nobody left listening is not an error
+                let _ = self.shared.deltas.send(Arc::new(delta));
+            }
+        }
+    }
+}
+
+fn router(shared: Arc<Shared>, auth: Authenticator) -> Router {
+    Router::new()
+        .route("/status", get(status))
+        .route("/positions", get(positions))
+        .route("/pause", post(pause))
+        .route("/resume", post(resume))
+        .route("/flatten", post(flatten))
+        .route("/params", get(params).put(update_params))
+        .route("/metrics", get(metrics))
+        .route("/stream", get(stream))
+        .layer(middleware::from_fn_with_state(
+            Arc::new(auth),
+            auth::require,
+        ))
+        // Probes are added after the auth layer, so it doesn't apply to them
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .with_state(shared)
+}
+
+async fn status(State(shared): State<Arc<Shared>>) -> Json<Status> {
+    let feed = shared.feed.lock().expect("live state lock poisoned");
+    let live = &feed.live;
+    Json(Status {
+        paused: shared.control.is_paused(),
+        symbol: live.symbol(),
+        equity: live.equity().last(),
+        open_positions: live.positions().len(),
+        risk: shared.control.risk(),
+    })
+}
+
+async fn positions(State(shared): State<Arc<Shared>>) -> Json<Vec<Position>> {
+    Json(
+        shared
+            .feed
+            .lock()
+            .expect("live state lock poisoned")
+            .live
+            .positions(),
+    )
+}
+
+async fn pause(State(shared): State<Arc<Shared>>) -> StatusCode {
+    shared.control.pause();
+    StatusCode::NO_CONTENT
+}
+
+async fn resume(State(shared): State<Arc<Shared>>) -> StatusCode {
+    shared.control.resume();
+    StatusCode::NO_CONTENT
+}
+
+async fn flatten(State(shared): State<Arc<Shared>>) -> StatusCode {
+    shared.control.flatten();
+    StatusCode::ACCEPTED
+}
+
+async fn params(State(shared): State<Arc<Shared>>) -> Json<RiskParams> {
+    Json(shared.control.risk())
+}
+
+async fn update_params(
+    State(shared): State<Arc<Shared>>,
+    Json(update): Json<ParamsUpdate>,
+) -> Result<Json<RiskParams>, (StatusCode, String)> {
+    shared
+        .control
+        .update_risk(update.take_profit, update.stop_loss)
+        .map(Json)
+        .map_err(|error| (StatusCode::UNPROCESSABLE_ENTITY, error.to_string()))
+}
+
+async fn metrics(State(shared): State<Arc<Shared>>) -> ([(HeaderName, &'static str); 1], String) {
+    (
+        [(CONTENT_TYPE, "text/plain; version=0.0.4")],
+        shared.latency.prometheus(),
+    )
+}
+
+async fn healthz(State(shared): State<Arc<Shared>>) -> (StatusCode, &'static str) {
+    if shared.probes.live(Instant::now()) {
+        (StatusCode::OK, "ok\n")
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "trading loop stalled\n")
+    }
+}
+
+async fn readyz(State(shared): State<Arc<Shared>>) -> (StatusCode, Json<Readiness>) {
+    let readiness = shared.probes.readiness(Instant::now());
+    let status = if readiness.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(readiness))
+}
+
+async fn stream(
+    upgrade: WebSocketUpgrade,
+    Query(query): Query<StreamQuery>,
+    State(shared): State<Arc<Shared>>,
+) -> Result<Response, (StatusCode, String)> {
+    let kinds: Vec<&'static str> = match &query.kinds {
+        Some(kinds) => kinds
+            .split(',')
+            .map(|kind| {
+                STREAMED_KINDS
+                    .into_iter()
+                    .find(|streamed| *streamed == kind.trim())
+                    .ok_or_else(|| {
+                        let message = format!(
+                            "unknown event kind {kind:?}, expected one of {}",
+                            STREAMED_KINDS.join(", ")
+                        );
+                        (StatusCode::BAD_REQUEST, message)
+                    })
+            })
+            .collect::<Result<_, _>>()?,
+        None => STREAMED_KINDS.to_vec(),
+    };
+    let receiver = shared.deltas.subscribe();
+    Ok(upgrade.on_upgrade(move |socket| push_deltas(socket, receiver, kinds)))
+}
+
+async fn push_deltas(
+    mut socket: WebSocket,
+    mut deltas: broadcast::Receiver<Arc<Delta>>,
+    kinds: Vec<&'static str>,
+) {
+    loop {
+        let message = match deltas.recv().await {
+            Ok(delta) if kinds.contains(&delta.kind) => delta.json.to_string(),
+            Ok(_) => continue,
+            Err(RecvError::Lagged(skipped)) => {
+                warn!("Stream client fell behind, skipped {} events", skipped);
+                format!(r#"{{"type":"lagged","skipped":{skipped}}}"#)
+            }
+            Err(RecvError::Closed) => return,
+        };
+        if socket.send(Message::Text(message)).await.is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use barter_integration::model::Side;
+    use chrono::Utc;
+    use tokio::io::AsyncReadExt;
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpStream;
+
+    use super::*;
+    use crate::auth::Scope;
+    use crate::config::AuthConfig;
+    use crate::config::ProbeConfig;
+    use crate::config::TokenConfig;
+    use crate::features::Features;
+    use crate::latency::Stage;
+    use crate::secrets::Secret;
+
+    /// Accepts `secret` for control and `watch` for reading.
+    fn auth() -> Authenticator {
+        let token = |secret: &str, scope| TokenConfig {
+            secret: Secret::Value(secret.to_owned()),
+            scope,
+        };
+        Authenticator::new(&AuthConfig {
+            tokens: vec![token("secret", Scope::Control), token("watch", Scope::Read)],
+            tls: None,
+        })
+        .unwrap()
+    }
+
+    async fn request(
+        address: SocketAddr,
+        method: &str,
+        path: &str,
+        token: &str,
+        body: &str,
+    ) -> String {
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        let request = format!(
+            "{method} {path} HTTP/1.1\r\nHost: localhost\r\nAuthorization: Bearer {token}\r\n\
+             Content-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    fn probes() -> Probes {
+        Probes::new(&ProbeConfig::default(), Instant::now())
+    }
+
+    /// Open a WebSocket at `path`, returning the status line and the connection.
+    async fn connect(address: SocketAddr, path: &str) -> (String, TcpStream) {
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        let request = format!(
+            "GET {path} HTTP/1.1\r\nHost: localhost\r\nAuthorization: Bearer secret\r\n\
+             Upgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Version: 13\r\n\
+             Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n"
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut head = Vec::new();
+        while !head.ends_with(b"\r\n\r\n") {
+            head.push(stream.read_u8().await.unwrap());
+        }
+        let head = String::from_utf8(head).unwrap();
+        (head.lines().next().unwrap().to_owned(), stream)
+    }
+
+    /// Read one unfragmented text frame, as the server sends them.
+    async fn read_text(stream: &mut TcpStream) -> serde_json::Value {
+        let mut header = [0; 2];
+        stream.read_exact(&mut header).await.unwrap();
+        assert_eq!(header[0], 0x81);
+        let length = match header[1] & 0x7f {
+            126 => stream.read_u16().await.unwrap() as usize,
+            length => length as usize,
+        };
+        let mut payload = vec![0; length];
+        stream.read_exact(&mut payload).await.unwrap();
+        serde_json::from_slice(&payload).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_stream_deltas() {
+        let api = ApiServer::bind(
+            "127.0.0.1:0",
+            auth(),
+            None,
+            Control::default(),
+            Latency::default(),
+            probes(),
+        )
+        .await
+        .unwrap();
+        let (status, _) = connect(api.local_addr(), "/stream?kinds=fill,trades").await;
+        assert!(status.starts_with("HTTP/1.1 400"));
+        let (status, mut stream) = connect(api.local_addr(), "/stream?kinds=signal,equity").await;
+        assert!(status.starts_with("HTTP/1.1 101"));
+
+        let now = Utc::now();
+        api.publish(&[
+            Event::Features {
+                time: now,
+                symbol: "BTC/USDT",
+                features: Features::default(),
+                book_time: None,
+            },
+            Event::Order {
+                time: now,
+                symbol: "BTC/USDT",
+                strategy: "imbalance".to_owned(),
+                side: Side::Buy,
+                price: 100.0,
+                size: 0.5,
+                book_time: None,
+            },
+            Event::Equity {
+                time: now,
+                symbol: "BTC/USDT",
+                portfolio_value: 1000.0,
+            },
+        ]);
+
+        // Features aren't streamed and orders weren't asked for, but still count toward `seq`
+        let delta = read_text(&mut stream).await;
+        assert_eq!(delta["type"], "equity");
+        assert_eq!(delta["seq"], 2);
+        assert_eq!(delta["portfolio_value"], 1000.0);
+    }
+
+    #[tokio::test]
+    async fn test_authorized_control() {
+        let control = Control::default();
+        let api = ApiServer::bind(
+            "127.0.0.1:0",
+            auth(),
+            None,
+            control.clone(),
+            Latency::default(),
+            probes(),
+        )
+        .await
+        .unwrap();
+        let address = api.local_addr();
+
+        let denied = request(address, "POST", "/pause", "wrong", "").await;
+        assert!(denied.starts_with("HTTP/1.1 401"));
+        let forbidden = request(address, "POST", "/pause", "watch", "").await;
+        assert!(forbidden.starts_with("HTTP/1.1 403"));
+        assert!(!control.is_paused());
+
+        let paused = request(address, "POST", "/pause", "secret", "").await;
+        assert!(paused.starts_with("HTTP/1.1 204"));
+        let status = request(address, "GET", "/status", "watch", "").await;
+        assert!(status.contains(r#""paused":true"#));
+        assert!(status.contains(r#""take_profit":0.01"#));
+
+        let updated = request(address, "PUT", "/params", "secret", r#"{"stop_loss":0.03}"#).await;
+        assert!(updated.ends_with(r#"{"take_profit":0.01,"stop_loss":0.03}"#));
+        assert_eq!(control.risk().stop_loss, 0.03);
+        let invalid = request(address, "PUT", "/params", "secret", r#"{"take_profit":2}"#).await;
+        assert!(invalid.starts_with("HTTP/1.1 422"));
+        assert_eq!(control.risk().take_profit, 0.01);
+
+        let flattened = request(address, "POST", "/flatten", "secret", "").await;
+        assert!(flattened.starts_with("HTTP/1.1 202"));
+        assert!(control.take_flatten());
+        let positions = request(address, "GET", "/positions", "secret", "").await;
+        assert!(positions.ends_with("[]"));
+    }
+
+    #[tokio::test]
+    async fn test_latency_metrics() {
+        let latency = Latency::default();
+        let api = ApiServer::bind(
+            "127.0.0.1:0",
+            auth(),
+            None,
+            Control::default(),
+            latency.clone(),
+            probes(),
+        )
+        .await
+        .unwrap();
+        let now = Utc::now();
+        latency.record(Stage::Decision, now, now);
+
+        let metrics = request(api.local_addr(), "GET", "/metrics", "watch", "").await;
+        assert!(metrics.starts_with("HTTP/1.1 200"));
+        assert!(metrics.contains("content-type: text/plain; version=0.0.4"));
+        assert!(metrics.contains("fit_latency_seconds_count{stage=\"decision\"} 1\n"));
+    }
+
+    #[tokio::test]
+    async fn test_probes_need_no_token() {
+        let probes = probes();
+        let api = ApiServer::bind(
+            "127.0.0.1:0",
+            auth(),
+            None,
+            Control::default(),
+            Latency::default(),
+            probes.clone(),
+        )
+        .await
+        .unwrap();
+
+        let health = request(api.local_addr(), "GET", "/healthz", "", "").await;
+        assert!(health.starts_with("HTTP/1.1 200"));
+        assert!(health.ends_with("ok\n"));
+        let ready = request(api.local_addr(), "GET", "/readyz", "", "").await;
+        assert!(ready.starts_with("HTTP/1.1 503"));
+        assert!(ready.contains(r#""feed_connected":false"#));
+
+        probes.on_book(Instant::now());
+        let ready = request(api.local_addr(), "GET", "/readyz", "", "").await;
+        assert!(ready.starts_with("HTTP/1.1 200"));
+        assert!(ready.contains(r#""ready":true"#));
+
+        // Everything else still needs a token
+        let status = request(api.local_addr(), "GET", "/status", "", "").await;
+        assert!(status.starts_with("HTTP/1.1 401"));
+    }
+}
@@ -0,0 +1,294 @@
+//! Cross-exchange arbitrage detection, [`[arbitrage]`](crate::config::ArbitrageConfig). The
+//! latest quote of every instrument is kept per venue, and each book is compared with the other
+//! venues' quotes of the same instrument: when buying at one venue's ask and selling at another's
+//! bid clears both venues' taker fees by at least `min_edge`, an alert announces the opportunity,
+//! and another once it has closed.
+//!
+//! Quotes are compared by when they were received, so one from a venue that has gone quiet for
+//! more than `max_age_ms` is never compared against. With a single venue subscribed there is
+//! nothing to compare, so nothing is ever raised.
+//!
+//! Opportunities are only detected, never traded: trading both legs needs an account at each
+//! venue, which neither the paper account nor the order gateway keeps.
+
+use chrono::DateTime;
+use chrono::TimeDelta;
+use chrono::Utc;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use tracing::info;
+
+use crate::config::ArbitrageConfig;
+use crate::event::Alert;
+use crate::event::Severity;
+use crate::features::Features;
+
+/// What every arbitrage alert's [condition](Alert::condition) starts with, followed by the
+/// instrument and the venues bought and sold at.
+pub const CONDITION: &str = "arbitrage";
+
+/// Buying an instrument on one venue and selling it on another for more.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Opportunity {
+    pub instrument: String,
+    /// The venue bought at, at its ask.
+    pub buy: String,
+    pub ask: f64,
+    /// The venue sold at, at its bid.
+    pub sell: String,
+    pub bid: f64,
+    /// What the round trip makes after both venues' fees, as a fraction of the ask.
+    pub edge: f64,
+}
+
+impl Opportunity {
+    fn condition(&self) -> String {
+        format!("{CONDITION}:{}:{}:{}", self.instrument, self.buy, self.sell)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Quote {
+    bid: f64,
+    ask: f64,
+    received: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+pub struct ArbitrageDetector {
+    config: ArbitrageConfig,
+    /// The latest quote of each instrument, by venue.
+    quotes: HashMap<String, HashMap<String, Quote>>,
+    /// The conditions of the opportunities open.
+    open: HashSet<String>,
+}
+
+impl ArbitrageDetector {
+    pub fn new(config: &ArbitrageConfig) -> Self {
+        Self {
+            config: config.clone(),
+            quotes: HashMap::new(),
+            open: HashSet::new(),
+        }
+    }
+
+    /// The taker fee at `exchange`, as a fraction of the notional.
+    fn fee(&self, exchange: &str) -> f64 {
+        self.config
+            .fees
+            .get(exchange)
+            .copied()
+            .unwrap_or(self.config.default_fee)
+    }
+
+    /// Buying at `ask` on `buy` and selling at `bid` on `sell`, if it clears the fees by
+    /// `min_edge`.
+    fn opportunity(
+        &self,
+        instrument: &str,
+        (buy, ask): (&str, f64),
+        (sell, bid): (&str, f64),
+    ) -> Option<Opportunity> {
+        let cost = ask * (1.0 + self.fee(buy));
+        let proceeds = bid * (1.0 - self.fee(sell));
+        let edge = (proceeds - cost) / ask;
+        (edge >= self.config.min_edge).then(|| Opportunity {
+            instrument: instrument.to_owned(),
+            buy: buy.to_owned(),
+            ask,
+            sell: sell.to_owned(),
+            bid,
+            edge,
+        })
+    }
+
+    /// Record the top of `exchange`'s `instrument` book in `features`, received at `received`,
+    /// returning the opportunities it makes against the other venues' fresh quotes.
+    pub fn opportunities(
+        &mut self,
+        exchange: &str,
+        instrument: &str,
+        features: &Features,
+        received: DateTime<Utc>,
+    ) -> Vec<Opportunity> {
+        let quote = Quote {
+            bid: features.bid,
+            ask: features.ask,
+            received,
+        };
+        self.quotes
+            .entry(instrument.to_owned())
+            .or_default()
+            .insert(exchange.to_owned(), quote);
+
+        let max_age = TimeDelta::milliseconds(self.config.max_age_ms as i64);
+        let mut opportunities = Vec::new();
+        for (other, theirs) in &self.quotes[instrument] {
+            if other == exchange || received - theirs.received > max_age {
+                continue;
+            }
+            opportunities.extend(self.opportunity(
+                instrument,
+                (exchange, quote.ask),
+                (other, theirs.bid),
+            ));
+            opportunities.extend(self.opportunity(
+                instrument,
+                (other, theirs.ask),
+                (exchange, quote.bid),
+            ));
+        }
+        opportunities
+    }
+
+    /// Compare the book like [`opportunities`](Self::opportunities), returning the alerts to
+    /// raise for opportunities between `exchange` and another venue that just opened or closed.
+    pub fn update(
+        &mut self,
+        exchange: &str,
+        instrument: &str,
+        features: &Features,
+        received: DateTime<Utc>,
+    ) -> Vec<Alert> {
+        let opportunities = self.opportunities(exchange, instrument, features, received);
+        let mut alerts = Vec::new();
+        let open: HashSet<String> = opportunities.iter().map(Opportunity::condition).collect();
+        // Only pairs with `exchange` in them were compared again
+        let prefix = format!("{CONDITION}:{instrument}:");
+        let closed: Vec<String> = self
+            .open
+            .iter()
+            .filter(|condition| !open.contains(*condition))
+            .filter(|condition| {
+                condition
+                    .strip_prefix(&prefix)
+                    .and_then(|venues| venues.split_once(':'))
+                    .is_some_and(|(buy, sell)| buy == exchange || sell == exchange)
+            })
+            .cloned()
+            .collect();
+        for condition in closed {
+            self.open.remove(&condition);
+            let message = format!("Arbitrage closed: {condition}");
+            info!("{}", message);
+            alerts.push(Alert {
+                severity: Severity::Info,
+                message,
+                condition: Some(condition),
+                resolved: true,
+            });
+        }
+        for opportunity in opportunities {
+            let condition = opportunity.condition();
+            if !self.open.insert(condition.clone()) {
+                continue;
+            }
+            let message = format!(
+                "Arbitrage on {}: buy on {} at {}, sell on {} at {}, {:.3}% after fees",
+                opportunity.instrument,
+                opportunity.buy,
+                opportunity.ask,
+                opportunity.sell,
+                opportunity.bid,
+                opportunity.edge * 100.0
+            );
+            info!("{}", message);
+            alerts.push(Alert {
+                severity: Severity::Info,
+                message,
+                condition: Some(condition),
+                resolved: false,
+            });
+        }
+        alerts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detector() -> ArbitrageDetector {
+        ArbitrageDetector::new(&ArbitrageConfig {
+            min_edge: 0.001,
+            default_fee: 0.0005,
+            fees: HashMap::from([("binance".to_owned(), 0.001)]),
+            max_age_ms: 1000,
+        })
+    }
+
+    fn book(bid: f64, ask: f64) -> Features {
+        Features {
+            bid,
+            ask,
+            ..Features::default()
+        }
+    }
+
+    fn at(millis: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp_millis(1_700_000_000_000 + millis).unwrap()
+    }
+
+    #[test]
+    fn test_opportunities_net_of_fees() {
+        let mut detector = detector();
+        assert!(detector
+            .opportunities("aevo", "btc_usd", &book(100.0, 100.1), at(0))
+            .is_empty());
+        // 0.4% apart covers 0.15% of fees, 0.2% doesn't clear them by the 0.1% minimum
+        let [opportunity] = detector
+            .opportunities("binance", "btc_usd", &book(100.5, 100.6), at(10))
+            .try_into()
+            .unwrap();
+        assert_eq!((opportunity.buy.as_str(), opportunity.ask), ("aevo", 100.1));
+        assert_eq!(
+            (opportunity.sell.as_str(), opportunity.bid),
+            ("binance", 100.5)
+        );
+        let edge = (100.5 * 0.999 - 100.1 * 1.0005) / 100.1;
+        assert!((opportunity.edge - edge).abs() < 1e-12);
+        assert!(detector
+            .opportunities("binance", "btc_usd", &book(100.3, 100.4), at(20))
+            .is_empty());
+        // Other instruments are never compared
+        assert!(detector
+            .opportunities("binance", "eth_usd", &book(200.0, 200.1), at(30))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_stale_quotes_ignored() {
+        let mut detector = detector();
+        detector.opportunities("aevo", "btc_usd", &book(100.0, 100.1), at(0));
+        assert!(detector
+            .opportunities("binance", "btc_usd", &book(101.0, 101.1), at(1001))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_alerts_on_open_and_close() {
+        let mut detector = detector();
+        assert!(detector
+            .update("aevo", "btc_usd", &book(100.0, 100.1), at(0))
+            .is_empty());
+        let [opened] = detector
+            .update("binance", "btc_usd", &book(101.0, 101.1), at(10))
+            .try_into()
+            .unwrap();
+        assert!(!opened.resolved);
+        assert_eq!(
+            opened.condition.as_deref(),
+            Some("arbitrage:btc_usd:aevo:binance")
+        );
+        assert!(detector
+            .update("binance", "btc_usd", &book(101.0, 101.1), at(20))
+            .is_empty());
+
+        let [closed] = detector
+            .update("aevo", "btc_usd", &book(101.0, 101.05), at(30))
+            .try_into()
+            .unwrap();
+        assert!(closed.resolved);
+        assert_eq!(closed.condition, opened.condition);
+    }
+}
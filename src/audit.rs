@@ -0,0 +1,266 @@
+//! Tamper-evident audit trail of every decision and execution: signals, orders, fills and
+//! rebalances, one JSON line each.
+//!
+//! Each line holds a `record` and its SHA-256 `hash`, and every record carries the hash of the
+//! record before it, so altering, removing or reordering any line breaks the chain from there
+//! on. [`verify`] walks the chain and reports the first line that does not hold up.
+//!
+//! ```json
+//! {"hash":"9f2c…","record":{"seq":1,"prev":"0000…","event":{"type":"order",…}}}
+//! ```
+
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::value::RawValue;
+use sha2::Digest;
+use sha2::Sha256;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::BufRead;
+use std::io::BufWriter;
+use std::io::Read;
+use std::io::Write;
+use std::path::Path;
+use tracing::warn;
+
+use crate::event::Event;
+
+/// The `prev` hash of the first record.
+const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+#[derive(Debug, thiserror::Error)]
+pub enum AuditError {
+    #[error("audit log error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("corrupt audit log entry on line {line}: {error}")]
+    Corrupt {
+        line: usize,
+        error: serde_json::Error,
+    },
+
+    #[error("audit log line {line} does not match its hash")]
+    Tampered { line: usize },
+
+    #[error("audit log line {line} does not follow the record before it")]
+    Broken { line: usize },
+}
+
+#[derive(Debug)]
+pub struct AuditLog {
+    writer: BufWriter<File>,
+    /// Sequence number of the last record written.
+    seq: u64,
+    /// Hash of the last record written.
+    head: String,
+}
+
+impl AuditLog {
+    /// Open the log at `path` for appending, creating it if needed. An existing log is verified
+    /// first, so a tampered log is never extended. A torn final line left by a crash mid-write is
+    /// cut off.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, AuditError> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(path)?;
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)?;
+        let end = contents
+            .iter()
+            .rposition(|&byte| byte == b'\n')
+            .map_or(0, |newline| newline + 1);
+        if end < contents.len() {
+            warn!(
+                "Cutting off {} bytes of torn audit log entry",
+                contents.len() - end
+            );
+            file.set_len(end as u64)?;
+            file.sync_data()?;
+        }
+
+        let chain = verify(&contents[..end])?;
+        Ok(Self {
+            writer: BufWriter::new(file),
+            seq: chain.records,
+            head: chain.head,
+        })
+    }
+
+    /// Hash of the last record written, which vouches for the whole history before it.
+    pub fn head(&self) -> &str {
+        &self.head
+    }
+
+    /// Append the decisions and executions among `events` and sync them to disk.
+    pub fn append(&mut self, events: &[Event]) -> Result<(), AuditError> {
+        let audited = events.iter().filter(|event| {
+            !matches!(
+                event,
+                Event::Features { .. }
+                    | Event::Equity { .. }
+                    | Event::Session { .. }
+                    | Event::Alert { .. }
+            )
+        });
+        for event in audited {
+            let record = Record {
+                seq: self.seq + 1,
+                prev: &self.head,
+                event,
+            };
+            let record =
+                serde_json::to_string(&record).map_err(|error| AuditError::Io(error.into()))?;
+            let hash = hash(&record);
+            writeln!(self.writer, r#"{{"hash":"{hash}","record":{record}}}"#)?;
+            self.seq += 1;
+            self.head = hash;
+        }
+        self.writer.flush()?;
+        self.writer.get_ref().sync_data()?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Record<'a> {
+    seq: u64,
+    prev: &'a str,
+    event: &'a Event,
+}
+
+/// A line as read back, keeping the record's exact bytes to hash.
+#[derive(Debug, Deserialize)]
+struct Line<'a> {
+    hash: String,
+    #[serde(borrow)]
+    record: &'a RawValue,
+}
+
+#[derive(Debug, Deserialize)]
+struct Link {
+    seq: u64,
+    prev: String,
+}
+
+/// An intact chain of audit records.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chain {
+    pub records: u64,
+    /// Hash of the last record, or the genesis hash for an empty log.
+    pub head: String,
+}
+
+/// Check every record in `log` against its hash and the record before it.
+pub fn verify(log: impl BufRead) -> Result<Chain, AuditError> {
+    let mut chain = Chain {
+        records: 0,
+        head: GENESIS.to_owned(),
+    };
+    for (index, line) in log.lines().enumerate() {
+        let line = line?;
+        let number = index + 1;
+        let corrupt = |error| AuditError::Corrupt {
+            line: number,
+            error,
+        };
+        let parsed: Line = serde_json::from_str(&line).map_err(corrupt)?;
+        if hash(parsed.record.get()) != parsed.hash {
+            return Err(AuditError::Tampered { line: number });
+        }
+        let link: Link = serde_json::from_str(parsed.record.get()).map_err(corrupt)?;
+        if link.seq != chain.records + 1 || link.prev != chain.head {
+            return Err(AuditError::Broken { line: number });
+        }
+        chain.records = link.seq;
+        chain.head = parsed.hash;
+    }
+    Ok(chain)
+}
+
+fn hash(record: &str) -> String {
+    hex::encode(Sha256::digest(record.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use barter_integration::model::Side;
+    use chrono::Utc;
+    use std::io::BufReader;
+    use std::time::SystemTime;
+
+    use super::*;
+    use crate::features::Features;
+
+    fn temp_file(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!(
+            "fit-{}-{}-{}",
+            name,
+            std::process::id(),
+            SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ))
+    }
+
+    fn order(price: f64) -> Event {
+        Event::Order {
+            time: Utc::now(),
+            symbol: "BTC/USDT",
+            strategy: "imbalance".to_owned(),
+            side: Side::Buy,
+            price,
+            size: 0.001,
+            book_time: None,
+        }
+    }
+
+    #[test]
+    fn test_chain_detects_tampering() {
+        let path = temp_file("audit");
+        let mut audit = AuditLog::open(&path).unwrap();
+        assert_eq!(audit.head(), GENESIS);
+        let features = Event::Features {
+            time: Utc::now(),
+            symbol: "BTC/USDT",
+            features: Features::default(),
+            book_time: None,
+        };
+        audit.append(&[features, order(100.0)]).unwrap();
+        drop(audit);
+
+        // Reopening carries the chain on
+        let mut audit = AuditLog::open(&path).unwrap();
+        audit.append(&[order(101.0), order(102.0)]).unwrap();
+        let head = audit.head().to_owned();
+        drop(audit);
+        let chain = verify(BufReader::new(File::open(&path).unwrap())).unwrap();
+        assert_eq!(chain.records, 3);
+        assert_eq!(chain.head, head);
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+
+        let edited = contents.replacen("\"price\":101.0", "\"price\":99.0", 1);
+        assert!(matches!(
+            verify(edited.as_bytes()),
+            Err(AuditError::Tampered { line: 2 })
+        ));
+
+        let removed = format!("{}\n{}\n", lines[0], lines[2]);
+        assert!(matches!(
+            verify(removed.as_bytes()),
+            Err(AuditError::Broken { line: 2 })
+        ));
+        // A tampered log is never extended
+        std::fs::write(&path, removed).unwrap();
+        assert!(matches!(
+            AuditLog::open(&path),
+            Err(AuditError::Broken { line: 2 })
+        ));
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
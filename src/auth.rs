@@ -0,0 +1,382 @@
+//! Authentication of the control surfaces: the control API, the web dashboard and the gRPC
+//! service all accept the same bearer tokens, configured through [secrets](crate::secrets), and
+//! can require client certificates.
+//!
+//! Over HTTP the token goes in an `Authorization: Bearer <token>` header, or an `access_token`
+//! query parameter where a browser can't set headers, as when opening a WebSocket. Safe methods
+//! (`GET`, `HEAD`) need the [`Scope::Read`] scope and anything else the [`Scope::Control`] scope.
+//! Over gRPC the token goes in the `authorization` metadata and each call needs the scope that
+//! matches what it does.
+
+use serde::Deserialize;
+use serde::Serialize;
+use sha2::Digest;
+use sha2::Sha256;
+
+use crate::config::AuthConfig;
+use crate::secrets::SecretError;
+
+#[cfg(feature = "tls")]
+use crate::config::TlsConfig;
+
+/// What a credential lets its holder do.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scope {
+    /// Watch the bot's state and events.
+    #[default]
+    Read,
+    /// Also pause, resume, flatten and change params.
+    Control,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum AuthError {
+    #[error("failed to resolve auth token: {0}")]
+    Secret(#[from] SecretError),
+
+    #[error(
+        "control endpoints require auth.tokens, or auth.tls.client_ca for client certificates"
+    )]
+    Unauthenticated,
+
+    #[error("TLS requires building with the tls feature")]
+    TlsUnsupported,
+
+    #[error("failed to read TLS file {path}: {source}")]
+    TlsFile {
+        path: std::path::PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("invalid TLS config: {0}")]
+    Tls(String),
+
+    #[error("failed to bind: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Why a request was turned away.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Denied {
+    /// It had no valid credentials.
+    Unauthenticated,
+    /// Its credentials don't grant the scope needed.
+    Forbidden,
+}
+
+/// Checks credentials against the configured tokens.
+#[derive(Debug, Clone)]
+pub struct Authenticator {
+    /// SHA-256 of each token, so checking a request takes the same time however much matches.
+    tokens: Vec<([u8; 32], Scope)>,
+    /// Granted to every client when the transport only accepts clients with a valid certificate.
+    client_scope: Option<Scope>,
+}
+
+impl Authenticator {
+    pub fn new(config: &AuthConfig) -> Result<Self, AuthError> {
+        let tokens = config
+            .tokens
+            .iter()
+            .map(|token| Ok((digest(&token.secret.resolve()?), token.scope)))
+            .collect::<Result<Vec<_>, AuthError>>()?;
+        let client_scope = config
+            .tls
+            .as_ref()
+            .filter(|tls| tls.client_ca.is_some())
+            .map(|tls| tls.client_scope);
+        if tokens.is_empty() && client_scope.is_none() {
+            return Err(AuthError::Unauthenticated);
+        }
+        Ok(Self {
+            tokens,
+            client_scope,
+        })
+    }
+
+    #[cfg(all(test, any(feature = "grpc", feature = "web")))]
+    pub(crate) fn with_token(token: &str, scope: Scope) -> Self {
+        Self {
+            tokens: vec![(digest(token), scope)],
+            client_scope: None,
+        }
+    }
+
+    /// The widest scope granted to a request bearing `token`, if any.
+    pub fn scope(&self, token: Option<&str>) -> Option<Scope> {
+        let token = token.map(digest);
+        // Compare against every token, so timing doesn't reveal which one matched
+        let granted = self
+            .tokens
+            .iter()
+            .filter(|(hash, _)| token.as_ref() == Some(hash))
+            .map(|(_, scope)| *scope)
+            .max();
+        granted.max(self.client_scope)
+    }
+
+    /// Let a request bearing `token` through if it is granted `needed`.
+    pub fn authorize(&self, token: Option<&str>, needed: Scope) -> Result<Scope, Denied> {
+        match self.scope(token) {
+            Some(scope) if scope >= needed => Ok(scope),
+            Some(_) => Err(Denied::Forbidden),
+            None => Err(Denied::Unauthenticated),
+        }
+    }
+}
+
+/// The token in an `Authorization: Bearer <token>` header value.
+pub fn bearer(authorization: Option<&str>) -> Option<&str> {
+    authorization.and_then(|value| value.strip_prefix("Bearer "))
+}
+
+fn digest(token: &str) -> [u8; 32] {
+    Sha256::digest(token.as_bytes()).into()
+}
+
+#[cfg(feature = "tls")]
+fn read(path: &std::path::Path) -> Result<Vec<u8>, AuthError> {
+    std::fs::read(path).map_err(|source| AuthError::TlsFile {
+        path: path.to_owned(),
+        source,
+    })
+}
+
+/// The PEM files named by a [`TlsConfig`].
+#[cfg(feature = "tls")]
+#[derive(Debug)]
+pub struct Pem {
+    pub cert: Vec<u8>,
+    pub key: Vec<u8>,
+    pub client_ca: Option<Vec<u8>>,
+}
+
+#[cfg(feature = "tls")]
+impl Pem {
+    pub fn read(tls: &TlsConfig) -> Result<Self, AuthError> {
+        Ok(Self {
+            cert: read(&tls.cert)?,
+            key: read(&tls.key)?,
+            client_ca: tls.client_ca.as_deref().map(read).transpose()?,
+        })
+    }
+}
+
+/// A rustls server config for `tls`, requiring client certificates signed by its CA if set.
+#[cfg(feature = "tls")]
+pub fn server_config(tls: &TlsConfig) -> Result<std::sync::Arc<rustls::ServerConfig>, AuthError> {
+    use rustls::server::WebPkiClientVerifier;
+    use rustls::RootCertStore;
+    use std::sync::Arc;
+
+    let invalid = |error: &dyn std::fmt::Display| AuthError::Tls(error.to_string());
+    let Pem {
+        cert,
+        key,
+        client_ca,
+    } = Pem::read(tls)?;
+    let certs = rustls_pemfile::certs(&mut cert.as_slice())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|error| invalid(&error))?;
+    let key = rustls_pemfile::private_key(&mut key.as_slice())
+        .map_err(|error| invalid(&error))?
+        .ok_or_else(|| AuthError::Tls(format!("no private key in {}", tls.key.display())))?;
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let builder = rustls::ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .map_err(|error| invalid(&error))?;
+    let builder = match client_ca {
+        Some(client_ca) => {
+            let mut roots = RootCertStore::empty();
+            for cert in rustls_pemfile::certs(&mut client_ca.as_slice()) {
+                roots
+                    .add(cert.map_err(|error| invalid(&error))?)
+                    .map_err(|error| invalid(&error))?;
+            }
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider)
+                .build()
+                .map_err(|error| invalid(&error))?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+    let mut config = builder
+        .with_single_cert(certs, key)
+        .map_err(|error| invalid(&error))?;
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(Arc::new(config))
+}
+
+/// Axum middleware letting through the requests granted the scope their method needs.
+#[cfg(any(feature = "api", feature = "web"))]
+pub(crate) async fn require(
+    axum::extract::State(auth): axum::extract::State<std::sync::Arc<Authenticator>>,
+    request: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> axum::response::Response {
+    use axum::http::header;
+    use axum::http::Method;
+    use axum::http::StatusCode;
+    use axum::response::IntoResponse;
+
+    let header = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok());
+    let query = request.uri().query().unwrap_or_default();
+    let token = bearer(header).or_else(|| {
+        query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("access_token="))
+    });
+    let needed = match *request.method() {
+        Method::GET | Method::HEAD => Scope::Read,
+        _ => Scope::Control,
+    };
+    match auth.authorize(token, needed) {
+        Ok(_) => next.run(request).await,
+        Err(Denied::Unauthenticated) => (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Bearer")],
+        )
+            .into_response(),
+        Err(Denied::Forbidden) => StatusCode::FORBIDDEN.into_response(),
+    }
+}
+
+/// Serve `app` on `listener` in the background, over TLS if `tls` is set.
+#[cfg(any(feature = "api", feature = "web"))]
+pub(crate) fn serve(
+    listener: tokio::net::TcpListener,
+    app: axum::Router,
+    tls: Option<&crate::config::TlsConfig>,
+    name: &'static str,
+) -> Result<(), AuthError> {
+    match tls {
+        None => {
+            tokio::spawn(async move {
+                if let Err(error) = axum::serve(listener, app).await {
+                    tracing::warn!("{} stopped: {}", name, error);
+                }
+            });
+        }
+        #[cfg(feature = "tls")]
+        Some(tls) => {
+            let config = axum_server::tls_rustls::RustlsConfig::from_config(server_config(tls)?);
+            let listener = listener.into_std()?;
+            tokio::spawn(async move {
+                let server = axum_server::from_tcp_rustls(listener, config);
+                if let Err(error) = server.serve(app.into_make_service()).await {
+                    tracing::warn!("{} stopped: {}", name, error);
+                }
+            });
+        }
+        #[cfg(not(feature = "tls"))]
+        Some(_) => return Err(AuthError::TlsUnsupported),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TokenConfig;
+    use crate::secrets::Secret;
+
+    fn token(secret: &str, scope: Scope) -> TokenConfig {
+        TokenConfig {
+            secret: Secret::Value(secret.to_owned()),
+            scope,
+        }
+    }
+
+    #[test]
+    fn test_scopes() {
+        assert!(matches!(
+            Authenticator::new(&AuthConfig::default()),
+            Err(AuthError::Unauthenticated)
+        ));
+
+        let auth = Authenticator::new(&AuthConfig {
+            tokens: vec![token("watch", Scope::Read), token("trade", Scope::Control)],
+            tls: None,
+        })
+        .unwrap();
+        assert_eq!(auth.scope(Some("watch")), Some(Scope::Read));
+        assert_eq!(
+            auth.scope(bearer(Some("Bearer trade"))),
+            Some(Scope::Control)
+        );
+        assert_eq!(auth.scope(bearer(Some("trade"))), None);
+        assert_eq!(
+            auth.authorize(Some("trade"), Scope::Read),
+            Ok(Scope::Control)
+        );
+        assert_eq!(
+            auth.authorize(Some("watch"), Scope::Control),
+            Err(Denied::Forbidden)
+        );
+        assert_eq!(
+            auth.authorize(Some("wrong"), Scope::Read),
+            Err(Denied::Unauthenticated)
+        );
+    }
+
+    #[test]
+    fn test_client_certificates_grant_scope() {
+        let auth = Authenticator::new(&AuthConfig {
+            tokens: vec![token("trade", Scope::Control)],
+            tls: Some(crate::config::TlsConfig {
+                cert: "server.pem".into(),
+                key: "server.key".into(),
+                client_ca: Some("clients-ca.pem".into()),
+                client_scope: Scope::Read,
+            }),
+        })
+        .unwrap();
+        assert_eq!(auth.scope(None), Some(Scope::Read));
+        assert_eq!(auth.scope(Some("trade")), Some(Scope::Control));
+    }
+
+    #[cfg(feature = "tls")]
+    #[test]
+    fn test_server_config() {
+        use std::time::SystemTime;
+
+        let dir = std::env::temp_dir().join(format!(
+            "fit-{}-{}-{}",
+            "tls",
+            std::process::id(),
+            SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let server = rcgen::generate_simple_self_signed(vec!["localhost".to_owned()]).unwrap();
+        let ca = rcgen::generate_simple_self_signed(vec!["clients".to_owned()]).unwrap();
+        std::fs::write(dir.join("server.pem"), server.cert.pem()).unwrap();
+        std::fs::write(dir.join("server.key"), server.key_pair.serialize_pem()).unwrap();
+        std::fs::write(dir.join("ca.pem"), ca.cert.pem()).unwrap();
+
+        let mut tls = TlsConfig {
+            cert: dir.join("server.pem"),
+            key: dir.join("server.key"),
+            client_ca: None,
+            client_scope: Scope::Read,
+        };
+        assert!(server_config(&tls).is_ok());
+        tls.client_ca = Some(dir.join("ca.pem"));
+        assert!(server_config(&tls).is_ok());
+        tls.key = dir.join("ca.pem");
+        assert!(matches!(server_config(&tls), Err(AuthError::Tls(_))));
+        tls.key = dir.join("missing.key");
+        assert!(matches!(
+            server_config(&tls),
+            Err(AuthError::TlsFile { .. })
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
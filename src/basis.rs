@@ -0,0 +1,351 @@
+//! Spot-perpetual basis trading, [`[basis]`](crate::config::BasisConfig). With it configured the
+//! spot book of the traded asset is subscribed to alongside the perpetual, and the basis is how
+//! far the perpetual's mid is above the spot mid, as a fraction of the spot. Its fair value is
+//! the funding expected over the next `periods` funding periods at the latest rate: while longs
+//! pay shorts the perpetual trades at a premium they pay back in funding.
+//!
+//! Once the basis is more than `entry` above fair value the spot is bought and the perpetual
+//! sold, and once it is more than `entry` below, the spot is sold and the perpetual bought. The
+//! two legs are held as one [`BasisPosition`], funding booked against the perpetual leg at every
+//! funding timestamp it is held through, and closed together once the basis is back within
+//! `exit` of fair value. An alert announces each position opened, and another once it is closed.
+//!
+//! Positions are paper traded on their own, outside the strategies' sleeves: a sleeve holds long
+//! positions in the one instrument it trades, so the short leg and the second instrument need an
+//! account that neither the paper sleeves nor the order gateway keep.
+
+use barter_integration::model::Side;
+use chrono::DateTime;
+use chrono::Utc;
+use tracing::info;
+
+use crate::config::BasisConfig;
+use crate::event::Alert;
+use crate::event::Severity;
+use crate::features::Features;
+use crate::funding::FundingRate;
+
+/// The [condition](Alert::condition) every basis alert is about.
+pub const CONDITION: &str = "basis";
+
+/// One leg of a basis position, entered at `price`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Leg {
+    pub side: Side,
+    pub price: f64,
+}
+
+impl Leg {
+    /// Taking `side` of `book`'s top.
+    pub fn take(side: Side, book: &Features) -> Self {
+        let price = match side {
+            Side::Buy => book.ask,
+            Side::Sell => book.bid,
+        };
+        Self { side, price }
+    }
+
+    /// What the leg makes on `size` closed against `book`'s top.
+    pub fn pnl(&self, book: &Features, size: f64) -> f64 {
+        match self.side {
+            Side::Buy => (book.bid - self.price) * size,
+            Side::Sell => (self.price - book.ask) * size,
+        }
+    }
+
+    /// The price the leg closes at against `book`'s top.
+    pub fn exit(&self, book: &Features) -> f64 {
+        match self.side {
+            Side::Buy => book.bid,
+            Side::Sell => book.ask,
+        }
+    }
+}
+
+/// A spot leg and the opposite perpetual leg of the same size, opened and closed together.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BasisPosition {
+    pub spot: Leg,
+    pub perpetual: Leg,
+    pub size: f64,
+    /// When the position was opened, or else the funding timestamp last booked.
+    pub funded: DateTime<Utc>,
+    /// Fees paid opening both legs.
+    pub fees: f64,
+    /// Funding received on the perpetual leg, negative when paid.
+    pub funding: f64,
+}
+
+impl BasisPosition {
+    /// Take `spot_side` of the `spot` top and the other side of the `perpetual` top on `size`
+    /// each at `now`, paying `fee` on both.
+    pub fn open(
+        spot_side: Side,
+        spot: &Features,
+        perpetual: &Features,
+        size: f64,
+        fee: f64,
+        now: DateTime<Utc>,
+    ) -> Self {
+        let perpetual_side = match spot_side {
+            Side::Buy => Side::Sell,
+            Side::Sell => Side::Buy,
+        };
+        let spot = Leg::take(spot_side, spot);
+        let perpetual = Leg::take(perpetual_side, perpetual);
+        Self {
+            spot,
+            perpetual,
+            size,
+            funded: now,
+            fees: (spot.price + perpetual.price) * size * fee,
+            funding: 0.0,
+        }
+    }
+
+    /// Book the funding `rate` due on the perpetual leg, marked at `mark`, if its funding
+    /// timestamp has been held through by `now`.
+    pub fn fund(&mut self, rate: FundingRate, mark: f64, now: DateTime<Utc>) {
+        let Some(next) = rate.next.filter(|next| *next > self.funded && *next <= now) else {
+            return;
+        };
+        let paid = rate.rate * self.size * mark;
+        self.funding += match self.perpetual.side {
+            Side::Buy => -paid,
+            Side::Sell => paid,
+        };
+        self.funded = next;
+    }
+
+    /// The position's exposure to the asset, long positive: none, with the legs cancelling out.
+    pub fn delta(&self) -> f64 {
+        [self.spot, self.perpetual]
+            .iter()
+            .map(|leg| match leg.side {
+                Side::Buy => self.size,
+                Side::Sell => -self.size,
+            })
+            .sum()
+    }
+
+    /// What closing both legs against the `spot` and `perpetual` tops would make, after every
+    /// fee and the funding so far.
+    pub fn pnl(&self, spot: &Features, perpetual: &Features, fee: f64) -> f64 {
+        let closing = (self.spot.exit(spot) + self.perpetual.exit(perpetual)) * self.size * fee;
+        self.spot.pnl(spot, self.size) + self.perpetual.pnl(perpetual, self.size) + self.funding
+            - self.fees
+            - closing
+    }
+}
+
+#[derive(Debug)]
+pub struct BasisTrader {
+    config: BasisConfig,
+    /// The latest spot top of book.
+    spot: Option<Features>,
+    rate: Option<FundingRate>,
+    position: Option<BasisPosition>,
+    /// P&L of the positions closed so far.
+    realised: f64,
+}
+
+impl BasisTrader {
+    pub fn new(config: &BasisConfig) -> Self {
+        Self {
+            config: config.clone(),
+            spot: None,
+            rate: None,
+            position: None,
+            realised: 0.0,
+        }
+    }
+
+    pub fn set_rate(&mut self, rate: FundingRate) {
+        self.rate = Some(rate);
+    }
+
+    /// Keep the spot book's `features` to price the basis against.
+    pub fn on_spot(&mut self, features: &Features) {
+        self.spot = Some(*features);
+    }
+
+    pub fn position(&self) -> Option<&BasisPosition> {
+        self.position.as_ref()
+    }
+
+    pub fn realised(&self) -> f64 {
+        self.realised
+    }
+
+    /// The basis the funding rate makes fair.
+    pub fn fair(&self) -> f64 {
+        self.rate
+            .map_or(0.0, |rate| rate.rate * self.config.periods)
+    }
+
+    /// The perpetual's premium in `perpetual` over the latest spot mid, once a spot book is in.
+    pub fn basis(&self, perpetual: &Features) -> Option<f64> {
+        let spot = self.spot?;
+        Some((perpetual.mid_price - spot.mid_price) / spot.mid_price)
+    }
+
+    /// Price the basis from the `perpetual` book at `now`, opening or closing the position,
+    /// returning the alert to raise if it did.
+    pub fn update(&mut self, perpetual: &Features, now: DateTime<Utc>) -> Option<Alert> {
+        if let (Some(position), Some(rate)) = (&mut self.position, self.rate) {
+            position.fund(rate, perpetual.mid_price, now);
+        }
+        let spot = self.spot?;
+        let basis = self.basis(perpetual)?;
+        let fair = self.fair();
+        let deviation = basis - fair;
+
+        if let Some(position) = self.position {
+            if deviation.abs() > self.config.exit {
+                return None;
+            }
+            let pnl = position.pnl(&spot, perpetual, self.config.fee);
+            self.realised += pnl;
+            self.position = None;
+            let message = format!(
+                "Basis back to {:.3}% against a fair {:.3}%, closed both legs for {:.2}",
+                basis * 100.0,
+                fair * 100.0,
+                pnl
+            );
+            info!("{}", message);
+            return Some(Alert {
+                severity: Severity::Info,
+                message,
+                condition: Some(CONDITION.to_owned()),
+                resolved: true,
+            });
+        }
+
+        // A rich perpetual is sold against the spot, a cheap one bought
+        let spot_side = if deviation > self.config.entry {
+            Side::Buy
+        } else if deviation < -self.config.entry {
+            Side::Sell
+        } else {
+            return None;
+        };
+        let position = BasisPosition::open(
+            spot_side,
+            &spot,
+            perpetual,
+            self.config.size,
+            self.config.fee,
+            now,
+        );
+        self.position = Some(position);
+        let legs = match spot_side {
+            Side::Buy => "long spot, short perpetual",
+            Side::Sell => "short spot, long perpetual",
+        };
+        let message = format!(
+            "Basis at {:.3}% against a fair {:.3}%, opened {} at {} and {}",
+            basis * 100.0,
+            fair * 100.0,
+            legs,
+            position.spot.price,
+            position.perpetual.price
+        );
+        info!("{}", message);
+        Some(Alert {
+            severity: Severity::Info,
+            message,
+            condition: Some(CONDITION.to_owned()),
+            resolved: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trader() -> BasisTrader {
+        BasisTrader::new(&BasisConfig {
+            entry: 0.002,
+            exit: 0.0005,
+            periods: 3.0,
+            fee: 0.0005,
+            size: 1.0,
+        })
+    }
+
+    fn book(bid: f64, ask: f64) -> Features {
+        Features {
+            bid,
+            ask,
+            mid_price: (bid + ask) / 2.0,
+            ..Features::default()
+        }
+    }
+
+    fn at(millis: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp_millis(1_700_000_000_000 + millis).unwrap()
+    }
+
+    #[test]
+    fn test_fair_value_includes_funding() {
+        let mut trader = trader();
+        assert_eq!(trader.update(&book(100.4, 100.6), at(0)), None);
+        trader.on_spot(&book(99.9, 100.1));
+        assert!((trader.basis(&book(100.4, 100.6)).unwrap() - 0.005).abs() < 1e-12);
+
+        // 0.12% funding a period over three periods makes 0.36% of the 0.5% premium fair
+        trader.set_rate(FundingRate {
+            rate: 0.0012,
+            next: None,
+        });
+        assert!((trader.fair() - 0.0036).abs() < 1e-12);
+        assert_eq!(trader.update(&book(100.4, 100.6), at(0)), None);
+        trader.set_rate(FundingRate {
+            rate: 0.0,
+            next: None,
+        });
+        let opened = trader.update(&book(100.4, 100.6), at(0)).unwrap();
+        assert!(!opened.resolved);
+        assert_eq!(opened.condition.as_deref(), Some(CONDITION));
+        let position = trader.position().unwrap();
+        assert_eq!(position.spot, Leg::take(Side::Buy, &book(99.9, 100.1)));
+        assert_eq!(position.perpetual.side, Side::Sell);
+        assert_eq!(position.delta(), 0.0);
+    }
+
+    #[test]
+    fn test_linked_legs_close_together() {
+        let mut trader = trader();
+        trader.on_spot(&book(99.9, 100.1));
+        // A cheap perpetual is bought against the spot sold
+        trader.update(&book(99.4, 99.6), at(0)).unwrap();
+        let position = *trader.position().unwrap();
+        assert_eq!(
+            (position.spot.side, position.spot.price),
+            (Side::Sell, 99.9)
+        );
+        assert_eq!(
+            (position.perpetual.side, position.perpetual.price),
+            (Side::Buy, 99.6)
+        );
+        assert!((position.fees - (99.9 + 99.6) * 0.0005).abs() < 1e-12);
+        assert_eq!(trader.update(&book(99.7, 99.9), at(10)), None);
+
+        // Longs receive negative funding at the timestamp held through
+        trader.set_rate(FundingRate {
+            rate: -0.0001,
+            next: Some(at(20)),
+        });
+        trader.on_spot(&book(100.9, 101.1));
+        let closed = trader.update(&book(100.9, 101.1), at(30)).unwrap();
+        assert!(closed.resolved);
+        assert_eq!(trader.position(), None);
+        // Spot sold at 99.9 and bought back at 101.1, perpetual bought at 99.6 and sold at 100.9
+        let funding = 0.0001 * 101.0;
+        let fees = (99.9 + 99.6 + 101.1 + 100.9) * 0.0005;
+        let pnl = (99.9 - 101.1) + (100.9 - 99.6) + funding - fees;
+        assert!((trader.realised() - pnl).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,204 @@
+//! Hard sanity bounds per instrument, [`[bounds]`](crate::config::BoundsConfig): the plausible
+//! price range and the largest order notional, so a unit or decimal bug, ours or the feed's,
+//! never reaches the exchange.
+//!
+//! Every order is placed at the top of the book, buys at the bid and sells at the ask, for
+//! [`TRADE_SIZE`], so a book whose prices are outside the bounds, or whose ask would make an
+//! order too large, blocks every signal and order on it, exits included. A critical alert is
+//! raised when trading is first blocked and another once books are back within bounds.
+
+use std::collections::HashMap;
+use std::fmt;
+use tracing::info;
+use tracing::warn;
+
+use crate::config::BoundsConfig;
+use crate::event::Alert;
+use crate::event::Severity;
+use crate::features::Features;
+use crate::TRADE_SIZE;
+
+/// The [condition](Alert::condition) every sanity bound alert is about.
+pub const CONDITION: &str = "sanity_bounds";
+
+/// Which bound a book is outside of.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Violation {
+    PriceBelow { price: f64, min: f64 },
+    PriceAbove { price: f64, max: f64 },
+    Notional { notional: f64, max: f64 },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PriceBelow { price, min } => write!(f, "price {price} below the {min} minimum"),
+            Self::PriceAbove { price, max } => write!(f, "price {price} above the {max} maximum"),
+            Self::Notional { notional, max } => {
+                write!(f, "order notional {notional} above the {max} maximum")
+            }
+        }
+    }
+}
+
+/// The bound `features` break for an instrument bounded by `bounds`, if any.
+pub fn check(bounds: &BoundsConfig, features: &Features) -> Option<Violation> {
+    // Prices that aren't numbers are as implausible as can be
+    for price in [features.bid, features.ask] {
+        if let Some(min) = bounds
+            .min_price
+            .filter(|min| price.is_nan() || price < *min)
+        {
+            return Some(Violation::PriceBelow { price, min });
+        }
+        if let Some(max) = bounds.max_price.filter(|max| price > *max) {
+            return Some(Violation::PriceAbove { price, max });
+        }
+    }
+    let notional = features.bid.max(features.ask) * TRADE_SIZE;
+    bounds
+        .max_notional
+        .filter(|max| notional > *max)
+        .map(|max| Violation::Notional { notional, max })
+}
+
+/// Blocks trading on books outside their instrument's bounds, instruments without any being
+/// left unbounded.
+#[derive(Debug)]
+pub struct SanityBounds {
+    bounds: HashMap<String, BoundsConfig>,
+    violation: Option<Violation>,
+}
+
+impl SanityBounds {
+    pub fn new(bounds: &HashMap<String, BoundsConfig>) -> Self {
+        Self {
+            bounds: bounds.clone(),
+            violation: None,
+        }
+    }
+
+    /// Judge the latest book of `symbol`, returning the alert to raise if trading was just
+    /// blocked or is no longer.
+    pub fn update(&mut self, symbol: &str, features: &Features) -> Option<Alert> {
+        let violation = self
+            .bounds
+            .get(symbol)
+            .and_then(|bounds| check(bounds, features));
+        let was_blocked = self.violation.is_some();
+        self.violation = violation;
+        match violation {
+            Some(violation) if !was_blocked => {
+                let message = format!("{symbol} {violation}, blocking every signal and order");
+                warn!("{}", message);
+                Some(Alert {
+                    severity: Severity::Critical,
+                    message,
+                    condition: Some(CONDITION.to_owned()),
+                    resolved: false,
+                })
+            }
+            None if was_blocked => {
+                let message = format!("{symbol} back within its sanity bounds, trading again");
+                info!("{}", message);
+                Some(Alert {
+                    severity: Severity::Info,
+                    message,
+                    condition: Some(CONDITION.to_owned()),
+                    resolved: true,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the latest book was outside its bounds.
+    pub fn blocked(&self) -> bool {
+        self.violation.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn features(bid: f64, ask: f64) -> Features {
+        Features {
+            bid,
+            ask,
+            ..Features::default()
+        }
+    }
+
+    fn bounds() -> BoundsConfig {
+        BoundsConfig {
+            min_price: Some(1_000.0),
+            max_price: Some(1_000_000.0),
+            max_notional: Some(500.0),
+        }
+    }
+
+    #[test]
+    fn test_check() {
+        assert_eq!(check(&bounds(), &features(60_000.0, 60_001.0)), None);
+        assert_eq!(
+            check(&bounds(), &features(0.6, 0.61)),
+            Some(Violation::PriceBelow {
+                price: 0.6,
+                min: 1_000.0
+            })
+        );
+        assert!(matches!(
+            check(&bounds(), &features(6_000_000.0, 6_000_001.0)),
+            Some(Violation::PriceAbove { .. })
+        ));
+        assert!(matches!(
+            check(&bounds(), &features(f64::NAN, 60_001.0)),
+            Some(Violation::PriceBelow { .. })
+        ));
+        // Within the price range, but the order would be too large
+        let tight = BoundsConfig {
+            max_notional: Some(50.0),
+            ..bounds()
+        };
+        assert!(matches!(
+            check(&tight, &features(60_000.0, 60_001.0)),
+            Some(Violation::Notional { .. })
+        ));
+        let unbounded = BoundsConfig {
+            min_price: None,
+            max_price: None,
+            max_notional: None,
+        };
+        assert_eq!(check(&unbounded, &features(0.6, 0.61)), None);
+    }
+
+    #[test]
+    fn test_block_and_alert() {
+        let mut sanity = SanityBounds::new(&HashMap::from([("BTC/USDT".to_owned(), bounds())]));
+        assert_eq!(
+            sanity.update("BTC/USDT", &features(60_000.0, 60_001.0)),
+            None
+        );
+        assert!(!sanity.blocked());
+
+        let alert = sanity.update("BTC/USDT", &features(0.6, 0.61)).unwrap();
+        assert_eq!(alert.severity, Severity::Critical);
+        assert_eq!(
+            alert.message,
+            "BTC/USDT price 0.6 below the 1000 minimum, blocking every signal and order"
+        );
+        assert!(sanity.blocked());
+        // Alerted once while it lasts
+        assert_eq!(sanity.update("BTC/USDT", &features(0.6, 0.61)), None);
+
+        let alert = sanity
+            .update("BTC/USDT", &features(60_000.0, 60_001.0))
+            .unwrap();
+        assert!(alert.resolved);
+        assert!(!sanity.blocked());
+
+        // Other instruments aren't bounded
+        assert_eq!(sanity.update("ETH/USDT", &features(0.6, 0.61)), None);
+    }
+}
@@ -0,0 +1,184 @@
+//! Calibrating the imbalance strategy's thresholds to the traded instrument,
+//! [`[calibration]`](crate::config::CalibrationConfig). A spread threshold tight for one
+//! instrument never lets another trade at all, so rather than one constant for every book the
+//! spread and OIR thresholds are set from percentiles of the last `window` books seen: only
+//! spreads at or under the `spread_percentile` of recent ones trade, and only OIR above its
+//! `oir_percentile` buys.
+//!
+//! Thresholds are first calibrated once `min_samples` books have been seen after startup, then
+//! again every `interval_secs`, and swapped into every `imbalance` member of the running config.
+//! Until then members keep the thresholds they are configured with. Configs reloaded or
+//! scheduled after a calibration keep its thresholds.
+
+use chrono::DateTime;
+use chrono::TimeDelta;
+use chrono::Utc;
+use std::collections::VecDeque;
+use tracing::info;
+
+use crate::config::CalibrationConfig;
+use crate::config::Config;
+use crate::features::Features;
+use crate::strategy::StrategyKind;
+
+/// Thresholds calibrated from recent books.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Thresholds {
+    pub spread: f64,
+    pub oir: f64,
+}
+
+/// The `percentile`, from 0 to 1, of `values` by nearest rank, or `None` without any.
+pub fn percentile(values: impl IntoIterator<Item = f64>, percentile: f64) -> Option<f64> {
+    let mut values: Vec<f64> = values.into_iter().collect();
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(f64::total_cmp);
+    let rank = (values.len() - 1) as f64 * percentile.clamp(0.0, 1.0);
+    Some(values[rank.round() as usize])
+}
+
+#[derive(Debug)]
+pub struct Calibrator {
+    config: CalibrationConfig,
+    /// The spread and OIR of the last `window` books, oldest first.
+    samples: VecDeque<(f64, f64)>,
+    calibrated: Option<(DateTime<Utc>, Thresholds)>,
+}
+
+impl Calibrator {
+    pub fn new(config: &CalibrationConfig) -> Self {
+        Self {
+            config: config.clone(),
+            samples: VecDeque::with_capacity(config.window),
+            calibrated: None,
+        }
+    }
+
+    /// Count the spread and OIR of the book of `features`, unless either isn't a number.
+    pub fn observe(&mut self, features: &Features) {
+        if !features.spread.is_finite() || !features.oir.is_finite() {
+            return;
+        }
+        if self.samples.len() >= self.config.window.max(1) {
+            self.samples.pop_front();
+        }
+        self.samples.push_back((features.spread, features.oir));
+    }
+
+    pub fn thresholds(&self) -> Option<Thresholds> {
+        self.calibrated.map(|(_, thresholds)| thresholds)
+    }
+
+    /// Recalibrate if enough books have been seen and none has been calibrated on for
+    /// `interval_secs` up to `now`, returning the new thresholds.
+    pub fn poll(&mut self, now: DateTime<Utc>) -> Option<Thresholds> {
+        if self.samples.len() < self.config.min_samples.max(1) {
+            return None;
+        }
+        let interval = TimeDelta::seconds(self.config.interval_secs as i64);
+        if self
+            .calibrated
+            .is_some_and(|(time, _)| now - time < interval)
+        {
+            return None;
+        }
+        let thresholds = Thresholds {
+            spread: percentile(
+                self.samples.iter().map(|(spread, _)| *spread),
+                self.config.spread_percentile,
+            )?,
+            oir: percentile(
+                self.samples.iter().map(|(_, oir)| *oir),
+                self.config.oir_percentile,
+            )?,
+        };
+        info!(
+            "Calibrated a spread threshold of {:.4}% and an OIR threshold of {:.3} over {} books",
+            thresholds.spread,
+            thresholds.oir,
+            self.samples.len()
+        );
+        self.calibrated = Some((now, thresholds));
+        Some(thresholds)
+    }
+
+    /// `config` with the calibrated thresholds, once there are any, in every imbalance member.
+    pub fn apply(&self, mut config: Config) -> Config {
+        let Some(thresholds) = self.thresholds() else {
+            return config;
+        };
+        for member in &mut config.strategy.members {
+            if let StrategyKind::Imbalance(params) = &mut member.strategy {
+                params.spread_threshold = thresholds.spread;
+                params.oir_threshold = thresholds.oir;
+            }
+        }
+        config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::StrategyConfig;
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000 + secs, 0).unwrap()
+    }
+
+    fn book(spread: f64, oir: f64) -> Features {
+        Features {
+            spread,
+            oir,
+            ..Features::default()
+        }
+    }
+
+    #[test]
+    fn test_calibrates_from_recent_books() {
+        assert_eq!(percentile([3.0, 1.0, 2.0, 5.0, 4.0], 0.3), Some(2.0));
+        assert_eq!(percentile([], 0.5), None);
+
+        let mut calibrator = Calibrator::new(&CalibrationConfig {
+            window: 10,
+            min_samples: 5,
+            interval_secs: 60,
+            spread_percentile: 0.3,
+            oir_percentile: 0.7,
+        });
+        for value in 1..=4 {
+            calibrator.observe(&book(value as f64 / 100.0, value as f64 / 10.0));
+        }
+        calibrator.observe(&book(f64::NAN, 0.5));
+        assert_eq!(calibrator.poll(at(0)), None);
+        assert_eq!(
+            calibrator.apply(Config::default()).strategy,
+            StrategyConfig::default()
+        );
+
+        calibrator.observe(&book(0.05, 0.5));
+        let thresholds = calibrator.poll(at(0)).unwrap();
+        assert_eq!((thresholds.spread, thresholds.oir), (0.02, 0.4));
+        let config = calibrator.apply(Config::default());
+        assert!(matches!(
+            config.strategy.members[0].strategy,
+            StrategyKind::Imbalance(params)
+                if params.spread_threshold == 0.02 && params.oir_threshold == 0.4
+        ));
+
+        // Only recalibrated once the interval is up, over the last window of books
+        for _ in 0..10 {
+            calibrator.observe(&book(0.1, 0.9));
+        }
+        assert_eq!(calibrator.poll(at(59)), None);
+        assert_eq!(
+            calibrator.poll(at(60)),
+            Some(Thresholds {
+                spread: 0.1,
+                oir: 0.9
+            })
+        );
+    }
+}
@@ -0,0 +1,287 @@
+//! Chaos mode, [`[chaos]`](crate::config::ChaosConfig), for exercising the bot's resilience on
+//! the paper account before going live. Faults are injected at random, drawn from the config's
+//! `seed` so a run that goes wrong can be replayed:
+//!
+//! - feed disconnects: every book is swallowed, as if the connection had dropped, until the
+//!   watchdog notices the silence and resubscribes or `outage_secs` pass
+//! - delayed books: held back up to `max_delay_ms`, arriving late and out of order
+//! - rejected orders: the sleeve's order is never filled, so it has to decide again
+//! - partial fills: only part of the order's lot fills on its book, the rest resting at the same
+//!   price until the next one. Positions are whole lots, so the order is booked as one fill once
+//!   the lot is complete, and the sleeve places nothing more meanwhile
+//!
+//! Take profit, stop loss and flattening are market orders placed by the bot itself and are left
+//! alone.
+
+use barter_data::event::MarketEvent;
+use barter_data::subscription::book::OrderBook;
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::info;
+use tracing::warn;
+
+use crate::config::ChaosConfig;
+use crate::market;
+use crate::rng::Rng;
+
+/// Injects faults into the order book feed. Lets every book through untouched when chaos mode is
+/// off.
+#[derive(Debug)]
+pub struct FeedChaos {
+    config: Option<ChaosConfig>,
+    rng: Rng,
+    /// When the outage in progress ends, if one is.
+    outage_until: Option<Instant>,
+    /// Books held back with when to let them through, soonest first.
+    delayed: Vec<(Instant, MarketEvent<OrderBook>)>,
+    disconnects: u64,
+    delays: u64,
+}
+
+impl FeedChaos {
+    pub fn new(config: Option<&ChaosConfig>, seed: u64) -> Self {
+        if config.is_some() {
+            warn!("Chaos mode on, injecting faults with seed {}", seed);
+        }
+        Self {
+            config: config.cloned(),
+            rng: Rng::stream(seed, "chaos.feed"),
+            outage_until: None,
+            delayed: Vec::new(),
+            disconnects: 0,
+            delays: 0,
+        }
+    }
+
+    /// Disconnects injected so far.
+    pub fn disconnects(&self) -> u64 {
+        self.disconnects
+    }
+
+    /// Books delayed so far.
+    pub fn delays(&self) -> u64 {
+        self.delays
+    }
+
+    /// Pass on a book received at `now`, unless it is lost to an outage or held back.
+    pub fn feed(
+        &mut self,
+        event: MarketEvent<OrderBook>,
+        now: Instant,
+    ) -> Option<MarketEvent<OrderBook>> {
+        let Some(config) = &self.config else {
+            return Some(event);
+        };
+        if self.outage_until.is_some_and(|until| now < until) {
+            return None;
+        }
+        self.outage_until = None;
+        if self.rng.unit() < config.disconnect_rate {
+            self.disconnects += 1;
+            warn!("Chaos: dropping the feed for up to {}s", config.outage_secs);
+            self.outage_until = Some(now + Duration::from_secs(config.outage_secs));
+            return None;
+        }
+        if self.rng.unit() < config.delay_rate {
+            let delay = Duration::from_secs_f64(self.rng.unit() * config.max_delay_ms as f64 / 1e3);
+            self.delays += 1;
+            info!(
+                "Chaos: delaying {} book from {} by {}ms",
+                market::subscription_name(&event.exchange, &event.instrument),
+                event.exchange_time,
+                delay.as_millis()
+            );
+            let release = now + delay;
+            let index = self.delayed.partition_point(|(due, _)| *due <= release);
+            self.delayed.insert(index, (release, event));
+            return None;
+        }
+        Some(event)
+    }
+
+    /// The longest held book that is due at `now`, if any.
+    pub fn release(&mut self, now: Instant) -> Option<MarketEvent<OrderBook>> {
+        match self.delayed.first() {
+            Some((due, _)) if *due <= now => Some(self.delayed.remove(0).1),
+            _ => None,
+        }
+    }
+
+    /// Wait until a held book is due. Never resolves while none are held.
+    pub async fn release_due(&self) {
+        match self.delayed.first() {
+            Some((due, _)) => tokio::time::sleep_until(*due).await,
+            None => std::future::pending().await,
+        }
+    }
+
+    /// The feed was resubscribed, which ends any outage.
+    pub fn on_resubscribe(&mut self) {
+        if self.outage_until.take().is_some() {
+            info!("Chaos: feed restored by resubscribing");
+        }
+    }
+}
+
+/// What happens to an order placed in chaos mode.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fault {
+    None,
+    Rejected,
+    /// Only this fraction of the lot fills on the order's book.
+    Partial(f64),
+}
+
+/// Injects faults into paper orders.
+#[derive(Debug)]
+pub struct OrderChaos {
+    config: ChaosConfig,
+    rng: Rng,
+}
+
+impl OrderChaos {
+    pub fn new(config: &ChaosConfig, seed: u64) -> Self {
+        Self {
+            config: config.clone(),
+            rng: Rng::stream(seed, "chaos.orders"),
+        }
+    }
+
+    /// The fault to inject into the next order.
+    pub fn fault(&mut self) -> Fault {
+        let draw = self.rng.unit();
+        if draw < self.config.reject_rate {
+            Fault::Rejected
+        } else if draw < self.config.reject_rate + self.config.partial_rate {
+            // Some of the lot and never all of it
+            Fault::Partial(0.1 + 0.8 * self.rng.unit())
+        } else {
+            Fault::None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use barter_data::subscription::book::Level;
+    use barter_data::subscription::book::OrderBookSide;
+    use barter_integration::model::instrument::kind::InstrumentKind;
+    use barter_integration::model::instrument::Instrument;
+    use barter_integration::model::Exchange;
+    use barter_integration::model::Side;
+    use chrono::DateTime;
+
+    use super::*;
+
+    fn book(millis: i64) -> MarketEvent<OrderBook> {
+        let time = DateTime::from_timestamp_millis(millis).unwrap();
+        MarketEvent {
+            exchange_time: time,
+            received_time: time,
+            exchange: Exchange::from("aevo"),
+            instrument: Instrument::from(("btc", "usdt", InstrumentKind::Perpetual)),
+            kind: OrderBook {
+                last_update_time: time,
+                bids: OrderBookSide::new(Side::Buy, Vec::<Level>::new()),
+                asks: OrderBookSide::new(Side::Sell, Vec::<Level>::new()),
+            },
+        }
+    }
+
+    fn config() -> ChaosConfig {
+        ChaosConfig {
+            disconnect_rate: 0.0,
+            outage_secs: 60,
+            delay_rate: 0.0,
+            max_delay_ms: 500,
+            reject_rate: 0.0,
+            partial_rate: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_off_passes_everything() {
+        let mut chaos = FeedChaos::new(None, 7);
+        let now = Instant::now();
+        assert_eq!(chaos.feed(book(1), now), Some(book(1)));
+        assert_eq!(chaos.release(now), None);
+    }
+
+    #[test]
+    fn test_outage_until_resubscribed() {
+        let mut chaos = FeedChaos::new(
+            Some(&ChaosConfig {
+                disconnect_rate: 1.0,
+                ..config()
+            }),
+            7,
+        );
+        let now = Instant::now();
+        assert_eq!(chaos.feed(book(1), now), None);
+        assert_eq!(chaos.feed(book(2), now + Duration::from_secs(30)), None);
+        assert_eq!(chaos.disconnects(), 1);
+
+        chaos.config.as_mut().unwrap().disconnect_rate = 0.0;
+        chaos.on_resubscribe();
+        assert_eq!(
+            chaos.feed(book(3), now + Duration::from_secs(31)),
+            Some(book(3))
+        );
+
+        // Outages also end on their own
+        chaos.config.as_mut().unwrap().disconnect_rate = 1.0;
+        assert_eq!(chaos.feed(book(4), now), None);
+        chaos.config.as_mut().unwrap().disconnect_rate = 0.0;
+        assert_eq!(
+            chaos.feed(book(5), now + Duration::from_secs(60)),
+            Some(book(5))
+        );
+    }
+
+    #[test]
+    fn test_delayed_books_released() {
+        let mut chaos = FeedChaos::new(
+            Some(&ChaosConfig {
+                delay_rate: 1.0,
+                ..config()
+            }),
+            7,
+        );
+        let now = Instant::now();
+        for millis in 1..=5 {
+            assert_eq!(chaos.feed(book(millis), now), None);
+        }
+        assert_eq!(chaos.delays(), 5);
+
+        let later = now + Duration::from_millis(500);
+        let mut released = Vec::new();
+        while let Some(event) = chaos.release(later) {
+            released.push(event.exchange_time.timestamp_millis());
+        }
+        released.sort_unstable();
+        assert_eq!(released, [1, 2, 3, 4, 5]);
+        assert_eq!(chaos.release(later), None);
+    }
+
+    #[test]
+    fn test_order_faults_are_seeded() {
+        let config = ChaosConfig {
+            reject_rate: 0.2,
+            partial_rate: 0.2,
+            ..config()
+        };
+        let faults = |seed| {
+            let mut chaos = OrderChaos::new(&config, seed);
+            (0..100).map(|_| chaos.fault()).collect::<Vec<_>>()
+        };
+        let first = faults(7);
+        assert_eq!(first, faults(7));
+        assert!(first.contains(&Fault::Rejected));
+        assert!(first.contains(&Fault::None));
+        assert!(first.iter().any(|fault| matches!(
+            fault,
+            Fault::Partial(fraction) if (0.1..0.9).contains(fraction)
+        )));
+        assert_ne!(first, faults(8));
+    }
+}
@@ -0,0 +1,272 @@
+//! Clock drift against the exchange's server time. Signed orders carry timestamps the exchange
+//! rejects when they are too far from its own clock, and every timestamp-sensitive check here
+//! goes quietly wrong with a skewed clock, so the guard warns once the drift passes `warn_ms` and
+//! blocks entries once it passes `halt_ms`. With the `clock` feature the poller keeps it fed by
+//! asking the exchange's time endpoint.
+//!
+//! The drift is measured like NTP does, against the local time halfway through the request, so
+//! it is only as precise as half the round trip.
+
+use chrono::DateTime;
+use chrono::TimeDelta;
+use chrono::Utc;
+use serde_json::Value;
+use tracing::info;
+use tracing::warn;
+
+use crate::config::ClockConfig;
+use crate::event::Alert;
+use crate::event::Severity;
+use crate::hours::Permission;
+#[cfg(feature = "clock")]
+use crate::poll;
+
+/// The [condition](Alert::condition) every clock drift alert is about.
+pub const CONDITION: &str = "clock_drift";
+
+/// One comparison of the local clock to the exchange's.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockSample {
+    /// How far the exchange's clock is ahead of the local one, behind when negative.
+    pub offset: TimeDelta,
+    pub round_trip: TimeDelta,
+}
+
+impl ClockSample {
+    /// Compare `server` time, as answered to a request sent at `sent` and answered at
+    /// `received`, both local times, to the local time halfway between.
+    pub fn measure(sent: DateTime<Utc>, server: DateTime<Utc>, received: DateTime<Utc>) -> Self {
+        let round_trip = received - sent;
+        Self {
+            offset: server - (sent + round_trip / 2),
+            round_trip,
+        }
+    }
+}
+
+/// Parse a server time response: a JSON object with `serverTime`, as Binance answers,
+/// `timestamp` or `time`, as a whole number, or number string, of seconds, milliseconds,
+/// microseconds or nanoseconds since the epoch, told apart by size, or as an RFC 3339 string.
+pub fn parse_server_time(body: &str) -> Option<DateTime<Utc>> {
+    let body: Value = serde_json::from_str(body).ok()?;
+    let time = ["serverTime", "timestamp", "time"]
+        .iter()
+        .find_map(|field| body.get(field))?;
+    let epoch: i64 = match time {
+        Value::String(time) => match time.parse() {
+            Ok(epoch) => epoch,
+            Err(_) => return DateTime::parse_from_rfc3339(time).ok().map(Into::into),
+        },
+        time => time.as_i64()?,
+    };
+    let nanos = match epoch.unsigned_abs() {
+        100_000_000_000_000_000.. => epoch,
+        100_000_000_000_000.. => epoch.checked_mul(1_000)?,
+        100_000_000_000.. => epoch.checked_mul(1_000_000)?,
+        _ => epoch.checked_mul(1_000_000_000)?,
+    };
+    Some(DateTime::from_timestamp_nanos(nanos))
+}
+
+/// How far the clock has drifted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Drift {
+    Within,
+    Warning,
+    Halted,
+}
+
+#[derive(Debug)]
+pub struct ClockGuard {
+    config: ClockConfig,
+    sample: Option<ClockSample>,
+    drift: Drift,
+}
+
+impl ClockGuard {
+    pub fn new(config: &ClockConfig) -> Self {
+        Self {
+            config: config.clone(),
+            sample: None,
+            drift: Drift::Within,
+        }
+    }
+
+    pub fn set_sample(&mut self, sample: ClockSample) {
+        self.sample = Some(sample);
+    }
+
+    /// Judge the latest sample, returning the alert to raise if the drift crossed a threshold
+    /// since the last one.
+    pub fn update(&mut self) -> Option<Alert> {
+        let sample = self.sample?;
+        let drift_ms = sample.offset.num_milliseconds().unsigned_abs();
+        let drift = if drift_ms >= self.config.halt_ms {
+            Drift::Halted
+        } else if drift_ms >= self.config.warn_ms {
+            Drift::Warning
+        } else {
+            Drift::Within
+        };
+        if drift == self.drift {
+            return None;
+        }
+        self.drift = drift;
+
+        let (severity, message) = match drift {
+            Drift::Within => (
+                Severity::Info,
+                format!("Clock back within {drift_ms}ms of the exchange's, allowing entries"),
+            ),
+            Drift::Warning => (
+                Severity::Warning,
+                format!(
+                    "Clock {drift_ms}ms off the exchange's, over the {}ms warning threshold",
+                    self.config.warn_ms
+                ),
+            ),
+            Drift::Halted => (
+                Severity::Critical,
+                format!(
+                    "Clock {drift_ms}ms off the exchange's, over the {}ms limit, blocking entries",
+                    self.config.halt_ms
+                ),
+            ),
+        };
+        match severity {
+            Severity::Info => info!(
+                "{} (round trip {}ms)",
+                message,
+                sample.round_trip.num_milliseconds()
+            ),
+            Severity::Warning | Severity::Critical => warn!(
+                "{} (round trip {}ms)",
+                message,
+                sample.round_trip.num_milliseconds()
+            ),
+        }
+        Some(Alert {
+            severity,
+            message,
+            condition: Some(CONDITION.to_owned()),
+            resolved: drift == Drift::Within,
+        })
+    }
+
+    /// Entries are blocked while the drift is over the limit.
+    pub fn permission(&self) -> Permission {
+        Permission {
+            entries: self.drift != Drift::Halted,
+            flatten: false,
+        }
+    }
+}
+
+/// Polls the exchange's server time in the background.
+#[cfg(feature = "clock")]
+#[derive(Debug)]
+pub struct ClockPoller {
+    receiver: tokio::sync::watch::Receiver<Option<ClockSample>>,
+}
+
+#[cfg(feature = "clock")]
+impl ClockPoller {
+    pub fn spawn(config: &ClockConfig) -> Self {
+        let receiver = poll::spawn(
+            config.url.clone(),
+            config.poll_secs,
+            "server time",
+            |body, sent, received| {
+                parse_server_time(body).map(|server| ClockSample::measure(sent, server, received))
+            },
+        );
+        Self { receiver }
+    }
+
+    /// The sample taken since the last call, if any.
+    pub fn latest(&mut self) -> Option<ClockSample> {
+        match self.receiver.has_changed() {
+            Ok(true) => *self.receiver.borrow_and_update(),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guard() -> ClockGuard {
+        ClockGuard::new(&ClockConfig {
+            url: "http://localhost/time".to_owned(),
+            poll_secs: 60,
+            warn_ms: 250,
+            halt_ms: 1000,
+        })
+    }
+
+    fn offset(millis: i64) -> ClockSample {
+        ClockSample {
+            offset: TimeDelta::milliseconds(millis),
+            round_trip: TimeDelta::milliseconds(40),
+        }
+    }
+
+    #[test]
+    fn test_parse_server_time() {
+        let time = DateTime::from_timestamp_millis(1_721_721_600_123).unwrap();
+        assert_eq!(
+            parse_server_time(r#"{"serverTime":1721721600123}"#),
+            Some(time)
+        );
+        assert_eq!(
+            parse_server_time(r#"{"timestamp":"1721721600123000000"}"#),
+            Some(time)
+        );
+        assert_eq!(
+            parse_server_time(r#"{"time":"2024-07-23T08:00:00.123Z"}"#),
+            Some(time)
+        );
+        assert_eq!(
+            parse_server_time(r#"{"time":1721721600}"#),
+            DateTime::from_timestamp(1_721_721_600, 0)
+        );
+        assert_eq!(parse_server_time(r#"{"status":"ok"}"#), None);
+    }
+
+    #[test]
+    fn test_measure() {
+        let sent = DateTime::from_timestamp_millis(1_000_000).unwrap();
+        let sample = ClockSample::measure(
+            sent,
+            sent + TimeDelta::milliseconds(550),
+            sent + TimeDelta::milliseconds(100),
+        );
+        assert_eq!(sample.round_trip, TimeDelta::milliseconds(100));
+        assert_eq!(sample.offset, TimeDelta::milliseconds(500));
+    }
+
+    #[test]
+    fn test_warn_and_halt() {
+        let mut guard = guard();
+        assert_eq!(guard.update(), None);
+        guard.set_sample(offset(100));
+        assert_eq!(guard.update(), None);
+
+        guard.set_sample(offset(-300));
+        assert_eq!(guard.update().unwrap().severity, Severity::Warning);
+        assert!(guard.permission().entries);
+        assert_eq!(guard.update(), None);
+
+        guard.set_sample(offset(1500));
+        let halted = guard.update().unwrap();
+        assert_eq!(halted.severity, Severity::Critical);
+        assert_eq!(halted.condition.as_deref(), Some(CONDITION));
+        assert!(!guard.permission().entries);
+
+        guard.set_sample(offset(20));
+        let recovered = guard.update().unwrap();
+        assert!(recovered.resolved);
+        assert!(guard.permission().entries);
+    }
+}
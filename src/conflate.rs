@@ -0,0 +1,157 @@
+//! A conflating channel: holds at most one value per key, each send replacing whatever is still
+//! waiting under its key, so a slow receiver only ever sees the latest value of each rather than
+//! working through a backlog. Keys are handed out in the order they first became pending.
+
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::Mutex;
+use tokio::sync::Notify;
+
+#[derive(Debug)]
+struct Slots<T> {
+    latest: HashMap<String, T>,
+    pending: VecDeque<String>,
+    replaced: u64,
+    sender_dropped: bool,
+    receiver_dropped: bool,
+}
+
+#[derive(Debug)]
+struct Shared<T> {
+    slots: Mutex<Slots<T>>,
+    notify: Notify,
+}
+
+impl<T> Shared<T> {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Slots<T>> {
+        self.slots.lock().expect("conflating channel lock poisoned")
+    }
+}
+
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(Shared {
+        slots: Mutex::new(Slots {
+            latest: HashMap::new(),
+            pending: VecDeque::new(),
+            replaced: 0,
+            sender_dropped: false,
+            receiver_dropped: false,
+        }),
+        notify: Notify::new(),
+    });
+    (
+        Sender {
+            shared: shared.clone(),
+        },
+        Receiver { shared },
+    )
+}
+
+#[derive(Debug)]
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Sender<T> {
+    /// Make `value` the latest under `key`, replacing any not yet received. Gives the value back
+    /// once the receiver is gone.
+    pub fn send(&self, key: String, value: T) -> Result<(), T> {
+        let mut slots = self.shared.lock();
+        if slots.receiver_dropped {
+            return Err(value);
+        }
+        if slots.latest.contains_key(&key) {
+            slots.replaced += 1;
+        } else {
+            slots.pending.push_back(key.clone());
+        }
+        slots.latest.insert(key, value);
+        drop(slots);
+        self.shared.notify.notify_one();
+        Ok(())
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        self.shared.lock().sender_dropped = true;
+        self.shared.notify.notify_one();
+    }
+}
+
+#[derive(Debug)]
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Receiver<T> {
+    /// The latest value under the longest waiting key, or `None` once the sender is gone and
+    /// nothing is left.
+    pub async fn recv(&mut self) -> Option<T> {
+        loop {
+            {
+                let mut slots = self.shared.lock();
+                if let Some(key) = slots.pending.pop_front() {
+                    return slots.latest.remove(&key);
+                }
+                if slots.sender_dropped {
+                    return None;
+                }
+            }
+            self.shared.notify.notified().await;
+        }
+    }
+
+    /// Values replaced before being received so far.
+    pub fn replaced(&self) -> u64 {
+        self.shared.lock().replaced
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.shared.lock().receiver_dropped = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_keeps_only_the_latest_per_key() {
+        let (sender, mut receiver) = channel();
+        for (key, value) in [("btc", 1), ("eth", 2), ("btc", 3), ("btc", 4)] {
+            sender.send(key.to_owned(), value).unwrap();
+        }
+        assert_eq!(receiver.recv().await, Some(4));
+        assert_eq!(receiver.recv().await, Some(2));
+        assert_eq!(receiver.replaced(), 2);
+
+        // Nothing pending, so nothing to receive yet
+        let waited = tokio::time::timeout(Duration::from_millis(10), receiver.recv()).await;
+        assert!(waited.is_err());
+        sender.send("eth".to_owned(), 5).unwrap();
+        assert_eq!(receiver.recv().await, Some(5));
+    }
+
+    #[tokio::test]
+    async fn test_closes_with_either_end() {
+        let (sender, mut receiver) = channel();
+        sender.send("btc".to_owned(), 1).unwrap();
+        let waiting = tokio::spawn(async move {
+            let first = receiver.recv().await;
+            (first, receiver.recv().await)
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        drop(sender);
+        assert_eq!(waiting.await.unwrap(), (Some(1), None));
+
+        let (sender, receiver) = channel();
+        drop(receiver);
+        assert_eq!(sender.send("btc".to_owned(), 1), Err(1));
+    }
+}
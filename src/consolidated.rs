@@ -0,0 +1,219 @@
+//! A consolidated book, [`[consolidated]`](crate::config::ConsolidatedConfig), merging the top
+//! `depth` levels of every subscribed venue's latest book of an instrument into one, with levels
+//! at the same price added together. Venues name the same instrument differently, so books are
+//! keyed by its [canonical](canonical) name.
+//!
+//! Both views are kept: each venue's own features and the consolidated book's. Orders still go
+//! to the venue the book came from, so signals trade on that venue's top of book with the
+//! [imbalance](with_imbalance) of the liquidity visible across every venue.
+
+use barter_data::subscription::book::Level;
+use barter_data::subscription::book::OrderBook;
+use barter_data::subscription::book::OrderBookSide;
+use barter_integration::model::instrument::Instrument;
+use barter_integration::model::Side;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use crate::config::ConsolidatedConfig;
+use crate::config::DepthConfig;
+use crate::config::Weighting;
+use crate::features::Features;
+
+/// The name an instrument goes by across venues: its base, its quote with dollar stablecoins
+/// counted as dollars, and its kind.
+pub fn canonical(instrument: &Instrument) -> String {
+    let quote = match instrument.quote.as_ref() {
+        "usdt" | "usdc" => "usd",
+        quote => quote,
+    };
+    format!("{}_{} {}", instrument.base, quote, instrument.kind)
+}
+
+/// `venue` features with the order imbalance, and its term structure, of the `consolidated`
+/// book's.
+pub fn with_imbalance(venue: Features, consolidated: &Features) -> Features {
+    Features {
+        voi: consolidated.voi,
+        oir: consolidated.oir,
+        oir_top1: consolidated.oir_top1,
+        oir_top5: consolidated.oir_top5,
+        oir_top20: consolidated.oir_top20,
+        oir_10bps: consolidated.oir_10bps,
+        oir_50bps: consolidated.oir_50bps,
+        ..venue
+    }
+}
+
+#[derive(Debug)]
+pub struct ConsolidatedBook {
+    depth: usize,
+    /// The window of the merged levels the imbalance is summed over, and how they are weighted.
+    window: DepthConfig,
+    weighting: Weighting,
+    /// The top levels of each venue's latest book, by canonical instrument, then venue.
+    books: HashMap<String, HashMap<String, OrderBook>>,
+}
+
+impl ConsolidatedBook {
+    pub fn new(config: &ConsolidatedConfig) -> Self {
+        Self {
+            depth: config.depth,
+            window: DepthConfig::default(),
+            weighting: Weighting::Amount,
+            books: HashMap::new(),
+        }
+    }
+
+    /// Sum the imbalance of each book over the `window`, weighted as `weighting` says, as for a
+    /// single venue's.
+    pub fn with_window(mut self, window: DepthConfig, weighting: Weighting) -> Self {
+        self.window = window;
+        self.weighting = weighting;
+        self
+    }
+
+    /// Record `exchange`'s latest `book` of `instrument`, returning the instrument's canonical
+    /// name.
+    pub fn update(&mut self, exchange: &str, instrument: &Instrument, book: &OrderBook) -> String {
+        let canonical = canonical(instrument);
+        let top = |side: &OrderBookSide| {
+            OrderBookSide::new(side.side, side.levels.iter().take(self.depth).copied())
+        };
+        let top = OrderBook {
+            last_update_time: book.last_update_time,
+            bids: top(&book.bids),
+            asks: top(&book.asks),
+        };
+        self.books
+            .entry(canonical.clone())
+            .or_default()
+            .insert(exchange.to_owned(), top);
+        canonical
+    }
+
+    /// The venues with a book of `canonical`.
+    pub fn venues(&self, canonical: &str) -> Vec<&str> {
+        self.books
+            .get(canonical)
+            .into_iter()
+            .flat_map(HashMap::keys)
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// The features of `exchange`'s own top levels of `canonical`.
+    pub fn venue(&self, canonical: &str, exchange: &str) -> Option<Features> {
+        let book = self.books.get(canonical)?.get(exchange)?;
+        Features::from_order_book_within(book, &self.window, self.weighting)
+    }
+
+    /// Every venue's top levels of `canonical` merged, best first, or `None` before any book of
+    /// it.
+    pub fn book(&self, canonical: &str) -> Option<OrderBook> {
+        let books = self.books.get(canonical)?;
+        let merged = |side: Side, levels: &dyn Fn(&OrderBook) -> &OrderBookSide| {
+            let mut merged: Vec<Level> = books
+                .values()
+                .flat_map(|book| levels(book).levels.iter().copied())
+                .collect();
+            merged.sort_by(|a, b| match side {
+                Side::Buy => b.price.total_cmp(&a.price),
+                Side::Sell => a.price.total_cmp(&b.price),
+            });
+            merged.dedup_by(|level, kept| {
+                let same = level.price.total_cmp(&kept.price) == Ordering::Equal;
+                if same {
+                    kept.amount += level.amount;
+                }
+                same
+            });
+            OrderBookSide::new(side, merged)
+        };
+        Some(OrderBook {
+            last_update_time: books.values().map(|book| book.last_update_time).max()?,
+            bids: merged(Side::Buy, &|book| &book.bids),
+            asks: merged(Side::Sell, &|book| &book.asks),
+        })
+    }
+
+    /// The features of the consolidated book of `canonical`.
+    pub fn features(&self, canonical: &str) -> Option<Features> {
+        Features::from_order_book_within(&self.book(canonical)?, &self.window, self.weighting)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use barter_integration::model::instrument::kind::InstrumentKind;
+    use chrono::DateTime;
+
+    use super::*;
+
+    fn book(bids: &[(f64, f64)], asks: &[(f64, f64)]) -> OrderBook {
+        OrderBook {
+            last_update_time: DateTime::from_timestamp_millis(0).unwrap(),
+            bids: OrderBookSide::new(Side::Buy, bids.iter().copied()),
+            asks: OrderBookSide::new(Side::Sell, asks.iter().copied()),
+        }
+    }
+
+    fn consolidated() -> ConsolidatedBook {
+        ConsolidatedBook::new(&ConsolidatedConfig { depth: 2 })
+    }
+
+    #[test]
+    fn test_canonical() {
+        let perpetual = |quote| Instrument::from(("btc", quote, InstrumentKind::Perpetual));
+        assert_eq!(canonical(&perpetual("usdt")), canonical(&perpetual("usd")));
+        assert_ne!(canonical(&perpetual("eur")), canonical(&perpetual("usd")));
+    }
+
+    #[test]
+    fn test_merges_top_levels() {
+        let mut consolidated = consolidated();
+        let usd = Instrument::from(("btc", "usd", InstrumentKind::Perpetual));
+        let usdt = Instrument::from(("btc", "usdt", InstrumentKind::Perpetual));
+        let canonical = consolidated.update(
+            "aevo",
+            &usd,
+            &book(&[(100.0, 1.0), (99.0, 2.0), (98.0, 5.0)], &[(101.0, 1.0)]),
+        );
+        assert_eq!(
+            consolidated.update(
+                "binance",
+                &usdt,
+                &book(&[(100.5, 1.0), (99.0, 1.0)], &[(101.0, 2.0), (102.0, 1.0)]),
+            ),
+            canonical
+        );
+        let mut venues = consolidated.venues(&canonical);
+        venues.sort_unstable();
+        assert_eq!(venues, ["aevo", "binance"]);
+
+        // Below the top two levels, aevo's 98 is left out
+        let merged = consolidated.book(&canonical).unwrap();
+        let levels = |side: &OrderBookSide| -> Vec<(f64, f64)> {
+            side.levels
+                .iter()
+                .map(|level| (level.price, level.amount))
+                .collect()
+        };
+        assert_eq!(
+            levels(&merged.bids),
+            [(100.5, 1.0), (100.0, 1.0), (99.0, 3.0)]
+        );
+        assert_eq!(levels(&merged.asks), [(101.0, 3.0), (102.0, 1.0)]);
+
+        let aevo = consolidated.venue(&canonical, "aevo").unwrap();
+        let features = consolidated.features(&canonical).unwrap();
+        assert_eq!((aevo.bid, aevo.voi), (100.0, 2.0));
+        assert_eq!((features.bid, features.ask), (100.5, 101.0));
+        assert_eq!(features.voi, 1.0);
+        let traded = with_imbalance(aevo, &features);
+        assert_eq!(
+            (traded.bid, traded.voi, traded.oir),
+            (100.0, 1.0, features.oir)
+        );
+    }
+}
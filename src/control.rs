@@ -0,0 +1,146 @@
+//! Runtime switches operators flip while the bot runs, shared between the trading loop and the
+//! control surfaces.
+//!
+//! While paused no strategy is evaluated and nothing is traded. Book updates are still logged and
+//! equity is still marked, so dashboards stay live. Flattening sells every open position on the
+//! next update and pauses trading, so the strategies don't reopen them straight away.
+
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use tracing::info;
+
+use crate::RiskParams;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ControlError {
+    #[error("{name} must be a fraction between 0 and 1, got {value}")]
+    OutOfRange { name: &'static str, value: f64 },
+}
+
+#[derive(Debug, Default)]
+struct Switches {
+    paused: AtomicBool,
+    flatten: AtomicBool,
+    risk: Mutex<RiskParams>,
+}
+
+/// A handle to the shared switches; clones control the same bot.
+#[derive(Debug, Clone, Default)]
+pub struct Control {
+    switches: Arc<Switches>,
+}
+
+impl Control {
+    pub fn is_paused(&self) -> bool {
+        self.switches.paused.load(Ordering::Relaxed)
+    }
+
+    /// Stop trading until resumed.
+    pub fn pause(&self) {
+        if !self.switches.paused.swap(true, Ordering::Relaxed) {
+            info!("Trading paused");
+        }
+    }
+
+    pub fn resume(&self) {
+        if self.switches.paused.swap(false, Ordering::Relaxed) {
+            info!("Trading resumed");
+        }
+    }
+
+    /// Ask the trading loop to close every open position, and pause trading.
+    pub fn flatten(&self) {
+        info!("Flattening requested");
+        self.switches.flatten.store(true, Ordering::Relaxed);
+        self.pause();
+    }
+
+    /// Whether flattening was requested since the last call.
+    pub fn take_flatten(&self) -> bool {
+        self.switches.flatten.swap(false, Ordering::Relaxed)
+    }
+
+    pub fn risk(&self) -> RiskParams {
+        *self
+            .switches
+            .risk
+            .lock()
+            .expect("risk params lock poisoned")
+    }
+
+    pub fn set_risk(&self, risk: RiskParams) {
+        info!(
+            "Risk params set to {:.2}% take profit, {:.2}% stop loss",
+            risk.take_profit * 100.0,
+            risk.stop_loss * 100.0
+        );
+        *self
+            .switches
+            .risk
+            .lock()
+            .expect("risk params lock poisoned") = risk;
+    }
+
+    /// Change the risk params given, keeping the others, and return the result. Nothing changes
+    /// unless every value is a fraction between 0 and 1.
+    pub fn update_risk(
+        &self,
+        take_profit: Option<f64>,
+        stop_loss: Option<f64>,
+    ) -> Result<RiskParams, ControlError> {
+        let mut risk = self.risk();
+        risk.take_profit = take_profit.unwrap_or(risk.take_profit);
+        risk.stop_loss = stop_loss.unwrap_or(risk.stop_loss);
+        check_risk(&risk)?;
+        self.set_risk(risk);
+        Ok(risk)
+    }
+}
+
+/// Whether every risk param is a fraction between 0 and 1.
+pub fn check_risk(risk: &RiskParams) -> Result<(), ControlError> {
+    for (name, value) in [
+        ("take_profit", risk.take_profit),
+        ("stop_loss", risk.stop_loss),
+    ] {
+        if !(value > 0.0 && value < 1.0) {
+            return Err(ControlError::OutOfRange { name, value });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clones_share_switches() {
+        let control = Control::default();
+        let remote = control.clone();
+        assert!(!control.is_paused());
+        remote.pause();
+        assert!(control.is_paused());
+        control.resume();
+        assert!(!remote.is_paused());
+
+        remote.flatten();
+        assert!(control.is_paused());
+        assert!(control.take_flatten());
+        assert!(!control.take_flatten());
+
+        let risk = RiskParams {
+            take_profit: 0.05,
+            stop_loss: 0.01,
+        };
+        remote.set_risk(risk);
+        assert_eq!(control.risk(), risk);
+
+        let updated = remote.update_risk(None, Some(0.02)).unwrap();
+        assert_eq!((updated.take_profit, updated.stop_loss), (0.05, 0.02));
+        assert!(remote.update_risk(Some(0.01), Some(1.5)).is_err());
+        assert_eq!(control.risk(), updated);
+    }
+}
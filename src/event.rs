@@ -0,0 +1,332 @@
+use barter_integration::model::Side;
+use chrono::DateTime;
+use chrono::Utc;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::features::Features;
+use crate::session::SessionSummary;
+use crate::strategy::Signal;
+use crate::Fill;
+
+/// Venue of fills booked by the paper account.
+pub const PAPER_VENUE: &str = "paper";
+
+/// How an order meets the book.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderType {
+    /// Rests at the touch, as signal orders do.
+    Limit,
+    /// Crosses the spread, as take profit and stop loss exits do.
+    Market,
+}
+
+/// Where and how a fill was executed, and the mid price when the decision to trade was made.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Execution {
+    pub venue: String,
+    pub order_type: OrderType,
+    pub decision_price: f64,
+    /// For an order that rested in the book, the size [estimated](crate::queue) still ahead of it
+    /// in its level's queue when it filled.
+    #[serde(default)]
+    pub queue_ahead: Option<f64>,
+}
+
+impl Execution {
+    /// A fill of the paper account, which trades against the book it decided on.
+    pub fn paper(order_type: OrderType, decision_price: f64) -> Self {
+        Self {
+            venue: PAPER_VENUE.to_owned(),
+            order_type,
+            decision_price,
+            queue_ahead: None,
+        }
+    }
+
+    /// What the fill cost relative to the decision price, in basis points. Positive is worse.
+    pub fn slippage_bps(&self, fill: &Fill) -> f64 {
+        let slippage = match fill.side {
+            Side::Buy => fill.price - self.decision_price,
+            Side::Sell => self.decision_price - fill.price,
+        };
+        slippage / self.decision_price * 10_000.0
+    }
+}
+
+/// When the book an event was decided on was stamped by the exchange and received from the feed.
+/// Alongside an event's `time`, when the bot handled it, this lets backtests replay in exchange
+/// time while latency analysis works from receipt time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BookTime {
+    pub exchange_time: DateTime<Utc>,
+    pub received_time: DateTime<Utc>,
+}
+
+/// How urgently an [alert](Alert) needs someone's attention.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Info,
+    Warning,
+    Critical,
+}
+
+/// Something an operator should hear about, such as the feed degrading or recovering.
+///
+/// Alerts about a lasting condition, such as the feed being degraded, name it in `condition`, so
+/// every alert raised while it lasts can be told apart from other conditions' and the one raised
+/// as it clears is `resolved`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Alert {
+    pub severity: Severity,
+    pub message: String,
+    #[serde(default)]
+    pub condition: Option<String>,
+    #[serde(default)]
+    pub resolved: bool,
+}
+
+/// Something the bot did, published to the configured [sinks](crate::sink) as JSON tagged by
+/// `type`.
+///
+/// Orders are only placed for buy and sell signals; take profit and stop loss exits show up as
+/// fills alone. Features are reported for every book update, whether or not anything traded.
+///
+/// Features, signals, orders and fills carry the [`BookTime`] of the update they happened on, once
+/// [stamped](Event::stamp). Fills booked from gateway reports carry the update they were booked
+/// on rather than the one the order was decided on.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    Features {
+        time: DateTime<Utc>,
+        symbol: &'static str,
+        features: Features,
+        #[serde(flatten)]
+        book_time: Option<BookTime>,
+    },
+    Signal {
+        time: DateTime<Utc>,
+        symbol: &'static str,
+        strategy: String,
+        signal: Signal,
+        features: Features,
+        #[serde(flatten)]
+        book_time: Option<BookTime>,
+    },
+    Order {
+        time: DateTime<Utc>,
+        symbol: &'static str,
+        strategy: String,
+        side: Side,
+        price: f64,
+        size: f64,
+        #[serde(flatten)]
+        book_time: Option<BookTime>,
+    },
+    Fill {
+        time: DateTime<Utc>,
+        symbol: &'static str,
+        strategy: String,
+        /// The allocator sleeve that traded, telling apart members running the same strategy.
+        sleeve: usize,
+        #[serde(flatten)]
+        execution: Execution,
+        #[serde(flatten)]
+        fill: Fill,
+        #[serde(flatten)]
+        book_time: Option<BookTime>,
+    },
+    Equity {
+        time: DateTime<Utc>,
+        symbol: &'static str,
+        portfolio_value: f64,
+    },
+    /// A sleeve's new share of equity after rebalancing, and the cash moved to reach it.
+    Rebalance {
+        time: DateTime<Utc>,
+        symbol: &'static str,
+        strategy: String,
+        sleeve: usize,
+        weight: f64,
+        capital: f64,
+        transfer: f64,
+    },
+    /// Summary of the trading session that just rolled over.
+    Session {
+        time: DateTime<Utc>,
+        symbol: &'static str,
+        #[serde(flatten)]
+        summary: SessionSummary,
+    },
+    Alert {
+        time: DateTime<Utc>,
+        symbol: &'static str,
+        #[serde(flatten)]
+        alert: Alert,
+    },
+}
+
+impl Event {
+    /// The `type` tag, used to route events to topics.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Features { .. } => "features",
+            Self::Signal { .. } => "signal",
+            Self::Order { .. } => "order",
+            Self::Fill { .. } => "fill",
+            Self::Equity { .. } => "equity",
+            Self::Rebalance { .. } => "rebalance",
+            Self::Session { .. } => "session",
+            Self::Alert { .. } => "alert",
+        }
+    }
+
+    pub fn time(&self) -> DateTime<Utc> {
+        match self {
+            Self::Features { time, .. }
+            | Self::Signal { time, .. }
+            | Self::Order { time, .. }
+            | Self::Fill { time, .. }
+            | Self::Equity { time, .. }
+            | Self::Rebalance { time, .. }
+            | Self::Session { time, .. }
+            | Self::Alert { time, .. } => *time,
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Self::Features { symbol, .. }
+            | Self::Signal { symbol, .. }
+            | Self::Order { symbol, .. }
+            | Self::Fill { symbol, .. }
+            | Self::Equity { symbol, .. }
+            | Self::Rebalance { symbol, .. }
+            | Self::Session { symbol, .. }
+            | Self::Alert { symbol, .. } => symbol,
+        }
+    }
+
+    pub fn book_time(&self) -> Option<BookTime> {
+        match self {
+            Self::Features { book_time, .. }
+            | Self::Signal { book_time, .. }
+            | Self::Order { book_time, .. }
+            | Self::Fill { book_time, .. } => *book_time,
+            Self::Equity { .. }
+            | Self::Rebalance { .. }
+            | Self::Session { .. }
+            | Self::Alert { .. } => None,
+        }
+    }
+
+    /// Stamp the book update the event happened on, unless it has been already or isn't about a
+    /// book update.
+    pub fn stamp(&mut self, book: BookTime) {
+        match self {
+            Self::Features { book_time, .. }
+            | Self::Signal { book_time, .. }
+            | Self::Order { book_time, .. }
+            | Self::Fill { book_time, .. } => {
+                book_time.get_or_insert(book);
+            }
+            Self::Equity { .. }
+            | Self::Rebalance { .. }
+            | Self::Session { .. }
+            | Self::Alert { .. } => {}
+        }
+    }
+
+    pub fn to_json(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("events always serialize")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+
+    use super::*;
+
+    #[test]
+    fn test_json() {
+        let time = DateTime::from_timestamp_millis(0).unwrap();
+        let fill = Event::Fill {
+            time,
+            symbol: "BTC/USDT",
+            strategy: "imbalance".to_owned(),
+            sleeve: 0,
+            execution: Execution::paper(OrderType::Limit, 99.5),
+            fill: Fill {
+                side: Side::Buy,
+                price: 100.0,
+                size: 0.001,
+                fee: 0.0005,
+            },
+            book_time: None,
+        };
+
+        let json: serde_json::Value = serde_json::from_slice(&fill.to_json()).unwrap();
+        assert_eq!(json["venue"], "paper");
+        assert_eq!(json["order_type"], "limit");
+        assert_eq!(json["decision_price"], 99.5);
+        assert_eq!(fill.kind(), "fill");
+        assert_eq!(json["type"], "fill");
+        assert_eq!(json["time"], "1970-01-01T00:00:00Z");
+        assert_eq!(json["price"], 100.0);
+        assert_eq!(json["side"], "Buy");
+
+        let signal = Event::Signal {
+            time,
+            symbol: "BTC/USDT",
+            strategy: "imbalance".to_owned(),
+            signal: Signal::Sell,
+            features: Features::default(),
+            book_time: None,
+        };
+        let json: serde_json::Value = serde_json::from_slice(&signal.to_json()).unwrap();
+        assert_eq!(json["signal"], "sell");
+        assert_eq!(json["features"]["oir"], 0.0);
+
+        let tick = Event::Features {
+            time,
+            symbol: "BTC/USDT",
+            features: Features::default(),
+            book_time: None,
+        };
+        let json: serde_json::Value = serde_json::from_slice(&tick.to_json()).unwrap();
+        assert_eq!(json["type"], "features");
+        assert_eq!(json["features"]["spread"], 0.0);
+        assert!(json.get("exchange_time").is_none());
+
+        let mut stamped = tick.clone();
+        let book = BookTime {
+            exchange_time: time,
+            received_time: time + TimeDelta::milliseconds(12),
+        };
+        stamped.stamp(book);
+        assert_eq!(stamped.book_time(), Some(book));
+        let json: serde_json::Value = serde_json::from_slice(&stamped.to_json()).unwrap();
+        assert_eq!(json["exchange_time"], "1970-01-01T00:00:00Z");
+        assert_eq!(json["received_time"], "1970-01-01T00:00:00.012Z");
+
+        let alert = Event::Alert {
+            time,
+            symbol: "BTC/USDT",
+            alert: Alert {
+                severity: Severity::Critical,
+                message: "Order book feed degraded".to_owned(),
+                condition: Some("feed_degraded".to_owned()),
+                resolved: false,
+            },
+        };
+        let json: serde_json::Value = serde_json::from_slice(&alert.to_json()).unwrap();
+        assert_eq!(json["type"], "alert");
+        assert_eq!(json["severity"], "critical");
+        assert_eq!(json["message"], "Order book feed degraded");
+        assert_eq!(json["condition"], "feed_degraded");
+    }
+}
@@ -0,0 +1,283 @@
+//! Labelled feature export to Parquet for offline research.
+//!
+//! Every feature vector is written with its forward mid price returns at each configured horizon,
+//! counted in book updates. Rows are partitioned by day, Hive style, so the output directory can be
+//! read directly as a dataset:
+//!
+//! ```text
+//! features/date=2024-06-01/part-1717200000000.parquet
+//! ```
+//!
+//! with columns `time`, the names in [`FEATURE_NAMES`](crate::features::FEATURE_NAMES), then `return_<horizon>` for each horizon.
+//! The last updates of a run have no future to label them against, so their returns are null.
+
+use arrow_array::Float64Array;
+use arrow_array::RecordBatch;
+use arrow_schema::ArrowError;
+use arrow_schema::DataType;
+use arrow_schema::Field;
+use arrow_schema::Schema;
+use chrono::DateTime;
+use chrono::NaiveDate;
+use chrono::Utc;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::errors::ParquetError;
+use parquet::file::properties::WriterProperties;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::config::ExportConfig;
+use crate::features::Features;
+use crate::feed::feature_columns;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ExportError {
+    #[error("failed to write feature export: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("failed to build feature batch: {0}")]
+    Arrow(#[from] ArrowError),
+
+    #[error("failed to write feature parquet: {0}")]
+    Parquet(#[from] ParquetError),
+}
+
+#[derive(Debug, Clone)]
+struct Row {
+    time: DateTime<Utc>,
+    features: Features,
+    returns: Vec<Option<f64>>,
+}
+
+/// Buffers feature vectors until their labels are known and writes them out in daily partitions.
+#[derive(Debug)]
+pub struct FeatureExporter {
+    dir: PathBuf,
+    horizons: Vec<usize>,
+    rows_per_file: usize,
+    /// Updates still waiting for their furthest label, oldest first.
+    pending: VecDeque<(DateTime<Utc>, Features)>,
+    /// Labelled rows of the current partition not yet written.
+    rows: Vec<Row>,
+}
+
+impl FeatureExporter {
+    pub fn new(dir: impl Into<PathBuf>, config: &ExportConfig) -> Self {
+        Self {
+            dir: dir.into(),
+            horizons: config.horizons.clone(),
+            rows_per_file: config.rows_per_file.max(1),
+            pending: VecDeque::new(),
+            rows: Vec::new(),
+        }
+    }
+
+    fn max_horizon(&self) -> usize {
+        self.horizons.iter().copied().max().unwrap_or(0)
+    }
+
+    pub fn record(&mut self, time: DateTime<Utc>, features: &Features) -> Result<(), ExportError> {
+        self.pending.push_back((time, *features));
+        if self.pending.len() > self.max_horizon() {
+            self.label_oldest()?;
+        }
+        Ok(())
+    }
+
+    /// Label and write everything still buffered.
+    pub fn finish(mut self) -> Result<(), ExportError> {
+        while !self.pending.is_empty() {
+            self.label_oldest()?;
+        }
+        self.flush()
+    }
+
+    fn label_oldest(&mut self) -> Result<(), ExportError> {
+        let Some(&(time, features)) = self.pending.front() else {
+            return Ok(());
+        };
+        let returns = self
+            .horizons
+            .iter()
+            .map(|&horizon| {
+                let (_, future) = self.pending.get(horizon)?;
+                (features.mid_price != 0.0).then(|| future.mid_price / features.mid_price - 1.0)
+            })
+            .collect();
+        self.pending.pop_front();
+
+        if self.rows.first().map(|row| row.time.date_naive()) != Some(time.date_naive()) {
+            self.flush()?;
+        }
+        self.rows.push(Row {
+            time,
+            features,
+            returns,
+        });
+        if self.rows.len() >= self.rows_per_file {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), ExportError> {
+        let Some(first) = self.rows.first() else {
+            return Ok(());
+        };
+
+        let dir = self.dir.join(partition(first.time.date_naive()));
+        std::fs::create_dir_all(&dir)?;
+        let file =
+            File::create(dir.join(format!("part-{}.parquet", first.time.timestamp_millis())))?;
+
+        let batch = self.batch()?;
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build();
+        let mut writer = ArrowWriter::try_new(file, batch.schema(), Some(properties))?;
+        writer.write(&batch)?;
+        writer.close()?;
+
+        self.rows.clear();
+        Ok(())
+    }
+
+    fn batch(&self) -> Result<RecordBatch, ArrowError> {
+        let (mut fields, mut columns) =
+            feature_columns(self.rows.iter().map(|row| (row.time, &row.features)));
+
+        for (index, horizon) in self.horizons.iter().enumerate() {
+            fields.push(Field::new(
+                format!("return_{horizon}"),
+                DataType::Float64,
+                true,
+            ));
+            columns.push(Arc::new(Float64Array::from_iter(
+                self.rows.iter().map(|row| row.returns[index]),
+            )));
+        }
+
+        RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)
+    }
+}
+
+fn partition(date: NaiveDate) -> String {
+    format!("date={}", date.format("%Y-%m-%d"))
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::Array;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use std::time::SystemTime;
+
+    use super::*;
+    use crate::features::FEATURE_COUNT;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "fit-{}-{}-{}",
+            name,
+            std::process::id(),
+            SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ))
+    }
+
+    fn read(path: PathBuf) -> RecordBatch {
+        let file = File::open(path).unwrap();
+        let mut reader = ParquetRecordBatchReaderBuilder::try_new(file)
+            .unwrap()
+            .build()
+            .unwrap();
+        reader.next().unwrap().unwrap()
+    }
+
+    fn column(batch: &RecordBatch, name: &str) -> Vec<Option<f64>> {
+        let column = batch
+            .column_by_name(name)
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        (0..column.len())
+            .map(|row| column.is_valid(row).then(|| column.value(row)))
+            .collect()
+    }
+
+    fn mid(mid_price: f64) -> Features {
+        Features {
+            mid_price,
+            ..Features::default()
+        }
+    }
+
+    #[test]
+    fn test_labels_and_partitions() {
+        let dir = temp_dir("export");
+        let config = ExportConfig {
+            horizons: vec![1, 2],
+            rows_per_file: 100,
+        };
+        let mut exporter = FeatureExporter::new(&dir, &config);
+
+        let day = DateTime::from_timestamp_millis(1_717_200_000_000).unwrap();
+        exporter.record(day, &mid(100.0)).unwrap();
+        exporter
+            .record(day + chrono::Duration::seconds(1), &mid(101.0))
+            .unwrap();
+        let next_day = day + chrono::Duration::days(1);
+        exporter.record(next_day, &mid(99.0)).unwrap();
+        exporter.finish().unwrap();
+
+        let first = read(dir.join("date=2024-06-01/part-1717200000000.parquet"));
+        assert_eq!(first.num_rows(), 2);
+        assert_eq!(first.num_columns(), 1 + FEATURE_COUNT + 2);
+        assert_eq!(column(&first, "mid_price"), [Some(100.0), Some(101.0)]);
+        let return_1 = column(&first, "return_1");
+        assert!((return_1[0].unwrap() - 0.01).abs() < 1e-12);
+        assert!((return_1[1].unwrap() - (99.0 / 101.0 - 1.0)).abs() < 1e-12);
+        let return_2 = column(&first, "return_2");
+        assert!((return_2[0].unwrap() + 0.01).abs() < 1e-12);
+        // Too close to the end of the run to be labelled
+        assert_eq!(return_2[1], None);
+
+        let second = read(dir.join(format!(
+            "date=2024-06-02/part-{}.parquet",
+            next_day.timestamp_millis()
+        )));
+        assert_eq!(column(&second, "return_1"), [None]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_rows_per_file() {
+        let dir = temp_dir("export-rows");
+        let config = ExportConfig {
+            horizons: vec![1],
+            rows_per_file: 2,
+        };
+        let mut exporter = FeatureExporter::new(&dir, &config);
+
+        let start = DateTime::from_timestamp_millis(1_717_200_000_000).unwrap();
+        for tick in 0..5 {
+            exporter
+                .record(start + chrono::Duration::seconds(tick), &mid(100.0))
+                .unwrap();
+        }
+        exporter.finish().unwrap();
+
+        let files = std::fs::read_dir(dir.join("date=2024-06-01"))
+            .unwrap()
+            .count();
+        assert_eq!(files, 3);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,421 @@
+use barter_data::subscription::book::Level;
+use barter_data::subscription::book::OrderBook;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::config::DepthConfig;
+use crate::config::Weighting;
+use crate::mbo::OrderStats;
+use crate::TradingState;
+
+/// Number of values in [`Features::to_array`].
+pub const FEATURE_COUNT: usize = 19;
+
+/// Names of the values in [`Features::to_array`].
+pub const FEATURE_NAMES: [&str; FEATURE_COUNT] = [
+    "bid",
+    "ask",
+    "mid_price",
+    "microprice",
+    "spread",
+    "voi",
+    "oir",
+    "mpb",
+    "flow",
+    "liquidations",
+    "order_imbalance",
+    "avg_order_size",
+    "persistent_oir",
+    "oir_top1",
+    "oir_top5",
+    "oir_top20",
+    "oir_10bps",
+    "oir_50bps",
+    "volatility",
+];
+
+/// The depth windows of the OIR term structure, nearest the touch first: the best 1, 5 and 20
+/// levels, then everything within 10 and 50 bps of the mid.
+pub const OIR_HORIZONS: [DepthConfig; 5] = [
+    DepthConfig {
+        levels: Some(1),
+        within_bps: None,
+    },
+    DepthConfig {
+        levels: Some(5),
+        within_bps: None,
+    },
+    DepthConfig {
+        levels: Some(20),
+        within_bps: None,
+    },
+    DepthConfig {
+        levels: None,
+        within_bps: Some(10.0),
+    },
+    DepthConfig {
+        levels: None,
+        within_bps: Some(50.0),
+    },
+];
+
+/// Signal inputs derived from a single order book snapshot.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Features {
+    pub bid: f64,
+    pub ask: f64,
+    pub mid_price: f64,
+    /// Top of book prices weighted by the size resting on the opposite side.
+    pub microprice: f64,
+    pub spread: f64,
+    pub voi: f64,
+    pub oir: f64,
+    pub mpb: f64,
+    /// Imbalance of the [Hawkes](crate::signals::hawkes) buy and sell trade intensities, from -1
+    /// to 1, or zero without `[hawkes]`. Not derived from the book, so set by whoever counts the
+    /// trades.
+    #[serde(default)]
+    pub flow: f64,
+    /// Net forced buying over the [liquidation](crate::signals::liquidation) window, in units of
+    /// `cascade_volume`: one or more and shorts are being squeezed, minus one or less and longs
+    /// flushed. Zero without `[liquidations]`, and set by whoever counts them like `flow`.
+    #[serde(default)]
+    pub liquidations: f64,
+    /// Imbalance of the number of resting bids against asks, from -1 to 1, from an
+    /// [L3 book](crate::mbo). Zero on L2 books, which don't count orders.
+    #[serde(default)]
+    pub order_imbalance: f64,
+    /// The mean size of the L3 book's resting orders, or zero on L2 books.
+    #[serde(default)]
+    pub avg_order_size: f64,
+    /// OIR of the book's size weighted by how long it has rested, from
+    /// [persistence](crate::signals::persistence) tracking. Zero without `[persistence]`, and set
+    /// by whoever tracks the books like `flow`.
+    #[serde(default)]
+    pub persistent_oir: f64,
+    /// The OIR term structure, OIR over each of the [`OIR_HORIZONS`] in turn, whatever the
+    /// `[depth]` window. A book leaning one way at the touch and the other further out reads the
+    /// same as a flat one in the aggregate, but not here. Zero over a window with nothing in it.
+    #[serde(default)]
+    pub oir_top1: f64,
+    #[serde(default)]
+    pub oir_top5: f64,
+    #[serde(default)]
+    pub oir_top20: f64,
+    #[serde(default)]
+    pub oir_10bps: f64,
+    #[serde(default)]
+    pub oir_50bps: f64,
+    /// The [forecast](crate::volatility) standard deviation of the mid's log return over the
+    /// holding horizon. Zero without `[volatility]` or a forecast yet, and set by whoever
+    /// forecasts it like `flow`.
+    #[serde(default)]
+    pub volatility: f64,
+}
+
+impl Features {
+    /// Compute the features for `order_book`, or `None` if either side of the book is empty.
+    pub fn from_order_book(order_book: &OrderBook) -> Option<Self> {
+        Self::from_order_book_within(order_book, &DepthConfig::default(), Weighting::Amount)
+    }
+
+    /// Compute the features for `order_book` with its imbalance summed over the `depth` window
+    /// and weighted as `weighting` says, or `None` if either side of the book is empty.
+    pub fn from_order_book_within(
+        order_book: &OrderBook,
+        depth: &DepthConfig,
+        weighting: Weighting,
+    ) -> Option<Self> {
+        let best_bid = order_book.bids.levels.first()?;
+        let best_ask = order_book.asks.levels.first()?;
+        let bid: f64 = best_bid.price;
+        let ask: f64 = best_ask.price;
+        let spread: f64 = TradingState::calculate_spread(bid, ask);
+        let mid_price: f64 = (bid + ask) / 2.0;
+        let last_price: f64 = mid_price;
+        let microprice: f64 = calculate_microprice(bid, best_bid.amount, ask, best_ask.amount);
+
+        // Calculate volume order imbalance
+        let (voi, bid_volume, ask_volume) =
+            TradingState::calculate_voi_within(order_book, depth, weighting);
+
+        // Calculate Order Imbalance Ratio (OIR)
+        let oir: f64 = TradingState::calculate_oir(bid_volume, ask_volume);
+
+        // Calculate Mid-Price Basis (MPB)
+        let mpb: f64 = TradingState::calculate_mpb(last_price, mid_price);
+
+        let [oir_top1, oir_top5, oir_top20, oir_10bps, oir_50bps] =
+            oir_curve(order_book, weighting);
+
+        Some(Self {
+            bid,
+            ask,
+            mid_price,
+            microprice,
+            spread,
+            voi,
+            oir,
+            mpb,
+            flow: 0.0,
+            liquidations: 0.0,
+            order_imbalance: 0.0,
+            avg_order_size: 0.0,
+            persistent_oir: 0.0,
+            oir_top1,
+            oir_top5,
+            oir_top20,
+            oir_10bps,
+            oir_50bps,
+            volatility: 0.0,
+        })
+    }
+
+    /// The features as a vector, in declaration order.
+    pub fn to_array(&self) -> [f64; FEATURE_COUNT] {
+        [
+            self.bid,
+            self.ask,
+            self.mid_price,
+            self.microprice,
+            self.spread,
+            self.voi,
+            self.oir,
+            self.mpb,
+            self.flow,
+            self.liquidations,
+            self.order_imbalance,
+            self.avg_order_size,
+            self.persistent_oir,
+            self.oir_top1,
+            self.oir_top5,
+            self.oir_top20,
+            self.oir_10bps,
+            self.oir_50bps,
+            self.volatility,
+        ]
+    }
+
+    /// The features with the spread and MPB measured against `fair_price` rather than the mid.
+    pub fn with_fair_price(self, fair_price: f64) -> Self {
+        Self {
+            spread: TradingState::calculate_spread_at(self.bid, self.ask, fair_price),
+            mpb: TradingState::calculate_mpb(self.mid_price, fair_price),
+            ..self
+        }
+    }
+
+    /// The features with the market-by-order `stats` of the instrument's L3 book.
+    pub fn with_order_stats(self, stats: &OrderStats) -> Self {
+        Self {
+            order_imbalance: stats.order_imbalance(),
+            avg_order_size: stats.avg_order_size(),
+            ..self
+        }
+    }
+
+    /// The OIR term structure as a vector, in the order of the [`OIR_HORIZONS`].
+    pub fn oir_curve(&self) -> [f64; 5] {
+        [
+            self.oir_top1,
+            self.oir_top5,
+            self.oir_top20,
+            self.oir_10bps,
+            self.oir_50bps,
+        ]
+    }
+}
+
+/// OIR of `order_book` over each of the [`OIR_HORIZONS`], weighted as `weighting` says, or zero
+/// over a window with nothing in it.
+pub fn oir_curve(order_book: &OrderBook, weighting: Weighting) -> [f64; 5] {
+    OIR_HORIZONS.map(|horizon| {
+        let (_, bid_volume, ask_volume) =
+            TradingState::calculate_voi_within(order_book, &horizon, weighting);
+        if bid_volume + ask_volume <= 0.0 {
+            return 0.0;
+        }
+        TradingState::calculate_oir(bid_volume, ask_volume)
+    })
+}
+
+/// The best of `levels`, sorted best first, within the `depth` window around `mid_price`.
+pub fn within_depth<'a>(levels: &'a [Level], depth: &DepthConfig, mid_price: f64) -> &'a [Level] {
+    let mut levels = &levels[..depth
+        .levels
+        .map_or(levels.len(), |top| top.min(levels.len()))];
+    if let Some(bps) = depth.within_bps {
+        let within = levels
+            .iter()
+            .take_while(|level| ((level.price - mid_price) / mid_price * 10_000.0).abs() <= bps)
+            .count();
+        levels = &levels[..within];
+    }
+    levels
+}
+
+/// Volume-weighted mid of the best `levels` of `order_book`'s sides: the VWAP of each side,
+/// weighted by the size on the other like the [microprice](calculate_microprice), which it is
+/// with a single level. `None` if either side is empty.
+pub fn weighted_mid(order_book: &OrderBook, levels: usize) -> Option<f64> {
+    let side = |side: &[Level]| -> Option<(f64, f64)> {
+        let best = side.first()?;
+        let top = &side[..levels.clamp(1, side.len())];
+        let amount: f64 = top.iter().map(|level| level.amount).sum();
+        if amount <= 0.0 {
+            return Some((best.price, 0.0));
+        }
+        let notional: f64 = top.iter().map(|level| level.price * level.amount).sum();
+        Some((notional / amount, amount))
+    };
+    let (bid, bid_amount) = side(&order_book.bids.levels)?;
+    let (ask, ask_amount) = side(&order_book.asks.levels)?;
+    Some(calculate_microprice(bid, bid_amount, ask, ask_amount))
+}
+
+/// Microprice of the top of book, falling back to the mid price when neither level has size.
+pub fn calculate_microprice(bid: f64, bid_amount: f64, ask: f64, ask_amount: f64) -> f64 {
+    let total_amount = bid_amount + ask_amount;
+    if total_amount > 0.0 {
+        (bid * ask_amount + ask * bid_amount) / total_amount
+    } else {
+        (bid + ask) / 2.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use barter_data::subscription::book::Level;
+    use barter_data::subscription::book::OrderBookSide;
+    use barter_integration::model::Side;
+    use chrono::DateTime;
+
+    use super::*;
+
+    fn order_book(bids: Vec<Level>, asks: Vec<Level>) -> OrderBook {
+        OrderBook {
+            last_update_time: DateTime::from_timestamp_millis(0).unwrap(),
+            bids: OrderBookSide::new(Side::Buy, bids),
+            asks: OrderBookSide::new(Side::Sell, asks),
+        }
+    }
+
+    #[test]
+    fn test_from_order_book() {
+        let book = order_book(
+            vec![Level {
+                price: 100.0,
+                amount: 3.0,
+            }],
+            vec![Level {
+                price: 101.0,
+                amount: 1.0,
+            }],
+        );
+
+        let features = Features::from_order_book(&book).unwrap();
+        assert_eq!(features.bid, 100.0);
+        assert_eq!(features.ask, 101.0);
+        assert_eq!(features.mid_price, 100.5);
+        assert_eq!(features.microprice, 100.75);
+        assert_eq!(features.spread, 1.0);
+        assert_eq!(features.voi, 2.0);
+        assert_eq!(features.oir, 0.5);
+    }
+
+    #[test]
+    fn test_depth_window() {
+        let level = |price, amount| Level { price, amount };
+        let book = order_book(
+            vec![level(100.0, 1.0), level(99.9, 2.0), level(99.0, 4.0)],
+            vec![level(100.2, 1.0), level(101.0, 8.0)],
+        );
+        assert_eq!(Features::from_order_book(&book).unwrap().voi, -2.0);
+
+        // Within 25bps of the 100.1 mid, then only the best two of each side
+        let within = DepthConfig {
+            levels: None,
+            within_bps: Some(25.0),
+        };
+        assert_eq!(
+            Features::from_order_book_within(&book, &within, Weighting::Amount)
+                .unwrap()
+                .voi,
+            2.0
+        );
+        let top = DepthConfig {
+            levels: Some(2),
+            within_bps: None,
+        };
+        let features = Features::from_order_book_within(&book, &top, Weighting::Amount).unwrap();
+        assert_eq!((features.voi, features.oir), (-6.0, -0.5));
+    }
+
+    #[test]
+    fn test_notional_weighting() {
+        let level = |price, amount| Level { price, amount };
+        // Equal amounts, but the deep bid is worth half what the ask is
+        let book = order_book(
+            vec![level(100.0, 1.0), level(50.0, 1.0)],
+            vec![level(101.0, 2.0)],
+        );
+        assert_eq!(Features::from_order_book(&book).unwrap().voi, 0.0);
+        let features =
+            Features::from_order_book_within(&book, &DepthConfig::default(), Weighting::Notional)
+                .unwrap();
+        assert_eq!(features.voi, 150.0 - 202.0);
+    }
+
+    #[test]
+    fn test_oir_curve() {
+        let level = |price, amount| Level { price, amount };
+        // Bids lead at the touch, asks pile up 20bps out
+        let book = order_book(
+            vec![level(100.0, 3.0), level(99.9, 2.0)],
+            vec![level(100.02, 1.0), level(100.2, 14.0)],
+        );
+        let features = Features::from_order_book(&book).unwrap();
+        assert_eq!(features.oir, -0.5);
+        assert_eq!(features.oir_curve(), [0.5, -0.5, -0.5, 0.5, -0.5]);
+        assert_eq!(features.oir_curve(), oir_curve(&book, Weighting::Amount));
+
+        // Nothing within 10bps of a wide book
+        let wide = order_book(vec![level(100.0, 1.0)], vec![level(101.0, 1.0)]);
+        assert_eq!(Features::from_order_book(&wide).unwrap().oir_10bps, 0.0);
+    }
+
+    #[test]
+    fn test_fair_price() {
+        let level = |price, amount| Level { price, amount };
+        // A single lot at the touch of a thin ask side, with the size behind it
+        let book = order_book(
+            vec![level(100.0, 4.0), level(99.0, 4.0)],
+            vec![level(100.2, 1.0), level(101.0, 7.0)],
+        );
+        let features = Features::from_order_book(&book).unwrap();
+        assert_eq!(weighted_mid(&book, 1), Some(features.microprice));
+
+        // Both sides' VWAPs, 99.5 and 100.9, with as much size on each
+        let fair_price = weighted_mid(&book, 5).unwrap();
+        assert!((fair_price - 100.2).abs() < 1e-9);
+        let features = features.with_fair_price(fair_price);
+        assert!((features.spread - 0.2 / fair_price * 100.0).abs() < 1e-12);
+        assert!((features.mpb + 0.1).abs() < 1e-9);
+
+        let empty = order_book(vec![level(100.0, 1.0)], vec![]);
+        assert_eq!(weighted_mid(&empty, 5), None);
+    }
+
+    #[test]
+    fn test_from_order_book_empty_side() {
+        let book = order_book(
+            vec![Level {
+                price: 100.0,
+                amount: 1.0,
+            }],
+            vec![],
+        );
+
+        assert!(Features::from_order_book(&book).is_none());
+    }
+}
@@ -0,0 +1,256 @@
+//! Live feature stream over TCP in the Arrow IPC streaming format, so external processes can
+//! consume ticks in real time without parsing logs.
+//!
+//! Every connected client receives the schema followed by one single-row record batch per book
+//! update, with columns `time` and the names in [`FEATURE_NAMES`].
+//!
+//! ```toml
+//! [feed]
+//! address = "127.0.0.1:9100"
+//! ```
+//!
+//! ```python
+//! import socket
+//! import pyarrow as pa
+//!
+//! stream = socket.create_connection(("127.0.0.1", 9100)).makefile("rb")
+//! for batch in pa.ipc.open_stream(stream):
+//!     print(batch.to_pydict())
+//! ```
+
+use arrow_array::ArrayRef;
+use arrow_array::Float64Array;
+use arrow_array::RecordBatch;
+use arrow_array::TimestampMillisecondArray;
+use arrow_ipc::writer::write_message;
+use arrow_ipc::writer::DictionaryTracker;
+use arrow_ipc::writer::IpcDataGenerator;
+use arrow_ipc::writer::IpcWriteOptions;
+use arrow_schema::ArrowError;
+use arrow_schema::DataType;
+use arrow_schema::Field;
+use arrow_schema::Schema;
+use arrow_schema::TimeUnit;
+use chrono::DateTime;
+use chrono::Utc;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpListener;
+use tokio::net::TcpStream;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::info;
+use tracing::warn;
+
+use crate::features::Features;
+use crate::features::FEATURE_NAMES;
+
+/// Messages buffered per client before a slow reader starts skipping ticks.
+const CLIENT_BUFFER: usize = 1024;
+
+/// Marks the end of an IPC stream.
+const END_OF_STREAM: [u8; 8] = [0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0];
+
+/// The `time` and feature columns for `rows`, shared by every Arrow output.
+pub fn feature_columns<'a>(
+    rows: impl Iterator<Item = (DateTime<Utc>, &'a Features)> + Clone,
+) -> (Vec<Field>, Vec<ArrayRef>) {
+    let mut fields = vec![Field::new(
+        "time",
+        DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into())),
+        false,
+    )];
+    let mut columns: Vec<ArrayRef> = vec![Arc::new(
+        TimestampMillisecondArray::from_iter_values(
+            rows.clone().map(|(time, _)| time.timestamp_millis()),
+        )
+        .with_timezone("UTC"),
+    )];
+
+    for (index, name) in FEATURE_NAMES.iter().enumerate() {
+        fields.push(Field::new(*name, DataType::Float64, false));
+        columns.push(Arc::new(Float64Array::from_iter_values(
+            rows.clone().map(|(_, features)| features.to_array()[index]),
+        )));
+    }
+
+    (fields, columns)
+}
+
+/// Publishes features to every client connected to the feed's listener.
+pub struct FeatureFeed {
+    local_addr: SocketAddr,
+    sender: broadcast::Sender<Arc<Vec<u8>>>,
+    data_gen: IpcDataGenerator,
+    dictionaries: DictionaryTracker,
+    options: IpcWriteOptions,
+}
+
+impl std::fmt::Debug for FeatureFeed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FeatureFeed")
+            .field("local_addr", &self.local_addr)
+            .field("clients", &self.sender.receiver_count())
+            .finish_non_exhaustive()
+    }
+}
+
+impl FeatureFeed {
+    /// Listen on `address` and start accepting clients in the background.
+    pub async fn bind(address: &str) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(address).await?;
+        let local_addr = listener.local_addr()?;
+        let (sender, _) = broadcast::channel(CLIENT_BUFFER);
+
+        let data_gen = IpcDataGenerator::default();
+        let options = IpcWriteOptions::default();
+        let (fields, _) = feature_columns(std::iter::empty());
+        let mut schema = Vec::new();
+        write_message(
+            &mut schema,
+            data_gen.schema_to_bytes(&Schema::new(fields), &options),
+            &options,
+        )
+        .map_err(std::io::Error::other)?;
+
+        tokio::spawn(accept(listener, sender.clone(), Arc::new(schema)));
+        info!("Serving feature feed on {}", local_addr);
+
+        Ok(Self {
+            local_addr,
+            sender,
+            data_gen,
+            dictionaries: DictionaryTracker::new(false),
+            options,
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    pub fn clients(&self) -> usize {
+        self.sender.receiver_count()
+    }
+
+    /// Send the features of one book update to every connected client.
+    pub fn publish(&mut self, time: DateTime<Utc>, features: &Features) -> Result<(), ArrowError> {
+        if self.clients() == 0 {
+            return Ok(());
+        }
+
+        let (fields, columns) = feature_columns(std::iter::once((time, features)));
+        let batch = RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)?;
+        let (dictionaries, batch) =
+            self.data_gen
+                .encoded_batch(&batch, &mut self.dictionaries, &self.options)?;
+
+        let mut message = Vec::new();
+        for dictionary in dictionaries {
+            write_message(&mut message, dictionary, &self.options)?;
+        }
+        write_message(&mut message, batch, &self.options)?;
+
+        // Clients that disconnected since the check above are not an error
+        let _ = self.sender.send(Arc::new(message));
+        Ok(())
+    }
+}
+
+async fn accept(
+    listener: TcpListener,
+    sender: broadcast::Sender<Arc<Vec<u8>>>,
+    schema: Arc<Vec<u8>>,
+) {
+    loop {
+        match listener.accept().await {
+            Ok((socket, peer)) => {
+                info!("Feature feed client {} connected", peer);
+                let messages = sender.subscribe();
+                let schema = schema.clone();
+                tokio::spawn(async move {
+                    if let Err(error) = serve(socket, &schema, messages).await {
+                        info!("Feature feed client {} disconnected: {}", peer, error);
+                    }
+                });
+            }
+            Err(error) => warn!("Failed to accept feature feed client: {}", error),
+        }
+    }
+}
+
+async fn serve(
+    mut socket: TcpStream,
+    schema: &[u8],
+    mut messages: broadcast::Receiver<Arc<Vec<u8>>>,
+) -> std::io::Result<()> {
+    socket.set_nodelay(true)?;
+    socket.write_all(schema).await?;
+
+    loop {
+        match messages.recv().await {
+            Ok(message) => socket.write_all(&message).await?,
+            Err(RecvError::Lagged(skipped)) => {
+                warn!("Feature feed client fell behind, skipped {} ticks", skipped);
+            }
+            Err(RecvError::Closed) => break,
+        }
+    }
+
+    socket.write_all(&END_OF_STREAM).await
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::Array;
+    use arrow_ipc::reader::StreamReader;
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_clients_receive_features() {
+        let mut feed = FeatureFeed::bind("127.0.0.1:0").await.unwrap();
+        let address = feed.local_addr();
+
+        // Nothing to send while nobody is listening
+        let time = DateTime::from_timestamp_millis(1_717_200_000_000).unwrap();
+        feed.publish(time, &Features::default()).unwrap();
+
+        let client = tokio::task::spawn_blocking(move || {
+            let socket = std::net::TcpStream::connect(address).unwrap();
+            StreamReader::try_new(socket, None)
+                .unwrap()
+                .take(2)
+                .map(|batch| batch.unwrap())
+                .collect::<Vec<_>>()
+        });
+        while feed.clients() == 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        for oir in [0.25, -0.5] {
+            let features = Features {
+                oir,
+                ..Features::default()
+            };
+            feed.publish(time, &features).unwrap();
+        }
+
+        let batches = client.await.unwrap();
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[0].num_columns(), 1 + FEATURE_NAMES.len());
+        let oir = |batch: &RecordBatch| {
+            let column = batch.column_by_name("oir").unwrap();
+            assert_eq!(column.len(), 1);
+            column
+                .as_any()
+                .downcast_ref::<Float64Array>()
+                .unwrap()
+                .value(0)
+        };
+        assert_eq!(oir(&batches[0]), 0.25);
+        assert_eq!(oir(&batches[1]), -0.5);
+    }
+}
@@ -0,0 +1,233 @@
+//! Staying out of perpetual funding payments. The guard blocks entries, and optionally closes
+//! positions, shortly before a funding timestamp at which longs would pay more than the
+//! configured threshold. With the `funding` feature the poller keeps it fed with the exchange's
+//! current rate.
+
+use chrono::DateTime;
+use chrono::TimeDelta;
+use chrono::Utc;
+use serde_json::Value;
+use tracing::info;
+
+use crate::config::FundingConfig;
+use crate::hours::Permission;
+#[cfg(feature = "funding")]
+use crate::poll;
+
+/// A funding rate as last reported by the exchange.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FundingRate {
+    pub rate: f64,
+    /// The funding timestamp the rate applies at, when the exchange says.
+    pub next: Option<DateTime<Utc>>,
+}
+
+impl FundingRate {
+    /// Parse an Aevo style funding response, `{"funding_rate": "0.0000125", "next_epoch":
+    /// "1721721600000000000"}` with the next epoch in nanoseconds. Either may be a number.
+    pub fn parse(body: &str) -> Option<Self> {
+        let body: Value = serde_json::from_str(body).ok()?;
+        let rate = match body.get("funding_rate")? {
+            Value::String(rate) => rate.parse().ok()?,
+            rate => rate.as_f64()?,
+        };
+        let next = match body.get("next_epoch") {
+            Some(Value::String(nanos)) => nanos.parse().ok(),
+            Some(nanos) => nanos.as_i64(),
+            None => None,
+        };
+        Some(Self {
+            rate,
+            next: next.map(DateTime::from_timestamp_nanos),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct FundingGuard {
+    config: FundingConfig,
+    rate: Option<FundingRate>,
+    // The funding timestamp positions were last closed ahead of
+    closed: Option<DateTime<Utc>>,
+    entries: bool,
+}
+
+impl FundingGuard {
+    pub fn new(config: &FundingConfig) -> Self {
+        Self {
+            config: config.clone(),
+            rate: None,
+            closed: None,
+            entries: true,
+        }
+    }
+
+    pub fn set_rate(&mut self, rate: FundingRate) {
+        self.rate = Some(rate);
+    }
+
+    /// The first funding timestamp after `now`: the one the exchange reported, or else the next
+    /// multiple of the funding interval since midnight UTC.
+    pub fn next_funding(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        if let Some(next) = self.rate.and_then(|rate| rate.next) {
+            if next > now {
+                return next;
+            }
+        }
+        let interval = self.config.interval_secs.max(1) as i64;
+        let midnight = now.date_naive().and_time(Default::default()).and_utc();
+        let elapsed = (now - midnight).num_seconds();
+        midnight + TimeDelta::seconds((elapsed / interval + 1) * interval)
+    }
+
+    /// What is allowed at `now`, logging whenever entries are blocked or allowed again.
+    pub fn update(&mut self, now: DateTime<Utc>) -> Permission {
+        let next = self.next_funding(now);
+        let costly = self
+            .rate
+            .map(|rate| rate.rate)
+            .filter(|rate| *rate > self.config.threshold);
+        let avoid = match costly {
+            Some(_) => next - now <= TimeDelta::minutes(self.config.window_mins as i64),
+            None => false,
+        };
+
+        if avoid == self.entries {
+            match costly {
+                Some(rate) if avoid => info!(
+                    "Funding of {:.4}% due at {}, blocking entries",
+                    rate * 100.0,
+                    next
+                ),
+                _ => info!("Funding window passed, allowing entries"),
+            }
+            self.entries = !avoid;
+        }
+
+        let flatten = avoid && self.config.close && self.closed != Some(next);
+        if flatten {
+            self.closed = Some(next);
+        }
+        Permission {
+            entries: !avoid,
+            flatten,
+        }
+    }
+}
+
+/// Polls the exchange's funding rate in the background.
+#[cfg(feature = "funding")]
+#[derive(Debug)]
+pub struct FundingPoller {
+    receiver: tokio::sync::watch::Receiver<Option<FundingRate>>,
+}
+
+#[cfg(feature = "funding")]
+impl FundingPoller {
+    pub fn spawn(config: &FundingConfig) -> Self {
+        let receiver = poll::spawn(
+            config.url.clone(),
+            config.poll_secs,
+            "funding rate",
+            |body, _, _| FundingRate::parse(body),
+        );
+        Self { receiver }
+    }
+
+    /// The rate polled since the last call, if any.
+    pub fn latest(&mut self) -> Option<FundingRate> {
+        match self.receiver.has_changed() {
+            Ok(true) => *self.receiver.borrow_and_update(),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 7, 23, hour, minute, 0).unwrap()
+    }
+
+    fn guard(close: bool) -> FundingGuard {
+        FundingGuard::new(&FundingConfig {
+            url: "http://localhost/funding".to_owned(),
+            poll_secs: 60,
+            interval_secs: 8 * 3600,
+            window_mins: 5,
+            threshold: 0.0001,
+            close,
+        })
+    }
+
+    #[test]
+    fn test_parse_aevo_response() {
+        let rate = FundingRate::parse(
+            r#"{"funding_rate":"0.0000125","next_epoch":"1721721600000000000"}"#,
+        )
+        .unwrap();
+        assert_eq!(rate.rate, 0.0000125);
+        assert_eq!(rate.next, Some(at(8, 0)));
+        assert_eq!(
+            FundingRate::parse(r#"{"funding_rate":-0.0002}"#),
+            Some(FundingRate {
+                rate: -0.0002,
+                next: None
+            })
+        );
+        assert_eq!(FundingRate::parse(r#"{"next_epoch":"1"}"#), None);
+    }
+
+    #[test]
+    fn test_next_funding_follows_schedule() {
+        let mut guard = guard(false);
+        assert_eq!(guard.next_funding(at(7, 0)), at(8, 0));
+        assert_eq!(guard.next_funding(at(8, 0)), at(16, 0));
+        assert_eq!(guard.next_funding(at(23, 0)), at(0, 0) + TimeDelta::days(1));
+
+        guard.set_rate(FundingRate {
+            rate: 0.0,
+            next: Some(at(9, 0)),
+        });
+        assert_eq!(guard.next_funding(at(7, 0)), at(9, 0));
+        assert_eq!(guard.next_funding(at(9, 30)), at(16, 0));
+    }
+
+    #[test]
+    fn test_costly_funding_blocks_entries() {
+        let mut guard = guard(true);
+        assert_eq!(guard.update(at(7, 58)), Permission::default());
+
+        // Longs receive negative funding, so there is nothing to avoid
+        guard.set_rate(FundingRate {
+            rate: -0.001,
+            next: None,
+        });
+        assert!(guard.update(at(7, 58)).entries);
+
+        guard.set_rate(FundingRate {
+            rate: 0.0005,
+            next: None,
+        });
+        assert!(guard.update(at(7, 54)).entries);
+        assert_eq!(
+            guard.update(at(7, 55)),
+            Permission {
+                entries: false,
+                flatten: true
+            }
+        );
+        assert_eq!(
+            guard.update(at(7, 59)),
+            Permission {
+                entries: false,
+                flatten: false
+            }
+        );
+        assert_eq!(guard.update(at(8, 0)), Permission::default());
+    }
+}
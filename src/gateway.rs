@@ -0,0 +1,804 @@
+//! Order gateway mode: instead of trading the paper account, every sleeve's orders are pushed as
+//! intents to an external execution service over ZeroMQ, and its state only changes when the
+//! service reports back.
+//!
+//! Intents and reports are single-frame JSON messages. The service answers each intent `id` with
+//! exactly one report:
+//!
+//! ```json
+//! {"id": 7, "time": "2024-06-01T00:00:00Z", "symbol": "BTC/USDT", "strategy": "imbalance", "side": "Buy", "price": 67000.0, "size": 0.001, "venue": "binance"}
+//! {"id": 7, "status": "filled", "price": 67000.5, "size": 0.001, "fee": 0.335, "venue": "binance"}
+//! {"id": 8, "status": "rejected", "reason": "insufficient margin"}
+//! ```
+//!
+//! An intent only names a `venue` when orders are [routed](crate::routing), and one rejected by
+//! its venue is then sent again to the next best under a new `id`. A fill's `venue` is optional
+//! and defaults to `gateway`. Fills are recorded as limit orders against the mid price when the
+//! intent was sent.
+//!
+//! A sleeve keeps evaluating its strategy while an order is in flight but places nothing more
+//! until it is reported. Take profit and stop loss are left to the execution service, while
+//! [flattening](OrderGateway::flatten) sends a sell for every lot held. The service can't cancel
+//! an order, so a buy still in flight when flattening is sold as soon as it fills.
+
+use barter_data::subscription::book::OrderBook;
+use barter_integration::model::Exchange;
+use barter_integration::model::Side;
+use chrono::DateTime;
+use chrono::Utc;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::HashMap;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::info;
+use tracing::warn;
+use tracing::Span;
+use zeromq::PullSocket;
+use zeromq::PushSocket;
+use zeromq::Socket;
+use zeromq::SocketRecv;
+use zeromq::SocketSend;
+use zeromq::ZmqError;
+
+use crate::allocation::Allocator;
+use crate::allocation::Sleeve;
+use crate::config::GatewayConfig;
+use crate::event::Event;
+use crate::event::Execution;
+use crate::event::OrderType;
+use crate::features::Features;
+use crate::latency::Latency;
+use crate::latency::Stage;
+use crate::routing::Route;
+use crate::routing::Router;
+use crate::telemetry;
+use crate::telemetry::Trades;
+use crate::Fill;
+
+#[derive(Debug, thiserror::Error)]
+pub enum GatewayError {
+    #[error("failed to connect order gateway {0}: {1}")]
+    Connect(String, ZmqError),
+}
+
+/// An order for the execution service to work.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OrderIntent {
+    pub id: u64,
+    pub time: DateTime<Utc>,
+    pub symbol: &'static str,
+    pub strategy: String,
+    pub side: Side,
+    pub price: f64,
+    pub size: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub venue: Option<String>,
+}
+
+/// The outcome of an [`OrderIntent`], tagged by `status`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ExecutionReport {
+    Filled {
+        id: u64,
+        price: f64,
+        size: f64,
+        fee: f64,
+        #[serde(default = "default_venue")]
+        venue: String,
+    },
+    Rejected {
+        id: u64,
+        reason: String,
+    },
+}
+
+impl ExecutionReport {
+    pub fn id(&self) -> u64 {
+        match self {
+            Self::Filled { id, .. } | Self::Rejected { id, .. } => *id,
+        }
+    }
+}
+
+fn default_venue() -> String {
+    "gateway".to_owned()
+}
+
+#[derive(Debug)]
+struct Pending {
+    sleeve: usize,
+    /// The order as last sent.
+    intent: OrderIntent,
+    /// Venues it was routed to so far.
+    tried: Vec<String>,
+    /// Mid price when the order was sent.
+    decision_price: f64,
+    /// Whether a buy is sold again once filled, having been in flight when flattening.
+    close_on_fill: bool,
+    // Trace spans of the decision and the order itself, open until it is reported
+    decision: Span,
+    order: Span,
+}
+
+/// Drives an [`Allocator`] through an external execution service.
+#[derive(Debug)]
+pub struct OrderGateway {
+    orders: mpsc::UnboundedSender<OrderIntent>,
+    reports: mpsc::UnboundedReceiver<ExecutionReport>,
+    pending: HashMap<u64, Pending>,
+    next_id: u64,
+    router: Option<Router>,
+    // The task receiving reports, which stops for good if the socket fails
+    receiving: Option<JoinHandle<()>>,
+}
+
+impl OrderGateway {
+    /// Connect to the service's order and report endpoints and start exchanging messages in the
+    /// background, counting the time from each intent's decision to sending it toward `latency`.
+    pub async fn connect(config: &GatewayConfig, latency: Latency) -> Result<Self, GatewayError> {
+        let mut orders = PushSocket::new();
+        orders
+            .connect(&config.orders)
+            .await
+            .map_err(|error| GatewayError::Connect(config.orders.clone(), error))?;
+        let mut reports = PullSocket::new();
+        reports
+            .connect(&config.reports)
+            .await
+            .map_err(|error| GatewayError::Connect(config.reports.clone(), error))?;
+
+        let (order_sender, order_receiver) = mpsc::unbounded_channel();
+        let (report_sender, report_receiver) = mpsc::unbounded_channel();
+        tokio::spawn(send_orders(
+            orders,
+            order_receiver,
+            report_sender.clone(),
+            latency,
+        ));
+        let receiving = tokio::spawn(receive_reports(reports, report_sender));
+        info!(
+            "Routing orders to {}, reports from {}",
+            config.orders, config.reports
+        );
+
+        Ok(Self {
+            receiving: Some(receiving),
+            router: (!config.routing.is_empty()).then(|| Router::new(&config.routing)),
+            ..Self::new(order_sender, report_receiver)
+        })
+    }
+
+    fn new(
+        orders: mpsc::UnboundedSender<OrderIntent>,
+        reports: mpsc::UnboundedReceiver<ExecutionReport>,
+    ) -> Self {
+        Self {
+            orders,
+            reports,
+            pending: HashMap::new(),
+            next_id: 1,
+            router: None,
+            receiving: None,
+        }
+    }
+
+    /// Route orders by `exchange`'s latest `book` too, when routing.
+    pub fn on_book(&mut self, exchange: &Exchange, book: &OrderBook) {
+        if let Some(router) = &mut self.router {
+            router.on_book(&exchange.to_string(), book);
+        }
+    }
+
+    /// Orders sent and not yet reported.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Whether orders are still being sent and reports received. ZeroMQ queues messages until the
+    /// service connects, so this only notices the gateway's own sockets failing.
+    pub fn is_reachable(&self) -> bool {
+        !self.orders.is_closed()
+            && self
+                .receiving
+                .as_ref()
+                .is_none_or(|receiving| !receiving.is_finished())
+    }
+
+    /// Book the reports received since the last update, let every sleeve's strategy evaluate
+    /// `features` and send the orders of those without one in flight, then rebalance if due.
+    pub fn on_features(
+        &mut self,
+        allocator: &mut Allocator,
+        features: &Features,
+        now: DateTime<Utc>,
+    ) -> Vec<Event> {
+        let mut events = Vec::new();
+        while let Ok(report) = self.reports.try_recv() {
+            events.extend(self.book(allocator, report, features, now));
+        }
+
+        for (index, sleeve) in allocator.sleeves_mut().iter_mut().enumerate() {
+            let started = telemetry::now();
+            let signal = sleeve.evaluate(features, now);
+            let evaluated = telemetry::now();
+            if self.pending.values().any(|pending| pending.sleeve == index) {
+                continue;
+            }
+            let Some((side, price)) = Sleeve::order(signal, features) else {
+                continue;
+            };
+            if side == Side::Sell && sleeve.state.positions.is_empty() {
+                continue;
+            }
+            let size = sleeve.lot_size(side);
+            let decision = sleeve.trades.decide(
+                sleeve.state.symbol,
+                sleeve.strategy.name(),
+                signal,
+                features,
+                started,
+                evaluated,
+            );
+            if self
+                .send(index, sleeve, (side, price, size), decision, features, now)
+                .is_some()
+            {
+                events.extend(sleeve.signal_events(signal, features, size, now));
+            }
+        }
+
+        // Shift capital toward the better performing strategies when due
+        events.extend(
+            allocator
+                .maybe_rebalance(now, features.bid)
+                .into_iter()
+                .flatten(),
+        );
+        events
+    }
+
+    /// Send a sell for every lot every sleeve holds at the bid of `features`, but for those
+    /// already being sold, and sell the buys still in flight once they fill, returning the orders.
+    pub fn flatten(
+        &mut self,
+        allocator: &mut Allocator,
+        features: &Features,
+        now: DateTime<Utc>,
+    ) -> Vec<Event> {
+        for pending in self.pending.values_mut() {
+            pending.close_on_fill = pending.intent.side == Side::Buy;
+        }
+        let mut events = Vec::new();
+        for (index, sleeve) in allocator.sleeves_mut().iter_mut().enumerate() {
+            let selling = self
+                .pending
+                .values()
+                .filter(|pending| pending.sleeve == index && pending.intent.side == Side::Sell)
+                .count();
+            // Sells close the newest lots first, so those in flight already cover them
+            let sizes: Vec<f64> = sleeve
+                .state
+                .positions
+                .iter()
+                .rev()
+                .skip(selling)
+                .map(|lot| lot.size)
+                .collect();
+            for size in sizes {
+                let decision = sleeve.trades.exit("flatten");
+                let order = (Side::Sell, features.bid, size);
+                events.extend(self.send(index, sleeve, order, decision, features, now));
+            }
+        }
+        events
+    }
+
+    /// Send `sleeve`'s order to `side` `size` at `price`, or at the best venue's price when
+    /// routing, under `decision`, returning its order event once sent.
+    fn send(
+        &mut self,
+        index: usize,
+        sleeve: &Sleeve,
+        (side, price, size): (Side, f64, f64),
+        decision: Span,
+        features: &Features,
+        now: DateTime<Utc>,
+    ) -> Option<Event> {
+        let (price, venue) = match &self.router {
+            Some(router) => match router.route(side, size, &[]) {
+                Some(route) => (route.price, Some(route.venue)),
+                None => {
+                    info!("No venue to route a {:?} order to", side);
+                    return None;
+                }
+            },
+            None => (price, None),
+        };
+        let order = Trades::order(&decision, side, price);
+        let id = self.next_id;
+        self.next_id += 1;
+        let intent = OrderIntent {
+            id,
+            time: now,
+            symbol: sleeve.state.symbol,
+            strategy: sleeve.strategy.name().to_owned(),
+            side,
+            price,
+            size,
+            venue,
+        };
+        if self.orders.send(intent.clone()).is_err() {
+            warn!("Order gateway has stopped, not sending order {}", id);
+            return None;
+        }
+        let event = Event::Order {
+            time: now,
+            symbol: intent.symbol,
+            strategy: intent.strategy.clone(),
+            side,
+            price,
+            size,
+            book_time: None,
+        };
+        self.pending.insert(
+            id,
+            Pending {
+                sleeve: index,
+                tried: intent.venue.iter().cloned().collect(),
+                intent,
+                decision_price: features.mid_price,
+                close_on_fill: false,
+                decision,
+                order,
+            },
+        );
+        Some(event)
+    }
+
+    fn book(
+        &mut self,
+        allocator: &mut Allocator,
+        report: ExecutionReport,
+        features: &Features,
+        now: DateTime<Utc>,
+    ) -> Vec<Event> {
+        let id = report.id();
+        let Some(pending) = self.pending.remove(&id) else {
+            warn!("Ignoring execution report for unknown order {}", id);
+            return Vec::new();
+        };
+        let sleeve = &mut allocator.sleeves_mut()[pending.sleeve];
+
+        match report {
+            ExecutionReport::Filled {
+                price,
+                size,
+                fee,
+                venue,
+                ..
+            } => {
+                let fill = Fill {
+                    side: pending.intent.side,
+                    price,
+                    size,
+                    fee,
+                };
+                sleeve.state.apply_fill(&fill);
+                if let Some(router) = &mut self.router {
+                    router.on_fill(&venue, &fill);
+                }
+                sleeve
+                    .trades
+                    .filled(pending.decision, &pending.order, &fill);
+                info!(
+                    "Order {} filled: {:?} {} {} at {} (cost: {})",
+                    id, fill.side, size, sleeve.state.symbol, price, fee
+                );
+                let execution = Execution {
+                    venue,
+                    order_type: OrderType::Limit,
+                    decision_price: pending.decision_price,
+                    queue_ahead: None,
+                };
+                let mut events = vec![sleeve.fill_event(fill, execution, now)];
+                if pending.close_on_fill {
+                    info!("Order {} filled after flattening, selling it", id);
+                    let decision = sleeve.trades.exit("flatten");
+                    let order = (Side::Sell, features.bid, size);
+                    events.extend(self.send(
+                        pending.sleeve,
+                        sleeve,
+                        order,
+                        decision,
+                        features,
+                        now,
+                    ));
+                }
+                events
+            }
+            ExecutionReport::Rejected { reason, .. } => {
+                warn!("Order {} rejected: {}", id, reason);
+                let intent = &pending.intent;
+                match self
+                    .router
+                    .as_ref()
+                    .and_then(|router| router.route(intent.side, intent.size, &pending.tried))
+                {
+                    Some(route) => self.reroute(pending, route),
+                    None => {
+                        pending.order.record("rejected", reason.as_str());
+                    }
+                }
+                Vec::new()
+            }
+        }
+    }
+
+    /// Send a rejected order again, to `route`'s venue under a new id.
+    fn reroute(&mut self, mut pending: Pending, route: Route) {
+        let id = self.next_id;
+        self.next_id += 1;
+        info!(
+            "Rerouting order {} to {} as order {}",
+            pending.intent.id, route.venue, id
+        );
+        pending.tried.push(route.venue.clone());
+        pending.intent = OrderIntent {
+            id,
+            price: route.price,
+            venue: Some(route.venue),
+            ..pending.intent
+        };
+        if self.orders.send(pending.intent.clone()).is_err() {
+            warn!("Order gateway has stopped, not sending order {}", id);
+            pending.order.record("rejected", "gateway stopped");
+            return;
+        }
+        self.pending.insert(id, pending);
+    }
+}
+
+async fn send_orders(
+    mut socket: PushSocket,
+    mut orders: mpsc::UnboundedReceiver<OrderIntent>,
+    reports: mpsc::UnboundedSender<ExecutionReport>,
+    latency: Latency,
+) {
+    while let Some(order) = orders.recv().await {
+        let message = serde_json::to_vec(&order).expect("order intents always serialize");
+        match socket.send(message.into()).await {
+            Ok(()) => latency.record(Stage::Order, order.time, Utc::now()),
+            // Release the sleeve rather than waiting on a report that will never come
+            Err(error) => {
+                let _ = reports.send(ExecutionReport::Rejected {
+                    id: order.id,
+                    reason: format!("failed to send: {error}"),
+                });
+            }
+        }
+    }
+}
+
+async fn receive_reports(mut socket: PullSocket, reports: mpsc::UnboundedSender<ExecutionReport>) {
+    loop {
+        let message = match socket.recv().await {
+            Ok(message) => message,
+            Err(error) => {
+                warn!("Stopped receiving execution reports: {}", error);
+                break;
+            }
+        };
+        let Some(frame) = message.get(0) else {
+            continue;
+        };
+        match serde_json::from_slice(frame) {
+            Ok(report) => {
+                if reports.send(report).is_err() {
+                    break;
+                }
+            }
+            Err(error) => warn!("Ignoring malformed execution report: {}", error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use barter_data::subscription::book::OrderBookSide;
+
+    use super::*;
+    use crate::config::AllocationConfig;
+    use crate::config::VenueConfig;
+    use crate::strategy::Signal;
+    use crate::strategy::Strategy;
+    use crate::Lot;
+    use crate::TradingState;
+    use crate::TRADE_SIZE;
+
+    /// Alternates between buying and selling so there is always something to send.
+    #[derive(Debug)]
+    struct Flip(Signal);
+
+    impl Strategy for Flip {
+        fn name(&self) -> &str {
+            "flip"
+        }
+
+        fn evaluate(&mut self, _features: &Features, _state: &TradingState) -> Signal {
+            self.0 = match self.0 {
+                Signal::Buy => Signal::Sell,
+                _ => Signal::Buy,
+            };
+            self.0
+        }
+    }
+
+    fn allocator() -> Allocator {
+        let members: Vec<(Box<dyn Strategy>, f64)> = vec![(Box::new(Flip(Signal::Sell)), 1.0)];
+        Allocator::new(
+            1000.0,
+            "BTC/USDT",
+            members,
+            AllocationConfig::default(),
+            Utc::now(),
+        )
+    }
+
+    fn features() -> Features {
+        Features {
+            bid: 100.0,
+            ask: 100.01,
+            mid_price: 100.005,
+            ..Features::default()
+        }
+    }
+
+    #[test]
+    fn test_orders_wait_for_reports() {
+        let (order_sender, mut orders) = mpsc::unbounded_channel();
+        let (reports, report_receiver) = mpsc::unbounded_channel();
+        let mut gateway = OrderGateway::new(order_sender, report_receiver);
+        let mut allocator = allocator();
+        let now = Utc::now();
+
+        let events = gateway.on_features(&mut allocator, &features(), now);
+        let kinds: Vec<_> = events.iter().map(Event::kind).collect();
+        assert_eq!(kinds, ["signal", "order"]);
+        let buy = orders.try_recv().unwrap();
+        assert_eq!((buy.id, buy.side, buy.price), (1, Side::Buy, 100.0));
+
+        // Nothing more is sent, or booked, until the buy is reported
+        assert!(gateway
+            .on_features(&mut allocator, &features(), now)
+            .is_empty());
+        assert!(orders.try_recv().is_err());
+        assert_eq!(allocator.sleeves()[0].state.cash, 1000.0);
+
+        reports
+            .send(ExecutionReport::Filled {
+                id: 1,
+                price: 99.5,
+                size: TRADE_SIZE,
+                fee: 0.01,
+                venue: "binance".to_owned(),
+            })
+            .unwrap();
+        let events = gateway.on_features(&mut allocator, &features(), now);
+        let kinds: Vec<_> = events.iter().map(Event::kind).collect();
+        assert_eq!(kinds, ["fill", "signal", "order"]);
+        let Event::Fill { execution, .. } = &events[0] else {
+            panic!("expected a fill");
+        };
+        assert_eq!(execution.venue, "binance");
+        assert_eq!(execution.decision_price, features().mid_price);
+        let state = &allocator.sleeves()[0].state;
+        assert_eq!(state.positions, [Lot::at(99.5)]);
+        assert!((state.cash - (1000.0 - 99.5 * TRADE_SIZE - 0.01)).abs() < 1e-9);
+
+        // The fill frees the sleeve for the strategy's next order
+        let next = orders.try_recv().unwrap();
+        assert_eq!((next.id, next.side), (2, Side::Buy));
+    }
+
+    #[test]
+    fn test_flatten_sells_every_lot() {
+        let (order_sender, mut orders) = mpsc::unbounded_channel();
+        let (reports, report_receiver) = mpsc::unbounded_channel();
+        let mut gateway = OrderGateway::new(order_sender, report_receiver);
+        let mut allocator = allocator();
+        let now = Utc::now();
+
+        gateway.on_features(&mut allocator, &features(), now);
+        assert_eq!(orders.try_recv().unwrap().side, Side::Buy);
+        allocator.sleeves_mut()[0].state.positions = vec![
+            Lot::at(99.0),
+            Lot {
+                price: 99.5,
+                size: TRADE_SIZE / 2.0,
+            },
+        ];
+
+        let events = gateway.flatten(&mut allocator, &features(), now);
+        let kinds: Vec<_> = events.iter().map(Event::kind).collect();
+        assert_eq!(kinds, ["order", "order"]);
+        let sells: Vec<_> = std::iter::from_fn(|| orders.try_recv().ok())
+            .map(|intent| (intent.id, intent.side, intent.price, intent.size))
+            .collect();
+        assert_eq!(
+            sells,
+            [
+                (2, Side::Sell, 100.0, TRADE_SIZE / 2.0),
+                (3, Side::Sell, 100.0, TRADE_SIZE)
+            ]
+        );
+        // Lots already being sold aren't sold again
+        assert!(gateway.flatten(&mut allocator, &features(), now).is_empty());
+
+        // The buy in flight is sold as soon as it fills
+        reports
+            .send(ExecutionReport::Filled {
+                id: 1,
+                price: 99.8,
+                size: TRADE_SIZE,
+                fee: 0.01,
+                venue: "binance".to_owned(),
+            })
+            .unwrap();
+        let events = gateway.on_features(&mut allocator, &features(), now);
+        let kinds: Vec<_> = events.iter().map(Event::kind).collect();
+        assert_eq!(kinds, ["fill", "order"]);
+        let sell = orders.try_recv().unwrap();
+        assert_eq!((sell.id, sell.side, sell.size), (4, Side::Sell, TRADE_SIZE));
+        assert!(orders.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_rejection_releases_sleeve() {
+        let (order_sender, mut orders) = mpsc::unbounded_channel();
+        let (reports, report_receiver) = mpsc::unbounded_channel();
+        let mut gateway = OrderGateway::new(order_sender, report_receiver);
+        let mut allocator = allocator();
+        let now = Utc::now();
+
+        gateway.on_features(&mut allocator, &features(), now);
+        assert_eq!(gateway.pending(), 1);
+        reports
+            .send(ExecutionReport::Rejected {
+                id: 1,
+                reason: "no".to_owned(),
+            })
+            .unwrap();
+        // A report for an order that was never sent is ignored
+        reports
+            .send(ExecutionReport::Rejected {
+                id: 42,
+                reason: "no".to_owned(),
+            })
+            .unwrap();
+
+        gateway.on_features(&mut allocator, &features(), now);
+        assert!(allocator.sleeves()[0].state.positions.is_empty());
+        assert_eq!(orders.try_recv().unwrap().id, 1);
+        // Sells are skipped while flat, the following buy goes out
+        assert!(orders.try_recv().is_err());
+        gateway.on_features(&mut allocator, &features(), now);
+        assert_eq!(orders.try_recv().unwrap().id, 2);
+
+        // Once orders can no longer be sent, execution is unreachable
+        assert!(gateway.is_reachable());
+        drop(orders);
+        assert!(!gateway.is_reachable());
+    }
+
+    #[test]
+    fn test_rejected_orders_rerouted() {
+        let (order_sender, mut orders) = mpsc::unbounded_channel();
+        let (reports, report_receiver) = mpsc::unbounded_channel();
+        let mut gateway = OrderGateway::new(order_sender, report_receiver);
+        let venues = HashMap::from([
+            ("aevo".to_owned(), VenueConfig::default()),
+            ("binance".to_owned(), VenueConfig::default()),
+        ]);
+        gateway.router = Some(Router::new(&venues));
+        let book = |ask: f64| OrderBook {
+            last_update_time: Utc::now(),
+            bids: OrderBookSide::new(Side::Buy, [(ask - 0.1, 1.0)]),
+            asks: OrderBookSide::new(Side::Sell, [(ask, 1.0)]),
+        };
+        gateway.on_book(&Exchange::from("aevo"), &book(100.1));
+        gateway.on_book(&Exchange::from("binance"), &book(100.2));
+        let mut allocator = allocator();
+        let now = Utc::now();
+
+        gateway.on_features(&mut allocator, &features(), now);
+        let buy = orders.try_recv().unwrap();
+        assert_eq!((buy.venue.as_deref(), buy.price), (Some("aevo"), 100.1));
+
+        let rejected = |id| ExecutionReport::Rejected {
+            id,
+            reason: "insufficient margin".to_owned(),
+        };
+        reports.send(rejected(1)).unwrap();
+        gateway.on_features(&mut allocator, &features(), now);
+        let rerouted = orders.try_recv().unwrap();
+        assert_eq!(rerouted.id, 2);
+        assert_eq!(
+            (rerouted.venue.as_deref(), rerouted.price),
+            (Some("binance"), 100.2)
+        );
+        assert_eq!(gateway.pending(), 1);
+
+        // With every venue tried, the sleeve is released for its next order, routed afresh
+        reports.send(rejected(2)).unwrap();
+        gateway.on_features(&mut allocator, &features(), now);
+        let next = orders.try_recv().unwrap();
+        assert_eq!((next.id, next.venue.as_deref()), (3, Some("aevo")));
+        assert_eq!(gateway.pending(), 1);
+    }
+
+    #[test]
+    fn test_parse_report() {
+        let report: ExecutionReport = serde_json::from_str(
+            r#"{"id": 7, "status": "filled", "price": 67000.5, "size": 0.001, "fee": 0.335}"#,
+        )
+        .unwrap();
+        assert_eq!(report.id(), 7);
+        assert!(matches!(report, ExecutionReport::Filled { venue, .. } if venue == "gateway"));
+
+        let report: ExecutionReport =
+            serde_json::from_str(r#"{"id": 8, "status": "rejected", "reason": "margin"}"#).unwrap();
+        assert_eq!(
+            report,
+            ExecutionReport::Rejected {
+                id: 8,
+                reason: "margin".to_owned()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_round_trip_over_zmq() {
+        // Stand in for the execution service, which binds both endpoints
+        let mut service_orders = PullSocket::new();
+        let orders = service_orders.bind("tcp://127.0.0.1:0").await.unwrap();
+        let mut service_reports = PushSocket::new();
+        let reports = service_reports.bind("tcp://127.0.0.1:0").await.unwrap();
+
+        let config = GatewayConfig {
+            orders: orders.to_string(),
+            reports: reports.to_string(),
+            routing: HashMap::new(),
+        };
+        let latency = Latency::default();
+        let mut gateway = OrderGateway::connect(&config, latency.clone())
+            .await
+            .unwrap();
+        let mut allocator = allocator();
+        gateway.on_features(&mut allocator, &features(), Utc::now());
+
+        let message = service_orders.recv().await.unwrap();
+        let intent: serde_json::Value = serde_json::from_slice(message.get(0).unwrap()).unwrap();
+        assert_eq!(intent["side"], "Buy");
+        assert_eq!(intent["strategy"], "flip");
+        let report = format!(
+            r#"{{"id": {}, "status": "filled", "price": 100.0, "size": 0.001, "fee": 0.0}}"#,
+            intent["id"]
+        );
+        // The service only learns of the bot's connection once it has been accepted
+        while service_reports.send(report.as_str().into()).await.is_err() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        while allocator.sleeves()[0].state.positions.is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            gateway.on_features(&mut allocator, &features(), Utc::now());
+        }
+        assert_eq!(allocator.sleeves()[0].state.positions, [Lot::at(100.0)]);
+        assert_eq!(latency.histogram(Stage::Order).count(), 1);
+        assert!(gateway.is_reachable());
+    }
+}
@@ -0,0 +1,440 @@
+//! gRPC service for controlling the engine and streaming its orders, fills and equity marks, for
+//! typed integrations from other services. The service is defined in `proto/engine.proto`.
+//!
+//! Calls need a token from the [auth](crate::auth) config as `authorization: Bearer <token>`
+//! metadata: status and the event stream any token, the others one with the `control` scope.
+//!
+//! ```toml
+//! [grpc]
+//! address = "127.0.0.1:50051"
+//! ```
+
+use barter_integration::model::Side;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::Mutex;
+use tokio::net::TcpListener;
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::wrappers::TcpListenerStream;
+use tokio_stream::Stream;
+use tokio_stream::StreamExt;
+use tonic::transport::Server;
+use tonic::Request;
+use tonic::Response;
+use tracing::info;
+use tracing::warn;
+
+use crate::auth;
+use crate::auth::AuthError;
+use crate::auth::Authenticator;
+use crate::auth::Denied;
+use crate::auth::Scope;
+use crate::config::TlsConfig;
+use crate::control::Control;
+use crate::event::Event;
+use crate::event::OrderType;
+use crate::live::LiveState;
+
+/// Messages and service code generated from `proto/engine.proto`.
+pub mod proto {
+    tonic::include_proto!("fast_imbalance_trading.engine");
+}
+
+use proto::engine_server::Engine;
+use proto::engine_server::EngineServer;
+
+/// Events buffered per streaming client before a slow reader starts skipping them.
+const CLIENT_BUFFER: usize = 1024;
+
+impl From<Side> for proto::Side {
+    fn from(side: Side) -> Self {
+        match side {
+            Side::Buy => Self::Buy,
+            Side::Sell => Self::Sell,
+        }
+    }
+}
+
+impl From<OrderType> for proto::OrderType {
+    fn from(order_type: OrderType) -> Self {
+        match order_type {
+            OrderType::Limit => Self::Limit,
+            OrderType::Market => Self::Market,
+        }
+    }
+}
+
+impl From<crate::RiskParams> for proto::RiskParams {
+    fn from(risk: crate::RiskParams) -> Self {
+        Self {
+            take_profit: risk.take_profit,
+            stop_loss: risk.stop_loss,
+        }
+    }
+}
+
+impl proto::Event {
+    /// The streamed form of `event`, if it is one of the kinds streamed.
+    pub fn from_event(event: &Event) -> Option<Self> {
+        use proto::event::Kind;
+
+        let kind = match event {
+            Event::Order {
+                strategy,
+                side,
+                price,
+                size,
+                ..
+            } => Kind::Order(proto::Order {
+                strategy: strategy.clone(),
+                side: proto::Side::from(*side).into(),
+                price: *price,
+                size: *size,
+            }),
+            Event::Fill {
+                strategy,
+                execution,
+                fill,
+                ..
+            } => Kind::Fill(proto::Fill {
+                strategy: strategy.clone(),
+                side: proto::Side::from(fill.side).into(),
+                price: fill.price,
+                size: fill.size,
+                fee: fill.fee,
+                venue: execution.venue.clone(),
+                order_type: proto::OrderType::from(execution.order_type).into(),
+                decision_price: execution.decision_price,
+                queue_ahead: execution.queue_ahead,
+            }),
+            Event::Equity {
+                portfolio_value, ..
+            } => Kind::Equity(proto::EquityMark {
+                portfolio_value: *portfolio_value,
+            }),
+            Event::Features { .. }
+            | Event::Signal { .. }
+            | Event::Rebalance { .. }
+            | Event::Session { .. }
+            | Event::Alert { .. } => return None,
+        };
+        Some(Self {
+            time: event.time().timestamp_millis(),
+            symbol: event.symbol().to_owned(),
+            kind: Some(kind),
+        })
+    }
+}
+
+#[derive(Debug)]
+struct Shared {
+    live: Mutex<LiveState>,
+    events: broadcast::Sender<proto::Event>,
+    control: Control,
+}
+
+impl Shared {
+    fn status(&self) -> proto::Status {
+        let live = self.live.lock().expect("live state lock poisoned");
+        proto::Status {
+            paused: self.control.is_paused(),
+            symbol: live.symbol().unwrap_or_default().to_owned(),
+            equity: live.equity().last(),
+            open_positions: live.positions().len() as u32,
+            risk: Some(self.control.risk().into()),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct EngineService {
+    shared: Arc<Shared>,
+    auth: Authenticator,
+}
+
+impl EngineService {
+    fn authorize<T>(&self, request: &Request<T>, needed: Scope) -> Result<Scope, Denied> {
+        let authorization = request
+            .metadata()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok());
+        self.auth.authorize(auth::bearer(authorization), needed)
+    }
+}
+
+impl From<Denied> for tonic::Status {
+    fn from(denied: Denied) -> Self {
+        match denied {
+            Denied::Unauthenticated => Self::unauthenticated("invalid token"),
+            Denied::Forbidden => {
+                Self::permission_denied("the token does not grant the control scope")
+            }
+        }
+    }
+}
+
+type EventStream = Pin<Box<dyn Stream<Item = Result<proto::Event, tonic::Status>> + Send>>;
+
+#[tonic::async_trait]
+impl Engine for EngineService {
+    type StreamEventsStream = EventStream;
+
+    async fn get_status(
+        &self,
+        request: Request<proto::StatusRequest>,
+    ) -> Result<Response<proto::Status>, tonic::Status> {
+        self.authorize(&request, Scope::Read)?;
+        Ok(Response::new(self.shared.status()))
+    }
+
+    async fn pause(
+        &self,
+        request: Request<proto::PauseRequest>,
+    ) -> Result<Response<proto::Status>, tonic::Status> {
+        self.authorize(&request, Scope::Control)?;
+        self.shared.control.pause();
+        Ok(Response::new(self.shared.status()))
+    }
+
+    async fn resume(
+        &self,
+        request: Request<proto::ResumeRequest>,
+    ) -> Result<Response<proto::Status>, tonic::Status> {
+        self.authorize(&request, Scope::Control)?;
+        self.shared.control.resume();
+        Ok(Response::new(self.shared.status()))
+    }
+
+    async fn flatten(
+        &self,
+        request: Request<proto::FlattenRequest>,
+    ) -> Result<Response<proto::Status>, tonic::Status> {
+        self.authorize(&request, Scope::Control)?;
+        self.shared.control.flatten();
+        Ok(Response::new(self.shared.status()))
+    }
+
+    async fn update_risk_params(
+        &self,
+        request: Request<proto::RiskParamsUpdate>,
+    ) -> Result<Response<proto::RiskParams>, tonic::Status> {
+        self.authorize(&request, Scope::Control)?;
+        let update = request.into_inner();
+        self.shared
+            .control
+            .update_risk(update.take_profit, update.stop_loss)
+            .map(|risk| Response::new(risk.into()))
+            .map_err(|error| tonic::Status::invalid_argument(error.to_string()))
+    }
+
+    async fn stream_events(
+        &self,
+        request: Request<proto::StreamEventsRequest>,
+    ) -> Result<Response<EventStream>, tonic::Status> {
+        self.authorize(&request, Scope::Read)?;
+        let events =
+            BroadcastStream::new(self.shared.events.subscribe()).filter_map(|event| match event {
+                Ok(event) => Some(Ok(event)),
+                Err(BroadcastStreamRecvError::Lagged(skipped)) => {
+                    warn!("gRPC client fell behind, skipped {} events", skipped);
+                    None
+                }
+            });
+        Ok(Response::new(Box::pin(events)))
+    }
+}
+
+/// Serves the engine service and streams the events it is given to subscribed clients.
+#[derive(Debug)]
+pub struct GrpcServer {
+    local_addr: SocketAddr,
+    shared: Arc<Shared>,
+}
+
+impl GrpcServer {
+    /// Listen on `address` and start serving in the background, over TLS if `tls` is set, to
+    /// the clients `auth` allows, managing the bot through `control`.
+    pub async fn bind(
+        address: &str,
+        auth: Authenticator,
+        tls: Option<&TlsConfig>,
+        control: Control,
+    ) -> Result<Self, AuthError> {
+        let listener = TcpListener::bind(address).await?;
+        let local_addr = listener.local_addr()?;
+        let (events, _) = broadcast::channel(CLIENT_BUFFER);
+        let shared = Arc::new(Shared {
+            live: Mutex::new(LiveState::default()),
+            events,
+            control,
+        });
+
+        let service = EngineServer::new(EngineService {
+            shared: shared.clone(),
+            auth,
+        });
+        let mut server = Server::builder();
+        if let Some(tls) = tls {
+            server = with_tls(server, tls)?;
+        }
+        tokio::spawn(async move {
+            let incoming = TcpListenerStream::new(listener);
+            if let Err(error) = server
+                .add_service(service)
+                .serve_with_incoming(incoming)
+                .await
+            {
+                warn!("gRPC server stopped: {}", error);
+            }
+        });
+        let scheme = if tls.is_some() { "https" } else { "http" };
+        info!("Serving gRPC on {}://{}", scheme, local_addr);
+
+        Ok(Self { local_addr, shared })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Count `events` toward the status and stream them to every subscribed client.
+    pub fn publish(&self, events: &[Event]) {
+        let mut live = self.shared.live.lock().expect("live state lock poisoned");
+        for event in events {
+            live.record(event);
+            if self.shared.events.receiver_count() > 0 {
+                if let Some(event) = proto::Event::from_event(event) {
+                    // Nobody left listening is not an error
+                    let _ = self.shared.events.send(event);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "tls")]
+fn with_tls(server: Server, tls: &TlsConfig) -> Result<Server, AuthError> {
+    use tonic::transport::Certificate;
+    use tonic::transport::Identity;
+    use tonic::transport::ServerTlsConfig;
+
+    let pem = auth::Pem::read(tls)?;
+    let mut config = ServerTlsConfig::new().identity(Identity::from_pem(pem.cert, pem.key));
+    if let Some(client_ca) = pem.client_ca {
+        config = config.client_ca_root(Certificate::from_pem(client_ca));
+    }
+    server
+        .tls_config(config)
+        .map_err(|error| AuthError::Tls(error.to_string()))
+}
+
+#[cfg(not(feature = "tls"))]
+fn with_tls(_: Server, _: &TlsConfig) -> Result<Server, AuthError> {
+    Err(AuthError::TlsUnsupported)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::proto::engine_client::EngineClient;
+    use super::*;
+    use crate::event::Execution;
+    use crate::Fill;
+
+    fn authorized<T>(message: T) -> Request<T> {
+        let mut request = Request::new(message);
+        let token = "Bearer secret".parse().unwrap();
+        request.metadata_mut().insert("authorization", token);
+        request
+    }
+
+    #[tokio::test]
+    async fn test_control_and_stream() {
+        let control = Control::default();
+        let auth = Authenticator::with_token("secret", Scope::Control);
+        let server = GrpcServer::bind("127.0.0.1:0", auth, None, control.clone())
+            .await
+            .unwrap();
+        let mut client = EngineClient::connect(format!("http://{}", server.local_addr()))
+            .await
+            .unwrap();
+
+        let error = client.pause(proto::PauseRequest {}).await.unwrap_err();
+        assert_eq!(error.code(), tonic::Code::Unauthenticated);
+        assert!(!control.is_paused());
+
+        let status = client
+            .pause(authorized(proto::PauseRequest {}))
+            .await
+            .unwrap();
+        assert!(status.into_inner().paused);
+        assert!(control.is_paused());
+
+        let update = proto::RiskParamsUpdate {
+            take_profit: Some(0.02),
+            stop_loss: None,
+        };
+        let risk = client
+            .update_risk_params(authorized(update))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!((risk.take_profit, risk.stop_loss), (0.02, 0.02));
+        let invalid = proto::RiskParamsUpdate {
+            take_profit: None,
+            stop_loss: Some(-1.0),
+        };
+        let error = client
+            .update_risk_params(authorized(invalid))
+            .await
+            .unwrap_err();
+        assert_eq!(error.code(), tonic::Code::InvalidArgument);
+
+        let mut stream = client
+            .stream_events(authorized(proto::StreamEventsRequest {}))
+            .await
+            .unwrap()
+            .into_inner();
+        server.publish(&[
+            Event::Features {
+                time: Utc::now(),
+                symbol: "BTC/USDT",
+                features: Default::default(),
+                book_time: None,
+            },
+            Event::Fill {
+                time: Utc::now(),
+                symbol: "BTC/USDT",
+                strategy: "imbalance".to_owned(),
+                sleeve: 0,
+                execution: Execution::paper(OrderType::Limit, 100.0),
+                fill: Fill {
+                    side: Side::Buy,
+                    price: 100.0,
+                    size: 0.5,
+                    fee: 0.1,
+                },
+                book_time: None,
+            },
+        ]);
+
+        // Features aren't streamed, so the fill comes first
+        let event = stream.message().await.unwrap().unwrap();
+        assert_eq!(event.symbol, "BTC/USDT");
+        let Some(proto::event::Kind::Fill(fill)) = event.kind else {
+            panic!("expected a fill, got {:?}", event.kind);
+        };
+        assert_eq!((fill.price, fill.venue.as_str()), (100.0, "paper"));
+        assert_eq!(fill.side(), proto::Side::Buy);
+
+        let status = client
+            .get_status(authorized(proto::StatusRequest {}))
+            .await
+            .unwrap();
+        assert_eq!(status.into_inner().open_positions, 1);
+    }
+}
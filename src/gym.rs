@@ -0,0 +1,187 @@
+//! Gym-style `reset()`/`step()` environment over the backtest simulator, so reinforcement learning
+//! agents train against the same order accounting the bot trades with.
+//!
+//! An episode replays a fixed sequence of books. Each step the agent picks one of [`ACTIONS`], which
+//! is executed at the current book, then the environment advances to the next book and rewards the
+//! change in portfolio value marked at its bid.
+
+use barter_data::subscription::book::OrderBook;
+
+use crate::features::Features;
+use crate::features::FEATURE_COUNT;
+use crate::strategy::Signal;
+use crate::TradingState;
+
+/// The discrete action space, indexed by action number.
+pub const ACTIONS: [Signal; 3] = [Signal::Hold, Signal::Buy, Signal::Sell];
+
+/// What the agent sees of each book: its [`Features`] as a vector.
+pub type Observation = [f64; FEATURE_COUNT];
+
+/// Outcome of one [`TradingEnv::step`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Step {
+    pub observation: Observation,
+    pub reward: f64,
+    /// Whether the episode ran out of books; further steps do nothing until [`TradingEnv::reset`].
+    pub done: bool,
+    pub portfolio_value: f64,
+    pub open_positions: usize,
+}
+
+#[derive(Debug)]
+pub struct TradingEnv {
+    episode: Vec<Features>,
+    cash: f64,
+    state: TradingState,
+    cursor: usize,
+}
+
+impl TradingEnv {
+    /// An environment replaying `books` with `cash` to start each episode, or `None` if no book has
+    /// both sides.
+    pub fn new<'a>(books: impl IntoIterator<Item = &'a OrderBook>, cash: f64) -> Option<Self> {
+        let episode: Vec<Features> = books
+            .into_iter()
+            .filter_map(Features::from_order_book)
+            .collect();
+        if episode.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            episode,
+            cash,
+            state: TradingState::new(cash, "GYM"),
+            cursor: 0,
+        })
+    }
+
+    /// Number of steps in a full episode.
+    pub fn len(&self) -> usize {
+        self.episode.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn state(&self) -> &TradingState {
+        &self.state
+    }
+
+    /// Start a new episode from the first book with a flat book and the initial cash.
+    pub fn reset(&mut self) -> Observation {
+        self.state = TradingState::new(self.cash, "GYM");
+        self.cursor = 0;
+        self.episode[0].to_array()
+    }
+
+    pub fn step(&mut self, action: Signal) -> Step {
+        let current = self.episode[self.cursor];
+        let done = self.cursor + 1 >= self.episode.len();
+        if done {
+            return Step {
+                observation: current.to_array(),
+                reward: 0.0,
+                done,
+                portfolio_value: self.state.calculate_portfolio_value(current.bid),
+                open_positions: self.state.positions.len(),
+            };
+        }
+
+        let before = self.state.calculate_portfolio_value(current.bid);
+        // An action the state can't take, like selling while flat, does nothing
+        let _ = self.state.execute_signal(action, &current);
+        self.state.check_exits(current.bid);
+
+        self.cursor += 1;
+        let next = self.episode[self.cursor];
+        let portfolio_value = self.state.calculate_portfolio_value(next.bid);
+
+        Step {
+            observation: next.to_array(),
+            reward: portfolio_value - before,
+            done: self.cursor + 1 >= self.episode.len(),
+            portfolio_value,
+            open_positions: self.state.positions.len(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use barter_data::subscription::book::Level;
+    use barter_data::subscription::book::OrderBookSide;
+    use barter_integration::model::Side;
+    use chrono::DateTime;
+
+    use super::*;
+    use crate::TRADE_SIZE;
+    use crate::TRANSACTION_COST;
+
+    fn book(bid: f64, ask: f64) -> OrderBook {
+        OrderBook {
+            last_update_time: DateTime::from_timestamp_millis(0).unwrap(),
+            bids: OrderBookSide::new(
+                Side::Buy,
+                vec![Level {
+                    price: bid,
+                    amount: 1.0,
+                }],
+            ),
+            asks: OrderBookSide::new(
+                Side::Sell,
+                vec![Level {
+                    price: ask,
+                    amount: 1.0,
+                }],
+            ),
+        }
+    }
+
+    #[test]
+    fn test_episode() {
+        let books = [
+            book(100.0, 100.01),
+            book(100.5, 100.51),
+            book(100.2, 100.21),
+        ];
+        let mut env = TradingEnv::new(&books, 1000.0).unwrap();
+        assert_eq!(env.len(), 2);
+
+        let observation = env.reset();
+        assert_eq!(observation[0], 100.0);
+
+        // Buying at 100 pays the fee and is marked up at the next bid
+        let step = env.step(Signal::Buy);
+        let fee = 100.0 * TRADE_SIZE * TRANSACTION_COST;
+        assert!((step.reward - (0.5 * TRADE_SIZE - fee)).abs() < 1e-9);
+        assert_eq!(step.observation[0], 100.5);
+        assert_eq!(step.open_positions, 1);
+        assert!(!step.done);
+
+        let step = env.step(Signal::Hold);
+        assert!((step.reward + 0.3 * TRADE_SIZE).abs() < 1e-9);
+        assert!(step.done);
+
+        // Stepping a finished episode does nothing
+        let finished = env.step(Signal::Buy);
+        assert_eq!(finished.reward, 0.0);
+        assert_eq!(finished.open_positions, 1);
+
+        env.reset();
+        assert_eq!(env.state().cash, 1000.0);
+        assert!(env.state().positions.is_empty());
+    }
+
+    #[test]
+    fn test_requires_a_tradable_book() {
+        let empty = OrderBook {
+            last_update_time: DateTime::from_timestamp_millis(0).unwrap(),
+            bids: OrderBookSide::new(Side::Buy, Vec::<Level>::new()),
+            asks: OrderBookSide::new(Side::Sell, Vec::<Level>::new()),
+        };
+        assert!(TradingEnv::new(&[empty], 1000.0).is_none());
+    }
+}
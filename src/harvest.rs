@@ -0,0 +1,264 @@
+//! Funding harvesting, [`[harvest]`](crate::config::HarvestConfig). Rather than trading the book,
+//! the harvester holds the side of the perpetual that funding pays while it persistently does:
+//! once each of the last `periods` funding rates has had the same sign and they average at least
+//! `min_rate`, the perpetual is shorted when longs pay, or bought when shorts do, and hedged with
+//! the opposite spot leg of the same size so price moves cancel out. Both legs close together
+//! once the latest rate pays the held side less than `exit_rate`.
+//!
+//! Decisions only change as new funding rates come in, a few times a day, however often books
+//! arrive. Funding is booked against the perpetual leg at every funding timestamp it is held
+//! through, and counted apart from the price P&L of the legs. An alert announces each position
+//! opened, and another once it is closed.
+//!
+//! Like [basis](crate::basis) positions, harvested ones are paper traded outside the strategies'
+//! sleeves.
+
+use barter_integration::model::Side;
+use chrono::DateTime;
+use chrono::Utc;
+use std::collections::VecDeque;
+use tracing::info;
+
+use crate::basis::BasisPosition;
+use crate::config::HarvestConfig;
+use crate::event::Alert;
+use crate::event::Severity;
+use crate::features::Features;
+use crate::funding::FundingRate;
+
+/// The [condition](Alert::condition) every funding harvesting alert is about.
+pub const CONDITION: &str = "funding_harvest";
+
+#[derive(Debug)]
+pub struct FundingHarvester {
+    config: HarvestConfig,
+    /// The latest spot top of book, for the hedge leg.
+    spot: Option<Features>,
+    rate: Option<FundingRate>,
+    /// The rate of each of the latest funding timestamps, oldest first.
+    history: VecDeque<(DateTime<Utc>, f64)>,
+    position: Option<BasisPosition>,
+    /// P&L of the positions closed so far, funding included.
+    realised: f64,
+    /// Funding booked by the positions closed so far.
+    collected: f64,
+}
+
+impl FundingHarvester {
+    pub fn new(config: &HarvestConfig) -> Self {
+        Self {
+            config: config.clone(),
+            spot: None,
+            rate: None,
+            history: VecDeque::new(),
+            position: None,
+            realised: 0.0,
+            collected: 0.0,
+        }
+    }
+
+    /// Take `rate` as the latest, and as the rate of its funding timestamp, if the exchange says
+    /// which.
+    pub fn set_rate(&mut self, rate: FundingRate) {
+        self.rate = Some(rate);
+        let Some(next) = rate.next else {
+            return;
+        };
+        match self.history.back_mut() {
+            Some((timestamp, latest)) if *timestamp == next => *latest = rate.rate,
+            _ => self.history.push_back((next, rate.rate)),
+        }
+        while self.history.len() > self.config.periods {
+            self.history.pop_front();
+        }
+    }
+
+    /// Keep the spot book's `features` to hedge against.
+    pub fn on_spot(&mut self, features: &Features) {
+        self.spot = Some(*features);
+    }
+
+    pub fn position(&self) -> Option<&BasisPosition> {
+        self.position.as_ref()
+    }
+
+    pub fn realised(&self) -> f64 {
+        self.realised
+    }
+
+    /// Funding booked so far, by closed positions and the one still held.
+    pub fn collected(&self) -> f64 {
+        self.collected + self.position.map_or(0.0, |position| position.funding)
+    }
+
+    /// The average rate of the last `periods` funding timestamps, once all of them are known and
+    /// have the same sign.
+    pub fn skew(&self) -> Option<f64> {
+        if self.history.len() < self.config.periods.max(1) {
+            return None;
+        }
+        let positive = self.history.iter().all(|(_, rate)| *rate > 0.0);
+        let negative = self.history.iter().all(|(_, rate)| *rate < 0.0);
+        let total: f64 = self.history.iter().map(|(_, rate)| rate).sum();
+        (positive || negative).then(|| total / self.history.len() as f64)
+    }
+
+    /// Book funding and open or close the position from the `perpetual` book at `now`,
+    /// returning the alert to raise if it opened or closed.
+    pub fn update(&mut self, perpetual: &Features, now: DateTime<Utc>) -> Option<Alert> {
+        if let (Some(position), Some(rate)) = (&mut self.position, self.rate) {
+            position.fund(rate, perpetual.mid_price, now);
+        }
+        let spot = self.spot?;
+
+        if let Some(position) = self.position {
+            // Held for as long as funding keeps paying the perpetual's side
+            let paid = self.rate.map_or(0.0, |rate| match position.perpetual.side {
+                Side::Buy => -rate.rate,
+                Side::Sell => rate.rate,
+            });
+            if paid >= self.config.exit_rate {
+                return None;
+            }
+            let pnl = position.pnl(&spot, perpetual, self.config.fee);
+            self.realised += pnl;
+            self.collected += position.funding;
+            self.position = None;
+            let message = format!(
+                "Funding down to {:.4}% for the held side, closed both legs for {:.2}, {:.2} of \
+                 it funding",
+                paid * 100.0,
+                pnl,
+                position.funding
+            );
+            info!("{}", message);
+            return Some(Alert {
+                severity: Severity::Info,
+                message,
+                condition: Some(CONDITION.to_owned()),
+                resolved: true,
+            });
+        }
+
+        let skew = self
+            .skew()
+            .filter(|skew| skew.abs() >= self.config.min_rate)?;
+        // Longs pay a positive rate, so the perpetual is shorted against spot bought
+        let spot_side = if skew > 0.0 { Side::Buy } else { Side::Sell };
+        let position = BasisPosition::open(
+            spot_side,
+            &spot,
+            perpetual,
+            self.config.size,
+            self.config.fee,
+            now,
+        );
+        self.position = Some(position);
+        let legs = match spot_side {
+            Side::Buy => "short perpetual hedged with long spot",
+            Side::Sell => "long perpetual hedged with short spot",
+        };
+        let message = format!(
+            "Funding averaging {:.4}% over the last {} periods, opened {} at {} and {}",
+            skew * 100.0,
+            self.history.len(),
+            legs,
+            position.perpetual.price,
+            position.spot.price
+        );
+        info!("{}", message);
+        Some(Alert {
+            severity: Severity::Info,
+            message,
+            condition: Some(CONDITION.to_owned()),
+            resolved: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn harvester() -> FundingHarvester {
+        let mut harvester = FundingHarvester::new(&HarvestConfig {
+            min_rate: 0.0001,
+            exit_rate: 0.00002,
+            periods: 3,
+            fee: 0.0005,
+            size: 1.0,
+        });
+        harvester.on_spot(&book(99.9, 100.1));
+        harvester
+    }
+
+    fn book(bid: f64, ask: f64) -> Features {
+        Features {
+            bid,
+            ask,
+            mid_price: (bid + ask) / 2.0,
+            ..Features::default()
+        }
+    }
+
+    fn epoch(hours: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000 + hours * 3600, 0).unwrap()
+    }
+
+    fn rate(rate: f64, hours: i64) -> FundingRate {
+        FundingRate {
+            rate,
+            next: Some(epoch(hours)),
+        }
+    }
+
+    #[test]
+    fn test_opens_only_on_persistent_skew() {
+        let mut harvester = harvester();
+        harvester.set_rate(rate(0.0003, 8));
+        harvester.set_rate(rate(-0.0001, 16));
+        // Repolled ahead of the same timestamp, the rate replaces the one before
+        harvester.set_rate(rate(0.0002, 16));
+        assert_eq!(harvester.skew(), None);
+        assert_eq!(harvester.update(&book(99.9, 100.1), epoch(0)), None);
+
+        harvester.set_rate(rate(0.0001, 24));
+        assert!((harvester.skew().unwrap() - 0.0002).abs() < 1e-12);
+        harvester.set_rate(rate(-0.0003, 32));
+        assert_eq!(harvester.skew(), None);
+        harvester.set_rate(rate(-0.0003, 40));
+        harvester.set_rate(rate(-0.0003, 48));
+        let opened = harvester.update(&book(99.9, 100.1), epoch(47)).unwrap();
+        assert!(!opened.resolved);
+        let position = harvester.position().unwrap();
+        assert_eq!(position.perpetual.side, Side::Buy);
+        assert_eq!(position.spot.side, Side::Sell);
+    }
+
+    #[test]
+    fn test_funding_accrues_until_it_fades() {
+        let mut harvester = harvester();
+        for hours in [8, 16, 24] {
+            harvester.set_rate(rate(0.0005, hours));
+        }
+        harvester.update(&book(99.9, 100.1), epoch(23)).unwrap();
+        assert_eq!(harvester.position().unwrap().perpetual.side, Side::Sell);
+
+        // Short, so paid at each timestamp held through, once each
+        assert_eq!(harvester.update(&book(99.9, 100.1), epoch(24)), None);
+        harvester.set_rate(rate(0.0004, 32));
+        assert_eq!(harvester.update(&book(99.9, 100.1), epoch(25)), None);
+        assert_eq!(harvester.update(&book(99.9, 100.1), epoch(32)), None);
+        let funding = (0.0005 + 0.0004) * 100.0;
+        assert!((harvester.collected() - funding).abs() < 1e-9);
+
+        harvester.set_rate(rate(0.00001, 40));
+        let closed = harvester.update(&book(99.9, 100.1), epoch(33)).unwrap();
+        assert!(closed.resolved);
+        assert_eq!(harvester.position(), None);
+        // Both legs crossed the spread twice and paid four fees, with the funding to show for it
+        let pnl = -0.2 * 2.0 - 4.0 * 0.0005 * 100.0 + funding;
+        assert!((harvester.realised() - pnl).abs() < 1e-9);
+        assert!((harvester.collected() - funding).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,264 @@
+//! Feed health: judges the order book feed by how often it goes silent and has to be
+//! resubscribed, and how many of its updates can't be turned into features, raising escalating
+//! alerts and holding off entries while it is degraded.
+
+use std::collections::VecDeque;
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::info;
+use tracing::warn;
+
+use crate::config::HealthConfig;
+use crate::event::Alert;
+use crate::event::Severity;
+use crate::hours::Permission;
+
+/// The [condition](Alert::condition) every feed health alert is about.
+pub const CONDITION: &str = "feed_degraded";
+
+/// Updates needed within the window before the error rate is judged, so a single bad book just
+/// after starting doesn't count as a degraded feed.
+const MIN_UPDATES: usize = 20;
+
+#[derive(Debug)]
+pub struct FeedHealth {
+    config: HealthConfig,
+    // Every update within the window and whether it was usable, oldest first
+    updates: VecDeque<(Instant, bool)>,
+    errors: usize,
+    resubscribes: VecDeque<Instant>,
+    silent: bool,
+    degraded_since: Option<Instant>,
+    escalated: bool,
+}
+
+impl FeedHealth {
+    pub fn new(config: &HealthConfig) -> Self {
+        Self {
+            config: config.clone(),
+            updates: VecDeque::new(),
+            errors: 0,
+            resubscribes: VecDeque::new(),
+            silent: false,
+            degraded_since: None,
+            escalated: false,
+        }
+    }
+
+    /// Count a book update, `usable` if features could be computed from it.
+    pub fn on_update(&mut self, usable: bool, now: Instant) {
+        self.silent = false;
+        self.updates.push_back((now, usable));
+        if !usable {
+            self.errors += 1;
+        }
+    }
+
+    /// Count the feed going silent and being resubscribed. It stays silent until the next update.
+    pub fn on_resubscribe(&mut self, now: Instant) {
+        self.silent = true;
+        self.resubscribes.push_back(now);
+    }
+
+    /// Forget updates and resubscribes older than the window.
+    fn expire(&mut self, now: Instant) {
+        let window = Duration::from_secs(self.config.window_secs);
+        while let Some(&(time, usable)) = self.updates.front() {
+            if now.duration_since(time) < window {
+                break;
+            }
+            self.updates.pop_front();
+            if !usable {
+                self.errors -= 1;
+            }
+        }
+        while let Some(&time) = self.resubscribes.front() {
+            if now.duration_since(time) < window {
+                break;
+            }
+            self.resubscribes.pop_front();
+        }
+    }
+
+    /// Why the feed counts as degraded, empty if it doesn't.
+    fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.silent {
+            problems.push("silent".to_owned());
+        }
+        if self.resubscribes.len() > self.config.max_resubscribes {
+            problems.push(format!(
+                "{} resubscribes in {}s",
+                self.resubscribes.len(),
+                self.config.window_secs
+            ));
+        }
+        if self.updates.len() >= MIN_UPDATES {
+            let error_rate = self.errors as f64 / self.updates.len() as f64;
+            if error_rate > self.config.max_error_rate {
+                problems.push(format!("{:.1}% of updates unusable", error_rate * 100.0));
+            }
+        }
+        problems
+    }
+
+    /// Judge the feed at `now`, returning the alert to raise if it degraded, has been degraded
+    /// long enough to escalate, or recovered since the last update.
+    pub fn update(&mut self, now: Instant) -> Option<Alert> {
+        self.expire(now);
+        let problems = self.problems();
+        let escalate_after = Duration::from_secs(self.config.escalate_after_secs);
+        match self.degraded_since {
+            None if problems.is_empty() => None,
+            None => {
+                self.degraded_since = Some(now);
+                self.escalated = false;
+                Some(raise(
+                    Severity::Warning,
+                    format!(
+                        "Order book feed degraded ({}), blocking entries",
+                        problems.join(", ")
+                    ),
+                ))
+            }
+            Some(since) if problems.is_empty() => {
+                self.degraded_since = None;
+                let mut recovered = raise(
+                    Severity::Info,
+                    format!(
+                        "Order book feed recovered after {}s, allowing entries",
+                        now.duration_since(since).as_secs()
+                    ),
+                );
+                recovered.resolved = true;
+                Some(recovered)
+            }
+            Some(since) if !self.escalated && now.duration_since(since) >= escalate_after => {
+                self.escalated = true;
+                Some(raise(
+                    Severity::Critical,
+                    format!(
+                        "Order book feed still degraded after {}s ({}), entries blocked",
+                        now.duration_since(since).as_secs(),
+                        problems.join(", ")
+                    ),
+                ))
+            }
+            Some(_) => None,
+        }
+    }
+
+    /// Entries are blocked while the feed is degraded.
+    pub fn permission(&self) -> Permission {
+        Permission {
+            entries: self.degraded_since.is_none(),
+            flatten: false,
+        }
+    }
+}
+
+/// Log an alert as it is raised.
+fn raise(severity: Severity, message: String) -> Alert {
+    match severity {
+        Severity::Info => info!("{}", message),
+        Severity::Warning | Severity::Critical => warn!("{}", message),
+    }
+    Alert {
+        severity,
+        message,
+        condition: Some(CONDITION.to_owned()),
+        resolved: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn health() -> FeedHealth {
+        FeedHealth::new(&HealthConfig {
+            window_secs: 60,
+            max_resubscribes: 1,
+            max_error_rate: 0.1,
+            escalate_after_secs: 30,
+        })
+    }
+
+    fn severity(alert: Option<Alert>) -> Option<Severity> {
+        alert.map(|alert| alert.severity)
+    }
+
+    #[test]
+    fn test_silence_degrades_until_the_next_update() {
+        let start = Instant::now();
+        let seconds = |secs| start + Duration::from_secs(secs);
+        let mut health = health();
+        assert_eq!(health.update(start), None);
+        assert!(health.permission().entries);
+
+        health.on_resubscribe(seconds(1));
+        assert_eq!(severity(health.update(seconds(1))), Some(Severity::Warning));
+        assert!(!health.permission().entries);
+        assert_eq!(health.update(seconds(2)), None);
+        assert_eq!(
+            severity(health.update(seconds(31))),
+            Some(Severity::Critical)
+        );
+        assert_eq!(health.update(seconds(40)), None);
+
+        health.on_update(true, seconds(41));
+        let recovered = health.update(seconds(41)).unwrap();
+        assert_eq!(recovered.severity, Severity::Info);
+        assert!(recovered.message.contains("after 40s"));
+        assert!(recovered.resolved);
+        assert_eq!(recovered.condition.as_deref(), Some(CONDITION));
+        assert!(health.permission().entries);
+    }
+
+    #[test]
+    fn test_repeated_resubscribes_degrade_within_the_window() {
+        let start = Instant::now();
+        let seconds = |secs| start + Duration::from_secs(secs);
+        let mut health = health();
+        for secs in [0, 10] {
+            health.on_resubscribe(seconds(secs));
+            health.on_update(true, seconds(secs + 1));
+        }
+        let degraded = health.update(seconds(11)).unwrap();
+        assert!(degraded.message.contains("2 resubscribes in 60s"));
+        assert!(!degraded.resolved);
+        assert!(!health.permission().entries);
+
+        // Escalated by then, and healthy once the first resubscribe leaves the window
+        assert_eq!(
+            severity(health.update(seconds(59))),
+            Some(Severity::Critical)
+        );
+        assert_eq!(severity(health.update(seconds(60))), Some(Severity::Info));
+    }
+
+    #[test]
+    fn test_unusable_updates_degrade() {
+        let start = Instant::now();
+        let mut health = health();
+        // Too few updates to judge
+        for _ in 0..5 {
+            health.on_update(false, start);
+        }
+        assert_eq!(health.update(start), None);
+
+        for _ in 0..50 {
+            health.on_update(true, start);
+        }
+        assert_eq!(health.update(start), None);
+        for _ in 0..5 {
+            health.on_update(false, start);
+        }
+        let degraded = health.update(start).unwrap();
+        assert!(degraded.message.contains("16.7% of updates unusable"));
+
+        let later = start + Duration::from_secs(60);
+        health.on_update(true, later);
+        assert_eq!(severity(health.update(later)), Some(Severity::Info));
+    }
+}
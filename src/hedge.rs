@@ -0,0 +1,221 @@
+//! Delta hedging, [`[hedge]`](crate::config::HedgeConfig), alongside whichever strategies are
+//! trading. After every update the net delta of every open position, the strategies' sleeves and
+//! the [basis](crate::basis) and [funding](crate::harvest) positions, is added to the hedge's own,
+//! and once it strays more than `band` from flat the hedge instrument is traded to bring it back,
+//! taking liquidity at the top of its latest book.
+//!
+//! The hedge is capped at `max_position` either way, when set. A warning is raised while the cap
+//! leaves the net delta outside the band, and resolved once it is back within it.
+//!
+//! Like [basis](crate::basis) positions, the hedge is paper traded on its own, outside the
+//! strategies' sleeves, which only ever hold longs.
+
+use barter_integration::model::instrument::kind::InstrumentKind;
+use barter_integration::model::Side;
+use tracing::info;
+use tracing::warn;
+
+use crate::allocation::Allocator;
+use crate::config::HedgeConfig;
+use crate::config::HedgeInstrument;
+use crate::event::Alert;
+use crate::event::Severity;
+use crate::features::Features;
+use crate::Fill;
+
+/// The [condition](Alert::condition) every delta hedging alert is about.
+pub const CONDITION: &str = "delta_hedge";
+
+/// The net delta of the strategies' sleeves, long positive: every lot they hold is long its size.
+pub fn sleeve_delta(allocator: &Allocator) -> f64 {
+    allocator
+        .sleeves()
+        .iter()
+        .map(|sleeve| sleeve.state.position_size())
+        .sum()
+}
+
+#[derive(Debug)]
+pub struct DeltaHedger {
+    config: HedgeConfig,
+    /// The latest top of book of the hedge instrument.
+    book: Option<Features>,
+    /// The hedge held, long positive.
+    position: f64,
+    /// Cash in from selling the hedge, less what buying it and every fee cost.
+    cash: f64,
+    limited: bool,
+}
+
+impl DeltaHedger {
+    pub fn new(config: &HedgeConfig) -> Self {
+        Self {
+            config: config.clone(),
+            book: None,
+            position: 0.0,
+            cash: 0.0,
+            limited: false,
+        }
+    }
+
+    /// Keep `features` of a `kind` book, if it is the hedge instrument's.
+    pub fn on_book(&mut self, kind: InstrumentKind, features: &Features) {
+        let hedged = match self.config.instrument {
+            HedgeInstrument::Perpetual => kind == InstrumentKind::Perpetual,
+            HedgeInstrument::Spot => kind == InstrumentKind::Spot,
+        };
+        if hedged {
+            self.book = Some(*features);
+        }
+    }
+
+    pub fn position(&self) -> f64 {
+        self.position
+    }
+
+    /// What the hedge has made so far, marked at the latest mid, once it has a book.
+    pub fn pnl(&self) -> Option<f64> {
+        Some(self.cash + self.position * self.book?.mid_price)
+    }
+
+    /// Trade the hedge to offset `delta`, the net delta of every other position, if it and the
+    /// hedge together are outside the band, returning the fill.
+    pub fn hedge(&mut self, delta: f64) -> Option<Fill> {
+        let book = self.book?;
+        let net = delta + self.position;
+        if net.abs() <= self.config.band {
+            return None;
+        }
+        let target = match self.config.max_position {
+            Some(max) => (-delta).clamp(-max, max),
+            None => -delta,
+        };
+        let size = (target - self.position).abs();
+        if size <= 0.0 {
+            return None;
+        }
+        let (side, price) = if target > self.position {
+            (Side::Buy, book.ask)
+        } else {
+            (Side::Sell, book.bid)
+        };
+        let fee = price * size * self.config.fee;
+        match side {
+            Side::Buy => {
+                self.position += size;
+                self.cash -= price * size + fee;
+            }
+            Side::Sell => {
+                self.position -= size;
+                self.cash += price * size - fee;
+            }
+        }
+        info!(
+            "Hedging a net delta of {:.6}: {:?} {} at {}, holding {:.6}",
+            net, side, size, price, self.position
+        );
+        Some(Fill {
+            side,
+            price,
+            size,
+            fee,
+        })
+    }
+
+    /// Hedge `delta` like [`hedge`](Self::hedge), returning the alert to raise if the cap just
+    /// stopped keeping the net delta within the band, or it is back within it.
+    pub fn update(&mut self, delta: f64) -> Option<Alert> {
+        self.hedge(delta);
+        let net = delta + self.position;
+        let limited = self.book.is_some() && net.abs() > self.config.band;
+        if limited == self.limited {
+            return None;
+        }
+        self.limited = limited;
+        if !limited {
+            let message = "Net delta back within the hedging band";
+            info!("{}", message);
+            return Some(Alert {
+                severity: Severity::Info,
+                message: message.to_owned(),
+                condition: Some(CONDITION.to_owned()),
+                resolved: true,
+            });
+        }
+        let message = format!(
+            "Hedge capped at {:.6}, leaving a net delta of {:.6} outside the {} band",
+            self.position, net, self.config.band
+        );
+        warn!("{}", message);
+        Some(Alert {
+            severity: Severity::Warning,
+            message,
+            condition: Some(CONDITION.to_owned()),
+            resolved: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hedger(max_position: Option<f64>) -> DeltaHedger {
+        let mut hedger = DeltaHedger::new(&HedgeConfig {
+            band: 0.002,
+            instrument: HedgeInstrument::Spot,
+            fee: 0.001,
+            max_position,
+        });
+        hedger.on_book(InstrumentKind::Perpetual, &book(200.0, 201.0));
+        hedger.on_book(InstrumentKind::Spot, &book(99.0, 101.0));
+        hedger
+    }
+
+    fn book(bid: f64, ask: f64) -> Features {
+        Features {
+            bid,
+            ask,
+            mid_price: (bid + ask) / 2.0,
+            ..Features::default()
+        }
+    }
+
+    #[test]
+    fn test_hedges_outside_the_band() {
+        let mut hedger = hedger(None);
+        assert_eq!(hedger.hedge(0.002), None);
+
+        // Long 0.005 is sold down to flat on the spot book, not the perpetual
+        let fill = hedger.hedge(0.005).unwrap();
+        assert_eq!(
+            (fill.side, fill.price, fill.size),
+            (Side::Sell, 99.0, 0.005)
+        );
+        assert_eq!(hedger.position(), -0.005);
+        assert_eq!(hedger.hedge(0.006), None);
+
+        // Then bought back once the longs are gone
+        let fill = hedger.hedge(0.0).unwrap();
+        assert_eq!(
+            (fill.side, fill.price, fill.size),
+            (Side::Buy, 101.0, 0.005)
+        );
+        assert_eq!(hedger.position(), 0.0);
+        let fees = 0.005 * (99.0 + 101.0) * 0.001;
+        assert!((hedger.pnl().unwrap() - (-0.005 * 2.0 - fees)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_capped_hedge_alerts() {
+        let mut hedger = hedger(Some(0.004));
+        assert_eq!(hedger.update(0.005), None);
+        assert_eq!(hedger.position(), -0.004);
+
+        let capped = hedger.update(0.01).unwrap();
+        assert_eq!(capped.severity, Severity::Warning);
+        assert_eq!(capped.condition.as_deref(), Some(CONDITION));
+        assert_eq!(hedger.update(0.01), None);
+        assert!(hedger.update(0.005).unwrap().resolved);
+    }
+}
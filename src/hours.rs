@@ -0,0 +1,217 @@
+//! Trading hours: the windows new positions may be opened in, the blackouts around scheduled
+//! events or exchange maintenance when they may not, and the time of day everything is closed.
+
+use chrono::DateTime;
+use chrono::Days;
+use chrono::NaiveTime;
+use chrono::Utc;
+use tracing::info;
+
+use crate::config::BlackoutConfig;
+use crate::config::HoursConfig;
+use crate::config::WindowConfig;
+
+/// What may be traded at a given time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Permission {
+    pub entries: bool,
+    /// Set once, on the first update inside a blackout or other period that flattens.
+    pub flatten: bool,
+}
+
+impl Default for Permission {
+    fn default() -> Self {
+        Self {
+            entries: true,
+            flatten: false,
+        }
+    }
+}
+
+impl Permission {
+    /// Allow only what both allow, and flatten when either asks to.
+    pub fn and(self, other: Self) -> Self {
+        Self {
+            entries: self.entries && other.entries,
+            flatten: self.flatten || other.flatten,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct TradingHours {
+    windows: Vec<WindowConfig>,
+    blackouts: Vec<BlackoutConfig>,
+    // Blackouts already flattened for, by index
+    flattened: Vec<bool>,
+    flatten_at: Option<NaiveTime>,
+    entries: bool,
+    last_update: Option<DateTime<Utc>>,
+}
+
+impl TradingHours {
+    pub fn new(config: &HoursConfig) -> Self {
+        Self {
+            windows: config.windows.clone(),
+            blackouts: config.blackouts.clone(),
+            flattened: vec![false; config.blackouts.len()],
+            flatten_at: config.flatten_at,
+            entries: true,
+            last_update: None,
+        }
+    }
+
+    /// Whether the daily flatten time passed since the last update. Nothing is closed on the
+    /// first update, so a restart after the time doesn't close what was restored.
+    fn flatten_due(&self, now: DateTime<Utc>) -> bool {
+        let (Some(flatten_at), Some(last_update)) = (self.flatten_at, self.last_update) else {
+            return false;
+        };
+        let mut latest = now.date_naive().and_time(flatten_at).and_utc();
+        if latest > now {
+            latest = latest - Days::new(1);
+        }
+        last_update < latest
+    }
+
+    fn in_window(&self, time: NaiveTime) -> bool {
+        self.windows.is_empty()
+            || self.windows.iter().any(|window| {
+                if window.start <= window.end {
+                    window.start <= time && time < window.end
+                } else {
+                    window.start <= time || time < window.end
+                }
+            })
+    }
+
+    /// The blackout in force at `now`, if any, by index.
+    fn blackout(&self, now: DateTime<Utc>) -> Option<usize> {
+        self.blackouts
+            .iter()
+            .position(|blackout| blackout.start <= now && now < blackout.end)
+    }
+
+    /// What is allowed at `now`, logging whenever entries are blocked or allowed again.
+    pub fn update(&mut self, now: DateTime<Utc>) -> Permission {
+        let blackout = self.blackout(now);
+        let entries = blackout.is_none() && self.in_window(now.time());
+        if entries != self.entries {
+            match blackout.map(|index| &self.blackouts[index]) {
+                Some(blackout) => info!(
+                    "Blackout {}until {}, blocking entries",
+                    blackout
+                        .name
+                        .as_ref()
+                        .map(|name| format!("{name} "))
+                        .unwrap_or_default(),
+                    blackout.end
+                ),
+                None if !entries => info!("Outside trading windows, blocking entries"),
+                None => info!("Inside trading hours, allowing entries"),
+            }
+            self.entries = entries;
+        }
+
+        let mut flatten = match blackout {
+            Some(index) if self.blackouts[index].flatten && !self.flattened[index] => {
+                self.flattened[index] = true;
+                true
+            }
+            _ => false,
+        };
+        if self.flatten_due(now) {
+            info!("End of day, closing every position");
+            flatten = true;
+        }
+        self.last_update = Some(now);
+        Permission { entries, flatten }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::config::Config;
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 7, 31, hour, minute, 0).unwrap()
+    }
+
+    fn hours(toml: &str) -> TradingHours {
+        TradingHours::new(&Config::parse(toml).unwrap().hours)
+    }
+
+    #[test]
+    fn test_windows_allow_entries() {
+        let mut always = TradingHours::new(&HoursConfig::default());
+        assert!(always.update(at(3, 0)).entries);
+
+        let mut hours = hours(
+            r#"
+            [hours]
+            windows = [{ start = "07:00:00", end = "12:00:00" }, { start = "22:00:00", end = "02:00:00" }]
+            "#,
+        );
+        let allowed: Vec<_> = [(6, 59), (7, 0), (11, 59), (12, 0), (23, 0), (1, 0), (2, 0)]
+            .into_iter()
+            .map(|(hour, minute)| hours.update(at(hour, minute)).entries)
+            .collect();
+        assert_eq!(allowed, [false, true, true, false, true, true, false]);
+    }
+
+    #[test]
+    fn test_blackouts_block_entries_and_flatten_once() {
+        let mut hours = hours(
+            r#"
+            [[hours.blackouts]]
+            name = "FOMC"
+            start = "2024-07-31T17:55:00Z"
+            end = "2024-07-31T18:30:00Z"
+            flatten = true
+
+            [[hours.blackouts]]
+            start = "2024-07-31T20:00:00Z"
+            end = "2024-07-31T21:00:00Z"
+            "#,
+        );
+
+        assert_eq!(
+            hours.update(at(17, 54)),
+            Permission {
+                entries: true,
+                flatten: false
+            }
+        );
+        assert_eq!(
+            hours.update(at(17, 55)),
+            Permission {
+                entries: false,
+                flatten: true
+            }
+        );
+        assert!(!hours.update(at(18, 0)).flatten);
+        assert!(hours.update(at(18, 30)).entries);
+
+        let maintenance = hours.update(at(20, 15));
+        assert!(!maintenance.entries && !maintenance.flatten);
+    }
+
+    #[test]
+    fn test_flatten_at_end_of_day() {
+        let config = "[hours]\nflatten_at = \"21:00:00\"";
+        // Starting after the flatten time leaves restored positions alone
+        let mut restarted = hours(config);
+        assert!(!restarted.update(at(21, 30)).flatten);
+        assert!(!restarted.update(at(22, 0)).flatten);
+
+        let mut daily = hours(config);
+        assert!(!daily.update(at(20, 59)).flatten);
+        assert!(daily.update(at(21, 0)).flatten);
+        assert!(!daily.update(at(21, 1)).flatten);
+        // Closed the next day too, even when no update lands on the time itself
+        assert!(daily.update(at(21, 30) + Days::new(1)).flatten);
+    }
+}
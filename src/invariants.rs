@@ -0,0 +1,420 @@
+//! Runtime accounting invariants, [`[invariants]`](crate::config::InvariantConfig), checked after
+//! every update: no sleeve's cash is negative, every price is a finite positive number, every
+//! fill is sized and priced, and nothing is sold that was never bought. A state breaking any of
+//! them can only come from a bug, so rather than trading on, the checker trips the circuit
+//! breaker: trading is paused, for an operator to resume once they have looked, a critical alert
+//! is raised and the whole trading state is dumped to the log.
+
+use barter_integration::model::Side;
+use serde_json::json;
+use std::collections::HashMap;
+use std::fmt;
+use tracing::info;
+use tracing::warn;
+
+use crate::allocation::Allocator;
+use crate::event::Alert;
+use crate::event::Event;
+use crate::event::Severity;
+use crate::features::Features;
+
+/// The [condition](Alert::condition) every invariant alert is about.
+pub const CONDITION: &str = "accounting_invariants";
+
+/// An impossible state.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Breach {
+    /// The latest book's `side` price isn't a finite positive number.
+    BookPrice {
+        side: &'static str,
+        price: f64,
+    },
+    NegativeCash {
+        strategy: String,
+        cash: f64,
+    },
+    /// A position is held at an entry price that isn't a finite positive number.
+    EntryPrice {
+        strategy: String,
+        price: f64,
+    },
+    /// A fill with a price or size that isn't a finite positive number, or a negative fee.
+    Fill {
+        strategy: String,
+        price: f64,
+        size: f64,
+        fee: f64,
+    },
+    /// A sell filled without a position to close.
+    SoldUnheld {
+        strategy: String,
+    },
+    /// Positions closed without a fill.
+    Vanished {
+        strategy: String,
+        lots: usize,
+    },
+}
+
+impl fmt::Display for Breach {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BookPrice { side, price } => write!(f, "book {side} price is {price}"),
+            Self::NegativeCash { strategy, cash } => write!(f, "{strategy} cash is {cash}"),
+            Self::EntryPrice { strategy, price } => {
+                write!(f, "{strategy} holds a position entered at {price}")
+            }
+            Self::Fill {
+                strategy,
+                price,
+                size,
+                fee,
+            } => write!(f, "{strategy} filled {size} at {price} for a {fee} fee"),
+            Self::SoldUnheld { strategy } => write!(f, "{strategy} sold a position it didn't hold"),
+            Self::Vanished { strategy, lots } => {
+                write!(f, "{strategy} lost {lots} positions without a fill")
+            }
+        }
+    }
+}
+
+fn positive(value: f64) -> bool {
+    value.is_finite() && value > 0.0
+}
+
+#[derive(Debug)]
+pub struct InvariantChecker {
+    /// Positions each sleeve held after the last check.
+    held: HashMap<usize, usize>,
+    tripped: bool,
+}
+
+impl InvariantChecker {
+    /// Check from the positions `allocator` holds now.
+    pub fn new(allocator: &Allocator) -> Self {
+        Self {
+            held: positions_held(allocator),
+            tripped: false,
+        }
+    }
+
+    /// Every invariant `allocator`, after trading on `features` and producing `events`, breaks.
+    pub fn check(
+        &mut self,
+        allocator: &Allocator,
+        features: &Features,
+        events: &[Event],
+    ) -> Vec<Breach> {
+        let mut breaches = Vec::new();
+        for (side, price) in [("bid", features.bid), ("ask", features.ask)] {
+            if !positive(price) {
+                breaches.push(Breach::BookPrice { side, price });
+            }
+        }
+        for sleeve in allocator.sleeves() {
+            let strategy = sleeve.strategy.name();
+            // Negative or not a number at all
+            if sleeve.state.cash.is_nan() || sleeve.state.cash < 0.0 {
+                breaches.push(Breach::NegativeCash {
+                    strategy: strategy.to_owned(),
+                    cash: sleeve.state.cash,
+                });
+            }
+            if let Some(price) = sleeve
+                .state
+                .positions
+                .iter()
+                .map(|lot| lot.price)
+                .find(|price| !positive(*price))
+            {
+                breaches.push(Breach::EntryPrice {
+                    strategy: strategy.to_owned(),
+                    price,
+                });
+            }
+        }
+
+        // Replay the update's fills over what was held before it
+        let mut expected = self.held.clone();
+        for event in events {
+            let Event::Fill {
+                strategy,
+                sleeve,
+                fill,
+                ..
+            } = event
+            else {
+                continue;
+            };
+            if !positive(fill.price) || !positive(fill.size) || fill.fee.is_nan() || fill.fee < 0.0
+            {
+                breaches.push(Breach::Fill {
+                    strategy: strategy.clone(),
+                    price: fill.price,
+                    size: fill.size,
+                    fee: fill.fee,
+                });
+            }
+            let lots = expected.entry(*sleeve).or_default();
+            match fill.side {
+                Side::Buy => *lots += 1,
+                Side::Sell if *lots > 0 => *lots -= 1,
+                Side::Sell => breaches.push(Breach::SoldUnheld {
+                    strategy: strategy.clone(),
+                }),
+            }
+        }
+        // Strategies swapped in by a reload start from what they hold
+        for sleeve in allocator.sleeves() {
+            let now = sleeve.state.positions.len();
+            let before = expected.get(&sleeve.index()).copied().unwrap_or(now);
+            if now < before {
+                breaches.push(Breach::Vanished {
+                    strategy: sleeve.strategy.name().to_owned(),
+                    lots: before - now,
+                });
+            }
+        }
+        let held = positions_held(allocator);
+        self.held = held;
+        breaches
+    }
+
+    /// Check the update like [`check`](Self::check), returning the alert to raise if the
+    /// breaker just tripped, when trading should be paused, or the invariants hold again.
+    pub fn update(
+        &mut self,
+        allocator: &Allocator,
+        features: &Features,
+        events: &[Event],
+    ) -> Option<Alert> {
+        let breaches = self.check(allocator, features, events);
+        let tripping = !breaches.is_empty();
+        if tripping == self.tripped {
+            return None;
+        }
+        self.tripped = tripping;
+        if !tripping {
+            let message = "Accounting invariants hold again, resume trading when satisfied";
+            info!("{}", message);
+            return Some(Alert {
+                severity: Severity::Info,
+                message: message.to_owned(),
+                condition: Some(CONDITION.to_owned()),
+                resolved: true,
+            });
+        }
+
+        let broken: Vec<String> = breaches.iter().map(ToString::to_string).collect();
+        let message = format!(
+            "Accounting invariants broken, pausing trading: {}",
+            broken.join("; ")
+        );
+        warn!("{}", message);
+        warn!("State at the breach: {}", dump(allocator, events));
+        Some(Alert {
+            severity: Severity::Critical,
+            message,
+            condition: Some(CONDITION.to_owned()),
+            resolved: false,
+        })
+    }
+}
+
+/// Positions held per sleeve.
+fn positions_held(allocator: &Allocator) -> HashMap<usize, usize> {
+    allocator
+        .sleeves()
+        .iter()
+        .map(|sleeve| (sleeve.index(), sleeve.state.positions.len()))
+        .collect()
+}
+
+/// The whole trading state, and the update that led to it, as JSON.
+fn dump(allocator: &Allocator, events: &[Event]) -> String {
+    json!({
+        "state": allocator.snapshot(),
+        "events": events,
+    })
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+    use crate::config::AllocationConfig;
+    use crate::event::Execution;
+    use crate::event::OrderType;
+    use crate::strategy::Signal;
+    use crate::strategy::Strategy;
+    use crate::Fill;
+    use crate::Lot;
+    use crate::TradingState;
+    use crate::TRADE_SIZE;
+    use crate::TRANSACTION_COST;
+
+    #[derive(Debug)]
+    struct Idle;
+
+    impl Strategy for Idle {
+        fn name(&self) -> &str {
+            "idle"
+        }
+
+        fn evaluate(&mut self, _features: &Features, _state: &TradingState) -> Signal {
+            Signal::Hold
+        }
+    }
+
+    fn allocator() -> Allocator {
+        Allocator::new(
+            1000.0,
+            "BTC/USDT",
+            vec![(Box::new(Idle), 1.0)],
+            AllocationConfig::default(),
+            Utc::now(),
+        )
+    }
+
+    fn features() -> Features {
+        Features {
+            bid: 100.0,
+            ask: 100.01,
+            ..Features::default()
+        }
+    }
+
+    fn filled(fill: Fill) -> Event {
+        Event::Fill {
+            time: Utc::now(),
+            symbol: "BTC/USDT",
+            strategy: "idle".to_owned(),
+            sleeve: 0,
+            execution: Execution::paper(OrderType::Market, fill.price),
+            fill,
+            book_time: None,
+        }
+    }
+
+    #[test]
+    fn test_trading_holds() {
+        let mut allocator = allocator();
+        let mut checker = InvariantChecker::new(&allocator);
+        let state = &mut allocator.sleeves_mut()[0].state;
+        let bought = state.execute_trade(100.0, Side::Buy, TRADE_SIZE, TRANSACTION_COST);
+        let sold = state.execute_trade(101.0, Side::Sell, TRADE_SIZE, TRANSACTION_COST);
+        let events = [filled(bought.unwrap()), filled(sold.unwrap())];
+        assert_eq!(checker.update(&allocator, &features(), &events), None);
+    }
+
+    #[test]
+    fn test_impossible_states() {
+        let mut allocator = allocator();
+        let mut checker = InvariantChecker::new(&allocator);
+        allocator.sleeves_mut()[0].state.cash = -5.0;
+        allocator.sleeves_mut()[0]
+            .state
+            .positions
+            .push(Lot::at(f64::NAN));
+        let nan_book = Features {
+            ask: f64::NAN,
+            ..features()
+        };
+        let breaches = checker.check(&allocator, &nan_book, &[]);
+        assert!(matches!(
+            breaches.as_slice(),
+            [
+                Breach::BookPrice { side: "ask", .. },
+                Breach::NegativeCash { .. },
+                Breach::EntryPrice { .. },
+            ]
+        ));
+    }
+
+    #[test]
+    fn test_sold_unheld_trips_once() {
+        let mut allocator = allocator();
+        let mut checker = InvariantChecker::new(&allocator);
+        // A gateway fill booked without any position to sell
+        let fill = Fill {
+            side: Side::Sell,
+            price: 100.0,
+            size: TRADE_SIZE,
+            fee: 0.0005,
+        };
+        allocator.sleeves_mut()[0].state.apply_fill(&fill);
+        let alert = checker
+            .update(&allocator, &features(), &[filled(fill)])
+            .unwrap();
+        assert_eq!(alert.severity, Severity::Critical);
+        assert_eq!(
+            alert.message,
+            "Accounting invariants broken, pausing trading: idle sold a position it didn't hold"
+        );
+        assert_eq!(alert.condition.as_deref(), Some(CONDITION));
+
+        allocator.sleeves_mut()[0].state.cash = -1.0;
+        assert_eq!(checker.update(&allocator, &features(), &[]), None);
+        allocator.sleeves_mut()[0].state.cash = 1.0;
+        assert!(
+            checker
+                .update(&allocator, &features(), &[])
+                .unwrap()
+                .resolved
+        );
+    }
+
+    #[test]
+    fn test_positions_vanishing_without_fills() {
+        let mut allocator = allocator();
+        allocator.sleeves_mut()[0]
+            .state
+            .positions
+            .push(Lot::at(100.0));
+        let mut checker = InvariantChecker::new(&allocator);
+        allocator.sleeves_mut()[0].state.positions.clear();
+        assert_eq!(
+            checker.check(&allocator, &features(), &[]),
+            [Breach::Vanished {
+                strategy: "idle".to_owned(),
+                lots: 1,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_members_running_the_same_strategy_held_apart() {
+        let mut allocator = Allocator::new(
+            1000.0,
+            "BTC/USDT",
+            vec![(Box::new(Idle), 1.0), (Box::new(Idle), 1.0)],
+            AllocationConfig::default(),
+            Utc::now(),
+        );
+        allocator.sleeves_mut()[0]
+            .state
+            .positions
+            .push(Lot::at(100.0));
+        let mut checker = InvariantChecker::new(&allocator);
+
+        // The second sleeve sells the lot only the first one holds
+        let fill = Fill {
+            side: Side::Sell,
+            price: 100.0,
+            size: TRADE_SIZE,
+            fee: 0.0005,
+        };
+        let mut event = filled(fill);
+        if let Event::Fill { sleeve, .. } = &mut event {
+            *sleeve = 1;
+        }
+        assert_eq!(
+            checker.check(&allocator, &features(), &[event]),
+            [Breach::SoldUnheld {
+                strategy: "idle".to_owned(),
+            }]
+        );
+    }
+}
@@ -0,0 +1,331 @@
+//! Broker-style trade journal in CSV, one row per round trip, for importing into journaling
+//! tools.
+//!
+//! Entries and exits are paired last in, first out per strategy, the way the trading state closes
+//! positions. Each side is annotated with the features of the signal that triggered it; exits
+//! without a sell signal were take profit or stop loss triggers and leave those columns empty.
+//! The worst and best unrealized returns while open are marked at the bid of every book update.
+//! Each side's exchange and receipt times are those of the book it was filled on, empty for fills
+//! logged without them.
+
+use barter_integration::model::Side;
+use chrono::DateTime;
+use chrono::SecondsFormat;
+use chrono::Utc;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::BufWriter;
+use std::io::Write;
+use std::path::Path;
+
+use crate::event::BookTime;
+use crate::event::Event;
+use crate::features::Features;
+use crate::report::excursion::Excursion;
+use crate::strategy::Signal;
+use crate::Fill;
+
+/// The journal's header row.
+pub const COLUMNS: [&str; 27] = [
+    "strategy",
+    "symbol",
+    "entry_time",
+    "exit_time",
+    "holding_secs",
+    "size",
+    "entry_price",
+    "exit_price",
+    "fees",
+    "gross_pnl",
+    "net_pnl",
+    "return_pct",
+    "exit_reason",
+    "mae_pct",
+    "mfe_pct",
+    "entry_voi",
+    "entry_oir",
+    "entry_mpb",
+    "entry_spread",
+    "exit_voi",
+    "exit_oir",
+    "exit_mpb",
+    "exit_spread",
+    "entry_exchange_time",
+    "entry_received_time",
+    "exit_exchange_time",
+    "exit_received_time",
+];
+
+#[derive(Debug, thiserror::Error)]
+pub enum JournalError {
+    #[error("failed to write trade journal: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// One side of a round trip.
+#[derive(Debug, Clone, Copy)]
+struct Leg {
+    time: DateTime<Utc>,
+    book_time: Option<BookTime>,
+    fill: Fill,
+    /// Features of the signal that triggered the fill, if one did.
+    trigger: Option<Features>,
+    /// Excursion since the fill, tracked for entries only.
+    excursion: Excursion,
+}
+
+#[derive(Debug)]
+pub struct TradeJournal<W: Write> {
+    writer: W,
+    /// Open entries per strategy and symbol, oldest first.
+    open: HashMap<(String, &'static str), Vec<Leg>>,
+    /// The last signal per strategy, to annotate the fills it triggers.
+    signals: HashMap<String, (DateTime<Utc>, Signal, Features)>,
+}
+
+impl TradeJournal<BufWriter<File>> {
+    /// Create the journal at `path`, replacing any existing file.
+    pub fn create(path: impl AsRef<Path>) -> Result<Self, JournalError> {
+        Self::new(BufWriter::new(File::create(path)?))
+    }
+}
+
+impl<W: Write> TradeJournal<W> {
+    pub fn new(mut writer: W) -> Result<Self, JournalError> {
+        writeln!(writer, "{}", COLUMNS.join(","))?;
+        writer.flush()?;
+        Ok(Self {
+            writer,
+            open: HashMap::new(),
+            signals: HashMap::new(),
+        })
+    }
+
+    /// Track `event`, writing a row when it closes a trade.
+    pub fn record(&mut self, event: &Event) -> Result<(), JournalError> {
+        match event {
+            Event::Features {
+                symbol, features, ..
+            } => {
+                let open = self
+                    .open
+                    .iter_mut()
+                    .filter(|((_, open_symbol), _)| open_symbol == symbol)
+                    .flat_map(|(_, open)| open.iter_mut());
+                for leg in open {
+                    leg.excursion.mark(leg.fill.price, features.bid);
+                }
+            }
+            Event::Signal {
+                time,
+                strategy,
+                signal,
+                features,
+                ..
+            } => {
+                self.signals
+                    .insert(strategy.clone(), (*time, *signal, *features));
+            }
+            Event::Fill {
+                time,
+                symbol,
+                strategy,
+                fill,
+                book_time,
+                ..
+            } => {
+                let trigger = self.trigger(strategy, *time, fill);
+                let leg = Leg {
+                    time: *time,
+                    book_time: *book_time,
+                    fill: *fill,
+                    trigger,
+                    excursion: Excursion::default(),
+                };
+                let open = self.open.entry((strategy.clone(), symbol)).or_default();
+                match fill.side {
+                    Side::Buy => open.push(leg),
+                    Side::Sell => {
+                        if let Some(mut entry) = open.pop() {
+                            entry.excursion.mark(entry.fill.price, fill.price);
+                            let row = row(strategy, symbol, &entry, &leg);
+                            writeln!(self.writer, "{row}")?;
+                            self.writer.flush()?;
+                        }
+                    }
+                }
+            }
+            Event::Order { .. }
+            | Event::Equity { .. }
+            | Event::Rebalance { .. }
+            | Event::Session { .. }
+            | Event::Alert { .. } => {}
+        }
+        Ok(())
+    }
+
+    /// Features of the signal `strategy` produced at `time` on the side of `fill`, if any.
+    fn trigger(&self, strategy: &str, time: DateTime<Utc>, fill: &Fill) -> Option<Features> {
+        let (signal_time, signal, features) = self.signals.get(strategy)?;
+        let side = match signal {
+            Signal::Buy => Side::Buy,
+            Signal::Sell => Side::Sell,
+            Signal::Hold => return None,
+        };
+        (*signal_time == time && side == fill.side).then_some(*features)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// The CSV row for the round trip from `entry` to `exit`, without a trailing newline.
+fn row(strategy: &str, symbol: &str, entry: &Leg, exit: &Leg) -> String {
+    let size = exit.fill.size;
+    let fees = entry.fill.fee + exit.fill.fee;
+    let gross_pnl = (exit.fill.price - entry.fill.price) * size;
+    let net_pnl = gross_pnl - fees;
+    let holding_secs = (exit.time - entry.time).num_milliseconds() as f64 / 1000.0;
+    let return_pct = net_pnl / (entry.fill.price * size) * 100.0;
+    let exit_reason = if exit.trigger.is_some() {
+        "signal"
+    } else {
+        "tp_sl"
+    };
+
+    let mut row = format!(
+        "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+        field(strategy),
+        field(symbol),
+        entry.time.to_rfc3339_opts(SecondsFormat::Millis, true),
+        exit.time.to_rfc3339_opts(SecondsFormat::Millis, true),
+        holding_secs,
+        size,
+        entry.fill.price,
+        exit.fill.price,
+        fees,
+        gross_pnl,
+        net_pnl,
+        return_pct,
+        exit_reason,
+        entry.excursion.mae_pct,
+        entry.excursion.mfe_pct
+    );
+    for trigger in [entry.trigger, exit.trigger] {
+        match trigger {
+            Some(features) => {
+                for value in [features.voi, features.oir, features.mpb, features.spread] {
+                    let _ = write!(row, ",{value}");
+                }
+            }
+            None => row.push_str(",,,,"),
+        }
+    }
+    let millis = |time: DateTime<Utc>| time.to_rfc3339_opts(SecondsFormat::Millis, true);
+    for book_time in [entry.book_time, exit.book_time] {
+        match book_time {
+            Some(book) => {
+                let _ = write!(
+                    row,
+                    ",{},{}",
+                    millis(book.exchange_time),
+                    millis(book.received_time)
+                );
+            }
+            None => row.push_str(",,"),
+        }
+    }
+    row
+}
+
+/// Quote `value` if it contains anything CSV treats specially.
+fn field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+    use crate::event::Execution;
+    use crate::event::OrderType;
+
+    fn fill(time: DateTime<Utc>, side: Side, price: f64) -> Event {
+        Event::Fill {
+            time,
+            symbol: "BTC/USDT",
+            strategy: "imbalance, fast".to_owned(),
+            sleeve: 0,
+            execution: Execution::paper(OrderType::Limit, price),
+            fill: Fill {
+                side,
+                price,
+                size: 0.5,
+                fee: 0.25,
+            },
+            book_time: None,
+        }
+    }
+
+    #[test]
+    fn test_round_trips_are_journaled() {
+        let entry = DateTime::from_timestamp_millis(1_717_200_000_000).unwrap();
+        let exit = entry + Duration::milliseconds(90_500);
+        let features = Features {
+            voi: 3.0,
+            oir: 0.5,
+            mpb: -0.25,
+            spread: 0.01,
+            ..Features::default()
+        };
+        let mut bought = fill(entry, Side::Buy, 100.0);
+        bought.stamp(BookTime {
+            exchange_time: entry - Duration::milliseconds(25),
+            received_time: entry - Duration::milliseconds(5),
+        });
+        let mut journal = TradeJournal::new(Vec::new()).unwrap();
+        let events = [
+            Event::Signal {
+                time: entry,
+                symbol: "BTC/USDT",
+                strategy: "imbalance, fast".to_owned(),
+                signal: Signal::Buy,
+                features,
+                book_time: None,
+            },
+            bought,
+            Event::Features {
+                time: entry,
+                symbol: "BTC/USDT",
+                features: Features {
+                    bid: 99.0,
+                    ..Features::default()
+                },
+                book_time: None,
+            },
+            // Closed by take profit, with no signal behind it
+            fill(exit, Side::Sell, 110.0),
+        ];
+        for event in &events {
+            journal.record(event).unwrap();
+        }
+
+        let csv = String::from_utf8(journal.into_inner()).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], COLUMNS.join(","));
+        assert_eq!(
+            lines[1],
+            "\"imbalance, fast\",BTC/USDT,2024-06-01T00:00:00.000Z,2024-06-01T00:01:30.500Z,\
+             90.5,0.5,100,110,0.5,5,4.5,9,tp_sl,-1,10,3,0.5,-0.25,0.01,,,,,\
+             2024-05-31T23:59:59.975Z,2024-05-31T23:59:59.995Z,,"
+        );
+    }
+}
@@ -0,0 +1,280 @@
+//! Latency from the exchange to the bot's orders, measured per book update in stages and kept as
+//! histograms with Prometheus style buckets:
+//!
+//! - `receipt`: from the exchange's timestamp on the book to the feed receiving it
+//! - `decision`: from receipt to the bot deciding on the book, including time queued behind
+//!   earlier updates
+//! - `order`: from the decision to the order being sent, to the order gateway's socket or booked
+//!   by the paper account
+//!
+//! Exchange timestamps come from the exchange's clock, so `receipt` includes any skew between it
+//! and the local clock. Apparently negative latencies are counted as zero.
+
+use chrono::DateTime;
+use chrono::Utc;
+use std::fmt::Write;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+/// Upper bounds of the histogram buckets, in seconds.
+pub const BUCKETS: [f64; 16] = [
+    0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5,
+    5.0, 10.0,
+];
+
+/// A leg of the way from the exchange to an order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Receipt,
+    Decision,
+    Order,
+}
+
+impl Stage {
+    pub const ALL: [Self; 3] = [Self::Receipt, Self::Decision, Self::Order];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Receipt => "receipt",
+            Self::Decision => "decision",
+            Self::Order => "order",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Histogram {
+    // Per bucket, not cumulative; anything above the last bound is only in the count
+    buckets: [u64; BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    pub fn record(&mut self, seconds: f64) {
+        let seconds = seconds.max(0.0);
+        if let Some(bucket) = BUCKETS.iter().position(|bound| seconds <= *bound) {
+            self.buckets[bucket] += 1;
+        }
+        self.count += 1;
+        self.sum += seconds;
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Total of every latency recorded, in seconds.
+    pub fn sum(&self) -> f64 {
+        self.sum
+    }
+
+    /// Upper bound of the bucket the `quantile` falls in, infinite past the last bound, or `None`
+    /// before anything is recorded.
+    pub fn quantile(&self, quantile: f64) -> Option<f64> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank = (quantile * self.count as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (bound, count) in BUCKETS.iter().zip(self.buckets) {
+            seen += count;
+            if seen >= rank {
+                return Some(*bound);
+            }
+        }
+        Some(f64::INFINITY)
+    }
+}
+
+/// Most latencies kept between [takes](Latency::take_samples), past which more are only counted
+/// in the histograms.
+pub const MAX_SAMPLES: usize = 10_000;
+
+#[derive(Debug, Default)]
+struct Recorded {
+    histograms: [Histogram; Stage::ALL.len()],
+    // Every latency since the last take, in seconds, once a push exporter has asked for them
+    samples: Option<Vec<(Stage, f64)>>,
+}
+
+/// Shared latency histograms, one per [`Stage`]. Clones record into the same histograms.
+#[derive(Debug, Clone, Default)]
+pub struct Latency {
+    recorded: Arc<Mutex<Recorded>>,
+}
+
+impl Latency {
+    /// Count the time from `start` to `end` toward `stage`.
+    pub fn record(&self, stage: Stage, start: DateTime<Utc>, end: DateTime<Utc>) {
+        let seconds = (end - start)
+            .to_std()
+            .map_or(0.0, |elapsed| elapsed.as_secs_f64());
+        let mut recorded = self.lock();
+        recorded.histograms[stage as usize].record(seconds);
+        if let Some(samples) = &mut recorded.samples {
+            if samples.len() < MAX_SAMPLES {
+                samples.push((stage, seconds));
+            }
+        }
+    }
+
+    pub fn histogram(&self, stage: Stage) -> Histogram {
+        self.lock().histograms[stage as usize].clone()
+    }
+
+    /// Keep every latency recorded from now on, for a push exporter to
+    /// [take](Self::take_samples) as well as counting it in the histograms.
+    pub fn keep_samples(&self) {
+        self.lock().samples.get_or_insert_with(Vec::new);
+    }
+
+    /// Every latency kept since the last take, by stage in seconds, up to [`MAX_SAMPLES`].
+    pub fn take_samples(&self) -> Vec<(Stage, f64)> {
+        self.lock()
+            .samples
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// Median and 99th percentile bucket of every stage, for logging.
+    pub fn summary(&self) -> String {
+        let recorded = self.lock();
+        let millis = |seconds: Option<f64>| match seconds {
+            Some(seconds) if seconds.is_finite() => format!("{}ms", seconds * 1000.0),
+            Some(_) => format!(">{}ms", BUCKETS[BUCKETS.len() - 1] * 1000.0),
+            None => "-".to_owned(),
+        };
+        Stage::ALL
+            .iter()
+            .map(|stage| {
+                let histogram = &recorded.histograms[*stage as usize];
+                format!(
+                    "{} p50 {} p99 {}",
+                    stage.name(),
+                    millis(histogram.quantile(0.5)),
+                    millis(histogram.quantile(0.99))
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Every histogram in the Prometheus text exposition format.
+    pub fn prometheus(&self) -> String {
+        let recorded = self.lock();
+        let mut text = String::new();
+        text.push_str(
+            "# HELP fit_latency_seconds Latency of each stage from the exchange to an order.\n",
+        );
+        text.push_str("# TYPE fit_latency_seconds histogram\n");
+        for stage in Stage::ALL {
+            let histogram = &recorded.histograms[stage as usize];
+            let stage = stage.name();
+            let mut cumulative = 0;
+            for (bound, count) in BUCKETS.iter().zip(histogram.buckets) {
+                cumulative += count;
+                let _ = writeln!(
+                    text,
+                    r#"fit_latency_seconds_bucket{{stage="{stage}",le="{bound}"}} {cumulative}"#
+                );
+            }
+            let _ = writeln!(
+                text,
+                r#"fit_latency_seconds_bucket{{stage="{stage}",le="+Inf"}} {}"#,
+                histogram.count
+            );
+            let _ = writeln!(
+                text,
+                r#"fit_latency_seconds_sum{{stage="{stage}"}} {}"#,
+                histogram.sum
+            );
+            let _ = writeln!(
+                text,
+                r#"fit_latency_seconds_count{{stage="{stage}"}} {}"#,
+                histogram.count
+            );
+        }
+        text
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Recorded> {
+        self.recorded.lock().expect("latency lock poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+
+    use super::*;
+
+    #[test]
+    fn test_histogram_buckets_and_quantiles() {
+        let mut histogram = Histogram::default();
+        assert_eq!(histogram.quantile(0.5), None);
+        for seconds in [0.0002, 0.0002, 0.003, 0.04, -0.5, 60.0] {
+            histogram.record(seconds);
+        }
+        assert_eq!(histogram.count(), 6);
+        assert!((histogram.sum() - 60.0434).abs() < 1e-9);
+        assert_eq!(histogram.quantile(0.0), Some(0.0001));
+        assert_eq!(histogram.quantile(0.5), Some(0.00025));
+        assert_eq!(histogram.quantile(0.8), Some(0.05));
+        assert_eq!(histogram.quantile(0.99), Some(f64::INFINITY));
+    }
+
+    #[test]
+    fn test_prometheus_exposition() {
+        let latency = Latency::default();
+        let start = Utc::now();
+        latency.record(Stage::Receipt, start, start + TimeDelta::milliseconds(3));
+        latency
+            .clone()
+            .record(Stage::Receipt, start, start + TimeDelta::seconds(20));
+        // Clock skew puts the exchange ahead of the local clock
+        latency.record(Stage::Order, start, start - TimeDelta::milliseconds(1));
+        assert_eq!(latency.histogram(Stage::Receipt).count(), 2);
+
+        let text = latency.prometheus();
+        assert!(text.contains("# TYPE fit_latency_seconds histogram\n"));
+        assert!(text.contains("fit_latency_seconds_bucket{stage=\"receipt\",le=\"0.0025\"} 0\n"));
+        assert!(text.contains("fit_latency_seconds_bucket{stage=\"receipt\",le=\"0.005\"} 1\n"));
+        assert!(text.contains("fit_latency_seconds_bucket{stage=\"receipt\",le=\"10\"} 1\n"));
+        assert!(text.contains("fit_latency_seconds_bucket{stage=\"receipt\",le=\"+Inf\"} 2\n"));
+        assert!(text.contains("fit_latency_seconds_count{stage=\"decision\"} 0\n"));
+        assert!(text.contains("fit_latency_seconds_bucket{stage=\"order\",le=\"0.0001\"} 1\n"));
+
+        assert_eq!(
+            latency.summary(),
+            "receipt p50 5ms p99 >10000ms, decision p50 - p99 -, order p50 0.1ms p99 0.1ms"
+        );
+    }
+
+    #[test]
+    fn test_keep_samples() {
+        let latency = Latency::default();
+        let start = Utc::now();
+        latency.record(Stage::Receipt, start, start + TimeDelta::milliseconds(2));
+        assert!(latency.take_samples().is_empty());
+
+        latency.keep_samples();
+        latency.record(Stage::Decision, start, start + TimeDelta::milliseconds(4));
+        latency.record(Stage::Order, start, start - TimeDelta::milliseconds(1));
+        assert_eq!(
+            latency.take_samples(),
+            [(Stage::Decision, 0.004), (Stage::Order, 0.0)]
+        );
+        assert!(latency.take_samples().is_empty());
+
+        for _ in 0..MAX_SAMPLES + 5 {
+            latency.record(Stage::Order, start, start);
+        }
+        assert_eq!(latency.take_samples().len(), MAX_SAMPLES);
+        assert_eq!(
+            latency.histogram(Stage::Order).count(),
+            (MAX_SAMPLES + 6) as u64
+        );
+    }
+}
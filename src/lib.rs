@@ -0,0 +1,828 @@
+use barter_data::subscription::book::OrderBook;
+use barter_integration::model::Side;
+use chrono::Utc;
+use serde::Deserialize;
+use serde::Serialize;
+use tracing::info;
+
+use crate::config::DepthConfig;
+use crate::config::Weighting;
+use crate::features::within_depth;
+use crate::features::Features;
+use crate::strategy::Signal;
+
+pub mod affinity;
+pub mod alerts;
+pub mod alloc_count;
+pub mod allocation;
+#[cfg(feature = "api")]
+pub mod api;
+pub mod arbitrage;
+pub mod audit;
+pub mod auth;
+pub mod backtest;
+pub mod basis;
+pub mod bounds;
+pub mod calibration;
+pub mod chaos;
+pub mod clock;
+pub mod config;
+pub mod conflate;
+pub mod consolidated;
+pub mod control;
+pub mod event;
+#[cfg(feature = "parquet")]
+pub mod export;
+pub mod features;
+#[cfg(feature = "arrow")]
+pub mod feed;
+pub mod funding;
+#[cfg(feature = "zmq")]
+pub mod gateway;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod gym;
+pub mod harvest;
+pub mod health;
+pub mod hedge;
+pub mod hours;
+pub mod invariants;
+pub mod journal;
+pub mod latency;
+pub mod live;
+pub mod logfile;
+pub mod mark;
+pub mod market;
+pub mod mbo;
+pub mod ml;
+pub mod open_interest;
+pub mod ordering;
+pub mod outliers;
+pub mod pairs;
+pub mod placement;
+#[cfg(any(
+    feature = "clock",
+    feature = "funding",
+    feature = "mark-price",
+    feature = "open-interest",
+    feature = "status"
+))]
+pub mod poll;
+pub mod probe;
+pub mod quality;
+pub mod queue;
+pub mod reload;
+pub mod report;
+#[cfg(feature = "ring")]
+pub mod ring;
+pub mod rng;
+pub mod routing;
+pub mod schedule;
+pub mod secrets;
+pub mod session;
+pub mod signals;
+pub mod simd;
+pub mod sink;
+pub mod snapshot;
+pub mod status;
+#[cfg(feature = "sled")]
+pub mod store;
+pub mod strategy;
+pub mod synthetic;
+pub mod systemd;
+pub mod telemetry;
+pub mod toxicity;
+pub mod triangular;
+#[cfg(feature = "tui")]
+pub mod tui;
+pub mod volatility;
+pub mod wal;
+pub mod watchdog;
+#[cfg(feature = "web")]
+pub mod web;
+pub mod worker;
+
+// Constants
+pub const TRADE_SIZE: f64 = 0.001;
+pub const SPREAD_THRESHOLD: f64 = 0.05; // Adjust based on backtesting and performance analysis
+pub const TAKE_PROFIT: f64 = 0.01; // 1%
+pub const STOP_LOSS: f64 = 0.02; // 2%
+pub const TRANSACTION_COST: f64 = 0.005; // 0.5%
+pub const OIR_THRESHOLD: f64 = 0.1;
+pub const MPB_THRESHOLD: f64 = -0.1;
+
+/// A trade executed against the paper account.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Fill {
+    pub side: Side,
+    pub price: f64,
+    pub size: f64,
+    pub fee: f64,
+}
+
+/// Take profit and stop loss thresholds, as fractions of the entry price. They can be changed
+/// while running, so they live with the state rather than as constants.
+///
+/// ```toml
+/// [risk]
+/// take_profit = 0.01
+/// stop_loss = 0.02
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RiskParams {
+    pub take_profit: f64,
+    pub stop_loss: f64,
+}
+
+impl Default for RiskParams {
+    fn default() -> Self {
+        Self {
+            take_profit: TAKE_PROFIT,
+            stop_loss: STOP_LOSS,
+        }
+    }
+}
+
+/// Why a trade couldn't be made.
+#[derive(Debug, Clone, Copy, PartialEq, thiserror::Error)]
+pub enum TradeError {
+    #[error("buying needs {needed} cash, only {available} is available")]
+    InsufficientCash { needed: f64, available: f64 },
+
+    #[error("no position to sell")]
+    NoPosition,
+
+    #[error("invalid trade size {0}")]
+    InvalidSize(f64),
+
+    #[error("invalid trade price {0}")]
+    InvalidPrice(f64),
+}
+
+/// A position opened by one buy, at its entry price.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(from = "SavedLot")]
+pub struct Lot {
+    pub price: f64,
+    pub size: f64,
+}
+
+impl Lot {
+    /// A lot of [`TRADE_SIZE`] bought at `price`.
+    pub fn at(price: f64) -> Self {
+        Self {
+            price,
+            size: TRADE_SIZE,
+        }
+    }
+}
+
+/// A lot as saved, which before lots were sized was only its entry price.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SavedLot {
+    Price(f64),
+    Sized { price: f64, size: f64 },
+}
+
+impl From<SavedLot> for Lot {
+    fn from(saved: SavedLot) -> Self {
+        match saved {
+            SavedLot::Price(price) => Self::at(price),
+            SavedLot::Sized { price, size } => Self { price, size },
+        }
+    }
+}
+
+// Struct to hold the trading state
+#[derive(Debug)]
+pub struct TradingState {
+    pub cash: f64,
+    pub positions: Vec<Lot>,
+    pub symbol: &'static str,
+    pub risk: RiskParams,
+}
+
+impl TradingState {
+    pub fn new(cash: f64, symbol: &'static str) -> Self {
+        Self {
+            cash,
+            positions: Vec::new(),
+            symbol,
+            risk: RiskParams::default(),
+        }
+    }
+
+    pub fn calculate_voi(order_book: &OrderBook) -> (f64, f64, f64) {
+        Self::calculate_voi_within(order_book, &DepthConfig::default(), Weighting::Amount)
+    }
+
+    /// VOI summed over the `depth` window of each side, around the mid when both sides have a
+    /// level, with each level counting for its amount or notional as `weighting` says.
+    pub fn calculate_voi_within(
+        order_book: &OrderBook,
+        depth: &DepthConfig,
+        weighting: Weighting,
+    ) -> (f64, f64, f64) {
+        let (bids, asks) = (&order_book.bids.levels, &order_book.asks.levels);
+        let mid_price = match (bids.first(), asks.first()) {
+            (Some(bid), Some(ask)) => (bid.price + ask.price) / 2.0,
+            _ => f64::NAN,
+        };
+        let depth = DepthConfig {
+            // Nothing to measure the distance from without a mid
+            within_bps: depth.within_bps.filter(|_| mid_price.is_finite()),
+            ..*depth
+        };
+        let total = match weighting {
+            Weighting::Amount => simd::total_amount,
+            Weighting::Notional => simd::total_notional,
+        };
+        let bid_volume: f64 = total(within_depth(bids, &depth, mid_price));
+        let ask_volume: f64 = total(within_depth(asks, &depth, mid_price));
+        let voi: f64 = bid_volume - ask_volume;
+        (voi, bid_volume, ask_volume)
+    }
+
+    pub fn calculate_oir(bid_volume: f64, ask_volume: f64) -> f64 {
+        (bid_volume - ask_volume) / (bid_volume + ask_volume)
+    }
+
+    pub fn calculate_mpb(last_price: f64, mid_price: f64) -> f64 {
+        last_price - mid_price
+    }
+
+    pub fn calculate_spread(bid: f64, ask: f64) -> f64 {
+        Self::calculate_spread_at(bid, ask, bid)
+    }
+
+    /// The spread as a percent of `reference` rather than the bid.
+    pub fn calculate_spread_at(bid: f64, ask: f64, reference: f64) -> f64 {
+        (ask - bid) / reference * 100.0
+    }
+
+    pub fn should_trade(spread: f64, voi: f64, spread_threshold: f64) -> bool {
+        spread <= spread_threshold && voi.abs() > 0.0
+    }
+
+    /// Trade at `price` paying `fee`, a fraction of the trade's value: a buy opens a position of
+    /// `trade_size`, a sell closes the newest at whatever size it was bought.
+    pub fn execute_trade(
+        &mut self,
+        price: f64,
+        side: Side,
+        trade_size: f64,
+        fee: f64,
+    ) -> Result<Fill, TradeError> {
+        if !trade_size.is_finite() || trade_size <= 0.0 {
+            return Err(TradeError::InvalidSize(trade_size));
+        }
+        if !price.is_finite() || price <= 0.0 {
+            return Err(TradeError::InvalidPrice(price));
+        }
+        let trade_size = match side {
+            Side::Buy => trade_size,
+            Side::Sell => match self.positions.last() {
+                Some(lot) => lot.size,
+                None => return Err(TradeError::NoPosition),
+            },
+        };
+        let transaction_cost = trade_size * price * fee;
+        match side {
+            Side::Buy => {
+                let needed = price * trade_size + transaction_cost;
+                if needed > self.cash {
+                    return Err(TradeError::InsufficientCash {
+                        needed,
+                        available: self.cash,
+                    });
+                }
+                self.positions.push(Lot {
+                    price,
+                    size: trade_size,
+                });
+                self.cash -= needed;
+                info!(
+                    "Buying {} {} at {} (cost: {}) at {}",
+                    trade_size,
+                    self.symbol,
+                    price,
+                    transaction_cost,
+                    Utc::now()
+                );
+            }
+            Side::Sell => {
+                self.positions.pop();
+                self.cash += price * trade_size - transaction_cost;
+                info!(
+                    "Selling {} {} at {} (cost: {}) at {}",
+                    trade_size,
+                    self.symbol,
+                    price,
+                    transaction_cost,
+                    Utc::now()
+                );
+            }
+        }
+        Ok(Fill {
+            side,
+            price,
+            size: trade_size,
+            fee: transaction_cost,
+        })
+    }
+
+    /// Trade on `signal` at the top of book in `features`, returning the fill, or none when
+    /// holding. Take profit and stop loss are left to [`check_exits`](Self::check_exits).
+    pub fn execute_signal(
+        &mut self,
+        signal: Signal,
+        features: &Features,
+    ) -> Result<Option<Fill>, TradeError> {
+        let (price, side) = match signal {
+            Signal::Buy => (features.bid, Side::Buy),
+            Signal::Sell => (features.ask, Side::Sell),
+            Signal::Hold => return Ok(None),
+        };
+        self.execute_trade(price, side, TRADE_SIZE, TRANSACTION_COST)
+            .map(Some)
+    }
+
+    /// Check take profit and stop loss at `bid` against the state's own risk params, returning
+    /// the fills.
+    pub fn check_exits(&mut self, bid: f64) -> Vec<Fill> {
+        self.check_exits_marked(bid, bid)
+    }
+
+    /// Check take profit and stop loss against `mark`, selling at `bid`, returning the fills.
+    pub fn check_exits_marked(&mut self, mark: f64, bid: f64) -> Vec<Fill> {
+        self.check_tp_sl_marked(mark, bid, self.risk.take_profit, self.risk.stop_loss)
+    }
+
+    /// Sell every position `tp` or more up, or `sl` or more down, at `bid`, returning the fills.
+    pub fn check_tp_sl(&mut self, bid: f64, tp: f64, sl: f64) -> Vec<Fill> {
+        self.check_tp_sl_marked(bid, bid, tp, sl)
+    }
+
+    /// Sell every position `tp` or more up, or `sl` or more down, at `mark`, selling at `bid`,
+    /// returning the fills.
+    pub fn check_tp_sl_marked(&mut self, mark: f64, bid: f64, tp: f64, sl: f64) -> Vec<Fill> {
+        let mut fills = Vec::new();
+        let mut index = 0;
+        while index < self.positions.len() {
+            let position = self.positions[index];
+            let profit_loss = (mark - position.price) / position.price;
+            if profit_loss >= tp {
+                info!(
+                    "Triggering Take Profit: Selling position at {} with profit/loss: {:.2}%",
+                    bid,
+                    profit_loss * 100.0
+                );
+            } else if profit_loss <= -sl {
+                info!(
+                    "Triggering Stop Loss: Selling position at {} with profit/loss: {:.2}%",
+                    bid,
+                    profit_loss * 100.0
+                );
+            } else {
+                index += 1;
+                continue;
+            }
+            // Selling closes the newest position, so make this one the newest, leaving any other
+            // with the same entry price alone
+            self.positions.remove(index);
+            self.positions.push(position);
+            match self.execute_trade(bid, Side::Sell, TRADE_SIZE, TRANSACTION_COST) {
+                Ok(fill) => fills.push(fill),
+                // Nothing sells at a bid that isn't a price, so stop rather than cycle forever
+                Err(_) => break,
+            }
+        }
+        fills
+    }
+
+    /// Book a fill executed elsewhere, such as by an external order gateway, the same way
+    /// [`execute_trade`](Self::execute_trade) books a paper trade.
+    pub fn apply_fill(&mut self, fill: &Fill) {
+        match fill.side {
+            Side::Buy => {
+                self.positions.push(Lot {
+                    price: fill.price,
+                    size: fill.size,
+                });
+                self.cash -= fill.price * fill.size + fill.fee;
+            }
+            Side::Sell => {
+                self.positions.pop();
+                self.cash += fill.price * fill.size - fill.fee;
+            }
+        }
+    }
+
+    /// The size of every open lot together.
+    pub fn position_size(&self) -> f64 {
+        self.positions.iter().map(|lot| lot.size).sum()
+    }
+
+    /// The mean entry price of the open lots, weighted by size, or `None` when flat.
+    pub fn average_entry(&self) -> Option<f64> {
+        let size = self.position_size();
+        let cost: f64 = self.positions.iter().map(|lot| lot.price * lot.size).sum();
+        (!self.positions.is_empty()).then(|| cost / size)
+    }
+
+    /// What the open lots would make or lose at `price`, before fees.
+    pub fn unrealized_pnl(&self, price: f64) -> f64 {
+        self.positions
+            .iter()
+            .map(|lot| (price - lot.price) * lot.size)
+            .sum()
+    }
+
+    pub fn calculate_portfolio_value(&self, bid: f64) -> f64 {
+        let position_value: f64 = self.position_size() * bid;
+        self.cash + position_value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use barter_data::subscription::book::Level;
+    use barter_data::subscription::book::OrderBookSide;
+    use barter_integration::model::Side;
+    use chrono::DateTime;
+    use proptest::prelude::*;
+
+    use super::*;
+
+    // Constants for tests
+    const TEST_TRADE_SIZE: f64 = 0.001;
+    const TEST_TAKE_PROFIT: f64 = 0.01;
+    const TEST_STOP_LOSS: f64 = 0.02;
+    const TEST_TRANSACTION_COST: f64 = 0.005;
+    const TEST_SPREAD_THRESHOLD: f64 = 0.05;
+    const FLOAT_TOLERANCE: f64 = 0.00001;
+
+    /// Helper function that rounds the floating-point numbers to a specified number of decimal places
+    /// before comparing them.
+    fn approx_equal(a: f64, b: f64, tolerance: f64) -> bool {
+        (a - b).abs() < tolerance
+    }
+
+    #[test]
+    fn test_calculate_voi() {
+        let order_book = OrderBook {
+            last_update_time: DateTime::from_timestamp_millis(0).unwrap(),
+            bids: OrderBookSide::new(
+                Side::Buy,
+                vec![Level {
+                    price: 100.0,
+                    amount: 1.0,
+                }],
+            ),
+            asks: OrderBookSide::new(
+                Side::Sell,
+                vec![Level {
+                    price: 101.0,
+                    amount: 1.0,
+                }],
+            ),
+        };
+
+        let (voi, bid_volume, ask_volume) = TradingState::calculate_voi(&order_book);
+        assert_eq!(voi, 0.0);
+        assert_eq!(bid_volume, 1.0);
+        assert_eq!(ask_volume, 1.0);
+    }
+
+    #[test]
+    fn test_calculate_oir() {
+        let oir = TradingState::calculate_oir(1.0, 1.0);
+        assert_eq!(oir, 0.0);
+    }
+
+    #[test]
+    fn test_calculate_mpb() {
+        let mpb = TradingState::calculate_mpb(100.0, 100.0);
+        assert_eq!(mpb, 0.0);
+    }
+
+    #[test]
+    fn test_calculate_spread() {
+        let spread = TradingState::calculate_spread(100.0, 101.0);
+        assert_eq!(spread, 1.0);
+    }
+
+    #[test]
+    fn test_should_trade() {
+        let valid_voi = 1.0;
+        assert!(TradingState::should_trade(
+            TEST_SPREAD_THRESHOLD,
+            valid_voi,
+            TEST_SPREAD_THRESHOLD
+        ));
+
+        let invalid_spread = 0.06;
+        let valid_voi = 1.0;
+        assert!(!TradingState::should_trade(
+            invalid_spread,
+            valid_voi,
+            TEST_SPREAD_THRESHOLD
+        ));
+
+        let invalid_voi = 0.0;
+        assert!(!TradingState::should_trade(
+            TEST_SPREAD_THRESHOLD,
+            invalid_voi,
+            TEST_SPREAD_THRESHOLD
+        ));
+    }
+
+    #[test]
+    fn test_execute_trade() {
+        let mut state = TradingState::new(1000.0, "BTC/USDT");
+        state
+            .execute_trade(100.0, Side::Buy, TEST_TRADE_SIZE, TEST_TRANSACTION_COST)
+            .unwrap();
+        let expected_cash_after_buy =
+            1000.0 - (100.0 * TEST_TRADE_SIZE) - (100.0 * TEST_TRADE_SIZE * TEST_TRANSACTION_COST);
+        assert_eq!(state.cash, expected_cash_after_buy);
+        assert_eq!(state.positions.len(), 1);
+
+        state
+            .execute_trade(100.0, Side::Sell, TEST_TRADE_SIZE, TEST_TRANSACTION_COST)
+            .unwrap();
+        let expected_cash_after_sell = expected_cash_after_buy + (100.0 * TEST_TRADE_SIZE)
+            - (100.0 * TEST_TRADE_SIZE * TEST_TRANSACTION_COST);
+        assert_eq!(state.cash, expected_cash_after_sell);
+        assert_eq!(state.positions.len(), 0);
+    }
+
+    #[test]
+    fn test_execute_trade_errors() {
+        let mut state = TradingState::new(0.1, "BTC/USDT");
+        assert_eq!(
+            state.execute_trade(100.0, Side::Sell, TEST_TRADE_SIZE, TEST_TRANSACTION_COST),
+            Err(TradeError::NoPosition)
+        );
+        assert_eq!(
+            state.execute_trade(100.0, Side::Buy, TEST_TRADE_SIZE, TEST_TRANSACTION_COST),
+            Err(TradeError::InsufficientCash {
+                needed: 100.0 * TEST_TRADE_SIZE + TEST_TRADE_SIZE * 100.0 * TEST_TRANSACTION_COST,
+                available: 0.1,
+            })
+        );
+        assert_eq!(
+            state.execute_trade(100.0, Side::Buy, 0.0, TEST_TRANSACTION_COST),
+            Err(TradeError::InvalidSize(0.0))
+        );
+        assert!(matches!(
+            state.execute_trade(f64::NAN, Side::Buy, TEST_TRADE_SIZE, TEST_TRANSACTION_COST),
+            Err(TradeError::InvalidPrice(price)) if price.is_nan()
+        ));
+        // None of them touched the books
+        assert_eq!(state.cash, 0.1);
+        assert!(state.positions.is_empty());
+
+        // Selling while flat is an error now, not a silent no-op
+        let features = Features {
+            bid: 100.0,
+            ask: 100.01,
+            ..Features::default()
+        };
+        assert_eq!(
+            state.execute_signal(Signal::Sell, &features),
+            Err(TradeError::NoPosition)
+        );
+        assert_eq!(state.execute_signal(Signal::Hold, &features), Ok(None));
+    }
+
+    #[test]
+    fn test_apply_fill_matches_execute_trade() {
+        let mut paper = TradingState::new(1000.0, "BTC/USDT");
+        let mut external = TradingState::new(1000.0, "BTC/USDT");
+        for (price, side) in [(100.0, Side::Buy), (101.0, Side::Buy), (102.0, Side::Sell)] {
+            let fill = paper
+                .execute_trade(price, side, TEST_TRADE_SIZE, TEST_TRANSACTION_COST)
+                .unwrap();
+            external.apply_fill(&fill);
+        }
+        assert_eq!(external.cash, paper.cash);
+        assert_eq!(external.positions, paper.positions);
+    }
+
+    #[test]
+    fn test_check_tp_sl() {
+        let mut state = TradingState::new(1000.0, "BTC/USDT");
+
+        // Testing Take Profit
+        state.positions.push(Lot::at(100.0));
+        state.check_tp_sl(102.0, TEST_TAKE_PROFIT, TEST_STOP_LOSS);
+        let proceeds_tp = 102.0 * TEST_TRADE_SIZE;
+        let transaction_cost_tp = 102.0 * TEST_TRADE_SIZE * TEST_TRANSACTION_COST;
+        let expected_cash_after_tp = 1000.0 + proceeds_tp - transaction_cost_tp;
+        assert_eq!(state.positions.len(), 0);
+        assert!(
+            approx_equal(state.cash, expected_cash_after_tp, FLOAT_TOLERANCE),
+            "Cash after TP not as expected. Got: {}, Expected: {}",
+            state.cash,
+            expected_cash_after_tp
+        );
+
+        // Testing Stop Loss
+        state.positions.push(Lot::at(100.0));
+        state.check_tp_sl(98.0, TEST_TAKE_PROFIT, TEST_STOP_LOSS);
+        let proceeds_sl = 98.0 * TEST_TRADE_SIZE;
+        let transaction_cost_sl = 98.0 * TEST_TRADE_SIZE * TEST_TRANSACTION_COST;
+        let expected_cash_after_sl = expected_cash_after_tp + proceeds_sl - transaction_cost_sl;
+        assert_eq!(state.positions.len(), 0);
+        assert!(
+            approx_equal(state.cash, expected_cash_after_sl, FLOAT_TOLERANCE),
+            "Cash after SL not as expected. Got: {}, Expected: {}",
+            state.cash,
+            expected_cash_after_sl
+        );
+
+        // Positions sharing an entry price are each sold once, and only they are
+        state.positions.extend([100.0, 101.5, 100.0].map(Lot::at));
+        let fills = state.check_tp_sl(102.0, TEST_TAKE_PROFIT, TEST_STOP_LOSS);
+        assert_eq!(fills.len(), 2);
+        assert_eq!(state.positions, [Lot::at(101.5)]);
+    }
+
+    #[test]
+    fn test_exits_judged_at_mark() {
+        let mut state = TradingState::new(1000.0, "BTC/USDT");
+        state.positions.push(Lot::at(100.0));
+        // The bid is within the stop, but the mark the position settles at isn't
+        let fills = state.check_tp_sl_marked(97.5, 99.0, TEST_TAKE_PROFIT, TEST_STOP_LOSS);
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].price, 99.0);
+        state.positions.push(Lot::at(100.0));
+        assert!(state
+            .check_tp_sl_marked(100.5, 101.5, TEST_TAKE_PROFIT, TEST_STOP_LOSS)
+            .is_empty());
+    }
+
+    /// An operation on the trading state, at a whole price so positions often share an entry.
+    #[derive(Debug, Clone, Copy)]
+    enum Op {
+        Buy(f64),
+        Sell(f64),
+        CheckTpSl(f64),
+    }
+
+    fn op() -> impl Strategy<Value = Op> {
+        let price = || (90u32..=110).prop_map(f64::from);
+        prop_oneof![
+            price().prop_map(Op::Buy),
+            price().prop_map(Op::Sell),
+            price().prop_map(Op::CheckTpSl),
+        ]
+    }
+
+    proptest! {
+        /// Drive random trades and exits through the state, keeping a ledger of its fills beside
+        /// it and a replica booking the same fills, and check the books agree after every step.
+        #[test]
+        fn test_accounting_invariants(
+            ops in prop::collection::vec(op(), 1..200),
+            tp in 0.001..0.05,
+            sl in 0.001..0.05,
+        ) {
+            let mut state = TradingState::new(1000.0, "BTC/USDT");
+            let mut replica = TradingState::new(1000.0, "BTC/USDT");
+            let mut ledger = 1000.0;
+            let mut lots = 0usize;
+            for op in ops {
+                let before = state.positions.clone();
+                let (Op::Buy(price) | Op::Sell(price) | Op::CheckTpSl(price)) = op;
+                let value = state.calculate_portfolio_value(price);
+                let fills: Vec<Fill> = match op {
+                    Op::Buy(price) | Op::Sell(price) => {
+                        let side = if matches!(op, Op::Buy(_)) { Side::Buy } else { Side::Sell };
+                        match state.execute_trade(price, side, TRADE_SIZE, TRANSACTION_COST) {
+                            Ok(fill) => vec![fill],
+                            // Refused trades leave the state as it was
+                            Err(error) => {
+                                let expected = match side {
+                                    Side::Buy => matches!(
+                                        error,
+                                        TradeError::InsufficientCash { .. }
+                                    ),
+                                    Side::Sell => error == TradeError::NoPosition,
+                                };
+                                prop_assert!(expected, "unexpected {:?}", error);
+                                prop_assert_eq!(&state.positions, &before);
+                                Vec::new()
+                            }
+                        }
+                    }
+                    Op::CheckTpSl(bid) => state.check_tp_sl(bid, tp, sl),
+                };
+
+                let mut fees = 0.0;
+                for fill in &fills {
+                    prop_assert!(fill.size > 0.0);
+                    prop_assert!(fill.fee >= 0.0);
+                    prop_assert_eq!(fill.price, price);
+                    fees += fill.fee;
+                    match fill.side {
+                        Side::Buy => {
+                            ledger -= fill.price * fill.size + fill.fee;
+                            lots += 1;
+                        }
+                        Side::Sell => {
+                            ledger += fill.price * fill.size - fill.fee;
+                            lots = lots.checked_sub(1).expect("sold a lot that wasn't held");
+                        }
+                    }
+                    replica.apply_fill(fill);
+                }
+                prop_assert!(approx_equal(state.cash, ledger, FLOAT_TOLERANCE));
+                prop_assert_eq!(state.positions.len(), lots);
+                prop_assert!(approx_equal(replica.cash, state.cash, FLOAT_TOLERANCE));
+                prop_assert_eq!(replica.positions.len(), lots);
+                // Trading at the mark price only costs the fees
+                prop_assert!(approx_equal(
+                    state.calculate_portfolio_value(price),
+                    value - fees,
+                    FLOAT_TOLERANCE
+                ));
+
+                if let Op::CheckTpSl(bid) = op {
+                    // Exactly the positions past a threshold are sold, each once
+                    let held = |entry: &&f64| {
+                        let profit_loss = (bid - **entry) / **entry;
+                        profit_loss > -sl && profit_loss < tp
+                    };
+                    let entries: Vec<f64> = before.iter().map(|lot| lot.price).collect();
+                    let mut kept: Vec<f64> = entries.iter().filter(held).copied().collect();
+                    let mut after: Vec<f64> = state.positions.iter().map(|lot| lot.price).collect();
+                    kept.sort_by(f64::total_cmp);
+                    after.sort_by(f64::total_cmp);
+                    prop_assert_eq!(fills.len(), before.len() - kept.len());
+                    prop_assert_eq!(after, kept);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_calculate_portfolio_value() {
+        let mut state = TradingState::new(1000.0, "BTC/USDT");
+        state.positions.push(Lot::at(100.0));
+        let portfolio_value = state.calculate_portfolio_value(101.0);
+        let expected_portfolio_value = 1000.0 + (101.0 * TEST_TRADE_SIZE);
+        assert_eq!(portfolio_value, expected_portfolio_value);
+    }
+
+    #[test]
+    fn test_lots_keep_their_size() {
+        let mut state = TradingState::new(1000.0, "BTC/USDT");
+        state
+            .execute_trade(100.0, Side::Buy, TEST_TRADE_SIZE, 0.0)
+            .unwrap();
+        state
+            .execute_trade(90.0, Side::Buy, TEST_TRADE_SIZE / 2.0, 0.0)
+            .unwrap();
+        assert_eq!(state.position_size(), TEST_TRADE_SIZE * 1.5);
+        assert!(approx_equal(
+            state.average_entry().unwrap(),
+            290.0 / 3.0,
+            FLOAT_TOLERANCE
+        ));
+        assert_eq!(
+            state.calculate_portfolio_value(100.0),
+            state.cash + 100.0 * TEST_TRADE_SIZE * 1.5
+        );
+        assert!(approx_equal(
+            state.unrealized_pnl(100.0),
+            10.0 * TEST_TRADE_SIZE / 2.0,
+            FLOAT_TOLERANCE
+        ));
+
+        // Sells close the newest lot whole, whatever size is asked for
+        let fill = state
+            .execute_trade(95.0, Side::Sell, TEST_TRADE_SIZE, 0.0)
+            .unwrap();
+        assert_eq!(fill.size, TEST_TRADE_SIZE / 2.0);
+        assert_eq!(state.positions, [Lot::at(100.0)]);
+        assert_eq!(TradingState::new(0.0, "BTC/USDT").average_entry(), None);
+
+        // Lots saved before they were sized are of the trade size
+        let saved: Vec<Lot> =
+            serde_json::from_str(r#"[100.0, {"price": 90.0, "size": 0.5}]"#).unwrap();
+        assert_eq!(
+            saved,
+            [
+                Lot::at(100.0),
+                Lot {
+                    price: 90.0,
+                    size: 0.5
+                }
+            ]
+        );
+    }
+}
@@ -0,0 +1,220 @@
+//! A live view of the bot assembled from its events, for the dashboards: the latest features,
+//! open positions with their unrealized PnL, the most recent trades and equity history.
+
+use barter_integration::model::Side;
+use chrono::DateTime;
+use chrono::Utc;
+use serde::Serialize;
+use std::collections::VecDeque;
+
+use crate::event::Event;
+use crate::features::Features;
+use crate::Fill;
+
+/// Trades kept, newest first.
+pub const RECENT_TRADES: usize = 12;
+/// Equity updates kept.
+pub const EQUITY_HISTORY: usize = 240;
+
+/// A fill as listed among the recent trades.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Trade {
+    pub time: DateTime<Utc>,
+    pub strategy: String,
+    #[serde(flatten)]
+    pub fill: Fill,
+}
+
+/// An open entry, marked at the current bid.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Position {
+    pub strategy: String,
+    pub entry: Fill,
+    pub unrealized_pnl: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LiveState {
+    symbol: Option<&'static str>,
+    features: Option<Features>,
+    /// Open entries per strategy, paired last in, first out as the trading state closes them.
+    open: Vec<(String, Vec<Fill>)>,
+    trades: VecDeque<Trade>,
+    equity: VecDeque<f64>,
+}
+
+impl LiveState {
+    pub fn record(&mut self, event: &Event) {
+        match event {
+            Event::Features {
+                symbol, features, ..
+            } => {
+                self.symbol = Some(symbol);
+                self.features = Some(*features);
+            }
+            Event::Fill {
+                time,
+                strategy,
+                fill,
+                ..
+            } => {
+                let open = match self.open.iter().position(|(name, _)| name == strategy) {
+                    Some(index) => &mut self.open[index].1,
+                    None => {
+                        self.open.push((strategy.clone(), Vec::new()));
+                        &mut self.open.last_mut().expect("just pushed").1
+                    }
+                };
+                match fill.side {
+                    Side::Buy => open.push(*fill),
+                    Side::Sell => {
+                        open.pop();
+                    }
+                }
+
+                self.trades.push_front(Trade {
+                    time: *time,
+                    strategy: strategy.clone(),
+                    fill: *fill,
+                });
+                self.trades.truncate(RECENT_TRADES);
+            }
+            Event::Equity {
+                portfolio_value, ..
+            } => {
+                if self.equity.len() == EQUITY_HISTORY {
+                    self.equity.pop_front();
+                }
+                self.equity.push_back(*portfolio_value);
+            }
+            Event::Signal { .. }
+            | Event::Order { .. }
+            | Event::Rebalance { .. }
+            | Event::Session { .. }
+            | Event::Alert { .. } => {}
+        }
+    }
+
+    pub fn symbol(&self) -> Option<&'static str> {
+        self.symbol
+    }
+
+    /// Features of the latest book update.
+    pub fn features(&self) -> Option<&Features> {
+        self.features.as_ref()
+    }
+
+    /// Open positions by strategy, marked at the latest bid.
+    pub fn positions(&self) -> Vec<Position> {
+        let bid = self.features.map_or(0.0, |features| features.bid);
+        self.open
+            .iter()
+            .flat_map(|(strategy, open)| {
+                open.iter().map(move |entry| Position {
+                    strategy: strategy.clone(),
+                    entry: *entry,
+                    unrealized_pnl: (bid - entry.price) * entry.size,
+                })
+            })
+            .collect()
+    }
+
+    /// The latest trades, newest first.
+    pub fn trades(&self) -> impl Iterator<Item = &Trade> {
+        self.trades.iter()
+    }
+
+    /// Portfolio value at the latest updates, oldest first.
+    pub fn equity(&self) -> impl Iterator<Item = f64> + '_ {
+        self.equity.iter().copied()
+    }
+
+    pub fn snapshot(&self) -> LiveSnapshot {
+        LiveSnapshot {
+            symbol: self.symbol,
+            features: self.features,
+            equity: self.equity.back().copied(),
+            positions: self.positions(),
+            trades: self.trades.iter().cloned().collect(),
+            equity_history: self.equity.iter().copied().collect(),
+        }
+    }
+}
+
+/// Everything in a [`LiveState`], as served to web clients.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LiveSnapshot {
+    pub symbol: Option<&'static str>,
+    pub features: Option<Features>,
+    pub equity: Option<f64>,
+    pub positions: Vec<Position>,
+    pub trades: Vec<Trade>,
+    pub equity_history: Vec<f64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::Execution;
+    use crate::event::OrderType;
+
+    fn fill(side: Side, price: f64) -> Event {
+        Event::Fill {
+            time: DateTime::from_timestamp_millis(1_717_200_000_000).unwrap(),
+            symbol: "BTC/USDT",
+            strategy: "imbalance".to_owned(),
+            sleeve: 0,
+            execution: Execution::paper(OrderType::Limit, price),
+            fill: Fill {
+                side,
+                price,
+                size: 0.5,
+                fee: 0.1,
+            },
+            book_time: None,
+        }
+    }
+
+    #[test]
+    fn test_state_tracks_events() {
+        let mut live = LiveState::default();
+        let events = [
+            fill(Side::Buy, 100.0),
+            fill(Side::Buy, 102.0),
+            fill(Side::Sell, 103.0),
+            Event::Features {
+                time: Utc::now(),
+                symbol: "BTC/USDT",
+                features: Features {
+                    bid: 104.0,
+                    ..Features::default()
+                },
+                book_time: None,
+            },
+            Event::Equity {
+                time: Utc::now(),
+                symbol: "BTC/USDT",
+                portfolio_value: 1000.0,
+            },
+        ];
+        for event in &events {
+            live.record(event);
+        }
+
+        // The later entry was closed, the earlier one is marked at the bid
+        let positions = live.positions();
+        assert_eq!(positions.len(), 1);
+        assert_eq!(
+            (positions[0].entry.price, positions[0].unrealized_pnl),
+            (100.0, 2.0)
+        );
+        let sides: Vec<Side> = live.trades().map(|trade| trade.fill.side).collect();
+        assert_eq!(sides, [Side::Sell, Side::Buy, Side::Buy]);
+
+        let json = serde_json::to_value(live.snapshot()).unwrap();
+        assert_eq!(json["symbol"], "BTC/USDT");
+        assert_eq!(json["equity"], 1000.0);
+        assert_eq!(json["trades"][0]["price"], 103.0);
+        assert_eq!(json["positions"][0]["unrealized_pnl"], 2.0);
+    }
+}
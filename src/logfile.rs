@@ -0,0 +1,258 @@
+//! The machine-readable log, [`[log]`](crate::config::LogConfig): everything logged to the
+//! terminal, and trade spans when asked for through `RUST_LOG`, as JSON lines in a file that is
+//! rotated by size and by date so a long-running session keeps its history without filling the
+//! disk.
+//!
+//! Lines are written from a thread of their own, so a slow disk never holds up the trading loop.
+//! If it falls too far behind, lines are dropped rather than waited on.
+
+use chrono::DateTime;
+use chrono::Utc;
+use serde::Deserialize;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::config::LogConfig;
+
+/// When to start a new log file regardless of its size, on UTC boundaries.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rotation {
+    Never,
+    Hourly,
+    #[default]
+    Daily,
+}
+
+impl Rotation {
+    /// The period `time` falls in, changing whenever a new file is due.
+    fn period(self, time: DateTime<Utc>) -> i64 {
+        match self {
+            Self::Never => 0,
+            Self::Hourly => time.timestamp().div_euclid(3_600),
+            Self::Daily => time.timestamp().div_euclid(86_400),
+        }
+    }
+}
+
+/// A file rotated once a write would take it past `max_size` bytes, or once the [`Rotation`]
+/// period changes. Rotated files are renamed `<path>.1`, the newest, to `<path>.<max_files>`,
+/// the oldest kept.
+///
+/// Each write goes in whole, so lines written one at a time never straddle two files.
+#[derive(Debug)]
+pub struct RollingFile {
+    path: PathBuf,
+    max_size: Option<u64>,
+    rotation: Rotation,
+    max_files: usize,
+    file: File,
+    size: u64,
+    period: i64,
+}
+
+impl RollingFile {
+    /// Open the file at `path` for appending, creating it and its directory if needed. A file
+    /// left over from a previous period is rotated on the first write.
+    pub fn open(path: impl Into<PathBuf>, config: &LogConfig) -> io::Result<Self> {
+        let path = path.into();
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let metadata = file.metadata()?;
+        let modified = metadata
+            .modified()
+            .map_or_else(|_| Utc::now(), DateTime::from);
+        Ok(Self {
+            max_size: (config.max_size_mb > 0).then(|| config.max_size_mb * 1024 * 1024),
+            rotation: config.rotation,
+            max_files: config.max_files,
+            file,
+            size: metadata.len(),
+            period: config.rotation.period(modified),
+            path,
+        })
+    }
+
+    /// The path of the `index`th newest rotated file.
+    fn rotated(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{index}"));
+        path.into()
+    }
+
+    /// Shift every rotated file one older, dropping the oldest, and start the file over.
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        remove(&self.rotated(self.max_files))?;
+        for index in (1..self.max_files).rev() {
+            let from = self.rotated(index);
+            if from.exists() {
+                std::fs::rename(from, self.rotated(index + 1))?;
+            }
+        }
+        if self.max_files > 0 {
+            std::fs::rename(&self.path, self.rotated(1))?;
+        }
+        self.file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+
+    fn write_at(&mut self, buf: &[u8], now: DateTime<Utc>) -> io::Result<usize> {
+        let period = self.rotation.period(now);
+        let full = self
+            .max_size
+            .is_some_and(|max_size| self.size + buf.len() as u64 > max_size);
+        if self.size > 0 && (full || period != self.period) {
+            self.rotate()?;
+        }
+        self.period = period;
+        self.file.write_all(buf)?;
+        self.size += buf.len() as u64;
+        Ok(buf.len())
+    }
+}
+
+impl Write for RollingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_at(buf, Utc::now())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Remove the file at `path`, if there is one.
+fn remove(path: &Path) -> io::Result<()> {
+    match std::fs::remove_file(path) {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error),
+        _ => Ok(()),
+    }
+}
+
+/// Writes the JSON log from a thread of its own, until dropped, when the lines still queued are
+/// written out.
+#[derive(Debug)]
+pub struct JsonLog {
+    _worker: WorkerGuard,
+}
+
+impl JsonLog {
+    /// Open the log at `path` as `config` says, with the layer to write JSON lines to it.
+    pub fn open<S>(
+        path: &Path,
+        config: &LogConfig,
+    ) -> io::Result<(Self, impl tracing_subscriber::Layer<S>)>
+    where
+        S: tracing::Subscriber + for<'span> LookupSpan<'span>,
+    {
+        let (writer, worker) = tracing_appender::non_blocking(RollingFile::open(path, config)?);
+        let layer = tracing_subscriber::fmt::layer()
+            .json()
+            .with_ansi(false)
+            .with_writer(writer);
+        Ok((Self { _worker: worker }, layer))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use super::*;
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "fit-logs-{}-{}",
+            std::process::id(),
+            SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ))
+    }
+
+    fn read(path: PathBuf) -> String {
+        std::fs::read_to_string(path).unwrap_or_default()
+    }
+
+    #[test]
+    fn test_rotate_by_size_and_date() {
+        let dir = temp_dir();
+        let path = dir.join("bot.jsonl");
+        let config = LogConfig {
+            path: Some(path.clone()),
+            max_size_mb: 1,
+            rotation: Rotation::Daily,
+            max_files: 2,
+        };
+        let mut file = RollingFile::open(&path, &config).unwrap();
+        let day = DateTime::from_timestamp(1_704_067_200, 0).unwrap();
+        let line = vec![b'a'; 600 * 1024];
+
+        file.write_at(&line, day).unwrap();
+        // Too big to fit alongside the first line
+        file.write_at(&line, day).unwrap();
+        file.write_at(b"b\n", day).unwrap();
+        assert_eq!(read(path.clone()).len(), line.len() + 2);
+        assert_eq!(read(file.rotated(1)).len(), line.len());
+
+        // A new day starts a new file
+        let next_day = day + chrono::TimeDelta::days(1);
+        file.write_at(b"c\n", next_day).unwrap();
+        file.write_at(b"d\n", next_day).unwrap();
+        assert_eq!(read(path.clone()), "c\nd\n");
+        assert!(read(file.rotated(1)).ends_with("b\n"));
+        assert_eq!(read(file.rotated(2)).len(), line.len());
+
+        // Reopening appends until the file, written today, is a day old, and only two rotated
+        // files are kept
+        let mut file = RollingFile::open(&path, &config).unwrap();
+        file.write_at(b"e\n", Utc::now()).unwrap();
+        assert_eq!(read(path.clone()), "c\nd\ne\n");
+        file.write_at(b"f\n", Utc::now() + chrono::TimeDelta::days(1))
+            .unwrap();
+        assert_eq!(read(path.clone()), "f\n");
+        assert_eq!(read(file.rotated(1)), "c\nd\ne\n");
+        assert!(read(file.rotated(2)).ends_with("b\n"));
+        assert!(!file.rotated(3).exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_json_lines() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let dir = temp_dir();
+        let path = dir.join("bot.jsonl");
+        let (log, layer) = JsonLog::open(&path, &LogConfig::default()).unwrap();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(symbol = "BTC/USDT", "Order filled");
+        });
+        drop(log);
+
+        let contents = read(path);
+        let line: serde_json::Value = serde_json::from_str(contents.trim_end()).unwrap();
+        assert_eq!(line["level"], "INFO");
+        assert_eq!(line["fields"]["message"], "Order filled");
+        assert_eq!(line["fields"]["symbol"], "BTC/USDT");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
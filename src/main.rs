@@ -1,345 +1,1635 @@
-use barter_data::exchange::aevo::Aevo;
-use barter_data::streams::Streams;
-use barter_data::subscription::book::OrderBook;
-use barter_data::subscription::book::OrderBooksL2;
 use barter_integration::model::instrument::kind::InstrumentKind;
 use chrono::Utc;
-use std::thread;
+use fast_imbalance_trading::affinity;
+use fast_imbalance_trading::alerts::AlertEngine;
+use fast_imbalance_trading::alerts::Metrics;
+#[cfg(feature = "alloc-count")]
+use fast_imbalance_trading::alloc_count::CountingAllocator;
+use fast_imbalance_trading::alloc_count::HotPath;
+use fast_imbalance_trading::allocation::Allocator;
+#[cfg(feature = "api")]
+use fast_imbalance_trading::api::ApiServer;
+use fast_imbalance_trading::arbitrage::ArbitrageDetector;
+use fast_imbalance_trading::audit;
+use fast_imbalance_trading::audit::AuditLog;
+#[cfg(any(feature = "api", feature = "grpc", feature = "web"))]
+use fast_imbalance_trading::auth::Authenticator;
+use fast_imbalance_trading::backtest;
+use fast_imbalance_trading::basis::BasisPosition;
+use fast_imbalance_trading::basis::BasisTrader;
+use fast_imbalance_trading::bounds::SanityBounds;
+use fast_imbalance_trading::calibration::Calibrator;
+use fast_imbalance_trading::chaos::FeedChaos;
+use fast_imbalance_trading::chaos::OrderChaos;
+use fast_imbalance_trading::clock::ClockGuard;
+#[cfg(feature = "clock")]
+use fast_imbalance_trading::clock::ClockPoller;
+use fast_imbalance_trading::config::ConfigSource;
+use fast_imbalance_trading::config::LogConfig;
+#[cfg(feature = "otel")]
+use fast_imbalance_trading::config::TelemetryConfig;
+use fast_imbalance_trading::config::CONFIG_PATH;
+use fast_imbalance_trading::config::PROFILE_VAR;
+use fast_imbalance_trading::consolidated;
+use fast_imbalance_trading::consolidated::ConsolidatedBook;
+use fast_imbalance_trading::control;
+use fast_imbalance_trading::control::Control;
+use fast_imbalance_trading::event::Alert;
+use fast_imbalance_trading::event::BookTime;
+use fast_imbalance_trading::event::Event;
+#[cfg(feature = "parquet")]
+use fast_imbalance_trading::export::FeatureExporter;
+use fast_imbalance_trading::features::weighted_mid;
+use fast_imbalance_trading::features::Features;
+#[cfg(feature = "arrow")]
+use fast_imbalance_trading::feed::FeatureFeed;
+use fast_imbalance_trading::funding::FundingGuard;
+#[cfg(feature = "funding")]
+use fast_imbalance_trading::funding::FundingPoller;
+#[cfg(feature = "zmq")]
+use fast_imbalance_trading::gateway::OrderGateway;
+#[cfg(feature = "grpc")]
+use fast_imbalance_trading::grpc::GrpcServer;
+use fast_imbalance_trading::harvest::FundingHarvester;
+use fast_imbalance_trading::health::FeedHealth;
+use fast_imbalance_trading::hedge;
+use fast_imbalance_trading::hedge::DeltaHedger;
+use fast_imbalance_trading::hours::Permission;
+use fast_imbalance_trading::hours::TradingHours;
+use fast_imbalance_trading::invariants::InvariantChecker;
+use fast_imbalance_trading::journal::TradeJournal;
+use fast_imbalance_trading::latency::Latency;
+use fast_imbalance_trading::latency::Stage;
+#[cfg(feature = "tui")]
+use fast_imbalance_trading::live::LiveState;
+use fast_imbalance_trading::logfile::JsonLog;
+#[cfg(feature = "mark-price")]
+use fast_imbalance_trading::mark::MarkPoller;
+use fast_imbalance_trading::mark::MarkPrices;
+use fast_imbalance_trading::market;
+use fast_imbalance_trading::market::Books;
+use fast_imbalance_trading::market::LiquidationFeed;
+use fast_imbalance_trading::market::OrderFeed;
+use fast_imbalance_trading::market::TradeFeed;
+use fast_imbalance_trading::mbo::OrderStats;
+use fast_imbalance_trading::open_interest::OpenInterestFilter;
+#[cfg(feature = "open-interest")]
+use fast_imbalance_trading::open_interest::OpenInterestPoller;
+use fast_imbalance_trading::ordering::Sequencer;
+use fast_imbalance_trading::outliers::OutlierFilter;
+use fast_imbalance_trading::outliers::Verdict;
+use fast_imbalance_trading::pairs::PairsTrader;
+use fast_imbalance_trading::placement::FillModel;
+use fast_imbalance_trading::placement::Placement;
+use fast_imbalance_trading::probe::Probes;
+use fast_imbalance_trading::quality::FeedQuality;
+use fast_imbalance_trading::reload::ConfigWatcher;
+use fast_imbalance_trading::reload::Reloader;
+#[cfg(feature = "charts")]
+use fast_imbalance_trading::report::chart;
+use fast_imbalance_trading::report::Report;
+use fast_imbalance_trading::schedule::Scheduler;
+use fast_imbalance_trading::session::Session;
+use fast_imbalance_trading::signals::hawkes::FlowIntensity;
+use fast_imbalance_trading::signals::liquidation::CascadeDetector;
+use fast_imbalance_trading::signals::persistence::PersistentImbalance;
+use fast_imbalance_trading::sink;
+use fast_imbalance_trading::sink::Sink;
+use fast_imbalance_trading::snapshot::EngineSnapshot;
+use fast_imbalance_trading::snapshot::SnapshotStore;
+use fast_imbalance_trading::status::StatusGuard;
+#[cfg(feature = "status")]
+use fast_imbalance_trading::status::StatusPoller;
+#[cfg(feature = "sled")]
+use fast_imbalance_trading::store::StateStore;
+use fast_imbalance_trading::strategy::plugin::PluginRegistry;
+use fast_imbalance_trading::synthetic::SyntheticConfig;
+use fast_imbalance_trading::synthetic::SyntheticMarket;
+use fast_imbalance_trading::systemd::Notifier;
+#[cfg(feature = "otel")]
+use fast_imbalance_trading::telemetry::SpanExporter;
+use fast_imbalance_trading::toxicity::ToxicityGuard;
+use fast_imbalance_trading::triangular::TriangularScanner;
+#[cfg(feature = "tui")]
+use fast_imbalance_trading::tui::Tui;
+use fast_imbalance_trading::volatility::VolatilityForecaster;
+use fast_imbalance_trading::wal;
+use fast_imbalance_trading::wal::WriteAheadLog;
+use fast_imbalance_trading::watchdog::Watchdog;
+#[cfg(feature = "web")]
+use fast_imbalance_trading::web::WebDashboard;
+use fast_imbalance_trading::worker;
+use fast_imbalance_trading::worker::Portfolio;
+use fast_imbalance_trading::worker::Tick;
+use fast_imbalance_trading::worker::Workers;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::BufReader;
+use std::io::ErrorKind;
+use std::iter::Peekable;
+use std::path::PathBuf;
+use std::sync::Mutex;
 use std::time::Duration;
+use tokio::time::Instant;
 use tracing::info;
+use tracing::warn;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::Layer;
+use tracing_subscriber::Registry;
 
-// Constants
-const TRADE_SIZE: f64 = 0.001;
-const SPREAD_THRESHOLD: f64 = 0.05; // Adjust based on backtesting and performance analysis
-const TAKE_PROFIT: f64 = 0.01; // 1%
-const STOP_LOSS: f64 = 0.02; // 2%
-const TRANSACTION_COST: f64 = 0.005; // 0.5%
-
-// Struct to hold the trading state
-#[derive(Debug)]
-struct TradingState {
-    cash: f64,
-    positions: Vec<f64>,
-    symbol: &'static str,
+/// Where logs go while the terminal dashboard has the screen.
+const TUI_LOG_PATH: &str = "fast-imbalance-trading.log";
+
+#[cfg(feature = "alloc-count")]
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Command line flags, all optional.
+#[derive(Debug, Default)]
+struct Args {
+    command: Command,
+    /// `--chart <file>`: with `report`, also render the equity curve, drawdown and trades to
+    /// `file`, a PNG or SVG by its extension.
+    chart: Option<PathBuf>,
+    /// `--export-features <dir>`: also write labelled feature vectors to Parquet under `dir`.
+    export_features: Option<PathBuf>,
+    /// `--journal <file>`: also write a CSV trade journal, one row per round trip, to `file`.
+    journal: Option<PathBuf>,
+    /// `--profile <name>`: layer `config.<name>.toml` over `config.toml`, in place of
+    /// [`PROFILE_VAR`].
+    profile: Option<String>,
+    /// `--resume`: restore the latest snapshot and replay the event log written since.
+    resume: bool,
+    /// `--tui`: show a live dashboard in the terminal, logging to [`TUI_LOG_PATH`] instead.
+    tui: bool,
 }
 
-impl TradingState {
-    fn new(cash: f64, symbol: &'static str) -> Self {
-        Self {
-            cash,
-            positions: Vec::new(),
-            symbol,
-        }
+#[derive(Debug, Default)]
+enum Command {
+    /// Trade live.
+    #[default]
+    Run,
+    /// `fit-fills [log]`: fit the [fill model](fast_imbalance_trading::placement) on the event
+    /// log, `state.wal` unless given, over `placement.horizon_secs`, and print it as JSON.
+    FitFills { log: Option<PathBuf> },
+    /// `rebuild-state [log]`: replay the event log, `state.wal` unless given, and print the
+    /// trading state it leads to.
+    RebuildState { log: Option<PathBuf> },
+    /// `report [log]`: analyse the event log, `state.wal` unless given.
+    Report { log: Option<PathBuf> },
+    /// `simulate [books]`: backtest the configured strategies on a synthetic market, 10000 books
+    /// unless given, generated from `seed`.
+    Simulate { books: usize },
+    /// `verify-audit [log]`: check the hash chain of the audit log, `audit.path` unless given.
+    VerifyAudit { log: Option<PathBuf> },
+}
+
+impl Args {
+    fn parse() -> Self {
+        Self::parse_from(std::env::args().skip(1))
     }
 
-    fn calculate_voi(order_book: &OrderBook) -> (f64, f64, f64) {
-        let bid_volume: f64 = order_book.bids.levels.iter().map(|bid| bid.amount).sum();
-        let ask_volume: f64 = order_book.asks.levels.iter().map(|ask| ask.amount).sum();
-        let voi: f64 = bid_volume - ask_volume;
-        (voi, bid_volume, ask_volume)
+    /// Parse `argv`, without the program name. A command's optional argument is only taken from
+    /// the next one when that isn't a flag, so `report --chart out.png` reads the default log.
+    fn parse_from(argv: impl IntoIterator<Item = String>) -> Self {
+        let mut args = Self::default();
+        let mut argv = argv.into_iter().peekable();
+        while let Some(arg) = argv.next() {
+            match arg.as_str() {
+                "--chart" => {
+                    let path = argv.next().expect("--chart requires a file");
+                    args.chart = Some(PathBuf::from(path));
+                }
+                "--export-features" => {
+                    let dir = argv.next().expect("--export-features requires a directory");
+                    args.export_features = Some(PathBuf::from(dir));
+                }
+                "--journal" => {
+                    let path = argv.next().expect("--journal requires a file");
+                    args.journal = Some(PathBuf::from(path));
+                }
+                "--profile" => {
+                    args.profile = Some(argv.next().expect("--profile requires a name"));
+                }
+                "--resume" => args.resume = true,
+                "--tui" => args.tui = true,
+                "fit-fills" => {
+                    args.command = Command::FitFills {
+                        log: operand(&mut argv).map(PathBuf::from),
+                    };
+                }
+                "rebuild-state" => {
+                    args.command = Command::RebuildState {
+                        log: operand(&mut argv).map(PathBuf::from),
+                    };
+                }
+                "report" => {
+                    args.command = Command::Report {
+                        log: operand(&mut argv).map(PathBuf::from),
+                    };
+                }
+                "simulate" => {
+                    let books = operand(&mut argv).map_or(10_000, |books| {
+                        books.parse().expect("simulate requires a number of books")
+                    });
+                    args.command = Command::Simulate { books };
+                }
+                "verify-audit" => {
+                    args.command = Command::VerifyAudit {
+                        log: operand(&mut argv).map(PathBuf::from),
+                    };
+                }
+                other => panic!("unknown argument {other}"),
+            }
+        }
+        args
     }
+}
+
+/// The next argument, unless it is a flag.
+fn operand(argv: &mut Peekable<impl Iterator<Item = String>>) -> Option<String> {
+    argv.next_if(|arg| !arg.starts_with("--"))
+}
 
-    fn calculate_oir(bid_volume: f64, ask_volume: f64) -> f64 {
-        (bid_volume - ask_volume) / (bid_volume + ask_volume)
+#[tokio::main]
+async fn main() {
+    let args = Args::parse();
+    let source = ConfigSource::new(
+        CONFIG_PATH,
+        args.profile
+            .clone()
+            .or_else(|| std::env::var(PROFILE_VAR).ok()),
+    );
+    let config = source.load().expect("failed to load config");
+    // The JSON log writes out what is still queued as it is dropped, when main returns
+    #[cfg(feature = "otel")]
+    let (_json_log, spans) = init_logging(
+        args.tui.then_some(TUI_LOG_PATH),
+        &config.log,
+        config.telemetry.as_ref(),
+    );
+    #[cfg(not(feature = "otel"))]
+    let _json_log = {
+        assert!(
+            config.telemetry.is_none(),
+            "span export requires building with the otel feature"
+        );
+        init_logging(args.tui.then_some(TUI_LOG_PATH), &config.log)
+    };
+    if let Some(profile) = &source.profile {
+        info!("Running with the {} profile", profile);
     }
 
-    fn calculate_mpb(last_price: f64, mid_price: f64) -> f64 {
-        last_price - mid_price
+    if let Command::Report { log } = &args.command {
+        let path = log
+            .as_ref()
+            .or(config.state.wal.as_ref())
+            .expect("report requires an event log, given or set as state.wal");
+        let log = File::open(path).expect("failed to open event log");
+        let report = Report::read(BufReader::new(log)).expect("failed to read event log");
+        print!("{report}");
+
+        #[cfg(feature = "charts")]
+        if let Some(path) = &args.chart {
+            chart::render(&report.equity, path).expect("failed to render chart");
+            info!("Rendered chart to {}", path.display());
+        }
+        #[cfg(not(feature = "charts"))]
+        assert!(
+            args.chart.is_none(),
+            "--chart requires building with the charts feature"
+        );
+        return;
     }
 
-    fn calculate_spread(bid: f64, ask: f64) -> f64 {
-        (ask - bid) / bid * 100.0
+    if let Command::VerifyAudit { log } = &args.command {
+        let path = log
+            .as_ref()
+            .or(config.audit.path.as_ref())
+            .expect("verify-audit requires an audit log, given or set as audit.path");
+        let log = File::open(path).expect("failed to open audit log");
+        let chain = audit::verify(BufReader::new(log)).expect("audit log failed verification");
+        info!("Verified {} records in {}", chain.records, path.display());
+        println!("{}", chain.head);
+        return;
     }
+    let plugins = PluginRegistry::load_dir(&config.plugins.dir).expect("failed to load plugins");
 
-    fn should_trade(spread: f64, voi: f64, spread_threshold: f64) -> bool {
-        spread <= spread_threshold && voi.abs() > 0.0
+    if let Command::Simulate { books } = args.command {
+        let market: Vec<_> = SyntheticMarket::new(config.seed, SyntheticConfig::default())
+            .take(books)
+            .map(|event| event.kind)
+            .collect();
+        let report =
+            backtest::run(&config, &plugins, 1000.0, &market).expect("failed to build strategies");
+        info!(
+            "Simulated {} synthetic books with seed {}",
+            report.ticks, config.seed
+        );
+        println!("Initial value: {:.2}", report.initial_value);
+        println!("Final value: {:.2}", report.final_value);
+        println!("Return: {:.2}%", report.return_pct);
+        println!("Max drawdown: {:.2}%", report.max_drawdown_pct);
+        println!("Open positions: {}", report.open_positions);
+        return;
     }
+    let symbol = "BTC/USDT";
+    if let Command::FitFills { log } = &args.command {
+        let path = log
+            .as_ref()
+            .or(config.state.wal.as_ref())
+            .expect("fit-fills requires an event log, given or set as state.wal");
+        let horizon_secs = config.placement.clone().unwrap_or_default().horizon_secs;
+        let log = File::open(path).expect("failed to open event log");
+        let model = FillModel::read(BufReader::new(log), symbol, horizon_secs)
+            .expect("failed to read event log");
+        let model = serde_json::to_string_pretty(&model).expect("fill models always serialize");
+        println!("{model}");
+        return;
+    }
+    let mut allocator = Allocator::from_config(&config, &plugins, 1000.0, symbol, Utc::now())
+        .expect("failed to build strategies");
 
-    fn execute_trade(&mut self, price: f64, side: &str, trade_size: f64, fee: f64) {
-        let transaction_cost = trade_size * price * fee;
-        if side == "buy" {
-            self.positions.push(price);
-            self.cash -= price * trade_size + transaction_cost;
-            info!(
-                "Buying {} {} at {} (cost: {}) at {}",
-                trade_size,
-                self.symbol,
-                price,
-                transaction_cost,
-                Utc::now()
-            );
-        } else if side == "sell" {
-            if let Some(_position) = self.positions.pop() {
-                self.cash += price * trade_size - transaction_cost;
-                info!(
-                    "Selling {} {} at {} (cost: {}) at {}",
-                    trade_size,
-                    self.symbol,
-                    price,
-                    transaction_cost,
-                    Utc::now()
-                );
-            }
-        }
+    if let Command::RebuildState { log } = &args.command {
+        let path = log
+            .as_ref()
+            .or(config.state.wal.as_ref())
+            .expect("rebuild-state requires an event log, given or set as state.wal");
+        let log = File::open(path).expect("failed to open event log");
+        let entries =
+            wal::rebuild(&mut allocator, BufReader::new(log)).expect("failed to rebuild state");
+        info!("Replayed {} entries from {}", entries, path.display());
+        let snapshot = serde_json::to_string_pretty(&allocator.snapshot())
+            .expect("snapshots always serialize");
+        println!("{snapshot}");
+        return;
     }
 
-    fn check_tp_sl(&mut self, bid: f64, tp: f64, sl: f64) {
-        let mut positions_to_sell: Vec<f64> = Vec::new();
+    #[cfg(feature = "sled")]
+    let mut store = config.state.path.as_ref().map(|path| {
+        let mut store = StateStore::open(path).expect("failed to open state store");
+        if let Some(snapshot) = store.load().expect("failed to read saved state") {
+            allocator
+                .restore(&snapshot)
+                .expect("failed to restore saved state");
+            info!("Resumed trading state from {}", path.display());
+        }
+        store
+    });
+    #[cfg(not(feature = "sled"))]
+    assert!(
+        config.state.path.is_none(),
+        "the state store requires building with the sled feature"
+    );
 
-        for position in &self.positions {
-            let profit_loss = (bid - *position) / *position;
-            if profit_loss >= tp {
-                info!(
-                    "Triggering Take Profit: Selling position at {} with profit/loss: {:.2}%",
-                    bid,
-                    profit_loss * 100.0
-                );
-                positions_to_sell.push(*position);
-            } else if profit_loss <= -sl {
-                info!(
-                    "Triggering Stop Loss: Selling position at {} with profit/loss: {:.2}%",
-                    bid,
-                    profit_loss * 100.0
-                );
-                positions_to_sell.push(*position);
+    let snapshots = config.state.snapshots.as_ref().map(|dir| {
+        SnapshotStore::open(dir, config.state.keep_snapshots)
+            .expect("failed to open snapshot directory")
+    });
+    let latest = match &snapshots {
+        Some(snapshots) => snapshots.latest().expect("failed to read snapshots"),
+        None => None,
+    };
+    // Takes precedence over the state store, as the event log can be ahead of it
+    if args.resume {
+        let mut after = 0;
+        if let Some(snapshot) = &latest {
+            allocator
+                .restore(&snapshot.allocator)
+                .expect("failed to restore snapshot");
+            after = snapshot.sequence;
+            info!("Restored snapshot taken at {}", snapshot.time);
+        }
+        if let Some(path) = &config.state.wal {
+            match File::open(path) {
+                Ok(log) => {
+                    let entries = wal::replay(&mut allocator, BufReader::new(log), after)
+                        .expect("failed to replay event log");
+                    info!("Replayed {} entries from {}", entries, path.display());
+                }
+                Err(error) if error.kind() == ErrorKind::NotFound => {}
+                Err(error) => panic!("failed to open event log: {error}"),
             }
         }
+    }
 
-        for position in positions_to_sell {
-            self.positions.retain(|&x| x != position);
-            self.execute_trade(bid, "sell", TRADE_SIZE, TRANSACTION_COST);
-        }
+    for sleeve in allocator.sleeves() {
+        info!(
+            "Running {} strategy with {:.1}% of equity",
+            sleeve.strategy.name(),
+            sleeve.weight * 100.0
+        );
     }
 
-    fn calculate_portfolio_value(&self, bid: f64) -> f64 {
-        let position_value: f64 = self.positions.len() as f64 * TRADE_SIZE * bid;
-        self.cash + position_value
+    #[cfg(feature = "parquet")]
+    let mut exporter = args.export_features.as_ref().map(|dir| {
+        info!("Exporting labelled features to {}", dir.display());
+        FeatureExporter::new(dir, &config.export)
+    });
+    #[cfg(not(feature = "parquet"))]
+    assert!(
+        args.export_features.is_none(),
+        "--export-features requires building with the parquet feature"
+    );
+
+    let mut journal = args.journal.as_ref().map(|path| {
+        info!("Journaling trades to {}", path.display());
+        TradeJournal::create(path).expect("failed to create trade journal")
+    });
+
+    #[cfg(feature = "tui")]
+    let mut tui = args
+        .tui
+        .then(|| Tui::enter().expect("failed to start terminal dashboard"));
+    #[cfg(feature = "tui")]
+    let mut live = LiveState::default();
+    #[cfg(not(feature = "tui"))]
+    assert!(!args.tui, "--tui requires building with the tui feature");
+
+    #[cfg(feature = "arrow")]
+    let mut feed = match &config.feed.address {
+        Some(address) => Some(
+            FeatureFeed::bind(address)
+                .await
+                .expect("failed to bind feature feed"),
+        ),
+        None => None,
+    };
+    #[cfg(not(feature = "arrow"))]
+    assert!(
+        config.feed.address.is_none(),
+        "the feature feed requires building with the arrow feature"
+    );
+
+    let control = Control::default();
+    control::check_risk(&config.risk).expect("invalid [risk] config");
+    control.set_risk(config.risk);
+    let latency = Latency::default();
+    // Every control surface accepts the same credentials
+    #[cfg(any(feature = "api", feature = "grpc", feature = "web"))]
+    let auth = || Authenticator::new(&config.auth).expect("failed to set up authentication");
+    #[cfg(feature = "web")]
+    let web = match &config.web.address {
+        Some(address) => Some(
+            WebDashboard::bind(address, auth(), config.auth.tls.as_ref(), control.clone())
+                .await
+                .expect("failed to bind web dashboard"),
+        ),
+        None => None,
+    };
+    #[cfg(not(feature = "web"))]
+    assert!(
+        config.web.address.is_none(),
+        "the web dashboard requires building with the web feature"
+    );
+    let probes = Probes::new(&config.probes, Instant::now());
+    #[cfg(feature = "api")]
+    let api = match &config.api.address {
+        Some(address) => Some(
+            ApiServer::bind(
+                address,
+                auth(),
+                config.auth.tls.as_ref(),
+                control.clone(),
+                latency.clone(),
+                probes.clone(),
+            )
+            .await
+            .expect("failed to bind control API"),
+        ),
+        None => None,
+    };
+    #[cfg(not(feature = "api"))]
+    assert!(
+        config.api.address.is_none(),
+        "the control API requires building with the api feature"
+    );
+    #[cfg(feature = "grpc")]
+    let grpc = match &config.grpc.address {
+        Some(address) => Some(
+            GrpcServer::bind(address, auth(), config.auth.tls.as_ref(), control.clone())
+                .await
+                .expect("failed to bind gRPC server"),
+        ),
+        None => None,
+    };
+    #[cfg(not(feature = "grpc"))]
+    assert!(
+        config.grpc.address.is_none(),
+        "the gRPC server requires building with the grpc feature"
+    );
+
+    #[cfg(feature = "zmq")]
+    let mut gateway = match &config.gateway {
+        Some(gateway) => Some(
+            OrderGateway::connect(gateway, latency.clone())
+                .await
+                .expect("failed to connect order gateway"),
+        ),
+        None => None,
+    };
+    #[cfg(not(feature = "zmq"))]
+    assert!(
+        config.gateway.is_none(),
+        "the order gateway requires building with the zmq feature"
+    );
+    assert!(
+        config.chaos.is_none() || config.gateway.is_none(),
+        "chaos mode only runs on the paper account, not through the order gateway"
+    );
+    if let Some(chaos) = &config.chaos {
+        allocator.set_chaos(OrderChaos::new(chaos, config.seed));
     }
-}
+    assert!(
+        config.placement.is_none() || config.gateway.is_none(),
+        "placement is left to the execution service with the order gateway"
+    );
+    let mut placement = config.placement.as_ref().map(|placement| {
+        let model = FillModel::load(&placement.model).expect("failed to load fill model");
+        info!("Loaded fill model from {}", placement.model.display());
+        Placement::new(placement, model)
+    });
 
-#[tokio::main]
-async fn main() {
-    init_logging();
+    let mut wal = config.state.wal.as_ref().map(|path| {
+        info!("Logging events to {}", path.display());
+        let after = latest.as_ref().map_or(0, |snapshot| snapshot.sequence);
+        WriteAheadLog::open(path, after).expect("failed to open event log")
+    });
 
-    let mut trading_state = TradingState::new(1000.0, "BTC/USDT");
+    let mut audit = config.audit.path.as_ref().map(|path| {
+        info!("Auditing decisions to {}", path.display());
+        AuditLog::open(path).expect("failed to open audit log")
+    });
 
-    // TODO: Add order book streams from other exchanges, then merge them
-    let streams = Streams::<OrderBooksL2>::builder()
-        .subscribe([(Aevo, "btc", "usd", InstrumentKind::Perpetual, OrderBooksL2)])
-        .init()
+    let mut sinks = sink::connect(&config.sink, &latency)
         .await
-        .unwrap();
+        .expect("failed to connect event sinks");
+    for sink in &sinks {
+        info!("Publishing events to {}", sink.name());
+    }
 
-    let mut joined_stream = streams.join().await;
+    for (thread, settings) in [
+        ("feed", &config.threads.feed),
+        ("strategy", &config.threads.strategy),
+    ] {
+        affinity::check(thread, settings).expect("invalid [threads] config");
+    }
+    #[cfg(not(feature = "affinity"))]
+    assert!(
+        !config.threads.is_set(),
+        "thread pinning and priority require building with the affinity feature"
+    );
+    #[cfg(not(feature = "ring"))]
+    assert!(
+        config.threads.ring_capacity.is_none(),
+        "ring mode requires building with the ring feature"
+    );
+    if config.threads.ring_capacity.is_some() && config.threads.strategy.core.is_none() {
+        warn!("Busy-polling the book ring without pinning the strategy thread to a core");
+    }
+    // The trading loop runs on the main thread, blocked on by the runtime
+    if let Err(error) = affinity::apply("strategy", &config.threads.strategy) {
+        warn!("{}", error);
+    }
+    let subscribed = market::books(&config);
+    let mut books = Books::subscribe(&config.threads, subscribed.clone())
+        .await
+        .expect("failed to subscribe to order books");
+    probes.set_feed_connected(true);
+    let mut flow = config.hawkes.as_ref().map(FlowIntensity::new);
+    let mut toxicity = config.toxicity.as_ref().map(ToxicityGuard::new);
+    // Partly filled chaos orders rest in the book, worked through their queue by the trades
+    let resting = config
+        .chaos
+        .as_ref()
+        .is_some_and(|chaos| chaos.partial_rate > 0.0);
+    let mut trades = if flow.is_some() || toxicity.is_some() || resting {
+        TradeFeed::subscribe(config.threads.feed)
+            .await
+            .map_err(|error| {
+                warn!(
+                    "Failed to subscribe to trades, no order flow is counted: {}",
+                    error
+                )
+            })
+            .ok()
+    } else {
+        None
+    };
+    let mut cascades = config.liquidations.as_ref().map(CascadeDetector::new);
+    let mut liquidations = if cascades.is_some() {
+        LiquidationFeed::subscribe(config.threads.feed)
+            .await
+            .map_err(|error| {
+                warn!(
+                    "Failed to subscribe to liquidations, no cascades are detected: {}",
+                    error
+                )
+            })
+            .ok()
+    } else {
+        None
+    };
+    let mut notifier = Notifier::from_env(Instant::now());
+    notifier.ready();
+    let mut watchdog = Watchdog::new(
+        Duration::from_secs(config.watchdog.silence_secs),
+        market::subscriptions(&subscribed),
+        Instant::now(),
+    );
+    let mut health = FeedHealth::new(&config.health);
+    let mut alerts =
+        AlertEngine::new(&config.alerts, Instant::now()).expect("invalid [[alerts]] config");
+    let mut metrics = Metrics::new(Instant::now());
+    let mut quality = FeedQuality::default();
+    let mut sequencer = Sequencer::new(&config.ordering);
+    let mut outliers = OutlierFilter::new(&config.outliers);
+    let mut chaos = FeedChaos::new(config.chaos.as_ref(), config.seed);
+    let mut last_snapshot = Utc::now();
+    let mut session = Session::new(config.session.rollover, Utc::now());
+    // Lots restored from saved state are sold during the session like any bought in it
+    for (index, sleeve) in allocator.sleeves().iter().enumerate() {
+        session.carry(index, &sleeve.state.positions);
+    }
+    let mut watcher = ConfigWatcher::spawn(
+        source,
+        config.reload.watch_interval_secs.map(Duration::from_secs),
+    );
+    let mut reloader = Reloader::new(&config);
+    let mut scheduler = Scheduler::new(config.clone());
+    let mut hours = TradingHours::new(&config.hours);
+    let mut bounds = SanityBounds::new(&config.bounds);
+    let mut invariants = config
+        .invariants
+        .enabled
+        .then(|| InvariantChecker::new(&allocator));
+    // Order books only stream from Aevo, so its funding is the only one to avoid
+    for exchange in config.funding.keys().filter(|exchange| *exchange != "aevo") {
+        warn!(
+            "Not streaming from {}, ignoring its funding config",
+            exchange
+        );
+    }
+    let mut funding = config.funding.get("aevo").map(FundingGuard::new);
+    #[cfg(feature = "funding")]
+    let mut funding_rates = config.funding.get("aevo").map(FundingPoller::spawn);
+    #[cfg(not(feature = "funding"))]
+    assert!(
+        funding.is_none(),
+        "funding avoidance requires building with the funding feature"
+    );
+    let mut open_interest = config.open_interest.as_ref().map(OpenInterestFilter::new);
+    #[cfg(feature = "open-interest")]
+    let mut open_interest_polls = config.open_interest.as_ref().map(OpenInterestPoller::spawn);
+    #[cfg(not(feature = "open-interest"))]
+    assert!(
+        open_interest.is_none(),
+        "open interest filtering requires building with the open-interest feature"
+    );
+    let mut marks = config.mark.as_ref().map(MarkPrices::new);
+    #[cfg(feature = "mark-price")]
+    let mut mark_polls = config.mark.as_ref().map(MarkPoller::spawn);
+    #[cfg(not(feature = "mark-price"))]
+    assert!(
+        marks.is_none(),
+        "mark prices require building with the mark-price feature"
+    );
+    let mut clock = config.clock.as_ref().map(ClockGuard::new);
+    #[cfg(feature = "clock")]
+    let mut clock_samples = config.clock.as_ref().map(ClockPoller::spawn);
+    #[cfg(not(feature = "clock"))]
+    assert!(
+        clock.is_none(),
+        "clock drift checks require building with the clock feature"
+    );
+    let mut status = config.status.as_ref().map(StatusGuard::new);
+    #[cfg(feature = "status")]
+    let mut statuses = config.status.as_ref().map(StatusPoller::spawn);
+    #[cfg(not(feature = "status"))]
+    assert!(
+        status.is_none(),
+        "exchange status checks require building with the status feature"
+    );
+    let weighting = config.weighting.get(symbol).copied().unwrap_or_default();
+    let mut consolidated = config.consolidated.as_ref().map(|consolidated| {
+        ConsolidatedBook::new(consolidated).with_window(config.depth, weighting)
+    });
+    let mut persistence = config
+        .persistence
+        .as_ref()
+        .map(|persistence| PersistentImbalance::new(persistence, config.depth, weighting));
+    // Order books only stream from Aevo so far, leaving no other venue to compare with
+    let mut arbitrage = config.arbitrage.as_ref().map(ArbitrageDetector::new);
+    if arbitrage.is_some() {
+        warn!("Only streaming from Aevo, no arbitrage can be detected");
+    }
+    let mut basis = config.basis.as_ref().map(BasisTrader::new);
+    if basis.is_some() && !config.funding.contains_key("aevo") {
+        warn!("No funding rate polled without [funding.aevo], taking the fair basis as zero");
+    }
+    let mut harvest = config.harvest.as_ref().map(FundingHarvester::new);
+    if harvest.is_some() && !config.funding.contains_key("aevo") {
+        warn!("No funding rate polled without [funding.aevo], no funding can be harvested");
+    }
+    let mut hedger = config.hedge.as_ref().map(DeltaHedger::new);
+    let mut pairs = config.pairs.as_ref().map(PairsTrader::new);
+    let mut volatility = config.volatility.as_ref().map(VolatilityForecaster::new);
+    let traded = market::traded();
+    let spot = market::spot();
+    let paired = config.pairs.as_ref().map(market::paired);
+    let mut triangular = config.triangular.as_ref().map(TriangularScanner::new);
+    // Market-by-order stats come from the L3 books of venues streaming them, and Aevo doesn't, so
+    // none are subscribed until books stream from one that does
+    let mut orders: Option<OrderFeed> = None;
+    let mut order_stats = HashMap::new();
+    // Every other book subscribed is traded on a worker of its own, on the paper account
+    let mut worker_books: HashMap<_, _> = match &config.workers {
+        Some(_) => subscribed
+            .iter()
+            .filter(|book| **book != traded)
+            .map(|book| (book.clone(), market::symbol(book)))
+            .collect(),
+        None => HashMap::new(),
+    };
+    let mut workers = config.workers.as_ref().map(|workers| {
+        let allocators = worker_books
+            .values()
+            .map(|&symbol| {
+                let allocator =
+                    Allocator::from_config(&config, &plugins, workers.capital, symbol, Utc::now())
+                        .expect("failed to build strategies");
+                (symbol, allocator)
+            })
+            .collect();
+        Workers::spawn(allocators)
+    });
+    let mut portfolio = Portfolio::default();
+    // Workers trade within the perpetual's latest permission, flattening on their next book
+    let mut worker_permission = Permission::default();
+    let mut flattening = HashSet::new();
+    scheduler
+        .check()
+        .expect("invalid risk params in [[schedule]]");
+    let mut calibrator = config.calibration.as_ref().map(Calibrator::new);
 
-    while let Some(market_event) = joined_stream.recv().await {
-        let order_book = market_event.kind;
-        let bid: f64 = order_book.bids.levels[0].price;
-        let ask: f64 = order_book.asks.levels[0].price;
-        let spread: f64 = TradingState::calculate_spread(bid, ask);
-        let last_price: f64 = (bid + ask) / 2.0;
+    // Reused for every update, along with its capacity
+    let mut events = Vec::new();
+    let mut hot_path = HotPath::default();
+    loop {
+        probes.heartbeat(Instant::now());
+        notifier.ping(Instant::now());
+        #[cfg(feature = "zmq")]
+        probes.set_execution_reachable(gateway.as_ref().is_none_or(OrderGateway::is_reachable));
+        for alert in alerts.evaluate(&metrics, Instant::now()) {
+            raise(&mut sinks, symbol, alert);
+        }
+        let arrived = tokio::select! {
+            // Chaos mode may lose or hold back the update, letting it through later
+            market_event = books.recv() => match market_event {
+                Some(market_event) => chaos.feed(market_event, Instant::now()),
+                None => break,
+            },
+            _ = chaos.release_due() => chaos.release(Instant::now()),
+            trade = market::next_trade(&mut trades) => {
+                if let Some(flow) = &mut flow {
+                    flow.on_trade(trade.kind.side, trade.received_time);
+                }
+                if let Some(toxicity) = &mut toxicity {
+                    toxicity.on_trade(trade.kind.side, trade.kind.amount);
+                }
+                allocator.on_trade(trade.kind.side, trade.kind.price, trade.kind.amount);
+                continue;
+            }
+            liquidation = market::next_liquidation(&mut liquidations) => {
+                if let Some(cascades) = &mut cascades {
+                    let kind = liquidation.kind;
+                    cascades.on_liquidation(kind.side, kind.quantity, liquidation.received_time);
+                }
+                continue;
+            }
+            order_book = market::next_order_book(&mut orders) => {
+                let stats = OrderStats::from_order_book(&order_book.kind);
+                order_stats.insert(order_book.instrument, stats);
+                continue;
+            }
+            outcome = worker::next_outcome(&mut workers) => {
+                portfolio.record(&outcome);
+                for sink in &mut sinks {
+                    for event in &outcome.events {
+                        sink.publish(event);
+                    }
+                }
+                continue;
+            }
+            _ = sequencer.expired() => None,
+            _ = notifier.ping_due() => continue,
+            _ = alerts.check_due() => continue,
+            _ = watchdog.expired() => {
+                for (subscription, quiet) in watchdog.silent(Instant::now()) {
+                    warn!("No updates from {} for {}s", subscription, quiet.as_secs());
+                }
+                warn!("Resubscribing to order books");
+                probes.set_feed_connected(false);
+                match Books::subscribe(&config.threads, subscribed.clone()).await {
+                    Ok(resubscribed) => {
+                        books = resubscribed;
+                        probes.set_feed_connected(true);
+                    }
+                    Err(error) => warn!("Failed to resubscribe, keeping the old streams: {}", error),
+                }
+                chaos.on_resubscribe();
+                health.on_resubscribe(Instant::now());
+                if let Some(alert) = health.update(Instant::now()) {
+                    raise(&mut sinks, symbol, alert);
+                }
+                watchdog.reset(Instant::now());
+                continue;
+            }
+            Some(reloaded) = watcher.changed() => {
+                let mut reloaded = scheduler.replace(reloaded, Utc::now());
+                if let Some(calibrator) = &calibrator {
+                    reloaded = calibrator.apply(reloaded);
+                }
+                match reloader.apply(&reloaded, &mut allocator, &control, &plugins) {
+                    Ok(changed) if changed.is_empty() => info!("Config reloaded, nothing to apply"),
+                    Ok(changed) => info!("Config reloaded, applied {}", changed.join(", ")),
+                    Err(error) => warn!("Config reload rejected: {}", error),
+                }
+                continue;
+            }
+            _ = tokio::signal::ctrl_c() => {
+                info!("Shutting down");
+                break;
+            }
+        };
+        if let Some(market_event) = arrived {
+            probes.on_book(Instant::now());
+            metrics.last_book = Instant::now();
+            watchdog.heard(
+                market::subscription_name(&market_event.exchange, &market_event.instrument),
+                Instant::now(),
+            );
+            latency.record(
+                Stage::Receipt,
+                market_event.exchange_time,
+                market_event.received_time,
+            );
+            quality.record(&market_event);
+            sequencer.push(market_event, Instant::now());
+        }
+        // Only updates in order, and not seen before, go on to signal computation
+        let Some(market_event) = sequencer.pop(Instant::now()) else {
+            continue;
+        };
+        // Quarantined updates wait on the next to tell a real move from an outlier, rejected ones
+        // count against the feed like any other unusable update
+        let verdict = outliers.check(&market_event);
+        if verdict == Verdict::Quarantined {
+            continue;
+        }
+        // Judge the feed on every update, usable or not, so alerts still go out while nothing
+        // else is published
+        let features = match verdict {
+            Verdict::Pass => hot_path.measure(|| {
+                Features::from_order_book_within(&market_event.kind, &config.depth, weighting)
+            }),
+            Verdict::Quarantined | Verdict::Rejected => None,
+        };
+        health.on_update(features.is_some(), Instant::now());
+        if let Some(alert) = health.update(Instant::now()) {
+            raise(&mut sinks, symbol, alert);
+        }
+        let Some(mut features) = features else {
+            continue;
+        };
+        // Cycles are priced from the books of all their legs, traded or not
+        if let Some(triangular) = &mut triangular {
+            for alert in triangular.update(&market_event.instrument, &features) {
+                raise(&mut sinks, symbol, alert);
+            }
+        }
+        // The perpetual is traded on here: the spot book prices the basis and hedges, the pair's
+        // book is traded against it, the triangular legs are priced, and with workers every one
+        // of them is also traded on a worker of its own
+        if market_event.instrument != traded {
+            if let (Some(running), Some(&symbol)) =
+                (&workers, worker_books.get(&market_event.instrument))
+            {
+                let tick = Tick {
+                    time: Utc::now(),
+                    features,
+                    risk: control.risk(),
+                    permission: Permission {
+                        entries: worker_permission.entries,
+                        flatten: flattening.remove(symbol),
+                    },
+                    paused: control.is_paused(),
+                };
+                if !running.dispatch(symbol, tick) {
+                    warn!(
+                        "The {} worker has stopped, no longer trading its book",
+                        symbol
+                    );
+                    worker_books.remove(&market_event.instrument);
+                }
+            }
+            if market_event.instrument == spot {
+                if let Some(basis) = &mut basis {
+                    basis.on_spot(&features);
+                }
+                if let Some(harvest) = &mut harvest {
+                    harvest.on_spot(&features);
+                }
+                if let Some(hedger) = &mut hedger {
+                    hedger.on_book(InstrumentKind::Spot, &features);
+                }
+            } else if paired.as_ref() == Some(&market_event.instrument) {
+                if let Some(pairs) = &mut pairs {
+                    pairs.on_pair(&features);
+                }
+            }
+            continue;
+        }
+        if let Some(hedger) = &mut hedger {
+            hedger.on_book(InstrumentKind::Perpetual, &features);
+        }
+        #[cfg(feature = "zmq")]
+        if let Some(gateway) = &mut gateway {
+            gateway.on_book(&market_event.exchange, &market_event.kind);
+        }
+        if let Some(persistence) = &mut persistence {
+            features.persistent_oir =
+                persistence.update(&market_event.kind, market_event.received_time);
+        }
+        let fair_price = config
+            .fair_price
+            .as_ref()
+            .and_then(|fair_price| weighted_mid(&market_event.kind, fair_price.levels));
+        if let Some(fair_price) = fair_price {
+            features = features.with_fair_price(fair_price);
+        }
+        if let Some(stats) = order_stats.get(&market_event.instrument) {
+            features = features.with_order_stats(stats);
+        }
+        // Signals weigh the liquidity visible across every venue, still trading on this one's top
+        // of book
+        if let Some(consolidated) = &mut consolidated {
+            let exchange = market_event.exchange.to_string();
+            let instrument =
+                consolidated.update(&exchange, &market_event.instrument, &market_event.kind);
+            if let Some(merged) = consolidated.features(&instrument) {
+                features = consolidated::with_imbalance(features, &merged);
+            }
+            // Strategies that weigh other venues' prices see them ahead of this update
+            for venue in consolidated.venues(&instrument) {
+                if venue == exchange {
+                    continue;
+                }
+                if let Some(other) = consolidated.venue(&instrument, venue) {
+                    allocator.on_reference(other.mid_price);
+                }
+            }
+        }
 
-        // Calculate volume order imbalance
-        let (voi, bid_volume, ask_volume) = TradingState::calculate_voi(&order_book);
+        #[cfg(feature = "parquet")]
+        if let Some(exporter) = &mut exporter {
+            if let Err(error) = exporter.record(market_event.kind.last_update_time, &features) {
+                warn!("{}", error);
+            }
+        }
 
-        // Calculate Order Imbalance Ratio (OIR)
-        let oir: f64 = TradingState::calculate_oir(bid_volume, ask_volume);
+        #[cfg(feature = "arrow")]
+        if let Some(feed) = &mut feed {
+            if let Err(error) = feed.publish(market_event.kind.last_update_time, &features) {
+                warn!("Failed to publish features: {}", error);
+            }
+        }
 
-        // Calculate Mid-Price Basis (MPB)
-        let mpb: f64 = TradingState::calculate_mpb(last_price, (bid + ask) / 2.0);
+        let bid: f64 = features.bid;
+        metrics.spread_pct = Some(features.spread);
+        let now = Utc::now();
+        latency.record(Stage::Decision, market_event.received_time, now);
+        if let Some(volatility) = &mut volatility {
+            volatility.update(&features, now);
+            features.volatility = volatility.horizon_forecast().unwrap_or(0.0);
+        }
+        if let Some(flow) = &flow {
+            features.flow = flow.ratio(now);
+        }
+        if let Some(cascades) = &mut cascades {
+            features.liquidations = cascades.pressure(now);
+        }
+        let book_time = BookTime {
+            exchange_time: market_event.exchange_time,
+            received_time: market_event.received_time,
+        };
 
-        // Check if a trade should be made
-        if TradingState::should_trade(spread, voi, SPREAD_THRESHOLD) {
-            // Buy at the bid price if VOI is positive and OIR indicates a strong buy signal
-            if voi > 0.0 && oir > 0.1 {
-                trading_state.execute_trade(bid, "buy", TRADE_SIZE, TRANSACTION_COST);
+        events.clear();
+        events.push(Event::Features {
+            time: now,
+            symbol,
+            features,
+            book_time: Some(book_time),
+        });
+        if let Some(arbitrage) = &mut arbitrage {
+            for alert in arbitrage.update(
+                &market_event.exchange.to_string(),
+                &market_event.instrument.to_string(),
+                &features,
+                market_event.received_time,
+            ) {
+                events.push(Event::Alert {
+                    time: now,
+                    symbol,
+                    alert,
+                });
+            }
+        }
+        if let Some(alert) = basis
+            .as_mut()
+            .and_then(|basis| basis.update(&features, now))
+        {
+            events.push(Event::Alert {
+                time: now,
+                symbol,
+                alert,
+            });
+        }
+        if let Some(alert) = harvest
+            .as_mut()
+            .and_then(|harvest| harvest.update(&features, now))
+        {
+            events.push(Event::Alert {
+                time: now,
+                symbol,
+                alert,
+            });
+        }
+        if let Some(alert) = pairs
+            .as_mut()
+            .and_then(|pairs| pairs.update(&features, now))
+        {
+            events.push(Event::Alert {
+                time: now,
+                symbol,
+                alert,
+            });
+        }
+
+        if let Some(mut scheduled) = scheduler.poll(now) {
+            if let Some(calibrator) = &calibrator {
+                scheduled = calibrator.apply(scheduled);
+            }
+            match reloader.apply(&scheduled, &mut allocator, &control, &plugins) {
+                Ok(changed) if changed.is_empty() => {}
+                Ok(changed) => info!("Scheduled {} applied", changed.join(", ")),
+                Err(error) => warn!("Scheduled params rejected: {}", error),
+            }
+        }
+        if let Some(calibrator) = &mut calibrator {
+            calibrator.observe(&features);
+            if calibrator.poll(now).is_some() {
+                let calibrated = calibrator.apply(scheduler.config_at(now));
+                match reloader.apply(&calibrated, &mut allocator, &control, &plugins) {
+                    Ok(changed) if changed.is_empty() => {}
+                    Ok(changed) => info!("Calibrated {} applied", changed.join(", ")),
+                    Err(error) => warn!("Calibrated thresholds rejected: {}", error),
+                }
+            }
+        }
+
+        // Pick up risk params changed through the control surfaces or a reload, widened while the
+        // exchange is degraded, hold off entries outside trading hours, ahead of costly funding,
+        // while the feed, clock or exchange is off, the flow toxic, open interest unconfirmed or
+        // liquidations cascading, and close everything when asked to, at the end of the day, or
+        // as a blackout or funding window that closes positions starts
+        #[cfg(feature = "status")]
+        if let (Some(status), Some(latest)) = (
+            &mut status,
+            statuses.as_mut().and_then(StatusPoller::latest),
+        ) {
+            status.set_status(latest);
+        }
+        if let Some(alert) = status.as_mut().and_then(|status| status.update(now)) {
+            events.push(Event::Alert {
+                time: now,
+                symbol,
+                alert,
+            });
+        }
+        let risk = volatility
+            .as_ref()
+            .map_or(control.risk(), |volatility| volatility.risk(control.risk()));
+        allocator.set_risk(status.as_ref().map_or(risk, |status| status.risk(risk)));
+        allocator.limit_lots(volatility.as_ref().and_then(VolatilityForecaster::max_lots));
+        if let Some(placement) = &mut placement {
+            allocator.set_crossing(placement.update(&features, now));
+        }
+        #[cfg(feature = "funding")]
+        if let Some(rate) = funding_rates.as_mut().and_then(FundingPoller::latest) {
+            if let Some(funding) = &mut funding {
+                funding.set_rate(rate);
             }
-            // Sell at the ask price if VOI is negative and MPB indicates a strong sell signal
-            else if voi < 0.0 && mpb < -0.1 && !trading_state.positions.is_empty() {
-                trading_state.execute_trade(ask, "sell", TRADE_SIZE, TRANSACTION_COST);
+            if let Some(basis) = &mut basis {
+                basis.set_rate(rate);
+            }
+            if let Some(harvest) = &mut harvest {
+                harvest.set_rate(rate);
+            }
+        }
+        #[cfg(feature = "open-interest")]
+        if let (Some(open_interest), Some((time, latest))) = (
+            &mut open_interest,
+            open_interest_polls
+                .as_mut()
+                .and_then(OpenInterestPoller::latest),
+        ) {
+            open_interest.set(time, latest);
+        }
+        metrics.open_interest = open_interest.as_ref().and_then(OpenInterestFilter::latest);
+        metrics.open_interest_change_pct = open_interest
+            .as_ref()
+            .and_then(OpenInterestFilter::change)
+            .map(|change| change * 100.0);
+        #[cfg(feature = "mark-price")]
+        if let (Some(marks), Some((time, latest))) =
+            (&mut marks, mark_polls.as_mut().and_then(MarkPoller::latest))
+        {
+            marks.set(time, latest);
+        }
+        // Without a fresh mark, positions are judged at the fair price when one is configured
+        let mark = marks
+            .as_mut()
+            .and_then(|marks| marks.update(now))
+            .or(fair_price);
+        allocator.set_mark(mark);
+        metrics.mark_premium_bps = marks
+            .as_ref()
+            .and_then(MarkPrices::latest)
+            .and_then(|latest| latest.premium_bps());
+        #[cfg(feature = "clock")]
+        if let (Some(clock), Some(sample)) = (
+            &mut clock,
+            clock_samples.as_mut().and_then(ClockPoller::latest),
+        ) {
+            clock.set_sample(sample);
+        }
+        if let Some(alert) = clock.as_mut().and_then(ClockGuard::update) {
+            events.push(Event::Alert {
+                time: now,
+                symbol,
+                alert,
+            });
+        }
+        if let Some(alert) = toxicity
+            .as_mut()
+            .and_then(|toxicity| toxicity.update(features.mid_price, now))
+        {
+            events.push(Event::Alert {
+                time: now,
+                symbol,
+                alert,
+            });
+        }
+        let permission = hours
+            .update(now)
+            .and(
+                funding
+                    .as_mut()
+                    .map_or_else(Permission::default, |funding| funding.update(now)),
+            )
+            .and(health.permission())
+            .and(
+                clock
+                    .as_ref()
+                    .map_or_else(Permission::default, ClockGuard::permission),
+            )
+            .and(
+                status
+                    .as_ref()
+                    .map_or_else(Permission::default, StatusGuard::permission),
+            )
+            .and(
+                toxicity
+                    .as_ref()
+                    .map_or_else(Permission::default, ToxicityGuard::permission),
+            )
+            .and(
+                open_interest
+                    .as_mut()
+                    .map_or_else(Permission::default, |filter| filter.update(&features)),
+            )
+            .and(
+                cascades
+                    .as_mut()
+                    .map_or_else(Permission::default, |cascades| cascades.update(&features)),
+            );
+        allocator.block_entries(!permission.entries);
+        // Nothing is traded on a book outside the instrument's sanity bounds, exits included
+        if let Some(alert) = bounds.update(symbol, &features) {
+            events.push(Event::Alert {
+                time: now,
+                symbol,
+                alert,
+            });
+        }
+        #[cfg(feature = "zmq")]
+        let paper = gateway.is_none();
+        #[cfg(not(feature = "zmq"))]
+        let paper = true;
+        worker_permission = permission;
+        let flatten = control.take_flatten() | permission.flatten;
+        if flatten {
+            flattening.extend(worker_books.values().copied());
+            if bounds.blocked() {
+                warn!("Not flattening on a book outside its sanity bounds");
+            } else {
+                #[cfg(feature = "zmq")]
+                let flattened = match &mut gateway {
+                    Some(gateway) => gateway.flatten(&mut allocator, &features, now),
+                    None => allocator.flatten(&features, now),
+                };
+                #[cfg(not(feature = "zmq"))]
+                let flattened = allocator.flatten(&features, now);
+                events.extend(flattened);
             }
         }
 
-        // Check for Take Profit or Stop Loss conditions
-        trading_state.check_tp_sl(bid, TAKE_PROFIT, STOP_LOSS);
+        // Let every strategy sleeve trade on the new features, or route its orders through the
+        // gateway when one is configured, unless trading is paused or the book is out of bounds
+        if !control.is_paused() && !bounds.blocked() {
+            #[cfg(feature = "zmq")]
+            let decided = hot_path.measure(|| match &mut gateway {
+                Some(gateway) => gateway.on_features(&mut allocator, &features, now),
+                None => allocator.on_features(&features, now),
+            });
+            #[cfg(not(feature = "zmq"))]
+            let decided = hot_path.measure(|| allocator.on_features(&features, now));
+            events.extend(decided);
+        }
+        // A resting order joins its level's queue on the book it rested on, then follows it
+        allocator.on_book(&market_event.kind);
+        if let Some(toxicity) = &mut toxicity {
+            for event in &events {
+                if let Event::Fill { fill, .. } = event {
+                    toxicity.on_fill(fill.side, now, features.mid_price);
+                }
+            }
+        }
+        // Trading allocates for its events, holding shouldn't allocate at all
+        #[cfg(feature = "alloc-count")]
+        {
+            let allocations = hot_path.take();
+            let traded = events
+                .iter()
+                .any(|event| matches!(event, Event::Order { .. } | Event::Fill { .. }));
+            if allocations > 0 && !traded {
+                warn!(
+                    "{} heap allocations deciding on a book that wasn't traded on",
+                    allocations
+                );
+            }
+        }
+        // The paper account books orders as they are decided on, the gateway times them as sent
+        if paper
+            && events
+                .iter()
+                .any(|event| matches!(event, Event::Order { .. }))
+        {
+            latency.record(Stage::Order, now, Utc::now());
+        }
+        // Trading on corrupt state only compounds it, so a broken invariant pauses everything
+        if let Some(invariants) = &mut invariants {
+            if let Some(alert) = invariants.update(&allocator, &features, &events) {
+                if !alert.resolved {
+                    control.pause();
+                }
+                events.push(Event::Alert {
+                    time: now,
+                    symbol,
+                    alert,
+                });
+            }
+        }
+        // Hedge whatever the update left open, unless the book is out of bounds
+        if let Some(hedger) = hedger.as_mut().filter(|_| !bounds.blocked()) {
+            let delta = hedge::sleeve_delta(&allocator)
+                + basis
+                    .as_ref()
+                    .and_then(BasisTrader::position)
+                    .map_or(0.0, BasisPosition::delta)
+                + harvest
+                    .as_ref()
+                    .and_then(FundingHarvester::position)
+                    .map_or(0.0, BasisPosition::delta);
+            if let Some(alert) = hedger.update(delta) {
+                events.push(Event::Alert {
+                    time: now,
+                    symbol,
+                    alert,
+                });
+            }
+        }
+        for event in &mut events {
+            event.stamp(book_time);
+        }
 
         // Calculate the current portfolio value
-        let portfolio_value = trading_state.calculate_portfolio_value(bid);
+        let portfolio_value = allocator.total_value(bid);
         info!(
             "Current portfolio value: ${:.2} at {}",
-            portfolio_value,
-            Utc::now()
+            portfolio_value, now
         );
+        if workers.is_some() {
+            info!(
+                "Portfolio value across every instrument: ${:.2}",
+                portfolio_value + portfolio.total()
+            );
+        }
+        events.push(Event::Equity {
+            time: now,
+            symbol,
+            portfolio_value,
+        });
 
-        // Sleep before the next iteration
-        thread::sleep(Duration::from_secs(1));
-    }
-}
+        // Close the day's session before counting this update toward the next one
+        if let Some(summary) = session.maybe_roll(now) {
+            info!(
+                "Session since {} closed: {} trades, {:.1}% won, gross pnl: {:.2}, net pnl: {:.2}, \
+                 fees: {:.2}, max drawdown: {:.2}%",
+                summary.start,
+                summary.trades,
+                summary.win_rate_pct,
+                summary.gross_pnl,
+                summary.net_pnl,
+                summary.fees,
+                summary.max_drawdown_pct
+            );
+            info!("Latency since starting: {}", latency.summary());
+            info!("{}", quality);
+            info!(
+                "Dropped {} duplicate and {} out of order book updates since starting",
+                sequencer.duplicates(),
+                sequencer.late()
+            );
+            info!(
+                "Rejected {} malformed books and quarantined {} price jumps, {} of them outliers, \
+                 since starting",
+                outliers.malformed(),
+                outliers.quarantined(),
+                outliers.outliers()
+            );
+            if config.chaos.is_some() {
+                info!(
+                    "Chaos mode injected {} feed disconnects and {} delayed books since starting",
+                    chaos.disconnects(),
+                    chaos.delays()
+                );
+            }
+            info!(
+                "Skipped {} stale book updates since subscribing",
+                books.skipped()
+            );
+            events.push(Event::Session {
+                time: now,
+                symbol,
+                summary,
+            });
+        }
+        for event in &events {
+            session.record(event);
+        }
+        metrics.equity = Some(portfolio_value);
+        metrics.unrealized_pnl = Some(allocator.unrealized_pnl(mark.unwrap_or(bid)));
+        metrics.session_pnl = Some(session.net_pnl());
 
-// Initialise an INFO `Subscriber` for `Tracing` Json logs and install it as the global default.
-fn init_logging() {
-    tracing_subscriber::fmt()
-        // Filter messages based on the INFO
-        .with_env_filter(
-            tracing_subscriber::filter::EnvFilter::builder()
-                .with_default_directive(tracing_subscriber::filter::LevelFilter::INFO.into())
-                .from_env_lossy(),
-        )
-        // Disable colours on release builds
-        .with_ansi(cfg!(debug_assertions))
-        // Enable Json formatting
-        .pretty()
-        // Install this Tracing subscriber as global default
-        .init()
-}
+        #[cfg(feature = "sled")]
+        if let Some(store) = &mut store {
+            if let Err(error) = store.save(&allocator) {
+                warn!("Failed to save trading state: {}", error);
+            }
+        }
 
-#[cfg(test)]
-mod tests {
-    use barter_data::subscription::book::Level;
-    use barter_data::subscription::book::OrderBookSide;
-    use barter_integration::model::Side;
-    use chrono::DateTime;
+        // Log events before anyone else sees them, so the log never lags the published history
+        if let Some(wal) = &mut wal {
+            if let Err(error) = wal.append(&events) {
+                warn!("Failed to log events: {}", error);
+            }
+        }
+        if let Some(audit) = &mut audit {
+            if let Err(error) = audit.append(&events) {
+                warn!("Failed to audit events: {}", error);
+            }
+        }
 
-    use super::*;
+        // Snapshot when due; the log up to here is no longer needed once it is on disk
+        if let Some(snapshots) = &snapshots {
+            if (now - last_snapshot).num_seconds() >= config.state.snapshot_interval_secs as i64 {
+                let snapshot = EngineSnapshot {
+                    sequence: wal.as_ref().map_or(0, WriteAheadLog::sequence),
+                    time: now,
+                    allocator: allocator.snapshot(),
+                };
+                match snapshots.save(&snapshot) {
+                    Ok(path) => {
+                        info!("Saved snapshot to {}", path.display());
+                        if let Some(wal) = &mut wal {
+                            if let Err(error) = wal.truncate() {
+                                warn!("Failed to truncate event log: {}", error);
+                            }
+                        }
+                    }
+                    Err(error) => warn!("Failed to save snapshot: {}", error),
+                }
+                last_snapshot = now;
+            }
+        }
 
-    // Constants for tests
-    const TEST_TRADE_SIZE: f64 = 0.001;
-    const TEST_TAKE_PROFIT: f64 = 0.01;
-    const TEST_STOP_LOSS: f64 = 0.02;
-    const TEST_TRANSACTION_COST: f64 = 0.005;
-    const TEST_SPREAD_THRESHOLD: f64 = 0.05;
-    const FLOAT_TOLERANCE: f64 = 0.00001;
-
-    /// Helper function that rounds the floating-point numbers to a specified number of decimal places
-    /// before comparing them.
-    fn approx_equal(a: f64, b: f64, tolerance: f64) -> bool {
-        (a - b).abs() < tolerance
-    }
+        for sink in &mut sinks {
+            for event in &events {
+                sink.publish(event);
+            }
+        }
 
-    #[test]
-    fn test_calculate_voi() {
-        let order_book = OrderBook {
-            last_update_time: DateTime::from_timestamp_millis(0).unwrap(),
-            bids: OrderBookSide::new(
-                Side::Buy,
-                vec![Level {
-                    price: 100.0,
-                    amount: 1.0,
-                }],
-            ),
-            asks: OrderBookSide::new(
-                Side::Sell,
-                vec![Level {
-                    price: 101.0,
-                    amount: 1.0,
-                }],
-            ),
-        };
+        if let Some(journal) = &mut journal {
+            for event in &events {
+                if let Err(error) = journal.record(event) {
+                    warn!("{}", error);
+                }
+            }
+        }
 
-        let (voi, bid_volume, ask_volume) = TradingState::calculate_voi(&order_book);
-        assert_eq!(voi, 0.0);
-        assert_eq!(bid_volume, 1.0);
-        assert_eq!(ask_volume, 1.0);
-    }
+        #[cfg(feature = "web")]
+        if let Some(web) = &web {
+            web.publish(&events);
+        }
+        #[cfg(feature = "api")]
+        if let Some(api) = &api {
+            api.publish(&events);
+        }
+        #[cfg(feature = "grpc")]
+        if let Some(grpc) = &grpc {
+            grpc.publish(&events);
+        }
 
-    #[test]
-    fn test_calculate_oir() {
-        let oir = TradingState::calculate_oir(1.0, 1.0);
-        assert_eq!(oir, 0.0);
+        #[cfg(feature = "tui")]
+        if let Some(tui) = &mut tui {
+            for event in &events {
+                live.record(event);
+            }
+            if let Err(error) = tui.draw(&live) {
+                warn!("Failed to draw dashboard: {}", error);
+            }
+            if tui.quit_requested().unwrap_or(false) {
+                info!("Shutting down");
+                break;
+            }
+        }
+    }
+    notifier.stopping();
+    info!("{}", quality);
+    // Let every worker finish the book in hand before saying where each instrument ended up
+    if let Some(workers) = workers {
+        workers.shutdown().await;
+        for (symbol, value) in portfolio.values() {
+            info!("Portfolio value on {}: ${:.2}", symbol, value);
+        }
     }
 
-    #[test]
-    fn test_calculate_mpb() {
-        let mpb = TradingState::calculate_mpb(100.0, 100.0);
-        assert_eq!(mpb, 0.0);
+    #[cfg(feature = "parquet")]
+    if let Some(exporter) = exporter {
+        exporter.finish().expect("failed to write feature export");
+    }
+    #[cfg(feature = "otel")]
+    if let Some(spans) = spans {
+        if let Err(error) = spans.shutdown() {
+            warn!("Failed to export the last trade spans: {}", error);
+        }
     }
+}
 
-    #[test]
-    fn test_calculate_spread() {
-        let spread = TradingState::calculate_spread(100.0, 101.0);
-        assert_eq!(spread, 1.0);
+/// Publish `alert` to every sink straight away, rather than with the next update's events.
+fn raise(sinks: &mut [Box<dyn Sink>], symbol: &'static str, alert: Alert) {
+    let event = Event::Alert {
+        time: Utc::now(),
+        symbol,
+        alert,
+    };
+    for sink in sinks {
+        sink.publish(&event);
     }
+}
 
-    #[test]
-    fn test_should_trade() {
-        let valid_voi = 1.0;
-        assert!(TradingState::should_trade(
-            TEST_SPREAD_THRESHOLD,
-            valid_voi,
-            TEST_SPREAD_THRESHOLD
-        ));
+// Initialise an INFO `Subscriber` for `Tracing` logs and install it as the global default,
+// writing to `log_file` instead of the terminal when given, and to the JSON log when `log` has a
+// path.
+#[cfg(not(feature = "otel"))]
+fn init_logging(log_file: Option<&str>, log: &LogConfig) -> Option<JsonLog> {
+    let (json_log, json) = json_layer(log);
+    tracing_subscriber::registry()
+        .with(log_layer(log_file))
+        .with(json)
+        .init();
+    json_log
+}
 
-        let invalid_spread = 0.06;
-        let valid_voi = 1.0;
-        assert!(!TradingState::should_trade(
-            invalid_spread,
-            valid_voi,
-            TEST_SPREAD_THRESHOLD
-        ));
+// Initialise an INFO `Subscriber` for `Tracing` logs and install it as the global default,
+// writing to `log_file` instead of the terminal when given, to the JSON log when `log` has a path,
+// and exporting trade spans over OTLP when `telemetry` is configured.
+#[cfg(feature = "otel")]
+fn init_logging(
+    log_file: Option<&str>,
+    log: &LogConfig,
+    telemetry: Option<&TelemetryConfig>,
+) -> (Option<JsonLog>, Option<SpanExporter>) {
+    let (exporter, spans) = match telemetry {
+        Some(telemetry) => {
+            let (exporter, spans) =
+                SpanExporter::new(telemetry).expect("failed to start span export");
+            (Some(exporter), Some(spans))
+        }
+        None => (None, None),
+    };
+    let (json_log, json) = json_layer(log);
+    tracing_subscriber::registry()
+        .with(log_layer(log_file))
+        .with(json)
+        .with(spans)
+        .init();
+    (json_log, exporter)
+}
 
-        let invalid_voi = 0.0;
-        assert!(!TradingState::should_trade(
-            TEST_SPREAD_THRESHOLD,
-            invalid_voi,
-            TEST_SPREAD_THRESHOLD
-        ));
+// Log messages, at INFO unless `RUST_LOG` says otherwise, leaving trade spans to the exporter.
+fn log_filter() -> tracing_subscriber::filter::EnvFilter {
+    tracing_subscriber::filter::EnvFilter::builder()
+        .with_default_directive(tracing_subscriber::filter::LevelFilter::INFO.into())
+        .from_env_lossy()
+}
+
+fn log_layer(log_file: Option<&str>) -> impl Layer<Registry> {
+    let layer = tracing_subscriber::fmt::layer()
+        // Disable colours on release builds
+        .with_ansi(cfg!(debug_assertions))
+        .pretty();
+    match log_file {
+        Some(path) => {
+            let file = File::create(path).expect("failed to create log file");
+            layer.with_ansi(false).with_writer(Mutex::new(file)).boxed()
+        }
+        None => layer.boxed(),
     }
+    .with_filter(log_filter())
+}
 
-    #[test]
-    fn test_execute_trade() {
-        let mut state = TradingState::new(1000.0, "BTC/USDT");
-        state.execute_trade(100.0, "buy", TEST_TRADE_SIZE, TEST_TRANSACTION_COST);
-        let expected_cash_after_buy =
-            1000.0 - (100.0 * TEST_TRADE_SIZE) - (100.0 * TEST_TRADE_SIZE * TEST_TRANSACTION_COST);
-        assert_eq!(state.cash, expected_cash_after_buy);
-        assert_eq!(state.positions.len(), 1);
-
-        state.execute_trade(100.0, "sell", TEST_TRADE_SIZE, TEST_TRANSACTION_COST);
-        let expected_cash_after_sell = expected_cash_after_buy + (100.0 * TEST_TRADE_SIZE)
-            - (100.0 * TEST_TRADE_SIZE * TEST_TRANSACTION_COST);
-        assert_eq!(state.cash, expected_cash_after_sell);
-        assert_eq!(state.positions.len(), 0);
+// The same messages as JSON lines, when `log` has a path to write them to.
+fn json_layer<S>(log: &LogConfig) -> (Option<JsonLog>, Option<impl Layer<S>>)
+where
+    S: tracing::Subscriber + for<'span> LookupSpan<'span>,
+{
+    match &log.path {
+        Some(path) => {
+            let (json_log, layer) = JsonLog::open(path, log).expect("failed to open JSON log");
+            (Some(json_log), Some(layer.with_filter(log_filter())))
+        }
+        None => (None, None),
     }
+}
 
-    #[test]
-    fn test_check_tp_sl() {
-        let mut state = TradingState::new(1000.0, "BTC/USDT");
-
-        // Testing Take Profit
-        state.positions.push(100.0);
-        state.check_tp_sl(102.0, TEST_TAKE_PROFIT, TEST_STOP_LOSS);
-        let profit = 100.0 * TEST_TRADE_SIZE * (1.0 + TEST_TAKE_PROFIT);
-        let transaction_cost_tp = 102.0 * TEST_TRADE_SIZE * TEST_TRANSACTION_COST;
-        let expected_cash_after_tp = 1000.0 + profit - transaction_cost_tp;
-        assert_eq!(state.positions.len(), 0);
-        assert!(
-            approx_equal(state.cash, expected_cash_after_tp, FLOAT_TOLERANCE),
-            "Cash after TP not as expected. Got: {}, Expected: {}",
-            state.cash,
-            expected_cash_after_tp
-        );
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
 
-        // Testing Stop Loss
-        state.positions.push(100.0);
-        state.check_tp_sl(98.0, TEST_TAKE_PROFIT, TEST_STOP_LOSS);
-        let loss = 100.0 * TEST_TRADE_SIZE * (1.0 - TEST_STOP_LOSS);
-        let transaction_cost_sl = 98.0 * TEST_TRADE_SIZE * TEST_TRANSACTION_COST;
-        let expected_cash_after_sl = expected_cash_after_tp - loss - transaction_cost_sl;
-        assert_eq!(state.positions.len(), 0);
-        assert!(
-            approx_equal(state.cash, expected_cash_after_sl, FLOAT_TOLERANCE),
-            "Cash after SL not as expected. Got: {}, Expected: {}",
-            state.cash,
-            expected_cash_after_sl
-        );
+    use super::*;
+
+    fn parse(argv: &[&str]) -> Args {
+        Args::parse_from(argv.iter().map(|arg| arg.to_string()))
     }
 
     #[test]
-    fn test_calculate_portfolio_value() {
-        let mut state = TradingState::new(1000.0, "BTC/USDT");
-        state.positions.push(100.0);
-        let portfolio_value = state.calculate_portfolio_value(101.0);
-        let expected_portfolio_value = 1000.0 + (101.0 * TEST_TRADE_SIZE);
-        assert_eq!(portfolio_value, expected_portfolio_value);
+    fn test_parse_args() {
+        let args = parse(&["report", "--chart", "out.png"]);
+        assert!(matches!(args.command, Command::Report { log: None }));
+        assert_eq!(args.chart, Some(PathBuf::from("out.png")));
+
+        let args = parse(&["report", "events.wal", "--chart", "out.svg"]);
+        assert!(matches!(
+            args.command,
+            Command::Report { log: Some(log) } if log.as_path() == Path::new("events.wal")
+        ));
+
+        let args = parse(&["rebuild-state", "--profile", "paper"]);
+        assert!(matches!(args.command, Command::RebuildState { log: None }));
+        assert_eq!(args.profile.as_deref(), Some("paper"));
+
+        let args = parse(&["simulate", "--tui"]);
+        assert!(matches!(args.command, Command::Simulate { books: 10_000 }));
+        assert!(args.tui);
+        assert!(matches!(
+            parse(&["simulate", "500"]).command,
+            Command::Simulate { books: 500 }
+        ));
     }
 }
@@ -0,0 +1,176 @@
+//! Mark and index price of the traded perpetual, [`[mark]`](crate::config::MarkConfig). Perps
+//! settle against the mark, the exchange's smoothed fair price, not the last trade or top of book,
+//! so with it configured take profit and stop loss are judged, and unrealized PnL reported,
+//! against the mark rather than the bid. Exits still sell at the bid. A mark older than
+//! `max_age_secs` is no better than a guess, and the bid is used again until a fresh one comes in.
+//!
+//! The feed streams no mark or index price, so with the `mark-price` feature the poller fetches
+//! them like [funding](crate::funding).
+
+use chrono::DateTime;
+use chrono::TimeDelta;
+use chrono::Utc;
+use serde_json::Value;
+use tracing::info;
+
+use crate::config::MarkConfig;
+#[cfg(feature = "mark-price")]
+use crate::poll;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MarkPrice {
+    pub mark: f64,
+    /// The spot index the mark is pegged to, when the exchange says.
+    pub index: Option<f64>,
+}
+
+impl MarkPrice {
+    /// How far the mark is above the index, in bps of the index.
+    pub fn premium_bps(&self) -> Option<f64> {
+        let index = self.index?;
+        (index > 0.0).then(|| (self.mark - index) / index * 10_000.0)
+    }
+}
+
+/// A positive price at `key` of `body`, at the top level or under `markets`, as a string or a
+/// number.
+fn price(body: &Value, key: &str) -> Option<f64> {
+    let price = body.get(key).or_else(|| body.get("markets")?.get(key))?;
+    let price = match price {
+        Value::String(price) => price.parse().ok()?,
+        price => price.as_f64()?,
+    };
+    (price.is_finite() && price > 0.0).then_some(price)
+}
+
+/// Parse an Aevo style instrument response, with `mark_price` and optionally `index_price`.
+pub fn parse(body: &str) -> Option<MarkPrice> {
+    let body: Value = serde_json::from_str(body).ok()?;
+    Some(MarkPrice {
+        mark: price(&body, "mark_price")?,
+        index: price(&body, "index_price"),
+    })
+}
+
+#[derive(Debug)]
+pub struct MarkPrices {
+    config: MarkConfig,
+    latest: Option<(DateTime<Utc>, MarkPrice)>,
+    stale: bool,
+}
+
+impl MarkPrices {
+    pub fn new(config: &MarkConfig) -> Self {
+        Self {
+            config: config.clone(),
+            latest: None,
+            stale: false,
+        }
+    }
+
+    /// Take `price` as polled at `time`.
+    pub fn set(&mut self, time: DateTime<Utc>, price: MarkPrice) {
+        self.latest = Some((time, price));
+    }
+
+    pub fn latest(&self) -> Option<MarkPrice> {
+        self.latest.map(|(_, price)| price)
+    }
+
+    /// The mark to judge positions against at `now`, unless none is fresh enough, logging
+    /// whenever it goes stale or fresh again.
+    pub fn update(&mut self, now: DateTime<Utc>) -> Option<f64> {
+        let (time, price) = self.latest?;
+        let stale = now - time > TimeDelta::seconds(self.config.max_age_secs as i64);
+        if stale != self.stale {
+            if stale {
+                info!(
+                    "Mark price over {}s old, judging positions at the bid",
+                    self.config.max_age_secs
+                );
+            } else {
+                info!("Mark price fresh again");
+            }
+            self.stale = stale;
+        }
+        (!stale).then_some(price.mark)
+    }
+}
+
+/// Polls the traded perpetual's mark and index price in the background.
+#[cfg(feature = "mark-price")]
+#[derive(Debug)]
+pub struct MarkPoller {
+    receiver: tokio::sync::watch::Receiver<Option<(DateTime<Utc>, MarkPrice)>>,
+}
+
+#[cfg(feature = "mark-price")]
+impl MarkPoller {
+    pub fn spawn(config: &MarkConfig) -> Self {
+        let receiver = poll::spawn(
+            config.url.clone(),
+            config.poll_secs,
+            "mark price",
+            |body, _, received| parse(body).map(|price| (received, price)),
+        );
+        Self { receiver }
+    }
+
+    /// The mark and index price polled since the last call, if any, with when it was.
+    pub fn latest(&mut self) -> Option<(DateTime<Utc>, MarkPrice)> {
+        match self.receiver.has_changed() {
+            Ok(true) => *self.receiver.borrow_and_update(),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000 + secs, 0).unwrap()
+    }
+
+    #[test]
+    fn test_parse_aevo_response() {
+        let price = parse(r#"{"mark_price": "67010.5", "index_price": "67000"}"#).unwrap();
+        assert_eq!(price.mark, 67010.5);
+        assert!((price.premium_bps().unwrap() - 10.5 / 67000.0 * 10_000.0).abs() < 1e-9);
+        assert_eq!(
+            parse(r#"{"markets": {"mark_price": 100}}"#),
+            Some(MarkPrice {
+                mark: 100.0,
+                index: None
+            })
+        );
+        assert_eq!(parse(r#"{"mark_price": "0"}"#), None);
+        assert_eq!(parse(r#"{"index_price": "67000"}"#), None);
+    }
+
+    #[test]
+    fn test_stale_mark_falls_back() {
+        let mut marks = MarkPrices::new(&MarkConfig {
+            max_age_secs: 10,
+            ..MarkConfig::default()
+        });
+        assert_eq!(marks.update(at(0)), None);
+        let price = MarkPrice {
+            mark: 100.0,
+            index: Some(99.9),
+        };
+        marks.set(at(0), price);
+        assert_eq!(marks.update(at(10)), Some(100.0));
+        assert_eq!(marks.update(at(11)), None);
+        assert_eq!(marks.latest(), Some(price));
+        marks.set(
+            at(12),
+            MarkPrice {
+                mark: 101.0,
+                ..price
+            },
+        );
+        assert_eq!(marks.update(at(12)), Some(101.0));
+    }
+}
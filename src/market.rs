@@ -0,0 +1,502 @@
+//! The order book subscriptions the bot trades on, the public trades subscribed to for the
+//! [order flow intensity](crate::signals::hawkes), the liquidations for
+//! [cascade detection](crate::signals::liquidation), and per-order books for
+//! [market-by-order stats](crate::mbo) where a venue streams them.
+//!
+//! Streams run on a runtime of their own, on a dedicated thread, so dropping a [`BookFeed`]
+//! tears down every connection and task it opened rather than leaving them retrying in the
+//! background, and a fresh feed can be subscribed in its place.
+//!
+//! Updates are [conflated](crate::conflate) per subscription, so however far behind the bot falls,
+//! after a pause or a burst of updates, the next book it gets is each subscription's latest.
+
+use barter_data::error::DataError;
+use barter_data::event::MarketEvent;
+use barter_data::exchange::aevo::Aevo;
+use barter_data::exchange::binance::futures::BinanceFuturesUsd;
+use barter_data::exchange::Connector;
+use barter_data::exchange::ExchangeId;
+use barter_data::exchange::StreamSelector;
+use barter_data::streams::Streams;
+use barter_data::subscription::book::OrderBook;
+use barter_data::subscription::book::OrderBooksL2;
+use barter_data::subscription::book::OrderBooksL3;
+use barter_data::subscription::liquidation::Liquidation;
+use barter_data::subscription::liquidation::Liquidations;
+use barter_data::subscription::trade::PublicTrade;
+use barter_data::subscription::trade::PublicTrades;
+use barter_data::subscription::SubKind;
+use barter_data::subscription::Subscription;
+use barter_data::Identifier;
+use barter_integration::model::instrument::kind::InstrumentKind;
+use barter_integration::model::instrument::Instrument;
+use barter_integration::model::Exchange;
+use tokio::sync::mpsc;
+use tokio::sync::oneshot;
+use tracing::warn;
+
+use crate::affinity;
+use crate::config::Config;
+use crate::config::HedgeInstrument;
+use crate::config::PairsConfig;
+use crate::config::ThreadConfig;
+use crate::config::ThreadsConfig;
+use crate::conflate;
+#[cfg(feature = "ring")]
+use crate::ring;
+use crate::triangular;
+
+// TODO: Add order book streams from other exchanges, then merge them
+/// The Aevo book traded on, as base and quote.
+const AEVO_TRADED: (&str, &str) = ("btc", "usd");
+
+/// The perpetual the strategies trade.
+pub fn traded() -> Instrument {
+    let (base, quote) = AEVO_TRADED;
+    Instrument::from((base, quote, InstrumentKind::Perpetual))
+}
+
+/// The spot book of the traded perpetual's asset.
+pub fn spot() -> Instrument {
+    let (base, quote) = AEVO_TRADED;
+    Instrument::from((base, quote, InstrumentKind::Spot))
+}
+
+/// The perpetual [paired](crate::pairs) with the traded one.
+pub fn paired(pairs: &PairsConfig) -> Instrument {
+    let (_, quote) = AEVO_TRADED;
+    Instrument::from((pairs.pair.as_str(), quote, InstrumentKind::Perpetual))
+}
+
+/// The symbol `instrument` is traded as on a [worker](crate::worker) of its own, such as
+/// `ETH/USD perpetual`. Symbols are `'static` throughout, so it is leaked: name each book once.
+pub fn symbol(instrument: &Instrument) -> &'static str {
+    let symbol = format!(
+        "{}/{} {}",
+        instrument.base.as_ref().to_uppercase(),
+        instrument.quote.as_ref().to_uppercase(),
+        instrument.kind
+    );
+    Box::leak(symbol.into_boxed_str())
+}
+
+/// Every Aevo book to subscribe to for `config`: the traded perpetual, the spot book to price the
+/// [basis](crate::basis) against and to hedge with, when either needs it, the perpetual
+/// [paired](crate::pairs) with it, and the legs of every [triangular](crate::triangular) cycle.
+pub fn books(config: &Config) -> Vec<Instrument> {
+    let mut books = vec![traded()];
+    let spot_hedged = config
+        .hedge
+        .as_ref()
+        .is_some_and(|hedge| hedge.instrument == HedgeInstrument::Spot);
+    if config.basis.is_some() || config.harvest.is_some() || spot_hedged {
+        books.push(spot());
+    }
+    if let Some(pairs) = &config.pairs {
+        books.push(paired(pairs));
+    }
+    if let Some(triangular) = &config.triangular {
+        for leg in triangular::legs(triangular) {
+            if !books.contains(&leg) {
+                books.push(leg);
+            }
+        }
+    }
+    books
+}
+
+/// How a subscription is named in logs and by the [watchdog](crate::watchdog).
+pub fn subscription_name(exchange: &Exchange, instrument: &Instrument) -> String {
+    format!("{exchange} {instrument}")
+}
+
+/// Every subscription a [`BookFeed`] of `books` opens, by [name](subscription_name).
+pub fn subscriptions(books: &[Instrument]) -> Vec<String> {
+    books
+        .iter()
+        .map(|instrument| subscription_name(&Exchange::from(Aevo::ID), instrument))
+        .collect()
+}
+
+#[derive(Debug)]
+pub struct BookFeed {
+    events: conflate::Receiver<MarketEvent<OrderBook>>,
+    // Dropped with the feed, stopping its runtime
+    _shutdown: oneshot::Sender<()>,
+}
+
+impl BookFeed {
+    /// Subscribe to each of the Aevo `books`, reading them on a thread set up as `thread` says,
+    /// returning once the streams are up.
+    pub async fn subscribe(
+        thread: ThreadConfig,
+        books: Vec<Instrument>,
+    ) -> Result<Self, DataError> {
+        let (event_sender, events) = conflate::channel();
+        let shutdown = spawn_streams(thread, Aevo, books, OrderBooksL2, move |event| {
+            let subscription = subscription_name(&event.exchange, &event.instrument);
+            event_sender.send(subscription, event).is_ok()
+        })
+        .await?;
+        Ok(Self {
+            events,
+            _shutdown: shutdown,
+        })
+    }
+
+    /// The latest book update of the subscription waiting longest, or `None` once every stream
+    /// has ended.
+    pub async fn recv(&mut self) -> Option<MarketEvent<OrderBook>> {
+        self.events.recv().await
+    }
+
+    /// Book updates skipped for a later one of the same subscription since subscribing.
+    pub fn skipped(&self) -> u64 {
+        self.events.replaced()
+    }
+}
+
+/// Venues streaming per-order L3 books, which an [`OrderFeed`] subscribes to. Aevo, the one venue
+/// order books are streamed from, isn't among them, so for now no L3 book is subscribed and the
+/// [market-by-order stats](crate::mbo) stay at zero.
+const L3_VENUES: &[ExchangeId] = &[];
+
+/// Whether `exchange` streams per-order L3 books.
+pub fn streams_l3(exchange: ExchangeId) -> bool {
+    L3_VENUES.contains(&exchange)
+}
+
+/// Per-order L3 books, every resting order a level of its own, of a venue that streams them.
+/// Conflated like a [`BookFeed`].
+#[derive(Debug)]
+pub struct OrderFeed {
+    events: conflate::Receiver<MarketEvent<OrderBook>>,
+    // Dropped with the feed, stopping its runtime
+    _shutdown: oneshot::Sender<()>,
+}
+
+impl OrderFeed {
+    /// Subscribe to the L3 books of each of the `venue`'s `instruments`, reading them on a thread
+    /// set up as `thread` says, returning once the streams are up, or `None` without subscribing
+    /// when the venue doesn't [stream them](streams_l3).
+    pub async fn subscribe<Venue>(
+        thread: ThreadConfig,
+        venue: Venue,
+        instruments: Vec<Instrument>,
+    ) -> Result<Option<Self>, DataError>
+    where
+        Venue: StreamSelector<OrderBooksL3> + Ord + Send + Sync + 'static,
+        Subscription<Venue, OrderBooksL3>: Identifier<Venue::Channel> + Identifier<Venue::Market>,
+    {
+        if !streams_l3(Venue::ID) {
+            warn!(
+                "{} streams no L3 books, not subscribing to them",
+                Venue::ID.as_str()
+            );
+            return Ok(None);
+        }
+        let (event_sender, events) = conflate::channel();
+        let shutdown = spawn_streams(thread, venue, instruments, OrderBooksL3, move |event| {
+            let subscription = subscription_name(&event.exchange, &event.instrument);
+            event_sender.send(subscription, event).is_ok()
+        })
+        .await?;
+        Ok(Some(Self {
+            events,
+            _shutdown: shutdown,
+        }))
+    }
+
+    /// The latest L3 book of the subscription waiting longest, or `None` once every stream has
+    /// ended.
+    pub async fn recv(&mut self) -> Option<MarketEvent<OrderBook>> {
+        self.events.recv().await
+    }
+}
+
+/// Order books written straight into a [ring buffer](crate::ring) by the feed thread, in place of
+/// a [`BookFeed`], for a strategy thread to busy-poll. Rather than being conflated per
+/// subscription, the oldest books are overwritten once the ring is full.
+#[cfg(feature = "ring")]
+pub struct RingFeed {
+    books: ring::Consumer<MarketEvent<OrderBook>>,
+    // Dropped with the feed, stopping its runtime
+    _shutdown: oneshot::Sender<()>,
+}
+
+#[cfg(feature = "ring")]
+impl RingFeed {
+    /// Subscribe to each of the Aevo `books` through a ring of at least `capacity` books, reading
+    /// them on a thread set up as `thread` says, returning once the streams are up.
+    pub async fn subscribe(
+        capacity: usize,
+        thread: ThreadConfig,
+        books: Vec<Instrument>,
+    ) -> Result<Self, DataError> {
+        let (mut producer, ring) = ring::channel(capacity);
+        // Books keep coming while the ring is full, overwriting the oldest, counted by the ring
+        let shutdown = spawn_streams(thread, Aevo, books, OrderBooksL2, move |event| {
+            producer.push(event);
+            true
+        })
+        .await?;
+        Ok(Self {
+            books: ring,
+            _shutdown: shutdown,
+        })
+    }
+
+    /// Busy-poll for the next book update, or return `None` once every stream has ended. Unlike
+    /// [`recv_spin`](Self::recv_spin) this yields to the runtime between polls, so the rest of a
+    /// trading loop selecting on it still runs, while keeping its thread spinning.
+    pub async fn recv(&mut self) -> Option<MarketEvent<OrderBook>> {
+        loop {
+            // Checked first, so a book written just before closing isn't missed
+            let closed = self.books.is_closed();
+            if let Some(event) = self.books.pop() {
+                return Some(event);
+            }
+            if closed {
+                return None;
+            }
+            std::hint::spin_loop();
+            tokio::task::yield_now().await;
+        }
+    }
+
+    /// The oldest book update not yet taken, if any.
+    pub fn try_recv(&mut self) -> Option<MarketEvent<OrderBook>> {
+        self.books.pop()
+    }
+
+    /// Spin until the next book update, or return `None` once every stream has ended.
+    pub fn recv_spin(&mut self) -> Option<MarketEvent<OrderBook>> {
+        self.books.pop_spin()
+    }
+
+    /// Book updates overwritten since subscribing, for arriving while the ring was full.
+    pub fn dropped(&self) -> u64 {
+        self.books.dropped()
+    }
+}
+
+/// Where the trading loop reads its order books from: a [`BookFeed`], or a [`RingFeed`] with
+/// [`ring_capacity`](crate::config::ThreadsConfig::ring_capacity) set.
+pub enum Books {
+    Channel(BookFeed),
+    #[cfg(feature = "ring")]
+    Ring(RingFeed),
+}
+
+impl Books {
+    /// Subscribe to each of the Aevo `books` the way `threads` says, returning once the streams
+    /// are up.
+    pub async fn subscribe(
+        threads: &ThreadsConfig,
+        books: Vec<Instrument>,
+    ) -> Result<Self, DataError> {
+        #[cfg(feature = "ring")]
+        if let Some(capacity) = threads.ring_capacity {
+            return Ok(Self::Ring(
+                RingFeed::subscribe(capacity, threads.feed, books).await?,
+            ));
+        }
+        Ok(Self::Channel(
+            BookFeed::subscribe(threads.feed, books).await?,
+        ))
+    }
+
+    /// The next book update, or `None` once every stream has ended.
+    pub async fn recv(&mut self) -> Option<MarketEvent<OrderBook>> {
+        match self {
+            Self::Channel(feed) => feed.recv().await,
+            #[cfg(feature = "ring")]
+            Self::Ring(feed) => feed.recv().await,
+        }
+    }
+
+    /// Book updates skipped for a later one since subscribing, conflated or overwritten.
+    pub fn skipped(&self) -> u64 {
+        match self {
+            Self::Channel(feed) => feed.skipped(),
+            #[cfg(feature = "ring")]
+            Self::Ring(feed) => feed.dropped(),
+        }
+    }
+}
+
+/// Every public trade of the traded perpetual, in the order they arrive. Unlike books, trades
+/// are never conflated: each one counts.
+#[derive(Debug)]
+pub struct TradeFeed {
+    trades: mpsc::UnboundedReceiver<MarketEvent<PublicTrade>>,
+    // Dropped with the feed, stopping its runtime
+    _shutdown: oneshot::Sender<()>,
+}
+
+impl TradeFeed {
+    /// Subscribe to the traded perpetual's trades, reading them on a thread set up as `thread`
+    /// says, returning once the stream is up.
+    pub async fn subscribe(thread: ThreadConfig) -> Result<Self, DataError> {
+        let (sender, trades) = mpsc::unbounded_channel();
+        let shutdown = spawn_streams(thread, Aevo, vec![traded()], PublicTrades, move |event| {
+            sender.send(event).is_ok()
+        })
+        .await?;
+        Ok(Self {
+            trades,
+            _shutdown: shutdown,
+        })
+    }
+
+    /// The next trade, or `None` once the stream has ended.
+    pub async fn recv(&mut self) -> Option<MarketEvent<PublicTrade>> {
+        self.trades.recv().await
+    }
+}
+
+/// The next trade from `feed`, if subscribed. Never resolves without a feed, nor once its stream
+/// has ended, dropping it with a warning.
+pub async fn next_trade(feed: &mut Option<TradeFeed>) -> MarketEvent<PublicTrade> {
+    if let Some(trades) = feed {
+        if let Some(trade) = trades.recv().await {
+            return trade;
+        }
+        warn!("Trade stream ended, no more order flow is counted");
+        *feed = None;
+    }
+    std::future::pending().await
+}
+
+/// The next L3 book from `feed`, if subscribed. Never resolves without a feed, nor once its
+/// streams have ended, dropping it with a warning.
+pub async fn next_order_book(feed: &mut Option<OrderFeed>) -> MarketEvent<OrderBook> {
+    if let Some(orders) = feed {
+        if let Some(book) = orders.recv().await {
+            return book;
+        }
+        warn!("L3 book stream ended, market-by-order stats are no longer updated");
+        *feed = None;
+    }
+    std::future::pending().await
+}
+
+/// Every forced liquidation on Binance's USD-margined perpetual of the traded asset. Aevo streams
+/// none, and liquidations on the largest venue move every other's price as well.
+#[derive(Debug)]
+pub struct LiquidationFeed {
+    liquidations: mpsc::UnboundedReceiver<MarketEvent<Liquidation>>,
+    // Dropped with the feed, stopping its runtime
+    _shutdown: oneshot::Sender<()>,
+}
+
+impl LiquidationFeed {
+    /// Subscribe to the liquidations, reading them on a thread set up as `thread` says,
+    /// returning once the stream is up.
+    pub async fn subscribe(thread: ThreadConfig) -> Result<Self, DataError> {
+        let (base, _) = AEVO_TRADED;
+        let instrument = Instrument::from((base, "usdt", InstrumentKind::Perpetual));
+        let (sender, liquidations) = mpsc::unbounded_channel();
+        let shutdown = spawn_streams(
+            thread,
+            BinanceFuturesUsd::default(),
+            vec![instrument],
+            Liquidations,
+            move |event| sender.send(event).is_ok(),
+        )
+        .await?;
+        Ok(Self {
+            liquidations,
+            _shutdown: shutdown,
+        })
+    }
+
+    /// The next liquidation, or `None` once the stream has ended.
+    pub async fn recv(&mut self) -> Option<MarketEvent<Liquidation>> {
+        self.liquidations.recv().await
+    }
+}
+
+/// The next liquidation from `feed`, if subscribed. Never resolves without a feed, nor once its
+/// stream has ended, dropping it with a warning.
+pub async fn next_liquidation(feed: &mut Option<LiquidationFeed>) -> MarketEvent<Liquidation> {
+    if let Some(liquidations) = feed {
+        if let Some(liquidation) = liquidations.recv().await {
+            return liquidation;
+        }
+        warn!("Liquidation stream ended, no more cascades are detected");
+        *feed = None;
+    }
+    std::future::pending().await
+}
+
+/// Open a `kind` stream of each of the `venue`'s `instruments` on a thread of its own, pinned and
+/// prioritised as `thread` says, handing each update to `forward` until it returns `false`.
+/// Returns once the streams are up, with the sender that stops them when dropped.
+async fn spawn_streams<Venue, Kind, F>(
+    thread: ThreadConfig,
+    venue: Venue,
+    instruments: Vec<Instrument>,
+    kind: Kind,
+    mut forward: F,
+) -> Result<oneshot::Sender<()>, DataError>
+where
+    Venue: StreamSelector<Kind> + Ord + Send + Sync + 'static,
+    Kind: SubKind + Copy + Ord + Send + Sync + 'static,
+    Kind::Event: Send,
+    Subscription<Venue, Kind>: Identifier<Venue::Channel> + Identifier<Venue::Market>,
+    F: FnMut(MarketEvent<Kind::Event>) -> bool + Send + 'static,
+{
+    let (ready_sender, ready) = oneshot::channel();
+    let (shutdown, shutdown_receiver) = oneshot::channel::<()>();
+
+    std::thread::Builder::new()
+        .name("book-feed".to_owned())
+        .spawn(move || {
+            if let Err(error) = affinity::apply("feed", &thread) {
+                warn!("{}", error);
+            }
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .expect("failed to start order book runtime");
+            runtime.block_on(async move {
+                let streams = Streams::<Kind>::builder()
+                    .subscribe(
+                        instruments
+                            .into_iter()
+                            .map(|instrument| (venue.clone(), instrument, kind)),
+                    )
+                    .init()
+                    .await;
+                let mut joined = match streams {
+                    Ok(streams) => {
+                        let _ = ready_sender.send(Ok(()));
+                        streams.join().await
+                    }
+                    Err(error) => {
+                        let _ = ready_sender.send(Err(error));
+                        return;
+                    }
+                };
+                let forwarding = async {
+                    while let Some(event) = joined.recv().await {
+                        if !forward(event) {
+                            break;
+                        }
+                    }
+                };
+                tokio::select! {
+                    _ = forwarding => {}
+                    _ = shutdown_receiver => {}
+                }
+            });
+            // Dropping the runtime cancels every stream task and closes its connection
+        })
+        .expect("failed to spawn order book thread");
+
+    ready
+        .await
+        .expect("order book thread stopped before subscribing")?;
+    Ok(shutdown)
+}
@@ -0,0 +1,97 @@
+//! Market-by-order statistics of an L3 book. An L2 book aggregates each price into one level, so
+//! a level of 10 could be one whale or a hundred small traders. An L3 book lists every resting
+//! order on its own, several at a price when they queue there, which tells apart what L2 can't:
+//!
+//! - The [`order_imbalance`](crate::features::Features::order_imbalance), of the number of bids
+//!   against asks rather than their size, from -1 with only asks to 1 with only bids. Size
+//!   imbalance led by a few large orders is easily pulled, one spread across many isn't.
+//! - The [`avg_order_size`](crate::features::Features::avg_order_size) of every resting order.
+//!
+//! Only venues [streaming](crate::market::streams_l3) per-order books are subscribed to through
+//! an [`OrderFeed`](crate::market::OrderFeed), and Aevo streams none, so on its L2 books both
+//! features stay at zero.
+
+use barter_data::subscription::book::OrderBook;
+use barter_data::subscription::book::OrderBookSide;
+
+/// Counts and sizes of the orders resting on each side of an L3 book.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct OrderStats {
+    pub bid_orders: usize,
+    pub ask_orders: usize,
+    pub bid_size: f64,
+    pub ask_size: f64,
+}
+
+/// The number and total size of the orders on `side`, skipping any left with nothing resting.
+fn orders(side: &OrderBookSide) -> (usize, f64) {
+    side.levels
+        .iter()
+        .filter(|order| order.amount > 0.0)
+        .fold((0, 0.0), |(count, size), order| {
+            (count + 1, size + order.amount)
+        })
+}
+
+impl OrderStats {
+    /// The stats of `book`, read as L3: every level one order.
+    pub fn from_order_book(book: &OrderBook) -> Self {
+        let (bid_orders, bid_size) = orders(&book.bids);
+        let (ask_orders, ask_size) = orders(&book.asks);
+        Self {
+            bid_orders,
+            ask_orders,
+            bid_size,
+            ask_size,
+        }
+    }
+
+    /// Imbalance of the number of bids against asks, from -1 to 1, or zero on an empty book.
+    pub fn order_imbalance(&self) -> f64 {
+        let total = self.bid_orders + self.ask_orders;
+        if total == 0 {
+            return 0.0;
+        }
+        (self.bid_orders as f64 - self.ask_orders as f64) / total as f64
+    }
+
+    /// The mean size of every resting order, or zero on an empty book.
+    pub fn avg_order_size(&self) -> f64 {
+        let total = self.bid_orders + self.ask_orders;
+        if total == 0 {
+            return 0.0;
+        }
+        (self.bid_size + self.ask_size) / total as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use barter_integration::model::Side;
+    use chrono::DateTime;
+
+    use super::*;
+    use crate::features::Features;
+
+    #[test]
+    fn test_counts_orders_not_levels() {
+        // Two bids queue at 100, against a single large ask
+        let book = OrderBook {
+            last_update_time: DateTime::from_timestamp_millis(0).unwrap(),
+            bids: OrderBookSide::new(Side::Buy, [(100.0, 1.0), (100.0, 2.0), (99.0, 3.0)]),
+            asks: OrderBookSide::new(Side::Sell, [(101.0, 10.0), (102.0, 0.0)]),
+        };
+        let stats = OrderStats::from_order_book(&book);
+        assert_eq!((stats.bid_orders, stats.ask_orders), (3, 1));
+        assert_eq!(stats.order_imbalance(), 0.5);
+        assert_eq!(stats.avg_order_size(), 4.0);
+        let features = Features::default().with_order_stats(&stats);
+        assert_eq!(
+            (features.order_imbalance, features.avg_order_size),
+            (0.5, 4.0)
+        );
+
+        assert_eq!(OrderStats::default().order_imbalance(), 0.0);
+        assert_eq!(OrderStats::default().avg_order_size(), 0.0);
+    }
+}
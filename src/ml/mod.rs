@@ -0,0 +1,9 @@
+/// Strategies backed by ONNX models trained offline.
+#[cfg(feature = "onnx")]
+pub mod onnx;
+
+/// Online logistic model of the probability of an up-move.
+pub mod prediction;
+
+/// Online recursive least squares model of the next-tick return.
+pub mod rls;
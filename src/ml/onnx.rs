@@ -0,0 +1,287 @@
+//! Entry signals from a user-supplied ONNX model, run in-process with
+//! [tract](https://github.com/sonos/tract).
+//!
+//! On every book update the model is fed the last `window` feature vectors, oldest first, as a
+//! `float32` tensor of shape `[1, window, 19]` with the features in the order
+//!
+//! `bid, ask, mid_price, microprice, spread, voi, oir, mpb, flow, liquidations, order_imbalance,
+//! avg_order_size, persistent_oir, oir_top1, oir_top5, oir_top20, oir_10bps, oir_50bps,
+//! volatility`
+//!
+//! The first element of the first output is read as the predicted short-horizon return: above
+//! `threshold` buys, below `-threshold` sells any open positions, anything in between holds. The
+//! strategy holds until `window` updates have been seen.
+//!
+//! ```toml
+//! [[strategy.members]]
+//! kind = "onnx"
+//! path = "models/imbalance.onnx"
+//! window = 16
+//! threshold = 0.0002
+//! ```
+
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use tracing::warn;
+use tract_onnx::prelude::tract_ndarray::Array3;
+use tract_onnx::prelude::tvec;
+use tract_onnx::prelude::DatumType;
+use tract_onnx::prelude::Framework;
+use tract_onnx::prelude::InferenceFact;
+use tract_onnx::prelude::InferenceModelExt;
+use tract_onnx::prelude::Tensor;
+use tract_onnx::prelude::TractResult;
+use tract_onnx::prelude::TypedModel;
+use tract_onnx::prelude::TypedRunnableModel;
+
+use crate::features::Features;
+use crate::features::FEATURE_COUNT;
+use crate::strategy::Signal;
+use crate::strategy::Strategy;
+use crate::TradingState;
+
+#[derive(Debug, thiserror::Error)]
+pub enum OnnxError {
+    #[error("failed to load onnx model {0}: {1}")]
+    Load(PathBuf, String),
+}
+
+/// Config for a strategy member backed by an ONNX model.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OnnxParams {
+    pub path: PathBuf,
+    /// Number of most recent updates fed to the model.
+    #[serde(default = "default_window")]
+    pub window: usize,
+    /// Predicted return the model must exceed, in either direction, to trade.
+    pub threshold: f64,
+}
+
+fn default_window() -> usize {
+    1
+}
+
+pub struct OnnxStrategy {
+    name: String,
+    params: OnnxParams,
+    model: TypedRunnableModel<TypedModel>,
+    history: VecDeque<[f32; FEATURE_COUNT]>,
+}
+
+impl std::fmt::Debug for OnnxStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OnnxStrategy")
+            .field("name", &self.name)
+            .field("params", &self.params)
+            .finish_non_exhaustive()
+    }
+}
+
+impl OnnxStrategy {
+    pub fn new(params: &OnnxParams) -> Result<Self, OnnxError> {
+        if params.window == 0 {
+            return Err(OnnxError::Load(
+                params.path.clone(),
+                "window must be at least 1".to_owned(),
+            ));
+        }
+
+        let model = Self::load(params)
+            .map_err(|error| OnnxError::Load(params.path.clone(), error.to_string()))?;
+        let name = params
+            .path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "onnx".to_owned());
+
+        Ok(Self {
+            name,
+            params: params.clone(),
+            model,
+            history: VecDeque::with_capacity(params.window),
+        })
+    }
+
+    fn load(params: &OnnxParams) -> TractResult<TypedRunnableModel<TypedModel>> {
+        tract_onnx::onnx()
+            .model_for_path(&params.path)?
+            .with_input_fact(
+                0,
+                InferenceFact::dt_shape(DatumType::F32, [1, params.window, FEATURE_COUNT]),
+            )?
+            .into_optimized()?
+            .into_runnable()
+    }
+
+    fn predict(&self) -> TractResult<Option<f32>> {
+        let input = Array3::from_shape_fn(
+            (1, self.params.window, FEATURE_COUNT),
+            |(_, tick, feature)| self.history[tick][feature],
+        );
+        let outputs = self.model.run(tvec!(Tensor::from(input).into()))?;
+        Ok(outputs[0].as_slice::<f32>()?.first().copied())
+    }
+}
+
+impl Strategy for OnnxStrategy {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn evaluate(&mut self, features: &Features, state: &TradingState) -> Signal {
+        if self.history.len() == self.params.window {
+            self.history.pop_front();
+        }
+        self.history
+            .push_back(features.to_array().map(|feature| feature as f32));
+        if self.history.len() < self.params.window {
+            return Signal::Hold;
+        }
+
+        // A failing model holds rather than taking the feed down with it
+        let prediction = match self.predict() {
+            Ok(Some(prediction)) => f64::from(prediction),
+            Ok(None) => {
+                warn!("Onnx model {} produced an empty output", self.name);
+                return Signal::Hold;
+            }
+            Err(error) => {
+                warn!("Onnx model {} failed: {}", self.name, error);
+                return Signal::Hold;
+            }
+        };
+
+        if prediction > self.params.threshold {
+            Signal::Buy
+        } else if prediction < -self.params.threshold && !state.positions.is_empty() {
+            Signal::Sell
+        } else {
+            Signal::Hold
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use onnx_prost::Message;
+    use std::time::SystemTime;
+    use tract_onnx::pb;
+
+    use super::*;
+    use crate::Lot;
+
+    /// Writes a model that predicts the OIR of the oldest update in the window, and returns its
+    /// params.
+    fn model(name: &str, window: usize) -> OnnxParams {
+        let float = pb::tensor_proto::DataType::Float as i32;
+        let tensor_type = |name: &str| pb::ValueInfoProto {
+            name: name.to_owned(),
+            r#type: Some(pb::TypeProto {
+                value: Some(pb::type_proto::Value::TensorType(pb::type_proto::Tensor {
+                    elem_type: float,
+                    shape: None,
+                })),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let mut weights = vec![0.0; FEATURE_COUNT];
+        weights[6] = 1.0;
+        let model = pb::ModelProto {
+            ir_version: 8,
+            opset_import: vec![pb::OperatorSetIdProto {
+                domain: String::new(),
+                version: 13,
+            }],
+            graph: Some(pb::GraphProto {
+                name: "oir".to_owned(),
+                node: vec![pb::NodeProto {
+                    op_type: "MatMul".to_owned(),
+                    input: vec!["features".to_owned(), "weights".to_owned()],
+                    output: vec!["prediction".to_owned()],
+                    ..Default::default()
+                }],
+                initializer: vec![pb::TensorProto {
+                    name: "weights".to_owned(),
+                    dims: vec![FEATURE_COUNT as i64, 1],
+                    data_type: float,
+                    float_data: weights,
+                    ..Default::default()
+                }],
+                input: vec![tensor_type("features")],
+                output: vec![tensor_type("prediction")],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let path = std::env::temp_dir().join(format!(
+            "fit-{}-{}-{}.onnx",
+            name,
+            std::process::id(),
+            SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        std::fs::write(&path, model.encode_to_vec()).unwrap();
+        OnnxParams {
+            path,
+            window,
+            threshold: 0.1,
+        }
+    }
+
+    fn oir(oir: f64) -> Features {
+        Features {
+            oir,
+            ..Features::default()
+        }
+    }
+
+    #[test]
+    fn test_predictions() {
+        let params = model("predictions", 1);
+        let mut strategy = OnnxStrategy::new(&params).unwrap();
+        let mut state = TradingState::new(1000.0, "BTC/USDT");
+
+        assert_eq!(strategy.evaluate(&oir(0.5), &state), Signal::Buy);
+        assert_eq!(strategy.evaluate(&oir(0.05), &state), Signal::Hold);
+        // Nothing to sell while flat
+        assert_eq!(strategy.evaluate(&oir(-0.5), &state), Signal::Hold);
+
+        state.positions.push(Lot::at(100.0));
+        assert_eq!(strategy.evaluate(&oir(-0.5), &state), Signal::Sell);
+        std::fs::remove_file(params.path).unwrap();
+    }
+
+    #[test]
+    fn test_window() {
+        let params = model("window", 3);
+        let mut strategy = OnnxStrategy::new(&params).unwrap();
+        let state = TradingState::new(1000.0, "BTC/USDT");
+
+        // Holds until the window is full, then trades on the oldest update in it
+        assert_eq!(strategy.evaluate(&oir(0.5), &state), Signal::Hold);
+        assert_eq!(strategy.evaluate(&oir(0.0), &state), Signal::Hold);
+        assert_eq!(strategy.evaluate(&oir(0.0), &state), Signal::Buy);
+        assert_eq!(strategy.evaluate(&oir(0.0), &state), Signal::Hold);
+        std::fs::remove_file(params.path).unwrap();
+    }
+
+    #[test]
+    fn test_missing_model() {
+        let params = OnnxParams {
+            path: PathBuf::from("does-not-exist.onnx"),
+            window: 1,
+            threshold: 0.1,
+        };
+        assert!(matches!(
+            OnnxStrategy::new(&params),
+            Err(OnnxError::Load(..))
+        ));
+    }
+}
@@ -0,0 +1,337 @@
+//! Online logistic model of whether the mid is higher `horizon_secs` from now, from every
+//! feature of the update.
+//!
+//! Each update's features are standardised against exponentially weighted means and variances,
+//! and once `horizon_secs` have passed, labelled by whether the mid has since risen. Every label
+//! takes one stochastic gradient step on the log loss, shrunk by `l2`, so the output is a
+//! probability the model keeps calibrating as the market moves, its Brier score tracked to show
+//! how well it does.
+//!
+//! Once `min_samples` labels are in, a probability of at least `buy` buys and one of at most
+//! `sell` sells an open position. How far past `buy` the probability is sizes the entry: the
+//! sleeve buys up to `max_lots` lots at a certainty of one, fewer on a weaker signal, and holds
+//! once it has as many as the confidence calls for.
+//!
+//! ```toml
+//! [[strategy.members]]
+//! kind = "prediction"
+//! horizon_secs = 10
+//! buy = 0.6
+//! sell = 0.4
+//! max_lots = 3
+//! ```
+
+use chrono::DateTime;
+use chrono::TimeDelta;
+use chrono::Utc;
+use serde::Deserialize;
+use std::collections::VecDeque;
+
+use crate::features::Features;
+use crate::strategy::Signal;
+use crate::strategy::Strategy;
+use crate::TradingState;
+
+/// Inputs per observation: an intercept, the microprice's offset from the mid, then the spread,
+/// VOI, OIR, MPB and flow. The bid, ask and mid only enter through the offset, as price levels
+/// say nothing about direction.
+const DIM: usize = 7;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PredictionParams {
+    /// How far ahead the move is predicted.
+    pub horizon_secs: u64,
+    /// Step size of each gradient update.
+    pub learning_rate: f64,
+    /// Shrinkage of the weights toward zero on each update.
+    pub l2: f64,
+    /// Weight kept by the standardising means and variances on each update, in `(0, 1)`.
+    pub decay: f64,
+    /// Probability of an up-move at or above which to buy.
+    pub buy: f64,
+    /// Probability of an up-move at or below which to sell.
+    pub sell: f64,
+    /// Lots held at full confidence.
+    pub max_lots: usize,
+    /// Labels learnt from before the model is trusted to trade.
+    pub min_samples: usize,
+}
+
+impl Default for PredictionParams {
+    fn default() -> Self {
+        Self {
+            horizon_secs: 5,
+            learning_rate: 0.01,
+            l2: 0.0001,
+            decay: 0.999,
+            buy: 0.6,
+            sell: 0.4,
+            max_lots: 3,
+            min_samples: 200,
+        }
+    }
+}
+
+fn inputs(features: &Features) -> [f64; DIM - 1] {
+    let offset = if features.mid_price > 0.0 {
+        (features.microprice - features.mid_price) / features.mid_price
+    } else {
+        0.0
+    };
+    [
+        offset,
+        features.spread,
+        features.voi,
+        features.oir,
+        features.mpb,
+        features.flow,
+    ]
+}
+
+fn sigmoid(z: f64) -> f64 {
+    1.0 / (1.0 + (-z).exp())
+}
+
+/// Exponentially weighted mean and variance of each input.
+#[derive(Debug, Clone)]
+struct Standardiser {
+    decay: f64,
+    means: [f64; DIM - 1],
+    variances: [f64; DIM - 1],
+    seen: bool,
+}
+
+impl Standardiser {
+    fn new(decay: f64) -> Self {
+        Self {
+            decay,
+            means: [0.0; DIM - 1],
+            variances: [0.0; DIM - 1],
+            seen: false,
+        }
+    }
+
+    /// Take in `inputs`, returning them standardised with an intercept in front.
+    fn standardise(&mut self, inputs: [f64; DIM - 1]) -> [f64; DIM] {
+        if !self.seen {
+            self.means = inputs;
+            self.seen = true;
+        }
+        let mut x = [1.0; DIM];
+        for (i, value) in inputs.into_iter().enumerate() {
+            let deviation = value - self.means[i];
+            self.means[i] += (1.0 - self.decay) * deviation;
+            self.variances[i] =
+                self.decay * (self.variances[i] + (1.0 - self.decay) * deviation * deviation);
+            let std = self.variances[i].sqrt();
+            x[i + 1] = if std > 0.0 { deviation / std } else { 0.0 };
+        }
+        x
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PredictionStrategy {
+    params: PredictionParams,
+    weights: [f64; DIM],
+    standardiser: Standardiser,
+    /// When the update about to be evaluated arrived.
+    now: Option<DateTime<Utc>>,
+    /// Inputs and mid price of each update not yet `horizon_secs` old, oldest first.
+    pending: VecDeque<(DateTime<Utc>, [f64; DIM], f64)>,
+    samples: usize,
+    /// Sum of the squared error of every probability labelled so far.
+    squared_error: f64,
+}
+
+impl PredictionStrategy {
+    pub fn new(params: PredictionParams) -> Self {
+        Self {
+            params,
+            weights: [0.0; DIM],
+            standardiser: Standardiser::new(params.decay),
+            now: None,
+            pending: VecDeque::new(),
+            samples: 0,
+            squared_error: 0.0,
+        }
+    }
+
+    /// The probability of standardised inputs `x` being followed by an up-move.
+    fn probability(&self, x: &[f64; DIM]) -> f64 {
+        sigmoid(self.weights.iter().zip(x).map(|(w, x)| w * x).sum())
+    }
+
+    /// The mean squared error of the probabilities labelled so far, a quarter for a coin toss.
+    pub fn brier(&self) -> Option<f64> {
+        (self.samples > 0).then(|| self.squared_error / self.samples as f64)
+    }
+
+    pub fn samples(&self) -> usize {
+        self.samples
+    }
+
+    /// Learn from inputs `x` having been followed by an up-move, or not.
+    fn learn(&mut self, x: &[f64; DIM], up: bool) {
+        let label = if up { 1.0 } else { 0.0 };
+        let error = self.probability(x) - label;
+        for (i, weight) in self.weights.iter_mut().enumerate() {
+            // The intercept isn't shrunk, only the weights of the inputs
+            let shrink = if i == 0 {
+                0.0
+            } else {
+                self.params.l2 * *weight
+            };
+            *weight -= self.params.learning_rate * (error * x[i] + shrink);
+        }
+        self.samples += 1;
+        self.squared_error += error * error;
+    }
+
+    /// Label every pending update at least `horizon_secs` older than `now` against `mid_price`.
+    fn label(&mut self, now: DateTime<Utc>, mid_price: f64) {
+        let horizon = TimeDelta::seconds(self.params.horizon_secs as i64);
+        while let Some(&(time, x, then)) = self.pending.front() {
+            if now - time < horizon {
+                break;
+            }
+            self.pending.pop_front();
+            self.learn(&x, mid_price > then);
+        }
+    }
+
+    /// How many lots a probability of `probability` calls for holding.
+    fn lots(&self, probability: f64) -> usize {
+        let max_lots = self.params.max_lots.max(1);
+        let room = 1.0 - self.params.buy;
+        if room <= 0.0 {
+            return max_lots;
+        }
+        let confidence = ((probability - self.params.buy) / room).clamp(0.0, 1.0);
+        ((confidence * max_lots as f64).ceil() as usize).clamp(1, max_lots)
+    }
+}
+
+impl Strategy for PredictionStrategy {
+    fn name(&self) -> &str {
+        "prediction"
+    }
+
+    fn evaluate(&mut self, features: &Features, state: &TradingState) -> Signal {
+        let Some(now) = self.now.take() else {
+            return Signal::Hold;
+        };
+        let mid_price = features.mid_price;
+        if !mid_price.is_finite() || mid_price <= 0.0 {
+            return Signal::Hold;
+        }
+        self.label(now, mid_price);
+        let x = self.standardiser.standardise(inputs(features));
+        self.pending.push_back((now, x, mid_price));
+
+        if self.samples < self.params.min_samples {
+            return Signal::Hold;
+        }
+        let probability = self.probability(&x);
+        if probability >= self.params.buy && state.positions.len() < self.lots(probability) {
+            Signal::Buy
+        } else if probability <= self.params.sell && !state.positions.is_empty() {
+            Signal::Sell
+        } else {
+            Signal::Hold
+        }
+    }
+
+    fn on_time(&mut self, now: DateTime<Utc>) {
+        self.now = Some(now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+    use crate::Lot;
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000 + secs, 0).unwrap()
+    }
+
+    fn features(mid_price: f64, oir: f64) -> Features {
+        Features {
+            mid_price,
+            microprice: mid_price,
+            oir,
+            ..Features::default()
+        }
+    }
+
+    /// A second of updates at a time, the mid moving the way the OIR leans a second later.
+    fn train(strategy: &mut PredictionStrategy, state: &TradingState, secs: i64) -> f64 {
+        let mut rng = Rng::new(3);
+        let mut mid_price = 100.0;
+        for second in 0..secs {
+            let oir = rng.centred();
+            strategy.on_time(at(second));
+            strategy.evaluate(&features(mid_price, oir), state);
+            mid_price += if oir > 0.0 { 0.5 } else { -0.5 };
+        }
+        mid_price
+    }
+
+    fn strategy() -> PredictionStrategy {
+        PredictionStrategy::new(PredictionParams {
+            horizon_secs: 1,
+            learning_rate: 0.05,
+            ..PredictionParams::default()
+        })
+    }
+
+    #[test]
+    fn test_learns_calibrated_direction() {
+        let mut strategy = strategy();
+        let state = TradingState::new(1000.0, "BTC/USDT");
+        // Nothing is predicted without the time of the update
+        assert_eq!(
+            strategy.evaluate(&features(100.0, 0.5), &state),
+            Signal::Hold
+        );
+        train(&mut strategy, &state, 2000);
+        assert_eq!(strategy.samples(), 1999);
+        // Far better than the quarter a coin toss scores
+        assert!(strategy.brier().unwrap() < 0.1);
+
+        let x = strategy
+            .standardiser
+            .clone()
+            .standardise(inputs(&features(100.0, 0.8)));
+        assert!(strategy.probability(&x) > 0.9);
+        let x = strategy
+            .standardiser
+            .clone()
+            .standardise(inputs(&features(100.0, -0.8)));
+        assert!(strategy.probability(&x) < 0.1);
+    }
+
+    #[test]
+    fn test_confidence_sizes_entries() {
+        let mut strategy = strategy();
+        let mut state = TradingState::new(1000.0, "BTC/USDT");
+        let mid_price = train(&mut strategy, &state, 2000);
+        assert_eq!(
+            (strategy.lots(0.6), strategy.lots(0.75), strategy.lots(1.0)),
+            (1, 2, 3)
+        );
+
+        let evaluate = |strategy: &mut PredictionStrategy, state: &TradingState, oir| {
+            strategy.on_time(at(2000));
+            strategy.evaluate(&features(mid_price, oir), state)
+        };
+        assert_eq!(evaluate(&mut strategy, &state, 0.9), Signal::Buy);
+        state.positions.extend([Lot::at(mid_price); 3]);
+        assert_eq!(evaluate(&mut strategy, &state, 0.9), Signal::Hold);
+        assert_eq!(evaluate(&mut strategy, &state, 0.0), Signal::Hold);
+        assert_eq!(evaluate(&mut strategy, &state, -0.9), Signal::Sell);
+    }
+}
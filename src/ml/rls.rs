@@ -0,0 +1,238 @@
+//! Recursive least squares fit of the next-tick mid return against the imbalance features,
+//! updated online on every book update.
+//!
+//! The model regresses `(mid[t + 1] - mid[t]) / mid[t]` on `[1, voi, oir, mpb]` at `t`, weighting
+//! past observations down by `forgetting` each update so the coefficients track the current
+//! regime. Once `min_samples` returns have been observed, a prediction above `threshold` buys and
+//! one below `-threshold` sells any open positions.
+//!
+//! ```toml
+//! [[strategy.members]]
+//! kind = "rls"
+//! forgetting = 0.995
+//! threshold = 0.0001
+//! ```
+
+use serde::Deserialize;
+
+use crate::features::Features;
+use crate::strategy::Signal;
+use crate::strategy::Strategy;
+use crate::TradingState;
+
+/// Regressors per observation: an intercept plus VOI, OIR and MPB.
+const DIM: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RlsParams {
+    /// Weight kept by past observations on each update, in `(0, 1]`.
+    pub forgetting: f64,
+    /// Initial variance of the coefficients; larger values adapt faster at the start.
+    pub delta: f64,
+    /// Predicted return the model must exceed, in either direction, to trade.
+    pub threshold: f64,
+    /// Returns observed before the model is trusted to trade.
+    pub min_samples: usize,
+}
+
+impl Default for RlsParams {
+    fn default() -> Self {
+        Self {
+            forgetting: 0.995,
+            delta: 1000.0,
+            threshold: 0.0001,
+            min_samples: 100,
+        }
+    }
+}
+
+fn regressors(features: &Features) -> [f64; DIM] {
+    [1.0, features.voi, features.oir, features.mpb]
+}
+
+fn dot(a: &[f64; DIM], b: &[f64; DIM]) -> f64 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+#[derive(Debug, Clone)]
+pub struct RlsStrategy {
+    params: RlsParams,
+    coefficients: [f64; DIM],
+    /// Inverse correlation matrix of the regressors.
+    covariance: [[f64; DIM]; DIM],
+    /// Regressors and mid price of the previous update, awaiting the return they predict.
+    previous: Option<([f64; DIM], f64)>,
+    samples: usize,
+}
+
+impl RlsStrategy {
+    pub fn new(params: RlsParams) -> Self {
+        let mut covariance = [[0.0; DIM]; DIM];
+        for (i, row) in covariance.iter_mut().enumerate() {
+            row[i] = params.delta;
+        }
+
+        Self {
+            params,
+            coefficients: [0.0; DIM],
+            covariance,
+            previous: None,
+            samples: 0,
+        }
+    }
+
+    /// Current coefficients for `[intercept, voi, oir, mpb]`.
+    pub fn coefficients(&self) -> [f64; DIM] {
+        self.coefficients
+    }
+
+    pub fn predict(&self, features: &Features) -> f64 {
+        dot(&self.coefficients, &regressors(features))
+    }
+
+    fn update(&mut self, x: &[f64; DIM], y: f64) {
+        let lambda = self.params.forgetting;
+
+        let mut px = [0.0; DIM];
+        for (i, row) in self.covariance.iter().enumerate() {
+            px[i] = dot(row, x);
+        }
+        let denominator = lambda + dot(x, &px);
+        let gain = px.map(|value| value / denominator);
+
+        let error = y - dot(&self.coefficients, x);
+        for (coefficient, gain) in self.coefficients.iter_mut().zip(&gain) {
+            *coefficient += gain * error;
+        }
+
+        // The covariance is symmetric, so xᵀP is the transpose of Px
+        for (i, row) in self.covariance.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (*value - gain[i] * px[j]) / lambda;
+            }
+        }
+
+        self.samples += 1;
+    }
+}
+
+impl Strategy for RlsStrategy {
+    fn name(&self) -> &str {
+        "rls"
+    }
+
+    fn evaluate(&mut self, features: &Features, state: &TradingState) -> Signal {
+        if let Some((x, mid_price)) = self.previous {
+            if mid_price > 0.0 {
+                self.update(&x, (features.mid_price - mid_price) / mid_price);
+            }
+        }
+        self.previous = Some((regressors(features), features.mid_price));
+
+        if self.samples < self.params.min_samples {
+            return Signal::Hold;
+        }
+
+        let prediction = self.predict(features);
+        if prediction > self.params.threshold {
+            Signal::Buy
+        } else if prediction < -self.params.threshold && !state.positions.is_empty() {
+            Signal::Sell
+        } else {
+            Signal::Hold
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Lot;
+
+    fn features(mid_price: f64, oir: f64) -> Features {
+        Features {
+            mid_price,
+            voi: oir * 10.0,
+            oir,
+            mpb: -oir,
+            ..Features::default()
+        }
+    }
+
+    /// Mid prices that move by `0.001 * oir` after each update.
+    fn train(strategy: &mut RlsStrategy, state: &TradingState, updates: usize) -> f64 {
+        let mut mid_price = 100.0;
+        for tick in 0..updates {
+            let oir = (tick as f64 * 0.7).sin();
+            strategy.evaluate(&features(mid_price, oir), state);
+            mid_price *= 1.0 + 0.001 * oir;
+        }
+        mid_price
+    }
+
+    #[test]
+    fn test_learns_relationship() {
+        let mut strategy = RlsStrategy::new(RlsParams {
+            forgetting: 1.0,
+            ..RlsParams::default()
+        });
+        let state = TradingState::new(1000.0, "BTC/USDT");
+        train(&mut strategy, &state, 500);
+
+        assert!((strategy.predict(&features(100.0, 0.5)) - 0.0005).abs() < 1e-6);
+        assert!((strategy.predict(&features(100.0, -0.5)) + 0.0005).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_signals() {
+        let mut strategy = RlsStrategy::new(RlsParams::default());
+        let mut state = TradingState::new(1000.0, "BTC/USDT");
+        let mid_price = train(&mut strategy, &state, 500);
+
+        assert_eq!(
+            strategy.evaluate(&features(mid_price, 0.5), &state),
+            Signal::Buy
+        );
+        assert_eq!(
+            strategy.evaluate(&features(mid_price, 0.0), &state),
+            Signal::Hold
+        );
+        // Nothing to sell while flat
+        assert_eq!(
+            strategy.evaluate(&features(mid_price, -0.5), &state),
+            Signal::Hold
+        );
+
+        state.positions.push(Lot::at(mid_price));
+        assert_eq!(
+            strategy.evaluate(&features(mid_price, -0.5), &state),
+            Signal::Sell
+        );
+    }
+
+    #[test]
+    fn test_holds_while_warming_up() {
+        let mut strategy = RlsStrategy::new(RlsParams {
+            threshold: 0.0,
+            min_samples: 10,
+            ..RlsParams::default()
+        });
+        let state = TradingState::new(1000.0, "BTC/USDT");
+
+        // The first update has no return to learn from, so ten updates give nine samples
+        let mut mid_price = 100.0;
+        for tick in 0..10 {
+            let oir = (tick as f64 * 0.7).sin();
+            assert_eq!(
+                strategy.evaluate(&features(mid_price, oir), &state),
+                Signal::Hold
+            );
+            mid_price *= 1.0 + 0.001 * oir;
+        }
+        assert_ne!(
+            strategy.evaluate(&features(mid_price, 0.5), &state),
+            Signal::Hold
+        );
+    }
+}
@@ -0,0 +1,180 @@
+//! Open interest of the traded perpetual, [`[open_interest]`](crate::config::OpenInterestConfig),
+//! as a filter on entries. Fresh positions opening behind a move are what carry it on, so a buy is
+//! only confirmed while open interest has risen by at least `min_change` over the last
+//! `window_secs` and the book's OIR leans the same way. A rally on falling open interest is shorts
+//! covering, and entries hold off until it is joined by new longs. Until a window's worth has been
+//! polled there is nothing to confirm with, and entries are left alone. Exits keep running
+//! throughout.
+//!
+//! The exchange streams no open interest the feed can subscribe to, so with the `open-interest`
+//! feature the poller fetches it like [funding](crate::funding).
+
+use chrono::DateTime;
+use chrono::TimeDelta;
+use chrono::Utc;
+use serde_json::Value;
+use std::collections::VecDeque;
+use tracing::info;
+
+use crate::config::OpenInterestConfig;
+use crate::features::Features;
+use crate::hours::Permission;
+#[cfg(feature = "open-interest")]
+use crate::poll;
+
+/// Parse an Aevo style instrument response, with `open_interest` at the top level or under
+/// `markets`, as a string or a number.
+pub fn parse(body: &str) -> Option<f64> {
+    let body: Value = serde_json::from_str(body).ok()?;
+    let open_interest = body
+        .get("open_interest")
+        .or_else(|| body.get("markets")?.get("open_interest"))?;
+    let open_interest = match open_interest {
+        Value::String(open_interest) => open_interest.parse().ok()?,
+        open_interest => open_interest.as_f64()?,
+    };
+    (open_interest.is_finite() && open_interest >= 0.0).then_some(open_interest)
+}
+
+#[derive(Debug)]
+pub struct OpenInterestFilter {
+    config: OpenInterestConfig,
+    /// Open interest as polled, oldest first, back to the last one a window before the latest.
+    samples: VecDeque<(DateTime<Utc>, f64)>,
+    confirmed: bool,
+}
+
+impl OpenInterestFilter {
+    pub fn new(config: &OpenInterestConfig) -> Self {
+        Self {
+            config: config.clone(),
+            samples: VecDeque::new(),
+            confirmed: true,
+        }
+    }
+
+    /// Take `open_interest` as polled at `time`.
+    pub fn set(&mut self, time: DateTime<Utc>, open_interest: f64) {
+        self.samples.push_back((time, open_interest));
+        let start = time - TimeDelta::seconds(self.config.window_secs as i64);
+        while self.samples.get(1).is_some_and(|(time, _)| *time <= start) {
+            self.samples.pop_front();
+        }
+    }
+
+    pub fn latest(&self) -> Option<f64> {
+        self.samples.back().map(|(_, open_interest)| *open_interest)
+    }
+
+    /// How much open interest changed over the window, as a fraction, once a window's worth is in.
+    pub fn change(&self) -> Option<f64> {
+        let (&(first, then), &(last, now)) = (self.samples.front()?, self.samples.back()?);
+        let window = TimeDelta::seconds(self.config.window_secs as i64);
+        (last - first >= window && then > 0.0).then(|| (now - then) / then)
+    }
+
+    /// Judge whether the book of `features` and open interest confirm entries, logging whenever
+    /// that changes.
+    pub fn update(&mut self, features: &Features) -> Permission {
+        let confirmed = match self.change() {
+            Some(change) => change >= self.config.min_change && features.oir > 0.0,
+            None => true,
+        };
+        if confirmed != self.confirmed {
+            match self.change() {
+                Some(change) if !confirmed => info!(
+                    "Open interest {:+.3}% over {}s with OIR {:.2}, holding off entries",
+                    change * 100.0,
+                    self.config.window_secs,
+                    features.oir
+                ),
+                _ => info!("Open interest confirms entries again"),
+            }
+            self.confirmed = confirmed;
+        }
+        Permission {
+            entries: confirmed,
+            flatten: false,
+        }
+    }
+}
+
+/// Polls the traded perpetual's open interest in the background.
+#[cfg(feature = "open-interest")]
+#[derive(Debug)]
+pub struct OpenInterestPoller {
+    receiver: tokio::sync::watch::Receiver<Option<(DateTime<Utc>, f64)>>,
+}
+
+#[cfg(feature = "open-interest")]
+impl OpenInterestPoller {
+    pub fn spawn(config: &OpenInterestConfig) -> Self {
+        let receiver = poll::spawn(
+            config.url.clone(),
+            config.poll_secs,
+            "open interest",
+            |body, _, received| parse(body).map(|open_interest| (received, open_interest)),
+        );
+        Self { receiver }
+    }
+
+    /// The open interest polled since the last call, if any, with when it was.
+    pub fn latest(&mut self) -> Option<(DateTime<Utc>, f64)> {
+        match self.receiver.has_changed() {
+            Ok(true) => *self.receiver.borrow_and_update(),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000 + secs, 0).unwrap()
+    }
+
+    fn book(oir: f64) -> Features {
+        Features {
+            oir,
+            ..Features::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_aevo_response() {
+        assert_eq!(parse(r#"{"open_interest": "1520.5"}"#), Some(1520.5));
+        assert_eq!(
+            parse(r#"{"instrument_name": "BTC-PERP", "markets": {"open_interest": 12}}"#),
+            Some(12.0)
+        );
+        assert_eq!(parse(r#"{"open_interest": "-1"}"#), None);
+        assert_eq!(parse(r#"{"mark_price": "67000"}"#), None);
+    }
+
+    #[test]
+    fn test_rising_open_interest_confirms_entries() {
+        let mut filter = OpenInterestFilter::new(&OpenInterestConfig {
+            window_secs: 60,
+            min_change: 0.01,
+            ..OpenInterestConfig::default()
+        });
+        filter.set(at(0), 1000.0);
+        filter.set(at(30), 1005.0);
+        assert_eq!(filter.change(), None);
+        assert!(filter.update(&book(-0.5)).entries);
+
+        // Up 2% over the window, with the book leaning the same way
+        filter.set(at(60), 1020.0);
+        assert!((filter.change().unwrap() - 0.02).abs() < 1e-12);
+        assert!(filter.update(&book(0.5)).entries);
+        assert!(!filter.update(&book(-0.5)).entries);
+
+        // Measured from the last poll a window back, open interest is now falling
+        filter.set(at(100), 1000.0);
+        assert_eq!(filter.latest(), Some(1000.0));
+        assert!((filter.change().unwrap() + 0.005 / 1.005).abs() < 1e-12);
+        assert!(!filter.update(&book(0.5)).entries);
+    }
+}
@@ -0,0 +1,249 @@
+//! Book update ordering. Feeds can deliver updates behind later ones, and repeat updates after
+//! reconnecting; the sequencer spots both from each subscription's exchange timestamps and
+//! applies the configured [`OrderingPolicy`] before anything reaches signal computation.
+
+use barter_data::event::MarketEvent;
+use barter_data::subscription::book::OrderBook;
+use chrono::DateTime;
+use chrono::Utc;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::warn;
+
+use crate::config::OrderingConfig;
+use crate::market;
+
+/// What to do with book updates that are out of order or duplicated.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderingPolicy {
+    /// Feed every update through as it arrives.
+    Pass,
+    /// Drop duplicates and updates older than the latest one fed through.
+    #[default]
+    Drop,
+    /// Hold every update for a while, feeding them through in exchange time order, and drop
+    /// duplicates and those arriving too late to be put back in order.
+    Reorder,
+}
+
+/// Per subscription history: recent exchange timestamps, to spot duplicates, and the latest one
+/// fed through.
+#[derive(Debug, Default)]
+struct Stream {
+    recent: VecDeque<DateTime<Utc>>,
+    latest: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug)]
+struct Held {
+    subscription: String,
+    release: Instant,
+    event: MarketEvent<OrderBook>,
+}
+
+#[derive(Debug)]
+pub struct Sequencer {
+    config: OrderingConfig,
+    streams: HashMap<String, Stream>,
+    /// Updates held for reordering, by exchange time.
+    held: Vec<Held>,
+    ready: VecDeque<MarketEvent<OrderBook>>,
+    duplicates: u64,
+    late: u64,
+}
+
+impl Sequencer {
+    pub fn new(config: &OrderingConfig) -> Self {
+        Self {
+            config: config.clone(),
+            streams: HashMap::new(),
+            held: Vec::new(),
+            ready: VecDeque::new(),
+            duplicates: 0,
+            late: 0,
+        }
+    }
+
+    /// Updates dropped as duplicates so far.
+    pub fn duplicates(&self) -> u64 {
+        self.duplicates
+    }
+
+    /// Updates dropped for arriving behind a later one so far.
+    pub fn late(&self) -> u64 {
+        self.late
+    }
+
+    /// Take in an update received at `now`.
+    pub fn push(&mut self, event: MarketEvent<OrderBook>, now: Instant) {
+        if self.config.policy == OrderingPolicy::Pass {
+            self.ready.push_back(event);
+            return;
+        }
+
+        let subscription = market::subscription_name(&event.exchange, &event.instrument);
+        let time = event.exchange_time;
+        let stream = self.streams.entry(subscription.clone()).or_default();
+        if stream.recent.contains(&time) {
+            self.duplicates += 1;
+            warn!("Dropping duplicate {} update from {}", subscription, time);
+            return;
+        }
+        if stream.latest.is_some_and(|latest| time < latest) {
+            self.late += 1;
+            warn!(
+                "Dropping out of order {} update from {}",
+                subscription, time
+            );
+            return;
+        }
+        if stream.recent.len() >= self.config.dedup_window.max(1) {
+            stream.recent.pop_front();
+        }
+        stream.recent.push_back(time);
+
+        match self.config.policy {
+            OrderingPolicy::Reorder => {
+                let index = self
+                    .held
+                    .partition_point(|held| held.event.exchange_time <= time);
+                let release = now + Duration::from_millis(self.config.reorder_ms);
+                self.held.insert(
+                    index,
+                    Held {
+                        subscription,
+                        release,
+                        event,
+                    },
+                );
+            }
+            OrderingPolicy::Pass | OrderingPolicy::Drop => {
+                stream.latest = Some(time);
+                self.ready.push_back(event);
+            }
+        }
+    }
+
+    /// When the first held update is due.
+    fn deadline(&self) -> Option<Instant> {
+        self.held.iter().map(|held| held.release).min()
+    }
+
+    /// Wait until an update is ready to be fed through. Never resolves while nothing is held.
+    pub async fn expired(&self) {
+        if !self.ready.is_empty() {
+            return;
+        }
+        match self.deadline() {
+            Some(deadline) => tokio::time::sleep_until(deadline).await,
+            None => std::future::pending().await,
+        }
+    }
+
+    /// The next update to feed through at `now`, if any. Once any held update is due, every one
+    /// before it in exchange time goes first.
+    pub fn pop(&mut self, now: Instant) -> Option<MarketEvent<OrderBook>> {
+        if let Some(event) = self.ready.pop_front() {
+            return Some(event);
+        }
+        if self.deadline()? > now {
+            return None;
+        }
+        let held = self.held.remove(0);
+        if let Some(stream) = self.streams.get_mut(&held.subscription) {
+            stream.latest = stream.latest.max(Some(held.event.exchange_time));
+        }
+        Some(held.event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use barter_data::subscription::book::OrderBookSide;
+    use barter_integration::model::instrument::kind::InstrumentKind;
+    use barter_integration::model::instrument::Instrument;
+    use barter_integration::model::Exchange;
+    use barter_integration::model::Side;
+
+    use super::*;
+
+    fn update(millis: i64) -> MarketEvent<OrderBook> {
+        let time = DateTime::from_timestamp_millis(millis).unwrap();
+        MarketEvent {
+            exchange_time: time,
+            received_time: time,
+            exchange: Exchange::from("aevo"),
+            instrument: Instrument::from(("btc", "usd", InstrumentKind::Perpetual)),
+            kind: OrderBook {
+                last_update_time: time,
+                bids: OrderBookSide::new(Side::Buy, Vec::<(f64, f64)>::new()),
+                asks: OrderBookSide::new(Side::Sell, Vec::<(f64, f64)>::new()),
+            },
+        }
+    }
+
+    fn sequencer(policy: OrderingPolicy) -> Sequencer {
+        Sequencer::new(&OrderingConfig {
+            policy,
+            reorder_ms: 50,
+            dedup_window: 2,
+        })
+    }
+
+    /// Exchange times of every update ready at `now`.
+    fn drain(sequencer: &mut Sequencer, now: Instant) -> Vec<i64> {
+        std::iter::from_fn(|| sequencer.pop(now))
+            .map(|event| event.exchange_time.timestamp_millis())
+            .collect()
+    }
+
+    #[test]
+    fn test_pass_feeds_everything() {
+        let now = Instant::now();
+        let mut sequencer = sequencer(OrderingPolicy::Pass);
+        for millis in [2, 1, 1] {
+            sequencer.push(update(millis), now);
+        }
+        assert_eq!(drain(&mut sequencer, now), [2, 1, 1]);
+    }
+
+    #[test]
+    fn test_drop_duplicates_and_late_updates() {
+        let now = Instant::now();
+        let mut sequencer = sequencer(OrderingPolicy::Drop);
+        for millis in [1, 3, 3, 2, 4, 5] {
+            sequencer.push(update(millis), now);
+        }
+        // Only the last two timestamps are remembered, so a repeat of the first looks late
+        sequencer.push(update(1), now);
+        assert_eq!(drain(&mut sequencer, now), [1, 3, 4, 5]);
+        assert_eq!((sequencer.duplicates(), sequencer.late()), (1, 2));
+    }
+
+    #[tokio::test]
+    async fn test_reorder_holds_updates_to_sort_them() {
+        let start = Instant::now();
+        let millis = |millis| start + Duration::from_millis(millis);
+        let mut sequencer = sequencer(OrderingPolicy::Reorder);
+        sequencer.push(update(20), start);
+        sequencer.push(update(10), millis(30));
+        sequencer.push(update(20), millis(40));
+        assert!(sequencer.pop(millis(49)).is_none());
+        assert_eq!(sequencer.deadline(), Some(millis(50)));
+
+        // Due at 50ms, with the update it was ahead of released first
+        assert_eq!(drain(&mut sequencer, millis(50)), [10, 20]);
+        sequencer.push(update(15), millis(60));
+        sequencer.push(update(30), millis(60));
+        assert_eq!(drain(&mut sequencer, millis(110)), [30]);
+        assert_eq!((sequencer.duplicates(), sequencer.late()), (1, 1));
+
+        // Nothing held, so nothing to wait for
+        let waited = tokio::time::timeout(Duration::from_millis(10), sequencer.expired()).await;
+        assert!(waited.is_err());
+    }
+}
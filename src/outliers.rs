@@ -0,0 +1,252 @@
+//! Price anomaly filter, between the [`Sequencer`](crate::ordering::Sequencer) and signal
+//! computation, so a single corrupt book can't open a trade or trip a stop-loss.
+//!
+//! Books with malformed levels, prices that aren't positive or sides out of price order, are
+//! rejected outright. A book whose mid jumps more than `max_jump_pct` from the last one let
+//! through is quarantined instead: if the next book's mid is close to it, the market really moved
+//! and the next book is let through, otherwise it was an outlier and is discarded.
+
+use barter_data::event::MarketEvent;
+use barter_data::subscription::book::Level;
+use barter_data::subscription::book::OrderBook;
+use std::collections::HashMap;
+use tracing::info;
+use tracing::warn;
+
+use crate::config::OutlierConfig;
+use crate::market;
+
+/// What to do with a book.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Pass,
+    /// Held back until the next book says whether its price jump was real.
+    Quarantined,
+    Rejected,
+}
+
+/// Per subscription prices: the mid of the last book let through, and of the one quarantined.
+#[derive(Debug, Default)]
+struct Stream {
+    mid: Option<f64>,
+    quarantined: Option<f64>,
+}
+
+#[derive(Debug)]
+pub struct OutlierFilter {
+    config: OutlierConfig,
+    streams: HashMap<String, Stream>,
+    malformed: u64,
+    quarantined: u64,
+    outliers: u64,
+}
+
+impl OutlierFilter {
+    pub fn new(config: &OutlierConfig) -> Self {
+        Self {
+            config: config.clone(),
+            streams: HashMap::new(),
+            malformed: 0,
+            quarantined: 0,
+            outliers: 0,
+        }
+    }
+
+    /// Books rejected for malformed levels so far.
+    pub fn malformed(&self) -> u64 {
+        self.malformed
+    }
+
+    /// Books quarantined for a price jump so far.
+    pub fn quarantined(&self) -> u64 {
+        self.quarantined
+    }
+
+    /// Quarantined books discarded as outliers so far.
+    pub fn outliers(&self) -> u64 {
+        self.outliers
+    }
+
+    /// Judge a book, in the order they are fed to signal computation.
+    pub fn check(&mut self, event: &MarketEvent<OrderBook>) -> Verdict {
+        let subscription = market::subscription_name(&event.exchange, &event.instrument);
+        if self.config.check_levels && !well_formed(&event.kind) {
+            self.malformed += 1;
+            warn!(
+                "Rejecting {} book from {} with malformed levels",
+                subscription, event.exchange_time
+            );
+            return Verdict::Rejected;
+        }
+        // Empty books are left for feature computation to skip
+        let (Some(bid), Some(ask)) = (
+            event.kind.bids.levels.first(),
+            event.kind.asks.levels.first(),
+        ) else {
+            return Verdict::Pass;
+        };
+        let mid = (bid.price + ask.price) / 2.0;
+        let max_jump_pct = self.config.max_jump_pct;
+        let close = |from: f64| max_jump_pct <= 0.0 || jump_pct(from, mid) <= max_jump_pct;
+
+        let stream = self.streams.entry(subscription.clone()).or_default();
+        let verdict = match (stream.mid, stream.quarantined) {
+            (Some(last), quarantined) if close(last) => {
+                if quarantined.is_some() {
+                    self.outliers += 1;
+                }
+                Verdict::Pass
+            }
+            (Some(_), Some(quarantined)) if close(quarantined) => {
+                info!(
+                    "Price jump to {} confirmed by the next {} book",
+                    quarantined, subscription
+                );
+                Verdict::Pass
+            }
+            (Some(last), quarantined) => {
+                if quarantined.is_some() {
+                    self.outliers += 1;
+                }
+                self.quarantined += 1;
+                warn!(
+                    "Quarantining {} book from {}, mid {} is {:.2}% from {}",
+                    subscription,
+                    event.exchange_time,
+                    mid,
+                    jump_pct(last, mid),
+                    last
+                );
+                stream.quarantined = Some(mid);
+                return Verdict::Quarantined;
+            }
+            (None, _) => Verdict::Pass,
+        };
+        stream.mid = Some(mid);
+        stream.quarantined = None;
+        verdict
+    }
+}
+
+/// How far `to` is from `from`, as a percentage of `from`.
+fn jump_pct(from: f64, to: f64) -> f64 {
+    ((to - from) / from).abs() * 100.0
+}
+
+/// Whether every level has a positive price and a size, bids run down in price and asks up.
+fn well_formed(book: &OrderBook) -> bool {
+    let valid = |level: &Level| {
+        level.price.is_finite()
+            && level.price > 0.0
+            && level.amount.is_finite()
+            && level.amount >= 0.0
+    };
+    let bids = &book.bids.levels;
+    let asks = &book.asks.levels;
+    bids.iter().chain(asks).all(valid)
+        && bids.windows(2).all(|pair| pair[0].price > pair[1].price)
+        && asks.windows(2).all(|pair| pair[0].price < pair[1].price)
+}
+
+#[cfg(test)]
+mod tests {
+    use barter_data::subscription::book::OrderBookSide;
+    use barter_integration::model::instrument::kind::InstrumentKind;
+    use barter_integration::model::instrument::Instrument;
+    use barter_integration::model::Exchange;
+    use barter_integration::model::Side;
+    use chrono::DateTime;
+
+    use super::*;
+
+    fn update(bids: &[f64], asks: &[f64]) -> MarketEvent<OrderBook> {
+        let levels = |prices: &[f64]| {
+            prices
+                .iter()
+                .map(|&price| Level::new(price, 1.0))
+                .collect::<Vec<_>>()
+        };
+        let time = DateTime::from_timestamp_millis(0).unwrap();
+        MarketEvent {
+            exchange_time: time,
+            received_time: time,
+            exchange: Exchange::from("aevo"),
+            instrument: Instrument::from(("btc", "usdt", InstrumentKind::Perpetual)),
+            kind: OrderBook {
+                last_update_time: time,
+                bids: OrderBookSide::new(Side::Buy, levels(bids)),
+                asks: OrderBookSide::new(Side::Sell, levels(asks)),
+            },
+        }
+    }
+
+    fn filter() -> OutlierFilter {
+        OutlierFilter::new(&OutlierConfig {
+            max_jump_pct: 5.0,
+            check_levels: true,
+        })
+    }
+
+    #[test]
+    fn test_malformed_levels() {
+        let mut filter = filter();
+        assert_eq!(
+            filter.check(&update(&[99.0, 98.0], &[100.0, 101.0])),
+            Verdict::Pass
+        );
+        assert_eq!(
+            filter.check(&update(&[98.0, 99.0], &[100.0])),
+            Verdict::Rejected
+        );
+        assert_eq!(
+            filter.check(&update(&[99.0], &[101.0, 100.0])),
+            Verdict::Rejected
+        );
+        assert_eq!(filter.check(&update(&[-1.0], &[100.0])), Verdict::Rejected);
+        assert_eq!(filter.check(&update(&[], &[100.0])), Verdict::Pass);
+        assert_eq!(filter.malformed(), 3);
+
+        let mut unchecked = OutlierFilter::new(&OutlierConfig {
+            max_jump_pct: 0.0,
+            check_levels: false,
+        });
+        assert_eq!(
+            unchecked.check(&update(&[98.0, 99.0], &[100.0])),
+            Verdict::Pass
+        );
+        assert_eq!(unchecked.check(&update(&[9.0], &[10.0])), Verdict::Pass);
+    }
+
+    #[test]
+    fn test_quarantine_price_jumps() {
+        let mut filter = filter();
+        assert_eq!(filter.check(&update(&[99.0], &[101.0])), Verdict::Pass);
+        // A lone spike is discarded once the next book is back near the last price
+        assert_eq!(
+            filter.check(&update(&[149.0], &[151.0])),
+            Verdict::Quarantined
+        );
+        assert_eq!(filter.check(&update(&[100.0], &[102.0])), Verdict::Pass);
+        assert_eq!((filter.quarantined(), filter.outliers()), (1, 1));
+
+        // A move confirmed by the next book is followed from then on
+        assert_eq!(
+            filter.check(&update(&[119.0], &[121.0])),
+            Verdict::Quarantined
+        );
+        assert_eq!(filter.check(&update(&[120.0], &[122.0])), Verdict::Pass);
+        assert_eq!(filter.check(&update(&[121.0], &[123.0])), Verdict::Pass);
+        assert_eq!((filter.quarantined(), filter.outliers()), (2, 1));
+
+        // Two spikes in a row that disagree are both quarantined, the first discarded
+        assert_eq!(
+            filter.check(&update(&[59.0], &[61.0])),
+            Verdict::Quarantined
+        );
+        assert_eq!(
+            filter.check(&update(&[199.0], &[201.0])),
+            Verdict::Quarantined
+        );
+        assert_eq!((filter.quarantined(), filter.outliers()), (4, 2));
+    }
+}
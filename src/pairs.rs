@@ -0,0 +1,328 @@
+//! Pairs trading, [`[pairs]`](crate::config::PairsConfig), of the traded perpetual against
+//! Aevo's perpetual of another asset. Log prices of both are sampled at most every `sample_ms`,
+//! and over the last `window` samples the traded one is regressed on the pair's, an Engle-Granger
+//! test: the residual spread is cointegrated while its Dickey-Fuller t-statistic is below
+//! `critical`, so it keeps reverting to the regression line rather than wandering off.
+//!
+//! While it is, a spread more than `entry_z` standard deviations rich sells the traded perpetual
+//! and buys the pair, and one as far cheap does the reverse, the pair sized by the regression to
+//! hedge the traded leg. The two legs are held as one [`PairPosition`], opened and closed
+//! together: once the spread is back within `exit_z`, or stops being cointegrated. An alert
+//! announces each position opened, and another once it is closed.
+//!
+//! Like [basis](crate::basis) positions, pairs are paper traded outside the strategies' sleeves.
+
+use barter_integration::model::Side;
+use chrono::DateTime;
+use chrono::TimeDelta;
+use chrono::Utc;
+use std::collections::VecDeque;
+use tracing::info;
+
+use crate::basis::Leg;
+use crate::config::PairsConfig;
+use crate::event::Alert;
+use crate::event::Severity;
+use crate::features::Features;
+
+/// The [condition](Alert::condition) every pairs trading alert is about.
+pub const CONDITION: &str = "pairs";
+
+/// The regression of the traded log price on the pair's, and how its residual spread behaves.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fit {
+    pub alpha: f64,
+    /// How far the traded log price moves with the pair's.
+    pub beta: f64,
+    /// The Dickey-Fuller t-statistic of the spread, the more negative the faster it reverts.
+    pub t_stat: f64,
+    /// The latest spread, in standard deviations.
+    pub z: f64,
+}
+
+impl Fit {
+    /// Regress `samples` of the traded and the pair's log prices, oldest first, unless too few
+    /// or too flat to.
+    pub fn new(samples: &VecDeque<(f64, f64)>) -> Option<Self> {
+        let n = samples.len() as f64;
+        if samples.len() < 3 {
+            return None;
+        }
+        let mean_y = samples.iter().map(|(y, _)| y).sum::<f64>() / n;
+        let mean_x = samples.iter().map(|(_, x)| x).sum::<f64>() / n;
+        let (sxx, sxy) = samples.iter().fold((0.0, 0.0), |(sxx, sxy), (y, x)| {
+            let dx = x - mean_x;
+            (sxx + dx * dx, sxy + dx * (y - mean_y))
+        });
+        if sxx <= 0.0 {
+            return None;
+        }
+        let beta = sxy / sxx;
+        let alpha = mean_y - beta * mean_x;
+        let spread: Vec<f64> = samples.iter().map(|(y, x)| y - alpha - beta * x).collect();
+        let std = (spread.iter().map(|e| e * e).sum::<f64>() / (n - 2.0)).sqrt();
+
+        // Regress each change in the spread on the spread before it
+        let (lagged, moved) = spread.windows(2).fold((0.0, 0.0), |(lagged, moved), pair| {
+            (
+                lagged + pair[0] * pair[0],
+                moved + pair[0] * (pair[1] - pair[0]),
+            )
+        });
+        if std <= 0.0 || lagged <= 0.0 {
+            return None;
+        }
+        let gamma = moved / lagged;
+        let noise = spread
+            .windows(2)
+            .map(|pair| (pair[1] - pair[0] - gamma * pair[0]).powi(2))
+            .sum::<f64>()
+            / (n - 2.0);
+        Some(Self {
+            alpha,
+            beta,
+            t_stat: gamma / (noise / lagged).sqrt(),
+            z: spread.last()? / std,
+        })
+    }
+}
+
+/// A leg of the traded perpetual and the opposite leg of the pair, opened and closed together.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PairPosition {
+    pub traded: Leg,
+    pub traded_size: f64,
+    pub pair: Leg,
+    pub pair_size: f64,
+    /// Fees paid opening both legs.
+    pub fees: f64,
+}
+
+impl PairPosition {
+    /// What closing both legs against the `traded` and `pair` tops would make, after every fee.
+    pub fn pnl(&self, traded: &Features, pair: &Features, fee: f64) -> f64 {
+        let closing = (self.traded.exit(traded) * self.traded_size
+            + self.pair.exit(pair) * self.pair_size)
+            * fee;
+        self.traded.pnl(traded, self.traded_size) + self.pair.pnl(pair, self.pair_size)
+            - self.fees
+            - closing
+    }
+}
+
+#[derive(Debug)]
+pub struct PairsTrader {
+    config: PairsConfig,
+    /// The latest top of the pair's book.
+    pair: Option<Features>,
+    /// Log prices of the traded perpetual and the pair, oldest first.
+    samples: VecDeque<(f64, f64)>,
+    sampled: Option<DateTime<Utc>>,
+    fit: Option<Fit>,
+    position: Option<PairPosition>,
+    /// P&L of the positions closed so far.
+    realised: f64,
+}
+
+impl PairsTrader {
+    pub fn new(config: &PairsConfig) -> Self {
+        Self {
+            config: config.clone(),
+            pair: None,
+            samples: VecDeque::new(),
+            sampled: None,
+            fit: None,
+            position: None,
+            realised: 0.0,
+        }
+    }
+
+    /// Keep the pair's book `features` to trade against.
+    pub fn on_pair(&mut self, features: &Features) {
+        self.pair = Some(*features);
+    }
+
+    /// The latest regression over a full window.
+    pub fn fit(&self) -> Option<Fit> {
+        self.fit
+    }
+
+    pub fn position(&self) -> Option<&PairPosition> {
+        self.position.as_ref()
+    }
+
+    pub fn realised(&self) -> f64 {
+        self.realised
+    }
+
+    /// Sample both log prices if a sample is due at `now`, fitting the window again once full.
+    fn sample(&mut self, traded: &Features, pair: &Features, now: DateTime<Utc>) {
+        let interval = TimeDelta::milliseconds(self.config.sample_ms as i64);
+        if self.sampled.is_some_and(|sampled| now - sampled < interval) {
+            return;
+        }
+        self.sampled = Some(now);
+        self.samples
+            .push_back((traded.mid_price.ln(), pair.mid_price.ln()));
+        while self.samples.len() > self.config.window {
+            self.samples.pop_front();
+        }
+        if self.samples.len() == self.config.window {
+            self.fit = Fit::new(&self.samples);
+        }
+    }
+
+    /// Price the spread from the `traded` book at `now`, opening or closing the position,
+    /// returning the alert to raise if it did.
+    pub fn update(&mut self, traded: &Features, now: DateTime<Utc>) -> Option<Alert> {
+        let pair = self.pair?;
+        self.sample(traded, &pair, now);
+        let fit = self.fit?;
+        let cointegrated = fit.t_stat < self.config.critical;
+
+        if let Some(position) = self.position {
+            if cointegrated && fit.z.abs() > self.config.exit_z {
+                return None;
+            }
+            let pnl = position.pnl(traded, &pair, self.config.fee);
+            self.realised += pnl;
+            self.position = None;
+            let why = if cointegrated {
+                "reverted"
+            } else {
+                "no longer cointegrated"
+            };
+            let message = format!(
+                "Spread {} at a z-score of {:.2}, closed both legs for {:.2}",
+                why, fit.z, pnl
+            );
+            info!("{}", message);
+            return Some(Alert {
+                severity: Severity::Info,
+                message,
+                condition: Some(CONDITION.to_owned()),
+                resolved: true,
+            });
+        }
+
+        if !cointegrated || fit.z.abs() < self.config.entry_z || fit.beta <= 0.0 {
+            return None;
+        }
+        // A rich spread is sold: the traded perpetual against the pair
+        let (traded_side, pair_side) = if fit.z > 0.0 {
+            (Side::Sell, Side::Buy)
+        } else {
+            (Side::Buy, Side::Sell)
+        };
+        let traded_size = self.config.size;
+        let pair_size = fit.beta * traded_size * traded.mid_price / pair.mid_price;
+        let traded_leg = Leg::take(traded_side, traded);
+        let pair_leg = Leg::take(pair_side, &pair);
+        let fees = (traded_leg.price * traded_size + pair_leg.price * pair_size) * self.config.fee;
+        self.position = Some(PairPosition {
+            traded: traded_leg,
+            traded_size,
+            pair: pair_leg,
+            pair_size,
+            fees,
+        });
+        let message = format!(
+            "Spread at a z-score of {:.2}, t-statistic {:.2}: {:?} {} at {} against {:?} {:.6} \
+             of the pair at {}",
+            fit.z,
+            fit.t_stat,
+            traded_side,
+            traded_size,
+            traded_leg.price,
+            pair_side,
+            pair_size,
+            pair_leg.price
+        );
+        info!("{}", message);
+        Some(Alert {
+            severity: Severity::Info,
+            message,
+            condition: Some(CONDITION.to_owned()),
+            resolved: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    /// A random walk of the pair's log price, with the traded one tied to it by `beta` plus
+    /// mean reverting noise, or wandering off on a walk of its own.
+    fn walks(beta: f64, tied: bool, n: usize) -> VecDeque<(f64, f64)> {
+        let mut rng = Rng::new(7);
+        let (mut x, mut drift, mut noise) = (8.0, 0.0, 0.0);
+        (0..n)
+            .map(|_| {
+                x += 0.01 * rng.centred();
+                noise = 0.5 * noise + 0.002 * rng.centred();
+                drift += 0.01 * rng.centred();
+                let y = 1.0 + beta * x + if tied { noise } else { drift };
+                (y, x)
+            })
+            .collect()
+    }
+
+    fn book(mid: f64) -> Features {
+        Features {
+            bid: mid - 0.5,
+            ask: mid + 0.5,
+            mid_price: mid,
+            ..Features::default()
+        }
+    }
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000 + secs, 0).unwrap()
+    }
+
+    #[test]
+    fn test_cointegration() {
+        let tied = Fit::new(&walks(1.2, true, 300)).unwrap();
+        assert!((tied.beta - 1.2).abs() < 0.05);
+        assert!(tied.t_stat < -3.34);
+        let wandering = Fit::new(&walks(1.2, false, 300)).unwrap();
+        assert!(wandering.t_stat > -3.34);
+    }
+
+    #[test]
+    fn test_legs_open_and_close_together() {
+        let mut trader = PairsTrader::new(&PairsConfig {
+            window: 300,
+            ..PairsConfig::default()
+        });
+        assert_eq!(trader.update(&book(60_000.0), at(0)), None);
+        for (secs, (y, x)) in walks(1.0, true, 300).into_iter().enumerate() {
+            trader.on_pair(&book(x.exp()));
+            trader.update(&book(y.exp()), at(secs as i64));
+        }
+        // Whatever was traded warming up aside
+        trader.position = None;
+        trader.realised = 0.0;
+        let fit = trader.fit().unwrap();
+        let pair = trader.pair.unwrap();
+
+        // The traded perpetual jumps well above the regression line
+        let rich = (fit.alpha + fit.beta * pair.mid_price.ln() + 0.02).exp();
+        let opened = trader.update(&book(rich), at(300)).unwrap();
+        assert!(!opened.resolved);
+        let position = *trader.position().unwrap();
+        assert_eq!(position.traded.side, Side::Sell);
+        assert_eq!(position.pair.side, Side::Buy);
+        let hedge = trader.fit().unwrap().beta * 0.001 * rich / pair.mid_price;
+        assert!((position.pair_size - hedge).abs() < 1e-12);
+
+        // Both legs close once it is back on the line
+        let fair = (fit.alpha + fit.beta * pair.mid_price.ln()).exp();
+        let closed = trader.update(&book(fair), at(301)).unwrap();
+        assert!(closed.resolved);
+        assert_eq!(trader.position(), None);
+        assert!(trader.realised() > 0.0);
+    }
+}
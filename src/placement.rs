@@ -0,0 +1,476 @@
+//! Maker or taker placement, [`[placement]`](crate::config::PlacementConfig), from an empirical
+//! model of how likely a limit order is to fill.
+//!
+//! The `fit-fills` command learns the model from the feature records of an event log. At every
+//! update a buy and a sell are placed at each of [`DISTANCES_BPS`] from the mid, or at the touch
+//! where that is inside the spread, and one counts as filled if a later book within
+//! `horizon_secs` trades through its price: the best bid falling below a buy, or the best ask
+//! rising above a sell. The queue isn't known, so only a trade through is sure to have reached the
+//! order and the probabilities err low. Orders are counted by how far from the mid they rest, by
+//! how hard the OIR leans away from their side, and by how volatile the mid was, in terciles of the
+//! log's volatility.
+//!
+//! Trading, the model is asked how likely an order resting at the touch is to fill given the
+//! book's imbalance and volatility now. A side less likely than `min_probability` crosses the
+//! spread instead, trading at once at the far touch as a market order, rather than rest while the
+//! price runs away from it. This applies to the paper account, as the order gateway leaves
+//! placement to the execution service.
+
+use barter_integration::model::Side;
+use chrono::DateTime;
+use chrono::TimeDelta;
+use chrono::Utc;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::fs;
+use std::io::BufRead;
+use std::path::Path;
+use std::path::PathBuf;
+use tracing::info;
+use tracing::warn;
+
+use crate::config::PlacementConfig;
+use crate::features::Features;
+use crate::report::ReportError;
+
+/// The distances from the mid, in bps, orders are placed at, each counted up to the next.
+pub const DISTANCES_BPS: [f64; 7] = [0.0, 0.5, 1.0, 2.0, 5.0, 10.0, 20.0];
+
+/// Where the OIR, leaning away from the order's side, divides weak, balanced and strong.
+const LEAN_EDGES: [f64; 2] = [-1.0 / 3.0, 1.0 / 3.0];
+
+/// Weight kept by the volatility estimate on each update.
+const VOLATILITY_DECAY: f64 = 0.99;
+
+/// Orders placed in a bucket before its fill rate is trusted.
+const MIN_PLACED: u64 = 20;
+
+#[derive(Debug, thiserror::Error)]
+pub enum PlacementError {
+    #[error("failed to read fill model {0}: {1}")]
+    Io(PathBuf, std::io::Error),
+
+    #[error("invalid fill model {0}: {1}")]
+    Invalid(PathBuf, serde_json::Error),
+}
+
+/// Exponentially weighted volatility of the mid, in bps over the square root of a second, so it
+/// doesn't depend on how often the book updates.
+#[derive(Debug, Clone, Default)]
+struct Volatility {
+    last: Option<(DateTime<Utc>, f64)>,
+    variance: f64,
+}
+
+impl Volatility {
+    fn update(&mut self, mid_price: f64, now: DateTime<Utc>) {
+        if !mid_price.is_finite() || mid_price <= 0.0 {
+            return;
+        }
+        if let Some((then, last)) = self.last {
+            let elapsed = (now - then).num_milliseconds().max(1) as f64 / 1000.0;
+            let r = (mid_price / last).ln() * 10_000.0;
+            self.variance =
+                VOLATILITY_DECAY * self.variance + (1.0 - VOLATILITY_DECAY) * r * r / elapsed;
+        }
+        self.last = Some((now, mid_price));
+    }
+
+    fn value(&self) -> f64 {
+        self.variance.sqrt()
+    }
+}
+
+/// How hard the OIR of `features` leans away from `side`, buying pressure against a buy.
+fn lean(side: Side, features: &Features) -> f64 {
+    match side {
+        Side::Buy => features.oir,
+        Side::Sell => -features.oir,
+    }
+}
+
+fn bucket(edges: &[f64], value: f64) -> usize {
+    edges.iter().filter(|edge| value >= **edge).count()
+}
+
+/// Orders placed in one bucket, and how many of them filled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Cell {
+    pub placed: u64,
+    pub filled: u64,
+}
+
+/// An order placed while fitting, waiting to be traded through.
+#[derive(Debug, Clone, Copy)]
+struct Placed {
+    time: DateTime<Utc>,
+    side: Side,
+    price: f64,
+    cell: (usize, usize, usize),
+}
+
+/// A logged event, as far as fitting needs it.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Record {
+    Features {
+        time: DateTime<Utc>,
+        symbol: String,
+        features: Features,
+    },
+    #[serde(other)]
+    Other,
+}
+
+/// The fill rate of limit orders by distance from the mid, lean of the imbalance and volatility.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FillModel {
+    pub horizon_secs: u64,
+    /// The terciles of the volatility over the log fitted on.
+    pub volatility_edges: [f64; 2],
+    /// By distance, then lean, then volatility.
+    pub cells: [[[Cell; 3]; 3]; DISTANCES_BPS.len()],
+}
+
+impl FillModel {
+    /// Fit the model on `symbol`'s feature records in `log`. A torn final line is skipped with a
+    /// warning, as the report does.
+    pub fn read(log: impl BufRead, symbol: &str, horizon_secs: u64) -> Result<Self, ReportError> {
+        let mut books = Vec::new();
+        let mut lines = log.lines().enumerate().peekable();
+        while let Some((index, line)) = lines.next() {
+            let line = line?;
+            match serde_json::from_str(&line) {
+                Ok(Record::Features {
+                    time,
+                    symbol: logged,
+                    features,
+                }) if logged == symbol => books.push((time, features)),
+                Ok(_) => {}
+                Err(error) if lines.peek().is_none() => {
+                    warn!("Skipping torn event log line {}: {}", index + 1, error);
+                }
+                Err(error) => {
+                    return Err(ReportError::Corrupt {
+                        line: index + 1,
+                        error,
+                    })
+                }
+            }
+        }
+        Ok(Self::fit(&books, horizon_secs))
+    }
+
+    /// Fit the model on `books`, in time order.
+    pub fn fit(books: &[(DateTime<Utc>, Features)], horizon_secs: u64) -> Self {
+        let mut volatility = Volatility::default();
+        let volatilities: Vec<f64> = books
+            .iter()
+            .map(|(time, features)| {
+                volatility.update(features.mid_price, *time);
+                volatility.value()
+            })
+            .collect();
+        let mut sorted = volatilities.clone();
+        sorted.sort_unstable_by(f64::total_cmp);
+        let tercile = |fraction: f64| {
+            sorted
+                .get((fraction * sorted.len() as f64) as usize)
+                .copied()
+                .unwrap_or_default()
+        };
+        let mut model = Self {
+            horizon_secs,
+            volatility_edges: [tercile(1.0 / 3.0), tercile(2.0 / 3.0)],
+            cells: Default::default(),
+        };
+
+        let horizon = TimeDelta::seconds(horizon_secs as i64);
+        let mut pending: VecDeque<Placed> = VecDeque::new();
+        for ((time, features), volatility) in books.iter().zip(volatilities) {
+            let (time, mid_price) = (*time, features.mid_price);
+            if !mid_price.is_finite() || mid_price <= 0.0 {
+                continue;
+            }
+            pending.retain(|order| {
+                let expired = time - order.time > horizon;
+                let filled = !expired
+                    && match order.side {
+                        Side::Buy => features.bid < order.price,
+                        Side::Sell => features.ask > order.price,
+                    };
+                if expired || filled {
+                    let (distance, lean, volatility) = order.cell;
+                    let cell = &mut model.cells[distance][lean][volatility];
+                    cell.placed += 1;
+                    cell.filled += u64::from(filled);
+                }
+                !expired && !filled
+            });
+
+            for side in [Side::Buy, Side::Sell] {
+                for distance in DISTANCES_BPS {
+                    let offset = mid_price * distance / 10_000.0;
+                    let price = match side {
+                        Side::Buy => (mid_price - offset).min(features.bid),
+                        Side::Sell => (mid_price + offset).max(features.ask),
+                    };
+                    let cell = model.cell(
+                        (price - mid_price).abs() / mid_price * 10_000.0,
+                        lean(side, features),
+                        volatility,
+                    );
+                    pending.push_back(Placed {
+                        time,
+                        side,
+                        price,
+                        cell,
+                    });
+                }
+            }
+        }
+        model
+    }
+
+    /// Load the model fitted to `path`.
+    pub fn load(path: &Path) -> Result<Self, PlacementError> {
+        let contents = fs::read_to_string(path)
+            .map_err(|error| PlacementError::Io(path.to_path_buf(), error))?;
+        serde_json::from_str(&contents)
+            .map_err(|error| PlacementError::Invalid(path.to_path_buf(), error))
+    }
+
+    fn cell(&self, distance_bps: f64, lean: f64, volatility: f64) -> (usize, usize, usize) {
+        (
+            bucket(&DISTANCES_BPS[1..], distance_bps),
+            bucket(&LEAN_EDGES, lean),
+            bucket(&self.volatility_edges, volatility),
+        )
+    }
+
+    /// The chance an order `distance_bps` from the mid fills within the horizon, with the OIR
+    /// leaning `lean` away from its side and the mid as volatile as `volatility`, once the bucket
+    /// has seen enough orders.
+    pub fn probability(&self, distance_bps: f64, lean: f64, volatility: f64) -> Option<f64> {
+        let (distance, lean, volatility) = self.cell(distance_bps, lean, volatility);
+        let cell = self.cells[distance][lean][volatility];
+        (cell.placed >= MIN_PLACED).then(|| cell.filled as f64 / cell.placed as f64)
+    }
+}
+
+/// Which sides' orders cross the spread rather than rest at the touch.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Crossing {
+    pub buys: bool,
+    pub sells: bool,
+}
+
+impl Crossing {
+    pub fn crosses(&self, side: Side) -> bool {
+        match side {
+            Side::Buy => self.buys,
+            Side::Sell => self.sells,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Placement {
+    config: PlacementConfig,
+    model: FillModel,
+    volatility: Volatility,
+    crossing: Crossing,
+}
+
+impl Placement {
+    pub fn new(config: &PlacementConfig, model: FillModel) -> Self {
+        if model.horizon_secs != config.horizon_secs {
+            warn!(
+                "Fill model was fitted over {}s, not the configured {}s",
+                model.horizon_secs, config.horizon_secs
+            );
+        }
+        Self {
+            config: config.clone(),
+            model,
+            volatility: Volatility::default(),
+            crossing: Crossing::default(),
+        }
+    }
+
+    /// The chance an order on `side` resting at the touch of `features` fills within the horizon.
+    pub fn probability(&self, side: Side, features: &Features) -> Option<f64> {
+        let mid_price = features.mid_price;
+        let touch = match side {
+            Side::Buy => features.bid,
+            Side::Sell => features.ask,
+        };
+        let distance = (touch - mid_price).abs() / mid_price * 10_000.0;
+        self.model
+            .probability(distance, lean(side, features), self.volatility.value())
+    }
+
+    /// Take in the book of `features` at `now`, returning which sides should cross the spread.
+    pub fn update(&mut self, features: &Features, now: DateTime<Utc>) -> Crossing {
+        self.volatility.update(features.mid_price, now);
+        if !features.mid_price.is_finite() || features.mid_price <= 0.0 {
+            return self.crossing;
+        }
+        let crosses = |side| {
+            self.probability(side, features)
+                .is_some_and(|probability| probability < self.config.min_probability)
+        };
+        let crossing = Crossing {
+            buys: crosses(Side::Buy),
+            sells: crosses(Side::Sell),
+        };
+        if crossing != self.crossing {
+            info!(
+                "Placing buys {} and sells {}",
+                if crossing.buys {
+                    "across the spread"
+                } else {
+                    "at the touch"
+                },
+                if crossing.sells {
+                    "across the spread"
+                } else {
+                    "at the touch"
+                }
+            );
+            self.crossing = crossing;
+        }
+        crossing
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::Event;
+
+    fn at(millis: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp_millis(1_700_000_000_000 + millis).unwrap()
+    }
+
+    fn book(bid: f64, oir: f64) -> Features {
+        Features {
+            bid,
+            ask: bid + 0.01,
+            mid_price: bid + 0.005,
+            oir,
+            ..Features::default()
+        }
+    }
+
+    /// The bid falling a cent a second with buyers leaning on the book, then rising a cent a
+    /// second with sellers leaning on it.
+    fn books() -> Vec<(DateTime<Utc>, Features)> {
+        (0..200)
+            .map(|second| {
+                let features = if second < 100 {
+                    book(100.0 - 0.01 * second as f64, -0.5)
+                } else {
+                    book(99.0 + 0.01 * (second - 100) as f64, 0.5)
+                };
+                (at(second * 1000), features)
+            })
+            .collect()
+    }
+
+    /// The fill rate of every order placed `distance_bps` out with the OIR leaning `lean`,
+    /// however volatile the mid was.
+    fn rate(model: &FillModel, distance_bps: f64, lean: f64) -> f64 {
+        let (distance, lean, _) = model.cell(distance_bps, lean, 0.0);
+        let cells = model.cells[distance][lean];
+        let placed: u64 = cells.iter().map(|cell| cell.placed).sum();
+        let filled: u64 = cells.iter().map(|cell| cell.filled).sum();
+        filled as f64 / placed as f64
+    }
+
+    #[test]
+    fn test_fits_fill_rates() {
+        let model = FillModel::fit(&books(), 2);
+        // Orders at the touch are traded through when the price comes to them, never when it
+        // runs away, nor far out within the horizon
+        assert_eq!(rate(&model, 0.25, -0.5), 1.0);
+        assert_eq!(rate(&model, 0.25, 0.5), 0.0);
+        assert_eq!(rate(&model, 20.0, -0.5), 0.0);
+
+        let json = serde_json::to_string(&model).unwrap();
+        assert_eq!(serde_json::from_str::<FillModel>(&json).unwrap(), model);
+
+        let mut log = String::new();
+        for (time, features) in books() {
+            let event = Event::Features {
+                time,
+                symbol: "BTC/USDT",
+                features,
+                book_time: None,
+            };
+            log.push_str(&String::from_utf8(event.to_json()).unwrap());
+            log.push('\n');
+        }
+        log.push_str("{\"type\": \"fea");
+        assert_eq!(
+            FillModel::read(log.as_bytes(), "BTC/USDT", 2).unwrap(),
+            model
+        );
+        let other = FillModel::read(log.as_bytes(), "ETH/USDT", 2).unwrap();
+        assert!(other
+            .cells
+            .iter()
+            .flatten()
+            .flatten()
+            .all(|cell| cell.placed == 0));
+    }
+
+    #[test]
+    fn test_crosses_when_unlikely_to_fill() {
+        let mut model = FillModel {
+            horizon_secs: 2,
+            volatility_edges: [1.0, 2.0],
+            cells: Default::default(),
+        };
+        // At the touch in a calm market: unlikely to fill with the OIR leaning away, likely with
+        // it leaning toward, and too few orders to tell while balanced
+        model.cells[0][2][0] = Cell {
+            placed: 100,
+            filled: 10,
+        };
+        model.cells[0][0][0] = Cell {
+            placed: 100,
+            filled: 90,
+        };
+        model.cells[0][1][0] = Cell {
+            placed: 5,
+            filled: 0,
+        };
+        let config = PlacementConfig {
+            horizon_secs: 2,
+            ..PlacementConfig::default()
+        };
+        let mut placement = Placement::new(&config, model);
+
+        // Buyers leaning on the book leave a resting buy behind, while a resting sell is hit
+        let crossing = placement.update(&book(100.0, 0.5), at(0));
+        assert_eq!(
+            crossing,
+            Crossing {
+                buys: true,
+                sells: false
+            }
+        );
+        assert!(crossing.crosses(Side::Buy) && !crossing.crosses(Side::Sell));
+        assert_eq!(
+            placement.probability(Side::Buy, &book(100.0, 0.5)),
+            Some(0.1)
+        );
+
+        let crossing = placement.update(&book(100.0, -0.5), at(1000));
+        assert_eq!((crossing.buys, crossing.sells), (false, true));
+        let crossing = placement.update(&book(100.0, 0.0), at(2000));
+        assert_eq!(crossing, Crossing::default());
+    }
+}
@@ -0,0 +1,57 @@
+//! Polling an exchange REST endpoint in the background, for the feeds the market data stream
+//! doesn't carry: server time, funding rates, exchange status, open interest and mark prices.
+
+use chrono::DateTime;
+use chrono::Utc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tracing::warn;
+
+/// Fetch `url` every `poll_secs`, at least a second apart, handing each response body to `parse`
+/// with when the request was sent and its response received, and sending on whatever it makes of
+/// it. Responses it can't make anything of and failed requests are logged as polls of `what`,
+/// and polling stops once the receiver is dropped.
+pub fn spawn<T, F>(
+    url: String,
+    poll_secs: u64,
+    what: &'static str,
+    parse: F,
+) -> watch::Receiver<Option<T>>
+where
+    T: Send + Sync + 'static,
+    F: Fn(&str, DateTime<Utc>, DateTime<Utc>) -> Option<T> + Send + 'static,
+{
+    let (sender, receiver) = watch::channel(None);
+    let period = Duration::from_secs(poll_secs.max(1));
+    tokio::spawn(async move {
+        let client = reqwest::Client::new();
+        let mut poll = tokio::time::interval(period);
+        poll.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            poll.tick().await;
+            let sent = Utc::now();
+            let body = fetch(&client, &url).await;
+            let received = Utc::now();
+            match body.map(|body| parse(&body, sent, received)) {
+                Ok(Some(value)) => {
+                    if sender.send(Some(value)).is_err() {
+                        break;
+                    }
+                }
+                Ok(None) => warn!("Unexpected {} response from {}", what, url),
+                Err(error) => warn!("Failed to poll {}: {}", what, error),
+            }
+        }
+    });
+    receiver
+}
+
+async fn fetch(client: &reqwest::Client, url: &str) -> Result<String, reqwest::Error> {
+    client
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await
+}
@@ -0,0 +1,132 @@
+//! Liveness and readiness, for an orchestrator to restart a wedged bot rather than leave it idling:
+//! the trading loop checks in on every pass and reports the state of the feed and execution, and
+//! the [control API](crate::api) serves both as probes under [`[probes]`](crate::config::ProbeConfig).
+//!
+//! The bot is live while the loop has checked in within `max_stall_secs`, and ready while it is
+//! live, the book feed is connected, the latest book is at most `max_book_age_secs` old and orders
+//! can reach execution: always for the paper account, while connected for the order gateway.
+
+use serde::Serialize;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::config::ProbeConfig;
+
+#[derive(Debug)]
+struct Checks {
+    heartbeat: Instant,
+    feed_connected: bool,
+    last_book: Option<Instant>,
+    execution_reachable: bool,
+}
+
+/// What each readiness check found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Readiness {
+    pub ready: bool,
+    pub live: bool,
+    pub feed_connected: bool,
+    pub book_fresh: bool,
+    pub execution_reachable: bool,
+}
+
+/// Shared probe state. Clones report into and read from the same state.
+#[derive(Debug, Clone)]
+pub struct Probes {
+    max_stall: Duration,
+    max_book_age: Duration,
+    checks: Arc<Mutex<Checks>>,
+}
+
+impl Probes {
+    /// Probes with the feed not yet connected, starting the stall allowance from `now`.
+    pub fn new(config: &ProbeConfig, now: Instant) -> Self {
+        Self {
+            max_stall: Duration::from_secs(config.max_stall_secs),
+            max_book_age: Duration::from_secs(config.max_book_age_secs),
+            checks: Arc::new(Mutex::new(Checks {
+                heartbeat: now,
+                feed_connected: false,
+                last_book: None,
+                execution_reachable: true,
+            })),
+        }
+    }
+
+    /// Check in from the trading loop.
+    pub fn heartbeat(&self, now: Instant) {
+        self.lock().heartbeat = now;
+    }
+
+    pub fn set_feed_connected(&self, connected: bool) {
+        self.lock().feed_connected = connected;
+    }
+
+    /// Count a book received at `now`, which also means the feed is connected.
+    pub fn on_book(&self, now: Instant) {
+        let mut checks = self.lock();
+        checks.feed_connected = true;
+        checks.last_book = Some(now);
+    }
+
+    pub fn set_execution_reachable(&self, reachable: bool) {
+        self.lock().execution_reachable = reachable;
+    }
+
+    /// Whether the trading loop has checked in recently enough.
+    pub fn live(&self, now: Instant) -> bool {
+        now.saturating_duration_since(self.lock().heartbeat) <= self.max_stall
+    }
+
+    pub fn readiness(&self, now: Instant) -> Readiness {
+        let checks = self.lock();
+        let live = now.saturating_duration_since(checks.heartbeat) <= self.max_stall;
+        let book_fresh = checks
+            .last_book
+            .is_some_and(|last_book| now.saturating_duration_since(last_book) <= self.max_book_age);
+        Readiness {
+            ready: live && checks.feed_connected && book_fresh && checks.execution_reachable,
+            live,
+            feed_connected: checks.feed_connected,
+            book_fresh,
+            execution_reachable: checks.execution_reachable,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Checks> {
+        self.checks.lock().expect("probe lock poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_probes() {
+        let start = Instant::now();
+        let probes = Probes::new(&ProbeConfig::default(), start);
+        assert!(probes.live(start));
+        let readiness = probes.readiness(start);
+        assert!(!readiness.ready);
+        assert!(!readiness.feed_connected && !readiness.book_fresh);
+        assert!(readiness.execution_reachable);
+
+        probes.clone().on_book(start);
+        assert!(probes.readiness(start + Duration::from_secs(10)).ready);
+        let stale = probes.readiness(start + Duration::from_secs(11));
+        assert!(!stale.ready && !stale.book_fresh && stale.live);
+
+        probes.heartbeat(start + Duration::from_secs(5));
+        assert!(probes.live(start + Duration::from_secs(65)));
+        assert!(!probes.live(start + Duration::from_secs(66)));
+
+        probes.set_execution_reachable(false);
+        assert!(!probes.readiness(start).ready);
+        probes.set_execution_reachable(true);
+        probes.set_feed_connected(false);
+        assert!(!probes.readiness(start).ready);
+    }
+}
@@ -0,0 +1,277 @@
+//! Feed quality by exchange: how fast and how often each venue delivers books, and how many of
+//! them look wrong, compared side by side for choosing which venue to run the strategy on.
+//!
+//! A book counts as anomalous when a side is empty, when it is crossed, with the best bid at or
+//! above the best ask, or when its exchange timestamp is no later than the venue's previous one.
+
+use barter_data::event::MarketEvent;
+use barter_data::subscription::book::OrderBook;
+use barter_integration::model::Exchange;
+use chrono::DateTime;
+use chrono::Utc;
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::latency::Histogram;
+use crate::latency::BUCKETS;
+
+/// Something wrong with a book update.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Anomaly {
+    Empty,
+    Crossed,
+    OutOfOrder,
+}
+
+impl Anomaly {
+    pub const ALL: [Self; 3] = [Self::Empty, Self::Crossed, Self::OutOfOrder];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Empty => "empty",
+            Self::Crossed => "crossed",
+            Self::OutOfOrder => "out of order",
+        }
+    }
+}
+
+/// What is wrong with `book`, given the exchange timestamp of the venue's previous update.
+pub fn anomalies(
+    book: &OrderBook,
+    exchange_time: DateTime<Utc>,
+    previous: Option<DateTime<Utc>>,
+) -> Vec<Anomaly> {
+    let mut anomalies = Vec::new();
+    match (book.bids.levels.first(), book.asks.levels.first()) {
+        (Some(bid), Some(ask)) if bid.price >= ask.price => anomalies.push(Anomaly::Crossed),
+        (Some(_), Some(_)) => {}
+        _ => anomalies.push(Anomaly::Empty),
+    }
+    if previous.is_some_and(|previous| exchange_time <= previous) {
+        anomalies.push(Anomaly::OutOfOrder);
+    }
+    anomalies
+}
+
+/// One venue's feed quality since the bot started.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VenueSummary {
+    pub venue: String,
+    pub updates: u64,
+    /// Updates per second between the first and latest received.
+    pub updates_per_sec: f64,
+    /// Median and 99th percentile bucket of the time from exchange timestamp to receipt.
+    pub latency_p50_ms: Option<f64>,
+    pub latency_p99_ms: Option<f64>,
+    /// Updates with each [`Anomaly`], in the order of [`Anomaly::ALL`].
+    pub anomalies: [u64; Anomaly::ALL.len()],
+    /// Share of updates with any anomaly.
+    pub anomalous_pct: f64,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Venue {
+    updates: u64,
+    first_received: Option<DateTime<Utc>>,
+    last_received: Option<DateTime<Utc>>,
+    last_exchange_time: Option<DateTime<Utc>>,
+    latency: Histogram,
+    anomalies: [u64; Anomaly::ALL.len()],
+    anomalous: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct FeedQuality {
+    venues: BTreeMap<Exchange, Venue>,
+}
+
+impl FeedQuality {
+    /// Count a book update toward its exchange.
+    pub fn record(&mut self, event: &MarketEvent<OrderBook>) {
+        // Looked up before inserting, so known venues aren't cloned for every update
+        if !self.venues.contains_key(&event.exchange) {
+            self.venues.insert(event.exchange.clone(), Venue::default());
+        }
+        let venue = self
+            .venues
+            .get_mut(&event.exchange)
+            .expect("venue was just inserted");
+        let found = anomalies(&event.kind, event.exchange_time, venue.last_exchange_time);
+        for anomaly in &found {
+            venue.anomalies[*anomaly as usize] += 1;
+        }
+        if !found.is_empty() {
+            venue.anomalous += 1;
+        }
+
+        venue.updates += 1;
+        venue.first_received.get_or_insert(event.received_time);
+        venue.last_received = Some(event.received_time);
+        venue.last_exchange_time = venue.last_exchange_time.max(Some(event.exchange_time));
+        venue.latency.record(
+            (event.received_time - event.exchange_time)
+                .to_std()
+                .map_or(0.0, |latency| latency.as_secs_f64()),
+        );
+    }
+
+    /// Every venue's feed quality, by name.
+    pub fn summaries(&self) -> Vec<VenueSummary> {
+        let millis = |seconds: Option<f64>| seconds.map(|seconds| seconds * 1000.0);
+        self.venues
+            .iter()
+            .map(|(name, venue)| {
+                let elapsed = match (venue.first_received, venue.last_received) {
+                    (Some(first), Some(last)) => (last - first).num_milliseconds() as f64 / 1000.0,
+                    _ => 0.0,
+                };
+                VenueSummary {
+                    venue: name.to_string(),
+                    updates: venue.updates,
+                    updates_per_sec: if elapsed > 0.0 {
+                        venue.updates as f64 / elapsed
+                    } else {
+                        0.0
+                    },
+                    latency_p50_ms: millis(venue.latency.quantile(0.5)),
+                    latency_p99_ms: millis(venue.latency.quantile(0.99)),
+                    anomalies: venue.anomalies,
+                    anomalous_pct: venue.anomalous as f64 / venue.updates as f64 * 100.0,
+                }
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for FeedQuality {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bucket = |millis: Option<f64>| match millis {
+            Some(millis) if millis.is_finite() => format!("{millis}"),
+            Some(_) => format!(">{}", BUCKETS[BUCKETS.len() - 1] * 1000.0),
+            None => "-".to_owned(),
+        };
+        writeln!(f, "Feed quality by venue")?;
+        writeln!(
+            f,
+            "\n{:<16} {:>9} {:>9} {:>9} {:>9} {:>7} {:>8} {:>12} {:>11}",
+            "venue",
+            "updates",
+            "per sec",
+            "p50 ms",
+            "p99 ms",
+            Anomaly::Empty.name(),
+            Anomaly::Crossed.name(),
+            Anomaly::OutOfOrder.name(),
+            "anomalous %"
+        )?;
+        for summary in self.summaries() {
+            writeln!(
+                f,
+                "{:<16} {:>9} {:>9.2} {:>9} {:>9} {:>7} {:>8} {:>12} {:>11.2}",
+                summary.venue,
+                summary.updates,
+                summary.updates_per_sec,
+                bucket(summary.latency_p50_ms),
+                bucket(summary.latency_p99_ms),
+                summary.anomalies[Anomaly::Empty as usize],
+                summary.anomalies[Anomaly::Crossed as usize],
+                summary.anomalies[Anomaly::OutOfOrder as usize],
+                summary.anomalous_pct
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use barter_data::subscription::book::Level;
+    use barter_data::subscription::book::OrderBookSide;
+    use barter_integration::model::instrument::kind::InstrumentKind;
+    use barter_integration::model::instrument::Instrument;
+    use barter_integration::model::Side;
+    use chrono::TimeDelta;
+
+    use super::*;
+
+    fn book(bids: Vec<Level>, asks: Vec<Level>) -> OrderBook {
+        OrderBook {
+            last_update_time: DateTime::from_timestamp_millis(0).unwrap(),
+            bids: OrderBookSide::new(Side::Buy, bids),
+            asks: OrderBookSide::new(Side::Sell, asks),
+        }
+    }
+
+    /// A book update from `exchange` stamped `millis` in, received `latency_millis` later.
+    fn update(
+        exchange: &'static str,
+        millis: i64,
+        latency_millis: i64,
+        book: OrderBook,
+    ) -> MarketEvent<OrderBook> {
+        let exchange_time = DateTime::from_timestamp_millis(millis).unwrap();
+        MarketEvent {
+            exchange_time,
+            received_time: exchange_time + TimeDelta::milliseconds(latency_millis),
+            exchange: Exchange::from(exchange),
+            instrument: Instrument::from(("btc", "usd", InstrumentKind::Perpetual)),
+            kind: book,
+        }
+    }
+
+    #[test]
+    fn test_anomalies() {
+        let level = |price| Level::new(price, 1.0);
+        let time = DateTime::from_timestamp_millis(1_000).unwrap();
+        let normal = book(vec![level(99.0)], vec![level(100.0)]);
+        assert!(anomalies(&normal, time, None).is_empty());
+        assert!(anomalies(&normal, time, Some(time - TimeDelta::milliseconds(1))).is_empty());
+        assert_eq!(anomalies(&normal, time, Some(time)), [Anomaly::OutOfOrder]);
+        assert_eq!(
+            anomalies(&book(vec![level(100.0)], vec![level(100.0)]), time, None),
+            [Anomaly::Crossed]
+        );
+        assert_eq!(
+            anomalies(&book(vec![], vec![level(100.0)]), time, None),
+            [Anomaly::Empty]
+        );
+    }
+
+    #[test]
+    fn test_compare_venues() {
+        let level = |price| Level::new(price, 1.0);
+        let normal = || book(vec![level(99.0)], vec![level(100.0)]);
+        let mut quality = FeedQuality::default();
+        for second in 0..5 {
+            quality.record(&update("aevo", second * 1000, 20, normal()));
+        }
+        quality.record(&update("binance", 0, 3, normal()));
+        quality.record(&update("binance", 500, 3, book(vec![], vec![])));
+        // Arrives after a later book and is crossed too
+        quality.record(&update(
+            "binance",
+            400,
+            3,
+            book(vec![level(101.0)], vec![level(100.0)]),
+        ));
+        quality.record(&update("binance", 1000, 3, normal()));
+
+        let summaries = quality.summaries();
+        assert_eq!(summaries.len(), 2);
+        let aevo = &summaries[0];
+        assert_eq!((aevo.venue.as_str(), aevo.updates), ("aevo", 5));
+        assert_eq!(aevo.updates_per_sec, 1.25);
+        assert_eq!(aevo.latency_p50_ms, Some(25.0));
+        assert_eq!(aevo.anomalous_pct, 0.0);
+
+        let binance = &summaries[1];
+        assert_eq!(binance.updates_per_sec, 4.0);
+        assert_eq!(binance.latency_p99_ms, Some(5.0));
+        assert_eq!(binance.anomalies, [1, 1, 1]);
+        assert_eq!(binance.anomalous_pct, 50.0);
+
+        let table = quality.to_string();
+        assert!(table.contains("out of order"));
+        assert!(table.lines().any(|line| line.starts_with("binance")));
+    }
+}
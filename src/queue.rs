@@ -0,0 +1,154 @@
+//! Where a resting order stands in its price level's queue. Paper orders don't show in the book,
+//! so an order joining a level is taken to join the back of it, everything already resting there
+//! ahead of it, and the size ahead is worked down from what the feed shows happening at its price:
+//!
+//! - Public trades at the price take from the front of the queue, and one through the price
+//!   means the whole level was taken.
+//! - When the level shrinks by more than was traded on it, the rest was cancelled, taken to be
+//!   spread evenly through the queue so only the share ahead of the order moves it forward.
+//!   Growth joins behind it.
+//!
+//! Only the change an L2 book shows at each update is known, so cancels and trades between
+//! updates are told apart only as well as the trades feed keeps up.
+
+use barter_data::subscription::book::OrderBook;
+use barter_data::subscription::book::OrderBookSide;
+use barter_integration::model::Side;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueuePosition {
+    side: Side,
+    price: f64,
+    /// Size estimated to rest ahead of the order.
+    ahead: f64,
+    /// Size resting at the order's price on the last book.
+    level: f64,
+    /// Size traded at the order's price since the last book.
+    traded: f64,
+}
+
+/// The size resting at `price` in `levels`, zero if the level is within the listed depth but
+/// empty, or `None` if the price is past the deepest listed level.
+fn level(levels: &OrderBookSide, side: Side, price: f64) -> Option<f64> {
+    if let Some(level) = levels.levels.iter().find(|level| level.price == price) {
+        return Some(level.amount);
+    }
+    let deepest = levels.levels.last().map(|level| level.price);
+    let listed = deepest.is_some_and(|deepest| match side {
+        Side::Buy => price > deepest,
+        Side::Sell => price < deepest,
+    });
+    listed.then_some(0.0)
+}
+
+impl QueuePosition {
+    /// Join the back of the queue at `price` on `side` of `book`.
+    pub fn join(side: Side, price: f64, book: &OrderBook) -> Self {
+        let size = level(Self::levels(side, book), side, price).unwrap_or_default();
+        Self {
+            side,
+            price,
+            ahead: size,
+            level: size,
+            traded: 0.0,
+        }
+    }
+
+    fn levels(side: Side, book: &OrderBook) -> &OrderBookSide {
+        match side {
+            Side::Buy => &book.bids,
+            Side::Sell => &book.asks,
+        }
+    }
+
+    /// The size estimated to rest ahead of the order, filling it once worked through.
+    pub fn ahead(&self) -> f64 {
+        self.ahead
+    }
+
+    /// Count a public trade of `amount` at `price`, taken on `side`.
+    pub fn on_trade(&mut self, side: Side, price: f64, amount: f64) {
+        // Only trades taking the other side reach a resting order: sells hit resting buys
+        if side == self.side || !amount.is_finite() || amount <= 0.0 {
+            return;
+        }
+        let through = match self.side {
+            Side::Buy => price < self.price,
+            Side::Sell => price > self.price,
+        };
+        if through {
+            self.ahead = 0.0;
+        } else if price == self.price {
+            self.ahead = (self.ahead - amount).max(0.0);
+            self.traded += amount;
+        }
+    }
+
+    /// Move the order forward by the cancels ahead of it on `book`.
+    pub fn on_book(&mut self, book: &OrderBook) {
+        let Some(size) = level(Self::levels(self.side, book), self.side, self.price) else {
+            return;
+        };
+        let remaining = (self.level - self.traded).max(0.0);
+        let cancelled = (remaining - size).max(0.0);
+        if remaining > 0.0 {
+            self.ahead -= cancelled * self.ahead / remaining;
+        }
+        self.ahead = self.ahead.clamp(0.0, size);
+        self.level = size;
+        self.traded = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+
+    use super::*;
+
+    fn book(bids: &[(f64, f64)]) -> OrderBook {
+        OrderBook {
+            last_update_time: DateTime::from_timestamp_millis(0).unwrap(),
+            bids: OrderBookSide::new(Side::Buy, bids.iter().copied()),
+            asks: OrderBookSide::new(Side::Sell, [(101.0, 1.0)]),
+        }
+    }
+
+    #[test]
+    fn test_trades_and_cancels_work_the_queue() {
+        let mut queue = QueuePosition::join(Side::Buy, 100.0, &book(&[(100.0, 10.0), (99.0, 5.0)]));
+        assert_eq!(queue.ahead(), 10.0);
+
+        // Buys and trades at other prices leave it, sells at the price take from the front
+        queue.on_trade(Side::Buy, 100.0, 3.0);
+        queue.on_trade(Side::Sell, 100.5, 3.0);
+        queue.on_trade(Side::Sell, 100.0, 2.0);
+        assert_eq!(queue.ahead(), 8.0);
+
+        // Of the 8 left, 4 more went than were traded, all cancelled ahead of an order at the
+        // back. Another 6 then join behind it, so of the next 5 cancelled only 4 in 10 are ahead
+        queue.on_book(&book(&[(100.0, 4.0), (99.0, 5.0)]));
+        assert_eq!(queue.ahead(), 4.0);
+        queue.on_book(&book(&[(100.0, 10.0), (99.0, 5.0)]));
+        assert_eq!(queue.ahead(), 4.0);
+        queue.on_book(&book(&[(100.0, 5.0), (99.0, 5.0)]));
+        assert_eq!(queue.ahead(), 2.0);
+
+        // Past the listed depth nothing is known, a trade through takes the level
+        queue.on_book(&book(&[(101.0, 1.0)]));
+        assert_eq!(queue.ahead(), 2.0);
+        queue.on_trade(Side::Sell, 99.5, 1.0);
+        assert_eq!(queue.ahead(), 0.0);
+    }
+
+    #[test]
+    fn test_emptied_level_clears_the_queue() {
+        let mut queue = QueuePosition::join(Side::Buy, 100.0, &book(&[(100.0, 10.0), (99.0, 5.0)]));
+        queue.on_book(&book(&[(100.5, 1.0), (99.0, 5.0)]));
+        assert_eq!(queue.ahead(), 0.0);
+
+        // Joining a price with nothing resting is to be first in line
+        let queue = QueuePosition::join(Side::Buy, 99.5, &book(&[(100.0, 10.0), (99.0, 5.0)]));
+        assert_eq!(queue.ahead(), 0.0);
+    }
+}
@@ -0,0 +1,355 @@
+//! Applying config changes to the running bot without a restart, so market data subscriptions
+//! and open positions survive a retune.
+//!
+//! Only the tunable sections are applied: `[strategy]`, `[allocation]`, `[risk]` and `[entries]`.
+//! Changed strategies are rebuilt and swapped into the running sleeves, matched to members in
+//! order, and start over with fresh internal state while every sleeve keeps its cash, positions
+//! and weight. Changed entry policies apply from the next buy, so lots already held keep their
+//! size. The number of members and whether they trade `independent`ly can't change while
+//! running.
+
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::SystemTime;
+use tokio::sync::mpsc;
+use tracing::info;
+use tracing::warn;
+
+use crate::allocation::Allocator;
+use crate::allocation::ReconfigureError;
+use crate::config::AllocationConfig;
+use crate::config::Config;
+use crate::config::ConfigSource;
+use crate::config::EntriesConfig;
+use crate::config::StrategyConfig;
+use crate::control;
+use crate::control::Control;
+use crate::control::ControlError;
+use crate::strategy::plugin::PluginRegistry;
+use crate::strategy::StrategyError;
+use crate::RiskParams;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReloadError {
+    #[error(transparent)]
+    Risk(#[from] ControlError),
+
+    #[error(transparent)]
+    Strategy(#[from] StrategyError),
+
+    #[error(transparent)]
+    Reconfigure(#[from] ReconfigureError),
+}
+
+/// Reloads the config on SIGHUP and, when polling, whenever the modification time of one of its
+/// files changes. Configs that fail to load are logged and skipped, leaving the running config in
+/// place.
+#[derive(Debug)]
+pub struct ConfigWatcher {
+    receiver: mpsc::Receiver<Config>,
+}
+
+impl ConfigWatcher {
+    pub fn spawn(source: ConfigSource, interval: Option<Duration>) -> Self {
+        let files = source.files();
+        let (sender, receiver) = mpsc::channel(1);
+        tokio::spawn(async move {
+            let mut poll = interval.map(|interval| {
+                let mut poll = tokio::time::interval(interval);
+                poll.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+                poll
+            });
+            let mut hangup = Hangup::listen();
+            let mut last_modified = modified(&files);
+            loop {
+                tokio::select! {
+                    _ = hangup.recv() => info!("Received SIGHUP, reloading config"),
+                    _ = tick(&mut poll) => {
+                        let current = modified(&files);
+                        if current == last_modified {
+                            continue;
+                        }
+                        last_modified = current;
+                        info!("Config file changed, reloading");
+                    }
+                }
+                match source.load() {
+                    Ok(config) => {
+                        if sender.send(config).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(error) => warn!("Keeping the running config: {}", error),
+                }
+            }
+        });
+        Self { receiver }
+    }
+
+    /// The next config loaded, or `None` once the watcher has stopped.
+    pub async fn changed(&mut self) -> Option<Config> {
+        self.receiver.recv().await
+    }
+}
+
+fn modified(files: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    files
+        .iter()
+        .map(|path| {
+            std::fs::metadata(path)
+                .and_then(|meta| meta.modified())
+                .ok()
+        })
+        .collect()
+}
+
+async fn tick(poll: &mut Option<tokio::time::Interval>) {
+    match poll {
+        Some(poll) => {
+            poll.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// SIGHUP, on platforms that have it.
+struct Hangup {
+    #[cfg(unix)]
+    signal: Option<tokio::signal::unix::Signal>,
+}
+
+impl Hangup {
+    fn listen() -> Self {
+        Self {
+            #[cfg(unix)]
+            signal: tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+                .map_err(|error| warn!("Failed to listen for SIGHUP: {}", error))
+                .ok(),
+        }
+    }
+
+    async fn recv(&mut self) {
+        #[cfg(unix)]
+        if let Some(signal) = &mut self.signal {
+            if signal.recv().await.is_some() {
+                return;
+            }
+        }
+        std::future::pending().await
+    }
+}
+
+/// Tracks the tunable sections last applied, so a reload only touches what changed in the file
+/// and leaves risk params set through the control surfaces alone otherwise.
+#[derive(Debug)]
+pub struct Reloader {
+    strategy: StrategyConfig,
+    allocation: AllocationConfig,
+    risk: RiskParams,
+    entries: EntriesConfig,
+}
+
+impl Reloader {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            strategy: config.strategy.clone(),
+            allocation: config.allocation.clone(),
+            risk: config.risk,
+            entries: config.entries,
+        }
+    }
+
+    /// Apply the tunable sections of `config` that changed and name them. Everything is checked
+    /// before anything is applied, so on error the bot carries on exactly as before.
+    pub fn apply(
+        &mut self,
+        config: &Config,
+        allocator: &mut Allocator,
+        control: &Control,
+        plugins: &PluginRegistry,
+    ) -> Result<Vec<&'static str>, ReloadError> {
+        let risk = config.risk != self.risk;
+        if risk {
+            control::check_risk(&config.risk)?;
+        }
+        let allocation = config.allocation != self.allocation;
+        if config.allocation.independent != self.allocation.independent {
+            return Err(ReconfigureError::Independent.into());
+        }
+        let members = if config.strategy != self.strategy {
+            Some(Allocator::build_members(
+                &config.strategy,
+                config.allocation.independent,
+                plugins,
+            )?)
+        } else {
+            None
+        };
+
+        let mut changed = Vec::new();
+        if let Some(members) = members {
+            allocator.replace_strategies(members)?;
+            self.strategy = config.strategy.clone();
+            changed.push("strategy");
+        }
+        if allocation {
+            allocator.set_allocation(config.allocation.clone())?;
+            self.allocation = config.allocation.clone();
+            changed.push("allocation");
+        }
+        if risk {
+            control.set_risk(config.risk);
+            self.risk = config.risk;
+            changed.push("risk");
+        }
+        if config.entries != self.entries {
+            allocator.set_entries(config.entries);
+            self.entries = config.entries;
+            changed.push("entries");
+        }
+        Ok(changed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+    use crate::config::MemberConfig;
+    use crate::strategy::imbalance::ImbalanceParams;
+    use crate::strategy::StrategyKind;
+    use crate::Lot;
+
+    fn independent(members: usize) -> Config {
+        let mut config = Config::default();
+        config.allocation.independent = true;
+        config.strategy.members = vec![
+            MemberConfig {
+                weight: 1.0,
+                strategy: StrategyKind::default(),
+            };
+            members
+        ];
+        config
+    }
+
+    fn setup(config: &Config) -> (Allocator, Reloader, Control, PluginRegistry) {
+        let plugins = PluginRegistry::default();
+        let allocator =
+            Allocator::from_config(config, &plugins, 1000.0, "BTC/USDT", Utc::now()).unwrap();
+        (
+            allocator,
+            Reloader::new(config),
+            Control::default(),
+            plugins,
+        )
+    }
+
+    #[test]
+    fn test_apply_keeps_positions() {
+        let config = independent(2);
+        let (mut allocator, mut reloader, control, plugins) = setup(&config);
+        allocator.sleeves_mut()[1]
+            .state
+            .positions
+            .push(Lot::at(100.0));
+
+        assert!(reloader
+            .apply(&config, &mut allocator, &control, &plugins)
+            .unwrap()
+            .is_empty());
+
+        let mut retuned = config.clone();
+        retuned.strategy.members[1].strategy = StrategyKind::Imbalance(ImbalanceParams {
+            oir_threshold: 0.9,
+            ..ImbalanceParams::default()
+        });
+        retuned.allocation.min_weight = 0.2;
+        retuned.risk.take_profit = 0.005;
+        retuned.entries.max_lots = Some(1);
+        let changed = reloader
+            .apply(&retuned, &mut allocator, &control, &plugins)
+            .unwrap();
+        assert_eq!(changed, ["strategy", "allocation", "risk", "entries"]);
+        assert_eq!(allocator.sleeves()[1].state.positions, [Lot::at(100.0)]);
+        assert_eq!(allocator.sleeves()[0].state.cash, 500.0);
+        assert_eq!(control.risk().take_profit, 0.005);
+
+        // Risk set through a control surface survives a reload that doesn't touch it
+        control.set_risk(RiskParams {
+            take_profit: 0.03,
+            ..control.risk()
+        });
+        retuned.allocation.min_weight = 0.1;
+        reloader
+            .apply(&retuned, &mut allocator, &control, &plugins)
+            .unwrap();
+        assert_eq!(control.risk().take_profit, 0.03);
+    }
+
+    #[test]
+    fn test_apply_rejects_without_changing_anything() {
+        let config = independent(2);
+        let (mut allocator, mut reloader, control, plugins) = setup(&config);
+
+        let mut invalid = independent(3);
+        invalid.risk.stop_loss = 0.5;
+        assert!(matches!(
+            reloader.apply(&invalid, &mut allocator, &control, &plugins),
+            Err(ReloadError::Reconfigure(ReconfigureError::SleeveCount {
+                running: 2,
+                configured: 3
+            }))
+        ));
+        assert_eq!(control.risk(), RiskParams::default());
+
+        let mut combined = config.clone();
+        combined.allocation.independent = false;
+        assert!(matches!(
+            reloader.apply(&combined, &mut allocator, &control, &plugins),
+            Err(ReloadError::Reconfigure(ReconfigureError::Independent))
+        ));
+
+        let mut risky = config.clone();
+        risky.risk.stop_loss = 1.5;
+        risky.allocation.min_weight = 0.2;
+        assert!(matches!(
+            reloader.apply(&risky, &mut allocator, &control, &plugins),
+            Err(ReloadError::Risk(_))
+        ));
+        assert_eq!(control.risk(), RiskParams::default());
+        assert_eq!(
+            reloader
+                .apply(&config, &mut allocator, &control, &plugins)
+                .unwrap(),
+            Vec::<&str>::new()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_watcher_reloads_changed_file() {
+        let path = std::env::temp_dir().join(format!(
+            "fit-reload-{}-{}",
+            std::process::id(),
+            SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        std::fs::write(&path, "[risk]\ntake_profit = 0.01\n").unwrap();
+        let mut watcher = ConfigWatcher::spawn(
+            ConfigSource::new(&path, None),
+            Some(Duration::from_millis(10)),
+        );
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        std::fs::write(&path, "[risk]\ntake_profit = 0.005\n").unwrap();
+        let config = tokio::time::timeout(Duration::from_secs(5), watcher.changed())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(config.risk.take_profit, 0.005);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,226 @@
+use barter_integration::model::Side;
+use chrono::DateTime;
+use chrono::TimeDelta;
+use chrono::Utc;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::event::Execution;
+use crate::Fill;
+
+/// Seconds after each fill the mid is marked at.
+pub const HORIZONS_SECS: [i64; 3] = [1, 5, 30];
+
+/// How the mid moved after the fills of one strategy's buys or sells on one venue, in basis
+/// points of the mid at the fill. Positive moved the fill's way, negative against it: filled
+/// just before the market went the other way, by someone who knew better.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdverseSummary {
+    pub strategy: String,
+    pub side: Side,
+    pub venue: String,
+    pub fills: usize,
+    /// The mean move at each of [`HORIZONS_SECS`], once any fill has been marked at it.
+    pub mean_bps: [Option<f64>; 3],
+}
+
+/// A fill awaiting the mid at each horizon.
+#[derive(Debug, Clone, PartialEq)]
+struct Pending {
+    key: (String, Side, String),
+    time: DateTime<Utc>,
+    mid_price: f64,
+    marked: [bool; 3],
+}
+
+/// Every fill's markout against the mid at each of [`HORIZONS_SECS`], by strategy, side and
+/// venue.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AdverseSelection {
+    /// The latest mid of each symbol.
+    mids: HashMap<String, f64>,
+    pending: HashMap<String, Vec<Pending>>,
+    fills: BTreeMap<(String, Side, String), usize>,
+    samples: BTreeMap<(String, Side, String), [Vec<f64>; 3]>,
+}
+
+impl AdverseSelection {
+    /// Take `mid_price` as `symbol`'s at `time`, marking every fill a horizon has passed for.
+    pub fn mark(&mut self, symbol: &str, time: DateTime<Utc>, mid_price: f64) {
+        if !mid_price.is_finite() || mid_price <= 0.0 {
+            return;
+        }
+        self.mids.insert(symbol.to_owned(), mid_price);
+        let Some(pending) = self.pending.get_mut(symbol) else {
+            return;
+        };
+        for fill in pending.iter_mut() {
+            for (index, secs) in HORIZONS_SECS.iter().enumerate() {
+                if fill.marked[index] || time - fill.time < TimeDelta::seconds(*secs) {
+                    continue;
+                }
+                let moved = (mid_price - fill.mid_price) / fill.mid_price * 10_000.0;
+                let bps = match fill.key.1 {
+                    Side::Buy => moved,
+                    Side::Sell => -moved,
+                };
+                self.samples.entry(fill.key.clone()).or_default()[index].push(bps);
+                fill.marked[index] = true;
+            }
+        }
+        pending.retain(|fill| !fill.marked.iter().all(|marked| *marked));
+    }
+
+    /// Start marking `fill` of `strategy` on `symbol` at `time`, against the mid it was filled at.
+    pub fn record(
+        &mut self,
+        time: DateTime<Utc>,
+        symbol: &str,
+        strategy: &str,
+        execution: &Execution,
+        fill: &Fill,
+    ) {
+        // Nothing to measure against without a book before the fill
+        let Some(mid_price) = self.mids.get(symbol).copied() else {
+            return;
+        };
+        let key = (strategy.to_owned(), fill.side, execution.venue.clone());
+        *self.fills.entry(key.clone()).or_default() += 1;
+        self.pending
+            .entry(symbol.to_owned())
+            .or_default()
+            .push(Pending {
+                key,
+                time,
+                mid_price,
+                marked: [false; 3],
+            });
+    }
+
+    pub fn summaries(&self) -> Vec<AdverseSummary> {
+        self.fills
+            .iter()
+            .map(|((strategy, side, venue), fills)| {
+                let samples = self.samples.get(&(strategy.clone(), *side, venue.clone()));
+                let mean_bps = std::array::from_fn(|index| {
+                    let bps = &samples?[index];
+                    (!bps.is_empty()).then(|| bps.iter().sum::<f64>() / bps.len() as f64)
+                });
+                AdverseSummary {
+                    strategy: strategy.clone(),
+                    side: *side,
+                    venue: venue.clone(),
+                    fills: *fills,
+                    mean_bps,
+                }
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for AdverseSelection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Mid moves after fills, in bps, negative against the fill"
+        )?;
+        writeln!(
+            f,
+            "\n{:<20} {:<5} {:<16} {:>7} {:>9} {:>9} {:>9}",
+            "strategy", "side", "venue", "fills", "1s", "5s", "30s"
+        )?;
+        for summary in self.summaries() {
+            let [one, five, thirty] = summary.mean_bps.map(|bps| match bps {
+                Some(bps) => format!("{bps:.2}"),
+                None => "-".to_owned(),
+            });
+            writeln!(
+                f,
+                "{:<20} {:<5} {:<16} {:>7} {:>9} {:>9} {:>9}",
+                summary.strategy,
+                match summary.side {
+                    Side::Buy => "buy",
+                    Side::Sell => "sell",
+                },
+                summary.venue,
+                summary.fills,
+                one,
+                five,
+                thirty
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::OrderType;
+
+    fn at(millis: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp_millis(1_717_200_000_000 + millis).unwrap()
+    }
+
+    fn fill(side: Side) -> Fill {
+        Fill {
+            side,
+            price: 100.0,
+            size: 1.0,
+            fee: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_markouts_by_strategy_side_and_venue() {
+        let mut adverse = AdverseSelection::default();
+        let paper = Execution::paper(OrderType::Limit, 100.0);
+        let gateway = Execution {
+            venue: "binance".to_owned(),
+            ..paper.clone()
+        };
+        // No book to measure against yet
+        adverse.record(at(0), "BTC/USDT", "imbalance", &paper, &fill(Side::Buy));
+
+        adverse.mark("BTC/USDT", at(0), 100.0);
+        adverse.record(at(0), "BTC/USDT", "imbalance", &paper, &fill(Side::Buy));
+        adverse.record(at(0), "BTC/USDT", "imbalance", &paper, &fill(Side::Sell));
+        adverse.record(at(0), "BTC/USDT", "rls", &gateway, &fill(Side::Buy));
+        // The mid drops right after, and is only seen again past the first horizon
+        adverse.mark("ETH/USDT", at(1500), 50.0);
+        adverse.mark("BTC/USDT", at(1500), 99.9);
+        adverse.mark("BTC/USDT", at(5000), 100.2);
+
+        let summaries = adverse.summaries();
+        let found: Vec<_> = summaries
+            .iter()
+            .map(|summary| {
+                (
+                    summary.strategy.as_str(),
+                    summary.side,
+                    summary.venue.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("imbalance", Side::Buy, "paper"),
+                ("imbalance", Side::Sell, "paper"),
+                ("rls", Side::Buy, "binance")
+            ]
+        );
+        let buys = summaries[0].mean_bps;
+        assert!((buys[0].unwrap() + 10.0).abs() < 1e-9);
+        assert!((buys[1].unwrap() - 20.0).abs() < 1e-9);
+        assert_eq!((summaries[0].fills, buys[2]), (1, None));
+        // The sell was right to sell into the drop
+        assert!((summaries[1].mean_bps[0].unwrap() - 10.0).abs() < 1e-9);
+
+        adverse.mark("BTC/USDT", at(30_000), 100.0);
+        assert_eq!(adverse.summaries()[2].mean_bps[2], Some(0.0));
+        assert!(adverse.pending.values().all(Vec::is_empty));
+        assert!(adverse.to_string().contains("rls                  buy"));
+    }
+}
@@ -0,0 +1,205 @@
+use barter_integration::model::Side;
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::Fill;
+
+/// Realized results of one strategy on one symbol, or an aggregate of several.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Attribution {
+    pub trades: usize,
+    pub wins: usize,
+    pub gross_pnl: f64,
+    /// Every fee paid, including on entries still open.
+    pub fees: f64,
+}
+
+impl Attribution {
+    pub fn net_pnl(&self) -> f64 {
+        self.gross_pnl - self.fees
+    }
+
+    pub fn win_rate_pct(&self) -> f64 {
+        if self.trades > 0 {
+            self.wins as f64 / self.trades as f64 * 100.0
+        } else {
+            0.0
+        }
+    }
+
+    fn add(&mut self, other: &Self) {
+        self.trades += other.trades;
+        self.wins += other.wins;
+        self.gross_pnl += other.gross_pnl;
+        self.fees += other.fees;
+    }
+}
+
+/// Attribution of every fill to the strategy and symbol it came from. Round trips are paired
+/// last in, first out per sleeve and symbol, as the trading state closes positions.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Ledger {
+    entries: BTreeMap<(String, String), Attribution>,
+    open: BTreeMap<(usize, String), Vec<Fill>>,
+}
+
+impl Ledger {
+    pub fn record(&mut self, strategy: &str, sleeve: usize, symbol: &str, fill: &Fill) {
+        let attribution = self
+            .entries
+            .entry((strategy.to_owned(), symbol.to_owned()))
+            .or_default();
+        attribution.fees += fill.fee;
+        let open = self.open.entry((sleeve, symbol.to_owned())).or_default();
+        match fill.side {
+            Side::Buy => open.push(*fill),
+            Side::Sell => {
+                if let Some(entry) = open.pop() {
+                    let pnl = (fill.price - entry.price) * fill.size;
+                    attribution.trades += 1;
+                    // A win has to cover the fees paid on both sides
+                    if pnl > entry.fee + fill.fee {
+                        attribution.wins += 1;
+                    }
+                    attribution.gross_pnl += pnl;
+                }
+            }
+        }
+    }
+
+    /// Results per strategy and symbol.
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &str, &Attribution)> {
+        self.entries
+            .iter()
+            .map(|((strategy, symbol), attribution)| {
+                (strategy.as_str(), symbol.as_str(), attribution)
+            })
+    }
+
+    /// Results per strategy, across symbols.
+    pub fn by_strategy(&self) -> BTreeMap<&str, Attribution> {
+        self.aggregate(|strategy, _| strategy)
+    }
+
+    /// Results per symbol, across strategies.
+    pub fn by_symbol(&self) -> BTreeMap<&str, Attribution> {
+        self.aggregate(|_, symbol| symbol)
+    }
+
+    pub fn total(&self) -> Attribution {
+        let mut total = Attribution::default();
+        for attribution in self.entries.values() {
+            total.add(attribution);
+        }
+        total
+    }
+
+    fn aggregate<'a>(
+        &'a self,
+        key: impl Fn(&'a str, &'a str) -> &'a str,
+    ) -> BTreeMap<&'a str, Attribution> {
+        let mut aggregates: BTreeMap<&str, Attribution> = BTreeMap::new();
+        for (strategy, symbol, attribution) in self.entries() {
+            aggregates
+                .entry(key(strategy, symbol))
+                .or_default()
+                .add(attribution);
+        }
+        aggregates
+    }
+}
+
+impl fmt::Display for Ledger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "PnL attribution")?;
+        header(f, "strategy / symbol")?;
+        for (strategy, symbol, attribution) in self.entries() {
+            row(f, &format!("{strategy} / {symbol}"), attribution)?;
+        }
+        header(f, "strategy")?;
+        for (strategy, attribution) in self.by_strategy() {
+            row(f, strategy, &attribution)?;
+        }
+        header(f, "symbol")?;
+        for (symbol, attribution) in self.by_symbol() {
+            row(f, symbol, &attribution)?;
+        }
+        row(f, "total", &self.total())
+    }
+}
+
+fn header(f: &mut fmt::Formatter<'_>, name: &str) -> fmt::Result {
+    writeln!(
+        f,
+        "\n{:<32} {:>7} {:>6} {:>12} {:>10} {:>12}",
+        name, "trades", "win %", "gross pnl", "fees", "net pnl"
+    )
+}
+
+fn row(f: &mut fmt::Formatter<'_>, name: &str, attribution: &Attribution) -> fmt::Result {
+    writeln!(
+        f,
+        "{:<32} {:>7} {:>6.1} {:>12.2} {:>10.2} {:>12.2}",
+        name,
+        attribution.trades,
+        attribution.win_rate_pct(),
+        attribution.gross_pnl,
+        attribution.fees,
+        attribution.net_pnl()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fill(side: Side, price: f64) -> Fill {
+        Fill {
+            side,
+            price,
+            size: 1.0,
+            fee: 0.5,
+        }
+    }
+
+    #[test]
+    fn test_attribution_by_strategy_and_symbol() {
+        let mut ledger = Ledger::default();
+        ledger.record("imbalance", 0, "BTC/USDT", &fill(Side::Buy, 100.0));
+        ledger.record("rls", 1, "BTC/USDT", &fill(Side::Buy, 100.0));
+        ledger.record("imbalance", 0, "ETH/USDT", &fill(Side::Buy, 50.0));
+        // Each sell closes its own strategy's and symbol's entry
+        ledger.record("imbalance", 0, "BTC/USDT", &fill(Side::Sell, 110.0));
+        ledger.record("rls", 1, "BTC/USDT", &fill(Side::Sell, 95.0));
+        ledger.record("imbalance", 0, "ETH/USDT", &fill(Side::Sell, 52.0));
+
+        let entries: Vec<_> = ledger
+            .entries()
+            .map(|(strategy, symbol, attribution)| (strategy, symbol, attribution.gross_pnl))
+            .collect();
+        assert_eq!(
+            entries,
+            [
+                ("imbalance", "BTC/USDT", 10.0),
+                ("imbalance", "ETH/USDT", 2.0),
+                ("rls", "BTC/USDT", -5.0)
+            ]
+        );
+
+        let by_strategy = ledger.by_strategy();
+        assert_eq!(by_strategy["imbalance"].trades, 2);
+        assert_eq!(by_strategy["imbalance"].wins, 2);
+        assert_eq!(by_strategy["imbalance"].net_pnl(), 10.0);
+        assert_eq!(by_strategy["rls"].net_pnl(), -6.0);
+
+        let by_symbol = ledger.by_symbol();
+        assert_eq!(by_symbol["BTC/USDT"].trades, 2);
+        assert_eq!(by_symbol["BTC/USDT"].win_rate_pct(), 50.0);
+        assert_eq!(by_symbol["ETH/USDT"].gross_pnl, 2.0);
+
+        let total = ledger.total();
+        assert_eq!(total.trades, 3);
+        assert_eq!(total.fees, 3.0);
+        assert_eq!(total.net_pnl(), 4.0);
+    }
+}
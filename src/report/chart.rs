@@ -0,0 +1,164 @@
+use barter_integration::model::Side;
+use chrono::Duration;
+use plotters::coord::Shift;
+use plotters::prelude::*;
+use std::path::Path;
+use std::path::PathBuf;
+
+use super::equity::EquityCurve;
+
+const SIZE: (u32, u32) = (1280, 800);
+
+#[derive(Debug, thiserror::Error)]
+pub enum ChartError {
+    #[error("{} is neither a .png nor an .svg file", .0.display())]
+    Format(PathBuf),
+
+    #[error("no equity updates to chart")]
+    Empty,
+
+    #[error("failed to draw chart: {0}")]
+    Draw(String),
+}
+
+/// Render `curve` to `path`, as a PNG or SVG by its extension: the equity curve with buys and
+/// sells marked on top, the drawdown from its running peak below.
+pub fn render(curve: &EquityCurve, path: &Path) -> Result<(), ChartError> {
+    if curve.points().is_empty() {
+        return Err(ChartError::Empty);
+    }
+    let extension = path.extension().and_then(|extension| extension.to_str());
+    match extension.map(str::to_ascii_lowercase).as_deref() {
+        Some("png") => draw(BitMapBackend::new(path, SIZE).into_drawing_area(), curve),
+        Some("svg") => draw(SVGBackend::new(path, SIZE).into_drawing_area(), curve),
+        _ => Err(ChartError::Format(path.to_owned())),
+    }
+}
+
+fn draw<DB: DrawingBackend>(
+    root: DrawingArea<DB, Shift>,
+    curve: &EquityCurve,
+) -> Result<(), ChartError> {
+    let error = |error: DrawingAreaErrorKind<DB::ErrorType>| ChartError::Draw(error.to_string());
+    let points = curve.points();
+    let start = points[0].time;
+    // A single update still needs a span of time to draw across
+    let end = points[points.len() - 1]
+        .time
+        .max(start + Duration::seconds(1));
+    let (low, high) = points
+        .iter()
+        .fold((f64::MAX, f64::MIN), |(low, high), point| {
+            (low.min(point.equity), high.max(point.equity))
+        });
+    let margin = ((high - low) * 0.05).max(high.abs() * 1e-4).max(1e-9);
+    let max_drawdown = curve.max_drawdown_pct().max(0.01) * 1.1;
+
+    root.fill(&WHITE).map_err(error)?;
+    let (upper, lower) = root.split_vertically(SIZE.1 * 2 / 3);
+
+    let mut equity = ChartBuilder::on(&upper)
+        .caption("Equity", ("sans-serif", 24))
+        .margin(12)
+        .x_label_area_size(32)
+        .y_label_area_size(80)
+        .build_cartesian_2d(start..end, (low - margin)..(high + margin))
+        .map_err(error)?;
+    equity
+        .configure_mesh()
+        .x_label_formatter(&|time| time.format("%m-%d %H:%M").to_string())
+        .y_desc("portfolio value")
+        .draw()
+        .map_err(error)?;
+    equity
+        .draw_series(LineSeries::new(
+            points.iter().map(|point| (point.time, point.equity)),
+            &BLUE,
+        ))
+        .map_err(error)?;
+    equity
+        .draw_series(curve.trades().iter().map(|trade| {
+            let color = match trade.side {
+                Side::Buy => GREEN,
+                Side::Sell => RED,
+            };
+            TriangleMarker::new((trade.time, trade.equity), 5, color.filled())
+        }))
+        .map_err(error)?;
+
+    let mut drawdown = ChartBuilder::on(&lower)
+        .caption("Drawdown", ("sans-serif", 24))
+        .margin(12)
+        .x_label_area_size(32)
+        .y_label_area_size(80)
+        .build_cartesian_2d(start..end, -max_drawdown..0.0)
+        .map_err(error)?;
+    drawdown
+        .configure_mesh()
+        .x_label_formatter(&|time| time.format("%m-%d %H:%M").to_string())
+        .y_desc("% below peak")
+        .draw()
+        .map_err(error)?;
+    drawdown
+        .draw_series(
+            AreaSeries::new(
+                points.iter().map(|point| (point.time, -point.drawdown_pct)),
+                0.0,
+                RED.mix(0.3),
+            )
+            .border_style(RED),
+        )
+        .map_err(error)?;
+
+    root.present().map_err(error)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+    use std::time::SystemTime;
+
+    use super::*;
+
+    fn temp_file(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "fit-{}-{}-{}",
+            name,
+            std::process::id(),
+            SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ))
+    }
+
+    #[test]
+    fn test_render_by_extension() {
+        let start = DateTime::from_timestamp_millis(1_717_200_000_000).unwrap();
+        let mut curve = EquityCurve::default();
+        assert!(matches!(
+            render(&curve, Path::new("chart.svg")),
+            Err(ChartError::Empty)
+        ));
+        for (seconds, equity) in [(0, 1000.0), (60, 1010.0), (120, 990.0), (180, 1005.0)] {
+            if seconds == 60 {
+                curve.record_fill(start + Duration::seconds(seconds), Side::Buy);
+            }
+            curve.record_equity(start + Duration::seconds(seconds), equity);
+        }
+
+        let svg = temp_file("chart").with_extension("svg");
+        render(&curve, &svg).unwrap();
+        assert!(std::fs::read_to_string(&svg).unwrap().contains("<svg"));
+        let png = temp_file("chart").with_extension("png");
+        render(&curve, &png).unwrap();
+        assert!(std::fs::read(&png).unwrap().starts_with(b"\x89PNG"));
+        assert!(matches!(
+            render(&curve, Path::new("chart.txt")),
+            Err(ChartError::Format(_))
+        ));
+
+        std::fs::remove_file(svg).unwrap();
+        std::fs::remove_file(png).unwrap();
+    }
+}
@@ -0,0 +1,137 @@
+use barter_integration::model::Side;
+use chrono::DateTime;
+use chrono::Utc;
+use std::fmt;
+
+/// Portfolio value at one update, and how far it stood below the peak so far.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EquityPoint {
+    pub time: DateTime<Utc>,
+    pub equity: f64,
+    pub drawdown_pct: f64,
+}
+
+/// A fill, placed on the curve at the first equity reported after it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TradeMarker {
+    pub time: DateTime<Utc>,
+    pub side: Side,
+    pub equity: f64,
+}
+
+/// The portfolio value over the log, with the drawdown from its running peak and every fill.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EquityCurve {
+    points: Vec<EquityPoint>,
+    trades: Vec<TradeMarker>,
+    /// Fills waiting on the next equity update to be placed.
+    pending: Vec<(DateTime<Utc>, Side)>,
+    peak: f64,
+}
+
+impl EquityCurve {
+    pub fn record_equity(&mut self, time: DateTime<Utc>, equity: f64) {
+        self.peak = self.peak.max(equity);
+        let drawdown_pct = if self.peak > 0.0 {
+            (self.peak - equity) / self.peak * 100.0
+        } else {
+            0.0
+        };
+        self.points.push(EquityPoint {
+            time,
+            equity,
+            drawdown_pct,
+        });
+        self.trades
+            .extend(
+                self.pending
+                    .drain(..)
+                    .map(|(time, side)| TradeMarker { time, side, equity }),
+            );
+    }
+
+    pub fn record_fill(&mut self, time: DateTime<Utc>, side: Side) {
+        self.pending.push((time, side));
+    }
+
+    pub fn points(&self) -> &[EquityPoint] {
+        &self.points
+    }
+
+    /// Fills placed on the curve so far, in the order they happened.
+    pub fn trades(&self) -> &[TradeMarker] {
+        &self.trades
+    }
+
+    pub fn max_drawdown_pct(&self) -> f64 {
+        self.points
+            .iter()
+            .map(|point| point.drawdown_pct)
+            .fold(0.0, f64::max)
+    }
+}
+
+impl fmt::Display for EquityCurve {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Equity over {} updates", self.points.len())?;
+        let (Some(first), Some(last)) = (self.points.first(), self.points.last()) else {
+            return Ok(());
+        };
+        writeln!(
+            f,
+            "\n{:.2} at {} to {:.2} at {}, peak {:.2}, max drawdown {:.2}%",
+            first.equity,
+            first.time,
+            last.equity,
+            last.time,
+            self.peak,
+            self.max_drawdown_pct()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_drawdown_and_markers() {
+        let start = DateTime::from_timestamp_millis(1_717_200_000_000).unwrap();
+        let at = |seconds| start + Duration::seconds(seconds);
+        let mut curve = EquityCurve::default();
+        curve.record_equity(at(0), 1000.0);
+        curve.record_fill(at(1), Side::Buy);
+        curve.record_equity(at(1), 1100.0);
+        curve.record_equity(at(2), 880.0);
+        curve.record_fill(at(3), Side::Sell);
+        curve.record_equity(at(3), 990.0);
+
+        let drawdowns: Vec<f64> = curve
+            .points()
+            .iter()
+            .map(|point| point.drawdown_pct)
+            .collect();
+        assert_eq!(drawdowns, [0.0, 0.0, 20.0, 10.0]);
+        assert_eq!(curve.max_drawdown_pct(), 20.0);
+        assert_eq!(
+            curve.trades(),
+            [
+                TradeMarker {
+                    time: at(1),
+                    side: Side::Buy,
+                    equity: 1100.0
+                },
+                TradeMarker {
+                    time: at(3),
+                    side: Side::Sell,
+                    equity: 990.0
+                }
+            ]
+        );
+        assert!(curve
+            .to_string()
+            .contains("peak 1100.00, max drawdown 20.00%"));
+    }
+}
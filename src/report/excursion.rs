@@ -0,0 +1,156 @@
+use barter_integration::model::Side;
+use std::collections::BTreeMap;
+use std::fmt;
+
+use super::percentile;
+use crate::Fill;
+use crate::STOP_LOSS;
+use crate::TAKE_PROFIT;
+
+/// The worst and best unrealized return a trade reached while open, in percent of its entry
+/// price and marked at the bid, as take profit and stop loss are.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Excursion {
+    /// Maximum adverse excursion, zero or below.
+    pub mae_pct: f64,
+    /// Maximum favorable excursion, zero or above.
+    pub mfe_pct: f64,
+}
+
+impl Excursion {
+    pub(crate) fn mark(&mut self, entry: f64, price: f64) {
+        let return_pct = (price - entry) / entry * 100.0;
+        self.mae_pct = self.mae_pct.min(return_pct);
+        self.mfe_pct = self.mfe_pct.max(return_pct);
+    }
+}
+
+/// Excursions of every round trip, paired last in, first out per sleeve and symbol.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Excursions {
+    open: BTreeMap<(usize, String), Vec<(f64, Excursion)>>,
+    closed: Vec<Excursion>,
+}
+
+impl Excursions {
+    /// Mark every trade open on `symbol` at `bid`.
+    pub fn mark(&mut self, symbol: &str, bid: f64) {
+        let open = self
+            .open
+            .iter_mut()
+            .filter(|((_, open_symbol), _)| open_symbol == symbol)
+            .flat_map(|(_, open)| open.iter_mut());
+        for (entry, excursion) in open {
+            excursion.mark(*entry, bid);
+        }
+    }
+
+    pub fn record(&mut self, sleeve: usize, symbol: &str, fill: &Fill) {
+        let open = self.open.entry((sleeve, symbol.to_owned())).or_default();
+        match fill.side {
+            Side::Buy => open.push((fill.price, Excursion::default())),
+            Side::Sell => {
+                if let Some((entry, mut excursion)) = open.pop() {
+                    excursion.mark(entry, fill.price);
+                    self.closed.push(excursion);
+                }
+            }
+        }
+    }
+
+    /// Excursions of the closed trades, in the order they closed.
+    pub fn closed(&self) -> &[Excursion] {
+        &self.closed
+    }
+}
+
+impl fmt::Display for Excursions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Excursions over {} closed trades", self.closed.len())?;
+        if self.closed.is_empty() {
+            return Ok(());
+        }
+
+        let mut mae: Vec<f64> = self.closed.iter().map(|trade| -trade.mae_pct).collect();
+        let mut mfe: Vec<f64> = self.closed.iter().map(|trade| trade.mfe_pct).collect();
+        mae.sort_unstable_by(f64::total_cmp);
+        mfe.sort_unstable_by(f64::total_cmp);
+        writeln!(
+            f,
+            "\n{:<8} {:>8} {:>8} {:>8} {:>8} {:>8}",
+            "%", "p25", "p50", "p75", "p90", "max"
+        )?;
+        for (name, sorted) in [("mae", &mae), ("mfe", &mfe)] {
+            writeln!(
+                f,
+                "{:<8} {:>8.3} {:>8.3} {:>8.3} {:>8.3} {:>8.3}",
+                name,
+                percentile(sorted, 0.25),
+                percentile(sorted, 0.5),
+                percentile(sorted, 0.75),
+                percentile(sorted, 0.9),
+                sorted[sorted.len() - 1]
+            )?;
+        }
+
+        let reached_tp = mfe
+            .iter()
+            .filter(|&&mfe| mfe >= TAKE_PROFIT * 100.0)
+            .count();
+        let reached_sl = mae.iter().filter(|&&mae| mae >= STOP_LOSS * 100.0).count();
+        writeln!(
+            f,
+            "\n{} trades reached the {:.1}% take profit and {} the {:.1}% stop loss",
+            reached_tp,
+            TAKE_PROFIT * 100.0,
+            reached_sl,
+            STOP_LOSS * 100.0
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fill(side: Side, price: f64) -> Fill {
+        Fill {
+            side,
+            price,
+            size: 1.0,
+            fee: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_excursions_while_open() {
+        let mut excursions = Excursions::default();
+        excursions.record(0, "BTC/USDT", &fill(Side::Buy, 100.0));
+        excursions.mark("BTC/USDT", 97.0);
+        // Other markets leave the trade alone
+        excursions.mark("ETH/USDT", 50.0);
+        excursions.mark("BTC/USDT", 101.5);
+        excursions.record(0, "BTC/USDT", &fill(Side::Sell, 100.5));
+
+        excursions.record(1, "BTC/USDT", &fill(Side::Buy, 100.0));
+        excursions.record(1, "BTC/USDT", &fill(Side::Sell, 102.0));
+
+        assert_eq!(
+            excursions.closed(),
+            [
+                Excursion {
+                    mae_pct: -3.0,
+                    mfe_pct: 1.5
+                },
+                Excursion {
+                    mae_pct: 0.0,
+                    mfe_pct: 2.0
+                }
+            ]
+        );
+        let report = excursions.to_string();
+        assert!(report.contains("2 trades reached the 1.0% take profit and 1 the 2.0% stop loss"));
+        assert_eq!(percentile(&[1.0, 2.0, 3.0, 4.0], 0.5), 2.0);
+        assert_eq!(percentile(&[1.0, 2.0, 3.0, 4.0], 0.9), 4.0);
+    }
+}
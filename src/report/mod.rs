@@ -0,0 +1,252 @@
+//! Offline analysis of the [event log](crate::wal), printed by the `report` command.
+
+use chrono::DateTime;
+use chrono::Utc;
+use serde::Deserialize;
+use std::fmt;
+use std::io::BufRead;
+use tracing::warn;
+
+use crate::event::Execution;
+use crate::features::Features;
+use crate::Fill;
+
+/// Mid-price moves after each fill, by strategy, side and venue.
+pub mod adverse;
+/// Profit and loss broken down by strategy and symbol.
+pub mod attribution;
+/// Equity curve, drawdown and trade markers rendered to PNG or SVG.
+#[cfg(feature = "charts")]
+pub mod chart;
+/// Portfolio value and drawdown over the log.
+pub mod equity;
+/// Worst and best unrealized returns of each trade while open.
+pub mod excursion;
+/// Fill prices against the prices they were decided on, by venue and order type.
+pub mod slippage;
+
+use adverse::AdverseSelection;
+use attribution::Ledger;
+use equity::EquityCurve;
+use excursion::Excursions;
+use slippage::Slippage;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReportError {
+    #[error("failed to read event log: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("corrupt event log entry on line {line}: {error}")]
+    Corrupt {
+        line: usize,
+        error: serde_json::Error,
+    },
+}
+
+/// A logged event, as far as the report needs it.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Record {
+    Features {
+        time: DateTime<Utc>,
+        symbol: String,
+        features: Features,
+    },
+    Fill {
+        time: DateTime<Utc>,
+        symbol: String,
+        strategy: String,
+        /// Missing from fills logged before sleeves were, which were all the first sleeve's.
+        #[serde(default)]
+        sleeve: usize,
+        /// Missing from fills logged before decision prices were recorded.
+        #[serde(flatten)]
+        execution: Option<Execution>,
+        #[serde(flatten)]
+        fill: Fill,
+    },
+    Equity {
+        time: DateTime<Utc>,
+        portfolio_value: f64,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Report {
+    pub adverse_selection: AdverseSelection,
+    pub attribution: Ledger,
+    pub equity: EquityCurve,
+    pub excursions: Excursions,
+    pub slippage: Slippage,
+}
+
+impl Report {
+    /// Build the report from every entry in `log`. A torn final line left by a crash mid-write is
+    /// skipped with a warning.
+    pub fn read(log: impl BufRead) -> Result<Self, ReportError> {
+        let mut report = Self::default();
+        let mut lines = log.lines().enumerate().peekable();
+        while let Some((index, line)) = lines.next() {
+            let line = line?;
+            let record: Record = match serde_json::from_str(&line) {
+                Ok(record) => record,
+                Err(error) if lines.peek().is_none() => {
+                    warn!("Skipping torn event log line {}: {}", index + 1, error);
+                    break;
+                }
+                Err(error) => {
+                    return Err(ReportError::Corrupt {
+                        line: index + 1,
+                        error,
+                    })
+                }
+            };
+            report.record(record);
+        }
+        Ok(report)
+    }
+
+    fn record(&mut self, record: Record) {
+        match record {
+            Record::Features {
+                time,
+                symbol,
+                features,
+            } => {
+                self.adverse_selection
+                    .mark(&symbol, time, features.mid_price);
+                self.excursions.mark(&symbol, features.bid);
+            }
+            Record::Fill {
+                time,
+                symbol,
+                strategy,
+                sleeve,
+                execution,
+                fill,
+            } => {
+                self.attribution.record(&strategy, sleeve, &symbol, &fill);
+                self.equity.record_fill(time, fill.side);
+                self.excursions.record(sleeve, &symbol, &fill);
+                if let Some(execution) = execution {
+                    self.adverse_selection
+                        .record(time, &symbol, &strategy, &execution, &fill);
+                    self.slippage.record(&execution, &fill);
+                }
+            }
+            Record::Equity {
+                time,
+                portfolio_value,
+            } => self.equity.record_equity(time, portfolio_value),
+            Record::Other => {}
+        }
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.attribution)?;
+        writeln!(f, "{}", self.equity)?;
+        writeln!(f, "{}", self.excursions)?;
+        writeln!(f, "{}", self.slippage)?;
+        write!(f, "{}", self.adverse_selection)
+    }
+}
+
+/// The value below which `fraction` of `sorted` falls, by nearest rank.
+fn percentile(sorted: &[f64], fraction: f64) -> f64 {
+    let rank = (fraction * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use barter_integration::model::Side;
+
+    use super::*;
+    use crate::event::Event;
+    use crate::event::Execution;
+    use crate::event::OrderType;
+
+    fn log(events: &[Event]) -> String {
+        let mut log = String::new();
+        for event in events {
+            log.push_str(&String::from_utf8(event.to_json()).unwrap());
+            log.push('\n');
+        }
+        log
+    }
+
+    #[test]
+    fn test_read_log() {
+        let time = DateTime::from_timestamp_millis(1_717_200_000_000).unwrap();
+        let fill = |side, price| Event::Fill {
+            time,
+            symbol: "BTC/USDT",
+            strategy: "imbalance".to_owned(),
+            sleeve: 0,
+            execution: Execution::paper(OrderType::Limit, 100.0),
+            fill: Fill {
+                side,
+                price,
+                size: 1.0,
+                fee: 0.5,
+            },
+            book_time: None,
+        };
+        let features = Event::Features {
+            time,
+            symbol: "BTC/USDT",
+            features: Features {
+                bid: 99.0,
+                ..Features::default()
+            },
+            book_time: None,
+        };
+        let equity = Event::Equity {
+            time,
+            symbol: "BTC/USDT",
+            portfolio_value: 1002.0,
+        };
+        let events = [
+            fill(Side::Buy, 100.0),
+            features,
+            fill(Side::Sell, 103.0),
+            equity,
+        ];
+        let torn = log(&events) + r#"{"seq":4,"type":"fi"#;
+
+        let report = Report::read(torn.as_bytes()).unwrap();
+        let total = report.attribution.total();
+        assert_eq!(total.trades, 1);
+        assert_eq!(total.net_pnl(), 2.0);
+        assert_eq!(report.excursions.closed()[0].mae_pct, -1.0);
+        assert_eq!(report.excursions.closed()[0].mfe_pct, 3.0);
+        assert_eq!(report.equity.points()[0].equity, 1002.0);
+        assert_eq!(report.equity.trades().len(), 2);
+        assert!(report.to_string().contains("imbalance / BTC/USDT"));
+        let limit = &report.slippage.summaries()[0];
+        assert_eq!((limit.fills, limit.worst_bps), (2, 0.0));
+
+        // Fills logged before decision prices were recorded still count toward PnL
+        let mut legacy = String::new();
+        for line in log(&events).lines() {
+            let mut event: serde_json::Value = serde_json::from_str(line).unwrap();
+            for field in ["venue", "order_type", "decision_price"] {
+                event.as_object_mut().unwrap().remove(field);
+            }
+            legacy.push_str(&format!("{event}\n"));
+        }
+        let report = Report::read(legacy.as_bytes()).unwrap();
+        assert_eq!(report.attribution.total().trades, 1);
+        assert!(report.slippage.summaries().is_empty());
+
+        let corrupt = format!("{}{{\n{}", log(&events[..1]), log(&events[2..]));
+        assert!(matches!(
+            Report::read(corrupt.as_bytes()),
+            Err(ReportError::Corrupt { line: 2, .. })
+        ));
+    }
+}
@@ -0,0 +1,150 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use super::percentile;
+use crate::event::Execution;
+use crate::event::OrderType;
+use crate::Fill;
+
+/// Slippage of the fills on one venue with one order type, in basis points of the decision price.
+/// Positive is worse than the decision price.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlippageSummary {
+    pub venue: String,
+    pub order_type: OrderType,
+    pub fills: usize,
+    pub mean_bps: f64,
+    pub p50_bps: f64,
+    pub p90_bps: f64,
+    pub worst_bps: f64,
+    /// What slippage cost in quote currency, summed over the fills.
+    pub cost: f64,
+}
+
+/// Every fill's slippage against its decision price, by venue and order type.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Slippage {
+    /// Slippage in basis points and quote currency per fill.
+    samples: BTreeMap<(String, OrderType), Vec<(f64, f64)>>,
+}
+
+impl Slippage {
+    pub fn record(&mut self, execution: &Execution, fill: &Fill) {
+        // Nothing to measure against without a book at decision time
+        if execution.decision_price <= 0.0 {
+            return;
+        }
+        let bps = execution.slippage_bps(fill);
+        let cost = bps / 10_000.0 * execution.decision_price * fill.size;
+        self.samples
+            .entry((execution.venue.clone(), execution.order_type))
+            .or_default()
+            .push((bps, cost));
+    }
+
+    pub fn summaries(&self) -> Vec<SlippageSummary> {
+        self.samples
+            .iter()
+            .map(|((venue, order_type), samples)| {
+                let mut bps: Vec<f64> = samples.iter().map(|(bps, _)| *bps).collect();
+                bps.sort_unstable_by(f64::total_cmp);
+                SlippageSummary {
+                    venue: venue.clone(),
+                    order_type: *order_type,
+                    fills: samples.len(),
+                    mean_bps: bps.iter().sum::<f64>() / bps.len() as f64,
+                    p50_bps: percentile(&bps, 0.5),
+                    p90_bps: percentile(&bps, 0.9),
+                    worst_bps: bps[bps.len() - 1],
+                    cost: samples.iter().map(|(_, cost)| cost).sum(),
+                }
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for Slippage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Slippage against decision prices")?;
+        writeln!(
+            f,
+            "\n{:<16} {:<8} {:>7} {:>9} {:>9} {:>9} {:>9} {:>10}",
+            "venue", "type", "fills", "mean bps", "p50 bps", "p90 bps", "worst", "cost"
+        )?;
+        for summary in self.summaries() {
+            let order_type = match summary.order_type {
+                OrderType::Limit => "limit",
+                OrderType::Market => "market",
+            };
+            writeln!(
+                f,
+                "{:<16} {:<8} {:>7} {:>9.2} {:>9.2} {:>9.2} {:>9.2} {:>10.4}",
+                summary.venue,
+                order_type,
+                summary.fills,
+                summary.mean_bps,
+                summary.p50_bps,
+                summary.p90_bps,
+                summary.worst_bps,
+                summary.cost
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use barter_integration::model::Side;
+
+    use super::*;
+
+    fn fill(side: Side, price: f64) -> Fill {
+        Fill {
+            side,
+            price,
+            size: 2.0,
+            fee: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_slippage_by_venue_and_order_type() {
+        let mut slippage = Slippage::default();
+        let limit = Execution::paper(OrderType::Limit, 100.0);
+        let market = Execution::paper(OrderType::Market, 100.0);
+        let gateway = Execution {
+            venue: "binance".to_owned(),
+            ..limit.clone()
+        };
+        // Buying above and selling below the decision price are both worse
+        slippage.record(&limit, &fill(Side::Buy, 99.99));
+        slippage.record(&limit, &fill(Side::Sell, 100.01));
+        slippage.record(&market, &fill(Side::Sell, 99.98));
+        slippage.record(&gateway, &fill(Side::Buy, 100.03));
+        // Fills without a decision price are left out
+        slippage.record(
+            &Execution::paper(OrderType::Market, 0.0),
+            &fill(Side::Sell, 99.0),
+        );
+
+        let summaries = slippage.summaries();
+        let found: Vec<_> = summaries
+            .iter()
+            .map(|summary| (summary.venue.as_str(), summary.order_type, summary.fills))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("binance", OrderType::Limit, 1),
+                ("paper", OrderType::Limit, 2),
+                ("paper", OrderType::Market, 1)
+            ]
+        );
+        assert!((summaries[0].worst_bps - 3.0).abs() < 1e-9);
+        assert!((summaries[0].cost - 0.06).abs() < 1e-9);
+        assert!((summaries[1].mean_bps + 1.0).abs() < 1e-9);
+        assert!((summaries[2].p50_bps - 2.0).abs() < 1e-9);
+        assert!(slippage.to_string().contains("paper            market"));
+    }
+}
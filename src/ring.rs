@@ -0,0 +1,245 @@
+//! A lock-free single producer, single consumer ring buffer, for running the strategy without
+//! channel wakeups: the book feed thread writes decoded books into slots allocated up front and
+//! a strategy thread, [pinned](crate::affinity) to a core of its own, busy-polls for them.
+//!
+//! Only the latest books matter, so a producer finding the ring full overwrites the oldest value
+//! not yet taken rather than dropping the new one.
+//!
+//! Busy-polling keeps the consumer's core fully loaded, so this only pays off with a core to
+//! spare for it.
+
+use std::cell::UnsafeCell;
+use std::mem::MaybeUninit;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+/// Keeps each index on a cache line of its own, so the producer and consumer don't contend.
+#[derive(Debug, Default)]
+#[repr(align(64))]
+struct Padded<T>(T);
+
+struct Ring<T> {
+    slots: Box<[UnsafeCell<MaybeUninit<T>>]>,
+    // All three only ever increase; slot `index & mask` holds the value at `index`. Values are
+    // claimed through `head`, by the consumer taking them or the producer overwriting them, and
+    // their slots are free to write again once `released` is past them.
+    head: Padded<AtomicUsize>,
+    released: Padded<AtomicUsize>,
+    tail: Padded<AtomicUsize>,
+    mask: usize,
+    dropped: AtomicU64,
+    closed: AtomicBool,
+}
+
+// SAFETY: a slot is only ever accessed by the producer before its value is published through
+// `tail`, and after, by whichever of the producer and consumer claims it through `head`, until
+// it is released through `released`.
+unsafe impl<T: Send> Sync for Ring<T> {}
+
+impl<T> Drop for Ring<T> {
+    fn drop(&mut self) {
+        let tail = *self.tail.0.get_mut();
+        for index in *self.head.0.get_mut()..tail {
+            // SAFETY: every slot from head to tail holds a value not yet taken
+            unsafe { self.slots[index & self.mask].get_mut().assume_init_drop() };
+        }
+    }
+}
+
+/// A ring holding at least `capacity` values, and two, rounded up to a power of two.
+pub fn channel<T>(capacity: usize) -> (Producer<T>, Consumer<T>) {
+    // With a single slot, the slot being written would be the one the consumer is reading
+    let capacity = capacity.max(2).next_power_of_two();
+    let ring = Arc::new(Ring {
+        slots: (0..capacity)
+            .map(|_| UnsafeCell::new(MaybeUninit::uninit()))
+            .collect(),
+        head: Padded(AtomicUsize::new(0)),
+        released: Padded(AtomicUsize::new(0)),
+        tail: Padded(AtomicUsize::new(0)),
+        mask: capacity - 1,
+        dropped: AtomicU64::new(0),
+        closed: AtomicBool::new(false),
+    });
+    (Producer { ring: ring.clone() }, Consumer { ring })
+}
+
+pub struct Producer<T> {
+    ring: Arc<Ring<T>>,
+}
+
+impl<T> Producer<T> {
+    /// Write `value` into the next slot. When the ring is full, as the consumer has fallen a
+    /// whole ring behind, the oldest value not yet taken is dropped and counted to make room,
+    /// waiting out the consumer if it is taking that one just then. Returns whether nothing was
+    /// dropped.
+    pub fn push(&mut self, value: T) -> bool {
+        let ring = &*self.ring;
+        let tail = ring.tail.0.load(Ordering::Relaxed);
+        let mut kept = true;
+        loop {
+            let released = ring.released.0.load(Ordering::Acquire);
+            if tail - released <= ring.mask {
+                break;
+            }
+            // Full, so the oldest value is the one in the slot to write, claimed unless the
+            // consumer has claimed it first
+            if ring
+                .head
+                .0
+                .compare_exchange(released, released + 1, Ordering::AcqRel, Ordering::Relaxed)
+                .is_ok()
+            {
+                // SAFETY: claimed through `head`, so the consumer will never read it
+                unsafe { (*ring.slots[released & ring.mask].get()).assume_init_drop() };
+                ring.released.0.fetch_max(released + 1, Ordering::Release);
+                ring.dropped.fetch_add(1, Ordering::Relaxed);
+                kept = false;
+                break;
+            }
+            std::hint::spin_loop();
+        }
+        // SAFETY: the slot at `tail` was released by whoever claimed its last value, or never used
+        unsafe { (*ring.slots[tail & ring.mask].get()).write(value) };
+        ring.tail.0.store(tail + 1, Ordering::Release);
+        kept
+    }
+}
+
+impl<T> Drop for Producer<T> {
+    fn drop(&mut self) {
+        self.ring.closed.store(true, Ordering::Release);
+    }
+}
+
+pub struct Consumer<T> {
+    ring: Arc<Ring<T>>,
+}
+
+impl<T> Consumer<T> {
+    /// The oldest value written and not overwritten, if any.
+    pub fn pop(&mut self) -> Option<T> {
+        let ring = &*self.ring;
+        let mut head = ring.head.0.load(Ordering::Acquire);
+        loop {
+            if head == ring.tail.0.load(Ordering::Acquire) {
+                return None;
+            }
+            // The producer may claim it first, to overwrite, leaving the next one to try
+            match ring.head.0.compare_exchange_weak(
+                head,
+                head + 1,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => break,
+                Err(current) => head = current,
+            }
+        }
+        // SAFETY: the producer published the slot at `head`, and won't write to it again until
+        // released now that it is claimed
+        let value = unsafe { (*ring.slots[head & ring.mask].get()).assume_init_read() };
+        ring.released.0.fetch_max(head + 1, Ordering::Release);
+        Some(value)
+    }
+
+    /// Whether the producer is gone, so nothing more will be written after what is in the ring.
+    pub fn is_closed(&self) -> bool {
+        self.ring.closed.load(Ordering::Acquire)
+    }
+
+    /// Spin until a value is written, or return `None` once the producer is gone and everything
+    /// it wrote has been taken.
+    pub fn pop_spin(&mut self) -> Option<T> {
+        loop {
+            // Checked first, so a value written just before closing isn't missed
+            let closed = self.is_closed();
+            if let Some(value) = self.pop() {
+                return Some(value);
+            }
+            if closed {
+                return None;
+            }
+            std::hint::spin_loop();
+        }
+    }
+
+    /// Values overwritten because the ring was full so far.
+    pub fn dropped(&self) -> u64 {
+        self.ring.dropped.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overwrites_oldest_when_full() {
+        let (mut producer, mut consumer) = channel(3);
+        for value in 0..5 {
+            producer.push(value);
+        }
+        assert_eq!(consumer.dropped(), 1);
+        assert_eq!(
+            std::iter::from_fn(|| consumer.pop()).collect::<Vec<_>>(),
+            [1, 2, 3, 4]
+        );
+
+        // Slots are reused once taken
+        assert!(producer.push(5));
+        assert_eq!(consumer.pop(), Some(5));
+
+        // Overwritten values are dropped as they are
+        let overwritten = Arc::new(());
+        let (mut producer, mut consumer) = channel(2);
+        producer.push(overwritten.clone());
+        producer.push(Arc::new(()));
+        assert!(!producer.push(Arc::new(())));
+        assert_eq!(Arc::strong_count(&overwritten), 1);
+        assert!(consumer.pop().is_some());
+
+        // Values never taken are dropped with the ring
+        let left = Arc::new(());
+        let (mut producer, consumer) = channel(2);
+        producer.push(left.clone());
+        drop((producer, consumer));
+        assert_eq!(Arc::strong_count(&left), 1);
+    }
+
+    #[test]
+    fn test_spins_across_threads_in_order() {
+        let (mut producer, mut consumer) = channel(1024);
+        let writer = std::thread::spawn(move || {
+            for value in 0..10_000u32 {
+                producer.push(value);
+            }
+        });
+        let received: Vec<_> = std::iter::from_fn(|| consumer.pop_spin()).collect();
+        writer.join().unwrap();
+
+        // However far behind the consumer fell, it saw every value it didn't lose, in order,
+        // through to the latest
+        assert!(received.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(received.last(), Some(&9_999));
+        assert_eq!(received.len() as u64 + consumer.dropped(), 10_000);
+    }
+
+    #[test]
+    fn test_overwrites_while_taken_across_threads() {
+        // A tiny ring, so the producer keeps overwriting what the consumer is about to take
+        let (mut producer, mut consumer) = channel(2);
+        let writer = std::thread::spawn(move || {
+            for value in 0..100_000u32 {
+                producer.push(Box::new(value));
+            }
+        });
+        let received: Vec<_> = std::iter::from_fn(|| consumer.pop_spin()).collect();
+        writer.join().unwrap();
+        assert!(received.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(received.len() as u64 + consumer.dropped(), 100_000);
+    }
+}
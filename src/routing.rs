@@ -0,0 +1,218 @@
+//! Smart order routing across venues, [`[gateway.routing]`](crate::config::VenueConfig), for
+//! orders sent through the order gateway. Each order goes to the venue where it nets the best
+//! price after the venue's fee, among those whose latest book has the depth to fill all of it and
+//! with the balance to pay for a buy or the position to cover a sell. When a venue rejects the
+//! order it is routed again, to the best venue not yet tried.
+//!
+//! A routed order takes liquidity: its price is the worst level it has to reach on the venue's
+//! book, and it nets the average of the levels it fills against.
+
+use barter_data::subscription::book::Level;
+use barter_data::subscription::book::OrderBook;
+use barter_integration::model::Side;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+
+use crate::config::VenueConfig;
+use crate::Fill;
+
+/// Where to send an order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Route {
+    pub venue: String,
+    /// The limit price, the worst level the order reaches.
+    pub price: f64,
+    /// The average price paid or received after the venue's fee.
+    pub net: f64,
+}
+
+/// The average and worst price of filling `size` against `levels`, best first, if they are deep
+/// enough.
+fn sweep(levels: &[Level], size: f64) -> Option<(f64, f64)> {
+    let mut left = size;
+    let mut notional = 0.0;
+    for level in levels {
+        let filled = left.min(level.amount);
+        notional += filled * level.price;
+        left -= filled;
+        if left <= 0.0 {
+            return Some((notional / size, level.price));
+        }
+    }
+    None
+}
+
+#[derive(Debug)]
+pub struct Router {
+    /// Venues by name, in order so ties always go the same way.
+    venues: BTreeMap<String, VenueConfig>,
+    /// The latest book of each venue.
+    books: HashMap<String, OrderBook>,
+}
+
+impl Router {
+    pub fn new(venues: &HashMap<String, VenueConfig>) -> Self {
+        Self {
+            venues: venues
+                .iter()
+                .map(|(name, venue)| (name.clone(), venue.clone()))
+                .collect(),
+            books: HashMap::new(),
+        }
+    }
+
+    /// Keep `book` as `exchange`'s latest, if it is routed to.
+    pub fn on_book(&mut self, exchange: &str, book: &OrderBook) {
+        if self.venues.contains_key(exchange) {
+            self.books.insert(exchange.to_owned(), book.clone());
+        }
+    }
+
+    /// The best venue to `side` `size` on, leaving out those in `tried`.
+    pub fn route(&self, side: Side, size: f64, tried: &[String]) -> Option<Route> {
+        let mut best: Option<Route> = None;
+        for (name, venue) in &self.venues {
+            if tried.contains(name) {
+                continue;
+            }
+            let Some(book) = self.books.get(name) else {
+                continue;
+            };
+            let levels = match side {
+                Side::Buy => &book.asks.levels,
+                Side::Sell => &book.bids.levels,
+            };
+            let Some((average, price)) = sweep(levels, size) else {
+                continue;
+            };
+            let (net, covered) = match side {
+                Side::Buy => {
+                    let net = average * (1.0 + venue.fee);
+                    (net, venue.cash.is_none_or(|cash| net * size <= cash))
+                }
+                Side::Sell => {
+                    let net = average * (1.0 - venue.fee);
+                    (net, venue.position.is_none_or(|held| size <= held))
+                }
+            };
+            let better = best.as_ref().is_none_or(|best| match side {
+                Side::Buy => net < best.net,
+                Side::Sell => net > best.net,
+            });
+            if covered && better {
+                best = Some(Route {
+                    venue: name.clone(),
+                    price,
+                    net,
+                });
+            }
+        }
+        best
+    }
+
+    /// Book `fill` against `venue`'s balances.
+    pub fn on_fill(&mut self, venue: &str, fill: &Fill) {
+        let Some(venue) = self.venues.get_mut(venue) else {
+            return;
+        };
+        let (cash, position) = match fill.side {
+            Side::Buy => (-(fill.price * fill.size + fill.fee), fill.size),
+            Side::Sell => (fill.price * fill.size - fill.fee, -fill.size),
+        };
+        if let Some(balance) = &mut venue.cash {
+            *balance += cash;
+        }
+        if let Some(held) = &mut venue.position {
+            *held += position;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use barter_data::subscription::book::OrderBookSide;
+    use chrono::DateTime;
+
+    use super::*;
+
+    fn book(bids: &[(f64, f64)], asks: &[(f64, f64)]) -> OrderBook {
+        OrderBook {
+            last_update_time: DateTime::from_timestamp_millis(0).unwrap(),
+            bids: OrderBookSide::new(Side::Buy, bids.iter().copied()),
+            asks: OrderBookSide::new(Side::Sell, asks.iter().copied()),
+        }
+    }
+
+    fn venue(fee: f64) -> VenueConfig {
+        VenueConfig {
+            fee,
+            cash: None,
+            position: None,
+        }
+    }
+
+    fn router() -> Router {
+        let mut router = Router::new(&HashMap::from([
+            ("aevo".to_owned(), venue(0.0005)),
+            ("binance".to_owned(), venue(0.002)),
+        ]));
+        router.on_book("aevo", &book(&[(99.9, 1.0)], &[(100.1, 1.0)]));
+        router.on_book(
+            "binance",
+            &book(&[(100.0, 1.0)], &[(100.0, 0.5), (100.2, 1.0)]),
+        );
+        router
+    }
+
+    #[test]
+    fn test_best_net_price() {
+        let router = router();
+        // Binance's lower ask loses to its fee
+        let buy = router.route(Side::Buy, 0.1, &[]).unwrap();
+        assert_eq!((buy.venue.as_str(), buy.price), ("aevo", 100.1));
+        assert!((buy.net - 100.1 * 1.0005).abs() < 1e-9);
+        let sell = router.route(Side::Sell, 0.1, &[]).unwrap();
+        assert_eq!(sell.venue, "aevo");
+
+        // Reaching past binance's top level averages the levels filled
+        let mut cheap = router;
+        cheap.venues.get_mut("binance").unwrap().fee = 0.0;
+        let buy = cheap.route(Side::Buy, 1.0, &[]).unwrap();
+        assert_eq!((buy.venue.as_str(), buy.price), ("binance", 100.2));
+        assert!((buy.net - 100.1).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_depth_balance_and_fallback() {
+        let mut router = router();
+        // Aevo's book is too thin for the whole order
+        assert_eq!(router.route(Side::Buy, 1.2, &[]).unwrap().venue, "binance");
+        assert_eq!(router.route(Side::Buy, 2.0, &[]), None);
+
+        // Rejected by aevo, the order falls back to binance, then has nowhere left to go
+        let tried = ["aevo".to_owned()];
+        assert_eq!(
+            router.route(Side::Buy, 0.1, &tried).unwrap().venue,
+            "binance"
+        );
+        let tried = ["aevo".to_owned(), "binance".to_owned()];
+        assert_eq!(router.route(Side::Buy, 0.1, &tried), None);
+
+        // Balances are kept up to date with fills
+        router.venues.get_mut("aevo").unwrap().cash = Some(5.0);
+        router.venues.get_mut("aevo").unwrap().position = Some(0.0);
+        assert_eq!(router.route(Side::Buy, 0.1, &[]).unwrap().venue, "binance");
+        assert_eq!(router.route(Side::Sell, 0.1, &[]).unwrap().venue, "binance");
+        router.on_fill(
+            "aevo",
+            &Fill {
+                side: Side::Sell,
+                price: 100.0,
+                size: 0.1,
+                fee: 0.0,
+            },
+        );
+        assert_eq!(router.venues["aevo"].cash, Some(15.0));
+        assert_eq!(router.route(Side::Buy, 0.1, &[]).unwrap().venue, "aevo");
+    }
+}
@@ -0,0 +1,161 @@
+//! Time-of-day parameter schedules. The scheduler tracks which `[[schedule]]` period is in force
+//! and, when another one starts, hands back the config to run from then on, which the
+//! [`Reloader`](crate::reload::Reloader) applies like any other config change.
+
+use chrono::DateTime;
+use chrono::NaiveTime;
+use chrono::Utc;
+use tracing::info;
+
+use crate::config::Config;
+use crate::config::ScheduleConfig;
+use crate::control;
+use crate::control::ControlError;
+
+#[derive(Debug)]
+pub struct Scheduler {
+    base: Config,
+    // Sorted by start time
+    periods: Vec<ScheduleConfig>,
+    active: Option<usize>,
+}
+
+impl Scheduler {
+    pub fn new(base: Config) -> Self {
+        let mut periods = base.schedule.clone();
+        periods.sort_by_key(|period| period.start);
+        Self {
+            base,
+            periods,
+            active: None,
+        }
+    }
+
+    /// Whether every period's risk params are fractions between 0 and 1.
+    pub fn check(&self) -> Result<(), ControlError> {
+        self.periods
+            .iter()
+            .filter_map(|period| period.risk.as_ref())
+            .try_for_each(control::check_risk)
+    }
+
+    /// The period in force at `time`: the last to start by then, or yesterday's last period
+    /// before today's first starts.
+    fn period_at(&self, time: NaiveTime) -> Option<usize> {
+        match self.periods.iter().rposition(|period| period.start <= time) {
+            Some(index) => Some(index),
+            None => self.periods.len().checked_sub(1),
+        }
+    }
+
+    /// The config to run at `now`: the base config with the period in force swapped in.
+    pub fn config_at(&self, now: DateTime<Utc>) -> Config {
+        let mut config = self.base.clone();
+        if let Some(period) = self.period_at(now.time()).map(|index| &self.periods[index]) {
+            if let Some(strategy) = &period.strategy {
+                config.strategy = strategy.clone();
+            }
+            if let Some(risk) = period.risk {
+                config.risk = risk;
+            }
+        }
+        config
+    }
+
+    /// The config to run from `now` if a different period came into force since the last call,
+    /// including the first period found.
+    pub fn poll(&mut self, now: DateTime<Utc>) -> Option<Config> {
+        let period = self.period_at(now.time())?;
+        if self.active == Some(period) {
+            return None;
+        }
+        self.active = Some(period);
+        info!(
+            "Schedule period starting at {} in force",
+            self.periods[period].start
+        );
+        Some(self.config_at(now))
+    }
+
+    /// Schedule from `base` instead, returning the config to run from `now`.
+    pub fn replace(&mut self, base: Config, now: DateTime<Utc>) -> Config {
+        *self = Self::new(base);
+        self.active = self.period_at(now.time());
+        self.config_at(now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::RiskParams;
+
+    fn at(hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 7, 1, hour, 30, 0).unwrap()
+    }
+
+    fn scheduled() -> Config {
+        Config::parse(
+            r#"
+            [risk]
+            take_profit = 0.01
+            stop_loss = 0.02
+
+            [[schedule]]
+            start = "08:00:00"
+
+            [[schedule]]
+            start = "00:00:00"
+            risk = { take_profit = 0.005, stop_loss = 0.01 }
+
+            [[schedule.strategy.members]]
+            kind = "imbalance"
+            spread_threshold = 0.1
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_periods_cover_the_day() {
+        let scheduler = Scheduler::new(scheduled());
+        scheduler.check().unwrap();
+
+        let asia = scheduler.config_at(at(3));
+        assert_eq!(asia.risk.stop_loss, 0.01);
+        assert_ne!(asia.strategy, scheduled().strategy);
+
+        let europe = scheduler.config_at(at(8));
+        assert_eq!(europe.risk, scheduled().risk);
+        assert_eq!(europe.strategy, scheduled().strategy);
+
+        // Without a period at midnight the day's last period runs on until the first starts
+        let mut late = scheduled();
+        late.schedule[1].start = NaiveTime::from_hms_opt(4, 0, 0).unwrap();
+        let scheduler = Scheduler::new(late);
+        assert_eq!(scheduler.config_at(at(2)).risk, scheduled().risk);
+        assert_eq!(scheduler.config_at(at(5)).risk.stop_loss, 0.01);
+    }
+
+    #[test]
+    fn test_poll_reports_period_changes() {
+        let mut scheduler = Scheduler::new(scheduled());
+        assert_eq!(scheduler.poll(at(3)).unwrap().risk.take_profit, 0.005);
+        assert!(scheduler.poll(at(4)).is_none());
+        assert_eq!(scheduler.poll(at(9)).unwrap().risk, scheduled().risk);
+        assert!(scheduler.poll(at(23)).is_none());
+
+        // A replacement takes effect straight away, without reporting the period again
+        let mut base = scheduled();
+        base.schedule[1].risk = Some(RiskParams {
+            take_profit: 0.003,
+            stop_loss: 0.01,
+        });
+        assert_eq!(scheduler.replace(base, at(2)).risk.take_profit, 0.003);
+        assert!(scheduler.poll(at(3)).is_none());
+
+        assert!(Scheduler::new(Config::default()).poll(at(3)).is_none());
+    }
+}
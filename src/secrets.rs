@@ -0,0 +1,116 @@
+//! Secrets referenced from the config rather than written into it. Anywhere the config takes a
+//! secret it accepts the value itself, the name of an environment variable holding it, or a file
+//! holding it, as mounted by Docker or Kubernetes secrets:
+//!
+//! ```toml
+//! secret = "change-me"
+//! secret = { env = "FIT_CONTROL_TOKEN" }
+//! secret = { file = "/run/secrets/fit-control-token" }
+//! ```
+
+use serde::Deserialize;
+use std::path::PathBuf;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SecretError {
+    #[error("secret environment variable {0} is not set")]
+    Env(String),
+
+    #[error("failed to read secret file {path}: {source}")]
+    File {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("secret is empty")]
+    Empty,
+}
+
+#[derive(Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum Secret {
+    Value(String),
+    Env { env: String },
+    File { file: PathBuf },
+}
+
+impl Secret {
+    /// The secret's value. A trailing newline in a file is not part of it.
+    pub fn resolve(&self) -> Result<String, SecretError> {
+        let value = match self {
+            Self::Value(value) => value.clone(),
+            Self::Env { env } => std::env::var(env).map_err(|_| SecretError::Env(env.clone()))?,
+            Self::File { file } => std::fs::read_to_string(file)
+                .map_err(|source| SecretError::File {
+                    path: file.clone(),
+                    source,
+                })?
+                .trim_end_matches(['\r', '\n'])
+                .to_owned(),
+        };
+        if value.is_empty() {
+            return Err(SecretError::Empty);
+        }
+        Ok(value)
+    }
+}
+
+/// Never prints a value written into the config.
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Value(_) => f.write_str("Value(<redacted>)"),
+            Self::Env { env } => f.debug_struct("Env").field("env", env).finish(),
+            Self::File { file } => f.debug_struct("File").field("file", file).finish(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use super::*;
+
+    #[derive(Debug, Deserialize)]
+    struct Holder {
+        secret: Secret,
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "fit-{}-{}-{}",
+            name,
+            std::process::id(),
+            SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ))
+    }
+
+    fn parse(toml: &str) -> Secret {
+        toml::from_str::<Holder>(toml).unwrap().secret
+    }
+
+    #[test]
+    fn test_resolve_sources() {
+        let secret = parse(r#"secret = "change-me""#);
+        assert_eq!(secret.resolve().unwrap(), "change-me");
+        assert_eq!(format!("{secret:?}"), "Value(<redacted>)");
+
+        let path = temp_path("secret");
+        std::fs::write(&path, "from-file\n").unwrap();
+        let secret = parse(&format!("secret = {{ file = {:?} }}", path));
+        assert_eq!(secret.resolve().unwrap(), "from-file");
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(secret.resolve(), Err(SecretError::File { .. })));
+
+        let secret = parse(r#"secret = { env = "FIT_SECRET_TEST_UNSET" }"#);
+        assert!(matches!(secret.resolve(), Err(SecretError::Env(_))));
+        assert!(matches!(
+            parse(r#"secret = """#).resolve(),
+            Err(SecretError::Empty)
+        ));
+    }
+}
@@ -0,0 +1,263 @@
+//! Daily trading sessions, rolled over at a configured UTC time of day.
+//!
+//! Round trips are paired last in, first out per sleeve, as the trading state closes positions,
+//! and count toward the session they close in. Drawdown is measured from the session's equity
+//! peak. Every intraday counter restarts at the rollover.
+
+use barter_integration::model::Side;
+use chrono::DateTime;
+use chrono::Days;
+use chrono::NaiveTime;
+use chrono::Utc;
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::event::Event;
+use crate::Fill;
+use crate::Lot;
+
+/// What happened over one session.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SessionSummary {
+    pub start: DateTime<Utc>,
+    pub trades: usize,
+    pub wins: usize,
+    pub win_rate_pct: f64,
+    pub gross_pnl: f64,
+    /// Gross PnL less every fee paid during the session.
+    pub net_pnl: f64,
+    pub fees: f64,
+    pub max_drawdown_pct: f64,
+}
+
+#[derive(Debug)]
+pub struct Session {
+    rollover: NaiveTime,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    /// Open entries per sleeve, carried across rollovers.
+    open: HashMap<usize, Vec<Fill>>,
+    trades: usize,
+    wins: usize,
+    gross_pnl: f64,
+    fees: f64,
+    peak: Option<f64>,
+    max_drawdown: f64,
+}
+
+impl Session {
+    /// Start a session at `now`, ending at the next `rollover`.
+    pub fn new(rollover: NaiveTime, now: DateTime<Utc>) -> Self {
+        Self {
+            rollover,
+            start: now,
+            end: next_rollover(rollover, now),
+            open: HashMap::new(),
+            trades: 0,
+            wins: 0,
+            gross_pnl: 0.0,
+            fees: 0.0,
+            peak: None,
+            max_drawdown: 0.0,
+        }
+    }
+
+    /// Realized PnL net of fees since the last rollover.
+    pub fn net_pnl(&self) -> f64 {
+        self.gross_pnl - self.fees
+    }
+
+    /// Carry the `lots` `sleeve` held before trading started, restored from saved state, as its
+    /// open entries, so selling them pairs into round trips. Their fees were paid back then.
+    pub fn carry(&mut self, sleeve: usize, lots: &[Lot]) {
+        self.open.insert(
+            sleeve,
+            lots.iter()
+                .map(|lot| Fill {
+                    side: Side::Buy,
+                    price: lot.price,
+                    size: lot.size,
+                    fee: 0.0,
+                })
+                .collect(),
+        );
+    }
+
+    /// Count fills and equity from `event` toward the current session.
+    pub fn record(&mut self, event: &Event) {
+        match event {
+            Event::Fill { sleeve, fill, .. } => {
+                self.fees += fill.fee;
+                let open = self.open.entry(*sleeve).or_default();
+                match fill.side {
+                    Side::Buy => open.push(*fill),
+                    Side::Sell => {
+                        if let Some(entry) = open.pop() {
+                            let pnl = (fill.price - entry.price) * fill.size;
+                            self.trades += 1;
+                            // A win has to cover the fees paid on both sides
+                            if pnl > entry.fee + fill.fee {
+                                self.wins += 1;
+                            }
+                            self.gross_pnl += pnl;
+                        }
+                    }
+                }
+            }
+            Event::Equity {
+                portfolio_value, ..
+            } => {
+                let peak = self.peak.get_or_insert(*portfolio_value);
+                *peak = peak.max(*portfolio_value);
+                if *peak > 0.0 {
+                    self.max_drawdown = self.max_drawdown.max((*peak - portfolio_value) / *peak);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Close the session if `now` is past its rollover, returning its summary and starting the
+    /// next one.
+    pub fn maybe_roll(&mut self, now: DateTime<Utc>) -> Option<SessionSummary> {
+        if now < self.end {
+            return None;
+        }
+
+        let summary = self.summary();
+        let open = std::mem::take(&mut self.open);
+        *self = Self {
+            open,
+            ..Self::new(self.rollover, now)
+        };
+        Some(summary)
+    }
+
+    pub fn summary(&self) -> SessionSummary {
+        SessionSummary {
+            start: self.start,
+            trades: self.trades,
+            wins: self.wins,
+            win_rate_pct: if self.trades > 0 {
+                self.wins as f64 / self.trades as f64 * 100.0
+            } else {
+                0.0
+            },
+            gross_pnl: self.gross_pnl,
+            net_pnl: self.net_pnl(),
+            fees: self.fees,
+            max_drawdown_pct: self.max_drawdown * 100.0,
+        }
+    }
+}
+
+/// The first `rollover` time of day strictly after `now`.
+fn next_rollover(rollover: NaiveTime, now: DateTime<Utc>) -> DateTime<Utc> {
+    let today = now.date_naive().and_time(rollover).and_utc();
+    if today > now {
+        today
+    } else {
+        today + Days::new(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+    use crate::event::Execution;
+    use crate::event::OrderType;
+
+    fn fill(side: Side, price: f64) -> Event {
+        Event::Fill {
+            time: Utc::now(),
+            symbol: "BTC/USDT",
+            strategy: "imbalance".to_owned(),
+            sleeve: 0,
+            execution: Execution::paper(OrderType::Limit, price),
+            fill: Fill {
+                side,
+                price,
+                size: 1.0,
+                fee: 0.5,
+            },
+            book_time: None,
+        }
+    }
+
+    fn equity(portfolio_value: f64) -> Event {
+        Event::Equity {
+            time: Utc::now(),
+            symbol: "BTC/USDT",
+            portfolio_value,
+        }
+    }
+
+    #[test]
+    fn test_summary_at_rollover() {
+        let rollover = NaiveTime::from_hms_opt(22, 0, 0).unwrap();
+        let start = DateTime::from_timestamp_millis(1_717_200_000_000).unwrap();
+        let mut session = Session::new(rollover, start);
+        assert_eq!(session.end, start.date_naive().and_time(rollover).and_utc());
+
+        for event in [
+            equity(1000.0),
+            fill(Side::Buy, 100.0),
+            fill(Side::Sell, 110.0),
+            equity(1200.0),
+            fill(Side::Buy, 100.0),
+            fill(Side::Sell, 100.5),
+            equity(900.0),
+            // Left open over the rollover
+            fill(Side::Buy, 100.0),
+        ] {
+            session.record(&event);
+        }
+        assert_eq!(session.maybe_roll(start + Duration::hours(21)), None);
+
+        let summary = session.maybe_roll(start + Duration::hours(22)).unwrap();
+        assert_eq!(summary.start, start);
+        assert_eq!(summary.trades, 2);
+        assert_eq!(summary.wins, 1);
+        assert_eq!(summary.win_rate_pct, 50.0);
+        assert_eq!(summary.gross_pnl, 10.5);
+        assert_eq!(summary.fees, 2.5);
+        assert_eq!(summary.net_pnl, 8.0);
+        assert_eq!(summary.max_drawdown_pct, 25.0);
+
+        // Counters restart while open positions carry over
+        assert_eq!(session.net_pnl(), 0.0);
+        session.record(&fill(Side::Sell, 104.0));
+        assert_eq!(session.summary().trades, 1);
+        assert_eq!(session.summary().gross_pnl, 4.0);
+        assert_eq!(
+            session.end,
+            start.date_naive().and_time(rollover).and_utc() + Days::new(1)
+        );
+    }
+
+    #[test]
+    fn test_restored_lots_pair_with_sells() {
+        let rollover = NaiveTime::from_hms_opt(22, 0, 0).unwrap();
+        let mut session = Session::new(rollover, Utc::now());
+        session.carry(
+            0,
+            &[
+                Lot::at(100.0),
+                Lot {
+                    price: 90.0,
+                    size: 2.0,
+                },
+            ],
+        );
+
+        session.record(&fill(Side::Sell, 95.0));
+        session.record(&fill(Side::Sell, 104.0));
+        let summary = session.summary();
+        assert_eq!(summary.trades, 2);
+        assert_eq!(summary.wins, 2);
+        assert_eq!(summary.gross_pnl, 9.0);
+        assert_eq!(summary.fees, 1.0);
+    }
+}
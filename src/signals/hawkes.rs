@@ -0,0 +1,155 @@
+//! Order flow intensity, [`[hawkes]`](crate::config::HawkesConfig), from the traded perpetual's
+//! public trades. Buys and sells each arrive as a self-exciting Hawkes process: the intensity,
+//! trades a second, rests at `baseline`, jumps by `excitation` with every trade, and decays back
+//! at `decay` a second,
+//!
+//! λ(t) = μ + Σ α e^(-β (t - tᵢ))
+//!
+//! so a burst of buying raises the buy intensity at once and fades once it stops, however many
+//! trades made it. The branching ratio `excitation / decay` is the share of trades set off by
+//! earlier ones, and has to stay below one for the intensity to settle.
+//!
+//! The [`flow`](crate::features::Features::flow) feature is the imbalance of the two intensities,
+//! from -1 with only selling to 1 with only buying. Unlike VOI, which counts what rests in the
+//! book, it weighs how hard each side is trading right now.
+
+use barter_integration::model::Side;
+use chrono::DateTime;
+use chrono::Utc;
+
+use crate::config::HawkesConfig;
+
+/// The intensity of one side's arrivals.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Intensity {
+    /// Excitation left above the baseline as of `updated`.
+    excess: f64,
+    updated: Option<DateTime<Utc>>,
+}
+
+impl Intensity {
+    fn new() -> Self {
+        Self {
+            excess: 0.0,
+            updated: None,
+        }
+    }
+
+    /// The excitation left at `now`, decayed at `decay` a second since the last update. Nothing
+    /// decays going back in time, for an arrival stamped behind the last.
+    fn excess(&self, decay: f64, now: DateTime<Utc>) -> f64 {
+        let Some(updated) = self.updated else {
+            return 0.0;
+        };
+        let elapsed = (now - updated).num_microseconds().unwrap_or(i64::MAX) as f64 / 1e6;
+        self.excess * (-decay * elapsed.max(0.0)).exp()
+    }
+
+    /// Excite by `excitation` on an arrival at `time`.
+    fn arrive(&mut self, excitation: f64, decay: f64, time: DateTime<Utc>) {
+        let time = self.updated.map_or(time, |updated| updated.max(time));
+        self.excess = self.excess(decay, time) + excitation;
+        self.updated = Some(time);
+    }
+}
+
+#[derive(Debug)]
+pub struct FlowIntensity {
+    config: HawkesConfig,
+    buys: Intensity,
+    sells: Intensity,
+}
+
+impl FlowIntensity {
+    pub fn new(config: &HawkesConfig) -> Self {
+        Self {
+            config: config.clone(),
+            buys: Intensity::new(),
+            sells: Intensity::new(),
+        }
+    }
+
+    /// Count a trade that took `side` at `time`.
+    pub fn on_trade(&mut self, side: Side, time: DateTime<Utc>) {
+        let intensity = match side {
+            Side::Buy => &mut self.buys,
+            Side::Sell => &mut self.sells,
+        };
+        intensity.arrive(self.config.excitation, self.config.decay, time);
+    }
+
+    /// The buy and sell intensities at `now`, in trades a second.
+    pub fn intensities(&self, now: DateTime<Utc>) -> (f64, f64) {
+        let (baseline, decay) = (self.config.baseline, self.config.decay);
+        (
+            baseline + self.buys.excess(decay, now),
+            baseline + self.sells.excess(decay, now),
+        )
+    }
+
+    /// The imbalance of the buy and sell intensities at `now`, from -1 to 1, or zero while
+    /// neither side has any.
+    pub fn ratio(&self, now: DateTime<Utc>) -> f64 {
+        let (buys, sells) = self.intensities(now);
+        if buys + sells <= 0.0 {
+            return 0.0;
+        }
+        (buys - sells) / (buys + sells)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(millis: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp_millis(1_700_000_000_000 + millis).unwrap()
+    }
+
+    fn flow() -> FlowIntensity {
+        FlowIntensity::new(&HawkesConfig {
+            baseline: 0.5,
+            excitation: 1.0,
+            decay: 2.0,
+        })
+    }
+
+    #[test]
+    fn test_intensity_excites_and_decays() {
+        let mut flow = flow();
+        assert_eq!(flow.intensities(at(0)), (0.5, 0.5));
+        assert_eq!(flow.ratio(at(0)), 0.0);
+
+        flow.on_trade(Side::Buy, at(0));
+        flow.on_trade(Side::Buy, at(500));
+        let excess = (-1.0f64).exp() + 1.0;
+        let (buys, sells) = flow.intensities(at(500));
+        assert!((buys - (0.5 + excess)).abs() < 1e-12);
+        assert_eq!(sells, 0.5);
+
+        // Half a second later both excitations have decayed by the same factor
+        let (buys, _) = flow.intensities(at(1000));
+        assert!((buys - (0.5 + excess * (-1.0f64).exp())).abs() < 1e-12);
+        // A trade stamped behind the last excites as of the last
+        flow.on_trade(Side::Buy, at(400));
+        let (late, _) = flow.intensities(at(1000));
+        assert!((late - buys - (-1.0f64).exp()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_ratio_follows_bursts() {
+        let mut flow = flow();
+        for millis in (0..1000).step_by(100) {
+            flow.on_trade(Side::Sell, at(millis));
+        }
+        flow.on_trade(Side::Buy, at(0));
+        assert!(flow.ratio(at(1000)) < -0.5);
+
+        // The sells fade once they stop, and a fresh burst of buying turns the ratio over
+        for millis in (5000..5500).step_by(100) {
+            flow.on_trade(Side::Buy, at(millis));
+        }
+        assert!(flow.ratio(at(5500)) > 0.5);
+        assert!(flow.ratio(at(60_000)).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,236 @@
+//! Kalman filter estimate of the latent fair price behind the noisy prices the book shows.
+//!
+//! The fair log price is modelled as a random walk moving by `process_noise` each update, and
+//! the log mid and microprice as noisy observations of it, off by `mid_noise` and
+//! `microprice_noise`. With `reference_noise` set, the instrument's mid on another venue is
+//! another observation, weighed in on the next update. Once `min_samples` updates have been
+//! filtered, a mid more than `threshold` below the estimate buys and one as far above it sells
+//! any open positions.
+//!
+//! ```toml
+//! [[strategy.members]]
+//! kind = "kalman"
+//! mid_noise = 0.0005
+//! reference_noise = 0.001
+//! threshold = 0.0005
+//! ```
+
+use serde::Deserialize;
+
+use crate::features::Features;
+use crate::strategy::Signal;
+use crate::strategy::Strategy;
+use crate::TradingState;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KalmanParams {
+    /// Standard deviation of the fair log price's move per update.
+    pub process_noise: f64,
+    /// Standard deviation of the log mid around the fair price.
+    pub mid_noise: f64,
+    /// Standard deviation of the log microprice around the fair price.
+    pub microprice_noise: f64,
+    /// Standard deviation of another venue's log mid around the fair price, or `None` to ignore
+    /// other venues.
+    pub reference_noise: Option<f64>,
+    /// Fraction of the estimate the mid must stray by, in either direction, to trade.
+    pub threshold: f64,
+    /// Updates filtered before the estimate is trusted to trade.
+    pub min_samples: usize,
+}
+
+impl Default for KalmanParams {
+    fn default() -> Self {
+        Self {
+            process_noise: 0.0002,
+            mid_noise: 0.0005,
+            microprice_noise: 0.0003,
+            reference_noise: None,
+            threshold: 0.0005,
+            min_samples: 50,
+        }
+    }
+}
+
+/// A scalar Kalman filter of a random walk.
+#[derive(Debug, Clone, Default)]
+pub struct KalmanFilter {
+    estimate: Option<f64>,
+    variance: f64,
+}
+
+impl KalmanFilter {
+    pub fn estimate(&self) -> Option<f64> {
+        self.estimate
+    }
+
+    /// Let the state wander by a step of `variance`.
+    pub fn predict(&mut self, variance: f64) {
+        self.variance += variance;
+    }
+
+    /// Weigh in `value`, observed with noise of `variance`. The first observation is taken as is.
+    pub fn observe(&mut self, value: f64, variance: f64) {
+        let Some(estimate) = self.estimate else {
+            self.estimate = Some(value);
+            self.variance = variance;
+            return;
+        };
+        let gain = self.variance / (self.variance + variance);
+        self.estimate = Some(estimate + gain * (value - estimate));
+        self.variance *= 1.0 - gain;
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct KalmanStrategy {
+    params: KalmanParams,
+    filter: KalmanFilter,
+    /// Another venue's mid since the last update, awaiting the next.
+    reference: Option<f64>,
+    samples: usize,
+}
+
+impl KalmanStrategy {
+    pub fn new(params: KalmanParams) -> Self {
+        Self {
+            params,
+            filter: KalmanFilter::default(),
+            reference: None,
+            samples: 0,
+        }
+    }
+
+    /// The estimated fair price, once anything has been observed.
+    pub fn fair(&self) -> Option<f64> {
+        self.filter.estimate().map(f64::exp)
+    }
+
+    fn observe(&mut self, price: f64, noise: f64) {
+        if price.is_finite() && price > 0.0 {
+            self.filter.observe(price.ln(), noise * noise);
+        }
+    }
+}
+
+impl Strategy for KalmanStrategy {
+    fn name(&self) -> &str {
+        "kalman"
+    }
+
+    fn evaluate(&mut self, features: &Features, state: &TradingState) -> Signal {
+        let mid_price = features.mid_price;
+        if !mid_price.is_finite() || mid_price <= 0.0 {
+            return Signal::Hold;
+        }
+        self.filter
+            .predict(self.params.process_noise * self.params.process_noise);
+        self.observe(mid_price, self.params.mid_noise);
+        self.observe(features.microprice, self.params.microprice_noise);
+        if let (Some(noise), Some(reference)) = (self.params.reference_noise, self.reference.take())
+        {
+            self.observe(reference, noise);
+        }
+        self.samples += 1;
+
+        let Some(fair) = self.fair() else {
+            return Signal::Hold;
+        };
+        if self.samples < self.params.min_samples {
+            return Signal::Hold;
+        }
+        let deviation = (mid_price - fair) / fair;
+        if deviation < -self.params.threshold {
+            Signal::Buy
+        } else if deviation > self.params.threshold && !state.positions.is_empty() {
+            Signal::Sell
+        } else {
+            Signal::Hold
+        }
+    }
+
+    fn on_reference(&mut self, price: f64) {
+        self.reference = Some(price);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Lot;
+
+    fn features(mid_price: f64, microprice: f64) -> Features {
+        Features {
+            mid_price,
+            microprice,
+            ..Features::default()
+        }
+    }
+
+    /// Mids jittering around 100 with the microprice on it.
+    fn warm_up(strategy: &mut KalmanStrategy, state: &TradingState) {
+        for tick in 0..100 {
+            let jitter = if tick % 2 == 0 { 0.01 } else { -0.01 };
+            strategy.evaluate(&features(100.0 + jitter, 100.0), state);
+        }
+    }
+
+    #[test]
+    fn test_filters_noise() {
+        let mut strategy = KalmanStrategy::new(KalmanParams::default());
+        let state = TradingState::new(1000.0, "BTC/USDT");
+        assert_eq!(strategy.fair(), None);
+        warm_up(&mut strategy, &state);
+        assert!((strategy.fair().unwrap() - 100.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_trades_deviations() {
+        let mut strategy = KalmanStrategy::new(KalmanParams::default());
+        let mut state = TradingState::new(1000.0, "BTC/USDT");
+        warm_up(&mut strategy, &state);
+
+        // The mid dips while the microprice holds, leaving it under the estimate
+        assert_eq!(
+            strategy.evaluate(&features(99.8, 100.0), &state),
+            Signal::Buy
+        );
+        warm_up(&mut strategy, &state);
+        // Nothing to sell while flat
+        assert_eq!(
+            strategy.evaluate(&features(100.2, 100.0), &state),
+            Signal::Hold
+        );
+        state.positions.push(Lot::at(100.0));
+        warm_up(&mut strategy, &state);
+        assert_eq!(
+            strategy.evaluate(&features(100.2, 100.0), &state),
+            Signal::Sell
+        );
+    }
+
+    #[test]
+    fn test_reference_venue() {
+        let params = KalmanParams {
+            reference_noise: Some(0.0003),
+            ..KalmanParams::default()
+        };
+        let state = TradingState::new(1000.0, "BTC/USDT");
+        let mut alone = KalmanStrategy::new(params);
+        let mut referenced = KalmanStrategy::new(params);
+        for _ in 0..10 {
+            alone.evaluate(&features(100.0, 100.0), &state);
+            referenced.on_reference(100.5);
+            referenced.evaluate(&features(100.0, 100.0), &state);
+        }
+        assert!((alone.fair().unwrap() - 100.0).abs() < 1e-9);
+        assert!(referenced.fair().unwrap() > 100.1);
+
+        // Other venues are ignored unless their noise is configured
+        let mut ignoring = KalmanStrategy::new(KalmanParams::default());
+        ignoring.on_reference(100.5);
+        ignoring.evaluate(&features(100.0, 100.0), &state);
+        assert!((ignoring.fair().unwrap() - 100.0).abs() < 1e-9);
+    }
+}
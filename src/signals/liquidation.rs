@@ -0,0 +1,250 @@
+//! Liquidation cascades, [`[liquidations]`](crate::config::LiquidationConfig), from the forced
+//! liquidations Binance streams for its perpetual of the traded asset. A liquidated long is sold
+//! out of and a liquidated short bought back, each fill pushing the price further into the next
+//! position's liquidation, so a cascade runs on forced flow rather than anyone's view until the
+//! positions left are far enough away.
+//!
+//! The [`liquidations`](crate::features::Features::liquidations) feature is the net forced buying
+//! over the last `window_secs`, in units of `cascade_volume`, and a cascade is on while it is one
+//! or more either way. Strategies can stand aside while it runs, with `stand_aside`, or fade its
+//! exhaustion with the [`exhaustion`](ExhaustionParams) strategy: once forced selling of at least
+//! `min_pressure` has eased to `exhaustion` of its peak the flush is taken to be over and it buys
+//! the rebound, and once forced buying has eased the same way it sells any open positions into
+//! the squeeze's top.
+//!
+//! ```toml
+//! [[strategy.members]]
+//! kind = "exhaustion"
+//! min_pressure = 1.0
+//! exhaustion = 0.25
+//! ```
+
+use barter_integration::model::Side;
+use chrono::DateTime;
+use chrono::TimeDelta;
+use chrono::Utc;
+use serde::Deserialize;
+use std::collections::VecDeque;
+use tracing::info;
+
+use crate::config::LiquidationConfig;
+use crate::features::Features;
+use crate::hours::Permission;
+use crate::strategy::Signal;
+use crate::strategy::Strategy;
+use crate::TradingState;
+
+#[derive(Debug)]
+pub struct CascadeDetector {
+    config: LiquidationConfig,
+    /// Each liquidation's time and signed quantity within the window, forced buys positive,
+    /// oldest first.
+    liquidations: VecDeque<(DateTime<Utc>, f64)>,
+    cascading: bool,
+}
+
+impl CascadeDetector {
+    pub fn new(config: &LiquidationConfig) -> Self {
+        Self {
+            config: config.clone(),
+            liquidations: VecDeque::new(),
+            cascading: false,
+        }
+    }
+
+    /// Count a liquidation of `quantity` at `time`, forced to trade on `side`: a sell closes a
+    /// liquidated long.
+    pub fn on_liquidation(&mut self, side: Side, quantity: f64, time: DateTime<Utc>) {
+        if !quantity.is_finite() || quantity <= 0.0 {
+            return;
+        }
+        self.liquidations.push_back((
+            time,
+            match side {
+                Side::Buy => quantity,
+                Side::Sell => -quantity,
+            },
+        ));
+    }
+
+    /// The net forced buying over the window up to `now`, in units of `cascade_volume`, or zero
+    /// without a volume to measure in.
+    pub fn pressure(&mut self, now: DateTime<Utc>) -> f64 {
+        let start = now - TimeDelta::seconds(self.config.window_secs as i64);
+        while self
+            .liquidations
+            .front()
+            .is_some_and(|(time, _)| *time <= start)
+        {
+            self.liquidations.pop_front();
+        }
+        if !self.config.cascade_volume.is_finite() || self.config.cascade_volume <= 0.0 {
+            return 0.0;
+        }
+        let net: f64 = self.liquidations.iter().map(|(_, quantity)| quantity).sum();
+        net / self.config.cascade_volume
+    }
+
+    /// Judge whether the liquidations of `features` are a cascade, logging whenever that changes,
+    /// and stand aside from entries while one runs if configured to.
+    pub fn update(&mut self, features: &Features) -> Permission {
+        let cascading = features.liquidations.abs() >= 1.0;
+        if cascading != self.cascading {
+            if cascading {
+                let forced = if features.liquidations > 0.0 {
+                    "buying"
+                } else {
+                    "selling"
+                };
+                info!(
+                    "Liquidation cascade, {:.1} volumes of forced {} over {}s{}",
+                    features.liquidations.abs(),
+                    forced,
+                    self.config.window_secs,
+                    if self.config.stand_aside {
+                        ", standing aside"
+                    } else {
+                        ""
+                    }
+                );
+            } else {
+                info!("Liquidation cascade over");
+            }
+            self.cascading = cascading;
+        }
+        Permission {
+            entries: !(cascading && self.config.stand_aside),
+            flatten: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExhaustionParams {
+    /// Net forced volume, in cascade volumes, a cascade must reach before it is faded.
+    pub min_pressure: f64,
+    /// Fraction of the cascade's peak it must ease to for it to be spent.
+    pub exhaustion: f64,
+}
+
+impl Default for ExhaustionParams {
+    fn default() -> Self {
+        Self {
+            min_pressure: 1.0,
+            exhaustion: 0.25,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ExhaustionStrategy {
+    params: ExhaustionParams,
+    /// The strongest pressure of the cascade being watched, signed like the feature.
+    peak: Option<f64>,
+}
+
+impl ExhaustionStrategy {
+    pub fn new(params: ExhaustionParams) -> Self {
+        Self { params, peak: None }
+    }
+}
+
+impl Strategy for ExhaustionStrategy {
+    fn name(&self) -> &str {
+        "exhaustion"
+    }
+
+    fn evaluate(&mut self, features: &Features, state: &TradingState) -> Signal {
+        let pressure = features.liquidations;
+        if !pressure.is_finite() {
+            return Signal::Hold;
+        }
+        if pressure.abs() >= self.params.min_pressure {
+            let stronger = self.peak.is_none_or(|peak| {
+                peak.signum() != pressure.signum() || pressure.abs() > peak.abs()
+            });
+            if stronger {
+                self.peak = Some(pressure);
+            }
+        }
+        let Some(peak) = self.peak else {
+            return Signal::Hold;
+        };
+        if pressure * peak.signum() > peak.abs() * self.params.exhaustion {
+            return Signal::Hold;
+        }
+        self.peak = None;
+        if peak < 0.0 {
+            Signal::Buy
+        } else if !state.positions.is_empty() {
+            Signal::Sell
+        } else {
+            Signal::Hold
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Lot;
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000 + secs, 0).unwrap()
+    }
+
+    fn pressure(liquidations: f64) -> Features {
+        Features {
+            liquidations,
+            ..Features::default()
+        }
+    }
+
+    #[test]
+    fn test_cascade_stands_aside() {
+        let mut detector = CascadeDetector::new(&LiquidationConfig {
+            window_secs: 10,
+            cascade_volume: 10.0,
+            stand_aside: true,
+        });
+        detector.on_liquidation(Side::Sell, 6.0, at(0));
+        detector.on_liquidation(Side::Buy, 2.0, at(3));
+        detector.on_liquidation(Side::Sell, f64::NAN, at(3));
+        assert_eq!(detector.pressure(at(5)), -0.4);
+        assert!(detector.update(&pressure(-0.4)).entries);
+
+        detector.on_liquidation(Side::Sell, 8.0, at(6));
+        assert_eq!(detector.pressure(at(6)), -1.2);
+        assert!(!detector.update(&pressure(-1.2)).entries);
+
+        // The first flush has left the window
+        assert_eq!(detector.pressure(at(10)), -0.6);
+        assert!(detector.update(&pressure(-0.6)).entries);
+        assert_eq!(detector.pressure(at(20)), 0.0);
+    }
+
+    #[test]
+    fn test_fades_exhausted_cascades() {
+        let mut strategy = ExhaustionStrategy::new(ExhaustionParams::default());
+        let mut state = TradingState::new(1000.0, "BTC/USDT");
+        assert_eq!(strategy.evaluate(&pressure(-0.9), &state), Signal::Hold);
+
+        // Longs flushed, peaking at 2 volumes, bought once down to a quarter of that
+        for liquidations in [-1.2, -2.0, -1.0, -0.6] {
+            assert_eq!(
+                strategy.evaluate(&pressure(liquidations), &state),
+                Signal::Hold
+            );
+        }
+        assert_eq!(strategy.evaluate(&pressure(-0.5), &state), Signal::Buy);
+        assert_eq!(strategy.evaluate(&pressure(-0.2), &state), Signal::Hold);
+
+        // A squeeze sells into its top only with something to sell
+        strategy.evaluate(&pressure(1.5), &state);
+        assert_eq!(strategy.evaluate(&pressure(0.1), &state), Signal::Hold);
+        state.positions.push(Lot::at(100.0));
+        strategy.evaluate(&pressure(1.5), &state);
+        assert_eq!(strategy.evaluate(&pressure(0.1), &state), Signal::Sell);
+    }
+}
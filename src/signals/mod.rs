@@ -0,0 +1,8 @@
+/// Hawkes process intensities of buy and sell order flow.
+pub mod hawkes;
+/// Kalman filter estimate of the latent fair price.
+pub mod kalman;
+/// Liquidation cascades, to stand aside from or fade.
+pub mod liquidation;
+/// Imbalance of resting liquidity weighted by how long it has rested.
+pub mod persistence;
@@ -0,0 +1,192 @@
+//! Imbalance of resting liquidity weighted by how long it has rested,
+//! [`[persistence]`](crate::config::PersistenceConfig). Size flashed into the book and pulled
+//! again moves OIR as much as size that has sat there for minutes, though only the latter is
+//! likely to still be there when the price gets to it. Tracking each level from one book to the
+//! next, size added to a level is dated, and counts for a weight that grows with its age toward
+//! one with a half-life of `half_life_ms`:
+//!
+//! w = 1 - 2^(-age / half-life)
+//!
+//! Size leaving a level is taken from the newest first, so a flash that is pulled again takes
+//! none of the size resting behind it along. The
+//! [`persistent_oir`](crate::features::Features::persistent_oir) feature is the OIR of the
+//! weighted size over the [depth window](crate::config::DepthConfig), weighted like the book's
+//! own.
+
+use barter_data::subscription::book::Level;
+use barter_data::subscription::book::OrderBook;
+use chrono::DateTime;
+use chrono::Utc;
+use std::collections::HashMap;
+
+use crate::config::DepthConfig;
+use crate::config::PersistenceConfig;
+use crate::config::Weighting;
+use crate::features::within_depth;
+
+/// The size resting at each level of one side, by the bits of its price, as when each part of
+/// it arrived and how much, oldest first.
+type Tranches = HashMap<u64, Vec<(DateTime<Utc>, f64)>>;
+
+#[derive(Debug)]
+pub struct PersistentImbalance {
+    config: PersistenceConfig,
+    depth: DepthConfig,
+    weighting: Weighting,
+    bids: Tranches,
+    asks: Tranches,
+}
+
+/// Date the size `levels` gained at `time`, and take what they lost from the newest, dropping
+/// levels no longer in the book.
+fn track(tranches: &mut Tranches, levels: &[Level], time: DateTime<Utc>) {
+    let mut next = HashMap::with_capacity(levels.len());
+    for level in levels {
+        let amount = level.amount.max(0.0);
+        let mut level_tranches = tranches.remove(&level.price.to_bits()).unwrap_or_default();
+        let mut resting: f64 = level_tranches.iter().map(|(_, size)| size).sum();
+        while resting > amount {
+            let Some((_, newest)) = level_tranches.last_mut() else {
+                break;
+            };
+            let taken = newest.min(resting - amount);
+            *newest -= taken;
+            resting -= taken;
+            if *newest <= 0.0 {
+                level_tranches.pop();
+            }
+        }
+        if amount > resting {
+            level_tranches.push((time, amount - resting));
+        }
+        next.insert(level.price.to_bits(), level_tranches);
+    }
+    *tranches = next;
+}
+
+impl PersistentImbalance {
+    pub fn new(config: &PersistenceConfig, depth: DepthConfig, weighting: Weighting) -> Self {
+        Self {
+            config: config.clone(),
+            depth,
+            weighting,
+            bids: HashMap::new(),
+            asks: HashMap::new(),
+        }
+    }
+
+    /// The size resting at `price` weighted by its age at `now`.
+    fn persisted(&self, tranches: &Tranches, price: f64, now: DateTime<Utc>) -> f64 {
+        let Some(level_tranches) = tranches.get(&price.to_bits()) else {
+            return 0.0;
+        };
+        let half_life = self.config.half_life_ms as f64 * 1000.0;
+        level_tranches
+            .iter()
+            .map(|(since, size)| {
+                if half_life <= 0.0 {
+                    return *size;
+                }
+                let age = (now - *since).num_microseconds().unwrap_or(i64::MAX) as f64;
+                size * (1.0 - 0.5f64.powf(age.max(0.0) / half_life))
+            })
+            .sum()
+    }
+
+    /// Track the levels of `book`, received at `time`, returning the OIR of their size weighted
+    /// by how long it has rested, or zero while none has.
+    pub fn update(&mut self, book: &OrderBook, time: DateTime<Utc>) -> f64 {
+        track(&mut self.bids, &book.bids.levels, time);
+        track(&mut self.asks, &book.asks.levels, time);
+
+        let (Some(bid), Some(ask)) = (book.bids.levels.first(), book.asks.levels.first()) else {
+            return 0.0;
+        };
+        let mid_price = (bid.price + ask.price) / 2.0;
+        let total = |levels: &[Level], tranches: &Tranches| -> f64 {
+            within_depth(levels, &self.depth, mid_price)
+                .iter()
+                .map(|level| {
+                    let persisted = self.persisted(tranches, level.price, time);
+                    match self.weighting {
+                        Weighting::Amount => persisted,
+                        Weighting::Notional => persisted * level.price,
+                    }
+                })
+                .sum()
+        };
+        let bid_volume = total(&book.bids.levels, &self.bids);
+        let ask_volume = total(&book.asks.levels, &self.asks);
+        if bid_volume + ask_volume <= 0.0 {
+            return 0.0;
+        }
+        (bid_volume - ask_volume) / (bid_volume + ask_volume)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use barter_data::subscription::book::OrderBookSide;
+    use barter_integration::model::Side;
+
+    use super::*;
+
+    fn at(millis: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp_millis(1_700_000_000_000 + millis).unwrap()
+    }
+
+    fn book(bid_amount: f64, ask_amount: f64) -> OrderBook {
+        OrderBook {
+            last_update_time: at(0),
+            bids: OrderBookSide::new(Side::Buy, [(100.0, bid_amount)]),
+            asks: OrderBookSide::new(Side::Sell, [(101.0, ask_amount)]),
+        }
+    }
+
+    #[test]
+    fn test_flashed_size_barely_counts() {
+        let mut imbalance = PersistentImbalance::new(
+            &PersistenceConfig { half_life_ms: 1000 },
+            DepthConfig::default(),
+            Weighting::Amount,
+        );
+        // Nothing has persisted on the first book, and a second later half of it has
+        assert_eq!(imbalance.update(&book(1.0, 1.0), at(0)), 0.0);
+        assert_eq!(imbalance.update(&book(1.0, 1.0), at(1000)), 0.0);
+
+        // A large bid flashes in for a tenth of a second, barely counting against what rests
+        imbalance.update(&book(9.0, 1.0), at(1900));
+        let flashed = imbalance.update(&book(9.0, 1.0), at(2000));
+        let (resting, fresh) = (0.75, 8.0 * (1.0 - 0.5f64.powf(0.1)));
+        let expected = (resting + fresh - resting) / (resting + fresh + resting);
+        assert!((flashed - expected).abs() < 1e-12);
+        assert!(flashed < 0.5 * (9.0 - 1.0) / (9.0 + 1.0));
+
+        // Pulled again, it takes none of the resting size with it
+        let pulled = imbalance.update(&book(1.0, 1.0), at(2000));
+        assert!(pulled.abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_resting_size_seasons_in() {
+        let mut imbalance = PersistentImbalance::new(
+            &PersistenceConfig { half_life_ms: 1000 },
+            DepthConfig::default(),
+            Weighting::Amount,
+        );
+        imbalance.update(&book(1.0, 1.0), at(0));
+        // Bids joined a second later are fresh while the rest is half way in
+        assert_eq!(imbalance.update(&book(3.0, 1.0), at(1000)), 0.0);
+        let young = imbalance.update(&book(3.0, 1.0), at(2000));
+        assert!((young - (1.75 - 0.75) / (1.75 + 0.75)).abs() < 1e-12);
+        let settled = imbalance.update(&book(3.0, 1.0), at(60_000));
+        assert!((settled - 0.5).abs() < 1e-9);
+        // Without a half-life size counts as soon as it rests
+        let mut at_once = PersistentImbalance::new(
+            &PersistenceConfig { half_life_ms: 0 },
+            DepthConfig::default(),
+            Weighting::Amount,
+        );
+        assert_eq!(at_once.update(&book(3.0, 1.0), at(0)), 0.5);
+    }
+}
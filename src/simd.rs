@@ -0,0 +1,125 @@
+//! Summing order book levels with SIMD, for books hundreds of levels deep across many symbols.
+//!
+//! [`total_amount`] picks the widest implementation the CPU supports at runtime: AVX on x86_64,
+//! reading two levels per load, and otherwise a scalar loop with independent accumulators, which
+//! still beats a plain `sum` by not waiting on one running total. Either adds the amounts in a
+//! different order than `sum` would, so totals can differ from it in the last bits.
+//!
+//! `cargo bench --bench voi` compares them.
+
+use barter_data::subscription::book::Level;
+
+/// Sum of every level's amount, with the fastest implementation available.
+pub fn total_amount(levels: &[Level]) -> f64 {
+    #[cfg(target_arch = "x86_64")]
+    if std::arch::is_x86_feature_detected!("avx") {
+        // SAFETY: AVX was just detected
+        return unsafe { avx::total_amount(levels) };
+    }
+    total_amount_unrolled(levels)
+}
+
+/// Sum of every level's amount, one level at a time.
+pub fn total_amount_scalar(levels: &[Level]) -> f64 {
+    levels.iter().map(|level| level.amount).sum()
+}
+
+/// Sum of every level's amount, into four running totals so the additions can overlap.
+pub fn total_amount_unrolled(levels: &[Level]) -> f64 {
+    let mut totals = [0.0; 4];
+    let chunks = levels.chunks_exact(totals.len());
+    let rest = chunks.remainder();
+    for chunk in chunks {
+        for (total, level) in totals.iter_mut().zip(chunk) {
+            *total += level.amount;
+        }
+    }
+    totals.iter().sum::<f64>() + total_amount_scalar(rest)
+}
+
+/// Sum of every level's price times amount, into four running totals like
+/// [`total_amount_unrolled`].
+pub fn total_notional(levels: &[Level]) -> f64 {
+    let mut totals = [0.0; 4];
+    let chunks = levels.chunks_exact(totals.len());
+    let rest = chunks.remainder();
+    for chunk in chunks {
+        for (total, level) in totals.iter_mut().zip(chunk) {
+            *total += level.price * level.amount;
+        }
+    }
+    let rest: f64 = rest.iter().map(|level| level.price * level.amount).sum();
+    totals.iter().sum::<f64>() + rest
+}
+
+#[cfg(target_arch = "x86_64")]
+pub mod avx {
+    use std::arch::x86_64::*;
+    use std::mem::offset_of;
+    use std::mem::size_of;
+
+    use barter_data::subscription::book::Level;
+
+    // Levels are read as pairs of f64s, so they must be exactly that, in either order
+    const _: () = assert!(size_of::<Level>() == 2 * size_of::<f64>());
+    /// Where each level's amount falls in a load of two levels.
+    const AMOUNT_LANES: i32 = if offset_of!(Level, amount) == 0 {
+        0b0101
+    } else {
+        0b1010
+    };
+
+    /// Sum of every level's amount, eight levels per iteration.
+    ///
+    /// # Safety
+    /// The CPU must support AVX.
+    #[target_feature(enable = "avx")]
+    pub unsafe fn total_amount(levels: &[Level]) -> f64 {
+        let zero = _mm256_setzero_pd();
+        let mut totals = [zero; 4];
+        let chunks = levels.chunks_exact(2 * totals.len());
+        let rest = chunks.remainder();
+        for chunk in chunks {
+            let pairs = chunk.as_ptr().cast::<f64>();
+            for (index, total) in totals.iter_mut().enumerate() {
+                // SAFETY: the chunk holds four pairs of levels, each two f64s
+                let pair = unsafe { _mm256_loadu_pd(pairs.add(4 * index)) };
+                *total = _mm256_add_pd(*total, _mm256_blend_pd::<AMOUNT_LANES>(zero, pair));
+            }
+        }
+        let total = _mm256_add_pd(
+            _mm256_add_pd(totals[0], totals[1]),
+            _mm256_add_pd(totals[2], totals[3]),
+        );
+        let mut lanes = [0.0; 4];
+        // SAFETY: `lanes` has room for all four
+        unsafe { _mm256_storeu_pd(lanes.as_mut_ptr(), total) };
+        lanes.iter().sum::<f64>() + super::total_amount_scalar(rest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_implementations_agree() {
+        // Whole amounts, so every order of summing is exact
+        for depth in [0, 1, 3, 8, 9, 31, 500] {
+            let levels: Vec<Level> = (0..depth)
+                .map(|index| Level::new(100.0 + index as f64, (index % 7 + 1) as f64))
+                .collect();
+            let expected = total_amount_scalar(&levels);
+            let notional: f64 = levels.iter().map(|level| level.price * level.amount).sum();
+            assert_eq!(total_notional(&levels), notional, "depth {depth}");
+            assert_eq!(total_amount_unrolled(&levels), expected, "depth {depth}");
+            assert_eq!(total_amount(&levels), expected, "depth {depth}");
+            #[cfg(target_arch = "x86_64")]
+            if std::arch::is_x86_feature_detected!("avx") {
+                // SAFETY: AVX was just detected
+                let total = unsafe { avx::total_amount(&levels) };
+                assert_eq!(total, expected, "depth {depth}");
+            }
+        }
+    }
+}
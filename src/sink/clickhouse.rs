@@ -0,0 +1,253 @@
+//! Bulk inserts into ClickHouse over its HTTP interface, for tick-level research storage.
+//!
+//! Events are buffered per table and inserted as `JSONEachRow`, one flat row per event: the
+//! `type` tag is dropped and nested features become columns of their own. Columns missing from a
+//! table are skipped, so tables only need the ones they care about, for example:
+//!
+//! ```sql
+//! CREATE TABLE fit.features (
+//!     time DateTime64(3, 'UTC'),
+//!     symbol LowCardinality(String),
+//!     bid Float64, ask Float64, mid_price Float64, microprice Float64,
+//!     spread Float64, voi Float64, oir Float64, mpb Float64, flow Float64,
+//!     liquidations Float64, order_imbalance Float64, avg_order_size Float64,
+//!     persistent_oir Float64, oir_top1 Float64, oir_top5 Float64, oir_top20 Float64,
+//!     oir_10bps Float64, oir_50bps Float64, volatility Float64
+//! ) ENGINE = MergeTree ORDER BY (symbol, time);
+//!
+//! CREATE TABLE fit.fills (
+//!     time DateTime64(3, 'UTC'),
+//!     symbol LowCardinality(String),
+//!     strategy LowCardinality(String),
+//!     side Enum8('Buy' = 1, 'Sell' = 2),
+//!     venue LowCardinality(String),
+//!     order_type Enum8('limit' = 1, 'market' = 2),
+//!     decision_price Float64, queue_ahead Nullable(Float64),
+//!     price Float64, size Float64, fee Float64,
+//!     exchange_time Nullable(DateTime64(3, 'UTC')), received_time Nullable(DateTime64(3, 'UTC'))
+//! ) ENGINE = MergeTree ORDER BY (symbol, time);
+//! ```
+
+use reqwest::Client;
+use reqwest::Error;
+use reqwest::RequestBuilder;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::MissedTickBehavior;
+use tracing::warn;
+
+use super::Queue;
+use super::Sink;
+use crate::config::ClickHouseConfig;
+use crate::config::Topics;
+use crate::event::Event;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ClickHouseError {
+    #[error("failed to connect to clickhouse: {0}")]
+    Connect(#[from] Error),
+}
+
+/// Inserts every event into the table configured for its type.
+#[derive(Debug)]
+pub struct ClickHouseSink {
+    queue: Queue,
+}
+
+impl ClickHouseSink {
+    pub async fn connect(config: &ClickHouseConfig) -> Result<Self, ClickHouseError> {
+        let client = Client::new();
+        let writer = Writer {
+            client,
+            url: config.url.trim_end_matches('/').to_owned(),
+            user: config.user.clone(),
+            password: config.password.clone(),
+        };
+        writer
+            .request(writer.client.get(format!("{}/ping", writer.url)))
+            .send()
+            .await?
+            .error_for_status()?;
+
+        let (queue, events) = Queue::new("clickhouse");
+        tokio::spawn(deliver(
+            writer,
+            config.tables.clone(),
+            config.batch_size.max(1),
+            Duration::from_millis(config.flush_interval_ms.max(1)),
+            events,
+        ));
+        Ok(Self { queue })
+    }
+}
+
+impl Sink for ClickHouseSink {
+    fn name(&self) -> &str {
+        "clickhouse"
+    }
+
+    fn publish(&mut self, event: &Event) {
+        self.queue.push(event);
+    }
+}
+
+/// `event` as a flat `JSONEachRow` row, with a trailing newline.
+fn row(event: &Event) -> Vec<u8> {
+    let mut value = serde_json::to_value(event).expect("events always serialize");
+    let object = value.as_object_mut().expect("events serialize as objects");
+    object.remove("type");
+    if let Some(Value::Object(features)) = object.remove("features") {
+        object.extend(features);
+    }
+
+    let mut row = serde_json::to_vec(&value).expect("events always serialize");
+    row.push(b'\n');
+    row
+}
+
+#[derive(Debug, Default)]
+struct Batch {
+    rows: usize,
+    body: Vec<u8>,
+}
+
+#[derive(Debug)]
+struct Writer {
+    client: Client,
+    url: String,
+    user: Option<String>,
+    password: Option<String>,
+}
+
+impl Writer {
+    fn request(&self, request: RequestBuilder) -> RequestBuilder {
+        let request = match &self.user {
+            Some(user) => request.header("X-ClickHouse-User", user),
+            None => request,
+        };
+        match &self.password {
+            Some(password) => request.header("X-ClickHouse-Key", password),
+            None => request,
+        }
+    }
+
+    async fn insert(&self, table: &str, batch: &mut Batch) {
+        if batch.rows == 0 {
+            return;
+        }
+
+        let query = format!("INSERT INTO {table} FORMAT JSONEachRow");
+        let request = self
+            .request(self.client.post(&self.url))
+            .query(&[
+                ("query", query.as_str()),
+                ("date_time_input_format", "best_effort"),
+                ("input_format_skip_unknown_fields", "1"),
+            ])
+            .body(std::mem::take(&mut batch.body));
+        let result = request
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        if let Err(error) = result {
+            warn!(
+                "Dropping {} rows, clickhouse insert into {}: {}",
+                batch.rows, table, error
+            );
+        }
+        batch.rows = 0;
+    }
+}
+
+async fn deliver(
+    writer: Writer,
+    tables: Topics,
+    batch_size: usize,
+    flush_interval: Duration,
+    mut events: mpsc::Receiver<Event>,
+) {
+    let mut batches: HashMap<String, Batch> = HashMap::new();
+    let mut interval = tokio::time::interval(flush_interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Some(event) => {
+                    let table = tables.for_event(&event);
+                    let batch = batches.entry(table.to_owned()).or_default();
+                    batch.body.extend(row(&event));
+                    batch.rows += 1;
+                    if batch.rows >= batch_size {
+                        writer.insert(table, batch).await;
+                    }
+                }
+                None => break,
+            },
+            _ = interval.tick() => {
+                for (table, batch) in &mut batches {
+                    writer.insert(table, batch).await;
+                }
+            }
+        }
+    }
+
+    for (table, batch) in &mut batches {
+        writer.insert(table, batch).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use barter_integration::model::Side;
+    use chrono::DateTime;
+
+    use super::*;
+    use crate::event::Execution;
+    use crate::event::OrderType;
+    use crate::features::Features;
+    use crate::Fill;
+
+    #[test]
+    fn test_rows_are_flat() {
+        let time = DateTime::from_timestamp_millis(1_717_200_000_000).unwrap();
+        let features = Event::Features {
+            time,
+            symbol: "BTC/USDT",
+            features: Features {
+                bid: 100.0,
+                ..Features::default()
+            },
+            book_time: None,
+        };
+        let flat: Value = serde_json::from_slice(&row(&features)).unwrap();
+        assert_eq!(flat["time"], "2024-06-01T00:00:00Z");
+        assert_eq!(flat["symbol"], "BTC/USDT");
+        assert_eq!(flat["bid"], 100.0);
+        assert!(flat.get("type").is_none());
+        assert!(flat.get("features").is_none());
+
+        let fill = Event::Fill {
+            time,
+            symbol: "BTC/USDT",
+            strategy: "imbalance".to_owned(),
+            sleeve: 0,
+            execution: Execution::paper(OrderType::Limit, 100.0),
+            fill: Fill {
+                side: Side::Buy,
+                price: 100.0,
+                size: 0.001,
+                fee: 0.0005,
+            },
+            book_time: None,
+        };
+        let line = row(&fill);
+        assert_eq!(line.last(), Some(&b'\n'));
+        let flat: Value = serde_json::from_slice(&line).unwrap();
+        assert_eq!(flat["side"], "Buy");
+        assert_eq!(flat["fee"], 0.0005);
+        assert_eq!(Topics::default().for_event(&fill), "fit.fills");
+    }
+}
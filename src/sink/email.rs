@@ -0,0 +1,229 @@
+//! Alerts emailed over SMTP, for the few events someone has to act on even when nobody is
+//! watching a dashboard or chat: anything raised at `min_severity` or above, critical by default,
+//! such as the feed staying degraded.
+//!
+//! Subjects and bodies are templates filled in with the alert's `{severity}`, `{symbol}`,
+//! `{message}` and `{time}`.
+
+use chrono::DateTime;
+use chrono::Utc;
+use lettre::address::AddressError;
+use lettre::message::header::ContentType;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::AsyncSmtpTransport;
+use lettre::AsyncTransport;
+use lettre::Message;
+use lettre::Tokio1Executor;
+use tokio::sync::mpsc;
+use tracing::warn;
+
+use super::Queue;
+use super::Sink;
+use crate::config::EmailConfig;
+use crate::config::SmtpTls;
+use crate::event::Alert;
+use crate::event::Event;
+use crate::event::Severity;
+use crate::secrets::SecretError;
+
+#[derive(Debug, thiserror::Error)]
+pub enum EmailError {
+    #[error("invalid email address: {0}")]
+    Address(#[from] AddressError),
+
+    #[error("failed to set up the SMTP relay: {0}")]
+    Relay(#[from] lettre::transport::smtp::Error),
+
+    #[error("failed to read the SMTP password: {0}")]
+    Password(#[from] SecretError),
+}
+
+/// Emails every alert at `min_severity` or above to every recipient, from a task of its own so a
+/// slow relay never holds up trading. Emails the relay refuses are dropped with a warning.
+#[derive(Debug)]
+pub struct EmailSink {
+    queue: Queue,
+}
+
+impl EmailSink {
+    pub fn connect(config: &EmailConfig) -> Result<Self, EmailError> {
+        let mailer = Mailer::new(config)?;
+        let (queue, events) = Queue::new("email");
+        tokio::spawn(deliver(mailer, events));
+        Ok(Self { queue })
+    }
+}
+
+impl Sink for EmailSink {
+    fn name(&self) -> &str {
+        "email"
+    }
+
+    fn publish(&mut self, event: &Event) {
+        if matches!(event, Event::Alert { .. }) {
+            self.queue.push(event);
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Mailer {
+    relay: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+    min_severity: Severity,
+    subject: String,
+    body: String,
+}
+
+impl Mailer {
+    fn new(config: &EmailConfig) -> Result<Self, EmailError> {
+        let relay = match config.tls {
+            SmtpTls::Implicit => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.relay)?,
+            SmtpTls::Starttls => {
+                AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.relay)?
+            }
+            SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.relay),
+        };
+        let relay = match config.port {
+            Some(port) => relay.port(port),
+            None => relay,
+        };
+        let relay = match (&config.username, &config.password) {
+            (Some(username), Some(password)) => {
+                relay.credentials(Credentials::new(username.clone(), password.resolve()?))
+            }
+            _ => relay,
+        };
+        Ok(Self {
+            relay: relay.build(),
+            from: config.from.parse()?,
+            to: config
+                .to
+                .iter()
+                .map(|to| to.parse())
+                .collect::<Result<_, _>>()?,
+            min_severity: config.min_severity,
+            subject: config.subject.clone(),
+            body: config.body.clone(),
+        })
+    }
+
+    /// The email for `alert`, unless it isn't severe enough to send.
+    fn email(
+        &self,
+        symbol: &str,
+        time: DateTime<Utc>,
+        alert: &Alert,
+    ) -> Option<Result<Message, lettre::error::Error>> {
+        if alert.severity < self.min_severity {
+            return None;
+        }
+        let message = self
+            .to
+            .iter()
+            .fold(Message::builder().from(self.from.clone()), |message, to| {
+                message.to(to.clone())
+            })
+            // Headers can't span lines
+            .subject(render(&self.subject, symbol, time, alert).replace(['\r', '\n'], " "))
+            .header(ContentType::TEXT_PLAIN)
+            .body(render(&self.body, symbol, time, alert));
+        Some(message)
+    }
+}
+
+/// `template` with the alert's fields filled in.
+fn render(template: &str, symbol: &str, time: DateTime<Utc>, alert: &Alert) -> String {
+    let severity = match alert.severity {
+        Severity::Info => "info",
+        Severity::Warning => "warning",
+        Severity::Critical => "critical",
+    };
+    template
+        .replace("{severity}", severity)
+        .replace("{symbol}", symbol)
+        .replace("{time}", &time.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+        .replace("{message}", &alert.message)
+}
+
+async fn deliver(mailer: Mailer, mut events: mpsc::Receiver<Event>) {
+    while let Some(event) = events.recv().await {
+        let Event::Alert {
+            time,
+            symbol,
+            alert,
+        } = &event
+        else {
+            continue;
+        };
+        let email = match mailer.email(symbol, *time, alert) {
+            Some(Ok(email)) => email,
+            Some(Err(error)) => {
+                warn!("Dropping alert email, building it: {}", error);
+                continue;
+            }
+            None => continue,
+        };
+        if let Err(error) = mailer.relay.send(email).await {
+            warn!("Dropping alert email, sending it: {}", error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn config() -> EmailConfig {
+        EmailConfig {
+            relay: "localhost".to_owned(),
+            port: Some(2525),
+            tls: SmtpTls::None,
+            username: None,
+            password: None,
+            from: "Bot <bot@example.com>".to_owned(),
+            to: vec!["ops@example.com".to_owned(), "desk@example.com".to_owned()],
+            min_severity: Severity::Critical,
+            subject: "[{severity}] {symbol}: {message}".to_owned(),
+            body: "{message}\n\nRaised at {time}.".to_owned(),
+        }
+    }
+
+    fn alert(severity: Severity) -> Alert {
+        Alert {
+            severity,
+            message: "feed degraded\nfor 120s".to_owned(),
+            condition: None,
+            resolved: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_alert_emails() {
+        let mailer = Mailer::new(&config()).unwrap();
+        let time = Utc.with_ymd_and_hms(2024, 6, 1, 12, 30, 0).unwrap();
+        assert!(mailer
+            .email("BTC/USDT", time, &alert(Severity::Warning))
+            .is_none());
+
+        let email = mailer
+            .email("BTC/USDT", time, &alert(Severity::Critical))
+            .unwrap()
+            .unwrap();
+        assert_eq!(email.envelope().to().len(), 2);
+        let formatted = String::from_utf8(email.formatted()).unwrap();
+        assert!(formatted.contains("From: Bot <bot@example.com>"));
+        assert!(formatted.contains("Subject: [critical] BTC/USDT: feed degraded for 120s"));
+        assert!(formatted.contains("Raised at 2024-06-01 12:30:00 UTC."));
+
+        let invalid = EmailConfig {
+            to: vec!["not an address".to_owned()],
+            ..config()
+        };
+        assert!(matches!(Mailer::new(&invalid), Err(EmailError::Address(_))));
+    }
+}
@@ -0,0 +1,385 @@
+//! Incidents opened on PagerDuty or Opsgenie for alerts at `min_severity` or above, critical by
+//! default, so someone is paged when the bot runs unattended.
+//!
+//! An alert about a [condition](crate::event::Alert::condition) opens its incident under a key
+//! made of the symbol and condition, so alerts raised while the condition lasts are grouped into
+//! one incident, which is resolved by the alert raised as the condition clears. Alerts without a
+//! condition open incidents of their own that have to be resolved by hand.
+
+use chrono::DateTime;
+use chrono::Utc;
+use reqwest::header::AUTHORIZATION;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::Client;
+use reqwest::RequestBuilder;
+use serde_json::json;
+use serde_json::Value;
+use std::collections::HashSet;
+use tokio::sync::mpsc;
+use tracing::warn;
+
+use super::Queue;
+use super::Sink;
+use crate::config::OpsgenieConfig;
+use crate::config::PagerDutyConfig;
+use crate::event::Alert;
+use crate::event::Event;
+use crate::event::Severity;
+use crate::secrets::SecretError;
+
+/// Longest message Opsgenie accepts.
+const OPSGENIE_MESSAGE_LIMIT: usize = 130;
+
+#[derive(Debug, thiserror::Error)]
+pub enum IncidentError {
+    #[error("failed to read the {0} key: {1}")]
+    Key(&'static str, SecretError),
+}
+
+/// The incident management service incidents are opened on.
+#[derive(Debug, Clone)]
+enum Service {
+    PagerDuty { url: String, routing_key: String },
+    Opsgenie { url: String, api_key: String },
+}
+
+impl Service {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::PagerDuty { .. } => "pagerduty",
+            Self::Opsgenie { .. } => "opsgenie",
+        }
+    }
+
+    /// The request opening, or adding to, the incident for `alert` under `key`.
+    fn trigger(
+        &self,
+        client: &Client,
+        source: &str,
+        key: Option<&str>,
+        symbol: &str,
+        time: DateTime<Utc>,
+        alert: &Alert,
+    ) -> RequestBuilder {
+        match self {
+            Self::PagerDuty { url, routing_key } => with_json(
+                client.post(url),
+                &pagerduty_trigger(routing_key, source, key, symbol, time, alert),
+            ),
+            Self::Opsgenie { url, api_key } => with_json(
+                client
+                    .post(format!("{url}/v2/alerts"))
+                    .header(AUTHORIZATION, format!("GenieKey {api_key}")),
+                &opsgenie_alert(source, key, symbol, alert),
+            ),
+        }
+    }
+
+    /// The request resolving the incident under `key`.
+    fn resolve(&self, client: &Client, source: &str, key: &str, alert: &Alert) -> RequestBuilder {
+        match self {
+            Self::PagerDuty { url, routing_key } => with_json(
+                client.post(url),
+                &json!({
+                    "routing_key": routing_key,
+                    "event_action": "resolve",
+                    "dedup_key": key,
+                }),
+            ),
+            Self::Opsgenie { url, api_key } => with_json(
+                client
+                    .post(format!("{url}/v2/alerts/{key}/close"))
+                    .query(&[("identifierType", "alias")])
+                    .header(AUTHORIZATION, format!("GenieKey {api_key}")),
+                &json!({ "source": source, "note": alert.message }),
+            ),
+        }
+    }
+}
+
+fn with_json(request: RequestBuilder, body: &Value) -> RequestBuilder {
+    request
+        .header(CONTENT_TYPE, "application/json")
+        .body(body.to_string())
+}
+
+fn severity_name(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Info => "info",
+        Severity::Warning => "warning",
+        Severity::Critical => "critical",
+    }
+}
+
+/// A PagerDuty Events API v2 trigger event.
+fn pagerduty_trigger(
+    routing_key: &str,
+    source: &str,
+    key: Option<&str>,
+    symbol: &str,
+    time: DateTime<Utc>,
+    alert: &Alert,
+) -> Value {
+    let mut event = json!({
+        "routing_key": routing_key,
+        "event_action": "trigger",
+        "payload": {
+            "summary": format!("{symbol}: {}", alert.message),
+            "source": source,
+            "severity": severity_name(alert.severity),
+            "timestamp": time,
+            "component": symbol,
+            "class": alert.condition,
+        },
+    });
+    if let Some(key) = key {
+        event["dedup_key"] = key.into();
+    }
+    event
+}
+
+/// An Opsgenie Alert API alert.
+fn opsgenie_alert(source: &str, key: Option<&str>, symbol: &str, alert: &Alert) -> Value {
+    let message = format!("{symbol}: {}", alert.message);
+    let priority = match alert.severity {
+        Severity::Critical => "P1",
+        Severity::Warning => "P3",
+        Severity::Info => "P5",
+    };
+    let mut body = json!({
+        "message": message.chars().take(OPSGENIE_MESSAGE_LIMIT).collect::<String>(),
+        "description": message,
+        "priority": priority,
+        "source": source,
+        "tags": [symbol, severity_name(alert.severity)],
+    });
+    if let Some(key) = key {
+        body["alias"] = key.into();
+    }
+    body
+}
+
+/// What to do about an alert.
+#[derive(Debug, PartialEq, Eq)]
+enum Action {
+    /// Open, or add to, an incident, under a key when the alert is about a condition.
+    Trigger(Option<String>),
+    /// Resolve the incident under the key.
+    Resolve(String),
+}
+
+/// The incident keys opened and not yet resolved.
+#[derive(Debug)]
+struct Incidents {
+    min_severity: Severity,
+    open: HashSet<String>,
+}
+
+impl Incidents {
+    fn action(&mut self, symbol: &str, alert: &Alert) -> Option<Action> {
+        // Opsgenie takes the key in a URL path, so it sticks to characters safe there
+        let key = alert.condition.as_ref().map(|condition| {
+            format!("fit-{symbol}-{condition}")
+                .chars()
+                .map(|c| match c {
+                    'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => c,
+                    _ => '-',
+                })
+                .collect::<String>()
+        });
+        match key {
+            // Only incidents this sink opened are resolved
+            Some(key) if alert.resolved => self.open.remove(&key).then_some(Action::Resolve(key)),
+            _ if alert.resolved || alert.severity < self.min_severity => None,
+            Some(key) => {
+                self.open.insert(key.clone());
+                Some(Action::Trigger(Some(key)))
+            }
+            None => Some(Action::Trigger(None)),
+        }
+    }
+}
+
+/// Opens and resolves incidents from alerts, from a task of its own. Requests the service
+/// refuses are dropped with a warning.
+#[derive(Debug)]
+pub struct IncidentSink {
+    name: &'static str,
+    queue: Queue,
+}
+
+impl IncidentSink {
+    pub fn pagerduty(config: &PagerDutyConfig) -> Result<Self, IncidentError> {
+        let service = Service::PagerDuty {
+            url: config.url.clone(),
+            routing_key: config
+                .routing_key
+                .resolve()
+                .map_err(|error| IncidentError::Key("pagerduty", error))?,
+        };
+        Ok(Self::spawn(service, &config.source, config.min_severity))
+    }
+
+    pub fn opsgenie(config: &OpsgenieConfig) -> Result<Self, IncidentError> {
+        let service = Service::Opsgenie {
+            url: config.url.trim_end_matches('/').to_owned(),
+            api_key: config
+                .api_key
+                .resolve()
+                .map_err(|error| IncidentError::Key("opsgenie", error))?,
+        };
+        Ok(Self::spawn(service, &config.source, config.min_severity))
+    }
+
+    fn spawn(service: Service, source: &str, min_severity: Severity) -> Self {
+        let name = service.name();
+        let (queue, events) = Queue::new(name);
+        let incidents = Incidents {
+            min_severity,
+            open: HashSet::new(),
+        };
+        tokio::spawn(deliver(
+            Client::new(),
+            service,
+            source.to_owned(),
+            incidents,
+            events,
+        ));
+        Self { name, queue }
+    }
+}
+
+impl Sink for IncidentSink {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn publish(&mut self, event: &Event) {
+        if matches!(event, Event::Alert { .. }) {
+            self.queue.push(event);
+        }
+    }
+}
+
+async fn deliver(
+    client: Client,
+    service: Service,
+    source: String,
+    mut incidents: Incidents,
+    mut events: mpsc::Receiver<Event>,
+) {
+    while let Some(event) = events.recv().await {
+        let Event::Alert {
+            time,
+            symbol,
+            alert,
+        } = &event
+        else {
+            continue;
+        };
+        let request = match incidents.action(symbol, alert) {
+            Some(Action::Trigger(key)) => {
+                service.trigger(&client, &source, key.as_deref(), symbol, *time, alert)
+            }
+            Some(Action::Resolve(key)) => service.resolve(&client, &source, &key, alert),
+            None => continue,
+        };
+        let result = request
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        if let Err(error) = result {
+            warn!(
+                "Dropping {} incident update, {}",
+                service.name(),
+                error.without_url()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn alert(severity: Severity, resolved: bool) -> Alert {
+        Alert {
+            severity,
+            message: "Order book feed degraded".to_owned(),
+            condition: Some("feed_degraded".to_owned()),
+            resolved,
+        }
+    }
+
+    #[test]
+    fn test_open_and_resolve() {
+        let mut incidents = Incidents {
+            min_severity: Severity::Critical,
+            open: HashSet::new(),
+        };
+        let key = "fit-BTC-USDT-feed_degraded".to_owned();
+        // Nothing open to resolve yet, and warnings don't page
+        assert_eq!(
+            incidents.action("BTC/USDT", &alert(Severity::Info, true)),
+            None
+        );
+        assert_eq!(
+            incidents.action("BTC/USDT", &alert(Severity::Warning, false)),
+            None
+        );
+        assert_eq!(
+            incidents.action("BTC/USDT", &alert(Severity::Critical, false)),
+            Some(Action::Trigger(Some(key.clone())))
+        );
+        assert_eq!(
+            incidents.action("BTC/USDT", &alert(Severity::Info, true)),
+            Some(Action::Resolve(key))
+        );
+        assert!(incidents.open.is_empty());
+
+        let unkeyed = Alert {
+            condition: None,
+            ..alert(Severity::Critical, false)
+        };
+        assert_eq!(
+            incidents.action("BTC/USDT", &unkeyed),
+            Some(Action::Trigger(None))
+        );
+    }
+
+    #[test]
+    fn test_request_bodies() {
+        let time = Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+        let alert = alert(Severity::Critical, false);
+        let event = pagerduty_trigger(
+            "routing-key",
+            "fit",
+            Some("fit-BTC-USDT-feed_degraded"),
+            "BTC/USDT",
+            time,
+            &alert,
+        );
+        assert_eq!(event["event_action"], "trigger");
+        assert_eq!(event["dedup_key"], "fit-BTC-USDT-feed_degraded");
+        assert_eq!(
+            event["payload"]["summary"],
+            "BTC/USDT: Order book feed degraded"
+        );
+        assert_eq!(event["payload"]["severity"], "critical");
+        assert_eq!(event["payload"]["timestamp"], "2024-06-01T12:00:00Z");
+
+        let long = Alert {
+            message: "x".repeat(200),
+            ..alert.clone()
+        };
+        let body = opsgenie_alert("fit", None, "BTC/USDT", &long);
+        assert_eq!(body["priority"], "P1");
+        assert_eq!(
+            body["message"].as_str().unwrap().len(),
+            OPSGENIE_MESSAGE_LIMIT
+        );
+        assert!(body.get("alias").is_none());
+    }
+}
@@ -0,0 +1,328 @@
+use reqwest::Client;
+use reqwest::Error;
+use reqwest::Request;
+use std::fmt::Write;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::MissedTickBehavior;
+use tracing::warn;
+
+use super::Queue;
+use super::Sink;
+use crate::config::InfluxConfig;
+use crate::event::Event;
+use crate::event::OrderType;
+use crate::event::Severity;
+use crate::features::FEATURE_NAMES;
+use crate::strategy::Signal;
+
+#[derive(Debug, thiserror::Error)]
+pub enum InfluxError {
+    #[error("failed to connect to influxdb: {0}")]
+    Connect(#[from] Error),
+}
+
+/// Writes every event to InfluxDB as a point in the measurement named after its type, tagged by
+/// symbol and, for trades, strategy and side. Points have millisecond precision.
+#[derive(Debug)]
+pub struct InfluxSink {
+    queue: Queue,
+}
+
+impl InfluxSink {
+    pub async fn connect(config: &InfluxConfig) -> Result<Self, InfluxError> {
+        let client = Client::new();
+        let url = config.url.trim_end_matches('/');
+        client
+            .get(format!("{url}/ping"))
+            .send()
+            .await?
+            .error_for_status()?;
+
+        let mut write = client.post(format!("{url}/api/v2/write")).query(&[
+            ("org", config.org.as_str()),
+            ("bucket", config.bucket.as_str()),
+            ("precision", "ms"),
+        ]);
+        if let Some(token) = &config.token {
+            write = write.header("Authorization", format!("Token {token}"));
+        }
+        let write = write.build()?;
+
+        let (queue, events) = Queue::new("influx");
+        tokio::spawn(deliver(
+            client,
+            write,
+            config.batch_size.max(1),
+            Duration::from_millis(config.flush_interval_ms.max(1)),
+            events,
+        ));
+        Ok(Self { queue })
+    }
+}
+
+impl Sink for InfluxSink {
+    fn name(&self) -> &str {
+        "influx"
+    }
+
+    fn publish(&mut self, event: &Event) {
+        self.queue.push(event);
+    }
+}
+
+/// The line protocol point for `event`, without a trailing newline, or `None` if it has no
+/// finite field values.
+fn line(event: &Event) -> Option<String> {
+    let mut tags = vec![("symbol", event.symbol().to_owned())];
+    let mut fields: Vec<(&str, String)> = Vec::new();
+    // Line protocol has no representation for NaN or infinity
+    let mut float = |key, value: f64| {
+        if value.is_finite() {
+            fields.push((key, value.to_string()));
+        }
+    };
+    match event {
+        Event::Features { features, .. } => {
+            for (name, value) in FEATURE_NAMES.iter().zip(features.to_array()) {
+                float(*name, value);
+            }
+        }
+        Event::Signal {
+            strategy, signal, ..
+        } => {
+            tags.push(("strategy", strategy.clone()));
+            let signal = match signal {
+                Signal::Buy => "buy",
+                Signal::Sell => "sell",
+                Signal::Hold => "hold",
+            };
+            fields.push(("signal", format!("\"{signal}\"")));
+        }
+        Event::Order {
+            strategy,
+            side,
+            price,
+            size,
+            ..
+        } => {
+            tags.push(("strategy", strategy.clone()));
+            tags.push(("side", side.to_string()));
+            float("price", *price);
+            float("size", *size);
+        }
+        Event::Fill {
+            strategy,
+            execution,
+            fill,
+            ..
+        } => {
+            tags.push(("strategy", strategy.clone()));
+            tags.push(("side", fill.side.to_string()));
+            tags.push(("venue", execution.venue.clone()));
+            let order_type = match execution.order_type {
+                OrderType::Limit => "limit",
+                OrderType::Market => "market",
+            };
+            tags.push(("order_type", order_type.to_owned()));
+            float("decision_price", execution.decision_price);
+            if let Some(queue_ahead) = execution.queue_ahead {
+                float("queue_ahead", queue_ahead);
+            }
+            float("price", fill.price);
+            float("size", fill.size);
+            float("fee", fill.fee);
+        }
+        Event::Equity {
+            portfolio_value, ..
+        } => float("portfolio_value", *portfolio_value),
+        Event::Rebalance {
+            strategy,
+            weight,
+            capital,
+            transfer,
+            ..
+        } => {
+            tags.push(("strategy", strategy.clone()));
+            float("weight", *weight);
+            float("capital", *capital);
+            float("transfer", *transfer);
+        }
+        Event::Session { summary, .. } => {
+            float("win_rate_pct", summary.win_rate_pct);
+            float("gross_pnl", summary.gross_pnl);
+            float("net_pnl", summary.net_pnl);
+            float("fees", summary.fees);
+            float("max_drawdown_pct", summary.max_drawdown_pct);
+            fields.push(("trades", format!("{}i", summary.trades)));
+            fields.push(("wins", format!("{}i", summary.wins)));
+        }
+        Event::Alert { alert, .. } => {
+            let severity = match alert.severity {
+                Severity::Info => "info",
+                Severity::Warning => "warning",
+                Severity::Critical => "critical",
+            };
+            tags.push(("severity", severity.to_owned()));
+            let message = alert.message.replace('\\', "\\\\").replace('"', "\\\"");
+            fields.push(("message", format!("\"{message}\"")));
+        }
+    }
+    if fields.is_empty() {
+        return None;
+    }
+
+    let mut line = event.kind().to_owned();
+    for (key, value) in tags {
+        let _ = write!(line, ",{}={}", key, escape(&value));
+    }
+    for (index, (key, value)) in fields.iter().enumerate() {
+        let separator = if index == 0 { ' ' } else { ',' };
+        let _ = write!(line, "{}{}={}", separator, key, value);
+    }
+    let _ = write!(line, " {}", event.time().timestamp_millis());
+    Some(line)
+}
+
+/// Escape the characters that delimit tag values.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(',', "\\,")
+        .replace('=', "\\=")
+        .replace(' ', "\\ ")
+}
+
+async fn deliver(
+    client: Client,
+    write: Request,
+    batch_size: usize,
+    flush_interval: Duration,
+    mut events: mpsc::Receiver<Event>,
+) {
+    let mut batch: Vec<String> = Vec::with_capacity(batch_size);
+    let mut interval = tokio::time::interval(flush_interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Some(event) => {
+                    batch.extend(line(&event));
+                    if batch.len() >= batch_size {
+                        flush(&client, &write, &mut batch).await;
+                    }
+                }
+                None => {
+                    flush(&client, &write, &mut batch).await;
+                    break;
+                }
+            },
+            _ = interval.tick() => flush(&client, &write, &mut batch).await,
+        }
+    }
+}
+
+async fn flush(client: &Client, write: &Request, batch: &mut Vec<String>) {
+    if batch.is_empty() {
+        return;
+    }
+
+    let mut request = write
+        .try_clone()
+        .expect("write requests have no streaming body");
+    *request.body_mut() = Some(batch.join("\n").into());
+    let result = client
+        .execute(request)
+        .await
+        .and_then(|response| response.error_for_status());
+    if let Err(error) = result {
+        warn!("Dropping {} points, influxdb write: {}", batch.len(), error);
+    }
+    batch.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use barter_integration::model::Side;
+    use chrono::DateTime;
+
+    use super::*;
+    use crate::event::Alert;
+    use crate::event::Execution;
+    use crate::features::Features;
+    use crate::Fill;
+
+    #[test]
+    fn test_line_protocol() {
+        let time = DateTime::from_timestamp_millis(1_717_200_000_000).unwrap();
+        let equity = Event::Equity {
+            time,
+            symbol: "BTC/USDT",
+            portfolio_value: 1000.5,
+        };
+        assert_eq!(
+            line(&equity).unwrap(),
+            "equity,symbol=BTC/USDT portfolio_value=1000.5 1717200000000"
+        );
+
+        let fill = Event::Fill {
+            time,
+            symbol: "BTC/USDT",
+            strategy: "mean reversion".to_owned(),
+            sleeve: 0,
+            execution: Execution::paper(OrderType::Market, 100.5),
+            fill: Fill {
+                side: Side::Sell,
+                price: 100.0,
+                size: 0.001,
+                fee: 0.0005,
+            },
+            book_time: None,
+        };
+        assert_eq!(
+            line(&fill).unwrap(),
+            "fill,symbol=BTC/USDT,strategy=mean\\ reversion,side=sell,venue=paper,\
+             order_type=market decision_price=100.5,price=100,size=0.001,fee=0.0005 1717200000000"
+        );
+
+        let features = Event::Features {
+            time,
+            symbol: "BTC/USDT",
+            features: Features {
+                bid: 100.0,
+                oir: f64::NAN,
+                ..Features::default()
+            },
+            book_time: None,
+        };
+        let point = line(&features).unwrap();
+        assert!(point.starts_with("features,symbol=BTC/USDT bid=100,ask=0,"));
+        // Only the NaN field is dropped, not the others named after it
+        let fields: Vec<&str> = point.split(' ').nth(1).unwrap().split(',').collect();
+        assert!(!fields.iter().any(|field| field.starts_with("oir=")));
+        assert!(fields.contains(&"persistent_oir=0"));
+
+        let nothing = Event::Equity {
+            time,
+            symbol: "BTC/USDT",
+            portfolio_value: f64::INFINITY,
+        };
+        assert_eq!(line(&nothing), None);
+
+        let alert = Event::Alert {
+            time,
+            symbol: "BTC/USDT",
+            alert: Alert {
+                severity: Severity::Warning,
+                message: "Feed \"degraded\"".to_owned(),
+                condition: None,
+                resolved: false,
+            },
+        };
+        assert_eq!(
+            line(&alert).unwrap(),
+            r#"alert,symbol=BTC/USDT,severity=warning message="Feed \"degraded\"" 1717200000000"#
+        );
+    }
+}
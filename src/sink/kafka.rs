@@ -0,0 +1,123 @@
+use rskafka::client::error::Error;
+use rskafka::client::partition::Compression;
+use rskafka::client::partition::PartitionClient;
+use rskafka::client::partition::UnknownTopicHandling;
+use rskafka::client::Client;
+use rskafka::client::ClientBuilder;
+use rskafka::record::Record;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use tokio::sync::mpsc;
+use tracing::warn;
+
+use super::Queue;
+use super::Sink;
+use crate::config::KafkaConfig;
+use crate::config::Topics;
+use crate::event::Event;
+
+#[derive(Debug, thiserror::Error)]
+pub enum KafkaError {
+    #[error("failed to connect to kafka: {0}")]
+    Connect(#[from] Error),
+}
+
+/// Produces every event as JSON to partition 0 of the topic configured for its type, keyed by
+/// symbol.
+#[derive(Debug)]
+pub struct KafkaSink {
+    queue: Queue,
+}
+
+impl KafkaSink {
+    pub async fn connect(config: &KafkaConfig) -> Result<Self, KafkaError> {
+        let client = ClientBuilder::new(config.brokers.clone()).build().await?;
+        let (queue, events) = Queue::new("kafka");
+        tokio::spawn(deliver(client, config.topics.clone(), events));
+        Ok(Self { queue })
+    }
+}
+
+impl Sink for KafkaSink {
+    fn name(&self) -> &str {
+        "kafka"
+    }
+
+    fn publish(&mut self, event: &Event) {
+        self.queue.push(event);
+    }
+}
+
+fn record(event: &Event) -> Record {
+    Record {
+        key: Some(event.symbol().as_bytes().to_vec()),
+        value: Some(event.to_json()),
+        headers: BTreeMap::new(),
+        timestamp: event.time(),
+    }
+}
+
+async fn deliver(client: Client, topics: Topics, mut events: mpsc::Receiver<Event>) {
+    let mut partitions: HashMap<String, PartitionClient> = HashMap::new();
+
+    while let Some(event) = events.recv().await {
+        let topic = topics.for_event(&event);
+        if !partitions.contains_key(topic) {
+            match client
+                .partition_client(topic, 0, UnknownTopicHandling::Retry)
+                .await
+            {
+                Ok(partition) => {
+                    partitions.insert(topic.to_owned(), partition);
+                }
+                Err(error) => {
+                    warn!(
+                        "Dropping {} event, kafka topic {}: {}",
+                        event.kind(),
+                        topic,
+                        error
+                    );
+                    continue;
+                }
+            }
+        }
+
+        let partition = &partitions[topic];
+        if let Err(error) = partition
+            .produce(vec![record(&event)], Compression::NoCompression)
+            .await
+        {
+            warn!(
+                "Dropping {} event, kafka topic {}: {}",
+                event.kind(),
+                topic,
+                error
+            );
+            // Look the partition leader up again on the next event
+            partitions.remove(topic);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+
+    use super::*;
+
+    #[test]
+    fn test_record() {
+        let time = DateTime::from_timestamp_millis(1_000).unwrap();
+        let event = Event::Equity {
+            time,
+            symbol: "BTC/USDT",
+            portfolio_value: 1000.0,
+        };
+
+        let record = record(&event);
+        assert_eq!(record.key.as_deref(), Some("BTC/USDT".as_bytes()));
+        assert_eq!(record.value, Some(event.to_json()));
+        assert_eq!(record.timestamp, time);
+        assert_eq!(Topics::default().for_event(&event), "fit.equity");
+    }
+}
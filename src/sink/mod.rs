@@ -0,0 +1,384 @@
+//! Destinations that [`Event`]s are published to, so the bot can feed a wider data platform.
+//!
+//! Publishing never blocks the trading loop: each sink queues events for a background task that
+//! does the network I/O, and drops new events with a warning while its queue is full.
+
+use std::fmt::Debug;
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
+use tracing::warn;
+
+#[cfg(feature = "clickhouse")]
+use self::clickhouse::ClickHouseError;
+#[cfg(feature = "clickhouse")]
+use self::clickhouse::ClickHouseSink;
+#[cfg(feature = "email")]
+use self::email::EmailError;
+#[cfg(feature = "email")]
+use self::email::EmailSink;
+#[cfg(feature = "incidents")]
+use self::incident::IncidentError;
+#[cfg(feature = "incidents")]
+use self::incident::IncidentSink;
+#[cfg(feature = "influx")]
+use self::influx::InfluxError;
+#[cfg(feature = "influx")]
+use self::influx::InfluxSink;
+#[cfg(feature = "kafka")]
+use self::kafka::KafkaError;
+#[cfg(feature = "kafka")]
+use self::kafka::KafkaSink;
+#[cfg(feature = "nats")]
+use self::nats::NatsError;
+#[cfg(feature = "nats")]
+use self::nats::NatsSink;
+#[cfg(feature = "postgres")]
+use self::postgres::PostgresError;
+#[cfg(feature = "postgres")]
+use self::postgres::PostgresSink;
+#[cfg(feature = "redis")]
+use self::redis::RedisSink;
+#[cfg(feature = "redis")]
+use self::redis::RedisSinkError;
+#[cfg(feature = "statsd")]
+use self::statsd::StatsdError;
+#[cfg(feature = "statsd")]
+use self::statsd::StatsdSink;
+#[cfg(feature = "webhooks")]
+use self::webhook::Chat;
+#[cfg(feature = "webhooks")]
+use self::webhook::WebhookSink;
+use crate::config::SinkConfig;
+use crate::event::Event;
+use crate::latency::Latency;
+
+/// Inserts events into ClickHouse tables in bulk.
+#[cfg(feature = "clickhouse")]
+pub mod clickhouse;
+/// Emails critical alerts over SMTP.
+#[cfg(feature = "email")]
+pub mod email;
+/// Opens and resolves PagerDuty and Opsgenie incidents from alerts.
+#[cfg(feature = "incidents")]
+pub mod incident;
+/// Writes events to an InfluxDB bucket in batches.
+#[cfg(feature = "influx")]
+pub mod influx;
+/// Publishes events to Kafka topics.
+#[cfg(feature = "kafka")]
+pub mod kafka;
+/// Publishes events to NATS subjects.
+#[cfg(feature = "nats")]
+pub mod nats;
+/// Persists trading activity to Postgres.
+#[cfg(feature = "postgres")]
+pub mod postgres;
+/// Publishes events to Redis pub/sub channels.
+#[cfg(feature = "redis")]
+pub mod redis;
+/// Pushes metrics to a StatsD agent, such as Datadog's.
+#[cfg(feature = "statsd")]
+pub mod statsd;
+/// Posts notifications to Discord and Slack webhooks.
+#[cfg(feature = "webhooks")]
+pub mod webhook;
+
+/// Events queued per sink before new ones are dropped, so a stalled broker can't hold up trading.
+const QUEUE_CAPACITY: usize = 10_000;
+
+pub trait Sink: Debug + Send {
+    fn name(&self) -> &str;
+
+    /// Queue `event` for delivery.
+    fn publish(&mut self, event: &Event);
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SinkError {
+    #[cfg(feature = "clickhouse")]
+    #[error(transparent)]
+    ClickHouse(#[from] ClickHouseError),
+
+    #[cfg(feature = "email")]
+    #[error(transparent)]
+    Email(#[from] EmailError),
+
+    #[cfg(feature = "incidents")]
+    #[error(transparent)]
+    Incident(#[from] IncidentError),
+
+    #[cfg(feature = "influx")]
+    #[error(transparent)]
+    Influx(#[from] InfluxError),
+
+    #[cfg(feature = "kafka")]
+    #[error(transparent)]
+    Kafka(#[from] KafkaError),
+
+    #[cfg(feature = "nats")]
+    #[error(transparent)]
+    Nats(#[from] NatsError),
+
+    #[cfg(feature = "postgres")]
+    #[error(transparent)]
+    Postgres(#[from] PostgresError),
+
+    #[cfg(feature = "redis")]
+    #[error(transparent)]
+    Redis(#[from] RedisSinkError),
+
+    #[cfg(feature = "statsd")]
+    #[error(transparent)]
+    Statsd(#[from] StatsdError),
+
+    #[error("the {0} sink requires building with the {0} feature")]
+    Disabled(&'static str),
+}
+
+/// Connect every sink enabled in `config`, with the latency histograms for those that push them.
+pub async fn connect(
+    config: &SinkConfig,
+    latency: &Latency,
+) -> Result<Vec<Box<dyn Sink>>, SinkError> {
+    #[cfg_attr(
+        not(any(
+            feature = "clickhouse",
+            feature = "email",
+            feature = "incidents",
+            feature = "influx",
+            feature = "kafka",
+            feature = "nats",
+            feature = "postgres",
+            feature = "redis",
+            feature = "statsd",
+            feature = "webhooks"
+        )),
+        allow(unused_mut)
+    )]
+    let mut sinks: Vec<Box<dyn Sink>> = Vec::new();
+
+    if let Some(clickhouse) = &config.clickhouse {
+        #[cfg(feature = "clickhouse")]
+        sinks.push(Box::new(ClickHouseSink::connect(clickhouse).await?));
+        #[cfg(not(feature = "clickhouse"))]
+        {
+            let _ = clickhouse;
+            return Err(SinkError::Disabled("clickhouse"));
+        }
+    }
+
+    if let Some(discord) = &config.discord {
+        #[cfg(feature = "webhooks")]
+        sinks.push(Box::new(WebhookSink::new(Chat::Discord, discord)));
+        #[cfg(not(feature = "webhooks"))]
+        {
+            let _ = discord;
+            return Err(SinkError::Disabled("webhooks"));
+        }
+    }
+
+    if let Some(email) = &config.email {
+        #[cfg(feature = "email")]
+        sinks.push(Box::new(EmailSink::connect(email)?));
+        #[cfg(not(feature = "email"))]
+        {
+            let _ = email;
+            return Err(SinkError::Disabled("email"));
+        }
+    }
+
+    if let Some(influx) = &config.influx {
+        #[cfg(feature = "influx")]
+        sinks.push(Box::new(InfluxSink::connect(influx).await?));
+        #[cfg(not(feature = "influx"))]
+        {
+            let _ = influx;
+            return Err(SinkError::Disabled("influx"));
+        }
+    }
+
+    if let Some(kafka) = &config.kafka {
+        #[cfg(feature = "kafka")]
+        sinks.push(Box::new(KafkaSink::connect(kafka).await?));
+        #[cfg(not(feature = "kafka"))]
+        {
+            let _ = kafka;
+            return Err(SinkError::Disabled("kafka"));
+        }
+    }
+
+    if let Some(nats) = &config.nats {
+        #[cfg(feature = "nats")]
+        sinks.push(Box::new(NatsSink::connect(nats).await?));
+        #[cfg(not(feature = "nats"))]
+        {
+            let _ = nats;
+            return Err(SinkError::Disabled("nats"));
+        }
+    }
+
+    if let Some(opsgenie) = &config.opsgenie {
+        #[cfg(feature = "incidents")]
+        sinks.push(Box::new(IncidentSink::opsgenie(opsgenie)?));
+        #[cfg(not(feature = "incidents"))]
+        {
+            let _ = opsgenie;
+            return Err(SinkError::Disabled("incidents"));
+        }
+    }
+
+    if let Some(pagerduty) = &config.pagerduty {
+        #[cfg(feature = "incidents")]
+        sinks.push(Box::new(IncidentSink::pagerduty(pagerduty)?));
+        #[cfg(not(feature = "incidents"))]
+        {
+            let _ = pagerduty;
+            return Err(SinkError::Disabled("incidents"));
+        }
+    }
+
+    if let Some(postgres) = &config.postgres {
+        #[cfg(feature = "postgres")]
+        sinks.push(Box::new(PostgresSink::connect(postgres).await?));
+        #[cfg(not(feature = "postgres"))]
+        {
+            let _ = postgres;
+            return Err(SinkError::Disabled("postgres"));
+        }
+    }
+
+    if let Some(redis) = &config.redis {
+        #[cfg(feature = "redis")]
+        sinks.push(Box::new(RedisSink::connect(redis).await?));
+        #[cfg(not(feature = "redis"))]
+        {
+            let _ = redis;
+            return Err(SinkError::Disabled("redis"));
+        }
+    }
+
+    if let Some(slack) = &config.slack {
+        #[cfg(feature = "webhooks")]
+        sinks.push(Box::new(WebhookSink::new(Chat::Slack, slack)));
+        #[cfg(not(feature = "webhooks"))]
+        {
+            let _ = slack;
+            return Err(SinkError::Disabled("webhooks"));
+        }
+    }
+
+    if let Some(statsd) = &config.statsd {
+        #[cfg(feature = "statsd")]
+        sinks.push(Box::new(StatsdSink::connect(statsd, latency).await?));
+        #[cfg(not(feature = "statsd"))]
+        {
+            let _ = (statsd, latency);
+            return Err(SinkError::Disabled("statsd"));
+        }
+    }
+
+    Ok(sinks)
+}
+
+/// Bounded hand-off from the trading loop to a sink's delivery task.
+#[derive(Debug)]
+#[cfg_attr(
+    not(any(
+        feature = "clickhouse",
+        feature = "email",
+        feature = "incidents",
+        feature = "influx",
+        feature = "kafka",
+        feature = "nats",
+        feature = "postgres",
+        feature = "redis",
+        feature = "statsd",
+        feature = "webhooks"
+    )),
+    allow(dead_code)
+)]
+struct Queue {
+    name: &'static str,
+    sender: mpsc::Sender<Event>,
+    dropped: u64,
+}
+
+#[cfg_attr(
+    not(any(
+        feature = "clickhouse",
+        feature = "email",
+        feature = "incidents",
+        feature = "influx",
+        feature = "kafka",
+        feature = "nats",
+        feature = "postgres",
+        feature = "redis",
+        feature = "statsd",
+        feature = "webhooks"
+    )),
+    allow(dead_code)
+)]
+impl Queue {
+    fn new(name: &'static str) -> (Self, mpsc::Receiver<Event>) {
+        let (sender, receiver) = mpsc::channel(QUEUE_CAPACITY);
+        let queue = Self {
+            name,
+            sender,
+            dropped: 0,
+        };
+        (queue, receiver)
+    }
+
+    fn push(&mut self, event: &Event) {
+        match self.sender.try_send(event.clone()) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                self.dropped += 1;
+                // Warn on the first drop and then less and less often while the sink is stalled
+                if self.dropped.is_power_of_two() {
+                    warn!(
+                        "{} sink is falling behind, dropped {} events",
+                        self.name, self.dropped
+                    );
+                }
+            }
+            Err(TrySendError::Closed(_)) => warn!("{} sink has stopped", self.name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+
+    fn equity() -> Event {
+        Event::Equity {
+            time: Utc::now(),
+            symbol: "BTC/USDT",
+            portfolio_value: 1000.0,
+        }
+    }
+
+    #[test]
+    fn test_queue_drops_when_full() {
+        let (mut queue, mut receiver) = Queue::new("test");
+        for _ in 0..QUEUE_CAPACITY + 5 {
+            queue.push(&equity());
+        }
+        assert_eq!(queue.dropped, 5);
+
+        receiver.try_recv().unwrap();
+        queue.push(&equity());
+        assert_eq!(queue.dropped, 5);
+    }
+
+    #[tokio::test]
+    async fn test_connect_without_sinks() {
+        let sinks = connect(&SinkConfig::default(), &Latency::default())
+            .await
+            .unwrap();
+        assert!(sinks.is_empty());
+    }
+}
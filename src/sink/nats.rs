@@ -0,0 +1,71 @@
+use async_nats::Client;
+use async_nats::ConnectError;
+use tokio::sync::mpsc;
+use tracing::warn;
+
+use super::Queue;
+use super::Sink;
+use crate::config::NatsConfig;
+use crate::config::Topics;
+use crate::event::Event;
+
+#[derive(Debug, thiserror::Error)]
+pub enum NatsError {
+    #[error("failed to connect to nats: {0}")]
+    Connect(#[from] ConnectError),
+}
+
+/// Publishes every event as JSON to the subject configured for its type. The client reconnects
+/// on its own, so events are only lost while the server is unreachable.
+#[derive(Debug)]
+pub struct NatsSink {
+    queue: Queue,
+}
+
+impl NatsSink {
+    pub async fn connect(config: &NatsConfig) -> Result<Self, NatsError> {
+        let client = async_nats::connect(config.url.as_str()).await?;
+        let (queue, events) = Queue::new("nats");
+        tokio::spawn(deliver(client, config.subjects.clone(), events));
+        Ok(Self { queue })
+    }
+}
+
+impl Sink for NatsSink {
+    fn name(&self) -> &str {
+        "nats"
+    }
+
+    fn publish(&mut self, event: &Event) {
+        self.queue.push(event);
+    }
+}
+
+async fn deliver(client: Client, subjects: Topics, mut events: mpsc::Receiver<Event>) {
+    while let Some(event) = events.recv().await {
+        let subject = subjects.for_event(&event).to_owned();
+        if let Err(error) = client.publish(subject, event.to_json().into()).await {
+            warn!(
+                "Dropping {} event, nats subject {}: {}",
+                event.kind(),
+                subjects.for_event(&event),
+                error
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_connect_fails_without_server() {
+        let config = NatsConfig {
+            url: "nats://127.0.0.1:1".to_owned(),
+            subjects: Topics::default(),
+        };
+        let error = NatsSink::connect(&config).await.unwrap_err();
+        assert!(error.to_string().starts_with("failed to connect to nats"));
+    }
+}
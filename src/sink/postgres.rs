@@ -0,0 +1,209 @@
+use barter_integration::model::Side;
+use sqlx::migrate::MigrateError;
+use sqlx::migrate::Migrator;
+use sqlx::postgres::PgPoolOptions;
+use sqlx::Error;
+use sqlx::PgPool;
+use tokio::sync::mpsc;
+use tracing::warn;
+
+use super::Queue;
+use super::Sink;
+use crate::config::PostgresConfig;
+use crate::event::Event;
+use crate::event::OrderType;
+
+/// The schema, embedded from `migrations/` and applied on connect.
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+#[derive(Debug, thiserror::Error)]
+pub enum PostgresError {
+    #[error("failed to connect to postgres: {0}")]
+    Connect(#[from] Error),
+
+    #[error("failed to migrate postgres: {0}")]
+    Migrate(#[from] MigrateError),
+}
+
+/// Persists orders, trades, open positions and equity. Signals, features, rebalances and session
+/// summaries are left to the time-series sinks.
+#[derive(Debug)]
+pub struct PostgresSink {
+    queue: Queue,
+}
+
+impl PostgresSink {
+    pub async fn connect(config: &PostgresConfig) -> Result<Self, PostgresError> {
+        let pool = PgPoolOptions::new()
+            .max_connections(2)
+            .connect(&config.url)
+            .await?;
+        MIGRATOR.run(&pool).await?;
+
+        // The bot starts flat, so positions left by a previous run of this instance are stale
+        sqlx::query("DELETE FROM positions WHERE instance = $1")
+            .bind(&config.instance)
+            .execute(&pool)
+            .await?;
+
+        let (queue, events) = Queue::new("postgres");
+        tokio::spawn(deliver(pool, config.instance.clone(), events));
+        Ok(Self { queue })
+    }
+}
+
+impl Sink for PostgresSink {
+    fn name(&self) -> &str {
+        "postgres"
+    }
+
+    fn publish(&mut self, event: &Event) {
+        self.queue.push(event);
+    }
+}
+
+async fn deliver(pool: PgPool, instance: String, mut events: mpsc::Receiver<Event>) {
+    while let Some(event) = events.recv().await {
+        if let Err(error) = store(&pool, &instance, &event).await {
+            warn!("Dropping {} event, postgres: {}", event.kind(), error);
+        }
+    }
+}
+
+async fn store(pool: &PgPool, instance: &str, event: &Event) -> Result<(), Error> {
+    match event {
+        Event::Order {
+            time,
+            symbol,
+            strategy,
+            side,
+            price,
+            size,
+            book_time,
+        } => {
+            sqlx::query(
+                "INSERT INTO orders (instance, time, symbol, strategy, side, price, size, \
+                 exchange_time, received_time) \
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+            )
+            .bind(instance)
+            .bind(time)
+            .bind(symbol)
+            .bind(strategy)
+            .bind(side.to_string())
+            .bind(price)
+            .bind(size)
+            .bind(book_time.map(|book| book.exchange_time))
+            .bind(book_time.map(|book| book.received_time))
+            .execute(pool)
+            .await?;
+        }
+        Event::Fill {
+            time,
+            symbol,
+            strategy,
+            sleeve,
+            execution,
+            fill,
+            book_time,
+        } => {
+            // Sleeves are numbered from the configured members, so always fit
+            let sleeve = *sleeve as i32;
+            let mut transaction = pool.begin().await?;
+            sqlx::query(
+                "INSERT INTO trades (instance, time, symbol, strategy, side, price, size, fee, \
+                 venue, order_type, decision_price, exchange_time, received_time, queue_ahead, \
+                 sleeve) \
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)",
+            )
+            .bind(instance)
+            .bind(time)
+            .bind(symbol)
+            .bind(strategy)
+            .bind(fill.side.to_string())
+            .bind(fill.price)
+            .bind(fill.size)
+            .bind(fill.fee)
+            .bind(&execution.venue)
+            .bind(match execution.order_type {
+                OrderType::Limit => "limit",
+                OrderType::Market => "market",
+            })
+            .bind(execution.decision_price)
+            .bind(book_time.map(|book| book.exchange_time))
+            .bind(book_time.map(|book| book.received_time))
+            .bind(execution.queue_ahead)
+            .bind(sleeve)
+            .execute(&mut *transaction)
+            .await?;
+
+            let position = match fill.side {
+                Side::Buy => sqlx::query(
+                    "INSERT INTO positions \
+                     (instance, symbol, strategy, entry_price, size, opened_at, sleeve) \
+                     VALUES ($1, $2, $3, $4, $5, $6, $7)",
+                )
+                .bind(instance)
+                .bind(symbol)
+                .bind(strategy)
+                .bind(fill.price)
+                .bind(fill.size)
+                .bind(time)
+                .bind(sleeve),
+                // Sells close the latest entry, as the trading state does
+                Side::Sell => sqlx::query(
+                    "DELETE FROM positions WHERE id = (\
+                         SELECT id FROM positions \
+                         WHERE instance = $1 AND symbol = $2 AND strategy = $3 AND sleeve = $4 \
+                         ORDER BY id DESC LIMIT 1)",
+                )
+                .bind(instance)
+                .bind(symbol)
+                .bind(strategy)
+                .bind(sleeve),
+            };
+            position.execute(&mut *transaction).await?;
+            transaction.commit().await?;
+        }
+        Event::Equity {
+            time,
+            symbol,
+            portfolio_value,
+        } => {
+            sqlx::query(
+                "INSERT INTO equity (instance, time, symbol, portfolio_value) \
+                 VALUES ($1, $2, $3, $4) ON CONFLICT DO NOTHING",
+            )
+            .bind(instance)
+            .bind(time)
+            .bind(symbol)
+            .bind(portfolio_value)
+            .execute(pool)
+            .await?;
+        }
+        Event::Features { .. }
+        | Event::Signal { .. }
+        | Event::Rebalance { .. }
+        | Event::Session { .. }
+        | Event::Alert { .. } => {}
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrations_are_embedded() {
+        let sql: String = MIGRATOR
+            .iter()
+            .map(|migration| migration.sql.as_ref())
+            .collect();
+        for table in ["orders", "trades", "positions", "equity"] {
+            assert!(sql.contains(&format!("CREATE TABLE {table} (")), "{table}");
+        }
+        assert!(sql.contains("ADD COLUMN received_time TIMESTAMPTZ"));
+        assert!(sql.contains("ADD COLUMN sleeve INTEGER"));
+    }
+}
@@ -0,0 +1,67 @@
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+use redis::Client;
+use redis::RedisError;
+use tokio::sync::mpsc;
+use tracing::warn;
+
+use super::Queue;
+use super::Sink;
+use crate::config::RedisConfig;
+use crate::config::Topics;
+use crate::event::Event;
+
+#[derive(Debug, thiserror::Error)]
+pub enum RedisSinkError {
+    #[error("failed to connect to redis: {0}")]
+    Connect(#[from] RedisError),
+}
+
+/// Publishes every event as JSON to the channel configured for its type. Like any Redis pub/sub,
+/// events are only seen by subscribers connected at the time.
+#[derive(Debug)]
+pub struct RedisSink {
+    queue: Queue,
+}
+
+impl RedisSink {
+    pub async fn connect(config: &RedisConfig) -> Result<Self, RedisSinkError> {
+        let client = Client::open(config.url.as_str())?;
+        let connection = ConnectionManager::new(client).await?;
+        let (queue, events) = Queue::new("redis");
+        tokio::spawn(deliver(connection, config.channels.clone(), events));
+        Ok(Self { queue })
+    }
+}
+
+impl Sink for RedisSink {
+    fn name(&self) -> &str {
+        "redis"
+    }
+
+    fn publish(&mut self, event: &Event) {
+        self.queue.push(event);
+    }
+}
+
+async fn deliver(
+    mut connection: ConnectionManager,
+    channels: Topics,
+    mut events: mpsc::Receiver<Event>,
+) {
+    while let Some(event) = events.recv().await {
+        let channel = channels.for_event(&event);
+        // The manager reconnects in the background after a failure
+        if let Err(error) = connection
+            .publish::<_, _, ()>(channel, event.to_json())
+            .await
+        {
+            warn!(
+                "Dropping {} event, redis channel {}: {}",
+                event.kind(),
+                channel,
+                error
+            );
+        }
+    }
+}
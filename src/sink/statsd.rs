@@ -0,0 +1,412 @@
+use std::io;
+use std::net::Ipv4Addr;
+use std::net::Ipv6Addr;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::sync::mpsc;
+use tokio::time::MissedTickBehavior;
+use tracing::warn;
+
+use super::Queue;
+use super::Sink;
+use crate::config::StatsdConfig;
+use crate::event::Event;
+use crate::event::OrderType;
+use crate::event::Severity;
+use crate::features::FEATURE_NAMES;
+use crate::latency::Latency;
+use crate::strategy::Signal;
+
+#[derive(Debug, thiserror::Error)]
+pub enum StatsdError {
+    #[error("failed to resolve statsd address {0}: {1}")]
+    Resolve(String, io::Error),
+
+    #[error("failed to open a socket to statsd: {0}")]
+    Socket(#[from] io::Error),
+}
+
+/// Pushes counters, gauges and timers to a StatsD agent: counts of signals, orders, fills and
+/// alerts, gauges of equity, features, sleeve weights and the last session, and the
+/// [latency](crate::latency) of every stage the `/metrics` endpoint reports as histograms.
+///
+/// StatsD is fire and forget over UDP, so metrics sent while the agent is down are lost.
+#[derive(Debug)]
+pub struct StatsdSink {
+    queue: Queue,
+}
+
+impl StatsdSink {
+    pub async fn connect(config: &StatsdConfig, latency: &Latency) -> Result<Self, StatsdError> {
+        let address = tokio::net::lookup_host(&config.address)
+            .await
+            .and_then(|mut addresses| {
+                addresses
+                    .next()
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no addresses found"))
+            })
+            .map_err(|error| StatsdError::Resolve(config.address.clone(), error))?;
+        let local: SocketAddr = match address {
+            SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+            SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+        };
+        let socket = UdpSocket::bind(local).await?;
+        socket.connect(address).await?;
+
+        latency.keep_samples();
+        let (queue, events) = Queue::new("statsd");
+        tokio::spawn(deliver(
+            socket,
+            Format::new(config),
+            config.max_packet_size.max(1),
+            Duration::from_millis(config.flush_interval_ms.max(1)),
+            latency.clone(),
+            events,
+        ));
+        Ok(Self { queue })
+    }
+}
+
+impl Sink for StatsdSink {
+    fn name(&self) -> &str {
+        "statsd"
+    }
+
+    fn publish(&mut self, event: &Event) {
+        self.queue.push(event);
+    }
+}
+
+/// How metric lines are written.
+#[derive(Debug, Clone)]
+struct Format {
+    prefix: String,
+    dogstatsd: bool,
+    // Configured tags, already joined for every line
+    tags: String,
+}
+
+impl Format {
+    fn new(config: &StatsdConfig) -> Self {
+        Self {
+            prefix: config.prefix.trim_end_matches('.').to_owned(),
+            dogstatsd: config.dogstatsd,
+            tags: config
+                .tags
+                .iter()
+                .map(|tag| clean(tag))
+                .collect::<Vec<_>>()
+                .join(","),
+        }
+    }
+
+    /// A line such as `fit.fills:1|c|#env:prod,strategy:imbalance`, with `kind` `c` for a counter,
+    /// `g` for a gauge or `ms` for a timer.
+    fn line(&self, name: &str, value: f64, kind: &str, tags: &[(&str, &str)]) -> String {
+        let mut line = if self.prefix.is_empty() {
+            format!("{name}:{value}|{kind}")
+        } else {
+            format!("{}.{name}:{value}|{kind}", self.prefix)
+        };
+        if self.dogstatsd {
+            let tags = tags
+                .iter()
+                .map(|(key, value)| format!("{key}:{}", clean(value)))
+                .chain((!self.tags.is_empty()).then(|| self.tags.clone()));
+            let tags = tags.collect::<Vec<_>>().join(",");
+            if !tags.is_empty() {
+                line.push_str("|#");
+                line.push_str(&tags);
+            }
+        }
+        line
+    }
+
+    /// The metric lines for `event`, leaving out values StatsD can't represent.
+    fn metrics(&self, event: &Event) -> Vec<String> {
+        let symbol = event.symbol();
+        let mut lines = Vec::new();
+        let mut push = |name: &str, value: f64, kind: &str, tags: &[(&str, &str)]| {
+            if value.is_finite() {
+                lines.push(self.line(name, value, kind, tags));
+            }
+        };
+        match event {
+            Event::Features { features, .. } => {
+                for (name, value) in FEATURE_NAMES.iter().zip(features.to_array()) {
+                    push(
+                        &format!("features.{name}"),
+                        value,
+                        "g",
+                        &[("symbol", symbol)],
+                    );
+                }
+            }
+            Event::Signal {
+                strategy, signal, ..
+            } => {
+                let signal = match signal {
+                    Signal::Buy => "buy",
+                    Signal::Sell => "sell",
+                    Signal::Hold => "hold",
+                };
+                let tags = [
+                    ("symbol", symbol),
+                    ("strategy", strategy),
+                    ("signal", signal),
+                ];
+                push("signals", 1.0, "c", &tags);
+            }
+            Event::Order { strategy, side, .. } => {
+                let side = side.to_string();
+                let tags = [("symbol", symbol), ("strategy", strategy), ("side", &side)];
+                push("orders", 1.0, "c", &tags);
+            }
+            Event::Fill {
+                strategy,
+                execution,
+                fill,
+                ..
+            } => {
+                let side = fill.side.to_string();
+                let order_type = match execution.order_type {
+                    OrderType::Limit => "limit",
+                    OrderType::Market => "market",
+                };
+                let tags = [
+                    ("symbol", symbol),
+                    ("strategy", strategy),
+                    ("side", &side),
+                    ("venue", &execution.venue),
+                    ("order_type", order_type),
+                ];
+                push("fills", 1.0, "c", &tags);
+                push("fees", fill.fee, "c", &tags);
+            }
+            Event::Equity {
+                portfolio_value, ..
+            } => push(
+                "portfolio_value",
+                *portfolio_value,
+                "g",
+                &[("symbol", symbol)],
+            ),
+            Event::Rebalance {
+                strategy,
+                weight,
+                capital,
+                ..
+            } => {
+                let tags = [("symbol", symbol), ("strategy", strategy)];
+                push("sleeve.weight", *weight, "g", &tags);
+                push("sleeve.capital", *capital, "g", &tags);
+            }
+            Event::Session { summary, .. } => {
+                let tags = [("symbol", symbol)];
+                push("session.trades", summary.trades as f64, "g", &tags);
+                push("session.win_rate_pct", summary.win_rate_pct, "g", &tags);
+                push("session.gross_pnl", summary.gross_pnl, "g", &tags);
+                push("session.net_pnl", summary.net_pnl, "g", &tags);
+                push("session.fees", summary.fees, "g", &tags);
+                push(
+                    "session.max_drawdown_pct",
+                    summary.max_drawdown_pct,
+                    "g",
+                    &tags,
+                );
+            }
+            Event::Alert { alert, .. } => {
+                let severity = match alert.severity {
+                    Severity::Info => "info",
+                    Severity::Warning => "warning",
+                    Severity::Critical => "critical",
+                };
+                push(
+                    "alerts",
+                    1.0,
+                    "c",
+                    &[("symbol", symbol), ("severity", severity)],
+                );
+            }
+        }
+        lines
+    }
+}
+
+/// Swap out the characters that delimit DogStatsD tags.
+fn clean(tag: &str) -> String {
+    tag.replace([',', '|', '#', '\n'], "_")
+}
+
+/// Pack `lines` into as few datagrams as fit in `max_packet_size` bytes each, one line per line.
+/// A line longer than that goes in a datagram of its own.
+fn packets(lines: &[String], max_packet_size: usize) -> Vec<String> {
+    let mut packets: Vec<String> = Vec::new();
+    for line in lines {
+        match packets.last_mut() {
+            Some(packet) if packet.len() + 1 + line.len() <= max_packet_size => {
+                packet.push('\n');
+                packet.push_str(line);
+            }
+            _ => packets.push(line.clone()),
+        }
+    }
+    packets
+}
+
+async fn deliver(
+    socket: UdpSocket,
+    format: Format,
+    max_packet_size: usize,
+    flush_interval: Duration,
+    latency: Latency,
+    mut events: mpsc::Receiver<Event>,
+) {
+    let mut lines: Vec<String> = Vec::new();
+    let mut interval = tokio::time::interval(flush_interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        let closed = tokio::select! {
+            event = events.recv() => match event {
+                Some(event) => {
+                    lines.extend(format.metrics(&event));
+                    continue;
+                }
+                None => true,
+            },
+            _ = interval.tick() => false,
+        };
+
+        for (stage, seconds) in latency.take_samples() {
+            lines.push(format.line(
+                "latency",
+                seconds * 1000.0,
+                "ms",
+                &[("stage", stage.name())],
+            ));
+        }
+        for packet in packets(&lines, max_packet_size) {
+            if let Err(error) = socket.send(packet.as_bytes()).await {
+                warn!("Dropping statsd metrics, sending to the agent: {}", error);
+            }
+        }
+        lines.clear();
+        if closed {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use barter_integration::model::Side;
+    use chrono::Utc;
+
+    use super::*;
+    use crate::event::Execution;
+    use crate::latency::Stage;
+    use crate::Fill;
+
+    fn config(address: &str) -> StatsdConfig {
+        StatsdConfig {
+            address: address.to_owned(),
+            prefix: "fit".to_owned(),
+            dogstatsd: true,
+            tags: vec!["env:prod".to_owned()],
+            flush_interval_ms: 10,
+            max_packet_size: 1_432,
+        }
+    }
+
+    fn fill() -> Event {
+        Event::Fill {
+            time: Utc::now(),
+            symbol: "BTC/USDT",
+            strategy: "imbalance".to_owned(),
+            sleeve: 0,
+            execution: Execution::paper(OrderType::Market, 100.5),
+            fill: Fill {
+                side: Side::Sell,
+                price: 100.0,
+                size: 0.001,
+                fee: 0.0005,
+            },
+            book_time: None,
+        }
+    }
+
+    #[test]
+    fn test_metric_lines() {
+        let format = Format::new(&config("localhost:8125"));
+        assert_eq!(
+            format.metrics(&fill()),
+            [
+                "fit.fills:1|c|#symbol:BTC/USDT,strategy:imbalance,side:sell,venue:paper,\
+                 order_type:market,env:prod",
+                "fit.fees:0.0005|c|#symbol:BTC/USDT,strategy:imbalance,side:sell,venue:paper,\
+                 order_type:market,env:prod",
+            ]
+        );
+
+        let plain = Format::new(&StatsdConfig {
+            dogstatsd: false,
+            ..config("localhost:8125")
+        });
+        let equity = |portfolio_value| Event::Equity {
+            time: Utc::now(),
+            symbol: "BTC/USDT",
+            portfolio_value,
+        };
+        assert_eq!(
+            plain.metrics(&equity(1000.5)),
+            ["fit.portfolio_value:1000.5|g"]
+        );
+        assert!(plain.metrics(&equity(f64::NAN)).is_empty());
+        assert_eq!(clean("team:a,b|c"), "team:a_b_c");
+    }
+
+    #[test]
+    fn test_packets_fit_the_limit() {
+        let lines: Vec<String> = ["a:1|c", "b:2|c", "c:3|c", "a_long_metric:4|c"]
+            .map(str::to_owned)
+            .into();
+        assert_eq!(
+            packets(&lines, 11),
+            ["a:1|c\nb:2|c", "c:3|c", "a_long_metric:4|c"]
+        );
+        assert!(packets(&[], 11).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_push_to_agent() {
+        let agent = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let address = agent.local_addr().unwrap().to_string();
+        let latency = Latency::default();
+        let mut sink = StatsdSink::connect(&config(&address), &latency)
+            .await
+            .unwrap();
+
+        let now = Utc::now();
+        latency.record(
+            Stage::Decision,
+            now,
+            now + chrono::TimeDelta::milliseconds(2),
+        );
+        sink.publish(&fill());
+
+        let mut received = String::new();
+        while !(received.contains("fit.fills") && received.contains("fit.latency")) {
+            let mut buffer = [0; 1_500];
+            let length = tokio::time::timeout(Duration::from_secs(5), agent.recv(&mut buffer))
+                .await
+                .expect("no metrics within 5s")
+                .unwrap();
+            received.push_str(std::str::from_utf8(&buffer[..length]).unwrap());
+            received.push('\n');
+        }
+        assert!(received.contains("fit.fills:1|c|#"));
+        assert!(received.contains("fit.latency:2|ms|#stage:decision,env:prod"));
+    }
+}
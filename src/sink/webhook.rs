@@ -0,0 +1,300 @@
+//! Notifications posted to Discord or Slack channels through incoming webhooks, a line of text
+//! per event, so the people running the bot hear about its trades and alerts where they already
+//! talk.
+//!
+//! Each type of event goes to the webhook configured for it, so trades can be posted to one
+//! channel and alerts to another, and types without one are left out.
+
+use reqwest::header::HeaderMap;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::header::RETRY_AFTER;
+use reqwest::Client;
+use reqwest::StatusCode;
+use serde_json::json;
+use serde_json::Value;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::warn;
+
+use super::Queue;
+use super::Sink;
+use crate::config::WebhookConfig;
+use crate::event::Event;
+use crate::event::Severity;
+
+/// Longest a rate limited post waits to be retried.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// The chat service a webhook belongs to, which decides the shape of what is posted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chat {
+    Discord,
+    Slack,
+}
+
+impl Chat {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Discord => "discord",
+            Self::Slack => "slack",
+        }
+    }
+
+    /// The JSON body posting `text` to a webhook.
+    fn payload(self, text: &str) -> Value {
+        match self {
+            Self::Discord => json!({ "content": text }),
+            Self::Slack => json!({ "text": text }),
+        }
+    }
+}
+
+/// Posts every event with a webhook configured for its type to that webhook. Posts rate limited
+/// by the service are retried once, after as long as it asks for, and dropped with a warning if
+/// they fail again.
+#[derive(Debug)]
+pub struct WebhookSink {
+    chat: Chat,
+    queue: Queue,
+}
+
+impl WebhookSink {
+    pub fn new(chat: Chat, config: &WebhookConfig) -> Self {
+        let (queue, events) = Queue::new(chat.name());
+        tokio::spawn(deliver(Client::new(), chat, config.clone(), events));
+        Self { chat, queue }
+    }
+}
+
+impl Sink for WebhookSink {
+    fn name(&self) -> &str {
+        self.chat.name()
+    }
+
+    fn publish(&mut self, event: &Event) {
+        self.queue.push(event);
+    }
+}
+
+/// A line of text describing `event`, for those that can be posted.
+fn message(event: &Event) -> Option<String> {
+    let symbol = event.symbol();
+    let message = match event {
+        Event::Features { .. } | Event::Equity { .. } => return None,
+        Event::Signal {
+            strategy, signal, ..
+        } => format!("{symbol} {strategy}: {signal:?} signal"),
+        Event::Order {
+            strategy,
+            side,
+            price,
+            size,
+            ..
+        } => format!("{symbol} {strategy}: {side} order for {size} at {price}"),
+        Event::Fill {
+            strategy,
+            execution,
+            fill,
+            ..
+        } => format!(
+            "{symbol} {strategy}: {} {} filled at {} on {}, {:.2} bps slippage, {} in fees",
+            fill.side,
+            fill.size,
+            fill.price,
+            execution.venue,
+            execution.slippage_bps(fill),
+            fill.fee
+        ),
+        Event::Rebalance {
+            strategy,
+            weight,
+            capital,
+            ..
+        } => format!(
+            "{symbol} {strategy}: rebalanced to {:.1}% of equity, {capital:.2} capital",
+            weight * 100.0
+        ),
+        Event::Session { summary, .. } => format!(
+            "{symbol} session from {}: {} trades, {:.1}% won, {:.2} net PnL after {:.2} in fees, \
+             {:.2}% max drawdown",
+            summary.start.format("%Y-%m-%d %H:%M UTC"),
+            summary.trades,
+            summary.win_rate_pct,
+            summary.net_pnl,
+            summary.fees,
+            summary.max_drawdown_pct
+        ),
+        Event::Alert { alert, .. } => {
+            let severity = match alert.severity {
+                Severity::Info => "Info",
+                Severity::Warning => "Warning",
+                Severity::Critical => "Critical",
+            };
+            format!("{severity}: {symbol} {}", alert.message)
+        }
+    };
+    Some(message)
+}
+
+/// How long a rate limited response asks to wait before posting again, a second if it doesn't say.
+fn retry_after(headers: &HeaderMap) -> Duration {
+    headers
+        .get(RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<f64>().ok())
+        .filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+        .map_or(Duration::from_secs(1), Duration::from_secs_f64)
+        .min(MAX_RETRY_AFTER)
+}
+
+async fn post(client: &Client, chat: Chat, url: &str, event: &Event, body: &str) {
+    for retried in [false, true] {
+        let response = client
+            .post(url)
+            .header(CONTENT_TYPE, "application/json")
+            .body(body.to_owned())
+            .send()
+            .await;
+        let error = match response {
+            Ok(response) if response.status() == StatusCode::TOO_MANY_REQUESTS && !retried => {
+                tokio::time::sleep(retry_after(response.headers())).await;
+                continue;
+            }
+            Ok(response) => match response.error_for_status() {
+                Ok(_) => return,
+                Err(error) => error,
+            },
+            Err(error) => error,
+        };
+        // Webhook URLs are secret, so only the service is named
+        warn!(
+            "Dropping {} event, posting to {}: {}",
+            event.kind(),
+            chat.name(),
+            error.without_url()
+        );
+        return;
+    }
+}
+
+async fn deliver(
+    client: Client,
+    chat: Chat,
+    config: WebhookConfig,
+    mut events: mpsc::Receiver<Event>,
+) {
+    while let Some(event) = events.recv().await {
+        let (Some(url), Some(message)) = (config.for_event(&event), message(&event)) else {
+            continue;
+        };
+        let body = chat.payload(&message).to_string();
+        post(&client, chat, url, &event, &body).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use barter_integration::model::Side;
+    use chrono::Utc;
+    use reqwest::header::HeaderValue;
+
+    use super::*;
+    use crate::event::Alert;
+    use crate::event::Execution;
+    use crate::event::OrderType;
+    use crate::features::Features;
+    use crate::Fill;
+
+    fn fill() -> Event {
+        Event::Fill {
+            time: Utc::now(),
+            symbol: "BTC/USDT",
+            strategy: "imbalance".to_owned(),
+            sleeve: 0,
+            execution: Execution::paper(OrderType::Limit, 100.0),
+            fill: Fill {
+                side: Side::Buy,
+                price: 100.01,
+                size: 0.001,
+                fee: 0.0005,
+            },
+            book_time: None,
+        }
+    }
+
+    fn alert() -> Event {
+        Event::Alert {
+            time: Utc::now(),
+            symbol: "BTC/USDT",
+            alert: Alert {
+                severity: Severity::Critical,
+                message: "feed degraded for 120s".to_owned(),
+                condition: None,
+                resolved: false,
+            },
+        }
+    }
+
+    #[test]
+    fn test_messages() {
+        assert_eq!(
+            message(&fill()).unwrap(),
+            "BTC/USDT imbalance: buy 0.001 filled at 100.01 on paper, 1.00 bps slippage, \
+             0.0005 in fees"
+        );
+        assert_eq!(
+            message(&alert()).unwrap(),
+            "Critical: BTC/USDT feed degraded for 120s"
+        );
+        let features = Event::Features {
+            time: Utc::now(),
+            symbol: "BTC/USDT",
+            features: Features::default(),
+            book_time: None,
+        };
+        assert_eq!(message(&features), None);
+
+        assert_eq!(
+            Chat::Discord.payload("hi").to_string(),
+            r#"{"content":"hi"}"#
+        );
+        assert_eq!(Chat::Slack.payload("hi").to_string(), r#"{"text":"hi"}"#);
+    }
+
+    #[test]
+    fn test_routes() {
+        let config = WebhookConfig {
+            fill: Some("https://example.com/trades".to_owned()),
+            alert: Some("https://example.com/errors".to_owned()),
+            ..WebhookConfig::default()
+        };
+        assert_eq!(
+            config.for_event(&fill()),
+            Some("https://example.com/trades")
+        );
+        assert_eq!(
+            config.for_event(&alert()),
+            Some("https://example.com/errors")
+        );
+        let order = Event::Order {
+            time: Utc::now(),
+            symbol: "BTC/USDT",
+            strategy: "imbalance".to_owned(),
+            side: Side::Buy,
+            price: 100.0,
+            size: 0.001,
+            book_time: None,
+        };
+        assert_eq!(config.for_event(&order), None);
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), Duration::from_secs(1));
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("2.5"));
+        assert_eq!(retry_after(&headers), Duration::from_millis(2_500));
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("3600"));
+        assert_eq!(retry_after(&headers), MAX_RETRY_AFTER);
+    }
+}
@@ -0,0 +1,211 @@
+//! Periodic crash recovery snapshots of the engine state, each covering the [event
+//! log](crate::wal) up to a sequence number so it can be truncated and only the tail replayed.
+//!
+//! A snapshot file is the magic bytes `FITSNAP1`, the CRC-32 of the rest of the file as four
+//! little-endian bytes, then the gzipped JSON [`EngineSnapshot`]. Files are written under a
+//! temporary name and renamed into place, and a snapshot failing its checksum on load is skipped
+//! in favour of the one before it.
+
+use chrono::DateTime;
+use chrono::Utc;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::Deserialize;
+use serde::Serialize;
+use std::fs::File;
+use std::io::Read;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use tracing::warn;
+
+use crate::allocation::AllocatorSnapshot;
+
+const MAGIC: &[u8; 8] = b"FITSNAP1";
+const HEADER_LEN: usize = MAGIC.len() + 4;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SnapshotError {
+    #[error("snapshot error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("{} is not a snapshot", .0.display())]
+    Format(PathBuf),
+
+    #[error("{} failed its checksum", .0.display())]
+    Checksum(PathBuf),
+
+    #[error("corrupt snapshot {}: {error}", .path.display())]
+    Corrupt {
+        path: PathBuf,
+        error: serde_json::Error,
+    },
+}
+
+/// Everything needed to resume trading: the allocator state after the event log entry numbered
+/// `sequence`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EngineSnapshot {
+    pub sequence: u64,
+    pub time: DateTime<Utc>,
+    pub allocator: AllocatorSnapshot,
+}
+
+/// Directory of snapshots, keeping the latest `keep`.
+#[derive(Debug)]
+pub struct SnapshotStore {
+    dir: PathBuf,
+    keep: usize,
+}
+
+impl SnapshotStore {
+    pub fn open(dir: impl Into<PathBuf>, keep: usize) -> Result<Self, SnapshotError> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir,
+            keep: keep.max(1),
+        })
+    }
+
+    /// Write `snapshot` durably, then prune all but the latest snapshots. Returns its path.
+    pub fn save(&self, snapshot: &EngineSnapshot) -> Result<PathBuf, SnapshotError> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        serde_json::to_writer(&mut encoder, snapshot)
+            .map_err(|error| SnapshotError::Io(error.into()))?;
+        let body = encoder.finish()?;
+
+        let path = self.dir.join(file_name(snapshot.sequence));
+        let temporary = path.with_extension("tmp");
+        let mut file = File::create(&temporary)?;
+        file.write_all(MAGIC)?;
+        file.write_all(&crc32fast::hash(&body).to_le_bytes())?;
+        file.write_all(&body)?;
+        file.sync_all()?;
+        std::fs::rename(&temporary, &path)?;
+        File::open(&self.dir)?.sync_all()?;
+
+        for stale in self.paths()?.into_iter().skip(self.keep) {
+            std::fs::remove_file(stale)?;
+        }
+        Ok(path)
+    }
+
+    /// The latest snapshot that passes its checksum, if any.
+    pub fn latest(&self) -> Result<Option<EngineSnapshot>, SnapshotError> {
+        for path in self.paths()? {
+            match load(&path) {
+                Ok(snapshot) => return Ok(Some(snapshot)),
+                Err(SnapshotError::Io(error)) => return Err(error.into()),
+                Err(error) => warn!("Skipping snapshot: {}", error),
+            }
+        }
+        Ok(None)
+    }
+
+    /// Snapshot files, newest first.
+    fn paths(&self) -> Result<Vec<PathBuf>, SnapshotError> {
+        let mut paths = Vec::new();
+        for entry in std::fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            let is_snapshot = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("snapshot-") && name.ends_with(".snap"));
+            if is_snapshot {
+                paths.push(path);
+            }
+        }
+        // Sequence numbers are zero padded, so names sort in order
+        paths.sort_unstable_by(|a, b| b.cmp(a));
+        Ok(paths)
+    }
+}
+
+fn file_name(sequence: u64) -> String {
+    format!("snapshot-{sequence:020}.snap")
+}
+
+/// Read and verify the snapshot at `path`.
+pub fn load(path: &Path) -> Result<EngineSnapshot, SnapshotError> {
+    let mut contents = Vec::new();
+    File::open(path)?.read_to_end(&mut contents)?;
+    if contents.len() < HEADER_LEN || &contents[..MAGIC.len()] != MAGIC {
+        return Err(SnapshotError::Format(path.to_owned()));
+    }
+
+    let (header, body) = contents.split_at(HEADER_LEN);
+    let checksum = u32::from_le_bytes(header[MAGIC.len()..].try_into().expect("four bytes"));
+    if crc32fast::hash(body) != checksum {
+        return Err(SnapshotError::Checksum(path.to_owned()));
+    }
+    serde_json::from_reader(GzDecoder::new(body)).map_err(|error| SnapshotError::Corrupt {
+        path: path.to_owned(),
+        error,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use super::*;
+    use crate::allocation::SleeveSnapshot;
+    use crate::Lot;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "fit-{}-{}-{}",
+            name,
+            std::process::id(),
+            SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ))
+    }
+
+    fn snapshot(sequence: u64) -> EngineSnapshot {
+        EngineSnapshot {
+            sequence,
+            time: Utc::now(),
+            allocator: AllocatorSnapshot {
+                last_rebalance: Utc::now(),
+                sleeves: vec![SleeveSnapshot {
+                    strategy: "imbalance".to_owned(),
+                    cash: 899.9,
+                    positions: vec![Lot::at(100.0)],
+                    weight: 1.0,
+                    capital: 1000.0,
+                    contributed: 1000.0,
+                }],
+            },
+        }
+    }
+
+    #[test]
+    fn test_latest_skips_corrupt_snapshots() {
+        let dir = temp_dir("snapshots");
+        let store = SnapshotStore::open(&dir, 2).unwrap();
+        assert_eq!(store.latest().unwrap(), None);
+
+        for sequence in [5, 10, 15] {
+            store.save(&snapshot(sequence)).unwrap();
+        }
+        assert_eq!(store.paths().unwrap().len(), 2);
+        let latest = store.latest().unwrap().unwrap();
+        assert_eq!(latest.sequence, 15);
+        assert_eq!(latest.allocator.sleeves[0].positions, [Lot::at(100.0)]);
+
+        // Flip a bit in the latest snapshot's body
+        let path = dir.join(file_name(15));
+        let mut contents = std::fs::read(&path).unwrap();
+        *contents.last_mut().unwrap() ^= 1;
+        std::fs::write(&path, contents).unwrap();
+        assert!(matches!(load(&path), Err(SnapshotError::Checksum(_))));
+        assert_eq!(store.latest().unwrap().unwrap().sequence, 10);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,345 @@
+//! The venue's own word on its health. While the exchange reports degraded service, or from
+//! `lead_mins` before a maintenance window it has announced until the window ends, the guard
+//! blocks entries and widens the take profit and stop loss by `risk_multiplier`, so positions
+//! still open aren't shaken out by a venue that is misbehaving. With the `status` feature the
+//! poller keeps it fed from the exchange's status endpoint.
+//!
+//! Two shapes of status response are understood: Binance style, `{"status": 0, "msg":
+//! "normal"}` with anything but 0 degraded, and Atlassian Statuspage's `summary.json`, which
+//! most exchange status pages serve, with its `status.indicator` and `scheduled_maintenances`.
+
+use chrono::DateTime;
+use chrono::TimeDelta;
+use chrono::Utc;
+use serde_json::Value;
+use tracing::info;
+use tracing::warn;
+
+use crate::config::StatusConfig;
+use crate::event::Alert;
+use crate::event::Severity;
+use crate::hours::Permission;
+#[cfg(feature = "status")]
+use crate::poll;
+use crate::RiskParams;
+
+/// The [condition](Alert::condition) every venue status alert is about.
+pub const CONDITION: &str = "venue_status";
+
+/// Maintenance the exchange has announced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Maintenance {
+    pub name: String,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+/// What the exchange last said about itself.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VenueStatus {
+    /// Why service is degraded, when it is.
+    pub degraded: Option<String>,
+    pub maintenance: Vec<Maintenance>,
+}
+
+impl VenueStatus {
+    /// Parse a Binance or Statuspage style status response.
+    pub fn parse(body: &str) -> Option<Self> {
+        let body: Value = serde_json::from_str(body).ok()?;
+        match body.get("status")? {
+            // Statuspage: an indicator of none, minor, major or critical
+            Value::Object(status) => {
+                let indicator = status.get("indicator")?.as_str()?;
+                let description = status
+                    .get("description")
+                    .and_then(Value::as_str)
+                    .unwrap_or(indicator);
+                Some(Self {
+                    degraded: (indicator != "none").then(|| description.to_owned()),
+                    maintenance: body
+                        .get("scheduled_maintenances")
+                        .and_then(Value::as_array)
+                        .into_iter()
+                        .flatten()
+                        .filter_map(maintenance)
+                        .collect(),
+                })
+            }
+            status => {
+                let message = body
+                    .get("msg")
+                    .and_then(Value::as_str)
+                    .unwrap_or("degraded");
+                Some(Self {
+                    degraded: (status.as_i64()? != 0).then(|| message.to_owned()),
+                    maintenance: Vec::new(),
+                })
+            }
+        }
+    }
+}
+
+/// A Statuspage scheduled maintenance, unless it is over or has no times.
+fn maintenance(scheduled: &Value) -> Option<Maintenance> {
+    if scheduled.get("status").and_then(Value::as_str) == Some("completed") {
+        return None;
+    }
+    let time = |field| {
+        let time = scheduled.get(field)?.as_str()?;
+        DateTime::parse_from_rfc3339(time)
+            .ok()
+            .map(DateTime::<Utc>::from)
+    };
+    Some(Maintenance {
+        name: scheduled
+            .get("name")
+            .and_then(Value::as_str)
+            .unwrap_or("maintenance")
+            .to_owned(),
+        start: time("scheduled_for")?,
+        end: time("scheduled_until")?,
+    })
+}
+
+#[derive(Debug)]
+pub struct StatusGuard {
+    config: StatusConfig,
+    status: VenueStatus,
+    /// Why entries are suspended, while they are.
+    suspended: Option<String>,
+}
+
+impl StatusGuard {
+    pub fn new(config: &StatusConfig) -> Self {
+        Self {
+            config: config.clone(),
+            status: VenueStatus::default(),
+            suspended: None,
+        }
+    }
+
+    pub fn set_status(&mut self, status: VenueStatus) {
+        self.status = status;
+    }
+
+    /// Why entries should be suspended at `now`, if they should.
+    fn reason(&self, now: DateTime<Utc>) -> Option<String> {
+        if let Some(degraded) = &self.status.degraded {
+            return Some(format!("reports {degraded}"));
+        }
+        let lead = TimeDelta::minutes(self.config.lead_mins as i64);
+        self.status
+            .maintenance
+            .iter()
+            .find(|maintenance| maintenance.start - lead <= now && now < maintenance.end)
+            .map(|maintenance| {
+                format!(
+                    "has {} scheduled from {} to {}",
+                    maintenance.name, maintenance.start, maintenance.end
+                )
+            })
+    }
+
+    /// Judge the venue at `now`, returning the alert to raise if entries were just suspended or
+    /// resumed.
+    pub fn update(&mut self, now: DateTime<Utc>) -> Option<Alert> {
+        let reason = self.reason(now);
+        if reason == self.suspended {
+            return None;
+        }
+        let alert = match &reason {
+            Some(reason) => {
+                let message = format!("Exchange {reason}, suspending entries");
+                warn!("{}", message);
+                Alert {
+                    severity: Severity::Warning,
+                    message,
+                    condition: Some(CONDITION.to_owned()),
+                    resolved: false,
+                }
+            }
+            None => {
+                let message = "Exchange status clear, resuming entries".to_owned();
+                info!("{}", message);
+                Alert {
+                    severity: Severity::Info,
+                    message,
+                    condition: Some(CONDITION.to_owned()),
+                    resolved: true,
+                }
+            }
+        };
+        self.suspended = reason;
+        Some(alert)
+    }
+
+    /// Entries are blocked while the venue is degraded or under maintenance.
+    pub fn permission(&self) -> Permission {
+        Permission {
+            entries: self.suspended.is_none(),
+            flatten: false,
+        }
+    }
+
+    /// `risk` widened while entries are suspended, kept under 1.
+    pub fn risk(&self, risk: RiskParams) -> RiskParams {
+        if self.suspended.is_none() {
+            return risk;
+        }
+        let widen = |fraction: f64| (fraction * self.config.risk_multiplier).min(0.99);
+        RiskParams {
+            take_profit: widen(risk.take_profit),
+            stop_loss: widen(risk.stop_loss),
+        }
+    }
+}
+
+/// Polls the exchange's status in the background.
+#[cfg(feature = "status")]
+#[derive(Debug)]
+pub struct StatusPoller {
+    receiver: tokio::sync::watch::Receiver<Option<VenueStatus>>,
+}
+
+#[cfg(feature = "status")]
+impl StatusPoller {
+    pub fn spawn(config: &StatusConfig) -> Self {
+        let receiver = poll::spawn(
+            config.url.clone(),
+            config.poll_secs,
+            "exchange status",
+            |body, _, _| VenueStatus::parse(body),
+        );
+        Self { receiver }
+    }
+
+    /// The status polled since the last call, if any.
+    pub fn latest(&mut self) -> Option<VenueStatus> {
+        match self.receiver.has_changed() {
+            Ok(true) => self.receiver.borrow_and_update().clone(),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 7, 23, hour, minute, 0).unwrap()
+    }
+
+    fn guard() -> StatusGuard {
+        StatusGuard::new(&StatusConfig {
+            url: "http://localhost/status".to_owned(),
+            poll_secs: 60,
+            lead_mins: 15,
+            risk_multiplier: 2.0,
+        })
+    }
+
+    #[test]
+    fn test_parse_status() {
+        assert_eq!(
+            VenueStatus::parse(r#"{"status":0,"msg":"normal"}"#),
+            Some(VenueStatus::default())
+        );
+        assert_eq!(
+            VenueStatus::parse(r#"{"status":1,"msg":"system maintenance"}"#)
+                .unwrap()
+                .degraded
+                .as_deref(),
+            Some("system maintenance")
+        );
+
+        let summary = VenueStatus::parse(
+            r#"{
+                "status": {"indicator": "minor", "description": "Partially Degraded Service"},
+                "scheduled_maintenances": [
+                    {
+                        "name": "Matching engine upgrade",
+                        "status": "scheduled",
+                        "scheduled_for": "2024-07-23T10:00:00Z",
+                        "scheduled_until": "2024-07-23T11:00:00Z"
+                    },
+                    {
+                        "name": "Done already",
+                        "status": "completed",
+                        "scheduled_for": "2024-07-22T10:00:00Z",
+                        "scheduled_until": "2024-07-22T11:00:00Z"
+                    }
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            summary.degraded.as_deref(),
+            Some("Partially Degraded Service")
+        );
+        assert_eq!(
+            summary.maintenance,
+            [Maintenance {
+                name: "Matching engine upgrade".to_owned(),
+                start: at(10, 0),
+                end: at(11, 0),
+            }]
+        );
+        assert_eq!(VenueStatus::parse(r#"{"ok":true}"#), None);
+    }
+
+    #[test]
+    fn test_suspend_for_maintenance() {
+        let mut guard = guard();
+        guard.set_status(VenueStatus {
+            degraded: None,
+            maintenance: vec![Maintenance {
+                name: "Upgrade".to_owned(),
+                start: at(10, 0),
+                end: at(11, 0),
+            }],
+        });
+        let risk = RiskParams {
+            take_profit: 0.01,
+            stop_loss: 0.6,
+        };
+        assert_eq!(guard.update(at(9, 44)), None);
+        assert!(guard.permission().entries);
+        assert_eq!(guard.risk(risk), risk);
+
+        let suspended = guard.update(at(9, 45)).unwrap();
+        assert_eq!(suspended.severity, Severity::Warning);
+        assert!(suspended.message.contains("Upgrade scheduled"));
+        assert!(!guard.permission().entries);
+        assert_eq!(
+            guard.risk(risk),
+            RiskParams {
+                take_profit: 0.02,
+                stop_loss: 0.99,
+            }
+        );
+        assert_eq!(guard.update(at(10, 30)), None);
+
+        let resumed = guard.update(at(11, 0)).unwrap();
+        assert!(resumed.resolved);
+        assert!(guard.permission().entries);
+    }
+
+    #[test]
+    fn test_suspend_while_degraded() {
+        let mut guard = guard();
+        guard.set_status(VenueStatus {
+            degraded: Some("high latency".to_owned()),
+            maintenance: Vec::new(),
+        });
+        let suspended = guard.update(at(9, 0)).unwrap();
+        assert_eq!(
+            suspended.message,
+            "Exchange reports high latency, suspending entries"
+        );
+        guard.set_status(VenueStatus::default());
+        assert!(guard.update(at(9, 1)).unwrap().resolved);
+    }
+}
@@ -0,0 +1,142 @@
+//! Crash-safe hot state: the allocator's [snapshot](AllocatorSnapshot) is written to an embedded
+//! sled database and flushed to disk whenever it changes, so a restarted bot resumes its cash,
+//! positions and weights instead of starting over.
+//!
+//! Orders in flight through the order gateway are not saved, so any reported
+//! after a crash have to be reconciled by hand.
+
+use std::path::Path;
+
+use crate::allocation::Allocator;
+use crate::allocation::AllocatorSnapshot;
+
+/// Key the allocator snapshot is stored under.
+const ALLOCATOR_KEY: &[u8] = b"allocator";
+
+#[derive(Debug, thiserror::Error)]
+pub enum StoreError {
+    #[error("state store error: {0}")]
+    Sled(#[from] sled::Error),
+
+    #[error("corrupt saved state: {0}")]
+    Corrupt(#[from] serde_json::Error),
+}
+
+#[derive(Debug)]
+pub struct StateStore {
+    db: sled::Db,
+    /// The last snapshot written, to skip writing unchanged state.
+    saved: Option<AllocatorSnapshot>,
+}
+
+impl StateStore {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, StoreError> {
+        // Every save is flushed explicitly, so there is no need for the background flusher
+        let db = sled::Config::new().path(path).flush_every_ms(None).open()?;
+        Ok(Self { db, saved: None })
+    }
+
+    /// The last saved snapshot, if anything was ever saved.
+    pub fn load(&mut self) -> Result<Option<AllocatorSnapshot>, StoreError> {
+        let Some(bytes) = self.db.get(ALLOCATOR_KEY)? else {
+            return Ok(None);
+        };
+        let snapshot: AllocatorSnapshot = serde_json::from_slice(&bytes)?;
+        self.saved = Some(snapshot.clone());
+        Ok(Some(snapshot))
+    }
+
+    /// Save `allocator` if it changed since the last save, returning whether it was written.
+    pub fn save(&mut self, allocator: &Allocator) -> Result<bool, StoreError> {
+        let snapshot = allocator.snapshot();
+        if self.saved.as_ref() == Some(&snapshot) {
+            return Ok(false);
+        }
+
+        self.db
+            .insert(ALLOCATOR_KEY, serde_json::to_vec(&snapshot)?)?;
+        self.db.flush()?;
+        self.saved = Some(snapshot);
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use std::time::SystemTime;
+
+    use super::*;
+    use crate::config::AllocationConfig;
+    use crate::features::Features;
+    use crate::strategy::Signal;
+    use crate::strategy::Strategy;
+    use crate::Lot;
+    use crate::TradingState;
+
+    #[derive(Debug)]
+    struct Idle;
+
+    impl Strategy for Idle {
+        fn name(&self) -> &str {
+            "idle"
+        }
+
+        fn evaluate(&mut self, _features: &Features, _state: &TradingState) -> Signal {
+            Signal::Hold
+        }
+    }
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!(
+            "fit-{}-{}-{}",
+            name,
+            std::process::id(),
+            SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ))
+    }
+
+    fn allocator() -> Allocator {
+        let members: Vec<(Box<dyn Strategy>, f64)> = vec![(Box::new(Idle), 1.0)];
+        Allocator::new(
+            1000.0,
+            "BTC/USDT",
+            members,
+            AllocationConfig::default(),
+            Utc::now(),
+        )
+    }
+
+    #[test]
+    fn test_resume_after_restart() {
+        let dir = temp_dir("store");
+        {
+            let mut store = StateStore::open(&dir).unwrap();
+            assert_eq!(store.load().unwrap(), None);
+
+            let mut running = allocator();
+            running.sleeves_mut()[0].state.cash = 900.0;
+            running.sleeves_mut()[0]
+                .state
+                .positions
+                .push(Lot::at(100.0));
+            assert!(store.save(&running).unwrap());
+            // Unchanged state is not written again
+            assert!(!store.save(&running).unwrap());
+        }
+
+        let mut store = StateStore::open(&dir).unwrap();
+        let snapshot = store.load().unwrap().unwrap();
+        let mut restarted = allocator();
+        restarted.restore(&snapshot).unwrap();
+        assert_eq!(restarted.sleeves()[0].state.cash, 900.0);
+        assert_eq!(restarted.sleeves()[0].state.positions, [Lot::at(100.0)]);
+        assert!(!store.save(&restarted).unwrap());
+
+        drop(store);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,194 @@
+use chrono::DateTime;
+use chrono::Utc;
+use serde::Deserialize;
+
+use super::Signal;
+use super::Strategy;
+use crate::features::Features;
+use crate::TradingState;
+
+/// Rule used by an [`Ensemble`] to merge its members' signals.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(tag = "rule", rename_all = "snake_case")]
+pub enum Combination {
+    /// Act when more than half of the members agree on a side.
+    Majority,
+    /// Score Buy as +1, Sell as -1 and Hold as 0, take the weighted mean over all members, and act
+    /// when its magnitude reaches `threshold`.
+    Weighted { threshold: f64 },
+    /// Act only when every member agrees on a side.
+    Unanimous,
+}
+
+/// Runs several strategies on the same features and merges their outputs into one decision.
+#[derive(Debug)]
+pub struct Ensemble {
+    members: Vec<(Box<dyn Strategy>, f64)>,
+    combination: Combination,
+    // Reused for every update's member signals, to keep evaluation off the heap
+    signals: Vec<Signal>,
+}
+
+impl Ensemble {
+    pub fn new(members: Vec<(Box<dyn Strategy>, f64)>, combination: Combination) -> Self {
+        Self {
+            signals: Vec::with_capacity(members.len()),
+            members,
+            combination,
+        }
+    }
+
+    fn combine(&self, signals: &[Signal]) -> Signal {
+        let count = |side: Signal| signals.iter().filter(|&&signal| signal == side).count();
+
+        match self.combination {
+            Combination::Majority => {
+                if count(Signal::Buy) * 2 > signals.len() {
+                    Signal::Buy
+                } else if count(Signal::Sell) * 2 > signals.len() {
+                    Signal::Sell
+                } else {
+                    Signal::Hold
+                }
+            }
+            Combination::Weighted { threshold } => {
+                let total_weight: f64 = self.members.iter().map(|(_, weight)| weight).sum();
+                if total_weight <= 0.0 {
+                    return Signal::Hold;
+                }
+
+                let score: f64 = signals
+                    .iter()
+                    .zip(&self.members)
+                    .map(|(signal, (_, weight))| match signal {
+                        Signal::Buy => *weight,
+                        Signal::Sell => -weight,
+                        Signal::Hold => 0.0,
+                    })
+                    .sum::<f64>()
+                    / total_weight;
+
+                if score >= threshold {
+                    Signal::Buy
+                } else if score <= -threshold {
+                    Signal::Sell
+                } else {
+                    Signal::Hold
+                }
+            }
+            Combination::Unanimous => match signals.first() {
+                Some(&first) if count(first) == signals.len() => first,
+                _ => Signal::Hold,
+            },
+        }
+    }
+}
+
+impl Strategy for Ensemble {
+    fn name(&self) -> &str {
+        "ensemble"
+    }
+
+    fn evaluate(&mut self, features: &Features, state: &TradingState) -> Signal {
+        // Every member is evaluated on every update so stateful strategies see the full stream
+        self.signals.clear();
+        self.signals.extend(
+            self.members
+                .iter_mut()
+                .map(|(strategy, _)| strategy.evaluate(features, state)),
+        );
+
+        self.combine(&self.signals)
+    }
+
+    fn on_reference(&mut self, price: f64) {
+        for (strategy, _) in &mut self.members {
+            strategy.on_reference(price);
+        }
+    }
+
+    fn on_time(&mut self, now: DateTime<Utc>) {
+        for (strategy, _) in &mut self.members {
+            strategy.on_time(now);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Member that always returns the same signal.
+    #[derive(Debug)]
+    struct Fixed(Signal);
+
+    impl Strategy for Fixed {
+        fn name(&self) -> &str {
+            "fixed"
+        }
+
+        fn evaluate(&mut self, _features: &Features, _state: &TradingState) -> Signal {
+            self.0
+        }
+    }
+
+    fn ensemble(members: &[(Signal, f64)], combination: Combination) -> Ensemble {
+        let members = members
+            .iter()
+            .map(|&(signal, weight)| (Box::new(Fixed(signal)) as Box<dyn Strategy>, weight))
+            .collect();
+        Ensemble::new(members, combination)
+    }
+
+    fn evaluate(ensemble: &mut Ensemble) -> Signal {
+        let features = Features {
+            bid: 100.0,
+            ask: 100.01,
+            mid_price: 100.005,
+            spread: 0.01,
+            ..Features::default()
+        };
+        ensemble.evaluate(&features, &TradingState::new(1000.0, "BTC/USDT"))
+    }
+
+    #[test]
+    fn test_majority() {
+        use Signal::*;
+
+        let mut majority = ensemble(
+            &[(Buy, 1.0), (Buy, 1.0), (Sell, 1.0)],
+            Combination::Majority,
+        );
+        assert_eq!(evaluate(&mut majority), Buy);
+
+        let mut split = ensemble(&[(Buy, 1.0), (Hold, 1.0)], Combination::Majority);
+        assert_eq!(evaluate(&mut split), Hold);
+    }
+
+    #[test]
+    fn test_weighted() {
+        use Signal::*;
+
+        let combination = Combination::Weighted { threshold: 0.5 };
+
+        let mut heavy_sell = ensemble(&[(Buy, 1.0), (Sell, 3.0)], combination);
+        assert_eq!(evaluate(&mut heavy_sell), Sell);
+
+        let mut below_threshold = ensemble(&[(Buy, 1.0), (Hold, 1.0), (Sell, 0.5)], combination);
+        assert_eq!(evaluate(&mut below_threshold), Hold);
+    }
+
+    #[test]
+    fn test_unanimous() {
+        use Signal::*;
+
+        let mut agree = ensemble(&[(Sell, 1.0), (Sell, 2.0)], Combination::Unanimous);
+        assert_eq!(evaluate(&mut agree), Sell);
+
+        let mut disagree = ensemble(&[(Buy, 1.0), (Hold, 1.0)], Combination::Unanimous);
+        assert_eq!(evaluate(&mut disagree), Hold);
+
+        let mut empty = ensemble(&[], Combination::Unanimous);
+        assert_eq!(evaluate(&mut empty), Hold);
+    }
+}
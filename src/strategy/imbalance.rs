@@ -0,0 +1,153 @@
+use serde::Deserialize;
+
+use super::Signal;
+use super::Strategy;
+use crate::features::Features;
+use crate::TradingState;
+use crate::MPB_THRESHOLD;
+use crate::OIR_THRESHOLD;
+use crate::SPREAD_THRESHOLD;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ImbalanceParams {
+    pub spread_threshold: f64,
+    pub oir_threshold: f64,
+    pub mpb_threshold: f64,
+    /// The spread threshold in sigmas of the forecast
+    /// [`volatility`](crate::features::Features::volatility) instead, while there is one.
+    pub spread_sigmas: Option<f64>,
+}
+
+impl Default for ImbalanceParams {
+    fn default() -> Self {
+        Self {
+            spread_threshold: SPREAD_THRESHOLD,
+            oir_threshold: OIR_THRESHOLD,
+            mpb_threshold: MPB_THRESHOLD,
+            spread_sigmas: None,
+        }
+    }
+}
+
+/// Buys on positive VOI backed by a strong OIR and sells on negative VOI backed by a negative MPB,
+/// provided the spread is tight enough to trade.
+#[derive(Debug, Clone)]
+pub struct ImbalanceStrategy {
+    params: ImbalanceParams,
+}
+
+impl ImbalanceStrategy {
+    pub fn new(params: ImbalanceParams) -> Self {
+        Self { params }
+    }
+
+    /// The widest spread, in percent like the feature, that `features` can be traded at.
+    fn spread_threshold(&self, features: &Features) -> f64 {
+        match self.params.spread_sigmas {
+            Some(sigmas) if features.volatility > 0.0 => sigmas * features.volatility * 100.0,
+            _ => self.params.spread_threshold,
+        }
+    }
+}
+
+impl Strategy for ImbalanceStrategy {
+    fn name(&self) -> &str {
+        "imbalance"
+    }
+
+    fn evaluate(&mut self, features: &Features, state: &TradingState) -> Signal {
+        let spread_threshold = self.spread_threshold(features);
+        if !TradingState::should_trade(features.spread, features.voi, spread_threshold) {
+            return Signal::Hold;
+        }
+
+        // Buy at the bid price if VOI is positive and OIR indicates a strong buy signal
+        if features.voi > 0.0 && features.oir > self.params.oir_threshold {
+            Signal::Buy
+        }
+        // Sell at the ask price if VOI is negative and MPB indicates a strong sell signal
+        else if features.voi < 0.0
+            && features.mpb < self.params.mpb_threshold
+            && !state.positions.is_empty()
+        {
+            Signal::Sell
+        } else {
+            Signal::Hold
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Lot;
+
+    fn features(spread: f64, voi: f64, oir: f64, mpb: f64) -> Features {
+        Features {
+            bid: 100.0,
+            ask: 100.01,
+            mid_price: 100.005,
+            spread,
+            voi,
+            oir,
+            mpb,
+            ..Features::default()
+        }
+    }
+
+    #[test]
+    fn test_evaluate_buy() {
+        let state = TradingState::new(1000.0, "BTC/USDT");
+        let mut strategy = ImbalanceStrategy::new(ImbalanceParams::default());
+
+        assert_eq!(
+            strategy.evaluate(&features(0.01, 2.0, 0.5, 0.0), &state),
+            Signal::Buy
+        );
+        assert_eq!(
+            strategy.evaluate(&features(0.01, 2.0, 0.05, 0.0), &state),
+            Signal::Hold
+        );
+        assert_eq!(
+            strategy.evaluate(&features(0.5, 2.0, 0.5, 0.0), &state),
+            Signal::Hold
+        );
+    }
+
+    #[test]
+    fn test_evaluate_sell_requires_position() {
+        let mut state = TradingState::new(1000.0, "BTC/USDT");
+        let mut strategy = ImbalanceStrategy::new(ImbalanceParams::default());
+
+        let bearish = features(0.01, -2.0, -0.5, -0.2);
+        assert_eq!(strategy.evaluate(&bearish, &state), Signal::Hold);
+
+        state.positions.push(Lot::at(100.0));
+        assert_eq!(strategy.evaluate(&bearish, &state), Signal::Sell);
+    }
+
+    #[test]
+    fn test_spread_threshold_in_sigmas() {
+        let state = TradingState::new(1000.0, "BTC/USDT");
+        let mut strategy = ImbalanceStrategy::new(ImbalanceParams {
+            spread_sigmas: Some(0.5),
+            ..ImbalanceParams::default()
+        });
+        let wide = features(0.5, 2.0, 0.5, 0.0);
+        // Without a forecast the raw threshold applies
+        assert_eq!(strategy.evaluate(&wide, &state), Signal::Hold);
+
+        // Half of a 2% sigma allows a 1% spread, but half of 0.5% doesn't
+        let volatile = Features {
+            volatility: 0.02,
+            ..wide
+        };
+        assert_eq!(strategy.evaluate(&volatile, &state), Signal::Buy);
+        let calm = Features {
+            volatility: 0.005,
+            ..wide
+        };
+        assert_eq!(strategy.evaluate(&calm, &state), Signal::Hold);
+    }
+}
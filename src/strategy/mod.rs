@@ -0,0 +1,155 @@
+use chrono::DateTime;
+use chrono::Utc;
+use serde::Deserialize;
+use serde::Serialize;
+use std::fmt::Debug;
+
+use crate::config::StrategyConfig;
+use crate::features::Features;
+#[cfg(feature = "onnx")]
+use crate::ml::onnx::OnnxError;
+#[cfg(feature = "onnx")]
+use crate::ml::onnx::OnnxParams;
+#[cfg(feature = "onnx")]
+use crate::ml::onnx::OnnxStrategy;
+use crate::ml::prediction::PredictionParams;
+use crate::ml::prediction::PredictionStrategy;
+use crate::ml::rls::RlsParams;
+use crate::ml::rls::RlsStrategy;
+use crate::signals::kalman::KalmanParams;
+use crate::signals::kalman::KalmanStrategy;
+use crate::signals::liquidation::ExhaustionParams;
+use crate::signals::liquidation::ExhaustionStrategy;
+use crate::TradingState;
+
+use self::ensemble::Ensemble;
+use self::imbalance::ImbalanceParams;
+use self::imbalance::ImbalanceStrategy;
+use self::plugin::PluginError;
+use self::plugin::PluginParams;
+use self::plugin::PluginRegistry;
+#[cfg(feature = "rhai")]
+use self::script::ScriptError;
+#[cfg(feature = "rhai")]
+use self::script::ScriptParams;
+#[cfg(feature = "rhai")]
+use self::script::ScriptStrategy;
+#[cfg(feature = "wasm")]
+use self::wasm::WasmError;
+#[cfg(feature = "wasm")]
+use self::wasm::WasmParams;
+#[cfg(feature = "wasm")]
+use self::wasm::WasmStrategy;
+
+/// Merges the signals of several strategies into a single decision.
+pub mod ensemble;
+
+/// The original volume order imbalance entry/exit rules.
+pub mod imbalance;
+
+/// Strategies loaded from shared libraries at startup.
+pub mod plugin;
+
+/// User-defined entry/exit rules written in Rhai.
+#[cfg(feature = "rhai")]
+pub mod script;
+
+/// Sandboxed strategies compiled to WebAssembly.
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+/// Order decision produced by a [`Strategy`] for a single book update.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Signal {
+    Buy,
+    Sell,
+    Hold,
+}
+
+/// Turns the features of each book update into an order decision for one instrument.
+pub trait Strategy: Debug + Send {
+    fn name(&self) -> &str;
+
+    fn evaluate(&mut self, features: &Features, state: &TradingState) -> Signal;
+
+    /// Take `price`, the instrument's mid on another venue, to weigh the next update against.
+    /// Most strategies only look at the book they trade on.
+    fn on_reference(&mut self, _price: f64) {}
+
+    /// Take `now`, when the update about to be evaluated arrived, for strategies that learn over
+    /// time rather than updates.
+    fn on_time(&mut self, _now: DateTime<Utc>) {}
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum StrategyError {
+    #[error(transparent)]
+    Plugin(#[from] PluginError),
+
+    #[cfg(feature = "rhai")]
+    #[error(transparent)]
+    Script(#[from] ScriptError),
+
+    #[cfg(feature = "wasm")]
+    #[error(transparent)]
+    Wasm(#[from] WasmError),
+
+    #[cfg(feature = "onnx")]
+    #[error(transparent)]
+    Onnx(#[from] OnnxError),
+}
+
+/// Strategy implementations selectable from config, tagged by `kind`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum StrategyKind {
+    Exhaustion(ExhaustionParams),
+    Imbalance(ImbalanceParams),
+    Kalman(KalmanParams),
+    Plugin(PluginParams),
+    Prediction(PredictionParams),
+    Rls(RlsParams),
+    #[cfg(feature = "rhai")]
+    Rhai(ScriptParams),
+    #[cfg(feature = "wasm")]
+    Wasm(WasmParams),
+    #[cfg(feature = "onnx")]
+    Onnx(OnnxParams),
+}
+
+impl Default for StrategyKind {
+    fn default() -> Self {
+        Self::Imbalance(ImbalanceParams::default())
+    }
+}
+
+impl StrategyKind {
+    pub fn build(&self, plugins: &PluginRegistry) -> Result<Box<dyn Strategy>, StrategyError> {
+        Ok(match self {
+            Self::Exhaustion(params) => Box::new(ExhaustionStrategy::new(*params)),
+            Self::Imbalance(params) => Box::new(ImbalanceStrategy::new(*params)),
+            Self::Kalman(params) => Box::new(KalmanStrategy::new(*params)),
+            Self::Plugin(params) => Box::new(plugins.instantiate(params)?),
+            Self::Prediction(params) => Box::new(PredictionStrategy::new(*params)),
+            Self::Rls(params) => Box::new(RlsStrategy::new(*params)),
+            #[cfg(feature = "rhai")]
+            Self::Rhai(params) => Box::new(ScriptStrategy::new(params)?),
+            #[cfg(feature = "wasm")]
+            Self::Wasm(params) => Box::new(WasmStrategy::new(params)?),
+            #[cfg(feature = "onnx")]
+            Self::Onnx(params) => Box::new(OnnxStrategy::new(params)?),
+        })
+    }
+}
+
+/// Build the strategy described by `config` for a single instrument.
+pub fn build(config: &StrategyConfig, plugins: &PluginRegistry) -> Result<Ensemble, StrategyError> {
+    let members = config
+        .members
+        .iter()
+        .map(|member| Ok((member.strategy.build(plugins)?, member.weight)))
+        .collect::<Result<_, StrategyError>>()?;
+
+    Ok(Ensemble::new(members, config.combination))
+}
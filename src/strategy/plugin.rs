@@ -0,0 +1,398 @@
+//! Strategies loaded at startup from shared libraries in the plugins directory.
+//!
+//! Rust trait objects have no stable ABI, so plugins talk to the host through the `#[repr(C)]`
+//! types below instead. A plugin is any `cdylib` exporting
+//!
+//! ```ignore
+//! #[no_mangle]
+//! pub extern "C" fn fit_strategy_plugin() -> *const PluginVTable
+//! ```
+//!
+//! returning a pointer to a `'static` [`PluginVTable`] whose `abi_version` equals
+//! [`PLUGIN_ABI_VERSION`]. Plugins declare identical copies of these types; any change to them
+//! must bump [`PLUGIN_ABI_VERSION`] so stale plugins are rejected at load time rather than
+//! misreading memory.
+
+use libloading::Library;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::ffi::c_char;
+use std::ffi::c_void;
+use std::ffi::CStr;
+use std::ffi::CString;
+use std::fmt;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tracing::info;
+
+use super::Signal;
+use super::Strategy;
+use crate::features::Features;
+use crate::TradingState;
+
+/// Version of the plugin ABI implemented by this build.
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+
+/// Symbol every plugin library must export.
+pub const PLUGIN_ENTRY_SYMBOL: &[u8] = b"fit_strategy_plugin\0";
+
+/// Features and position state handed to a plugin on every book update.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct PluginFeatures {
+    pub bid: f64,
+    pub ask: f64,
+    pub mid_price: f64,
+    pub spread: f64,
+    pub voi: f64,
+    pub oir: f64,
+    pub mpb: f64,
+    pub cash: f64,
+    pub open_positions: u64,
+}
+
+/// Function table exported by a plugin.
+///
+/// `evaluate` returns `1` to buy, `-1` to sell and anything else to hold.
+#[repr(C)]
+pub struct PluginVTable {
+    pub abi_version: u32,
+    /// NUL-terminated name the plugin is referred to by in config.
+    pub name: *const c_char,
+    /// Create an instance from the member's NUL-terminated `params` string, or return null.
+    pub create: unsafe extern "C" fn(params: *const c_char) -> *mut c_void,
+    pub evaluate:
+        unsafe extern "C" fn(instance: *mut c_void, features: *const PluginFeatures) -> i32,
+    pub destroy: unsafe extern "C" fn(instance: *mut c_void),
+}
+
+type PluginEntry = unsafe extern "C" fn() -> *const PluginVTable;
+
+#[derive(Debug, thiserror::Error)]
+pub enum PluginError {
+    #[error("failed to read plugins directory {0}: {1}")]
+    Io(PathBuf, std::io::Error),
+
+    #[error("failed to load plugin {0}: {1}")]
+    Load(PathBuf, libloading::Error),
+
+    #[error("plugin {0} implements ABI version {1}, expected {PLUGIN_ABI_VERSION}")]
+    AbiMismatch(String, u32),
+
+    #[error("plugin {0} exported an invalid vtable")]
+    InvalidVTable(String),
+
+    #[error("no plugin named {0} was loaded")]
+    Unknown(String),
+
+    #[error("params for plugin {0} contain a NUL byte")]
+    InvalidParams(String),
+
+    #[error("plugin {0} failed to create a strategy instance")]
+    Create(String),
+}
+
+/// Config for a strategy member backed by a plugin.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PluginParams {
+    /// Name the plugin reports in its vtable.
+    pub name: String,
+    /// Opaque string passed to the plugin's `create`, e.g. JSON or TOML of its own params.
+    #[serde(default)]
+    pub params: String,
+}
+
+#[derive(Clone)]
+struct Plugin {
+    vtable: *const PluginVTable,
+    // Keeps the code behind `vtable` mapped for as long as any instance is alive
+    _library: Option<Arc<Library>>,
+}
+
+// SAFETY: the vtable is immutable static data inside the library, which the Arc keeps loaded.
+unsafe impl Send for Plugin {}
+
+impl Plugin {
+    /// Validate `vtable` and return the plugin along with its name.
+    ///
+    /// # Safety
+    /// `vtable` must be null or point to a `PluginVTable` that outlives the returned plugin.
+    unsafe fn new(
+        origin: &str,
+        vtable: *const PluginVTable,
+        library: Option<Arc<Library>>,
+    ) -> Result<(String, Self), PluginError> {
+        let Some(table) = vtable.as_ref() else {
+            return Err(PluginError::InvalidVTable(origin.to_owned()));
+        };
+        if table.abi_version != PLUGIN_ABI_VERSION {
+            return Err(PluginError::AbiMismatch(
+                origin.to_owned(),
+                table.abi_version,
+            ));
+        }
+        if table.name.is_null() {
+            return Err(PluginError::InvalidVTable(origin.to_owned()));
+        }
+
+        let name = CStr::from_ptr(table.name).to_string_lossy().into_owned();
+        Ok((
+            name,
+            Self {
+                vtable,
+                _library: library,
+            },
+        ))
+    }
+
+    fn vtable(&self) -> &PluginVTable {
+        // SAFETY: checked non-null in `new` and kept alive by `_library`
+        unsafe { &*self.vtable }
+    }
+}
+
+/// Plugins discovered at startup, keyed by the name each reports.
+#[derive(Default)]
+pub struct PluginRegistry {
+    plugins: HashMap<String, Plugin>,
+}
+
+impl fmt::Debug for PluginRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.plugins.keys()).finish()
+    }
+}
+
+impl PluginRegistry {
+    /// Load every shared library in `dir`. A missing directory yields an empty registry.
+    pub fn load_dir(dir: impl AsRef<Path>) -> Result<Self, PluginError> {
+        let dir = dir.as_ref();
+        let mut registry = Self::default();
+
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(registry),
+            Err(error) => return Err(PluginError::Io(dir.to_owned(), error)),
+        };
+
+        for entry in entries {
+            let path = entry
+                .map_err(|error| PluginError::Io(dir.to_owned(), error))?
+                .path();
+            if path.extension().and_then(|ext| ext.to_str())
+                != Some(std::env::consts::DLL_EXTENSION)
+            {
+                continue;
+            }
+
+            // SAFETY: loading a library runs its initialisers; plugins are trusted code placed in
+            // the plugins directory by the operator.
+            let (name, plugin) = unsafe {
+                let library =
+                    Library::new(&path).map_err(|error| PluginError::Load(path.clone(), error))?;
+                let entry = library
+                    .get::<PluginEntry>(PLUGIN_ENTRY_SYMBOL)
+                    .map_err(|error| PluginError::Load(path.clone(), error))?;
+                let vtable = entry();
+                Plugin::new(&path.display().to_string(), vtable, Some(Arc::new(library)))?
+            };
+
+            info!("Loaded strategy plugin {} from {}", name, path.display());
+            registry.plugins.insert(name, plugin);
+        }
+
+        Ok(registry)
+    }
+
+    /// Create a strategy instance from the plugin named in `params`.
+    pub fn instantiate(&self, params: &PluginParams) -> Result<PluginStrategy, PluginError> {
+        let plugin = self
+            .plugins
+            .get(&params.name)
+            .ok_or_else(|| PluginError::Unknown(params.name.clone()))?;
+        let raw_params = CString::new(params.params.as_str())
+            .map_err(|_| PluginError::InvalidParams(params.name.clone()))?;
+
+        // SAFETY: the vtable was validated on load and `raw_params` outlives the call
+        let instance = unsafe { (plugin.vtable().create)(raw_params.as_ptr()) };
+        if instance.is_null() {
+            return Err(PluginError::Create(params.name.clone()));
+        }
+
+        Ok(PluginStrategy {
+            name: params.name.clone(),
+            plugin: plugin.clone(),
+            instance,
+        })
+    }
+}
+
+/// A [`Strategy`] instance owned by a plugin.
+pub struct PluginStrategy {
+    name: String,
+    plugin: Plugin,
+    instance: *mut c_void,
+}
+
+// SAFETY: an instance is only ever used from one thread at a time, through `&mut self`.
+unsafe impl Send for PluginStrategy {}
+
+impl fmt::Debug for PluginStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PluginStrategy")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Strategy for PluginStrategy {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn evaluate(&mut self, features: &Features, state: &TradingState) -> Signal {
+        let features = PluginFeatures {
+            bid: features.bid,
+            ask: features.ask,
+            mid_price: features.mid_price,
+            spread: features.spread,
+            voi: features.voi,
+            oir: features.oir,
+            mpb: features.mpb,
+            cash: state.cash,
+            open_positions: state.positions.len() as u64,
+        };
+
+        // SAFETY: `instance` came from this plugin's `create` and has not been destroyed
+        match unsafe { (self.plugin.vtable().evaluate)(self.instance, &features) } {
+            1 => Signal::Buy,
+            -1 => Signal::Sell,
+            _ => Signal::Hold,
+        }
+    }
+}
+
+impl Drop for PluginStrategy {
+    fn drop(&mut self) {
+        // SAFETY: `instance` came from this plugin's `create` and is destroyed exactly once
+        unsafe { (self.plugin.vtable().destroy)(self.instance) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+
+    use super::*;
+    use crate::Lot;
+
+    static DESTROYED: AtomicUsize = AtomicUsize::new(0);
+
+    // In-process stand-in for a plugin: buys whenever OIR exceeds the threshold in its params.
+    unsafe extern "C" fn create(params: *const c_char) -> *mut c_void {
+        let params = CStr::from_ptr(params).to_str().unwrap_or_default();
+        match params.parse::<f64>() {
+            Ok(threshold) => Box::into_raw(Box::new(threshold)) as *mut c_void,
+            Err(_) => std::ptr::null_mut(),
+        }
+    }
+
+    unsafe extern "C" fn evaluate(instance: *mut c_void, features: *const PluginFeatures) -> i32 {
+        let threshold = *(instance as *const f64);
+        let features = &*features;
+        if features.oir > threshold {
+            1
+        } else if features.open_positions > 0 {
+            -1
+        } else {
+            0
+        }
+    }
+
+    unsafe extern "C" fn destroy(instance: *mut c_void) {
+        drop(Box::from_raw(instance as *mut f64));
+        DESTROYED.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn vtable(abi_version: u32) -> &'static PluginVTable {
+        Box::leak(Box::new(PluginVTable {
+            abi_version,
+            name: c"threshold".as_ptr(),
+            create,
+            evaluate,
+            destroy,
+        }))
+    }
+
+    fn registry() -> PluginRegistry {
+        let (name, plugin) =
+            unsafe { Plugin::new("test", vtable(PLUGIN_ABI_VERSION), None) }.unwrap();
+        let mut registry = PluginRegistry::default();
+        registry.plugins.insert(name, plugin);
+        registry
+    }
+
+    fn params(name: &str, params: &str) -> PluginParams {
+        PluginParams {
+            name: name.to_owned(),
+            params: params.to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_plugin_strategy_evaluate() {
+        let features = Features {
+            bid: 100.0,
+            ask: 100.01,
+            mid_price: 100.005,
+            spread: 0.01,
+            voi: 1.0,
+            oir: 0.5,
+            ..Features::default()
+        };
+        let mut state = TradingState::new(1000.0, "BTC/USDT");
+
+        let destroyed = DESTROYED.load(Ordering::SeqCst);
+        {
+            let mut strategy = registry().instantiate(&params("threshold", "0.2")).unwrap();
+            assert_eq!(strategy.name(), "threshold");
+            assert_eq!(strategy.evaluate(&features, &state), Signal::Buy);
+
+            state.positions.push(Lot::at(100.0));
+            let bearish = Features {
+                oir: 0.0,
+                ..features
+            };
+            assert_eq!(strategy.evaluate(&bearish, &state), Signal::Sell);
+        }
+        assert!(DESTROYED.load(Ordering::SeqCst) > destroyed);
+    }
+
+    #[test]
+    fn test_instantiate_errors() {
+        let registry = registry();
+        assert!(matches!(
+            registry.instantiate(&params("missing", "")),
+            Err(PluginError::Unknown(_))
+        ));
+        assert!(matches!(
+            registry.instantiate(&params("threshold", "not a number")),
+            Err(PluginError::Create(_))
+        ));
+    }
+
+    #[test]
+    fn test_abi_mismatch_rejected() {
+        let result = unsafe { Plugin::new("test", vtable(PLUGIN_ABI_VERSION + 1), None) };
+        assert!(matches!(result, Err(PluginError::AbiMismatch(_, _))));
+    }
+
+    #[test]
+    fn test_load_missing_dir() {
+        let registry = PluginRegistry::load_dir("does-not-exist").unwrap();
+        assert!(registry.plugins.is_empty());
+    }
+}
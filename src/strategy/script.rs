@@ -0,0 +1,227 @@
+//! Entry/exit rules written as [Rhai](https://rhai.rs) scripts, so rules can be iterated on
+//! without recompiling the bot.
+//!
+//! The script runs once per book update with the following constants in scope and must evaluate
+//! to `"buy"`, `"sell"` or `"hold"`:
+//!
+//! | name              | type    |                                         |
+//! |-------------------|---------|-----------------------------------------|
+//! | `bid`, `ask`      | `float` | best bid and ask                        |
+//! | `mid_price`       | `float` |                                         |
+//! | `microprice`      | `float` |                                         |
+//! | `spread`          | `float` | percent of the bid                      |
+//! | `voi`, `oir`, `mpb` | `float` |                                       |
+//! | `flow`            | `float` | trade intensity imbalance, -1 to 1      |
+//! | `liquidations`    | `float` | net forced buying, in cascade volumes   |
+//! | `order_imbalance` | `float` | L3 order count imbalance, -1 to 1       |
+//! | `avg_order_size`  | `float` | mean L3 resting order size              |
+//! | `persistent_oir`  | `float` | OIR weighted by how long size has rested |
+//! | `oir_top1`, `oir_top5`, `oir_top20` | `float` | OIR of the best 1, 5 and 20 levels |
+//! | `oir_10bps`, `oir_50bps` | `float` | OIR within 10 and 50 bps of the mid |
+//! | `volatility`      | `float` | forecast σ over the holding horizon     |
+//! | `cash`            | `float` |                                         |
+//! | `open_positions`  | `int`   | number of open lots                     |
+//! | `avg_entry_price` | `float` | mean entry of open lots, `0.0` when flat |
+//!
+//! ```rhai
+//! if spread <= 0.05 && oir > 0.2 {
+//!     "buy"
+//! } else if open_positions > 0 && oir < -0.2 {
+//!     "sell"
+//! } else {
+//!     "hold"
+//! }
+//! ```
+
+use rhai::Engine;
+use rhai::Scope;
+use rhai::AST;
+use serde::Deserialize;
+use std::path::PathBuf;
+use tracing::warn;
+
+use super::Signal;
+use super::Strategy;
+use crate::features::Features;
+use crate::TradingState;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ScriptError {
+    #[error("failed to compile script {0}: {1}")]
+    Compile(PathBuf, String),
+}
+
+/// Config for a strategy member backed by a Rhai script.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScriptParams {
+    pub path: PathBuf,
+    /// Upper bound on operations per evaluation, so a runaway script can't stall the feed.
+    #[serde(default = "default_max_operations")]
+    pub max_operations: u64,
+}
+
+fn default_max_operations() -> u64 {
+    100_000
+}
+
+pub struct ScriptStrategy {
+    name: String,
+    engine: Engine,
+    ast: AST,
+}
+
+impl std::fmt::Debug for ScriptStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ScriptStrategy")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ScriptStrategy {
+    pub fn new(params: &ScriptParams) -> Result<Self, ScriptError> {
+        let source = std::fs::read_to_string(&params.path)
+            .map_err(|error| ScriptError::Compile(params.path.clone(), error.to_string()))?;
+        let name = params
+            .path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "rhai".to_owned());
+
+        Self::compile(name, &source, params.max_operations)
+            .map_err(|error| ScriptError::Compile(params.path.clone(), error))
+    }
+
+    fn compile(name: String, source: &str, max_operations: u64) -> Result<Self, String> {
+        let mut engine = Engine::new();
+        engine.set_max_operations(max_operations);
+        let ast = engine.compile(source).map_err(|error| error.to_string())?;
+
+        Ok(Self { name, engine, ast })
+    }
+}
+
+impl Strategy for ScriptStrategy {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn evaluate(&mut self, features: &Features, state: &TradingState) -> Signal {
+        let open_positions = state.positions.len();
+        let avg_entry_price = state.average_entry().unwrap_or(0.0);
+
+        let mut scope = Scope::new();
+        scope
+            .push_constant("bid", features.bid)
+            .push_constant("ask", features.ask)
+            .push_constant("mid_price", features.mid_price)
+            .push_constant("microprice", features.microprice)
+            .push_constant("spread", features.spread)
+            .push_constant("voi", features.voi)
+            .push_constant("oir", features.oir)
+            .push_constant("mpb", features.mpb)
+            .push_constant("flow", features.flow)
+            .push_constant("liquidations", features.liquidations)
+            .push_constant("order_imbalance", features.order_imbalance)
+            .push_constant("avg_order_size", features.avg_order_size)
+            .push_constant("persistent_oir", features.persistent_oir)
+            .push_constant("oir_top1", features.oir_top1)
+            .push_constant("oir_top5", features.oir_top5)
+            .push_constant("oir_top20", features.oir_top20)
+            .push_constant("oir_10bps", features.oir_10bps)
+            .push_constant("oir_50bps", features.oir_50bps)
+            .push_constant("volatility", features.volatility)
+            .push_constant("cash", state.cash)
+            .push_constant("open_positions", open_positions as i64)
+            .push_constant("avg_entry_price", avg_entry_price);
+
+        // A failing script holds rather than taking the feed down with it
+        let decision = match self
+            .engine
+            .eval_ast_with_scope::<String>(&mut scope, &self.ast)
+        {
+            Ok(decision) => decision,
+            Err(error) => {
+                warn!("Script {} failed: {}", self.name, error);
+                return Signal::Hold;
+            }
+        };
+
+        match decision.as_str() {
+            "buy" => Signal::Buy,
+            "sell" => Signal::Sell,
+            "hold" => Signal::Hold,
+            other => {
+                warn!("Script {} returned unknown decision {:?}", self.name, other);
+                Signal::Hold
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Lot;
+
+    const RULES: &str = r#"
+        if spread <= 0.05 && oir > 0.2 {
+            "buy"
+        } else if open_positions > 0 && bid > avg_entry_price * 1.01 {
+            "sell"
+        } else {
+            "hold"
+        }
+    "#;
+
+    fn strategy(source: &str) -> ScriptStrategy {
+        ScriptStrategy::compile("test".to_owned(), source, default_max_operations()).unwrap()
+    }
+
+    #[test]
+    fn test_script_decisions() {
+        let mut strategy = strategy(RULES);
+        let mut state = TradingState::new(1000.0, "BTC/USDT");
+        let features = Features {
+            bid: 100.0,
+            ask: 100.01,
+            spread: 0.01,
+            oir: 0.5,
+            ..Features::default()
+        };
+
+        assert_eq!(strategy.evaluate(&features, &state), Signal::Buy);
+
+        let neutral = Features {
+            oir: 0.0,
+            ..features
+        };
+        assert_eq!(strategy.evaluate(&neutral, &state), Signal::Hold);
+
+        state.positions.push(Lot::at(98.0));
+        assert_eq!(strategy.evaluate(&neutral, &state), Signal::Sell);
+    }
+
+    #[test]
+    fn test_script_errors_hold() {
+        let state = TradingState::new(1000.0, "BTC/USDT");
+
+        let mut wrong_type = strategy("42");
+        assert_eq!(
+            wrong_type.evaluate(&Features::default(), &state),
+            Signal::Hold
+        );
+
+        let mut runaway = strategy("loop {}");
+        assert_eq!(runaway.evaluate(&Features::default(), &state), Signal::Hold);
+
+        let mut unknown = strategy(r#""short""#);
+        assert_eq!(unknown.evaluate(&Features::default(), &state), Signal::Hold);
+    }
+
+    #[test]
+    fn test_compile_error() {
+        assert!(ScriptStrategy::compile("test".to_owned(), "if {", 1000).is_err());
+    }
+}
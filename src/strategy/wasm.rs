@@ -0,0 +1,377 @@
+//! Third-party strategies compiled to WebAssembly and run in a wasmtime sandbox.
+//!
+//! The host interface is deliberately narrow: a module gets no imports at all, and must export
+//!
+//! ```wat
+//! (func (export "evaluate")
+//!   (param $bid f64) (param $ask f64) (param $mid_price f64) (param $microprice f64)
+//!   (param $spread f64) (param $voi f64) (param $oir f64) (param $mpb f64)
+//!   (param $cash f64) (param $open_positions i64)
+//!   (result i32))
+//! ```
+//!
+//! returning `1` to buy, `-1` to sell and anything else to hold. Every call is bounded by a fuel
+//! budget, and its memory by `max_memory_bytes`: a module asking for more to start with fails to
+//! load, and growing past it fails. The module file is watched, and a rebuilt strategy compiled,
+//! on a thread of its own, so it is swapped in on the next update without restarting the feed or
+//! holding it up.
+
+use serde::Deserialize;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::Duration;
+use std::time::SystemTime;
+use tracing::info;
+use tracing::warn;
+use wasmtime::Config;
+use wasmtime::Engine;
+use wasmtime::Instance;
+use wasmtime::Module;
+use wasmtime::Store;
+use wasmtime::StoreLimits;
+use wasmtime::StoreLimitsBuilder;
+use wasmtime::TypedFunc;
+
+use super::Signal;
+use super::Strategy;
+use crate::features::Features;
+use crate::TradingState;
+
+type EvaluateParams = (f64, f64, f64, f64, f64, f64, f64, f64, f64, i64);
+
+#[derive(Debug, thiserror::Error)]
+pub enum WasmError {
+    #[error("failed to load wasm strategy {0}: {1}")]
+    Load(PathBuf, String),
+}
+
+/// Config for a strategy member backed by a WebAssembly module (`.wasm` or `.wat`).
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WasmParams {
+    pub path: PathBuf,
+    /// Fuel available to each `evaluate` call; running out holds for that update.
+    #[serde(default = "default_fuel")]
+    pub fuel: u64,
+    /// Linear memory the module may use, beyond which it fails to load or grow.
+    #[serde(default = "default_max_memory_bytes")]
+    pub max_memory_bytes: usize,
+    /// How often the module file is checked for changes.
+    #[serde(default = "default_reload_interval_secs")]
+    pub reload_interval_secs: u64,
+}
+
+fn default_fuel() -> u64 {
+    1_000_000
+}
+
+fn default_max_memory_bytes() -> usize {
+    16 << 20
+}
+
+fn default_reload_interval_secs() -> u64 {
+    5
+}
+
+/// Identifies the version of the module file on disk.
+type Fingerprint = Option<(SystemTime, u64)>;
+
+fn fingerprint(path: &PathBuf) -> Fingerprint {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// The shortest wait between checks of the module file, so a zero interval doesn't spin.
+const MIN_CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+struct Loaded {
+    store: Store<StoreLimits>,
+    evaluate: TypedFunc<EvaluateParams, i32>,
+}
+
+pub struct WasmStrategy {
+    name: String,
+    params: WasmParams,
+    loaded: Loaded,
+    /// Modules rebuilt on disk and compiled by the watcher, waiting to be swapped in.
+    reloads: mpsc::Receiver<Loaded>,
+    // Dropped with the strategy, stopping the watcher
+    _stop: mpsc::Sender<()>,
+}
+
+impl std::fmt::Debug for WasmStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WasmStrategy")
+            .field("name", &self.name)
+            .field("params", &self.params)
+            .finish_non_exhaustive()
+    }
+}
+
+impl WasmStrategy {
+    pub fn new(params: &WasmParams) -> Result<Self, WasmError> {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config)
+            .map_err(|error| WasmError::Load(params.path.clone(), error.to_string()))?;
+
+        let fingerprint = fingerprint(&params.path);
+        let loaded = load(&engine, params)?;
+        let name = params
+            .path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "wasm".to_owned());
+
+        let (reload_sender, reloads) = mpsc::channel();
+        let (stop, stopped) = mpsc::channel();
+        let watched = params.clone();
+        let watcher = name.clone();
+        std::thread::Builder::new()
+            .name(format!("wasm-{name}"))
+            .spawn(move || {
+                watch(
+                    engine,
+                    watched,
+                    watcher,
+                    fingerprint,
+                    reload_sender,
+                    stopped,
+                )
+            })
+            .map_err(|error| WasmError::Load(params.path.clone(), error.to_string()))?;
+
+        Ok(Self {
+            name,
+            params: params.clone(),
+            loaded,
+            reloads,
+            _stop: stop,
+        })
+    }
+
+    /// Swap in the latest module the watcher has compiled since the last update, if any.
+    fn maybe_reload(&mut self) {
+        if let Some(loaded) = self.reloads.try_iter().last() {
+            self.loaded = loaded;
+            info!("Reloaded wasm strategy {}", self.name);
+        }
+    }
+}
+
+fn load(engine: &Engine, params: &WasmParams) -> Result<Loaded, WasmError> {
+    let error = |error: wasmtime::Error| WasmError::Load(params.path.clone(), error.to_string());
+
+    let module = Module::from_file(engine, &params.path).map_err(error)?;
+    let limits = StoreLimitsBuilder::new()
+        .memory_size(params.max_memory_bytes)
+        .build();
+    let mut store = Store::new(engine, limits);
+    store.limiter(|limits| limits);
+    // No imports are provided, so modules that need any host access fail to instantiate
+    let instance = Instance::new(&mut store, &module, &[]).map_err(error)?;
+    let evaluate = instance
+        .get_typed_func::<EvaluateParams, i32>(&mut store, "evaluate")
+        .map_err(error)?;
+
+    Ok(Loaded { store, evaluate })
+}
+
+/// Check the module file every `reload_interval_secs` until `stop` disconnects, compiling it
+/// whenever it changes and sending it on to be swapped in. A module that fails to load is
+/// reported once and the previous one keeps trading.
+fn watch(
+    engine: Engine,
+    params: WasmParams,
+    name: String,
+    mut loaded: Fingerprint,
+    reloads: mpsc::Sender<Loaded>,
+    stop: mpsc::Receiver<()>,
+) {
+    let interval = Duration::from_secs(params.reload_interval_secs).max(MIN_CHECK_INTERVAL);
+    while let Err(RecvTimeoutError::Timeout) = stop.recv_timeout(interval) {
+        let current = fingerprint(&params.path);
+        if current.is_none() || current == loaded {
+            continue;
+        }
+        loaded = current;
+
+        match load(&engine, &params) {
+            Ok(module) => {
+                if reloads.send(module).is_err() {
+                    break;
+                }
+            }
+            Err(error) => warn!("Keeping previous wasm strategy {}: {}", name, error),
+        }
+    }
+}
+
+impl Strategy for WasmStrategy {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn evaluate(&mut self, features: &Features, state: &TradingState) -> Signal {
+        self.maybe_reload();
+
+        let Loaded { store, evaluate } = &mut self.loaded;
+        if let Err(error) = store.set_fuel(self.params.fuel) {
+            warn!("Wasm strategy {} fuel error: {}", self.name, error);
+            return Signal::Hold;
+        }
+
+        let params = (
+            features.bid,
+            features.ask,
+            features.mid_price,
+            features.microprice,
+            features.spread,
+            features.voi,
+            features.oir,
+            features.mpb,
+            state.cash,
+            state.positions.len() as i64,
+        );
+
+        // A trapping module holds rather than taking the feed down with it
+        match evaluate.call(store, params) {
+            Ok(1) => Signal::Buy,
+            Ok(-1) => Signal::Sell,
+            Ok(_) => Signal::Hold,
+            Err(error) => {
+                warn!("Wasm strategy {} trapped: {}", self.name, error);
+                Signal::Hold
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BUY_ON_OIR: &str = r#"
+        (module
+          (func (export "evaluate")
+            (param f64 f64 f64 f64 f64 f64 f64 f64 f64 i64) (result i32)
+            (if (result i32) (f64.gt (local.get 6) (f64.const 0.2))
+              (then (i32.const 1))
+              (else (i32.const 0)))))
+    "#;
+
+    const ALWAYS_SELL: &str = r#"
+        (module
+          (func (export "evaluate")
+            (param f64 f64 f64 f64 f64 f64 f64 f64 f64 i64) (result i32)
+            (i32.const -1)))
+    "#;
+
+    /// Writes `source` to a unique path in the temp dir and returns its params.
+    fn module(name: &str, source: &str) -> WasmParams {
+        let path = std::env::temp_dir().join(format!(
+            "fit-{}-{}-{}.wat",
+            name,
+            std::process::id(),
+            SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        std::fs::write(&path, source).unwrap();
+        WasmParams {
+            path,
+            fuel: default_fuel(),
+            max_memory_bytes: default_max_memory_bytes(),
+            reload_interval_secs: 0,
+        }
+    }
+
+    fn bullish() -> Features {
+        Features {
+            oir: 0.5,
+            ..Features::default()
+        }
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let params = module("evaluate", BUY_ON_OIR);
+        let mut strategy = WasmStrategy::new(&params).unwrap();
+        let state = TradingState::new(1000.0, "BTC/USDT");
+
+        assert_eq!(strategy.evaluate(&bullish(), &state), Signal::Buy);
+        assert_eq!(
+            strategy.evaluate(&Features::default(), &state),
+            Signal::Hold
+        );
+        std::fs::remove_file(params.path).unwrap();
+    }
+
+    #[test]
+    fn test_hot_reload() {
+        let params = module("reload", BUY_ON_OIR);
+        let mut strategy = WasmStrategy::new(&params).unwrap();
+        let state = TradingState::new(1000.0, "BTC/USDT");
+        assert_eq!(strategy.evaluate(&bullish(), &state), Signal::Buy);
+
+        // A broken module is ignored and the previous one keeps trading
+        std::fs::write(&params.path, "(module").unwrap();
+        std::thread::sleep(MIN_CHECK_INTERVAL * 10);
+        assert_eq!(strategy.evaluate(&bullish(), &state), Signal::Buy);
+
+        // Compiled off the update path, so swapped in once the watcher has it
+        std::fs::write(&params.path, ALWAYS_SELL).unwrap();
+        let swapped = (0..500).any(|_| {
+            std::thread::sleep(MIN_CHECK_INTERVAL);
+            strategy.evaluate(&bullish(), &state) == Signal::Sell
+        });
+        assert!(swapped);
+        std::fs::remove_file(params.path).unwrap();
+    }
+
+    #[test]
+    fn test_sandbox_limits() {
+        let state = TradingState::new(1000.0, "BTC/USDT");
+
+        let importing = module(
+            "imports",
+            r#"(module (import "env" "now" (func)) (func (export "evaluate")
+                (param f64 f64 f64 f64 f64 f64 f64 f64 f64 i64) (result i32) (i32.const 1)))"#,
+        );
+        assert!(WasmStrategy::new(&importing).is_err());
+        std::fs::remove_file(importing.path).unwrap();
+
+        let runaway = module(
+            "runaway",
+            r#"(module (func (export "evaluate")
+                (param f64 f64 f64 f64 f64 f64 f64 f64 f64 i64) (result i32)
+                (loop (br 0)) (i32.const 1)))"#,
+        );
+        let mut strategy = WasmStrategy::new(&runaway).unwrap();
+        assert_eq!(strategy.evaluate(&bullish(), &state), Signal::Hold);
+        std::fs::remove_file(runaway.path).unwrap();
+
+        // 64 KiB pages: asking for 32 MiB up front fails to load, growing there fails
+        let hungry = module(
+            "hungry",
+            r#"(module (memory 512) (func (export "evaluate")
+                (param f64 f64 f64 f64 f64 f64 f64 f64 f64 i64) (result i32) (i32.const 1)))"#,
+        );
+        assert!(WasmStrategy::new(&hungry).is_err());
+        std::fs::remove_file(hungry.path).unwrap();
+
+        let growing = module(
+            "growing",
+            r#"(module (memory 1) (func (export "evaluate")
+                (param f64 f64 f64 f64 f64 f64 f64 f64 f64 i64) (result i32)
+                (if (result i32) (i32.eq (memory.grow (i32.const 511)) (i32.const -1))
+                  (then (i32.const 0))
+                  (else (i32.const 1)))))"#,
+        );
+        let mut strategy = WasmStrategy::new(&growing).unwrap();
+        assert_eq!(strategy.evaluate(&bullish(), &state), Signal::Hold);
+        std::fs::remove_file(growing.path).unwrap();
+    }
+}
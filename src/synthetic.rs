@@ -0,0 +1,373 @@
+//! Deterministic synthetic order books and trades, for benchmarks, tests and demos without any
+//! exchange data: the mid price takes a seeded random walk, pulled back toward where it started,
+//! with volatility that clusters. Every book is built around it to a fixed depth, the size on
+//! each side skewed at random, and now and then an episode of heavily imbalanced depth leans the
+//! book, the trades and the mid one way, so imbalance signals have something to find. The same
+//! seed and config always give the same market.
+
+use barter_data::event::MarketEvent;
+use barter_data::subscription::book::Level;
+use barter_data::subscription::book::OrderBook;
+use barter_data::subscription::book::OrderBookSide;
+use barter_data::subscription::trade::PublicTrade;
+use barter_integration::model::instrument::kind::InstrumentKind;
+use barter_integration::model::instrument::Instrument;
+use barter_integration::model::Exchange;
+use barter_integration::model::Side;
+use chrono::DateTime;
+use chrono::TimeDelta;
+use chrono::Utc;
+
+use crate::rng::Rng;
+
+/// Shape of a synthetic market.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntheticConfig {
+    /// Mid price to start from.
+    pub mid_price: f64,
+    pub tick_size: f64,
+    /// Levels on each side of every book.
+    pub depth: usize,
+    /// Long run standard deviation of the mid price's relative move per book.
+    pub volatility: f64,
+    /// Fraction of the log distance back to `mid_price` the mid is pulled each book.
+    pub mean_reversion: f64,
+    /// GARCH(1,1) clustering: how much of the last book's variance carries over, and how much of
+    /// its squared move is added. Their sum must be under 1, otherwise volatility stays constant.
+    pub volatility_persistence: f64,
+    pub volatility_reaction: f64,
+    /// Chance per book of an imbalanced depth episode starting, lasting `episode_books` books with
+    /// the depth on one side, at random, scaled by 1 + `episode_skew` and the other's by
+    /// 1 - `episode_skew`.
+    pub episode_rate: f64,
+    pub episode_books: u32,
+    pub episode_skew: f64,
+    /// Exchange time between books.
+    pub interval: TimeDelta,
+    /// Most a book or trade takes to be received, the least being none.
+    pub max_latency: TimeDelta,
+}
+
+impl Default for SyntheticConfig {
+    fn default() -> Self {
+        Self {
+            mid_price: 60_000.0,
+            tick_size: 0.5,
+            depth: 20,
+            volatility: 0.0001,
+            mean_reversion: 0.001,
+            volatility_persistence: 0.9,
+            volatility_reaction: 0.05,
+            episode_rate: 0.002,
+            episode_books: 50,
+            episode_skew: 0.6,
+            interval: TimeDelta::milliseconds(100),
+            max_latency: TimeDelta::milliseconds(5),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SyntheticMarket {
+    config: SyntheticConfig,
+    rng: Rng,
+    mid_price: f64,
+    /// Variance of the next relative move.
+    variance: f64,
+    last_move: f64,
+    /// Books left in the episode in progress, and which way it leans: 1 toward the bids.
+    episode: Option<(u32, f64)>,
+    time: DateTime<Utc>,
+    trades: u64,
+}
+
+impl SyntheticMarket {
+    pub fn new(seed: u64, config: SyntheticConfig) -> Self {
+        Self {
+            mid_price: config.mid_price,
+            variance: config.volatility.powi(2),
+            last_move: 0.0,
+            episode: None,
+            config,
+            rng: Rng::new(seed),
+            time: DateTime::from_timestamp(1_704_067_200, 0).expect("2024-01-01 is representable"),
+            trades: 0,
+        }
+    }
+
+    /// Where the mid price has walked to.
+    pub fn mid_price(&self) -> f64 {
+        self.mid_price
+    }
+
+    /// Which way the imbalanced depth episode in progress leans, 1 toward the bids and -1 toward
+    /// the asks, if one is.
+    pub fn episode(&self) -> Option<f64> {
+        self.episode.map(|(_, direction)| direction)
+    }
+
+    /// Move the mid price on: a clustered volatility shock, the pull back toward the start and,
+    /// during an episode, a drift the way the book leans.
+    fn step_mid(&mut self) {
+        let config = &self.config;
+        let carry = config.volatility_persistence + config.volatility_reaction;
+        if carry < 1.0 {
+            self.variance = config.volatility.powi(2) * (1.0 - carry)
+                + config.volatility_persistence * self.variance
+                + config.volatility_reaction * self.last_move.powi(2);
+        }
+        let deviation = self.variance.sqrt();
+        let shock = deviation * self.rng.centred();
+        let reversion = config.mean_reversion * (config.mid_price / self.mid_price).ln();
+        let drift = self.episode().map_or(0.0, |direction| {
+            direction * config.episode_skew * deviation / 2.0
+        });
+        self.last_move = shock;
+        self.mid_price *= (shock + reversion + drift).exp();
+    }
+
+    /// Count the episode in progress down, or maybe start one.
+    fn step_episode(&mut self) {
+        self.episode = match self.episode {
+            Some((left, direction)) if left > 1 => Some((left - 1, direction)),
+            _ if self.rng.unit() < self.config.episode_rate => {
+                let direction = if self.rng.unit() < 0.5 { 1.0 } else { -1.0 };
+                Some((self.config.episode_books, direction))
+            }
+            _ => None,
+        };
+    }
+
+    /// Stamp an update at the current exchange time, received some way into the latency range.
+    fn event<T>(&mut self, kind: T) -> MarketEvent<T> {
+        let latency = self.config.max_latency.num_microseconds().unwrap_or(0) as f64;
+        MarketEvent {
+            exchange_time: self.time,
+            received_time: self.time + TimeDelta::microseconds((self.rng.unit() * latency) as i64),
+            exchange: Exchange::from("aevo"),
+            instrument: Instrument::from(("btc", "usd", InstrumentKind::Perpetual)),
+            kind,
+        }
+    }
+
+    /// Step the mid price and time on, and build the book around the new mid.
+    pub fn next_book(&mut self) -> MarketEvent<OrderBook> {
+        self.step_episode();
+        self.step_mid();
+        self.time += self.config.interval;
+
+        let tick = self.config.tick_size;
+        // One to three ticks wide, and never crossed
+        let spread_ticks = 1 + self.rng.next_u64() % 3;
+        let best_bid = ((self.mid_price - spread_ticks as f64 * tick / 2.0) / tick).floor() * tick;
+        let best_ask = best_bid + spread_ticks as f64 * tick;
+        let skew = match self.episode() {
+            Some(direction) => direction * self.config.episode_skew,
+            None => self.rng.unit() - 0.5,
+        };
+        let mut side = |best: f64, direction: f64, weight: f64| {
+            (0..self.config.depth)
+                .map(|level| {
+                    let amount = (0.1 + self.rng.unit() * 2.0) * weight;
+                    Level::new(
+                        best + direction * level as f64 * tick,
+                        (amount * 1000.0).round() / 1000.0,
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        let bids = side(best_bid, -1.0, 1.0 + skew);
+        let asks = side(best_ask, 1.0, 1.0 - skew);
+
+        let book = OrderBook {
+            last_update_time: self.time,
+            bids: OrderBookSide::new(Side::Buy, bids),
+            asks: OrderBookSide::new(Side::Sell, asks),
+        };
+        self.event(book)
+    }
+
+    /// A trade at the book's current touch, without moving the market, on a random side that
+    /// leans the episode's way during one.
+    pub fn next_trade(&mut self) -> MarketEvent<PublicTrade> {
+        self.trades += 1;
+        let buys = 0.5
+            + self
+                .episode()
+                .map_or(0.0, |direction| direction * self.config.episode_skew / 2.0);
+        let side = if self.rng.unit() < buys {
+            Side::Buy
+        } else {
+            Side::Sell
+        };
+        let half_spread = self.config.tick_size / 2.0;
+        let price = match side {
+            Side::Buy => self.mid_price + half_spread,
+            Side::Sell => self.mid_price - half_spread,
+        };
+        let trade = PublicTrade {
+            id: self.trades.to_string(),
+            price,
+            amount: ((0.001 + self.rng.unit() * 0.5) * 1000.0).round() / 1000.0,
+            side,
+        };
+        self.event(trade)
+    }
+}
+
+impl Iterator for SyntheticMarket {
+    type Item = MarketEvent<OrderBook>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.next_book())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_seed_same_market() {
+        let books = |seed| {
+            SyntheticMarket::new(seed, SyntheticConfig::default())
+                .take(50)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(books(7), books(7));
+        assert_ne!(books(7), books(8));
+
+        let mut market = SyntheticMarket::new(7, SyntheticConfig::default());
+        let first = market.next_trade();
+        assert_eq!(first.kind.id, "1");
+        assert_eq!(market.next_trade().kind.id, "2");
+        let mut again = SyntheticMarket::new(7, SyntheticConfig::default());
+        assert_eq!(again.next_trade(), first);
+    }
+
+    /// The mid price after each of `books` books.
+    fn mids(config: SyntheticConfig, books: usize) -> Vec<f64> {
+        let mut market = SyntheticMarket::new(11, config);
+        (0..books)
+            .map(|_| {
+                market.next_book();
+                market.mid_price()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_mean_reversion() {
+        let config = SyntheticConfig {
+            volatility: 0.001,
+            mean_reversion: 0.05,
+            ..SyntheticConfig::default()
+        };
+        let start = config.mid_price;
+        assert!(mids(config, 20_000)
+            .iter()
+            .all(|mid| (mid / start - 1.0).abs() < 0.05));
+    }
+
+    #[test]
+    fn test_volatility_clusters() {
+        // Autocorrelation of the size of consecutive moves
+        let clustering = |config: SyntheticConfig| {
+            let mids = mids(config, 20_000);
+            let moves: Vec<f64> = mids
+                .windows(2)
+                .map(|pair| (pair[1] / pair[0]).ln().abs())
+                .collect();
+            let mean = moves.iter().sum::<f64>() / moves.len() as f64;
+            let variance: f64 = moves.iter().map(|size| (size - mean).powi(2)).sum();
+            let covariance: f64 = moves
+                .windows(2)
+                .map(|pair| (pair[0] - mean) * (pair[1] - mean))
+                .sum();
+            covariance / variance
+        };
+        let constant = SyntheticConfig {
+            volatility_persistence: 0.0,
+            volatility_reaction: 0.0,
+            episode_rate: 0.0,
+            ..SyntheticConfig::default()
+        };
+        let clustered = SyntheticConfig {
+            volatility_persistence: 0.75,
+            volatility_reaction: 0.2,
+            ..constant.clone()
+        };
+        assert!(clustering(constant) < 0.05);
+        assert!(clustering(clustered) > 0.1);
+    }
+
+    #[test]
+    fn test_imbalanced_depth_episodes() {
+        // Mean absolute imbalance of total depth, and how often an episode was in progress
+        let imbalance = |config: SyntheticConfig| {
+            let mut market = SyntheticMarket::new(5, config);
+            let mut total = 0.0;
+            let mut episodes = 0;
+            for _ in 0..2_000 {
+                let book = market.next_book().kind;
+                let depth = |levels: &[Level]| levels.iter().map(|level| level.amount).sum::<f64>();
+                let (bids, asks) = (depth(&book.bids.levels), depth(&book.asks.levels));
+                total += ((bids - asks) / (bids + asks)).abs();
+                episodes += usize::from(market.episode().is_some());
+            }
+            (total / 2_000.0, episodes)
+        };
+        let (calm, none) = imbalance(SyntheticConfig {
+            episode_rate: 0.0,
+            ..SyntheticConfig::default()
+        });
+        assert_eq!(none, 0);
+        let (leaning, episodes) = imbalance(SyntheticConfig {
+            episode_rate: 0.1,
+            episode_books: 20,
+            ..SyntheticConfig::default()
+        });
+        assert!(episodes > 1_000);
+        assert!(leaning > calm + 0.1);
+
+        // Trades lean the episode's way
+        let mut market = SyntheticMarket::new(
+            5,
+            SyntheticConfig {
+                episode_rate: 1.0,
+                episode_books: 1_000,
+                ..SyntheticConfig::default()
+            },
+        );
+        market.next_book();
+        let direction = market.episode().unwrap();
+        let buys = (0..1_000)
+            .filter(|_| market.next_trade().kind.side == Side::Buy)
+            .count() as f64;
+        assert!(direction * (buys / 1_000.0 - 0.5) > 0.2);
+    }
+
+    #[test]
+    fn test_books_are_well_formed() {
+        let config = SyntheticConfig {
+            depth: 5,
+            ..SyntheticConfig::default()
+        };
+        let mut previous = None;
+        for event in SyntheticMarket::new(1, config.clone()).take(1000) {
+            let (bids, asks) = (&event.kind.bids.levels, &event.kind.asks.levels);
+            assert_eq!((bids.len(), asks.len()), (5, 5));
+            assert!(bids[0].price < asks[0].price);
+            assert!(bids.windows(2).all(|pair| pair[0].price > pair[1].price));
+            assert!(asks.windows(2).all(|pair| pair[0].price < pair[1].price));
+            assert!(bids.iter().chain(asks).all(|level| level.amount > 0.0));
+
+            assert!(event.received_time >= event.exchange_time);
+            assert!(event.received_time - event.exchange_time <= config.max_latency);
+            if let Some(previous) = previous {
+                assert_eq!(event.exchange_time - previous, config.interval);
+            }
+            previous = Some(event.exchange_time);
+        }
+    }
+}
@@ -0,0 +1,182 @@
+//! systemd's service notifications, for running as a `Type=notify` unit: the bot says it is ready
+//! once its feeds are subscribed, pings the watchdog from the trading loop so systemd restarts it
+//! if the loop hangs, and says when it is stopping.
+//!
+//! Everything is configured by systemd through the environment, `NOTIFY_SOCKET` for where to
+//! notify and `WATCHDOG_USEC` with `WatchdogSec=`, so outside systemd, and on platforms without
+//! Unix sockets, nothing is sent.
+
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::warn;
+
+/// Sends notifications to systemd, when started by it.
+#[derive(Debug)]
+pub struct Notifier {
+    #[cfg(unix)]
+    socket: Option<std::os::unix::net::UnixDatagram>,
+    ping_interval: Option<Duration>,
+    next_ping: Option<Instant>,
+}
+
+impl Notifier {
+    /// Connect to the socket systemd passed in `NOTIFY_SOCKET`, pinging twice as often as
+    /// `WATCHDOG_USEC` requires when the watchdog is meant for this process.
+    pub fn from_env(now: Instant) -> Self {
+        #[cfg(unix)]
+        let socket = std::env::var_os("NOTIFY_SOCKET").and_then(|path| {
+            connect(&path)
+                .map_err(|error| {
+                    warn!(
+                        "Not notifying systemd, connecting to {}: {}",
+                        path.to_string_lossy(),
+                        error
+                    )
+                })
+                .ok()
+        });
+        // Without anywhere to send them, there is no point in timing pings
+        #[cfg(unix)]
+        let ping_interval = socket.as_ref().and_then(|_| {
+            ping_interval(
+                std::env::var("WATCHDOG_USEC").ok().as_deref(),
+                std::env::var("WATCHDOG_PID").ok().as_deref(),
+                std::process::id(),
+            )
+        });
+        #[cfg(not(unix))]
+        let ping_interval = None;
+        Self {
+            #[cfg(unix)]
+            socket,
+            ping_interval,
+            next_ping: ping_interval.map(|_| now),
+        }
+    }
+
+    /// Tell systemd startup has finished.
+    pub fn ready(&self) {
+        self.send("READY=1");
+    }
+
+    /// Tell systemd the bot is shutting down.
+    pub fn stopping(&self) {
+        self.send("STOPPING=1");
+    }
+
+    /// Ping the watchdog, if it is due at `now`.
+    pub fn ping(&mut self, now: Instant) {
+        let (Some(interval), Some(next_ping)) = (self.ping_interval, self.next_ping) else {
+            return;
+        };
+        if now >= next_ping {
+            self.send("WATCHDOG=1");
+            self.next_ping = Some(now + interval);
+        }
+    }
+
+    /// Wait until the next watchdog ping is due. Never resolves without a watchdog.
+    pub async fn ping_due(&self) {
+        match self.next_ping {
+            Some(next_ping) => tokio::time::sleep_until(next_ping).await,
+            None => std::future::pending().await,
+        }
+    }
+
+    fn send(&self, state: &str) {
+        #[cfg(unix)]
+        if let Some(socket) = &self.socket {
+            if let Err(error) = socket.send(state.as_bytes()) {
+                warn!("Failed to notify systemd of {}: {}", state, error);
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = state;
+    }
+}
+
+/// How often to ping a watchdog expecting one every `usec` microseconds, unless it watches
+/// another process than `own`.
+fn ping_interval(usec: Option<&str>, pid: Option<&str>, own: u32) -> Option<Duration> {
+    if pid.is_some_and(|pid| pid.parse() != Ok(own)) {
+        return None;
+    }
+    let usec: u64 = usec?.parse().ok().filter(|usec| *usec > 0)?;
+    Some(Duration::from_micros(usec) / 2)
+}
+
+/// A datagram socket connected to `path`, which is in the abstract namespace when it starts `@`.
+#[cfg(unix)]
+fn connect(path: &std::ffi::OsStr) -> std::io::Result<std::os::unix::net::UnixDatagram> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let socket = std::os::unix::net::UnixDatagram::unbound()?;
+    socket.set_nonblocking(true)?;
+    match path.as_bytes().strip_prefix(b"@") {
+        #[cfg(target_os = "linux")]
+        Some(name) => {
+            use std::os::linux::net::SocketAddrExt;
+            socket.connect_addr(&std::os::unix::net::SocketAddr::from_abstract_name(name)?)?
+        }
+        _ => socket.connect(path)?,
+    }
+    Ok(socket)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ping_interval() {
+        assert_eq!(
+            ping_interval(Some("30000000"), None, 7),
+            Some(Duration::from_secs(15))
+        );
+        assert_eq!(
+            ping_interval(Some("30000000"), Some("7"), 7),
+            Some(Duration::from_secs(15))
+        );
+        assert_eq!(ping_interval(Some("30000000"), Some("8"), 7), None);
+        assert_eq!(ping_interval(Some("0"), None, 7), None);
+        assert_eq!(ping_interval(Some("soon"), None, 7), None);
+        assert_eq!(ping_interval(None, None, 7), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_notify() {
+        let path = std::env::temp_dir().join(format!(
+            "fit-notify-{}-{}.sock",
+            std::process::id(),
+            std::time::SystemTime::now()
+                .duration_since(std::time::SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        let systemd = std::os::unix::net::UnixDatagram::bind(&path).unwrap();
+        let start = Instant::now();
+        let mut notifier = Notifier {
+            socket: Some(connect(path.as_os_str()).unwrap()),
+            ping_interval: Some(Duration::from_secs(15)),
+            next_ping: Some(start),
+        };
+        let recv = || {
+            let mut buffer = [0; 64];
+            let length = systemd.recv(&mut buffer).unwrap();
+            String::from_utf8(buffer[..length].to_vec()).unwrap()
+        };
+
+        notifier.ready();
+        assert_eq!(recv(), "READY=1");
+        notifier.ping(start);
+        assert_eq!(recv(), "WATCHDOG=1");
+        // Not due again until the interval has passed
+        notifier.ping(start + Duration::from_secs(14));
+        notifier.ping(start + Duration::from_secs(15));
+        assert_eq!(recv(), "WATCHDOG=1");
+        notifier.stopping();
+        assert_eq!(recv(), "STOPPING=1");
+        std::fs::remove_file(path).unwrap();
+    }
+}
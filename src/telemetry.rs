@@ -0,0 +1,332 @@
+//! Spans over the lifecycle of every trade, exported over OTLP with the `otel` feature and
+//! [`[telemetry]`](crate::config::TelemetryConfig) so a tracing backend can show where the time in
+//! each decision goes.
+//!
+//! Each position gets a `trade` span, open from its entry until it is closed, holding the entry's
+//! stages, `signal`, `risk_check`, `order` and `fill`, and a `close` span holding the exit's. Every
+//! book is evaluated, almost always to hold, so a decision's stages are only recorded once it
+//! trades, dated back to when they ran. Paper orders fill as they are placed, while the order
+//! gateway's stay open until their execution report arrives.
+//!
+//! The spans are at debug level under the [`TARGET`] target, so left out of the logs unless asked
+//! for.
+
+use std::time::SystemTime;
+
+use barter_integration::model::Side;
+use tracing::field;
+use tracing::Level;
+use tracing::Span;
+#[cfg(feature = "otel")]
+use tracing_opentelemetry::OtelData;
+#[cfg(feature = "otel")]
+use tracing_subscriber::registry::LookupSpan;
+#[cfg(feature = "otel")]
+use tracing_subscriber::Registry;
+
+#[cfg(feature = "otel")]
+use crate::config::TelemetryConfig;
+use crate::features::Features;
+use crate::strategy::Signal;
+use crate::Fill;
+
+pub const TARGET: &str = "trade";
+
+/// The current time if trade spans are being recorded, to date a stage from.
+pub fn now() -> Option<SystemTime> {
+    tracing::enabled!(target: TARGET, Level::DEBUG).then(SystemTime::now)
+}
+
+/// Date `span` as running from `start`, and until `end` when given rather than until it closes.
+/// Only exported spans carry times, so this does nothing without the `otel` feature.
+fn date(span: &Span, start: Option<SystemTime>, end: Option<SystemTime>) {
+    #[cfg(feature = "otel")]
+    span.with_subscriber(|(id, dispatch)| {
+        let Some(span) = dispatch
+            .downcast_ref::<Registry>()
+            .and_then(|registry| registry.span(id))
+        else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        if let Some(data) = extensions.get_mut::<OtelData>() {
+            data.builder.start_time = start.or(data.builder.start_time);
+            data.builder.end_time = end;
+        }
+    });
+    #[cfg(not(feature = "otel"))]
+    let _ = (span, start, end);
+}
+
+/// The trade span of each position a sleeve holds, newest last, as positions are closed.
+///
+/// Positions restored from a snapshot have none, so their closes are traced on their own.
+#[derive(Debug, Default)]
+pub struct Trades {
+    open: Vec<Span>,
+}
+
+impl Trades {
+    /// Record the signal and risk check stages of a decision to trade on `signal`, evaluated from
+    /// `started` and checked from `evaluated`, returning the span to place its order under: a new
+    /// trade when buying, or the close of the newest open trade when selling.
+    pub fn decide(
+        &self,
+        symbol: &'static str,
+        strategy: &str,
+        signal: Signal,
+        features: &Features,
+        started: Option<SystemTime>,
+        evaluated: Option<SystemTime>,
+    ) -> Span {
+        let decision = match signal {
+            Signal::Sell => self.exit("signal"),
+            _ => tracing::debug_span!(target: TARGET, parent: None, "trade", symbol, strategy),
+        };
+        date(&decision, started, None);
+
+        // Both stages are over, so close as soon as they are dated
+        date(
+            &tracing::debug_span!(
+                target: TARGET,
+                parent: &decision,
+                "signal",
+                signal = ?signal,
+                voi = features.voi,
+                spread = features.spread,
+            ),
+            started,
+            evaluated,
+        );
+        date(
+            &tracing::debug_span!(target: TARGET, parent: &decision, "risk_check"),
+            evaluated,
+            None,
+        );
+        decision
+    }
+
+    /// The span to place an order closing the newest open trade under, for `reason`: `risk` when
+    /// take profit or stop loss trigger, `flatten` when closing everything.
+    pub fn exit(&self, reason: &'static str) -> Span {
+        let trade = self.open.last().and_then(Span::id);
+        tracing::debug_span!(target: TARGET, parent: trade, "close", reason)
+    }
+
+    /// An order span under `decision`, open until the order is filled or rejected.
+    pub fn order(decision: &Span, side: Side, price: f64) -> Span {
+        tracing::debug_span!(
+            target: TARGET,
+            parent: decision,
+            "order",
+            side = ?side,
+            price,
+            rejected = field::Empty,
+        )
+    }
+
+    /// Record `fill` of the order under `decision`, opening its trade on a buy or closing the
+    /// newest on a sell.
+    pub fn filled(&mut self, decision: Span, order: &Span, fill: &Fill) {
+        let _fill = tracing::debug_span!(
+            target: TARGET,
+            parent: order,
+            "fill",
+            price = fill.price,
+            size = fill.size,
+            fee = fill.fee,
+        );
+        match fill.side {
+            Side::Buy => self.open.push(decision),
+            Side::Sell => {
+                self.open.pop();
+            }
+        }
+    }
+}
+
+#[cfg(feature = "otel")]
+#[derive(Debug, thiserror::Error)]
+pub enum TelemetryError {
+    #[error("failed to build the OTLP span exporter: {0}")]
+    Exporter(#[from] opentelemetry::trace::TraceError),
+}
+
+/// Exports trade spans to an OTLP collector in batches, until shut down.
+#[cfg(feature = "otel")]
+#[derive(Debug)]
+pub struct SpanExporter {
+    provider: opentelemetry_sdk::trace::TracerProvider,
+}
+
+#[cfg(feature = "otel")]
+impl SpanExporter {
+    /// Connect an exporter as `config` says, with the layer to record trade spans through it.
+    /// Needs a Tokio runtime to export from.
+    pub fn new<S>(
+        config: &TelemetryConfig,
+    ) -> Result<(Self, impl tracing_subscriber::Layer<S>), TelemetryError>
+    where
+        S: tracing::Subscriber + for<'span> LookupSpan<'span>,
+    {
+        use opentelemetry::trace::TracerProvider;
+        use opentelemetry_otlp::WithExportConfig;
+        use tracing_subscriber::Layer;
+
+        let exporter = opentelemetry_otlp::SpanExporter::builder()
+            .with_tonic()
+            .with_endpoint(&config.endpoint)
+            .build()?;
+        let provider = opentelemetry_sdk::trace::TracerProvider::builder()
+            .with_batch_exporter(exporter, opentelemetry_sdk::runtime::Tokio)
+            .with_resource(opentelemetry_sdk::Resource::new([
+                opentelemetry::KeyValue::new("service.name", config.service_name.clone()),
+            ]))
+            .build();
+        let layer = tracing_opentelemetry::layer()
+            .with_tracer(provider.tracer(env!("CARGO_PKG_NAME")))
+            .with_filter(
+                tracing_subscriber::filter::Targets::new().with_target(TARGET, Level::DEBUG),
+            );
+        Ok((Self { provider }, layer))
+    }
+
+    /// Export every span still queued and stop.
+    pub fn shutdown(&self) -> Result<(), TelemetryError> {
+        Ok(self.provider.shutdown()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::Mutex;
+
+    use tracing::span;
+    use tracing::Subscriber;
+    use tracing_subscriber::layer::Context;
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::registry::LookupSpan;
+    use tracing_subscriber::Layer;
+
+    use super::*;
+
+    /// A span's name and its parent's.
+    type Named = (&'static str, Option<&'static str>);
+
+    /// Records each span as it closes.
+    #[derive(Clone, Default)]
+    struct Closed(Arc<Mutex<Vec<Named>>>);
+
+    impl<S: Subscriber + for<'span> LookupSpan<'span>> Layer<S> for Closed {
+        fn on_close(&self, id: span::Id, ctx: Context<'_, S>) {
+            let span = ctx.span(&id).unwrap();
+            let parent = span.parent().map(|parent| parent.name());
+            self.0.lock().unwrap().push((span.name(), parent));
+        }
+    }
+
+    fn features() -> Features {
+        Features {
+            bid: 100.0,
+            ask: 101.0,
+            mid_price: 100.5,
+            microprice: 100.5,
+            spread: 1.0,
+            voi: 2.0,
+            oir: 0.5,
+            mpb: 0.0,
+            flow: 0.0,
+            liquidations: 0.0,
+            order_imbalance: 0.0,
+            avg_order_size: 0.0,
+            persistent_oir: 0.0,
+            oir_top1: 0.5,
+            oir_top5: 0.5,
+            oir_top20: 0.5,
+            oir_10bps: 0.5,
+            oir_50bps: 0.5,
+            volatility: 0.0,
+        }
+    }
+
+    fn fill(side: Side) -> Fill {
+        Fill {
+            side,
+            price: 100.0,
+            size: 0.001,
+            fee: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_trade_lifecycle() {
+        let closed = Closed::default();
+        let subscriber = tracing_subscriber::registry().with(closed.clone());
+        tracing::subscriber::with_default(subscriber, || {
+            let mut trades = Trades::default();
+            let started = now();
+            assert!(started.is_some());
+
+            let entry = trades.decide(
+                "BTC/USDT",
+                "imbalance",
+                Signal::Buy,
+                &features(),
+                started,
+                now(),
+            );
+            let order = Trades::order(&entry, Side::Buy, 100.0);
+            trades.filled(entry, &order, &fill(Side::Buy));
+            drop(order);
+            assert_eq!(trades.open.len(), 1);
+
+            let exit = trades.exit("risk");
+            let order = Trades::order(&exit, Side::Sell, 100.0);
+            trades.filled(exit, &order, &fill(Side::Sell));
+            drop(order);
+            assert!(trades.open.is_empty());
+
+            // Without an open trade the close is traced on its own
+            drop(trades.exit("flatten"));
+        });
+
+        let closed = closed.0.lock().unwrap();
+        assert_eq!(
+            *closed,
+            [
+                ("signal", Some("trade")),
+                ("risk_check", Some("trade")),
+                ("fill", Some("order")),
+                ("order", Some("trade")),
+                ("fill", Some("order")),
+                ("order", Some("close")),
+                ("close", Some("trade")),
+                ("trade", None),
+                ("close", None),
+            ]
+        );
+    }
+
+    #[cfg(feature = "otel")]
+    #[test]
+    fn test_date_exported_spans() {
+        let layer = tracing_opentelemetry::layer()
+            .with_tracer(opentelemetry::trace::noop::NoopTracer::new());
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            let start = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(60);
+            let end = start + std::time::Duration::from_millis(5);
+            let span = tracing::debug_span!(target: TARGET, "signal");
+            date(&span, Some(start), Some(end));
+            let times = span.with_subscriber(|(id, dispatch)| {
+                let registry = dispatch.downcast_ref::<Registry>().unwrap();
+                let span = registry.span(id).unwrap();
+                let extensions = span.extensions();
+                let data = extensions.get::<OtelData>().unwrap();
+                (data.builder.start_time, data.builder.end_time)
+            });
+            assert_eq!(times, Some((Some(start), Some(end))));
+        });
+    }
+}
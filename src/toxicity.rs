@@ -0,0 +1,290 @@
+//! Standing down from toxic flow, [`[toxicity]`](crate::config::ToxicityConfig). Two measures
+//! of informed trading block entries while either says the flow is toxic:
+//!
+//! - VPIN, the volume-synchronised probability of informed trading: the traded perpetual's
+//!   public trades are filled into buckets of `bucket_volume`, and VPIN is the mean imbalance
+//!   between buy and sell volume over the last `buckets` of them. Once it reaches `max_vpin`
+//!   entries stop, and resume once it is back down to `resume_vpin`.
+//! - Adverse selection of the bot's own fills, with `max_adverse_bps` set: each fill is marked
+//!   against the mid `markout_secs` later, and once the last `fills` of them average more than
+//!   `max_adverse_bps` against the fills, entries stop for `cooldown_secs`. With entries blocked
+//!   no new fills come in to say things are better, so the markouts start afresh after it.
+//!
+//! A warning is raised on standing down, naming the measure, and resolved once both allow
+//! entries again. Exits keep running throughout.
+
+use barter_integration::model::Side;
+use chrono::DateTime;
+use chrono::TimeDelta;
+use chrono::Utc;
+use std::collections::VecDeque;
+use tracing::info;
+use tracing::warn;
+
+use crate::config::ToxicityConfig;
+use crate::event::Alert;
+use crate::event::Severity;
+use crate::hours::Permission;
+
+/// The [condition](Alert::condition) every toxicity alert is about.
+pub const CONDITION: &str = "toxicity";
+
+/// Volume-synchronised probability of informed trading over equal volume buckets.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Vpin {
+    bucket_volume: f64,
+    buckets: usize,
+    /// Buy and sell volume of the bucket being filled.
+    filling: (f64, f64),
+    /// The volume imbalance of each of the latest full buckets, as a fraction, oldest first.
+    imbalances: VecDeque<f64>,
+}
+
+impl Vpin {
+    pub fn new(bucket_volume: f64, buckets: usize) -> Self {
+        Self {
+            bucket_volume,
+            buckets: buckets.max(1),
+            filling: (0.0, 0.0),
+            imbalances: VecDeque::new(),
+        }
+    }
+
+    /// Fill `amount` taken on `side` into the buckets, spilling into the next as each fills.
+    pub fn on_trade(&mut self, side: Side, amount: f64) {
+        if !amount.is_finite() || amount <= 0.0 || self.bucket_volume <= 0.0 {
+            return;
+        }
+        let mut left = amount;
+        while left > 0.0 {
+            let (buys, sells) = &mut self.filling;
+            let room = self.bucket_volume - *buys - *sells;
+            let taken = left.min(room);
+            match side {
+                Side::Buy => *buys += taken,
+                Side::Sell => *sells += taken,
+            }
+            left -= taken;
+            if taken >= room {
+                let (buys, sells) = self.filling;
+                self.imbalances
+                    .push_back((buys - sells).abs() / self.bucket_volume);
+                while self.imbalances.len() > self.buckets {
+                    self.imbalances.pop_front();
+                }
+                self.filling = (0.0, 0.0);
+            }
+        }
+    }
+
+    /// The mean imbalance over the last `buckets`, once that many have filled.
+    pub fn value(&self) -> Option<f64> {
+        (self.imbalances.len() == self.buckets)
+            .then(|| self.imbalances.iter().sum::<f64>() / self.buckets as f64)
+    }
+}
+
+#[derive(Debug)]
+pub struct ToxicityGuard {
+    config: ToxicityConfig,
+    vpin: Vpin,
+    /// Whether VPIN has reached `max_vpin` and not yet come back down to `resume_vpin`.
+    vpin_toxic: bool,
+    /// The side, time and mid of each fill yet to be marked, oldest first.
+    unmarked: VecDeque<(Side, DateTime<Utc>, f64)>,
+    /// How far the mid moved the way of each of the latest marked fills, in bps.
+    markouts: VecDeque<f64>,
+    /// Until when entries stay blocked after the fills were adversely selected.
+    cooling: Option<DateTime<Utc>>,
+    toxic: bool,
+}
+
+impl ToxicityGuard {
+    pub fn new(config: &ToxicityConfig) -> Self {
+        Self {
+            config: config.clone(),
+            vpin: Vpin::new(config.bucket_volume, config.buckets),
+            vpin_toxic: false,
+            unmarked: VecDeque::new(),
+            markouts: VecDeque::new(),
+            cooling: None,
+            toxic: false,
+        }
+    }
+
+    pub fn vpin(&self) -> Option<f64> {
+        self.vpin.value()
+    }
+
+    /// Count a public trade of `amount` taken on `side`.
+    pub fn on_trade(&mut self, side: Side, amount: f64) {
+        self.vpin.on_trade(side, amount);
+    }
+
+    /// Start marking a fill on `side` at `time`, when the mid was `mid_price`.
+    pub fn on_fill(&mut self, side: Side, time: DateTime<Utc>, mid_price: f64) {
+        if self.config.max_adverse_bps.is_some() && mid_price > 0.0 {
+            self.unmarked.push_back((side, time, mid_price));
+        }
+    }
+
+    /// The mean markout of the last `fills` fills, once that many have been marked.
+    pub fn markout(&self) -> Option<f64> {
+        let fills = self.config.fills.max(1);
+        (self.markouts.len() == fills).then(|| self.markouts.iter().sum::<f64>() / fills as f64)
+    }
+
+    /// Mark fills against `mid_price` at `now`, and judge the flow, returning the alert to raise
+    /// if it just turned toxic or stopped being so.
+    pub fn update(&mut self, mid_price: f64, now: DateTime<Utc>) -> Option<Alert> {
+        let horizon = TimeDelta::seconds(self.config.markout_secs as i64);
+        while let Some(&(side, time, then)) = self.unmarked.front() {
+            if now - time < horizon {
+                break;
+            }
+            self.unmarked.pop_front();
+            let moved = (mid_price - then) / then * 10_000.0;
+            self.markouts.push_back(match side {
+                Side::Buy => moved,
+                Side::Sell => -moved,
+            });
+            while self.markouts.len() > self.config.fills.max(1) {
+                self.markouts.pop_front();
+            }
+        }
+
+        if let Some(vpin) = self.vpin.value() {
+            if vpin >= self.config.max_vpin {
+                self.vpin_toxic = true;
+            } else if vpin <= self.config.resume_vpin {
+                self.vpin_toxic = false;
+            }
+        }
+        let markout = self.markout();
+        if let (Some(max), Some(markout)) = (self.config.max_adverse_bps, markout) {
+            if markout < -max {
+                self.cooling = Some(now + TimeDelta::seconds(self.config.cooldown_secs as i64));
+                self.markouts.clear();
+            }
+        }
+        if self.cooling.is_some_and(|until| now >= until) {
+            self.cooling = None;
+        }
+
+        let toxic = self.vpin_toxic || self.cooling.is_some();
+        if toxic == self.toxic {
+            return None;
+        }
+        self.toxic = toxic;
+        if !toxic {
+            let message = "Flow no longer toxic, allowing entries";
+            info!("{}", message);
+            return Some(Alert {
+                severity: Severity::Info,
+                message: message.to_owned(),
+                condition: Some(CONDITION.to_owned()),
+                resolved: true,
+            });
+        }
+        let message = match (self.vpin_toxic, markout) {
+            (true, _) => format!(
+                "VPIN at {:.2}, over the {} limit, blocking entries",
+                self.vpin.value().unwrap_or_default(),
+                self.config.max_vpin
+            ),
+            (false, markout) => format!(
+                "Fills averaging {:.2}bps against them after {}s, blocking entries for {}s",
+                -markout.unwrap_or_default(),
+                self.config.markout_secs,
+                self.config.cooldown_secs
+            ),
+        };
+        warn!("{}", message);
+        Some(Alert {
+            severity: Severity::Warning,
+            message,
+            condition: Some(CONDITION.to_owned()),
+            resolved: false,
+        })
+    }
+
+    pub fn permission(&self) -> Permission {
+        Permission {
+            entries: !self.toxic,
+            flatten: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000 + secs, 0).unwrap()
+    }
+
+    #[test]
+    fn test_vpin_buckets() {
+        let mut vpin = Vpin::new(10.0, 2);
+        vpin.on_trade(Side::Buy, 6.0);
+        vpin.on_trade(Side::Sell, 2.0);
+        assert_eq!(vpin.value(), None);
+        // Spills over: 2 fills the first bucket at 8 against 2, the rest starts the next
+        vpin.on_trade(Side::Buy, 14.0);
+        assert_eq!(vpin.value(), Some((0.6 + 1.0) / 2.0));
+        vpin.on_trade(Side::Sell, 10.0);
+        assert_eq!(vpin.value(), Some((1.0 + 0.6) / 2.0));
+        vpin.on_trade(Side::Buy, f64::NAN);
+        vpin.on_trade(Side::Buy, 5.0);
+        vpin.on_trade(Side::Sell, 5.0);
+        assert_eq!(vpin.value(), Some((0.6 + 0.0) / 2.0));
+    }
+
+    #[test]
+    fn test_stands_down_on_vpin() {
+        let mut guard = ToxicityGuard::new(&ToxicityConfig {
+            bucket_volume: 10.0,
+            buckets: 2,
+            ..ToxicityConfig::default()
+        });
+        guard.on_trade(Side::Buy, 20.0);
+        let alert = guard.update(100.0, at(0)).unwrap();
+        assert_eq!(alert.condition.as_deref(), Some(CONDITION));
+        assert!(!guard.permission().entries);
+
+        // Still toxic between the thresholds, allowed again below the lower
+        guard.on_trade(Side::Buy, 5.0);
+        guard.on_trade(Side::Sell, 5.0);
+        assert_eq!(guard.update(100.0, at(1)), None);
+        guard.on_trade(Side::Buy, 5.0);
+        guard.on_trade(Side::Sell, 5.0);
+        assert!(guard.update(100.0, at(2)).unwrap().resolved);
+        assert!(guard.permission().entries);
+    }
+
+    #[test]
+    fn test_stands_down_on_adverse_fills() {
+        let mut guard = ToxicityGuard::new(&ToxicityConfig {
+            markout_secs: 5,
+            fills: 2,
+            max_adverse_bps: Some(5.0),
+            cooldown_secs: 60,
+            ..ToxicityConfig::default()
+        });
+        // A buy followed by a rise, then two run over as the mid drops
+        guard.on_fill(Side::Buy, at(0), 100.0);
+        assert_eq!(guard.update(100.1, at(5)), None);
+        assert_eq!(guard.markout(), None);
+        guard.on_fill(Side::Buy, at(10), 100.0);
+        guard.on_fill(Side::Buy, at(10), 100.0);
+        assert_eq!(guard.update(100.0, at(14)), None);
+        let alert = guard.update(99.9, at(15)).unwrap();
+        assert_eq!(alert.severity, Severity::Warning);
+        assert!(!guard.permission().entries);
+
+        assert_eq!(guard.update(100.0, at(74)), None);
+        assert!(guard.update(100.0, at(75)).unwrap().resolved);
+        assert_eq!(guard.markout(), None);
+    }
+}
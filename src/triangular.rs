@@ -0,0 +1,253 @@
+//! Triangular arbitrage within one exchange, [`[triangular]`](crate::config::TriangularConfig).
+//! Each configured cycle of three currencies, such as USDT to BTC to ETH and back to USDT, is
+//! priced from the latest top of book of the pairs between them, in both directions: converting
+//! into a pair's base buys it at the ask, converting out of it sells at the bid, and every leg
+//! pays the taker `fee`. When a unit of the starting currency comes back as more than `1 +
+//! min_edge`, an alert announces the cycle, and another once it has closed.
+//!
+//! The spot books of every cycle's [`legs`] are subscribed to alongside the traded one, each
+//! cycle naming the quote currency of the other two first and the quote of the third second, as
+//! `["usdt", "btc", "eth"]` names BTC/USDT, ETH/USDT and ETH/BTC.
+//!
+//! Cycles are only detected, never traded: the trading state holds positions in one instrument,
+//! so trading the three legs needs an account holding all three currencies, which neither the
+//! paper account nor the order gateway keeps.
+
+use barter_integration::model::instrument::kind::InstrumentKind;
+use barter_integration::model::instrument::Instrument;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use tracing::info;
+
+use crate::config::TriangularConfig;
+use crate::event::Alert;
+use crate::event::Severity;
+use crate::features::Features;
+
+/// What every triangular arbitrage alert's [condition](Alert::condition) starts with, followed by
+/// the currencies of the cycle in the order they are converted.
+pub const CONDITION: &str = "triangular";
+
+/// Converting through three currencies and back for more than was started with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cycle {
+    /// The currencies in the order they are converted, starting and ending with the first.
+    pub path: [String; 3],
+    /// What the cycle makes per unit of the starting currency, after fees.
+    pub edge: f64,
+}
+
+impl Cycle {
+    fn condition(&self) -> String {
+        format!("{CONDITION}:{}", self.path.join(">"))
+    }
+}
+
+/// The spot pair of each leg of every cycle in `config`, each once.
+pub fn legs(config: &TriangularConfig) -> Vec<Instrument> {
+    let mut legs: Vec<Instrument> = Vec::new();
+    for [a, b, c] in &config.cycles {
+        for (base, quote) in [(b, a), (c, a), (c, b)] {
+            let leg = Instrument::from((base.as_str(), quote.as_str(), InstrumentKind::Spot));
+            if !legs.contains(&leg) {
+                legs.push(leg);
+            }
+        }
+    }
+    legs
+}
+
+#[derive(Debug)]
+pub struct TriangularScanner {
+    config: TriangularConfig,
+    /// The latest top of book of each pair, as base and quote.
+    quotes: HashMap<(String, String), (f64, f64)>,
+    /// The conditions of the cycles open.
+    open: HashSet<String>,
+}
+
+impl TriangularScanner {
+    pub fn new(config: &TriangularConfig) -> Self {
+        Self {
+            config: config.clone(),
+            quotes: HashMap::new(),
+            open: HashSet::new(),
+        }
+    }
+
+    /// How much of `to` one unit of `from` converts into, after the fee, if a pair between them
+    /// has been quoted.
+    fn rate(&self, from: &str, to: &str) -> Option<f64> {
+        let keep = 1.0 - self.config.fee;
+        if let Some(&(_, ask)) = self.quotes.get(&(to.to_owned(), from.to_owned())) {
+            return Some(keep / ask);
+        }
+        let &(bid, _) = self.quotes.get(&(from.to_owned(), to.to_owned()))?;
+        Some(keep * bid)
+    }
+
+    /// The cycle through `path`, if it makes at least `min_edge`.
+    fn cycle(&self, path: [&str; 3]) -> Option<Cycle> {
+        let [a, b, c] = path;
+        let edge = self.rate(a, b)? * self.rate(b, c)? * self.rate(c, a)? - 1.0;
+        (edge >= self.config.min_edge).then(|| Cycle {
+            path: path.map(str::to_owned),
+            edge,
+        })
+    }
+
+    /// Record the top of `instrument`'s book in `features`, returning every configured cycle,
+    /// either way round, that now makes at least `min_edge`.
+    pub fn cycles(&mut self, instrument: &Instrument, features: &Features) -> Vec<Cycle> {
+        let pair = (instrument.base.to_string(), instrument.quote.to_string());
+        self.quotes.insert(pair, (features.bid, features.ask));
+        self.config
+            .cycles
+            .iter()
+            .flat_map(|[a, b, c]| {
+                [
+                    self.cycle([a.as_str(), b.as_str(), c.as_str()]),
+                    self.cycle([a.as_str(), c.as_str(), b.as_str()]),
+                ]
+            })
+            .flatten()
+            .collect()
+    }
+
+    /// Price the cycles like [`cycles`](Self::cycles), returning the alerts to raise for those
+    /// that just opened or closed.
+    pub fn update(&mut self, instrument: &Instrument, features: &Features) -> Vec<Alert> {
+        let cycles = self.cycles(instrument, features);
+        let open: HashSet<String> = cycles.iter().map(Cycle::condition).collect();
+        let mut alerts = Vec::new();
+        let closed: Vec<String> = self.open.difference(&open).cloned().collect();
+        for condition in closed {
+            self.open.remove(&condition);
+            let message = format!("Triangular arbitrage closed: {condition}");
+            info!("{}", message);
+            alerts.push(Alert {
+                severity: Severity::Info,
+                message,
+                condition: Some(condition),
+                resolved: true,
+            });
+        }
+        for cycle in cycles {
+            let condition = cycle.condition();
+            if !self.open.insert(condition.clone()) {
+                continue;
+            }
+            let message = format!(
+                "Triangular arbitrage: {} and back makes {:.3}% after fees",
+                cycle.path.join(" to "),
+                cycle.edge * 100.0
+            );
+            info!("{}", message);
+            alerts.push(Alert {
+                severity: Severity::Info,
+                message,
+                condition: Some(condition),
+                resolved: false,
+            });
+        }
+        alerts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scanner() -> TriangularScanner {
+        TriangularScanner::new(&TriangularConfig {
+            fee: 0.001,
+            min_edge: 0.0,
+            cycles: vec![["usdt".to_owned(), "btc".to_owned(), "eth".to_owned()]],
+        })
+    }
+
+    fn pair(base: &str, quote: &str) -> Instrument {
+        Instrument::from((base, quote, InstrumentKind::Spot))
+    }
+
+    fn book(bid: f64, ask: f64) -> Features {
+        Features {
+            bid,
+            ask,
+            ..Features::default()
+        }
+    }
+
+    #[test]
+    fn test_legs() {
+        let config = TriangularConfig {
+            cycles: vec![
+                ["usdt".to_owned(), "btc".to_owned(), "eth".to_owned()],
+                ["usdt".to_owned(), "btc".to_owned(), "sol".to_owned()],
+            ],
+            ..TriangularConfig::default()
+        };
+        assert_eq!(
+            legs(&config),
+            [
+                pair("btc", "usdt"),
+                pair("eth", "usdt"),
+                pair("eth", "btc"),
+                pair("sol", "usdt"),
+                pair("sol", "btc"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_implied_cross() {
+        let mut scanner = scanner();
+        assert!(scanner
+            .cycles(&pair("btc", "usdt"), &book(59_990.0, 60_000.0))
+            .is_empty());
+        assert!(scanner
+            .cycles(&pair("eth", "usdt"), &book(3_000.0, 3_001.0))
+            .is_empty());
+        // ETH/BTC in line with the implied 0.05 leaves nothing after fees
+        assert!(scanner
+            .cycles(&pair("eth", "btc"), &book(0.04999, 0.05001))
+            .is_empty());
+
+        // ETH cheap in BTC: buy BTC, buy ETH with it, sell the ETH for USDT
+        let [cycle] = scanner
+            .cycles(&pair("eth", "btc"), &book(0.0489, 0.049))
+            .try_into()
+            .unwrap();
+        assert_eq!(cycle.path, ["usdt", "btc", "eth"]);
+        let edge = 0.999 / 60_000.0 * 0.999 / 0.049 * 0.999 * 3_000.0 - 1.0;
+        assert!((cycle.edge - edge).abs() < 1e-12);
+
+        // ETH dear in BTC goes the other way round
+        let [cycle] = scanner
+            .cycles(&pair("eth", "btc"), &book(0.051, 0.0511))
+            .try_into()
+            .unwrap();
+        assert_eq!(cycle.path, ["usdt", "eth", "btc"]);
+    }
+
+    #[test]
+    fn test_alerts_on_open_and_close() {
+        let mut scanner = scanner();
+        scanner.update(&pair("btc", "usdt"), &book(59_990.0, 60_000.0));
+        scanner.update(&pair("eth", "usdt"), &book(3_000.0, 3_001.0));
+        let [opened] = scanner
+            .update(&pair("eth", "btc"), &book(0.0489, 0.049))
+            .try_into()
+            .unwrap();
+        assert_eq!(opened.condition.as_deref(), Some("triangular:usdt>btc>eth"));
+        assert!(scanner
+            .update(&pair("eth", "btc"), &book(0.0489, 0.049))
+            .is_empty());
+        let [closed] = scanner
+            .update(&pair("eth", "btc"), &book(0.04999, 0.05001))
+            .try_into()
+            .unwrap();
+        assert!(closed.resolved);
+        assert_eq!(closed.condition, opened.condition);
+    }
+}
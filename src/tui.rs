@@ -0,0 +1,255 @@
+//! Live terminal dashboard: the top of book, the current imbalance features, open positions with
+//! their unrealized PnL, the latest trades and a sparkline of equity.
+//!
+//! The dashboard takes over the terminal while it runs, so logs go to a file instead. Press `q`
+//! or Ctrl-C to stop trading.
+
+use barter_integration::model::Side;
+use ratatui::backend::CrosstermBackend;
+use ratatui::crossterm::event;
+use ratatui::crossterm::event::Event as TerminalEvent;
+use ratatui::crossterm::event::KeyCode;
+use ratatui::crossterm::event::KeyEventKind;
+use ratatui::crossterm::event::KeyModifiers;
+use ratatui::crossterm::execute;
+use ratatui::crossterm::terminal;
+use ratatui::crossterm::terminal::EnterAlternateScreen;
+use ratatui::crossterm::terminal::LeaveAlternateScreen;
+use ratatui::layout::Constraint;
+use ratatui::layout::Layout;
+use ratatui::layout::Rect;
+use ratatui::style::Color;
+use ratatui::style::Style;
+use ratatui::text::Line;
+use ratatui::widgets::Block;
+use ratatui::widgets::Paragraph;
+use ratatui::widgets::Row;
+use ratatui::widgets::Sparkline;
+use ratatui::widgets::Table;
+use ratatui::Frame;
+use ratatui::Terminal;
+use std::io::Stdout;
+use std::time::Duration;
+
+use crate::live::LiveState;
+use crate::live::Position;
+use crate::live::RECENT_TRADES;
+
+/// Equity history scaled from its low to its high, for the sparkline.
+fn sparkline(live: &LiveState) -> Vec<u64> {
+    let low = live.equity().fold(f64::MAX, f64::min);
+    let high = live.equity().fold(f64::MIN, f64::max);
+    let range = (high - low).max(f64::EPSILON);
+    live.equity()
+        .map(|equity| ((equity - low) / range * 100.0).round() as u64 + 1)
+        .collect()
+}
+
+/// Draw the dashboard for `live` over the whole of `frame`.
+pub fn draw(frame: &mut Frame, live: &LiveState) {
+    let [header, positions, trades, equity] = Layout::vertical([
+        Constraint::Length(4),
+        Constraint::Min(5),
+        Constraint::Length(RECENT_TRADES as u16 + 3),
+        Constraint::Length(6),
+    ])
+    .areas(frame.size());
+    let [book, signals] =
+        Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(header);
+
+    let symbol = live.symbol().unwrap_or("waiting for the book");
+    let features = live.features().copied().unwrap_or_default();
+    let book_lines = vec![
+        Line::from(format!("bid {:.2}  ask {:.2}", features.bid, features.ask)),
+        Line::from(format!(
+            "mid {:.2}  spread {:.4}",
+            features.mid_price, features.spread
+        )),
+    ];
+    frame.render_widget(
+        Paragraph::new(book_lines).block(Block::bordered().title(format!(" {symbol} "))),
+        book,
+    );
+    let signal_lines = vec![
+        Line::from(format!("VOI {:.4}  OIR {:.4}", features.voi, features.oir)),
+        Line::from(format!(
+            "MPB {:.4}  microprice {:.2}",
+            features.mpb, features.microprice
+        )),
+    ];
+    frame.render_widget(
+        Paragraph::new(signal_lines).block(Block::bordered().title(" Features ")),
+        signals,
+    );
+
+    draw_positions(frame, positions, live);
+    draw_trades(frame, trades, live);
+
+    let latest = live.equity().last().unwrap_or_default();
+    frame.render_widget(
+        Sparkline::default()
+            .block(Block::bordered().title(format!(" Equity {latest:.2} ")))
+            .data(&sparkline(live))
+            .style(Style::default().fg(Color::Cyan)),
+        equity,
+    );
+}
+
+fn draw_positions(frame: &mut Frame, area: Rect, live: &LiveState) {
+    let rows = live.positions().into_iter().map(|position| {
+        let Position {
+            strategy,
+            entry,
+            unrealized_pnl: pnl,
+        } = position;
+        let color = if pnl >= 0.0 { Color::Green } else { Color::Red };
+        Row::new(vec![
+            strategy,
+            format!("{:.2}", entry.price),
+            format!("{}", entry.size),
+            format!("{pnl:.4}"),
+            format!("{:.3}", pnl / (entry.price * entry.size) * 100.0),
+        ])
+        .style(Style::default().fg(color))
+    });
+    let widths = [
+        Constraint::Fill(1),
+        Constraint::Length(12),
+        Constraint::Length(10),
+        Constraint::Length(12),
+        Constraint::Length(8),
+    ];
+    let table = Table::new(rows, widths)
+        .header(Row::new(["strategy", "entry", "size", "unrealized", "%"]))
+        .block(Block::bordered().title(" Open positions "));
+    frame.render_widget(table, area);
+}
+
+fn draw_trades(frame: &mut Frame, area: Rect, live: &LiveState) {
+    let rows = live.trades().map(|trade| {
+        let color = match trade.fill.side {
+            Side::Buy => Color::Green,
+            Side::Sell => Color::Red,
+        };
+        Row::new(vec![
+            trade.time.format("%H:%M:%S").to_string(),
+            trade.strategy.clone(),
+            trade.fill.side.to_string(),
+            format!("{:.2}", trade.fill.price),
+            format!("{}", trade.fill.size),
+            format!("{:.4}", trade.fill.fee),
+        ])
+        .style(Style::default().fg(color))
+    });
+    let widths = [
+        Constraint::Length(10),
+        Constraint::Fill(1),
+        Constraint::Length(6),
+        Constraint::Length(12),
+        Constraint::Length(10),
+        Constraint::Length(10),
+    ];
+    let table = Table::new(rows, widths)
+        .header(Row::new([
+            "time", "strategy", "side", "price", "size", "fee",
+        ]))
+        .block(Block::bordered().title(" Recent trades "));
+    frame.render_widget(table, area);
+}
+
+/// The terminal, switched to the dashboard's alternate screen until dropped.
+pub struct Tui {
+    terminal: Terminal<CrosstermBackend<Stdout>>,
+}
+
+impl Tui {
+    pub fn enter() -> std::io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        let mut stdout = std::io::stdout();
+        execute!(stdout, EnterAlternateScreen)?;
+        let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+        terminal.clear()?;
+        Ok(Self { terminal })
+    }
+
+    /// Whether `q` or Ctrl-C was pressed since the last call. Never waits for input.
+    pub fn quit_requested(&self) -> std::io::Result<bool> {
+        while event::poll(Duration::ZERO)? {
+            if let TerminalEvent::Key(key) = event::read()? {
+                let ctrl_c =
+                    key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+                if key.kind == KeyEventKind::Press && (key.code == KeyCode::Char('q') || ctrl_c) {
+                    return Ok(true);
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    pub fn draw(&mut self, live: &LiveState) -> std::io::Result<()> {
+        self.terminal.draw(|frame| draw(frame, live))?;
+        Ok(())
+    }
+}
+
+impl Drop for Tui {
+    fn drop(&mut self) {
+        let _ = terminal::disable_raw_mode();
+        let _ = execute!(std::io::stdout(), LeaveAlternateScreen);
+        let _ = self.terminal.show_cursor();
+    }
+}
+
+impl std::fmt::Debug for Tui {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Tui").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use ratatui::backend::TestBackend;
+
+    use super::*;
+    use crate::event::Event;
+    use crate::features::Features;
+
+    #[test]
+    fn test_draw_dashboard() {
+        let mut live = LiveState::default();
+        let features = Features {
+            bid: 104.0,
+            ask: 104.5,
+            voi: 2.5,
+            ..Features::default()
+        };
+        live.record(&Event::Features {
+            time: Utc::now(),
+            symbol: "BTC/USDT",
+            features,
+            book_time: None,
+        });
+        for portfolio_value in [1000.0, 1010.0] {
+            live.record(&Event::Equity {
+                time: Utc::now(),
+                symbol: "BTC/USDT",
+                portfolio_value,
+            });
+        }
+        assert_eq!(sparkline(&live), [1, 101]);
+
+        let mut terminal = Terminal::new(TestBackend::new(100, 40)).unwrap();
+        terminal.draw(|frame| draw(frame, &live)).unwrap();
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+        assert!(screen.contains("BTC/USDT"));
+        assert!(screen.contains("VOI 2.5000"));
+        assert!(screen.contains("Equity 1010.00"));
+    }
+}
@@ -0,0 +1,282 @@
+//! Conditional volatility forecasting, [`[volatility]`](crate::config::VolatilityConfig). The
+//! traded mid is sampled into bars of `bar_secs`, and each bar's log return updates a GARCH(1,1)
+//! forecast of the next bar's variance:
+//!
+//! σ²ₜ₊₁ = ω + α rₜ² + β σ²ₜ
+//!
+//! with ω set by variance targeting, so the forecast reverts to the long run variance of every
+//! bar seen so far. A burst of large returns lifts the forecast straight away and it decays back
+//! as calm bars follow, so stops and sizes adjust as volatility builds rather than once a
+//! trailing window has caught up with it.
+//!
+//! Once `min_bars` returns are in, the forecast against the long run level widens the stop loss
+//! by that ratio, and caps how many lots each strategy holds at `max_lots` divided by it, so
+//! positions shrink as volatility expands. Below the long run level the stop tightens, though
+//! the cap never rises above `max_lots`.
+//!
+//! Exits can also be set in units of the forecast itself, scaled to the holding horizon of
+//! `horizon_secs` by the square root of time: `take_profit_sigmas = 1.5` takes profit 1.5σ up.
+//! Either left out stays a percent of the entry, the stop scaled as above. The horizon forecast
+//! is also the [`volatility`](crate::features::Features::volatility) feature, which the
+//! imbalance strategy's `spread_sigmas` measures the spread threshold in.
+
+use chrono::DateTime;
+use chrono::TimeDelta;
+use chrono::Utc;
+use tracing::info;
+
+use crate::config::VolatilityConfig;
+use crate::features::Features;
+use crate::RiskParams;
+
+#[derive(Debug)]
+pub struct VolatilityForecaster {
+    config: VolatilityConfig,
+    /// When the current bar opened, and the close of the one before it.
+    bar: Option<(DateTime<Utc>, f64)>,
+    /// The latest mid, closing the current bar once it ends.
+    close: f64,
+    bars: usize,
+    /// Sum of every bar's squared return so far.
+    squares: f64,
+    /// The forecast variance of the next bar's return.
+    variance: Option<f64>,
+    lots: Option<usize>,
+}
+
+impl VolatilityForecaster {
+    pub fn new(config: &VolatilityConfig) -> Self {
+        Self {
+            config: config.clone(),
+            bar: None,
+            close: 0.0,
+            bars: 0,
+            squares: 0.0,
+            variance: None,
+            lots: None,
+        }
+    }
+
+    /// The mean squared bar return so far, the variance the forecast reverts to.
+    pub fn long_run(&self) -> Option<f64> {
+        (self.bars > 0).then(|| self.squares / self.bars as f64)
+    }
+
+    /// The forecast standard deviation of the next bar's log return, once `min_bars` are in.
+    pub fn forecast(&self) -> Option<f64> {
+        if self.bars < self.config.min_bars.max(1) {
+            return None;
+        }
+        self.variance.map(f64::sqrt)
+    }
+
+    /// How many times the long run volatility the forecast is, within `max_scale` either way.
+    pub fn ratio(&self) -> Option<f64> {
+        let long_run = self.long_run()?.sqrt();
+        if long_run <= 0.0 {
+            return None;
+        }
+        let max = self.config.max_scale.max(1.0);
+        Some((self.forecast()? / long_run).clamp(1.0 / max, max))
+    }
+
+    /// The forecast standard deviation of the log return over `horizon_secs`, once `min_bars`
+    /// are in.
+    pub fn horizon_forecast(&self) -> Option<f64> {
+        let bars = self.config.horizon_secs as f64 / self.config.bar_secs.max(1) as f64;
+        Some(self.forecast()? * bars.sqrt())
+    }
+
+    /// `risk` with the stop loss scaled to the forecast, and either exit set in sigmas of it as
+    /// configured, as it is until there is one.
+    pub fn risk(&self, risk: RiskParams) -> RiskParams {
+        let Some(ratio) = self.ratio() else {
+            return risk;
+        };
+        let mut risk = RiskParams {
+            stop_loss: (risk.stop_loss * ratio).min(0.99),
+            ..risk
+        };
+        if let Some(sigma) = self.horizon_forecast() {
+            if let Some(sigmas) = self.config.take_profit_sigmas {
+                risk.take_profit = (sigmas * sigma).min(0.99);
+            }
+            if let Some(sigmas) = self.config.stop_loss_sigmas {
+                risk.stop_loss = (sigmas * sigma).min(0.99);
+            }
+        }
+        risk
+    }
+
+    /// The most lots each strategy should hold on the forecast, or no cap until there is one.
+    pub fn max_lots(&self) -> Option<usize> {
+        self.lots
+    }
+
+    /// Sample the mid in `features` at `now`, updating the forecast as each bar closes.
+    pub fn update(&mut self, features: &Features, now: DateTime<Utc>) {
+        let mid_price = features.mid_price;
+        if !mid_price.is_finite() || mid_price <= 0.0 {
+            return;
+        }
+        let Some((opened, previous)) = self.bar else {
+            self.bar = Some((now, mid_price));
+            self.close = mid_price;
+            return;
+        };
+        let length = TimeDelta::seconds(self.config.bar_secs as i64);
+        if now - opened < length {
+            self.close = mid_price;
+            return;
+        }
+        // The bar closes on the last mid within it, the new one opening with this mid
+        let close = self.close;
+        self.bar = Some((now, close));
+        self.close = mid_price;
+        self.observe((close / previous).ln());
+    }
+
+    /// Update the forecast with a bar's log return `r`.
+    fn observe(&mut self, r: f64) {
+        let square = r * r;
+        self.bars += 1;
+        self.squares += square;
+        let long_run = self.squares / self.bars as f64;
+        let (alpha, beta) = (self.config.alpha, self.config.beta);
+        let omega = (1.0 - alpha - beta).max(0.0) * long_run;
+        self.variance = Some(match self.variance {
+            Some(variance) => omega + alpha * square + beta * variance,
+            None => square,
+        });
+
+        let lots = self.ratio().map(|ratio| {
+            let max = self.config.max_lots.max(1);
+            ((max as f64 / ratio.max(1.0)).round() as usize).clamp(1, max)
+        });
+        if lots != self.lots {
+            if let (Some(lots), Some(forecast)) = (lots, self.forecast()) {
+                info!(
+                    "Forecast volatility {:.4}% a bar, holding at most {} lots",
+                    forecast * 100.0,
+                    lots
+                );
+            }
+            self.lots = lots;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    fn config() -> VolatilityConfig {
+        VolatilityConfig {
+            bar_secs: 60,
+            alpha: 0.1,
+            beta: 0.85,
+            min_bars: 30,
+            max_lots: 4,
+            max_scale: 3.0,
+            horizon_secs: 60,
+            take_profit_sigmas: None,
+            stop_loss_sigmas: None,
+        }
+    }
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000 + secs, 0).unwrap()
+    }
+
+    fn book(mid_price: f64) -> Features {
+        Features {
+            mid_price,
+            ..Features::default()
+        }
+    }
+
+    /// Bars of returns `scale` wide, one book a second, from `start` seconds on.
+    fn bars(forecaster: &mut VolatilityForecaster, rng: &mut Rng, start: i64, n: i64, scale: f64) {
+        let mut mid = forecaster.close.max(100.0);
+        for secs in start..start + n * 60 {
+            if secs % 60 == 0 {
+                mid *= (scale * rng.centred()).exp();
+            }
+            forecaster.update(&book(mid), at(secs));
+        }
+    }
+
+    #[test]
+    fn test_bars_close_on_the_last_mid() {
+        let mut forecaster = VolatilityForecaster::new(&VolatilityConfig {
+            min_bars: 1,
+            ..config()
+        });
+        forecaster.update(&book(100.0), at(0));
+        forecaster.update(&book(101.0), at(30));
+        forecaster.update(&book(f64::NAN), at(45));
+        assert_eq!(forecaster.forecast(), None);
+        // The first bar closes on its last mid, 101, as 90 starts the second
+        forecaster.update(&book(90.0), at(60));
+        let r = (101.0f64 / 100.0).ln();
+        assert!((forecaster.forecast().unwrap() - r.abs()).abs() < 1e-12);
+        assert!((forecaster.long_run().unwrap() - r * r).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_expansion_widens_stops_and_shrinks_lots() {
+        let mut forecaster = VolatilityForecaster::new(&config());
+        let risk = RiskParams {
+            take_profit: 0.01,
+            stop_loss: 0.02,
+        };
+        let mut rng = Rng::new(11);
+        bars(&mut forecaster, &mut rng, 0, 20, 0.001);
+        assert_eq!((forecaster.risk(risk), forecaster.max_lots()), (risk, None));
+
+        bars(&mut forecaster, &mut rng, 1200, 200, 0.001);
+        let calm = forecaster.forecast().unwrap();
+        assert_eq!(forecaster.max_lots(), Some(4));
+
+        // A few violent bars lift the forecast well before the long run level catches up
+        bars(&mut forecaster, &mut rng, 13_200, 5, 0.01);
+        let ratio = forecaster.ratio().unwrap();
+        assert!(forecaster.forecast().unwrap() > 2.0 * calm);
+        assert!(ratio > 2.0);
+        let widened = forecaster.risk(risk);
+        assert_eq!(widened.take_profit, 0.01);
+        assert!((widened.stop_loss - 0.02 * ratio).abs() < 1e-12);
+        assert!(forecaster.max_lots().unwrap() < 4);
+
+        // And it decays back once calm returns
+        bars(&mut forecaster, &mut rng, 13_500, 200, 0.001);
+        assert!(forecaster.ratio().unwrap() < 1.5);
+    }
+
+    #[test]
+    fn test_exits_in_sigmas() {
+        let mut forecaster = VolatilityForecaster::new(&VolatilityConfig {
+            horizon_secs: 240,
+            take_profit_sigmas: Some(1.5),
+            ..config()
+        });
+        let risk = RiskParams {
+            take_profit: 0.01,
+            stop_loss: 0.02,
+        };
+        let mut rng = Rng::new(7);
+        bars(&mut forecaster, &mut rng, 0, 20, 0.001);
+        assert_eq!(forecaster.horizon_forecast(), None);
+        assert_eq!(forecaster.risk(risk), risk);
+
+        // Four bars to the horizon, twice a bar's volatility
+        bars(&mut forecaster, &mut rng, 1200, 100, 0.001);
+        let sigma = forecaster.horizon_forecast().unwrap();
+        assert!((sigma - 2.0 * forecaster.forecast().unwrap()).abs() < 1e-15);
+        let scaled = forecaster.risk(risk);
+        assert!((scaled.take_profit - 1.5 * sigma).abs() < 1e-15);
+        // The stop is still a percent, scaled by the ratio
+        assert!((scaled.stop_loss - 0.02 * forecaster.ratio().unwrap()).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,437 @@
+//! Append-only event log: every market event consumed, signal produced and execution result is
+//! written as one JSON line before it is published, so the accounting can be audited and the
+//! trading state re-derived from scratch with [`rebuild`].
+//!
+//! Every line carries a sequence number, continuing across [truncations](WriteAheadLog::truncate),
+//! so a [snapshot](crate::snapshot) can record the last entry it covers and [`replay`] the rest.
+
+use chrono::DateTime;
+use chrono::Utc;
+use serde::Deserialize;
+use serde::Serialize;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::BufRead;
+use std::io::BufWriter;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
+use std::path::Path;
+use tracing::warn;
+
+use crate::allocation::Allocator;
+use crate::event::Event;
+use crate::Fill;
+
+#[derive(Debug, thiserror::Error)]
+pub enum WalError {
+    #[error("event log error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("corrupt event log entry on line {line}: {error}")]
+    Corrupt {
+        line: usize,
+        error: serde_json::Error,
+    },
+
+    #[error("event log line {line} is for strategy {strategy}, which is not configured")]
+    UnknownStrategy { line: usize, strategy: String },
+}
+
+#[derive(Debug)]
+pub struct WriteAheadLog {
+    writer: BufWriter<File>,
+    /// Sequence number of the last entry written.
+    sequence: u64,
+}
+
+impl WriteAheadLog {
+    /// Open the log at `path` for appending, creating it if needed. Entries are numbered on from
+    /// the last one in the log or `after`, whichever is later.
+    ///
+    /// A torn final line left by a crash mid-write is cut off, so new entries start on a line of
+    /// their own.
+    pub fn open(path: impl AsRef<Path>, after: u64) -> Result<Self, WalError> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(path)?;
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)?;
+        let end = contents
+            .iter()
+            .rposition(|&byte| byte == b'\n')
+            .map_or(0, |newline| newline + 1);
+        if end < contents.len() {
+            warn!(
+                "Cutting off {} bytes of torn event log entry",
+                contents.len() - end
+            );
+            file.set_len(end as u64)?;
+            file.sync_data()?;
+        }
+
+        let complete = &contents[..end];
+        let last = match complete
+            .strip_suffix(b"\n")
+            .and_then(|lines| lines.rsplit(|&byte| byte == b'\n').next())
+        {
+            Some(line) => {
+                let sequenced: Sequenced =
+                    serde_json::from_slice(line).map_err(|error| WalError::Corrupt {
+                        line: complete.iter().filter(|&&byte| byte == b'\n').count(),
+                        error,
+                    })?;
+                sequenced.seq
+            }
+            None => 0,
+        };
+        Ok(Self {
+            writer: BufWriter::new(file),
+            sequence: last.max(after),
+        })
+    }
+
+    /// Sequence number of the last entry written, or of the entry it was opened after.
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Append `events` and sync them to disk.
+    pub fn append(&mut self, events: &[Event]) -> Result<(), WalError> {
+        for event in events {
+            let record = Record {
+                seq: self.sequence + 1,
+                event,
+            };
+            serde_json::to_writer(&mut self.writer, &record)
+                .map_err(|error| WalError::Io(error.into()))?;
+            self.writer.write_all(b"\n")?;
+            self.sequence += 1;
+        }
+        self.writer.flush()?;
+        self.writer.get_ref().sync_data()?;
+        Ok(())
+    }
+
+    /// Drop every entry written so far, once a snapshot covers them. Numbering carries on.
+    pub fn truncate(&mut self) -> Result<(), WalError> {
+        self.writer.flush()?;
+        let file = self.writer.get_mut();
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        file.sync_all()?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Record<'a> {
+    seq: u64,
+    #[serde(flatten)]
+    event: &'a Event,
+}
+
+#[derive(Debug, Deserialize)]
+struct Sequenced {
+    seq: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct Logged {
+    seq: u64,
+    #[serde(flatten)]
+    entry: Entry,
+}
+
+/// The parts of a logged event that change the trading state.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Entry {
+    Fill {
+        strategy: String,
+        #[serde(default)]
+        sleeve: Option<usize>,
+        #[serde(flatten)]
+        fill: Fill,
+    },
+    Rebalance {
+        time: DateTime<Utc>,
+        strategy: String,
+        #[serde(default)]
+        sleeve: Option<usize>,
+        weight: f64,
+        capital: f64,
+        transfer: f64,
+    },
+    #[serde(other)]
+    Other,
+}
+
+/// Replay the fills and rebalances in `log` onto a freshly built `allocator`, returning the
+/// number of entries read.
+pub fn rebuild(allocator: &mut Allocator, log: impl BufRead) -> Result<usize, WalError> {
+    replay(allocator, log, 0)
+}
+
+/// Replay the fills and rebalances in `log` numbered after `after` onto `allocator`, returning
+/// the number of entries replayed.
+///
+/// A final line that fails to parse was torn by a crash mid-write, so it is skipped with a
+/// warning rather than failing the replay.
+pub fn replay(allocator: &mut Allocator, log: impl BufRead, after: u64) -> Result<usize, WalError> {
+    let mut lines = log.lines().enumerate().peekable();
+    let mut previous = after;
+    let mut entries = 0;
+    while let Some((index, line)) = lines.next() {
+        let line = line?;
+        let number = index + 1;
+        let logged: Logged = match serde_json::from_str(&line) {
+            Ok(logged) => logged,
+            Err(error) if lines.peek().is_none() => {
+                warn!("Skipping torn event log line {}: {}", number, error);
+                break;
+            }
+            Err(error) => {
+                return Err(WalError::Corrupt {
+                    line: number,
+                    error,
+                })
+            }
+        };
+
+        if logged.seq <= after {
+            continue;
+        }
+        if logged.seq > previous + 1 {
+            warn!(
+                "Event log skips from entry {} to {}, the state may be missing changes",
+                previous, logged.seq
+            );
+        }
+        previous = logged.seq;
+
+        let unknown = match logged.entry {
+            Entry::Fill {
+                strategy,
+                sleeve,
+                fill,
+            } => (!allocator.replay_fill(sleeve, &strategy, &fill)).then_some(strategy),
+            Entry::Rebalance {
+                time,
+                strategy,
+                sleeve,
+                weight,
+                capital,
+                transfer,
+            } => (!allocator.replay_rebalance(time, sleeve, &strategy, weight, capital, transfer))
+                .then_some(strategy),
+            Entry::Other => None,
+        };
+        if let Some(strategy) = unknown {
+            return Err(WalError::UnknownStrategy {
+                line: number,
+                strategy,
+            });
+        }
+        entries += 1;
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use std::io::BufReader;
+    use std::time::SystemTime;
+
+    use super::*;
+    use crate::config::AllocationConfig;
+    use crate::features::Features;
+    use crate::strategy::Signal;
+    use crate::strategy::Strategy;
+    use crate::TradingState;
+
+    /// Buys when flat and sells when holding.
+    #[derive(Debug)]
+    struct Flip;
+
+    impl Strategy for Flip {
+        fn name(&self) -> &str {
+            "flip"
+        }
+
+        fn evaluate(&mut self, _features: &Features, state: &TradingState) -> Signal {
+            if state.positions.is_empty() {
+                Signal::Buy
+            } else {
+                Signal::Sell
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    struct Idle;
+
+    impl Strategy for Idle {
+        fn name(&self) -> &str {
+            "idle"
+        }
+
+        fn evaluate(&mut self, _features: &Features, _state: &TradingState) -> Signal {
+            Signal::Hold
+        }
+    }
+
+    fn temp_file(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!(
+            "fit-{}-{}-{}",
+            name,
+            std::process::id(),
+            SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ))
+    }
+
+    fn allocator(start: DateTime<Utc>) -> Allocator {
+        let members: Vec<(Box<dyn Strategy>, f64)> =
+            vec![(Box::new(Flip), 1.0), (Box::new(Idle), 1.0)];
+        let config = AllocationConfig {
+            independent: true,
+            rebalance_interval_secs: Some(60),
+            ..AllocationConfig::default()
+        };
+        Allocator::new(1000.0, "BTC/USDT", members, config, start)
+    }
+
+    /// Trade `ticks` through `running`, 30 seconds apart, logging every event to `wal`.
+    fn trade(
+        running: &mut Allocator,
+        wal: &mut WriteAheadLog,
+        start: DateTime<Utc>,
+        ticks: std::ops::Range<i64>,
+    ) {
+        for tick in ticks {
+            let features = Features {
+                bid: 100.0 + tick as f64,
+                ask: 100.01 + tick as f64,
+                ..Features::default()
+            };
+            let now = start + Duration::seconds(30 * tick);
+            let events = running.on_features(&features, now);
+            wal.append(&events).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_rebuild_matches_running_state() {
+        let path = temp_file("wal");
+        let start = Utc::now();
+        let mut running = allocator(start);
+        let mut wal = WriteAheadLog::open(&path, 0).unwrap();
+        trade(&mut running, &mut wal, start, 0..5);
+        drop(wal);
+
+        let mut rebuilt = allocator(start);
+        let log = BufReader::new(File::open(&path).unwrap());
+        assert!(rebuild(&mut rebuilt, log).unwrap() > 0);
+        assert_eq!(rebuilt.snapshot(), running.snapshot());
+
+        // A crash mid-write leaves a torn final line, which is skipped
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"{\"type\":\"fi")
+            .unwrap();
+        let mut rebuilt = allocator(start);
+        let log = BufReader::new(File::open(&path).unwrap());
+        rebuild(&mut rebuilt, log).unwrap();
+        assert_eq!(rebuilt.snapshot(), running.snapshot());
+
+        let mut unknown = Allocator::new(
+            1000.0,
+            "BTC/USDT",
+            vec![(Box::new(Idle), 1.0)],
+            AllocationConfig::default(),
+            start,
+        );
+        let log = BufReader::new(File::open(&path).unwrap());
+        assert!(matches!(
+            rebuild(&mut unknown, log),
+            Err(WalError::UnknownStrategy { strategy, .. }) if strategy == "flip"
+        ));
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_rebuild_members_running_the_same_strategy() {
+        let path = temp_file("wal-members");
+        let start = Utc::now();
+        let members = || -> Vec<(Box<dyn Strategy>, f64)> {
+            vec![(Box::new(Flip), 1.0), (Box::new(Flip), 3.0)]
+        };
+        let config = AllocationConfig {
+            independent: true,
+            rebalance_interval_secs: Some(60),
+            ..AllocationConfig::default()
+        };
+        let mut running = Allocator::new(1000.0, "BTC/USDT", members(), config.clone(), start);
+        let mut wal = WriteAheadLog::open(&path, 0).unwrap();
+        trade(&mut running, &mut wal, start, 0..5);
+        drop(wal);
+
+        // Each sleeve's fills and rebalances go back to it, not the first running the strategy
+        let mut rebuilt = Allocator::new(1000.0, "BTC/USDT", members(), config, start);
+        let log = BufReader::new(File::open(&path).unwrap());
+        rebuild(&mut rebuilt, log).unwrap();
+        assert_eq!(rebuilt.snapshot(), running.snapshot());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_replay_tail_after_truncate() {
+        let path = temp_file("wal-tail");
+        let start = Utc::now();
+        let mut running = allocator(start);
+        let mut wal = WriteAheadLog::open(&path, 0).unwrap();
+        trade(&mut running, &mut wal, start, 0..3);
+        let sequence = wal.sequence();
+        let snapshot = running.snapshot();
+        wal.truncate().unwrap();
+        trade(&mut running, &mut wal, start, 3..5);
+        drop(wal);
+
+        // Numbering carries on past a torn line when the log is reopened
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"{\"seq\":")
+            .unwrap();
+        let reopened = WriteAheadLog::open(&path, sequence).unwrap();
+        assert!(reopened.sequence() > sequence);
+        let written = reopened.sequence();
+        drop(reopened);
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(contents.ends_with('\n'));
+        assert_eq!(WriteAheadLog::open(&path, 0).unwrap().sequence(), written);
+
+        let mut resumed = allocator(start);
+        resumed.restore(&snapshot).unwrap();
+        let log = BufReader::new(File::open(&path).unwrap());
+        assert_eq!(
+            replay(&mut resumed, log, sequence).unwrap() as u64,
+            written - sequence
+        );
+        assert_eq!(resumed.snapshot(), running.snapshot());
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,101 @@
+//! Feed watchdog: tracks the time since each subscription last delivered an update, so a feed
+//! that goes quiet is noticed and resubscribed instead of waited on forever.
+
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::Instant;
+
+#[derive(Debug)]
+pub struct Watchdog {
+    silence: Duration,
+    last_heard: HashMap<String, Instant>,
+}
+
+impl Watchdog {
+    /// Watch `subscriptions`, allowing each up to `silence` between updates, starting from `now`.
+    pub fn new(
+        silence: Duration,
+        subscriptions: impl IntoIterator<Item = String>,
+        now: Instant,
+    ) -> Self {
+        Self {
+            silence,
+            last_heard: subscriptions
+                .into_iter()
+                .map(|subscription| (subscription, now))
+                .collect(),
+        }
+    }
+
+    pub fn heard(&mut self, subscription: String, now: Instant) {
+        self.last_heard.insert(subscription, now);
+    }
+
+    /// When the quietest subscription will have been silent too long, unless it is heard from.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.last_heard
+            .values()
+            .min()
+            .map(|last_heard| *last_heard + self.silence)
+    }
+
+    /// Wait until a subscription has been silent too long. Never resolves without any.
+    pub async fn expired(&self) {
+        match self.deadline() {
+            Some(deadline) => tokio::time::sleep_until(deadline).await,
+            None => std::future::pending().await,
+        }
+    }
+
+    /// Every subscription silent too long at `now` and for how long, by name.
+    pub fn silent(&self, now: Instant) -> Vec<(&str, Duration)> {
+        let mut silent: Vec<_> = self
+            .last_heard
+            .iter()
+            .map(|(subscription, last_heard)| (subscription.as_str(), now - *last_heard))
+            .filter(|(_, quiet)| *quiet >= self.silence)
+            .collect();
+        silent.sort();
+        silent
+    }
+
+    /// Start every subscription's clock over from `now`, as after resubscribing.
+    pub fn reset(&mut self, now: Instant) {
+        for last_heard in self.last_heard.values_mut() {
+            *last_heard = now;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reports_silent_subscriptions() {
+        let start = Instant::now();
+        let seconds = |secs| start + Duration::from_secs(secs);
+        let mut watchdog = Watchdog::new(
+            Duration::from_secs(30),
+            ["aevo btc".to_owned(), "aevo eth".to_owned()],
+            start,
+        );
+        assert_eq!(watchdog.deadline(), Some(seconds(30)));
+
+        watchdog.heard("aevo btc".to_owned(), seconds(20));
+        assert!(watchdog.silent(seconds(29)).is_empty());
+        assert_eq!(watchdog.deadline(), Some(seconds(30)));
+        assert_eq!(
+            watchdog.silent(seconds(35)),
+            [("aevo eth", Duration::from_secs(35))]
+        );
+        assert_eq!(watchdog.silent(seconds(50)).len(), 2);
+
+        watchdog.reset(seconds(50));
+        assert!(watchdog.silent(seconds(60)).is_empty());
+        assert_eq!(watchdog.deadline(), Some(seconds(80)));
+
+        let empty = Watchdog::new(Duration::from_secs(30), [], start);
+        assert_eq!(empty.deadline(), None);
+    }
+}
@@ -0,0 +1,99 @@
+// Renders /api/state, refreshed whenever the engine pushes an event over /api/events.
+
+const $ = (id) => document.getElementById(id);
+
+// The API token, from the page's #token=... fragment or asked for once per browser session
+function token() {
+  const fragment = new URLSearchParams(location.hash.slice(1)).get("token");
+  if (fragment) {
+    sessionStorage.setItem("token", fragment);
+    history.replaceState(null, "", location.pathname);
+  }
+  if (!sessionStorage.getItem("token")) {
+    sessionStorage.setItem("token", prompt("API token") ?? "");
+  }
+  return sessionStorage.getItem("token");
+}
+
+async function api(path, options = {}) {
+  const response = await fetch(path, { ...options, headers: { Authorization: `Bearer ${token()}` } });
+  if (response.status === 401) sessionStorage.removeItem("token");
+  if (!response.ok) throw new Error(`${path}: ${response.status}`);
+  return response;
+}
+
+function cells(values) {
+  return values.map((value) => `<td>${value}</td>`).join("");
+}
+
+function drawCurve(history) {
+  const canvas = $("curve");
+  const context = canvas.getContext("2d");
+  context.clearRect(0, 0, canvas.width, canvas.height);
+  if (history.length < 2) return;
+  const low = Math.min(...history);
+  const range = Math.max(...history) - low || 1;
+  context.beginPath();
+  history.forEach((equity, index) => {
+    const x = (index / (history.length - 1)) * canvas.width;
+    const y = canvas.height - ((equity - low) / range) * (canvas.height - 4) - 2;
+    index === 0 ? context.moveTo(x, y) : context.lineTo(x, y);
+  });
+  context.strokeStyle = "#0969da";
+  context.stroke();
+}
+
+function render(state) {
+  $("symbol").textContent = state.symbol ?? "waiting for the book";
+  $("status").textContent = state.paused ? "paused" : "trading";
+  $("status").className = state.paused ? "paused" : "";
+  $("equity").textContent = state.equity == null ? "" : state.equity.toFixed(2);
+  drawCurve(state.equity_history);
+
+  const f = state.features;
+  $("features").innerHTML = f
+    ? cells([f.bid.toFixed(2), f.ask.toFixed(2), f.spread.toFixed(4), f.voi.toFixed(4), f.oir.toFixed(4), f.mpb.toFixed(4)])
+    : "";
+  $("positions").innerHTML = state.positions
+    .map((p) => `<tr class="${p.unrealized_pnl >= 0 ? "up" : "down"}">` +
+      cells([p.strategy, p.entry.price.toFixed(2), p.entry.size, p.unrealized_pnl.toFixed(4)]) + "</tr>")
+    .join("");
+  $("trades").innerHTML = state.trades
+    .map((t) => `<tr class="${t.side.toLowerCase()}">` +
+      cells([new Date(t.time).toLocaleTimeString(), t.strategy, t.side, t.price.toFixed(2), t.size, t.fee.toFixed(4)]) + "</tr>")
+    .join("");
+}
+
+// Events arrive in bursts, so skip refreshes while one is in flight
+let refreshing = false;
+
+async function refresh() {
+  if (refreshing) return;
+  refreshing = true;
+  try {
+    const response = await api("/api/state");
+    render(await response.json());
+  } finally {
+    refreshing = false;
+  }
+}
+
+async function control(action) {
+  await api(`/api/${action}`, { method: "POST" });
+  refresh();
+}
+
+function subscribe() {
+  const scheme = location.protocol === "https:" ? "wss" : "ws";
+  // Browsers can't set headers on WebSockets, so the token goes in the query
+  const query = `access_token=${encodeURIComponent(token())}`;
+  const socket = new WebSocket(`${scheme}://${location.host}/api/events?${query}`);
+  socket.onmessage = refresh;
+  // Reconnect after the bot restarts
+  socket.onclose = () => setTimeout(subscribe, 2000);
+}
+
+$("pause").onclick = () => control("pause");
+$("resume").onclick = () => control("resume");
+refresh();
+subscribe();
@@ -0,0 +1,251 @@
+//! Embedded web dashboard: live portfolio state, signal values and trade history in the browser,
+//! with buttons to pause and resume trading.
+//!
+//! Besides the page itself the server exposes, to clients with a token from the
+//! [auth](crate::auth) config:
+//!
+//! - `GET /api/state`: the [live state](crate::live) and whether trading is paused, as JSON
+//! - `GET /api/events`: a WebSocket pushing every event as its JSON
+//! - `POST /api/pause` and `POST /api/resume`: flip the [control](crate::control) switch,
+//!   answering `{"paused": true}` or `{"paused": false}`, with a `control` token
+//!
+//! The page asks for the token and keeps it for the browser session. Opening it as
+//! `http://127.0.0.1:8080/#token=<token>` skips asking.
+//!
+//! ```toml
+//! [web]
+//! address = "127.0.0.1:8080"
+//! ```
+
+use axum::extract::ws::Message;
+use axum::extract::ws::WebSocket;
+use axum::extract::State;
+use axum::extract::WebSocketUpgrade;
+use axum::http::header;
+use axum::middleware;
+use axum::response::Html;
+use axum::response::IntoResponse;
+use axum::response::Response;
+use axum::routing::get;
+use axum::routing::post;
+use axum::Json;
+use axum::Router;
+use serde::Serialize;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::Mutex;
+use tokio::net::TcpListener;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::info;
+use tracing::warn;
+
+use crate::auth;
+use crate::auth::AuthError;
+use crate::auth::Authenticator;
+use crate::config::TlsConfig;
+use crate::control::Control;
+use crate::event::Event;
+use crate::live::LiveSnapshot;
+use crate::live::LiveState;
+
+/// Events buffered per WebSocket client before a slow reader starts skipping them.
+const CLIENT_BUFFER: usize = 1024;
+
+const INDEX: &str = include_str!("index.html");
+const SCRIPT: &str = include_str!("app.js");
+
+#[derive(Debug)]
+struct Shared {
+    live: Mutex<LiveState>,
+    events: broadcast::Sender<Arc<str>>,
+    control: Control,
+}
+
+#[derive(Debug, Serialize)]
+struct StateView {
+    paused: bool,
+    #[serde(flatten)]
+    live: LiveSnapshot,
+}
+
+#[derive(Debug, Serialize)]
+struct ControlView {
+    paused: bool,
+}
+
+/// Serves the dashboard and keeps it up to date with the events it is given.
+#[derive(Debug)]
+pub struct WebDashboard {
+    local_addr: SocketAddr,
+    shared: Arc<Shared>,
+}
+
+impl WebDashboard {
+    /// Listen on `address` and start serving in the background, over TLS if `tls` is set, to
+    /// the clients `auth` allows, pausing and resuming trading through `control`.
+    pub async fn bind(
+        address: &str,
+        auth: Authenticator,
+        tls: Option<&TlsConfig>,
+        control: Control,
+    ) -> Result<Self, AuthError> {
+        let listener = TcpListener::bind(address).await?;
+        let local_addr = listener.local_addr()?;
+        let (events, _) = broadcast::channel(CLIENT_BUFFER);
+        let shared = Arc::new(Shared {
+            live: Mutex::new(LiveState::default()),
+            events,
+            control,
+        });
+
+        auth::serve(listener, router(shared.clone(), auth), tls, "Web dashboard")?;
+        let scheme = if tls.is_some() { "https" } else { "http" };
+        info!("Serving web dashboard on {}://{}", scheme, local_addr);
+
+        Ok(Self { local_addr, shared })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Count `events` toward the live state and push them to every connected client.
+    pub fn publish(&self, events: &[Event]) {
+        let mut live = self.shared.live.lock().expect("live state lock poisoned");
+        for event in events {
+            live.record(event);
+            if self.shared.events.receiver_count() > 0 {
+                let json = String::from_utf8(event.to_json()).expect("events serialize to UTF-8");
+                // Nobody left listening is not an error
+                let _ = self.shared.events.send(json.into());
+            }
+        }
+    }
+}
+
+fn router(shared: Arc<Shared>, auth: Authenticator) -> Router {
+    // The page and script hold nothing secret, so only the API behind them needs a token
+    Router::new()
+        .route("/api/state", get(state))
+        .route("/api/events", get(events))
+        .route("/api/pause", post(pause))
+        .route("/api/resume", post(resume))
+        .route_layer(middleware::from_fn_with_state(
+            Arc::new(auth),
+            auth::require,
+        ))
+        .route("/", get(|| async { Html(INDEX) }))
+        .route("/app.js", get(script))
+        .with_state(shared)
+}
+
+async fn script() -> Response {
+    ([(header::CONTENT_TYPE, "text/javascript")], SCRIPT).into_response()
+}
+
+async fn state(State(shared): State<Arc<Shared>>) -> Json<StateView> {
+    let live = shared
+        .live
+        .lock()
+        .expect("live state lock poisoned")
+        .snapshot();
+    Json(StateView {
+        paused: shared.control.is_paused(),
+        live,
+    })
+}
+
+async fn pause(State(shared): State<Arc<Shared>>) -> Json<ControlView> {
+    shared.control.pause();
+    Json(ControlView { paused: true })
+}
+
+async fn resume(State(shared): State<Arc<Shared>>) -> Json<ControlView> {
+    shared.control.resume();
+    Json(ControlView { paused: false })
+}
+
+async fn events(upgrade: WebSocketUpgrade, State(shared): State<Arc<Shared>>) -> Response {
+    let receiver = shared.events.subscribe();
+    upgrade.on_upgrade(|socket| push_events(socket, receiver))
+}
+
+async fn push_events(mut socket: WebSocket, mut events: broadcast::Receiver<Arc<str>>) {
+    loop {
+        let json = match events.recv().await {
+            Ok(json) => json,
+            Err(RecvError::Lagged(skipped)) => {
+                warn!(
+                    "Web dashboard client fell behind, skipped {} events",
+                    skipped
+                );
+                continue;
+            }
+            Err(RecvError::Closed) => return,
+        };
+        if socket.send(Message::Text(json.to_string())).await.is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use tokio::io::AsyncReadExt;
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpStream;
+
+    use super::*;
+    use crate::auth::Scope;
+    use crate::features::Features;
+
+    /// Send a bodyless request bearing `token` and return the whole response.
+    async fn request(address: SocketAddr, method: &str, path: &str, token: &str) -> String {
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        let request = format!(
+            "{method} {path} HTTP/1.1\r\nHost: localhost\r\nAuthorization: Bearer {token}\r\n\
+             Content-Length: 0\r\nConnection: close\r\n\r\n"
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn test_state_and_controls() {
+        let control = Control::default();
+        let auth = Authenticator::with_token("secret", Scope::Control);
+        let web = WebDashboard::bind("127.0.0.1:0", auth, None, control.clone())
+            .await
+            .unwrap();
+        web.publish(&[Event::Features {
+            time: Utc::now(),
+            symbol: "BTC/USDT",
+            features: Features {
+                voi: 2.5,
+                ..Features::default()
+            },
+            book_time: None,
+        }]);
+
+        let page = request(web.local_addr(), "GET", "/", "").await;
+        assert!(page.starts_with("HTTP/1.1 200"));
+        assert!(page.contains("/app.js"));
+        let denied = request(web.local_addr(), "GET", "/api/state", "wrong").await;
+        assert!(denied.starts_with("HTTP/1.1 401"));
+
+        let state = request(web.local_addr(), "GET", "/api/state", "secret").await;
+        assert!(state.contains(r#""paused":false"#));
+        assert!(state.contains(r#""symbol":"BTC/USDT""#));
+        assert!(state.contains(r#""voi":2.5"#));
+
+        let paused = request(web.local_addr(), "POST", "/api/pause", "secret").await;
+        assert!(paused.ends_with(r#"{"paused":true}"#));
+        assert!(control.is_paused());
+        request(web.local_addr(), "POST", "/api/resume", "secret").await;
+        assert!(!control.is_paused());
+    }
+}
@@ -0,0 +1,253 @@
+//! Per instrument workers, for trading many symbols at once: each instrument gets a task of its
+//! own running its own strategies and trading state, fed the latest features through a
+//! [conflating channel](crate::conflate), and a [`Portfolio`] adds up what they report.
+//!
+//! Tasks run on the multi-threaded runtime, so instruments are evaluated in parallel and a slow
+//! one only falls behind on its own books. The live loop trades the perpetual itself and, with
+//! [`WorkersConfig`](crate::config::WorkersConfig) set, hands every other book it subscribes to,
+//! spot, paired and triangular legs alike, to a worker of its own on the paper account.
+
+use chrono::DateTime;
+use chrono::Utc;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+use crate::allocation::Allocator;
+use crate::conflate;
+use crate::event::Event;
+use crate::features::Features;
+use crate::hours::Permission;
+use crate::RiskParams;
+
+/// An instrument's latest features, with what the aggregator allows it to do with them.
+#[derive(Debug, Clone, Copy)]
+pub struct Tick {
+    pub time: DateTime<Utc>,
+    pub features: Features,
+    pub risk: RiskParams,
+    pub permission: Permission,
+    pub paused: bool,
+}
+
+/// What a worker made of a [`Tick`].
+#[derive(Debug)]
+pub struct Outcome {
+    pub symbol: &'static str,
+    pub time: DateTime<Utc>,
+    pub events: Vec<Event>,
+    /// The instrument's portfolio value at the tick's bid.
+    pub value: f64,
+}
+
+/// Trade one instrument's ticks until the channel closes, then hand its state back.
+async fn run(
+    symbol: &'static str,
+    mut allocator: Allocator,
+    mut ticks: conflate::Receiver<Tick>,
+    outcomes: mpsc::UnboundedSender<Outcome>,
+) -> Allocator {
+    while let Some(tick) = ticks.recv().await {
+        allocator.set_risk(tick.risk);
+        allocator.block_entries(!tick.permission.entries);
+        let mut events = Vec::new();
+        if tick.permission.flatten {
+            events.extend(allocator.flatten(&tick.features, tick.time));
+        }
+        if !tick.paused {
+            events.extend(allocator.on_features(&tick.features, tick.time));
+        }
+        let outcome = Outcome {
+            symbol,
+            time: tick.time,
+            events,
+            value: allocator.total_value(tick.features.bid),
+        };
+        if outcomes.send(outcome).is_err() {
+            break;
+        }
+    }
+    allocator
+}
+
+#[derive(Debug)]
+struct Worker {
+    ticks: conflate::Sender<Tick>,
+    task: JoinHandle<Allocator>,
+}
+
+#[derive(Debug)]
+pub struct Workers {
+    workers: HashMap<&'static str, Worker>,
+    outcomes: mpsc::UnboundedReceiver<Outcome>,
+}
+
+impl Workers {
+    /// Start a worker for each symbol, trading with its allocator.
+    pub fn spawn(allocators: Vec<(&'static str, Allocator)>) -> Self {
+        let (outcome_sender, outcomes) = mpsc::unbounded_channel();
+        let workers = allocators
+            .into_iter()
+            .map(|(symbol, allocator)| {
+                let (ticks, receiver) = conflate::channel();
+                let task = tokio::spawn(run(symbol, allocator, receiver, outcome_sender.clone()));
+                (symbol, Worker { ticks, task })
+            })
+            .collect();
+        Self { workers, outcomes }
+    }
+
+    /// Hand `tick` to the symbol's worker, replacing any it hasn't started on. Returns `false`
+    /// when there is no worker for the symbol, or it has stopped.
+    pub fn dispatch(&self, symbol: &str, tick: Tick) -> bool {
+        self.workers
+            .get(symbol)
+            .is_some_and(|worker| worker.ticks.send(symbol.to_owned(), tick).is_ok())
+    }
+
+    /// The next outcome from any worker, or `None` once every worker has stopped.
+    pub async fn recv(&mut self) -> Option<Outcome> {
+        self.outcomes.recv().await
+    }
+
+    /// Stop every worker once it has finished its tick in hand, returning each symbol's state.
+    /// Workers that panicked are left out.
+    pub async fn shutdown(self) -> Vec<(&'static str, Allocator)> {
+        let mut allocators = Vec::new();
+        for (symbol, worker) in self.workers {
+            drop(worker.ticks);
+            if let Ok(allocator) = worker.task.await {
+                allocators.push((symbol, allocator));
+            }
+        }
+        allocators
+    }
+}
+
+/// The next outcome from `workers`, if any run. Never resolves without workers, nor once every
+/// one has stopped, dropping them with a warning.
+pub async fn next_outcome(workers: &mut Option<Workers>) -> Outcome {
+    if let Some(running) = workers {
+        if let Some(outcome) = running.recv().await {
+            return outcome;
+        }
+        warn!("Every instrument worker has stopped, only the perpetual is traded");
+        *workers = None;
+    }
+    std::future::pending().await
+}
+
+/// Portfolio value across every instrument, from the latest outcome of each.
+#[derive(Debug, Default)]
+pub struct Portfolio {
+    values: BTreeMap<&'static str, f64>,
+}
+
+impl Portfolio {
+    pub fn record(&mut self, outcome: &Outcome) {
+        self.values.insert(outcome.symbol, outcome.value);
+    }
+
+    /// Each instrument's latest value, by symbol.
+    pub fn values(&self) -> &BTreeMap<&'static str, f64> {
+        &self.values
+    }
+
+    pub fn total(&self) -> f64 {
+        self.values.values().sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::AllocationConfig;
+    use crate::strategy::Signal;
+    use crate::strategy::Strategy;
+    use crate::TradingState;
+
+    use super::*;
+
+    #[derive(Debug)]
+    struct Buyer;
+
+    impl Strategy for Buyer {
+        fn name(&self) -> &str {
+            "buyer"
+        }
+
+        fn evaluate(&mut self, _features: &Features, _state: &TradingState) -> Signal {
+            Signal::Buy
+        }
+    }
+
+    fn allocator(symbol: &'static str) -> Allocator {
+        let members: Vec<(Box<dyn Strategy>, f64)> = vec![(Box::new(Buyer), 1.0)];
+        Allocator::new(
+            1000.0,
+            symbol,
+            members,
+            AllocationConfig::default(),
+            Utc::now(),
+        )
+    }
+
+    fn tick(bid: f64, entries: bool) -> Tick {
+        Tick {
+            time: Utc::now(),
+            features: Features {
+                bid,
+                ask: bid + 0.01,
+                ..Features::default()
+            },
+            risk: RiskParams::default(),
+            permission: Permission {
+                entries,
+                flatten: false,
+            },
+            paused: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_workers_trade_their_own_instrument() {
+        let symbols = ["BTC/USDT", "ETH/USDT"];
+        let mut workers = Workers::spawn(symbols.map(|symbol| (symbol, allocator(symbol))).into());
+        assert!(workers.dispatch("BTC/USDT", tick(100.0, true)));
+        assert!(!workers.dispatch("SOL/USDT", tick(100.0, true)));
+
+        let bought = workers.recv().await.unwrap();
+        assert_eq!(bought.symbol, "BTC/USDT");
+        assert!(bought
+            .events
+            .iter()
+            .all(|event| event.symbol() == "BTC/USDT"));
+        assert!(bought
+            .events
+            .iter()
+            .any(|event| matches!(event, Event::Fill { .. })));
+
+        assert!(workers.dispatch("ETH/USDT", tick(10.0, false)));
+        let blocked = workers.recv().await.unwrap();
+        assert_eq!(blocked.symbol, "ETH/USDT");
+        assert!(!blocked
+            .events
+            .iter()
+            .any(|event| matches!(event, Event::Fill { .. })));
+
+        let mut portfolio = Portfolio::default();
+        portfolio.record(&bought);
+        portfolio.record(&blocked);
+        assert_eq!(portfolio.values().len(), 2);
+        assert_eq!(portfolio.total(), bought.value + blocked.value);
+
+        let allocators = workers.shutdown().await;
+        assert_eq!(allocators.len(), 2);
+        let (_, btc) = allocators
+            .iter()
+            .find(|(symbol, _)| *symbol == "BTC/USDT")
+            .unwrap();
+        assert!(!btc.sleeves()[0].state.positions.is_empty());
+    }
+}